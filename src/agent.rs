@@ -1,2 +1,415 @@
+use std::collections::BTreeSet;
+use std::path::Path;
+
+use futures_util::FutureExt;
+use rig::agent::{Agent, AgentBuilder};
+use rig::client::ProviderClient;
+use rig::completion::ToolDefinition;
+use rig::providers::anthropic;
+use rig::tool::Tool;
+use serde::Deserialize;
+use serde_json::{Map, Value, json};
+
+use crate::agent::{
+    mcp::McpTool,
+    outcome::Enveloped,
+    plugin::PluginTool,
+    tools::{
+        BashCommand, CodeMetrics, ConfigInventory, CustomTool, DeepSearch, FindDuplicates,
+        FindTodos, ForgeTool, GitHistory, LicenseScan, ListEndpoints, ListObjects, ModuleGraph,
+        ObjectStorage, ReadFile, ReadLog, ReadObject, SearchDocs, SpawnAgent, TicketTool,
+    },
+    wasm::WasmTool,
+};
+
+pub mod audit;
+pub mod cassette;
 pub mod hooks;
+pub mod keyring;
+pub mod lifecycle;
+pub mod loops;
+pub mod mcp;
+pub mod models;
+pub mod outcome;
+pub mod paranoid;
+pub mod plugin;
+pub mod policy;
+pub mod preamble;
+pub mod preflight;
+pub mod profile;
+pub mod ratelimit;
+pub mod recovery;
+pub mod remote;
+pub mod rpc;
+pub mod salvage;
+pub mod scope;
+pub mod telemetry;
+pub mod timebox;
 pub mod tools;
+pub mod verify;
+pub mod wasm;
+
+/// Maximum number of tool calls from one model response that run at once.
+///
+/// Exploration turns often issue several independent greps and reads, which
+/// overlap instead of waiting on each other.
+pub const TOOL_CONCURRENCY: usize = 4;
+
+/// Tokens left for the answer on top of the thinking budget
+const ANSWER_MAX_TOKENS: u64 = 8192;
+
+/// `[thinking]` config section
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ThinkingConfig {
+    /// Token budget for extended thinking; thinking is off when unset
+    pub budget: Option<u64>,
+    /// Show thinking blocks in the REPL, toggled with `/thinking`
+    pub show: bool,
+}
+
+impl Default for ThinkingConfig {
+    fn default() -> Self {
+        Self {
+            budget: None,
+            show: true,
+        }
+    }
+}
+
+/// Tools registered next to the native ones
+#[derive(Clone, Default)]
+pub struct ExtraTools {
+    /// Issues and pull requests of the workspace's forge
+    pub forge: Option<ForgeTool>,
+    /// Tickets of the team's issue tracker
+    pub ticket: Option<TicketTool>,
+    /// Objects under the configured S3/GCS prefixes
+    pub object_storage: Option<ObjectStorage>,
+    /// Sub-agents for scoped investigations
+    pub spawn_agent: Option<SpawnAgent>,
+    /// Tools mounted from MCP servers
+    pub mcp: Vec<McpTool>,
+    /// Shell commands declared as tools in config
+    pub custom: Vec<CustomTool>,
+    /// Tools of external plugin processes
+    pub plugins: Vec<PluginTool>,
+    /// Tools of sandboxed WASM modules
+    pub wasm: Vec<WasmTool>,
+}
+
+/// The Anthropic API client, pointed at the cassette endpoint while a
+/// session is recorded or replayed.
+///
+/// The API key is read from `ANTHROPIC_API_KEY`, or else the system keyring
+/// (see `horse init`); replaying doesn't need one.
+pub fn client() -> anthropic::Client {
+    let key = keyring::api_key().map(|(key, _)| key);
+    match (cassette::base_url(), key) {
+        (Some(url), key) => anthropic::Client::builder()
+            .api_key(&key.unwrap_or_default())
+            .base_url(url)
+            .build()
+            .expect("Failed to build the Anthropic client"),
+        (None, Some(key)) => anthropic::Client::builder()
+            .api_key(&key)
+            .build()
+            .expect("Failed to build the Anthropic client"),
+        // Fails with rig's message on the missing key
+        (None, None) => anthropic::Client::from_env(),
+    }
+}
+
+/// Build a search agent confined to `base_dir`, with the native tools plus
+/// the given extra tools, leaving out the `disabled` ones. With a
+/// `thinking_budget`, Claude's extended thinking is enabled with that many
+/// tokens.
+///
+/// The Anthropic API key is read from `ANTHROPIC_API_KEY`.
+pub fn build(
+    base_dir: &Path,
+    model: &str,
+    max_turns: usize,
+    thinking_budget: Option<u64>,
+    preamble: &str,
+    extra: ExtraTools,
+    disabled: &BTreeSet<String>,
+) -> Agent<anthropic::completion::CompletionModel> {
+    let model = anthropic::completion::CompletionModel::new(client(), model).with_prompt_caching();
+
+    let builder = AgentBuilder::new(model)
+        .preamble(preamble)
+        .default_max_turns(max_turns);
+    let mut params = profile::get().params();
+    let builder = match thinking_budget {
+        // The budget counts towards max_tokens, which must leave room for the answer
+        Some(budget) => {
+            params.insert(
+                "thinking".to_string(),
+                json!({ "type": "enabled", "budget_tokens": budget }),
+            );
+            builder.max_tokens(budget + ANSWER_MAX_TOKENS)
+        }
+        None => builder,
+    };
+    let builder = match profile::get().temperature {
+        Some(temperature) => builder.temperature(temperature),
+        None => builder,
+    };
+    let mut definitions = Vec::new();
+
+    let builder = with_tool(
+        builder,
+        ReadFile::new(base_dir.to_path_buf()),
+        base_dir,
+        disabled,
+        &mut definitions,
+    );
+    let builder = with_tool(
+        builder,
+        ReadLog::new(base_dir.to_path_buf()),
+        base_dir,
+        disabled,
+        &mut definitions,
+    );
+    let builder = with_tool(
+        builder,
+        BashCommand::new(base_dir.to_path_buf()),
+        base_dir,
+        disabled,
+        &mut definitions,
+    );
+    let builder = with_tool(
+        builder,
+        SearchDocs::new(base_dir.to_path_buf()),
+        base_dir,
+        disabled,
+        &mut definitions,
+    );
+    let builder = with_tool(
+        builder,
+        DeepSearch::new(base_dir.to_path_buf()),
+        base_dir,
+        disabled,
+        &mut definitions,
+    );
+    let builder = with_tool(
+        builder,
+        CodeMetrics::new(base_dir.to_path_buf()),
+        base_dir,
+        disabled,
+        &mut definitions,
+    );
+    let builder = with_tool(
+        builder,
+        ConfigInventory::new(base_dir.to_path_buf()),
+        base_dir,
+        disabled,
+        &mut definitions,
+    );
+    let builder = with_tool(
+        builder,
+        FindDuplicates::new(base_dir.to_path_buf()),
+        base_dir,
+        disabled,
+        &mut definitions,
+    );
+    let builder = with_tool(
+        builder,
+        FindTodos::new(base_dir.to_path_buf()),
+        base_dir,
+        disabled,
+        &mut definitions,
+    );
+    let builder = with_tool(
+        builder,
+        GitHistory::new(base_dir.to_path_buf()),
+        base_dir,
+        disabled,
+        &mut definitions,
+    );
+    let builder = with_tool(
+        builder,
+        LicenseScan::new(base_dir.to_path_buf()),
+        base_dir,
+        disabled,
+        &mut definitions,
+    );
+    let builder = with_tool(
+        builder,
+        ListEndpoints::new(base_dir.to_path_buf()),
+        base_dir,
+        disabled,
+        &mut definitions,
+    );
+    let builder = with_tool(
+        builder,
+        ModuleGraph::new(base_dir.to_path_buf()),
+        base_dir,
+        disabled,
+        &mut definitions,
+    );
+    let builder = extra.forge.into_iter().fold(builder, |b, t| {
+        with_tool(b, t, base_dir, disabled, &mut definitions)
+    });
+    let builder = extra.ticket.into_iter().fold(builder, |b, t| {
+        with_tool(b, t, base_dir, disabled, &mut definitions)
+    });
+    let builder = extra
+        .object_storage
+        .into_iter()
+        .fold(builder, |b, storage| {
+            let b = with_tool(
+                b,
+                ListObjects::new(storage.clone()),
+                base_dir,
+                disabled,
+                &mut definitions,
+            );
+            with_tool(
+                b,
+                ReadObject::new(storage),
+                base_dir,
+                disabled,
+                &mut definitions,
+            )
+        });
+    let builder = extra.spawn_agent.into_iter().fold(builder, |b, t| {
+        with_tool(b, t, base_dir, disabled, &mut definitions)
+    });
+    let builder = extra.mcp.into_iter().fold(builder, |b, t| {
+        with_tool(b, t, base_dir, disabled, &mut definitions)
+    });
+    let builder = extra.custom.into_iter().fold(builder, |b, t| {
+        with_tool(b, t, base_dir, disabled, &mut definitions)
+    });
+    let builder = extra.plugins.into_iter().fold(builder, |b, t| {
+        with_tool(b, t, base_dir, disabled, &mut definitions)
+    });
+    let builder = extra.wasm.into_iter().fold(builder, |b, t| {
+        with_tool(b, t, base_dir, disabled, &mut definitions)
+    });
+
+    params.extend(cache_breakpoints(preamble, &definitions));
+    builder.additional_params(Value::Object(params)).build()
+}
+
+/// Request parameters that mark the ends of the tool definitions and of the
+/// system prompt as cache breakpoints. The tools are cached on their own
+/// when only the system prompt changes, e.g. in another workspace, and
+/// both together otherwise.
+fn cache_breakpoints(preamble: &str, definitions: &[ToolDefinition]) -> Map<String, Value> {
+    let breakpoint = json!({ "type": "ephemeral" });
+    let tools: Vec<Value> = definitions
+        .iter()
+        .enumerate()
+        .map(|(i, definition)| {
+            let mut tool = json!({
+                "name": definition.name,
+                "description": definition.description,
+                "input_schema": definition.parameters,
+            });
+            if i + 1 == definitions.len() {
+                tool["cache_control"] = breakpoint.clone();
+            }
+            tool
+        })
+        .collect();
+
+    let mut params = Map::new();
+    params.insert(
+        "system".to_string(),
+        json!([{ "type": "text", "text": preamble, "cache_control": breakpoint }]),
+    );
+    if !tools.is_empty() {
+        params.insert("tools".to_string(), Value::Array(tools));
+    }
+    params
+}
+
+/// Register `tool` on the agent unless it is disabled, with its failures
+/// reported in an error envelope, and add its definition to `definitions`
+fn with_tool(
+    builder: AgentBuilder<anthropic::completion::CompletionModel>,
+    tool: impl Tool<Output: Send> + 'static,
+    base_dir: &Path,
+    disabled: &BTreeSet<String>,
+    definitions: &mut Vec<ToolDefinition>,
+) -> AgentBuilder<anthropic::completion::CompletionModel> {
+    if disabled.contains(&tool.name()) {
+        builder
+    } else {
+        let tool = Enveloped::new(tool, base_dir.to_path_buf());
+        // Definitions are built without waiting on anything
+        definitions.extend(tool.definition(String::new()).now_or_never());
+        builder.tool(tool)
+    }
+}
+
+/// Names of the tools `build` registers with `extra`, native ones first
+pub fn tool_names(extra: &ExtraTools) -> Vec<String> {
+    let native = [
+        ReadFile::NAME,
+        ReadLog::NAME,
+        BashCommand::NAME,
+        SearchDocs::NAME,
+        DeepSearch::NAME,
+        CodeMetrics::NAME,
+        ConfigInventory::NAME,
+        FindDuplicates::NAME,
+        FindTodos::NAME,
+        GitHistory::NAME,
+        LicenseScan::NAME,
+        ListEndpoints::NAME,
+        ModuleGraph::NAME,
+    ]
+    .map(str::to_string);
+    let object_storage = extra
+        .object_storage
+        .iter()
+        .flat_map(|_| [ListObjects::NAME, ReadObject::NAME].map(str::to_string));
+    native
+        .into_iter()
+        .chain(extra.forge.iter().map(Tool::name))
+        .chain(extra.ticket.iter().map(Tool::name))
+        .chain(object_storage)
+        .chain(extra.spawn_agent.iter().map(Tool::name))
+        .chain(extra.mcp.iter().map(Tool::name))
+        .chain(extra.custom.iter().map(Tool::name))
+        .chain(extra.plugins.iter().map(Tool::name))
+        .chain(extra.wasm.iter().map(Tool::name))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cache_breakpoints() {
+        let definition = |name: &str| ToolDefinition {
+            name: name.to_string(),
+            description: format!("The {name} tool"),
+            parameters: json!({ "type": "object" }),
+        };
+        let params = cache_breakpoints(
+            "You are horse.",
+            &[definition("read_file"), definition("bash")],
+        );
+
+        assert_eq!(
+            params["system"],
+            json!([{
+                "type": "text",
+                "text": "You are horse.",
+                "cache_control": { "type": "ephemeral" }
+            }])
+        );
+        let tools = params["tools"].as_array().unwrap();
+        assert_eq!(tools[0]["input_schema"], json!({ "type": "object" }));
+        assert!(tools[0].get("cache_control").is_none());
+        assert_eq!(tools[1]["cache_control"], json!({ "type": "ephemeral" }));
+
+        assert!(!cache_breakpoints("You are horse.", &[]).contains_key("tools"));
+    }
+}
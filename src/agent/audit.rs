@@ -0,0 +1,204 @@
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::state;
+
+/// Directory of the audit log, relative to the workspace
+pub const AUDIT_DIR: &str = ".horse/audit";
+
+/// Name of the audit log file inside `AUDIT_DIR`
+const AUDIT_FILE: &str = "audit.jsonl";
+
+/// `prev` hash of the first entry
+const GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+/// The audit log of this process, if enabled
+static AUDIT: OnceLock<AuditLog> = OnceLock::new();
+
+/// What a tool call did, as recorded in the audit log
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuditRecord {
+    pub timestamp: DateTime<Utc>,
+    pub tool: String,
+    /// Arguments as sent by the model: the command, path or search query
+    pub args: serde_json::Value,
+    pub failed: bool,
+    pub output_bytes: usize,
+    /// Hash of the previous entry, chaining the log
+    pub prev: String,
+}
+
+/// A line of the audit log: a record and the hash over it
+#[derive(Debug, Serialize, Deserialize)]
+struct AuditEntry {
+    #[serde(flatten)]
+    record: AuditRecord,
+    hash: String,
+}
+
+impl AuditRecord {
+    fn hash(&self) -> Result<String> {
+        let json = serde_json::to_string(self)?;
+        Ok(format!("{:x}", Sha256::digest(json.as_bytes())))
+    }
+}
+
+/// Append-only, hash-chained log of every tool invocation
+#[derive(Debug)]
+struct AuditLog {
+    path: PathBuf,
+    /// Hash of the last entry written
+    last_hash: Mutex<String>,
+}
+
+impl AuditLog {
+    fn append(&self, mut record: AuditRecord) -> Result<()> {
+        let mut last_hash = self
+            .last_hash
+            .lock()
+            .map_err(|_| anyhow::anyhow!("Audit log lock poisoned"))?;
+        record.prev = last_hash.clone();
+        let hash = record.hash()?;
+
+        let mut line = serde_json::to_string(&AuditEntry {
+            record,
+            hash: hash.clone(),
+        })?;
+        line.push('\n');
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .and_then(|mut file| file.write_all(line.as_bytes()))
+            .with_context(|| format!("Failed to append to {}", self.path.display()))?;
+
+        *last_hash = hash;
+        Ok(())
+    }
+}
+
+/// Location of the audit log of a workspace
+pub fn log_path(base_dir: &Path) -> PathBuf {
+    base_dir.join(AUDIT_DIR).join(AUDIT_FILE)
+}
+
+/// Start recording tool invocations to the workspace's audit log, continuing
+/// the hash chain of an existing log
+pub fn init(base_dir: &Path) -> Result<()> {
+    let path = log_path(base_dir);
+    if let Some(dir) = path.parent() {
+        state::create_dir(dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+    }
+    let last_hash = match File::open(&path) {
+        Ok(file) => BufReader::new(file)
+            .lines()
+            .map_while(Result::ok)
+            .filter_map(|line| serde_json::from_str::<AuditEntry>(&line).ok())
+            .last()
+            .map_or_else(|| GENESIS_HASH.to_string(), |entry| entry.hash),
+        Err(_) => GENESIS_HASH.to_string(),
+    };
+
+    let _ = AUDIT.set(AuditLog {
+        path,
+        last_hash: Mutex::new(last_hash),
+    });
+    Ok(())
+}
+
+/// Record a finished tool call, if the audit log is enabled.
+///
+/// Failing to write the log is reported as an error, but doesn't stop the
+/// tool result from reaching the model.
+pub fn record(tool: &str, args: &str, failed: bool, output_bytes: usize) {
+    if let Some(log) = AUDIT.get() {
+        let record = AuditRecord {
+            timestamp: Utc::now(),
+            tool: tool.to_string(),
+            args: serde_json::from_str(args)
+                .unwrap_or_else(|_| serde_json::Value::String(args.to_string())),
+            failed,
+            output_bytes,
+            prev: String::new(),
+        };
+        if let Err(e) = log.append(record) {
+            tracing::error!("[!] Failed to write audit log: {e:#}");
+        }
+    }
+}
+
+/// Check that every entry's hash matches its content and links to the
+/// previous entry.
+///
+/// Returns the number of entries, or an error naming the first broken line.
+pub fn verify(content: &str) -> Result<usize> {
+    content
+        .lines()
+        .enumerate()
+        .try_fold((GENESIS_HASH.to_string(), 0), |(prev, count), (i, line)| {
+            let line_no = i + 1;
+            let entry: AuditEntry = serde_json::from_str(line)
+                .with_context(|| format!("Line {line_no} is not a valid audit entry"))?;
+            anyhow::ensure!(
+                entry.record.prev == prev,
+                "Line {line_no} does not follow the previous entry; entries were removed or reordered"
+            );
+            anyhow::ensure!(
+                entry.record.hash()? == entry.hash,
+                "Line {line_no} was modified after it was written"
+            );
+            Ok((entry.hash, count + 1))
+        })
+        .map(|(_, count)| count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chain(tools: &[&str]) -> Vec<String> {
+        let mut prev = GENESIS_HASH.to_string();
+        tools
+            .iter()
+            .map(|tool| {
+                let record = AuditRecord {
+                    timestamp: DateTime::from_timestamp(1_700_000_000, 0).unwrap(),
+                    tool: tool.to_string(),
+                    args: serde_json::json!({"command": "ls"}),
+                    failed: false,
+                    output_bytes: 10,
+                    prev: prev.clone(),
+                };
+                let hash = record.hash().unwrap();
+                prev = hash.clone();
+                serde_json::to_string(&AuditEntry { record, hash }).unwrap()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_verify() {
+        let lines = chain(&["bash", "read_file", "search_docs"]);
+        assert_eq!(verify(&lines.join("\n")).unwrap(), 3);
+
+        // Dropping an entry breaks the chain
+        let removed = [lines[0].clone(), lines[2].clone()].join("\n");
+        assert!(verify(&removed).unwrap_err().to_string().contains("Line 2"));
+
+        // Editing an entry breaks its hash
+        let edited = lines.join("\n").replace("\"ls\"", "\"rm\"");
+        assert!(
+            verify(&edited)
+                .unwrap_err()
+                .to_string()
+                .contains("modified")
+        );
+    }
+}
@@ -0,0 +1,285 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+
+use anyhow::{Context, Result};
+use axum::Router;
+use axum::body::Bytes;
+use axum::http::{HeaderMap, StatusCode, Uri, header};
+use axum::response::{IntoResponse, Response};
+use serde::{Deserialize, Serialize};
+
+/// Where recorded requests are sent
+const UPSTREAM: &str = "https://api.anthropic.com";
+
+/// Request headers passed on to the API when recording
+const FORWARDED_HEADERS: &[&str] = &[
+    "x-api-key",
+    "anthropic-version",
+    "anthropic-beta",
+    "content-type",
+];
+
+/// Tool result of a call the cassette has no recording of
+const NOT_RECORDED: &str = "No result was recorded for this tool call";
+
+/// The cassette of this process, if recording or replaying
+static CASSETTE: OnceLock<Cassette> = OnceLock::new();
+
+/// Whether a session is captured to a cassette or re-run from one
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Mode {
+    Record(PathBuf),
+    Replay(PathBuf),
+}
+
+/// A provider request and the response it got
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Exchange {
+    path: String,
+    /// The request body, without the headers and so without the API key
+    request: serde_json::Value,
+    status: u16,
+    content_type: String,
+    response: String,
+}
+
+/// A tool call and its result, as passed to the model
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct ToolRecord {
+    tool: String,
+    args: String,
+    result: String,
+    /// Set once replayed, so repeated identical calls get their own results
+    #[serde(skip)]
+    used: bool,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Tape {
+    exchanges: Vec<Exchange>,
+    tool_results: Vec<ToolRecord>,
+}
+
+#[derive(Debug)]
+struct Cassette {
+    mode: Mode,
+    tape: Mutex<Tape>,
+    /// Exchanges replayed so far
+    replayed: Mutex<usize>,
+    /// Local endpoint the provider client talks to
+    base_url: String,
+}
+
+impl Cassette {
+    fn save(&self, path: &Path) {
+        let json = self
+            .tape
+            .lock()
+            .map_err(|_| anyhow::anyhow!("Cassette lock poisoned"))
+            .and_then(|tape| Ok(serde_json::to_string_pretty(&*tape)?));
+        if let Err(e) = json.and_then(|json| {
+            fs::write(path, json).with_context(|| format!("Failed to write {}", path.display()))
+        }) {
+            tracing::error!("[!] Failed to write cassette: {e:#}");
+        }
+    }
+}
+
+/// Start recording or replaying: provider requests go through a local
+/// endpoint that forwards and records them, or answers them from the
+/// cassette in order
+pub async fn init(mode: Mode) -> Result<()> {
+    let tape = match &mode {
+        Mode::Record(_) => Tape::default(),
+        Mode::Replay(path) => {
+            let content = fs::read_to_string(path)
+                .with_context(|| format!("Failed to read cassette {}", path.display()))?;
+            serde_json::from_str(&content)
+                .with_context(|| format!("Failed to parse cassette {}", path.display()))?
+        }
+    };
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+        .await
+        .context("Failed to bind the cassette endpoint")?;
+    let base_url = format!("http://{}", listener.local_addr()?);
+    tokio::spawn(async move {
+        let app = Router::new().fallback(exchange);
+        if let Err(e) = axum::serve(listener, app).await {
+            tracing::error!("[!] Cassette endpoint failed: {e}");
+        }
+    });
+
+    let _ = CASSETTE.set(Cassette {
+        mode,
+        tape: Mutex::new(tape),
+        replayed: Mutex::new(0),
+        base_url,
+    });
+    Ok(())
+}
+
+/// The endpoint the provider client must use instead of the API, if
+/// recording or replaying
+pub fn base_url() -> Option<&'static str> {
+    CASSETTE.get().map(|cassette| cassette.base_url.as_str())
+}
+
+/// The recorded result of a tool call, when replaying; the tool must not
+/// run then. Calls are matched by tool and arguments, in order.
+pub fn replay_tool(tool: &str, args: &str) -> Option<String> {
+    CASSETTE
+        .get()
+        .filter(|cassette| matches!(cassette.mode, Mode::Replay(_)))
+        .map(|cassette| {
+            cassette
+                .tape
+                .lock()
+                .ok()
+                .and_then(|mut tape| {
+                    tape.tool_results
+                        .iter_mut()
+                        .find(|record| !record.used && record.tool == tool && record.args == args)
+                        .map(|record| {
+                            record.used = true;
+                            record.result.clone()
+                        })
+                })
+                .unwrap_or_else(|| {
+                    tracing::warn!("[!] Warning: No recorded result for {tool}({args})");
+                    NOT_RECORDED.to_string()
+                })
+        })
+}
+
+/// Record the result of a tool call, when recording
+pub fn record_tool(tool: &str, args: &str, result: &str) {
+    if let Some(cassette) = CASSETTE.get()
+        && let Mode::Record(path) = &cassette.mode
+    {
+        if let Ok(mut tape) = cassette.tape.lock() {
+            tape.tool_results.push(ToolRecord {
+                tool: tool.to_string(),
+                args: args.to_string(),
+                result: result.to_string(),
+                used: false,
+            });
+        }
+        cassette.save(path);
+    }
+}
+
+/// Answer a provider request: forward and record it, or replay the next
+/// recorded response
+async fn exchange(uri: Uri, headers: HeaderMap, body: Bytes) -> Response {
+    let result = match CASSETTE.get().map(|cassette| (cassette, &cassette.mode)) {
+        Some((cassette, Mode::Record(path))) => {
+            forward(&uri, &headers, body).await.inspect(|exchange| {
+                if let Ok(mut tape) = cassette.tape.lock() {
+                    tape.exchanges.push(exchange.clone());
+                }
+                cassette.save(path);
+            })
+        }
+        Some((cassette, Mode::Replay(_))) => next_exchange(cassette),
+        None => Err(anyhow::anyhow!("No cassette is loaded")),
+    };
+
+    match result {
+        Ok(exchange) => (
+            StatusCode::from_u16(exchange.status).unwrap_or(StatusCode::BAD_GATEWAY),
+            [(header::CONTENT_TYPE, exchange.content_type)],
+            exchange.response,
+        )
+            .into_response(),
+        Err(e) => {
+            let error = serde_json::json!({
+                "type": "error",
+                "error": { "type": "api_error", "message": format!("{e:#}") }
+            });
+            (StatusCode::BAD_GATEWAY, axum::Json(error)).into_response()
+        }
+    }
+}
+
+async fn forward(uri: &Uri, headers: &HeaderMap, body: Bytes) -> Result<Exchange> {
+    let path = uri.path_and_query().map_or("/", |p| p.as_str()).to_string();
+    let request = FORWARDED_HEADERS
+        .iter()
+        .filter_map(|name| headers.get(*name).map(|value| (*name, value)))
+        .fold(
+            reqwest::Client::new().post(format!("{UPSTREAM}{path}")),
+            |request, (name, value)| request.header(name, value.as_bytes()),
+        );
+    let response = request
+        .body(body.clone())
+        .send()
+        .await
+        .context("Failed to reach the API")?;
+
+    let status = response.status().as_u16();
+    let content_type = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or("application/json")
+        .to_string();
+    Ok(Exchange {
+        path,
+        request: serde_json::from_slice(&body).unwrap_or_default(),
+        status,
+        content_type,
+        response: response
+            .text()
+            .await
+            .context("Failed to read the API response")?,
+    })
+}
+
+fn next_exchange(cassette: &Cassette) -> Result<Exchange> {
+    let mut replayed = cassette
+        .replayed
+        .lock()
+        .map_err(|_| anyhow::anyhow!("Cassette lock poisoned"))?;
+    let exchange = cassette
+        .tape
+        .lock()
+        .map_err(|_| anyhow::anyhow!("Cassette lock poisoned"))?
+        .exchanges
+        .get(*replayed)
+        .cloned()
+        .with_context(|| format!("The cassette has no more recorded responses after {replayed}"))?;
+    *replayed += 1;
+    Ok(exchange)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tape_roundtrip() {
+        let tape = Tape {
+            exchanges: vec![Exchange {
+                path: "/v1/messages".to_string(),
+                request: serde_json::json!({ "model": "claude-sonnet-4-0" }),
+                status: 200,
+                content_type: "application/json".to_string(),
+                response: r#"{"content":[]}"#.to_string(),
+            }],
+            tool_results: vec![ToolRecord {
+                tool: "bash".to_string(),
+                args: r#"{"command":"ls"}"#.to_string(),
+                result: "\"Cargo.toml\\nsrc\"".to_string(),
+                used: true,
+            }],
+        };
+        let json = serde_json::to_string(&tape).unwrap();
+        assert!(!json.contains("used"));
+
+        let read: Tape = serde_json::from_str(&json).unwrap();
+        assert_eq!(read.exchanges[0].path, "/v1/messages");
+        assert!(!read.tool_results[0].used);
+    }
+}
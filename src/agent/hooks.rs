@@ -1,29 +1,158 @@
-use crate::agent::tools::Tools;
-use crate::console::colors;
+use crate::agent::audit;
+use crate::agent::cassette;
+use crate::agent::lifecycle;
+use crate::agent::loops::{LoopGuard, Verdict};
+use crate::agent::models;
+use crate::agent::outcome::{ErrorKind, ToolFailure};
+use crate::agent::policy::{self, Action};
+use crate::agent::preflight::{self, BYTES_PER_TOKEN};
+use crate::agent::ratelimit;
+use crate::agent::telemetry::SpanTracker;
+use crate::agent::timebox;
+use crate::agent::tools::{CACHED_MARKER, Tools};
 use crate::console::spinner::create_spinner;
+use crate::console::{colors, hyperlinks, i18n, logging, stats, usage};
 use indicatif::ProgressBar;
 use rig::agent::{HookAction, PromptHook, ToolCallHookAction};
-use rig::completion::{CompletionModel, CompletionResponse, Usage};
+use rig::completion::{AssistantContent, CompletionModel, CompletionResponse, Message, Usage};
+use std::collections::{HashMap, HashSet};
+use std::io::IsTerminal;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc::UnboundedSender;
+use tracing::Level;
+
+/// Number of result lines shown in tool result previews
+const PREVIEW_LINES: usize = 3;
+
+/// Tool result the model receives for a call the tool policy refused
+const DENIED_BY_POLICY: &str = "The tool call was denied by the user's tool policy. \
+    Don't retry it; continue without it or ask the user.";
+
+/// Reason a turn stops when the user doesn't send its tool output
+const OUTPUT_NOT_SENT: &str = "The user chose not to send the tool output to the API";
+
+/// Reason a turn stops for its tool output to be trimmed before it is sent
+const OUTPUT_TRIMMED: &str = "The user chose to trim the tool output before it is sent";
+
+/// Whether a request was cancelled with `reason` for the tool output of its
+/// turn to be trimmed
+pub fn trim_requested(reason: &str) -> bool {
+    reason == OUTPUT_TRIMMED
+}
+
+/// What the user decided about the tool output of a turn
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum OutputDecision {
+    Send,
+    Trim,
+    Stop,
+}
+
+/// Tool result the model receives for a call an `on_tool_call` hook refused
+fn blocked_by_hook(reason: String) -> String {
+    format!("The tool call was blocked by the user's hook: {reason}. Don't retry it.")
+}
+
+/// Wall time, result size and outcome of a single tool call
+#[derive(Clone, Debug)]
+pub struct ToolCallOutcome {
+    pub name: String,
+    pub duration: Duration,
+    /// Size of the decoded result
+    pub bytes: usize,
+    /// How the call failed, if it did
+    pub error: Option<ErrorKind>,
+}
+
+/// Amount of tool output a turn may send before the user is asked, and the
+/// model whose prices the estimate uses
+#[derive(Clone, Debug)]
+struct OutputGate {
+    limit_bytes: usize,
+    model: String,
+}
 
 /// A hook that displays tool calls and results in real-time during agent execution.
 /// Skips reasoning tokens by default. Tracks token usage including cache reads.
 #[derive(Clone, Debug)]
 pub struct ProgressHook {
     total_usage: Arc<Mutex<Usage>>,
+    last_usage: Arc<Mutex<Usage>>,
     spinner: Arc<Mutex<Option<ProgressBar>>>,
     external_spinner: Arc<Mutex<Option<ProgressBar>>>,
+    /// Start times of in-flight tool calls, keyed by internal call id
+    tool_starts: Arc<Mutex<HashMap<String, Instant>>>,
+    /// Outcomes of the tool calls finished since the last `take_tool_calls`
+    tool_calls: Arc<Mutex<Vec<ToolCallOutcome>>>,
+    /// Print extended thinking blocks, dimmed
+    show_thinking: Arc<AtomicBool>,
+    /// Tools the user allowed for the rest of the session when asked
+    always_allowed: Arc<Mutex<HashSet<String>>>,
+    /// Serializes approval questions of concurrent tool calls
+    ask_lock: Arc<tokio::sync::Mutex<()>>,
+    /// Ask before a turn sends more tool output than this
+    output_gate: Arc<Mutex<Option<OutputGate>>>,
+    /// Tool output of the current turn not yet confirmed by the user
+    unconfirmed_output: Arc<AtomicUsize>,
+    /// Tool calls of the current turn, to catch the agent repeating itself
+    loop_guard: Arc<LoopGuard>,
+    spans: SpanTracker,
 }
 
 impl ProgressHook {
     pub fn new() -> Self {
         Self {
             total_usage: Arc::new(Mutex::new(Usage::default())),
+            last_usage: Arc::new(Mutex::new(Usage::default())),
             spinner: Arc::new(Mutex::new(None)),
             external_spinner: Arc::new(Mutex::new(None)),
+            tool_starts: Arc::new(Mutex::new(HashMap::new())),
+            tool_calls: Arc::new(Mutex::new(Vec::new())),
+            show_thinking: Arc::new(AtomicBool::new(false)),
+            always_allowed: Arc::new(Mutex::new(HashSet::new())),
+            ask_lock: Arc::new(tokio::sync::Mutex::new(())),
+            output_gate: Arc::new(Mutex::new(None)),
+            unconfirmed_output: Arc::new(AtomicUsize::new(0)),
+            loop_guard: Arc::new(LoopGuard::default()),
+            spans: SpanTracker::default(),
+        }
+    }
+
+    /// Ask before a turn sends more than `limit_kb` KB of tool output to
+    /// the API; 0 never asks
+    pub fn set_output_gate(&self, limit_kb: usize, model: &str) {
+        if let Ok(mut gate) = self.output_gate.lock() {
+            *gate = (limit_kb > 0).then(|| OutputGate {
+                limit_bytes: limit_kb * 1024,
+                model: model.to_string(),
+            });
         }
     }
 
+    /// Show or hide extended thinking blocks from now on
+    pub fn set_show_thinking(&self, show: bool) {
+        self.show_thinking.store(show, Ordering::Relaxed);
+    }
+
+    pub fn show_thinking(&self) -> bool {
+        self.show_thinking.load(Ordering::Relaxed)
+    }
+
+    /// Text of the thinking blocks of a raw Anthropic response
+    pub fn extract_thinking(raw: &serde_json::Value) -> Vec<String> {
+        raw["content"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter(|block| block["type"] == "thinking")
+            .filter_map(|block| block["thinking"].as_str())
+            .map(|text| text.trim().to_string())
+            .filter(|text| !text.is_empty())
+            .collect()
+    }
+
     pub fn get_total_usage(&self) -> Usage {
         *self.total_usage.lock().unwrap()
     }
@@ -52,13 +181,38 @@ impl ProgressHook {
         self.external_spinner.lock().ok().and_then(|mut s| s.take())
     }
 
+    /// Take the outcomes of the tool calls finished since the last call.
+    ///
+    /// Called at the end of a turn, which also starts counting the tool
+    /// output and calls of the next turn.
+    pub fn take_tool_calls(&self) -> Vec<ToolCallOutcome> {
+        self.unconfirmed_output.store(0, Ordering::Relaxed);
+        self.loop_guard.reset();
+        self.tool_calls
+            .lock()
+            .map(|mut t| std::mem::take(&mut *t))
+            .unwrap_or_default()
+    }
+
+    /// Usage of the most recent completion response
+    pub fn get_last_usage(&self) -> Usage {
+        *self.last_usage.lock().unwrap()
+    }
+
+    /// Set the session totals back to an earlier value, taking the turns
+    /// since out of them
+    pub fn restore_total_usage(&self, total: Usage) {
+        *self.total_usage.lock().unwrap() = total;
+    }
+
     pub fn set_total_usage(&self, delta: Usage) {
         let mut total = self.total_usage.lock().unwrap();
         *total += delta;
+        *self.last_usage.lock().unwrap() = delta;
     }
 
     /// Truncate long strings with an ellipsis for display
-    fn truncate_display(s: &str, max_len: usize) -> String {
+    pub fn truncate_display(s: &str, max_len: usize) -> String {
         if s.len() <= max_len {
             s.to_string()
         } else {
@@ -72,17 +226,190 @@ impl ProgressHook {
             format!("{}...", &s[..truncate_at])
         }
     }
+
+    /// Decode a tool result, which arrives JSON-encoded for string outputs
+    pub fn decode_tool_output(result: &str) -> String {
+        serde_json::from_str::<String>(result).unwrap_or_else(|_| result.to_string())
+    }
+
+    /// Apply the tool policy, asking the user on the terminal when a rule
+    /// says so. Without a terminal to ask on, the call is denied.
+    async fn authorize(&self, tool_name: &str, display_args: &str, args: &str) -> Action {
+        let always = self
+            .always_allowed
+            .lock()
+            .is_ok_and(|allowed| allowed.contains(tool_name));
+        match policy::decide(tool_name, args) {
+            Action::Ask if always => Action::Allow,
+            Action::Ask if std::io::stdin().is_terminal() => {
+                let _guard = self.ask_lock.lock().await;
+                let question = i18n::tr_with(
+                    ">> Allow {tool}({args})? [y]es, [N]o, [a]lways for {tool}:",
+                    &[("tool", &tool_name), ("args", &display_args)],
+                );
+                let answer = self.ask(&question).await;
+
+                match answer.as_deref() {
+                    Some("y" | "yes") => Action::Allow,
+                    Some("a" | "always") => {
+                        if let Ok(mut allowed) = self.always_allowed.lock() {
+                            allowed.insert(tool_name.to_string());
+                        }
+                        Action::Allow
+                    }
+                    _ => Action::Deny,
+                }
+            }
+            Action::Ask => Action::Deny,
+            action => action,
+        }
+    }
+
+    /// Wait until the rate limits allow a request of `tokens`, showing the
+    /// queue in the spinner meanwhile
+    async fn wait_for_rate_limit(&self, tokens: u64) {
+        let spinner = self.external_spinner.lock().ok().and_then(|s| s.clone());
+        let message = spinner.as_ref().map(ProgressBar::message);
+        ratelimit::acquire(tokens, |wait, waiting| match &spinner {
+            Some(spinner) => spinner.set_message(i18n::tr_with(
+                "Rate limited: {waiting} queued, next slot in {secs}s",
+                &[("waiting", &waiting), ("secs", &wait.as_secs().max(1))],
+            )),
+            None => ratelimit::log_wait(wait, waiting),
+        })
+        .await;
+        if let (Some(spinner), Some(message)) = (spinner, message) {
+            spinner.set_message(message);
+        }
+    }
+
+    /// Amount of tool output a turn may send without asking, in bytes
+    pub fn output_limit(&self) -> Option<usize> {
+        self.output_gate
+            .lock()
+            .ok()
+            .and_then(|gate| gate.as_ref().map(|gate| gate.limit_bytes))
+    }
+
+    /// Whether the tool output of this turn may be sent, asking the user on
+    /// the terminal once it exceeds the gate. Without a terminal to ask on,
+    /// it is sent.
+    async fn confirm_output(&self) -> OutputDecision {
+        let pending = self.unconfirmed_output.load(Ordering::Relaxed);
+        let gate = self.output_gate.lock().ok().and_then(|gate| gate.clone());
+
+        match gate {
+            Some(gate) if pending > gate.limit_bytes && std::io::stdin().is_terminal() => {
+                let _guard = self.ask_lock.lock().await;
+                let tokens = pending / BYTES_PER_TOKEN;
+                let cost = models::estimate_cost(&gate.model, tokens as u64, 0, 0)
+                    .map(|cost| format!(", ~${cost:.2}"))
+                    .unwrap_or_default();
+                let question = i18n::tr_with(
+                    ">> This turn is about to send {size} of tool output to the API (~{tokens} tokens{cost}). Send it? [y]es, [t]rim it to {limit}, [N]o:",
+                    &[
+                        ("size", &stats::format_size(pending)),
+                        ("tokens", &tokens),
+                        ("cost", &cost),
+                        ("limit", &stats::format_size(gate.limit_bytes)),
+                    ],
+                );
+                let answer = self.ask(&question).await;
+
+                let decision = match answer.as_deref() {
+                    Some("y" | "yes") => OutputDecision::Send,
+                    Some("t" | "trim") => OutputDecision::Trim,
+                    _ => OutputDecision::Stop,
+                };
+                if decision != OutputDecision::Stop {
+                    self.unconfirmed_output.store(0, Ordering::Relaxed);
+                }
+                decision
+            }
+            _ => OutputDecision::Send,
+        }
+    }
+
+    /// Ask a question on the terminal and read the trimmed answer
+    async fn ask(&self, question: &str) -> Option<String> {
+        // The question would be overdrawn by a running spinner
+        for spinner in [self.get_spinner(), self.get_external_spinner()]
+            .into_iter()
+            .flatten()
+        {
+            spinner.finish_and_clear();
+        }
+        eprint!("{} ", colors::color_warning(question));
+
+        let answer = tokio::task::spawn_blocking(|| {
+            let mut line = String::new();
+            std::io::stdin().read_line(&mut line).map(|_| line)
+        })
+        .await;
+        answer
+            .ok()
+            .and_then(Result::ok)
+            .map(|line| line.trim().to_string())
+    }
+
+    fn executing_message(running: usize) -> String {
+        match running {
+            0 | 1 => "Executing tool".to_string(),
+            n => format!("Executing {n} tools"),
+        }
+    }
+
+    /// Build a short summary of a tool result: its size followed by the first few lines
+    fn format_result_preview(result: &str) -> String {
+        let output = Self::decode_tool_output(result);
+        let line_count = output.lines().count();
+        let size = stats::format_size(output.len());
+
+        let noun = if line_count == 1 { "line" } else { "lines" };
+
+        let mut preview = format!("   -> {line_count} {noun}, {size}");
+        for line in output
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .take(PREVIEW_LINES)
+        {
+            preview.push_str("\n   | ");
+            preview.push_str(&Self::truncate_display(line, 120));
+        }
+        preview
+    }
 }
 
 impl<M> PromptHook<M> for ProgressHook
 where
     M: CompletionModel,
 {
+    async fn on_completion_call(
+        &self,
+        prompt: &rig::completion::Message,
+        history: &[rig::completion::Message],
+    ) -> HookAction {
+        let last_usage = self.get_last_usage();
+        match preflight::gate(prompt, history, last_usage).and_then(|()| timebox::gate()) {
+            Err(reason) => HookAction::terminate(reason),
+            Ok(()) => match self.confirm_output().await {
+                OutputDecision::Stop => HookAction::terminate(OUTPUT_NOT_SENT),
+                OutputDecision::Trim => HookAction::terminate(OUTPUT_TRIMMED),
+                OutputDecision::Send => {
+                    self.wait_for_rate_limit(preflight::estimate(prompt, history, last_usage))
+                        .await;
+                    self.spans.completion_started();
+                    HookAction::cont()
+                }
+            },
+        }
+    }
+
     async fn on_tool_call(
         &self,
         tool_name: &str,
         _tool_call_id: Option<String>,
-        _internal_call_id: &str,
+        internal_call_id: &str,
         args: &str,
     ) -> ToolCallHookAction {
         // Stop the external spinner (if any) before printing tool call
@@ -94,36 +421,134 @@ where
         let display_args = Tools::try_from(tool_name)
             .map(|tool| tool.extract_display_args(args))
             .unwrap_or_else(|_| args.to_string());
-
         let truncated_args = Self::truncate_display(&display_args, 200);
-        println!(
-            "{}",
-            colors::color_debug(format!("\n>> {tool_name}({truncated_args})"))
-        );
 
-        // Start spinner for tool execution
-        let spinner = create_spinner("Executing tool");
-        self.set_spinner(spinner);
+        // A repeated call is answered without asking the user again
+        let verdict = match self.loop_guard.check(tool_name, args) {
+            Verdict::Run => {
+                let blocked =
+                    if self.authorize(tool_name, &truncated_args, args).await == Action::Deny {
+                        Some(DENIED_BY_POLICY.to_string())
+                    } else {
+                        lifecycle::tool_call(tool_name, args)
+                            .await
+                            .map(blocked_by_hook)
+                    };
+                if let Some(reason) = &blocked {
+                    tracing::warn!(target: logging::TOOLS_TARGET, ">> Denied: {tool_name}({truncated_args})");
+                    self.loop_guard.deny(tool_name, args, reason);
+                }
+                blocked.map_or(Verdict::Run, Verdict::Skip)
+            }
+            Verdict::Skip(reason) => {
+                tracing::warn!(target: logging::TOOLS_TARGET, ">> Skipped: {tool_name}({truncated_args})");
+                Verdict::Skip(reason)
+            }
+            Verdict::Stop(reason) => {
+                tracing::warn!(target: logging::TOOLS_TARGET, ">> {reason}");
+                Verdict::Stop(reason)
+            }
+        };
+
+        match verdict {
+            Verdict::Stop(reason) => ToolCallHookAction::terminate(reason),
+            Verdict::Skip(reason) => ToolCallHookAction::skip(reason),
+            Verdict::Run => {
+                self.spans.tool_started(internal_call_id, tool_name);
+                let running = self
+                    .tool_starts
+                    .lock()
+                    .map(|mut starts| {
+                        starts.insert(internal_call_id.to_string(), Instant::now());
+                        starts.len()
+                    })
+                    .unwrap_or(1);
+
+                tracing::info!(target: logging::TOOLS_TARGET, ">> {tool_name}({truncated_args})");
+                tracing::debug!(target: logging::TOOLS_TARGET, "   args: {args}");
 
-        ToolCallHookAction::cont()
+                // Calls of one response run concurrently and share a single spinner
+                match self.spinner.lock().ok().and_then(|s| s.clone()) {
+                    Some(spinner) => spinner.set_message(Self::executing_message(running)),
+                    None => self.set_spinner(create_spinner(&Self::executing_message(running))),
+                }
+
+                // A replayed call doesn't run; its recorded result is reported
+                // as if it had
+                match cassette::replay_tool(tool_name, args) {
+                    Some(result) => {
+                        PromptHook::<M>::on_tool_result(
+                            self,
+                            tool_name,
+                            None,
+                            internal_call_id,
+                            args,
+                            &result,
+                        )
+                        .await;
+                        ToolCallHookAction::skip(result)
+                    }
+                    None => ToolCallHookAction::cont(),
+                }
+            }
+        }
     }
 
     async fn on_tool_result(
         &self,
-        _tool_name: &str,
+        tool_name: &str,
         _tool_call_id: Option<String>,
-        _internal_call_id: &str,
-        _args: &str,
+        internal_call_id: &str,
+        args: &str,
         result: &str,
     ) -> HookAction {
-        // Check if result contains an ToolCallError and display it
-        // TODO: would be nice to have a better way to detect errors (open an issue in rig repo?)
-        if result.contains("ToolCallError") {
-            let truncated_result = Self::truncate_display(result, 500);
-            println!(
-                "{}",
-                colors::color_error(format!(">> Error: {truncated_result}"))
-            );
+        let (started, running) = self
+            .tool_starts
+            .lock()
+            .map(|mut starts| (starts.remove(internal_call_id), starts.len()))
+            .unwrap_or_default();
+        let failure = ToolFailure::from_result(tool_name, result);
+        let failed = failure.is_some();
+        let output = Self::decode_tool_output(result);
+        self.unconfirmed_output
+            .fetch_add(output.len(), Ordering::Relaxed);
+        self.loop_guard.record(tool_name, args, &output);
+        self.spans
+            .tool_finished(internal_call_id, failure.as_ref(), &output);
+        audit::record(tool_name, args, failed, output.len());
+        cassette::record_tool(tool_name, args, result);
+        if let (Some(started), Ok(mut calls)) = (started, self.tool_calls.lock()) {
+            calls.push(ToolCallOutcome {
+                name: tool_name.to_string(),
+                duration: started.elapsed(),
+                bytes: output.len(),
+                error: failure.as_ref().map(|f| f.kind),
+            });
+        }
+
+        // Keep the spinner until the last concurrent call has finished
+        if running == 0 {
+            if let Some(s) = self.get_spinner() {
+                s.finish_and_clear();
+            }
+        } else if let Some(s) = self.spinner.lock().ok().and_then(|s| s.clone()) {
+            s.set_message(Self::executing_message(running));
+        }
+
+        // Display errors reported by the tool
+        if let Some(failure) = &failure {
+            let message = Self::truncate_display(&failure.message, 500);
+            tracing::error!(target: logging::TOOLS_TARGET, ">> Error ({}): {message}", failure.kind);
+        } else if output.starts_with(CACHED_MARKER) {
+            tracing::info!(target: logging::TOOLS_TARGET, "   -> {CACHED_MARKER}");
+        } else if tracing::enabled!(target: logging::TOOLS_TARGET, Level::DEBUG) {
+            // Suspend the tool spinner so the preview isn't overdrawn
+            let preview = hyperlinks::linkify(&Self::format_result_preview(result));
+            let log = || tracing::debug!(target: logging::TOOLS_TARGET, "{preview}");
+            match self.spinner.lock().ok().and_then(|s| s.clone()) {
+                Some(spinner) => spinner.suspend(log),
+                None => log(),
+            }
         }
 
         HookAction::cont()
@@ -139,7 +564,27 @@ where
             s.finish_and_clear();
         }
 
+        let raw = serde_json::to_value(&response.raw_response).unwrap_or_default();
+        if self.show_thinking() {
+            for thinking in Self::extract_thinking(&raw) {
+                let print = || eprintln!("{}\n", colors::color_dim(&thinking));
+                match self.external_spinner.lock().ok().and_then(|s| s.clone()) {
+                    Some(spinner) => spinner.suspend(print),
+                    None => print(),
+                }
+            }
+        }
+
+        if tracing::enabled!(Level::TRACE) {
+            tracing::trace!(
+                request_id = raw["id"].as_str().unwrap_or("-"),
+                "completion response: {:?}",
+                response.usage
+            );
+        }
+
         // Extract and accumulate token usage
+        self.spans.completion_finished(response.usage);
         self.set_total_usage(response.usage);
 
         HookAction::cont()
@@ -151,3 +596,338 @@ impl Default for ProgressHook {
         Self::new()
     }
 }
+
+/// Agent progress events, emitted by [`EventHook`] for frontends that
+/// render progress themselves instead of printing to the terminal.
+#[derive(Debug, Clone)]
+pub enum AgentEvent {
+    /// A tool is about to be called
+    ToolCall { name: String, args: String },
+    /// A tool call finished
+    ToolResult {
+        name: String,
+        result: String,
+        /// How the call failed, if it did
+        error: Option<ToolFailure>,
+    },
+    /// Token usage of a single completion response
+    Usage(Usage),
+}
+
+/// A hook that forwards tool calls, results and token usage over a channel.
+#[derive(Clone, Debug)]
+pub struct EventHook {
+    sender: UnboundedSender<AgentEvent>,
+    /// Usage of the most recent completion response, for preflight checks
+    last_usage: Arc<Mutex<Usage>>,
+    spans: SpanTracker,
+}
+
+impl EventHook {
+    pub fn new(sender: UnboundedSender<AgentEvent>) -> Self {
+        Self {
+            sender,
+            last_usage: Arc::new(Mutex::new(Usage::default())),
+            spans: SpanTracker::default(),
+        }
+    }
+
+    fn send(&self, event: AgentEvent) {
+        // The receiver going away just means nobody is interested anymore
+        let _ = self.sender.send(event);
+    }
+}
+
+impl<M> PromptHook<M> for EventHook
+where
+    M: CompletionModel,
+{
+    async fn on_completion_call(
+        &self,
+        prompt: &rig::completion::Message,
+        history: &[rig::completion::Message],
+    ) -> HookAction {
+        let last_usage = self.last_usage.lock().map(|u| *u).unwrap_or_default();
+        match preflight::gate(prompt, history, last_usage).and_then(|()| timebox::gate()) {
+            Ok(()) => {
+                ratelimit::acquire(
+                    preflight::estimate(prompt, history, last_usage),
+                    ratelimit::log_wait,
+                )
+                .await;
+                self.spans.completion_started();
+                HookAction::cont()
+            }
+            Err(reason) => HookAction::terminate(reason),
+        }
+    }
+
+    async fn on_tool_call(
+        &self,
+        tool_name: &str,
+        _tool_call_id: Option<String>,
+        internal_call_id: &str,
+        args: &str,
+    ) -> ToolCallHookAction {
+        // Frontends driven by events have nobody to ask on a terminal
+        let blocked = match policy::decide(tool_name, args) {
+            Action::Allow => lifecycle::tool_call(tool_name, args)
+                .await
+                .map(blocked_by_hook),
+            Action::Ask | Action::Deny => Some(DENIED_BY_POLICY.to_string()),
+        };
+
+        match blocked {
+            None => {
+                self.spans.tool_started(internal_call_id, tool_name);
+                self.send(AgentEvent::ToolCall {
+                    name: tool_name.to_string(),
+                    args: args.to_string(),
+                });
+                match cassette::replay_tool(tool_name, args) {
+                    Some(result) => {
+                        PromptHook::<M>::on_tool_result(
+                            self,
+                            tool_name,
+                            None,
+                            internal_call_id,
+                            args,
+                            &result,
+                        )
+                        .await;
+                        ToolCallHookAction::skip(result)
+                    }
+                    None => ToolCallHookAction::cont(),
+                }
+            }
+            Some(reason) => {
+                tracing::warn!(target: logging::TOOLS_TARGET, ">> Denied: {tool_name}({args})");
+                ToolCallHookAction::skip(reason)
+            }
+        }
+    }
+
+    async fn on_tool_result(
+        &self,
+        tool_name: &str,
+        _tool_call_id: Option<String>,
+        internal_call_id: &str,
+        args: &str,
+        result: &str,
+    ) -> HookAction {
+        let error = ToolFailure::from_result(tool_name, result);
+        let output = ProgressHook::decode_tool_output(result);
+        self.spans
+            .tool_finished(internal_call_id, error.as_ref(), &output);
+        audit::record(tool_name, args, error.is_some(), output.len());
+        cassette::record_tool(tool_name, args, result);
+        self.send(AgentEvent::ToolResult {
+            name: tool_name.to_string(),
+            result: result.to_string(),
+            error,
+        });
+
+        HookAction::cont()
+    }
+
+    async fn on_completion_response(
+        &self,
+        _prompt: &rig::completion::Message,
+        response: &CompletionResponse<M::Response>,
+    ) -> HookAction {
+        self.spans.completion_finished(response.usage);
+        if let Ok(mut last_usage) = self.last_usage.lock() {
+            *last_usage = response.usage;
+        }
+        self.send(AgentEvent::Usage(response.usage));
+
+        HookAction::cont()
+    }
+}
+
+/// Hook of the requests made outside a frontend's turns: sub-agents and
+/// helper prompts such as translations, summaries and citation checks.
+/// They are rate limited and logged to the usage log under their own
+/// model. Their tool calls go through the tool policy, the `on_tool_call`
+/// hooks and the audit log, and are recorded to and replayed from the
+/// cassette, like the main agent's.
+#[derive(Clone, Debug)]
+pub struct HelperHook {
+    model: String,
+    /// When the request in flight was sent
+    sent: Arc<Mutex<Option<Instant>>>,
+}
+
+impl HelperHook {
+    pub fn new(model: &str) -> Self {
+        Self {
+            model: model.to_string(),
+            sent: Arc::new(Mutex::new(None)),
+        }
+    }
+}
+
+impl<M> PromptHook<M> for HelperHook
+where
+    M: CompletionModel,
+{
+    async fn on_completion_call(&self, prompt: &Message, history: &[Message]) -> HookAction {
+        ratelimit::acquire(
+            preflight::estimate(prompt, history, Usage::default()),
+            ratelimit::log_wait,
+        )
+        .await;
+        if let Ok(mut sent) = self.sent.lock() {
+            *sent = Some(Instant::now());
+        }
+        HookAction::cont()
+    }
+
+    async fn on_completion_response(
+        &self,
+        _prompt: &Message,
+        response: &CompletionResponse<M::Response>,
+    ) -> HookAction {
+        let duration = self
+            .sent
+            .lock()
+            .ok()
+            .and_then(|mut sent| sent.take())
+            .map(|sent| sent.elapsed())
+            .unwrap_or_default();
+        let tool_calls = response
+            .choice
+            .iter()
+            .filter(|content| matches!(content, AssistantContent::ToolCall(_)))
+            .count();
+        usage::record_helper(&self.model, response.usage.into(), tool_calls, duration);
+        HookAction::cont()
+    }
+
+    async fn on_tool_call(
+        &self,
+        tool_name: &str,
+        _tool_call_id: Option<String>,
+        internal_call_id: &str,
+        args: &str,
+    ) -> ToolCallHookAction {
+        // Nobody can be asked in the middle of a helper's work
+        let blocked = match policy::decide(tool_name, args) {
+            Action::Allow => lifecycle::tool_call(tool_name, args)
+                .await
+                .map(blocked_by_hook),
+            Action::Ask | Action::Deny => Some(DENIED_BY_POLICY.to_string()),
+        };
+
+        match blocked {
+            Some(reason) => {
+                tracing::warn!(target: logging::TOOLS_TARGET, ">> Denied: {tool_name}({args})");
+                ToolCallHookAction::skip(reason)
+            }
+            // A replayed call doesn't run
+            None => match cassette::replay_tool(tool_name, args) {
+                Some(result) => {
+                    PromptHook::<M>::on_tool_result(
+                        self,
+                        tool_name,
+                        None,
+                        internal_call_id,
+                        args,
+                        &result,
+                    )
+                    .await;
+                    ToolCallHookAction::skip(result)
+                }
+                None => ToolCallHookAction::cont(),
+            },
+        }
+    }
+
+    async fn on_tool_result(
+        &self,
+        tool_name: &str,
+        _tool_call_id: Option<String>,
+        _internal_call_id: &str,
+        args: &str,
+        result: &str,
+    ) -> HookAction {
+        let failed = ToolFailure::from_result(tool_name, result).is_some();
+        audit::record(
+            tool_name,
+            args,
+            failed,
+            ProgressHook::decode_tool_output(result).len(),
+        );
+        cassette::record_tool(tool_name, args, result);
+        HookAction::cont()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extract_thinking() {
+        let raw = serde_json::json!({
+            "content": [
+                { "type": "thinking", "thinking": "The router is in src/app.rs\n", "signature": "abc" },
+                { "type": "redacted_thinking", "data": "xyz" },
+                { "type": "text", "text": "It's in src/app.rs" }
+            ]
+        });
+        assert_eq!(
+            ProgressHook::extract_thinking(&raw),
+            ["The router is in src/app.rs"]
+        );
+        assert!(ProgressHook::extract_thinking(&serde_json::Value::Null).is_empty());
+    }
+
+    #[test]
+    fn test_format_result_preview() {
+        let result =
+            serde_json::to_string("src/main.rs:1:fn main\n\nsrc/lib.rs:2:mod a\nx\ny").unwrap();
+        let preview = ProgressHook::format_result_preview(&result);
+        assert_eq!(
+            preview,
+            "   -> 5 lines, 45B\n   | src/main.rs:1:fn main\n   | src/lib.rs:2:mod a\n   | x"
+        );
+
+        // Non-JSON results are previewed as-is
+        let preview = ProgressHook::format_result_preview("plain");
+        assert_eq!(preview, "   -> 1 line, 5B\n   | plain");
+    }
+
+    #[test]
+    fn test_executing_message() {
+        assert_eq!(ProgressHook::executing_message(1), "Executing tool");
+        assert_eq!(ProgressHook::executing_message(3), "Executing 3 tools");
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_tool_results() {
+        type Model = rig::providers::anthropic::completion::CompletionModel;
+
+        let hook = ProgressHook::new();
+        if let Ok(mut starts) = hook.tool_starts.lock() {
+            starts.insert("call-1".to_string(), Instant::now());
+            starts.insert("call-2".to_string(), Instant::now());
+        }
+
+        // Results arrive out of order, each matched to its own call
+        PromptHook::<Model>::on_tool_result(&hook, "bash", None, "call-2", "{}", "\"b\"").await;
+        assert_eq!(hook.tool_starts.lock().unwrap().len(), 1);
+        PromptHook::<Model>::on_tool_result(&hook, "read_file", None, "call-1", "{}", "\"a\"")
+            .await;
+        assert!(hook.tool_starts.lock().unwrap().is_empty());
+
+        let calls = hook.take_tool_calls();
+        let names: Vec<&str> = calls.iter().map(|call| call.name.as_str()).collect();
+        assert_eq!(names, ["bash", "read_file"]);
+        assert!(
+            calls
+                .iter()
+                .all(|call| call.bytes == 1 && call.error.is_none())
+        );
+    }
+}
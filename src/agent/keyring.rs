@@ -0,0 +1,91 @@
+use std::io::{self, Write};
+use std::process::{Command, Stdio};
+use std::sync::OnceLock;
+
+/// Service the API keys are stored under in the system keyring
+const SERVICE: &str = "horse";
+
+/// Environment variable that takes precedence over the stored key
+const API_KEY_VAR: &str = "ANTHROPIC_API_KEY";
+
+/// The key `horse init` stored, looked up once per process
+static STORED: OnceLock<Option<String>> = OnceLock::new();
+
+/// Where the API key of this process comes from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeySource {
+    Env,
+    Keyring,
+}
+
+/// The Anthropic API key: `ANTHROPIC_API_KEY`, or else the key stored in
+/// the system keyring by `horse init`
+pub fn api_key() -> Option<(String, KeySource)> {
+    std::env::var(API_KEY_VAR)
+        .ok()
+        .filter(|key| !key.is_empty())
+        .map(|key| (key, KeySource::Env))
+        .or_else(|| {
+            STORED
+                .get_or_init(|| load("anthropic"))
+                .clone()
+                .map(|key| (key, KeySource::Keyring))
+        })
+}
+
+/// The key of `provider`, or token of a forge, in the system keyring,
+/// through `security` on macOS and libsecret's `secret-tool` elsewhere
+pub fn load(provider: &str) -> Option<String> {
+    let output = if cfg!(target_os = "macos") {
+        Command::new("security")
+            .args(["find-generic-password", "-s", SERVICE, "-a", provider, "-w"])
+            .stderr(Stdio::null())
+            .output()
+    } else {
+        Command::new("secret-tool")
+            .args(["lookup", "service", SERVICE, "account", provider])
+            .stderr(Stdio::null())
+            .output()
+    };
+    output
+        .ok()
+        .filter(|output| output.status.success())
+        .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string())
+        .filter(|key| !key.is_empty())
+}
+
+/// Store the key of `provider` in the system keyring, replacing any
+/// previous one. The key is written to the tool's stdin, never passed as an
+/// argument that other processes could read.
+pub fn store(provider: &str, key: &str) -> io::Result<()> {
+    let (mut command, input) = if cfg!(target_os = "macos") {
+        // A trailing `-w` without a value has `security` prompt for the
+        // password and then for it again
+        let mut command = Command::new("security");
+        command
+            .args(["add-generic-password", "-U", "-s", SERVICE, "-a", provider])
+            .arg("-w");
+        (command, format!("{key}\n{key}\n"))
+    } else {
+        let mut command = Command::new("secret-tool");
+        command
+            .args(["store", "--label", &format!("{SERVICE} {provider} API key")])
+            .args(["service", SERVICE, "account", provider]);
+        (command, key.to_string())
+    };
+    let mut child = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .spawn()?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(input.as_bytes())?;
+    }
+    let status = child.wait()?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(io::Error::other(format!(
+            "The keyring tool exited with {status}"
+        )))
+    }
+}
@@ -0,0 +1,198 @@
+use std::fmt::Display;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+use anyhow::{Context, Result, anyhow};
+use serde::Deserialize;
+use serde_json::{Value, json};
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+use tokio::time::timeout;
+
+/// How long a hook command may run before it is killed
+const HOOK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The hooks of this process, set from config at startup
+static HOOKS: OnceLock<Hooks> = OnceLock::new();
+
+/// `[hooks]` config section: shell commands run on agent lifecycle events,
+/// with the event as JSON on stdin
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HooksConfig {
+    /// Run before each tool call; a non-zero exit blocks the call, with
+    /// stderr as the reason given to the model
+    #[serde(alias = "on-tool-call")]
+    pub on_tool_call: Vec<String>,
+    /// Run after each answer or failed turn
+    #[serde(alias = "on-turn-end")]
+    pub on_turn_end: Vec<String>,
+    /// Run once when horse exits
+    #[serde(alias = "on-session-end")]
+    pub on_session_end: Vec<String>,
+}
+
+#[derive(Debug)]
+struct Hooks {
+    config: HooksConfig,
+    base_dir: PathBuf,
+    /// Turns finished so far, reported at the end of the session
+    turns: AtomicUsize,
+}
+
+impl Hooks {
+    fn event(&self, name: &str, mut fields: Value) -> Value {
+        fields["event"] = json!(name);
+        fields["workspace"] = json!(self.base_dir);
+        fields
+    }
+}
+
+/// Set the hooks of this process
+pub fn init(config: &HooksConfig, base_dir: &Path) {
+    let _ = HOOKS.set(Hooks {
+        config: config.clone(),
+        base_dir: base_dir.to_path_buf(),
+        turns: AtomicUsize::new(0),
+    });
+}
+
+/// Run a hook command through `sh -c` in `base_dir`, feeding it `event`.
+/// Fails on a non-zero exit with the command's stderr, or when it doesn't
+/// finish within `limit`, reading its input included.
+async fn run(command: &str, base_dir: &Path, event: &Value, limit: Duration) -> Result<()> {
+    let mut child = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .current_dir(base_dir)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .with_context(|| format!("Failed to run hook `{command}`"))?;
+
+    // Written while the output is read, so a hook filling its stderr pipe
+    // before reading doesn't stall either side
+    let stdin = child.stdin.take();
+    let input = event.to_string();
+    let write = async move {
+        if let Some(mut stdin) = stdin
+            && let Err(e) = stdin.write_all(input.as_bytes()).await
+        {
+            // A hook that doesn't read its input closes the pipe early
+            tracing::debug!("Hook `{command}` didn't read its input: {e}");
+        }
+    };
+
+    let (_, output) = timeout(limit, async {
+        tokio::join!(write, child.wait_with_output())
+    })
+    .await
+    .map_err(|_| {
+        anyhow!(
+            "Hook `{command}` timed out after {} seconds",
+            limit.as_secs_f32()
+        )
+    })?;
+    let output = output?;
+
+    if output.status.success() {
+        Ok(())
+    } else {
+        let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+        Err(anyhow!(if stderr.is_empty() {
+            format!("Hook `{command}` exited with {}", output.status)
+        } else {
+            stderr
+        }))
+    }
+}
+
+/// Run the hooks of an event whose outcome doesn't matter, reporting failures
+async fn notify(commands: &[String], hooks: &Hooks, event: Value) {
+    for command in commands {
+        if let Err(e) = run(command, &hooks.base_dir, &event, HOOK_TIMEOUT).await {
+            tracing::warn!("[!] Warning: {e:#}");
+        }
+    }
+}
+
+/// Run the `on_tool_call` hooks for a call about to happen.
+///
+/// Returns why the call is blocked, if a hook refused it or failed to run.
+pub async fn tool_call(tool: &str, args: &str) -> Option<String> {
+    let hooks = HOOKS.get()?;
+    let args = serde_json::from_str(args).unwrap_or_else(|_| json!(args));
+    let event = hooks.event("tool_call", json!({ "tool": tool, "args": args }));
+
+    // The first hook that refuses decides, the others aren't asked
+    let mut blocked = None;
+    for command in &hooks.config.on_tool_call {
+        if blocked.is_none()
+            && let Err(e) = run(command, &hooks.base_dir, &event, HOOK_TIMEOUT).await
+        {
+            blocked = Some(format!("{e:#}"));
+        }
+    }
+    blocked
+}
+
+/// Run the `on_turn_end` hooks with the prompt and the answer or error
+pub async fn turn_end<E: Display>(prompt: &str, result: &Result<String, E>) {
+    if let Some(hooks) = HOOKS.get() {
+        hooks.turns.fetch_add(1, Ordering::Relaxed);
+        let (answer, error) = match result {
+            Ok(answer) => (Some(answer.as_str()), None),
+            Err(e) => (None, Some(format!("{e:#}"))),
+        };
+        let event = hooks.event(
+            "turn_end",
+            json!({ "prompt": prompt, "answer": answer, "error": error }),
+        );
+        notify(&hooks.config.on_turn_end, hooks, event).await;
+    }
+}
+
+/// Run the `on_session_end` hooks with the number of turns taken
+pub async fn session_end() {
+    if let Some(hooks) = HOOKS.get() {
+        let turns = hooks.turns.load(Ordering::Relaxed);
+        let event = hooks.event("session_end", json!({ "turns": turns }));
+        notify(&hooks.config.on_session_end, hooks, event).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_run() {
+        let base_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+        let event = json!({ "event": "tool_call", "tool": "bash" });
+
+        let run = |command| run(command, base_dir, &event, HOOK_TIMEOUT);
+
+        assert!(run(r#"grep -q '"tool":"bash"'"#).await.is_ok());
+        let blocked = run("echo 'bash is not allowed' >&2; exit 1").await;
+        assert_eq!(blocked.unwrap_err().to_string(), "bash is not allowed");
+        assert!(run("exit 3").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_run_unread_input() {
+        let base_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+        // Larger than a pipe's buffer
+        let event = json!({ "event": "turn_end", "answer": "x".repeat(1 << 20) });
+
+        assert!(run("exit 0", base_dir, &event, HOOK_TIMEOUT).await.is_ok());
+        let started = std::time::Instant::now();
+        let stalled = run("sleep 5", base_dir, &event, Duration::from_millis(200)).await;
+        assert!(stalled.unwrap_err().to_string().contains("timed out"));
+        assert!(started.elapsed() < Duration::from_secs(2));
+    }
+}
@@ -0,0 +1,206 @@
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+
+use serde::Deserialize;
+
+/// Characters of an earlier result repeated to the model, at most
+const MAX_REPEATED_CHARS: usize = 2000;
+
+/// The limits of this process, set from config at startup
+static CONFIG: OnceLock<ToolCallsConfig> = OnceLock::new();
+
+/// `[tool-calls]` config section
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ToolCallsConfig {
+    /// Tool calls a single query may make; further calls get no result but a
+    /// request to answer. 0 for no limit.
+    #[serde(alias = "max-per-query")]
+    pub max_per_query: usize,
+    /// Times a call may be repeated with the same arguments within a query,
+    /// each answered with the earlier result, before the query is stopped
+    #[serde(alias = "max-repeats")]
+    pub max_repeats: usize,
+}
+
+impl Default for ToolCallsConfig {
+    fn default() -> Self {
+        Self {
+            max_per_query: 100,
+            max_repeats: 2,
+        }
+    }
+}
+
+/// Apply the configured limits to all queries of this process
+pub fn init(config: &ToolCallsConfig) {
+    let _ = CONFIG.set(config.clone());
+}
+
+fn config() -> &'static ToolCallsConfig {
+    CONFIG.get_or_init(ToolCallsConfig::default)
+}
+
+/// What happens to a tool call
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Verdict {
+    Run,
+    /// Don't run it; the model gets this instead of a result
+    Skip(String),
+    /// The agent is looping; stop the query for this reason
+    Stop(String),
+}
+
+/// How the first of a call's repeats went
+#[derive(Debug, Default)]
+enum Outcome {
+    #[default]
+    Running,
+    Finished(String),
+    /// Not run, for this reason, e.g. the tool policy
+    Denied(String),
+}
+
+#[derive(Debug, Default)]
+struct Calls {
+    count: usize,
+    /// Times each call was made, and how it went, keyed by tool and
+    /// normalized arguments
+    seen: HashMap<String, (usize, Outcome)>,
+}
+
+/// Counts the tool calls of a query and catches the ones repeating an
+/// earlier call verbatim
+#[derive(Debug, Default)]
+pub struct LoopGuard {
+    calls: Mutex<Calls>,
+}
+
+impl LoopGuard {
+    /// Decide a call of the current query, counting it
+    pub fn check(&self, tool: &str, args: &str) -> Verdict {
+        self.calls.lock().map_or(Verdict::Run, |mut calls| {
+            calls.count += 1;
+            let count = calls.count;
+            let (repeats, result) = calls.seen.entry(key(tool, args)).or_default();
+            *repeats += 1;
+            let config = config();
+
+            match (*repeats - 1, result) {
+                (0, _) if config.max_per_query > 0 && count > config.max_per_query => {
+                    Verdict::Skip(format!(
+                        "The limit of {} tool calls per question is reached. Don't call any \
+                         more tools; answer with what you found so far.",
+                        config.max_per_query
+                    ))
+                }
+                (0, _) => Verdict::Run,
+                (repeated, _) if repeated > config.max_repeats => Verdict::Stop(format!(
+                    "Stopped a loop: {tool} was called {repeats} times with the same arguments"
+                )),
+                (_, Outcome::Finished(result)) => Verdict::Skip(format!(
+                    "You already called {tool} with these arguments for this question. Don't \
+                     repeat calls; use this result or try something different. The result \
+                     was:\n\n{}",
+                    truncate(result)
+                )),
+                (_, Outcome::Denied(reason)) => Verdict::Skip(format!(
+                    "You already called {tool} with these arguments for this question, and \
+                     the call was refused: {reason}"
+                )),
+                (_, Outcome::Running) => Verdict::Skip(format!(
+                    "You already called {tool} with these arguments for this question and it \
+                     is still running. Don't repeat calls."
+                )),
+            }
+        })
+    }
+
+    /// Keep the result of a finished call for repeats of it
+    pub fn record(&self, tool: &str, args: &str, output: &str) {
+        self.settle(tool, args, Outcome::Finished(output.to_string()));
+    }
+
+    /// Keep why a call was refused before it ran, e.g. by the tool policy or
+    /// a hook, for repeats of it
+    pub fn deny(&self, tool: &str, args: &str, reason: &str) {
+        self.settle(tool, args, Outcome::Denied(reason.to_string()));
+    }
+
+    fn settle(&self, tool: &str, args: &str, outcome: Outcome) {
+        if let Ok(mut calls) = self.calls.lock()
+            && let Some((_, settled)) = calls.seen.get_mut(&key(tool, args))
+        {
+            *settled = outcome;
+        }
+    }
+
+    /// Start counting a new query
+    pub fn reset(&self) {
+        if let Ok(mut calls) = self.calls.lock() {
+            *calls = Calls::default();
+        }
+    }
+}
+
+/// Arguments that differ only in key order or whitespace are the same call
+fn key(tool: &str, args: &str) -> String {
+    let args = serde_json::from_str::<serde_json::Value>(args)
+        .map_or_else(|_| args.trim().to_string(), |value| value.to_string());
+    format!("{tool}\0{args}")
+}
+
+fn truncate(result: &str) -> String {
+    match result.char_indices().nth(MAX_REPEATED_CHARS) {
+        Some((end, _)) => format!("{}\n[truncated]", &result[..end]),
+        None => result.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_repeated_calls() {
+        let guard = LoopGuard::default();
+        let args = r#"{"pattern": "fn main", "path": "src"}"#;
+
+        assert_eq!(guard.check("deep_search", args), Verdict::Run);
+        assert!(matches!(
+            guard.check("deep_search", r#"{"path":"src","pattern":"fn main"}"#),
+            Verdict::Skip(reason) if reason.contains("still running")
+        ));
+        guard.record("deep_search", args, "src/main.rs:12");
+        assert!(matches!(
+            guard.check("deep_search", args),
+            Verdict::Skip(reason) if reason.ends_with("src/main.rs:12")
+        ));
+        assert!(matches!(guard.check("deep_search", args), Verdict::Stop(_)));
+        assert_eq!(
+            guard.check("deep_search", r#"{"pattern": "fn run"}"#),
+            Verdict::Run
+        );
+
+        guard.reset();
+        assert_eq!(guard.check("deep_search", args), Verdict::Run);
+    }
+
+    #[test]
+    fn test_denied_calls() {
+        let guard = LoopGuard::default();
+        let args = r#"{"command": "cat .env"}"#;
+
+        assert_eq!(guard.check("bash", args), Verdict::Run);
+        guard.deny(
+            "bash",
+            args,
+            "The tool call was denied by the user's tool policy.",
+        );
+        assert!(matches!(
+            guard.check("bash", args),
+            Verdict::Skip(reason)
+                if reason.contains("was refused: The tool call was denied by the user's tool policy")
+        ));
+    }
+}
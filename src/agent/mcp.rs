@@ -0,0 +1,209 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::Arc;
+
+use rig::completion::ToolDefinition;
+use rig::tool::Tool;
+use serde::Deserialize;
+use serde_json::{Value, json};
+use thiserror::Error;
+
+use crate::agent::rpc::{RpcClient, RpcError};
+
+/// MCP protocol revision spoken by the client
+const PROTOCOL_VERSION: &str = "2024-11-05";
+
+/// An MCP server declared in config under `[mcp.<name>]`
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct McpServerConfig {
+    /// Executable that speaks MCP over stdio
+    pub command: String,
+    pub args: Vec<String>,
+    /// Extra environment variables for the server process
+    pub env: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Error)]
+pub enum McpError {
+    #[error("MCP server: {0}")]
+    Rpc(#[from] RpcError),
+    #[error("MCP server returned an invalid tool list: {0}")]
+    InvalidToolList(String),
+    #[error("Tool failed: {0}")]
+    ToolFailed(String),
+}
+
+/// A tool advertised by an MCP server
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct McpToolInfo {
+    name: String,
+    #[serde(default)]
+    description: String,
+    input_schema: Value,
+}
+
+/// An MCP server process, after the handshake
+pub struct McpClient {
+    rpc: RpcClient,
+}
+
+impl McpClient {
+    /// Spawn the server and perform the MCP handshake
+    async fn start(config: &McpServerConfig, base_dir: &Path) -> Result<Self, McpError> {
+        let rpc = RpcClient::spawn(&config.command, &config.args, &config.env, base_dir)?;
+        rpc.request(
+            "initialize",
+            json!({
+                "protocolVersion": PROTOCOL_VERSION,
+                "capabilities": {},
+                "clientInfo": { "name": "horse", "version": env!("CARGO_PKG_VERSION") }
+            }),
+        )
+        .await?;
+        rpc.notify("notifications/initialized").await?;
+
+        Ok(Self { rpc })
+    }
+
+    async fn list_tools(&self) -> Result<Vec<McpToolInfo>, McpError> {
+        let result = self.rpc.request("tools/list", json!({})).await?;
+        serde_json::from_value(result["tools"].clone())
+            .map_err(|e| McpError::InvalidToolList(e.to_string()))
+    }
+
+    async fn call_tool(&self, name: &str, arguments: Value) -> Result<String, McpError> {
+        let result = self
+            .rpc
+            .request(
+                "tools/call",
+                json!({ "name": name, "arguments": arguments }),
+            )
+            .await?;
+
+        let text = content_text(&result);
+        if result["isError"].as_bool().unwrap_or(false) {
+            Err(McpError::ToolFailed(text))
+        } else {
+            Ok(text)
+        }
+    }
+}
+
+/// Join the text items of a `tools/call` result, describing other content types
+fn content_text(result: &Value) -> String {
+    result["content"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .map(|item| match item["text"].as_str() {
+            Some(text) => text.to_string(),
+            None => format!("[{} content]", item["type"].as_str().unwrap_or("unknown")),
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Name under which a server's tool is registered, unique across servers and
+/// limited to the characters tool names allow
+pub(crate) fn qualified_name(server: &str, tool: &str) -> String {
+    format!("{server}__{tool}")
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '_' || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .take(64)
+        .collect()
+}
+
+/// A tool provided by an MCP server, registered on the agent like a native tool
+#[derive(Clone)]
+pub struct McpTool {
+    client: Arc<McpClient>,
+    name: String,
+    info: McpToolInfo,
+}
+
+impl Tool for McpTool {
+    /// Placeholder; the registered name comes from `name()`
+    const NAME: &'static str = "mcp";
+
+    type Error = McpError;
+    type Args = Value;
+    type Output = String;
+
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    async fn definition(&self, _prompt: String) -> ToolDefinition {
+        ToolDefinition {
+            name: self.name.clone(),
+            description: self.info.description.clone(),
+            parameters: self.info.input_schema.clone(),
+        }
+    }
+
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+        self.client.call_tool(&self.info.name, args).await
+    }
+}
+
+/// Start the configured MCP servers and collect their tools.
+///
+/// Servers that fail to start are reported and skipped.
+pub async fn load_tools(
+    servers: &BTreeMap<String, McpServerConfig>,
+    base_dir: &Path,
+) -> Vec<McpTool> {
+    let mut tools = Vec::new();
+
+    for (server, config) in servers {
+        let started = match McpClient::start(config, base_dir).await {
+            Ok(client) => client.list_tools().await.map(|infos| (client, infos)),
+            Err(e) => Err(e),
+        };
+
+        match started {
+            Ok((client, infos)) => {
+                tracing::info!(">> MCP server {server}: {} tools", infos.len());
+                let client = Arc::new(client);
+                tools.extend(infos.into_iter().map(|info| McpTool {
+                    client: client.clone(),
+                    name: qualified_name(server, &info.name),
+                    info,
+                }));
+            }
+            Err(e) => tracing::warn!("[!] Warning: MCP server {server} unavailable: {e}"),
+        }
+    }
+
+    tools
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_content_text() {
+        let result = json!({
+            "content": [
+                { "type": "text", "text": "2 rows" },
+                { "type": "image", "data": "..." }
+            ]
+        });
+        assert_eq!(content_text(&result), "2 rows\n[image content]");
+        assert_eq!(content_text(&json!({})), "");
+    }
+
+    #[test]
+    fn test_qualified_name() {
+        assert_eq!(qualified_name("pg", "run.query"), "pg__run_query");
+    }
+}
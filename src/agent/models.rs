@@ -0,0 +1,107 @@
+/// Context window of all current Claude models, in tokens
+const CLAUDE_CONTEXT_WINDOW: u64 = 200_000;
+
+/// Model names offered by shell completion; any other name is accepted as well
+pub const KNOWN_MODELS: &[&str] = &[
+    "claude-sonnet-4-0",
+    "claude-opus-4-0",
+    "claude-opus-4-1",
+    "claude-sonnet-4-5",
+    "claude-haiku-4-5",
+    "claude-3-7-sonnet-latest",
+    "claude-3-5-haiku-latest",
+];
+
+/// Context window size of a model, in tokens
+pub fn context_window(_model: &str) -> u64 {
+    CLAUDE_CONTEXT_WINDOW
+}
+
+/// Price of a model in USD per million tokens
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Pricing {
+    pub input: f64,
+    pub output: f64,
+    pub cached_input: f64,
+}
+
+/// Prices by model family, matched against the model name in order
+const PRICING: &[(&str, Pricing)] = &[
+    (
+        "opus-4-5",
+        Pricing {
+            input: 5.0,
+            output: 25.0,
+            cached_input: 0.5,
+        },
+    ),
+    (
+        "opus",
+        Pricing {
+            input: 15.0,
+            output: 75.0,
+            cached_input: 1.5,
+        },
+    ),
+    (
+        "sonnet",
+        Pricing {
+            input: 3.0,
+            output: 15.0,
+            cached_input: 0.3,
+        },
+    ),
+    (
+        "3-5-haiku",
+        Pricing {
+            input: 0.8,
+            output: 4.0,
+            cached_input: 0.08,
+        },
+    ),
+    (
+        "haiku",
+        Pricing {
+            input: 1.0,
+            output: 5.0,
+            cached_input: 0.1,
+        },
+    ),
+];
+
+/// Pricing of a model, if its family is known
+pub fn pricing(model: &str) -> Option<Pricing> {
+    PRICING
+        .iter()
+        .find(|(family, _)| model.contains(family))
+        .map(|(_, pricing)| *pricing)
+}
+
+/// Estimated cost in USD of the given token counts.
+///
+/// Cache writes are billed at the input price, so the estimate is slightly low
+/// for turns that populate the prompt cache.
+pub fn estimate_cost(model: &str, input: u64, output: u64, cached_input: u64) -> Option<f64> {
+    pricing(model).map(|p| {
+        (input as f64 * p.input + output as f64 * p.output + cached_input as f64 * p.cached_input)
+            / 1_000_000.0
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_estimate_cost() {
+        let cost = estimate_cost("claude-sonnet-4-0", 1_000_000, 100_000, 2_000_000).unwrap();
+        assert!((cost - 5.1).abs() < 1e-9);
+        assert_eq!(pricing("claude-opus-4-5").map(|p| p.input), Some(5.0));
+        assert_eq!(pricing("claude-opus-4-1").map(|p| p.input), Some(15.0));
+        assert_eq!(
+            pricing("claude-3-5-haiku-latest").map(|p| p.input),
+            Some(0.8)
+        );
+        assert_eq!(pricing("gpt-4o"), None);
+    }
+}
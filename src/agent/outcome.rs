@@ -0,0 +1,259 @@
+use std::error::Error;
+use std::fmt;
+use std::path::PathBuf;
+
+use rig::completion::ToolDefinition;
+use rig::tool::Tool;
+use serde::{Deserialize, Serialize};
+
+use crate::agent::tools::{BashCommandError, GitHistoryError, ReadFileError, ReadLogError};
+use crate::agent::{paranoid, recovery};
+
+/// Errors rig reports itself, before or instead of calling a tool, by the
+/// prefix of their message
+const RIG_ERRORS: &[(&str, ErrorKind)] = &[
+    ("ToolNotFoundError: ", ErrorKind::UnknownTool),
+    ("JsonError: ", ErrorKind::InvalidArgs),
+    ("ToolCallError: ", ErrorKind::Failed),
+];
+
+/// How a tool call failed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorKind {
+    /// The arguments don't match the tool's parameters
+    InvalidArgs,
+    /// The model called a tool that isn't registered
+    UnknownTool,
+    NotFound,
+    /// The tool refused the call, e.g. a command outside its allowlist
+    NotAllowed,
+    PermissionDenied,
+    Timeout,
+    Failed,
+}
+
+impl ErrorKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::InvalidArgs => "invalid_args",
+            Self::UnknownTool => "unknown_tool",
+            Self::NotFound => "not_found",
+            Self::NotAllowed => "not_allowed",
+            Self::PermissionDenied => "permission_denied",
+            Self::Timeout => "timeout",
+            Self::Failed => "failed",
+        }
+    }
+
+    /// Kind of the innermost error of `error`'s source chain that tells one
+    fn of<'a>(error: &'a (dyn Error + 'static)) -> Self {
+        std::iter::successors(Some(error), |&e: &&'a (dyn Error + 'static)| e.source())
+            .find_map(|e| {
+                if let Some(bash) = e.downcast_ref::<BashCommandError>() {
+                    match bash {
+                        BashCommandError::CommandNotAllowed(..)
+                        | BashCommandError::ForbiddenPattern(_)
+                        | BashCommandError::OutsideScope(_) => Some(Self::NotAllowed),
+                        BashCommandError::Timeout(_) => Some(Self::Timeout),
+                        BashCommandError::EmptyCommand => Some(Self::InvalidArgs),
+                        _ => None,
+                    }
+                } else if let Some(
+                    ReadFileError::PathTraversal(_)
+                    | ReadFileError::OutsideBaseDir
+                    | ReadFileError::OutsideScope(_),
+                ) = e.downcast_ref::<ReadFileError>()
+                {
+                    Some(Self::NotAllowed)
+                } else if let Some(
+                    GitHistoryError::PathTraversal(_) | GitHistoryError::OutsideScope(_),
+                ) = e.downcast_ref::<GitHistoryError>()
+                {
+                    Some(Self::NotAllowed)
+                } else if let Some(
+                    ReadLogError::PathTraversal(_)
+                    | ReadLogError::OutsideBaseDir
+                    | ReadLogError::OutsideScope(_),
+                ) = e.downcast_ref::<ReadLogError>()
+                {
+                    Some(Self::NotAllowed)
+                } else if let Some(io) = e.downcast_ref::<std::io::Error>() {
+                    match io.kind() {
+                        std::io::ErrorKind::NotFound => Some(Self::NotFound),
+                        std::io::ErrorKind::PermissionDenied => Some(Self::PermissionDenied),
+                        std::io::ErrorKind::TimedOut => Some(Self::Timeout),
+                        std::io::ErrorKind::InvalidInput => Some(Self::InvalidArgs),
+                        _ => None,
+                    }
+                } else {
+                    e.is::<tokio::time::error::Elapsed>()
+                        .then_some(Self::Timeout)
+                }
+            })
+            .unwrap_or(Self::Failed)
+    }
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A failed tool call, as the model and the hooks see it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ToolFailure {
+    pub tool: String,
+    pub kind: ErrorKind,
+    pub message: String,
+    /// How to recover in the next call, e.g. the closest existing path
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hint: Option<String>,
+}
+
+impl ToolFailure {
+    fn new(tool: String, error: &(dyn Error + 'static)) -> Self {
+        Self {
+            tool,
+            kind: ErrorKind::of(error),
+            message: error.to_string(),
+            hint: None,
+        }
+    }
+
+    /// The failure a tool call's result reports, if the call failed: the
+    /// error envelope of a wrapped tool, or an error rig raised itself
+    pub fn from_result(tool: &str, result: &str) -> Option<Self> {
+        serde_json::from_str::<Envelope<serde::de::IgnoredAny>>(result)
+            .ok()
+            .and_then(|envelope| match envelope {
+                Envelope::Err { error } => Some(error),
+                Envelope::Ok(_) => None,
+            })
+            .or_else(|| {
+                RIG_ERRORS.iter().find_map(|(prefix, kind)| {
+                    result.strip_prefix(prefix).map(|message| Self {
+                        tool: tool.to_string(),
+                        kind: *kind,
+                        message: message.to_string(),
+                        hint: None,
+                    })
+                })
+            })
+    }
+}
+
+/// Result of a wrapped tool call: the tool's output as is, or
+/// `{"error": {"tool": .., "kind": .., "message": ..}}`
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Envelope<O> {
+    Err { error: ToolFailure },
+    Ok(O),
+}
+
+/// A tool whose failures are returned as a typed error envelope rather than
+/// rig's error string, so they can be told from output reliably, with a hint
+/// on how to recover
+pub struct Enveloped<T> {
+    tool: T,
+    base_dir: PathBuf,
+}
+
+impl<T> Enveloped<T> {
+    pub fn new(tool: T, base_dir: PathBuf) -> Self {
+        Self { tool, base_dir }
+    }
+}
+
+impl<T> Tool for Enveloped<T>
+where
+    T: Tool,
+    T::Output: Send,
+{
+    const NAME: &'static str = T::NAME;
+
+    type Error = T::Error;
+    // Parsed here, so invalid arguments fail like any other call
+    type Args = serde_json::Value;
+    type Output = Envelope<T::Output>;
+
+    fn name(&self) -> String {
+        self.tool.name()
+    }
+
+    async fn definition(&self, prompt: String) -> ToolDefinition {
+        self.tool.definition(prompt).await
+    }
+
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+        let result = match serde_json::from_value::<T::Args>(args.clone()) {
+            Ok(parsed) => self
+                .tool
+                .call(parsed)
+                .await
+                .map_err(Box::<dyn Error + Send + Sync>::from),
+            Err(e) => Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, e).into()),
+        };
+        // Any tool may write, whether it means to or not
+        paranoid::check(&format!("{} {args}", self.name())).await;
+        match result {
+            Ok(output) => Ok(Envelope::Ok(output)),
+            Err(e) => {
+                let mut error = ToolFailure::new(self.name(), e.as_ref());
+                let parameters = self.tool.definition(String::new()).await.parameters;
+                error.hint =
+                    recovery::hint(&self.base_dir, e.as_ref(), error.kind, &args, &parameters)
+                        .await;
+                Ok(Envelope::Err { error })
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_result() {
+        let failure = ToolFailure {
+            tool: "read_file".to_string(),
+            kind: ErrorKind::NotFound,
+            message: "No such file".to_string(),
+            hint: Some("Did you mean `src/main.rs`?".to_string()),
+        };
+        let result = serde_json::to_string(&Envelope::<String>::Err {
+            error: failure.clone(),
+        })
+        .unwrap();
+        assert_eq!(
+            ToolFailure::from_result("read_file", &result),
+            Some(failure)
+        );
+
+        // Output that merely mentions errors isn't a failure
+        let output = serde_json::to_string(&Envelope::Ok("ToolCallError: in a log")).unwrap();
+        assert_eq!(ToolFailure::from_result("read_log", &output), None);
+        let output = serde_json::to_string(&Envelope::Ok(serde_json::json!({"error": 1}))).unwrap();
+        assert_eq!(ToolFailure::from_result("custom", &output), None);
+
+        assert_eq!(
+            ToolFailure::from_result("grep", "ToolNotFoundError: grep").map(|f| f.kind),
+            Some(ErrorKind::UnknownTool)
+        );
+    }
+
+    #[test]
+    fn test_kind() {
+        #[derive(Debug, thiserror::Error)]
+        #[error("Failed to read: {0}")]
+        struct ReadError(#[from] std::io::Error);
+
+        let error = ReadError(std::io::Error::from(std::io::ErrorKind::NotFound));
+        assert_eq!(ErrorKind::of(&error), ErrorKind::NotFound);
+        let error = ReadError(std::io::Error::other("disk on fire"));
+        assert_eq!(ErrorKind::of(&error), ErrorKind::Failed);
+    }
+}
@@ -0,0 +1,267 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+use std::time::SystemTime;
+
+use anyhow::{Context, Result, ensure};
+use sha2::{Digest, Sha256};
+
+use crate::agent::remote;
+
+/// Exit code used when the workspace changed during the session
+pub const EXIT_WORKSPACE_CHANGED: u8 = 3;
+
+/// Lists the files watched: hidden and ignored ones too, such as `.env`
+/// and build output, without git's and horse's own state
+const LIST_ARGS: &[&str] = &[
+    "--files",
+    "--hidden",
+    "--no-ignore",
+    "--glob",
+    "!.git",
+    "--glob",
+    "!.horse",
+];
+
+/// The workspace snapshots of this process, set by `--paranoid`
+static GUARD: OnceLock<Guard> = OnceLock::new();
+
+/// A file as it was when last looked at
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct FileState {
+    modified: Option<SystemTime>,
+    len: u64,
+    hash: String,
+}
+
+/// The files of the workspace by path
+type Snapshot = BTreeMap<String, FileState>;
+
+#[derive(Debug)]
+struct Guard {
+    base_dir: PathBuf,
+    /// The workspace at the start of the session
+    start: Snapshot,
+    /// The workspace at the last check, so each change is reported once
+    last: Mutex<Snapshot>,
+}
+
+/// Files that differ between two snapshots
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Changes {
+    pub modified: Vec<String>,
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+}
+
+impl Changes {
+    fn between(before: &Snapshot, after: &Snapshot) -> Self {
+        Self {
+            modified: after
+                .iter()
+                .filter(|(path, state)| {
+                    before
+                        .get(*path)
+                        .is_some_and(|previous| previous.hash != state.hash)
+                })
+                .map(|(path, _)| path.clone())
+                .collect(),
+            added: after
+                .keys()
+                .filter(|path| !before.contains_key(*path))
+                .cloned()
+                .collect(),
+            removed: before
+                .keys()
+                .filter(|path| !after.contains_key(*path))
+                .cloned()
+                .collect(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.modified.is_empty() && self.added.is_empty() && self.removed.is_empty()
+    }
+}
+
+impl fmt::Display for Changes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let groups = [
+            ("modified", &self.modified),
+            ("added", &self.added),
+            ("removed", &self.removed),
+        ];
+        let parts: Vec<String> = groups
+            .iter()
+            .filter(|(_, paths)| !paths.is_empty())
+            .map(|(label, paths)| format!("{label}: {}", paths.join(", ")))
+            .collect();
+        write!(f, "{}", parts.join("; "))
+    }
+}
+
+/// Snapshot the workspace, to prove at exit and after every tool call that
+/// nothing in it changed. Remote workspaces can't be watched.
+pub async fn init(base_dir: &Path) -> Result<()> {
+    ensure!(
+        remote::get().is_none(),
+        "--paranoid needs a local workspace"
+    );
+    let start = snapshot(base_dir, &Snapshot::new()).await?;
+    tracing::info!(
+        ">> Paranoid mode: watching {} files for changes",
+        start.len()
+    );
+    let _ = GUARD.set(Guard {
+        base_dir: base_dir.to_path_buf(),
+        last: Mutex::new(start.clone()),
+        start,
+    });
+    Ok(())
+}
+
+/// Check the workspace for changes since the last check, alerting about
+/// any. `after` names what ran before, e.g. a tool call and its arguments.
+pub async fn check(after: &str) {
+    if let Some(guard) = GUARD.get() {
+        let previous = guard
+            .last
+            .lock()
+            .map(|last| last.clone())
+            .unwrap_or_default();
+        match snapshot(&guard.base_dir, &previous).await {
+            Ok(current) => {
+                let changes = Changes::between(&previous, &current);
+                if !changes.is_empty() {
+                    tracing::error!(
+                        "[!] PARANOID: The workspace changed after `{after}`: {changes}"
+                    );
+                }
+                if let Ok(mut last) = guard.last.lock() {
+                    *last = current;
+                }
+            }
+            Err(e) => tracing::error!("[!] PARANOID: Could not check the workspace: {e:#}"),
+        }
+    }
+}
+
+/// Compare the workspace with its snapshot from the start of the session
+/// and report the outcome.
+///
+/// Returns whether the workspace is unchanged, or wasn't watched.
+pub async fn verify() -> bool {
+    match GUARD.get() {
+        Some(guard) => {
+            let previous = guard
+                .last
+                .lock()
+                .map(|last| last.clone())
+                .unwrap_or_default();
+            match snapshot(&guard.base_dir, &previous).await {
+                Ok(current) => {
+                    let changes = Changes::between(&guard.start, &current);
+                    if changes.is_empty() {
+                        tracing::info!(
+                            ">> Paranoid mode: verified that none of the {} files changed",
+                            guard.start.len()
+                        );
+                    } else {
+                        tracing::error!(
+                            "[!] PARANOID: The workspace changed during the session: {changes}"
+                        );
+                    }
+                    changes.is_empty()
+                }
+                Err(e) => {
+                    tracing::error!("[!] PARANOID: Could not verify the workspace: {e:#}");
+                    false
+                }
+            }
+        }
+        None => true,
+    }
+}
+
+/// The files `rg` lists. Hashes of `previous` are reused for files whose modification
+/// time and size are the same.
+async fn snapshot(base_dir: &Path, previous: &Snapshot) -> Result<Snapshot> {
+    let output = remote::command(base_dir, "rg", LIST_ARGS)
+        .output()
+        .await
+        .context("Failed to list the workspace's files with rg")?;
+    let listing = String::from_utf8_lossy(&output.stdout);
+    Ok(hash_files(base_dir, listing.lines(), previous).await)
+}
+
+async fn hash_files<'a>(
+    base_dir: &Path,
+    files: impl Iterator<Item = &'a str>,
+    previous: &Snapshot,
+) -> Snapshot {
+    let mut snapshot = Snapshot::new();
+    for file in files.map(|file| file.trim_start_matches("./")) {
+        let path = base_dir.join(file);
+        // A file removed while listing is left out, as removed
+        if let Ok(metadata) = tokio::fs::metadata(&path).await {
+            let (modified, len) = (metadata.modified().ok(), metadata.len());
+            let hash = match previous.get(file) {
+                Some(state) if state.modified == modified && state.len == len => state.hash.clone(),
+                _ => {
+                    let content = tokio::fs::read(&path).await.unwrap_or_default();
+                    format!("{:x}", Sha256::digest(&content))
+                }
+            };
+            snapshot.insert(
+                file.to_string(),
+                FileState {
+                    modified,
+                    len,
+                    hash,
+                },
+            );
+        }
+    }
+    snapshot
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_changes() {
+        let base_dir =
+            std::env::temp_dir().join(format!("horse-paranoid-test-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&base_dir).unwrap();
+        std::fs::write(base_dir.join("kept.rs"), "fn kept() {}").unwrap();
+        std::fs::write(base_dir.join("edited.rs"), "fn edited() {}").unwrap();
+        std::fs::write(base_dir.join("removed.rs"), "").unwrap();
+
+        let files = ["kept.rs", "edited.rs", "removed.rs"];
+        let before = hash_files(&base_dir, files.into_iter(), &Snapshot::new()).await;
+        std::fs::write(base_dir.join("edited.rs"), "fn edited() { todo!() }").unwrap();
+        std::fs::remove_file(base_dir.join("removed.rs")).unwrap();
+        std::fs::write(base_dir.join("added.rs"), "").unwrap();
+
+        let files = ["kept.rs", "edited.rs", "removed.rs", "added.rs"];
+        let after = hash_files(&base_dir, files.into_iter(), &before).await;
+        let changes = Changes::between(&before, &after);
+        assert_eq!(
+            changes,
+            Changes {
+                modified: vec!["edited.rs".to_string()],
+                added: vec!["added.rs".to_string()],
+                removed: vec!["removed.rs".to_string()],
+            }
+        );
+        assert_eq!(
+            changes.to_string(),
+            "modified: edited.rs; added: added.rs; removed: removed.rs"
+        );
+        assert!(Changes::between(&after, &after).is_empty());
+
+        std::fs::remove_dir_all(&base_dir).unwrap();
+    }
+}
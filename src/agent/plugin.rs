@@ -0,0 +1,152 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::Arc;
+
+use rig::completion::ToolDefinition;
+use rig::tool::Tool;
+use serde::Deserialize;
+use serde_json::{Value, json};
+use thiserror::Error;
+
+use crate::agent::mcp;
+use crate::agent::rpc::{RpcClient, RpcError};
+
+/// A plugin declared in config under `[plugin.<name>]`
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PluginConfig {
+    /// Executable that speaks the plugin protocol over stdio
+    pub command: String,
+    pub args: Vec<String>,
+    /// Extra environment variables for the plugin process
+    pub env: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Error)]
+pub enum PluginError {
+    #[error("Plugin: {0}")]
+    Rpc(#[from] RpcError),
+    #[error("Plugin returned an invalid response: {0}")]
+    InvalidResponse(String),
+}
+
+/// A tool as described by a plugin's `describe` response (or a WASM module's)
+#[derive(Debug, Clone, Deserialize)]
+pub(crate) struct PluginToolInfo {
+    pub(crate) name: String,
+    #[serde(default)]
+    pub(crate) description: String,
+    /// JSON schema of the arguments
+    #[serde(default = "empty_schema")]
+    pub(crate) parameters: Value,
+}
+
+fn empty_schema() -> Value {
+    json!({ "type": "object", "properties": {} })
+}
+
+/// Ask a plugin for its tools
+async fn describe(rpc: &RpcClient) -> Result<Vec<PluginToolInfo>, PluginError> {
+    let result = rpc.request("describe", json!({})).await?;
+    serde_json::from_value(result["tools"].clone())
+        .map_err(|e| PluginError::InvalidResponse(e.to_string()))
+}
+
+/// Text of a `call` result: its `output`, or the whole result if there is none
+fn output_text(result: &Value) -> String {
+    match &result["output"] {
+        Value::String(output) => output.clone(),
+        Value::Null => result.to_string(),
+        output => output.to_string(),
+    }
+}
+
+/// A tool provided by a plugin process, registered on the agent like a
+/// native tool
+#[derive(Clone)]
+pub struct PluginTool {
+    rpc: Arc<RpcClient>,
+    name: String,
+    info: PluginToolInfo,
+}
+
+impl Tool for PluginTool {
+    /// Placeholder; the registered name comes from `name()`
+    const NAME: &'static str = "plugin";
+
+    type Error = PluginError;
+    type Args = Value;
+    type Output = String;
+
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    async fn definition(&self, _prompt: String) -> ToolDefinition {
+        ToolDefinition {
+            name: self.name.clone(),
+            description: self.info.description.clone(),
+            parameters: self.info.parameters.clone(),
+        }
+    }
+
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+        let result = self
+            .rpc
+            .request("call", json!({ "name": self.info.name, "arguments": args }))
+            .await?;
+        Ok(output_text(&result))
+    }
+}
+
+/// Start the configured plugins and collect their tools.
+///
+/// Plugins that fail to start or describe themselves are reported and skipped.
+pub async fn load_tools(
+    plugins: &BTreeMap<String, PluginConfig>,
+    base_dir: &Path,
+) -> Vec<PluginTool> {
+    let mut tools = Vec::new();
+
+    for (plugin, config) in plugins {
+        let started = match RpcClient::spawn(&config.command, &config.args, &config.env, base_dir) {
+            Ok(rpc) => describe(&rpc).await.map(|infos| (rpc, infos)),
+            Err(e) => Err(e.into()),
+        };
+
+        match started {
+            Ok((rpc, infos)) => {
+                tracing::info!(">> Plugin {plugin}: {} tools", infos.len());
+                let rpc = Arc::new(rpc);
+                tools.extend(infos.into_iter().map(|info| PluginTool {
+                    rpc: rpc.clone(),
+                    name: mcp::qualified_name(plugin, &info.name),
+                    info,
+                }));
+            }
+            Err(e) => tracing::warn!("[!] Warning: Plugin {plugin} unavailable: {e}"),
+        }
+    }
+
+    tools
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_output_text() {
+        assert_eq!(output_text(&json!({ "output": "3 hits" })), "3 hits");
+        assert_eq!(output_text(&json!({ "output": [1, 2] })), "[1,2]");
+        assert_eq!(output_text(&json!({ "hits": 3 })), r#"{"hits":3}"#);
+    }
+
+    #[test]
+    fn test_describe_defaults() {
+        let infos: Vec<PluginToolInfo> =
+            serde_json::from_value(json!([{ "name": "search" }])).unwrap();
+        assert_eq!(infos[0].parameters, empty_schema());
+        assert_eq!(infos[0].description, "");
+    }
+}
@@ -0,0 +1,273 @@
+use std::str::FromStr;
+use std::sync::OnceLock;
+
+use anyhow::{Result, anyhow};
+use glob::{MatchOptions, Pattern};
+use serde::Deserialize;
+
+/// Rules applied after the configured ones: the native tools are read-only
+/// and confined to the workspace, except for files that usually hold secrets.
+/// Searches skip those files on their own, but not when given one as `path`.
+const BUILTIN_RULES: &[&str] = &[
+    "read_file:path=**/.env*: deny",
+    "read_file: allow",
+    "read_log:path=**/.env*: deny",
+    "read_log: allow",
+    "search_docs:path=**/.env*: deny",
+    "search_docs: allow",
+    "deep_search:path=**/.env*: deny",
+    "deep_search: allow",
+    "code_metrics:path=**/.env*: deny",
+    "code_metrics: allow",
+    "config_inventory:path=**/.env*: deny",
+    "config_inventory: allow",
+    "find_duplicates:path=**/.env*: deny",
+    "find_duplicates: allow",
+    "find_todos:path=**/.env*: deny",
+    "find_todos: allow",
+    "git_history:path=**/.env*: deny",
+    "git_history: allow",
+    "license_scan:path=**/.env*: deny",
+    "license_scan: allow",
+    "list_endpoints:path=**/.env*: deny",
+    "list_endpoints: allow",
+    "module_graph:path=**/.env*: deny",
+    "module_graph: allow",
+    // Reads any file, `.env` ones included
+    "bash: ask",
+    "spawn_agent: allow",
+    "forge: allow",
+    "ticket: allow",
+    "list_objects: allow",
+    "read_object: allow",
+];
+
+/// `*` and `?` don't cross directory separators, `**` does
+const MATCH_OPTIONS: MatchOptions = MatchOptions {
+    case_sensitive: true,
+    require_literal_separator: true,
+    require_literal_leading_dot: false,
+};
+
+/// The policy of this process, set from config at startup
+static POLICY: OnceLock<Policy> = OnceLock::new();
+
+/// What happens to a tool call
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Action {
+    Allow,
+    /// Ask the user in the REPL; denied where nobody can be asked
+    Ask,
+    Deny,
+}
+
+impl FromStr for Action {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "allow" => Ok(Action::Allow),
+            "ask" => Ok(Action::Ask),
+            "deny" => Ok(Action::Deny),
+            other => Err(format!(
+                "unknown action `{other}`, expected allow, ask or deny"
+            )),
+        }
+    }
+}
+
+/// `[policy]` config section
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PolicyConfig {
+    /// Action for tool calls no rule matches, `ask` unless set
+    pub default: Option<Action>,
+    /// Rules such as `bash: ask` or `read_file:path=secrets/**: deny`; the
+    /// first matching rule wins
+    pub rules: Vec<String>,
+}
+
+/// `<tool>[:<arg>=<glob>]: <action>`, where `<tool>` may be a glob as well
+#[derive(Debug)]
+struct Rule {
+    tool: Pattern,
+    /// Argument name and the pattern its value must match
+    condition: Option<(String, Pattern)>,
+    action: Action,
+}
+
+impl FromStr for Rule {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (matcher, action) = s
+            .rsplit_once(':')
+            .ok_or_else(|| format!("expected `<tool>[:<arg>=<glob>]: <action>`, got `{s}`"))?;
+        let (tool, condition) = match matcher.split_once(':') {
+            Some((tool, condition)) => {
+                let (arg, pattern) = condition
+                    .split_once('=')
+                    .ok_or_else(|| format!("expected `<arg>=<glob>`, got `{condition}`"))?;
+                (tool, Some((arg.trim().to_string(), glob(pattern)?)))
+            }
+            None => (matcher, None),
+        };
+
+        Ok(Self {
+            tool: glob(tool)?,
+            condition,
+            action: action.trim().parse()?,
+        })
+    }
+}
+
+fn glob(pattern: &str) -> Result<Pattern, String> {
+    Pattern::new(pattern.trim()).map_err(|e| format!("invalid pattern `{pattern}`: {e}"))
+}
+
+impl Rule {
+    fn matches(&self, tool: &str, args: &serde_json::Value) -> bool {
+        self.tool.matches_with(tool, MATCH_OPTIONS)
+            && self.condition.as_ref().is_none_or(|(arg, pattern)| {
+                let value = match &args[arg.as_str()] {
+                    serde_json::Value::Null => None,
+                    serde_json::Value::String(value) => Some(value.clone()),
+                    value => Some(value.to_string()),
+                };
+                value.is_some_and(|value| pattern.matches_with(&value, MATCH_OPTIONS))
+            })
+    }
+}
+
+/// Decides whether a tool call may run
+#[derive(Debug)]
+pub struct Policy {
+    rules: Vec<Rule>,
+    default: Action,
+}
+
+impl Policy {
+    pub fn from_config(config: &PolicyConfig) -> Result<Self> {
+        let configured = config
+            .rules
+            .iter()
+            .map(|rule| {
+                rule.parse()
+                    .map_err(|e| anyhow!("Invalid policy rule: {e}"))
+            })
+            .collect::<Result<Vec<Rule>>>()?;
+        let builtin = BUILTIN_RULES.iter().filter_map(|rule| rule.parse().ok());
+
+        Ok(Self {
+            rules: configured.into_iter().chain(builtin).collect(),
+            default: config.default.unwrap_or(Action::Ask),
+        })
+    }
+
+    /// Action of the first rule matching the call, or the default
+    pub fn decide(&self, tool: &str, args: &str) -> Action {
+        let args = serde_json::from_str(args).unwrap_or_default();
+        self.rules
+            .iter()
+            .find(|rule| rule.matches(tool, &args))
+            .map_or(self.default, |rule| rule.action)
+    }
+}
+
+/// Apply the configured policy to all tool calls of this process
+pub fn init(config: &PolicyConfig) -> Result<()> {
+    let policy = Policy::from_config(config)?;
+    let _ = POLICY.set(policy);
+    Ok(())
+}
+
+/// Decide a tool call with the policy set by `init`, or the built-in rules
+pub fn decide(tool: &str, args: &str) -> Action {
+    POLICY
+        .get_or_init(|| Policy {
+            rules: BUILTIN_RULES
+                .iter()
+                .filter_map(|rule| rule.parse().ok())
+                .collect(),
+            default: Action::Ask,
+        })
+        .decide(tool, args)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decide() {
+        let policy = Policy::from_config(&PolicyConfig {
+            default: None,
+            rules: vec![
+                "read_file:path=secrets/**: deny".to_string(),
+                "bash: ask".to_string(),
+                "postgres__*: allow".to_string(),
+            ],
+        })
+        .unwrap();
+
+        let read = |path: &str| policy.decide("read_file", &format!(r#"{{"path":"{path}"}}"#));
+        assert_eq!(read("secrets/prod/key.pem"), Action::Deny);
+        assert_eq!(read("config/.env.local"), Action::Deny);
+        assert_eq!(read("src/secrets.rs"), Action::Allow);
+        assert_eq!(policy.decide("bash", r#"{"command":"ls"}"#), Action::Ask);
+        assert_eq!(policy.decide("postgres__query", "{}"), Action::Allow);
+        assert_eq!(policy.decide("github__create_issue", "{}"), Action::Ask);
+    }
+
+    #[test]
+    fn test_builtin_rules() {
+        let policy = Policy::from_config(&PolicyConfig::default()).unwrap();
+        assert_eq!(
+            policy.decide("read_file", r#"{"path":"src/main.rs"}"#),
+            Action::Allow
+        );
+        assert_eq!(
+            policy.decide("read_file", r#"{"path":".env"}"#),
+            Action::Deny
+        );
+        assert_eq!(
+            policy.decide("bash", r#"{"command":"cat .env"}"#),
+            Action::Ask
+        );
+
+        for tool in [
+            "read_log",
+            "deep_search",
+            "find_todos",
+            "config_inventory",
+            "search_docs",
+        ] {
+            assert_eq!(
+                policy.decide(tool, r#"{"path":"config/.env.local","query":"KEY"}"#),
+                Action::Deny,
+                "{tool}"
+            );
+            assert_eq!(
+                policy.decide(tool, r#"{"path":"logs","query":"KEY"}"#),
+                Action::Allow,
+                "{tool}"
+            );
+        }
+        assert_eq!(
+            policy.decide("deep_search", r#"{"query":"API_KEY"}"#),
+            Action::Allow
+        );
+    }
+
+    #[test]
+    fn test_invalid_rules() {
+        for rule in ["bash", "bash: maybe", "read_file:path: deny", "[: allow"] {
+            let config = PolicyConfig {
+                default: None,
+                rules: vec![rule.to_string()],
+            };
+            assert!(Policy::from_config(&config).is_err(), "{rule}");
+        }
+    }
+}
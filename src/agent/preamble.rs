@@ -0,0 +1,89 @@
+use std::path::Path;
+
+use anyhow::{Context, Result};
+
+use crate::agent::{remote, scope};
+use crate::console::i18n;
+
+/// Preamble used when the workspace has no AGENTS.md
+const DEFAULT_PREAMBLE: &str = "You are a helpful search assistant. You can read files and execute safe bash commands \
+    to help users explore and understand their codebase.";
+
+/// Appended to every preamble, so answers can be checked against the code
+const CITATION_INSTRUCTIONS: &str = "\n\n## Citations\n\n\
+    Back every claim about the code with a `path:line` reference, with the path relative \
+    to the working directory, e.g. `src/main.rs:42`. Only cite locations you have read.";
+
+/// Gather directory structure by running `find` command
+async fn gather_directory_context(base_dir: &Path) -> Result<String> {
+    let output = remote::command(base_dir, "find", &[".", "-maxdepth", "3", "-type", "f"])
+        .output()
+        .await
+        .context("Failed to execute find command")?;
+
+    if output.status.success() {
+        let listing =
+            String::from_utf8(output.stdout).context("Failed to parse find output as UTF-8")?;
+        // `find` lists in directory order, which can differ between runs.
+        // Sorted, the preamble is byte-identical across sessions, so its
+        // prompt cache entry keeps being reused.
+        let mut files: Vec<&str> = listing
+            .lines()
+            .filter(|file| scope::allows(Path::new(file)))
+            .collect();
+        files.sort_unstable();
+        Ok(files.join("\n"))
+    } else {
+        Ok("(Directory listing unavailable)".to_string())
+    }
+}
+
+/// Load the AGENTS.md file from the target directory if it exists,
+/// otherwise return a default preamble.
+pub async fn load(base_dir: &Path) -> Result<String> {
+    let instructions = async {
+        match remote::get() {
+            // Whether the file exists is only known by trying to read it
+            Some(_) => Ok(remote::read_to_string(base_dir, "AGENTS.md")
+                .await
+                .inspect(|_| tracing::info!(">> Loaded AGENTS.md"))
+                .unwrap_or_else(|_| DEFAULT_PREAMBLE.to_string())),
+            None if base_dir.join("AGENTS.md").exists() => {
+                tracing::info!(">> Loading AGENTS.md...");
+                remote::read_to_string(base_dir, "AGENTS.md")
+                    .await
+                    .context("Failed to read AGENTS.md")
+            }
+            None => Ok(DEFAULT_PREAMBLE.to_string()),
+        }
+    };
+    let directory_context = async {
+        tracing::info!(">> Gathering directory structure...");
+        gather_directory_context(base_dir).await
+    };
+    // Scanning a large tree takes longest; read AGENTS.md meanwhile
+    let (instructions, directory_context) = tokio::join!(instructions, directory_context);
+
+    let mut preamble = instructions?;
+    preamble.push_str(CITATION_INSTRUCTIONS);
+    if let Some(instructions) = i18n::answer_instructions() {
+        preamble.push_str(&instructions);
+    }
+    if let Some(scope) = scope::describe() {
+        preamble.push_str(&format!("\n\n## Scope\n\n{scope}"));
+    }
+
+    // Add directory context
+    match directory_context {
+        Ok(file_list) => {
+            preamble.push_str("\n\n## Available Files\n\n");
+            preamble.push_str("The following files are available in the working directory:\n\n");
+            preamble.push_str(&file_list);
+        }
+        Err(e) => {
+            tracing::warn!("[!] Warning: Could not gather directory context: {e:#}");
+        }
+    }
+
+    Ok(preamble)
+}
@@ -0,0 +1,111 @@
+use std::sync::OnceLock;
+
+use rig::completion::{Message, Usage};
+
+use crate::agent::models;
+
+/// Rough size of a token in English text and code, for estimates
+pub const BYTES_PER_TOKEN: usize = 4;
+
+/// Share of the context window above which a request is warned about
+const WARN_RATIO: f64 = 0.9;
+
+/// Context window of the model of this process, set at startup
+static CONTEXT_WINDOW: OnceLock<u64> = OnceLock::new();
+
+/// What to do with a request of the estimated size
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Preflight {
+    Send,
+    /// Close to the context window
+    Warn {
+        tokens: u64,
+        window: u64,
+    },
+    /// Larger than the context window; the API would reject it
+    Deny {
+        tokens: u64,
+        window: u64,
+    },
+}
+
+/// Set the model whose context window requests are checked against
+pub fn init(model: &str) {
+    let _ = CONTEXT_WINDOW.set(models::context_window(model));
+}
+
+/// Estimated input tokens of the request that sends `prompt` after
+/// `history`.
+///
+/// Once the conversation has had a response, its real usage (the input of
+/// the last request plus the output it produced) is the base, and only the
+/// new message is estimated. Otherwise the whole conversation is estimated
+/// from its size, which leaves out the preamble and tool definitions.
+pub fn estimate(prompt: &Message, history: &[Message], last_usage: Usage) -> u64 {
+    let size = |message: &Message| serde_json::to_string(message).map_or(0, |json| json.len());
+    let base = last_usage.input_tokens + last_usage.cached_input_tokens + last_usage.output_tokens;
+
+    if history.is_empty() || base == 0 {
+        (history.iter().map(size).sum::<usize>() + size(prompt)) as u64 / BYTES_PER_TOKEN as u64
+    } else {
+        base + (size(prompt) / BYTES_PER_TOKEN) as u64
+    }
+}
+
+/// Check a request of an estimated size against the context window
+pub fn check(tokens: u64) -> Preflight {
+    let window = *CONTEXT_WINDOW.get_or_init(|| models::context_window(""));
+    if tokens > window {
+        Preflight::Deny { tokens, window }
+    } else if tokens as f64 > window as f64 * WARN_RATIO {
+        Preflight::Warn { tokens, window }
+    } else {
+        Preflight::Send
+    }
+}
+
+/// Check the request that sends `prompt` before it goes out, warning when
+/// it is close to the context window.
+///
+/// Returns why it must not be sent if it exceeds the window.
+pub fn gate(prompt: &Message, history: &[Message], last_usage: Usage) -> Result<(), String> {
+    match check(estimate(prompt, history, last_usage)) {
+        Preflight::Send => Ok(()),
+        Preflight::Warn { tokens, window } => {
+            tracing::warn!(
+                "[!] Warning: The next request is about {tokens} tokens, close to the context window of {window}"
+            );
+            Ok(())
+        }
+        Preflight::Deny { tokens, window } => Err(format!(
+            "The next request would be about {tokens} tokens, more than the model's context \
+             window of {window}. Start a new conversation or ask about less at once."
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_estimate() {
+        let prompt = Message::user("x".repeat(4000));
+        assert!((1000..1100).contains(&estimate(&prompt, &[], Usage::default())));
+
+        // With a response in the conversation, its usage is the base
+        let history = [Message::user("question"), Message::assistant("answer")];
+        let last_usage = Usage {
+            input_tokens: 500,
+            output_tokens: 100,
+            total_tokens: 600,
+            cached_input_tokens: 150_000,
+        };
+        let tokens = estimate(&prompt, &history, last_usage);
+        assert!((151_600..151_700).contains(&tokens));
+
+        assert_eq!(check(1000), Preflight::Send);
+        assert!(matches!(check(190_000), Preflight::Warn { .. }));
+        assert!(matches!(check(250_000), Preflight::Deny { .. }));
+    }
+}
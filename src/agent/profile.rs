@@ -0,0 +1,124 @@
+use std::collections::BTreeMap;
+use std::sync::OnceLock;
+
+use anyhow::{Result, bail};
+use rig::agent::AgentBuilder;
+use rig::providers::anthropic;
+use serde::Deserialize;
+use serde_json::{Map, Value, json};
+
+/// The profile of this process, set from config at startup
+static PROFILE: OnceLock<Profile> = OnceLock::new();
+
+/// `[profiles.<name>]` config section: provider parameters sent with the
+/// requests
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Profile {
+    /// Sequences that end the answer when the model generates them
+    #[serde(alias = "stop-sequences")]
+    pub stop_sequences: Vec<String>,
+    /// Sample from the `top_k` most likely tokens only
+    #[serde(alias = "top-k")]
+    pub top_k: Option<u64>,
+    /// Sample from the most likely tokens up to this cumulative probability
+    #[serde(alias = "top-p")]
+    pub top_p: Option<f64>,
+    pub temperature: Option<f64>,
+    /// Opaque id sent as Anthropic's `metadata.user_id`, e.g. to tell the
+    /// API traffic of projects apart for accounting
+    #[serde(alias = "user-id")]
+    pub user_id: Option<String>,
+}
+
+impl Profile {
+    /// Parameters merged into the body of the main agent's requests
+    pub fn params(&self) -> Map<String, Value> {
+        let mut params = self.metadata();
+        if !self.stop_sequences.is_empty() {
+            params.insert("stop_sequences".to_string(), json!(self.stop_sequences));
+        }
+        if let Some(top_k) = self.top_k {
+            params.insert("top_k".to_string(), json!(top_k));
+        }
+        if let Some(top_p) = self.top_p {
+            params.insert("top_p".to_string(), json!(top_p));
+        }
+        params
+    }
+
+    /// The request tags alone, for helper prompts whose sampling is their own
+    fn metadata(&self) -> Map<String, Value> {
+        self.user_id
+            .iter()
+            .map(|user_id| ("metadata".to_string(), json!({ "user_id": user_id })))
+            .collect()
+    }
+}
+
+/// Use the profile `name` of `profiles` for the requests of this process
+pub fn init(profiles: &BTreeMap<String, Profile>, name: Option<&str>) -> Result<()> {
+    match name.map(|name| (name, profiles.get(name))) {
+        Some((_, Some(profile))) => {
+            let _ = PROFILE.set(profile.clone());
+        }
+        Some((name, None)) => bail!(
+            "Unknown profile: {name} (configured: {})",
+            profiles.keys().cloned().collect::<Vec<_>>().join(", ")
+        ),
+        None => {}
+    }
+    Ok(())
+}
+
+/// The profile of this process; the default one sets nothing
+pub fn get() -> &'static Profile {
+    PROFILE.get_or_init(Profile::default)
+}
+
+/// Tag the requests of a helper agent (sub-agents, translation, summaries)
+/// with the profile's metadata, leaving its sampling alone
+pub fn tag(
+    builder: AgentBuilder<anthropic::completion::CompletionModel>,
+) -> AgentBuilder<anthropic::completion::CompletionModel> {
+    let metadata = get().metadata();
+    if metadata.is_empty() {
+        builder
+    } else {
+        builder.additional_params(Value::Object(metadata))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_params() {
+        let profile: Profile = toml::from_str(
+            r#"
+            stop-sequences = ["</answer>"]
+            top-k = 40
+            user-id = "team-payments"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            Value::Object(profile.params()),
+            json!({
+                "metadata": { "user_id": "team-payments" },
+                "stop_sequences": ["</answer>"],
+                "top_k": 40
+            })
+        );
+        assert!(Profile::default().params().is_empty());
+    }
+
+    #[test]
+    fn test_init() {
+        let profiles = BTreeMap::from([("ci".to_string(), Profile::default())]);
+        assert!(init(&profiles, Some("ci")).is_ok());
+        assert!(init(&profiles, Some("prod")).is_err());
+        assert!(init(&profiles, None).is_ok());
+    }
+}
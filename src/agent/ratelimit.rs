@@ -0,0 +1,168 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+use anyhow::{Result, bail};
+use serde::Deserialize;
+
+/// Providers horse sends requests to, as named in `[rate-limit.<provider>]`
+const PROVIDERS: &[&str] = &["anthropic"];
+
+/// The provider of all requests of this process
+const PROVIDER: &str = "anthropic";
+
+/// Period the limits are counted over
+const WINDOW: Duration = Duration::from_secs(60);
+
+/// The limiter of this process, set from config at startup
+static LIMITER: OnceLock<RateLimiter> = OnceLock::new();
+
+/// `[rate-limit.<provider>]` config section
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RateLimitConfig {
+    /// Requests sent per minute, at most
+    #[serde(alias = "requests-per-minute")]
+    pub requests_per_minute: Option<usize>,
+    /// Estimated input tokens sent per minute, at most
+    #[serde(alias = "tokens-per-minute")]
+    pub tokens_per_minute: Option<u64>,
+}
+
+/// Limit the requests of this process to the provider's configured rates
+pub fn init(config: &BTreeMap<String, RateLimitConfig>) -> Result<()> {
+    if let Some(unknown) = config
+        .keys()
+        .find(|name| !PROVIDERS.contains(&name.as_str()))
+    {
+        bail!(
+            "Unknown provider in [rate-limit]: {unknown} (known: {})",
+            PROVIDERS.join(", ")
+        );
+    }
+    if let Some(config) = config.get(PROVIDER) {
+        let _ = LIMITER.set(RateLimiter::new(config.clone()));
+    }
+    Ok(())
+}
+
+/// Wait until a request of `tokens` estimated input tokens may be sent
+/// without exceeding the configured rates, then count it.
+///
+/// While it waits, `on_wait` is called with the time until the next slot
+/// frees up and the number of requests waiting, this one included.
+pub async fn acquire(tokens: u64, on_wait: impl Fn(Duration, usize)) {
+    if let Some(limiter) = LIMITER.get() {
+        limiter.acquire(tokens, on_wait).await;
+    }
+}
+
+/// Requests and their estimated tokens sent in the last minute
+#[derive(Debug)]
+pub struct RateLimiter {
+    config: RateLimitConfig,
+    sent: Mutex<VecDeque<(Instant, u64)>>,
+    waiting: AtomicUsize,
+}
+
+impl RateLimiter {
+    pub fn new(config: RateLimitConfig) -> Self {
+        Self {
+            config,
+            sent: Mutex::new(VecDeque::new()),
+            waiting: AtomicUsize::new(0),
+        }
+    }
+
+    async fn acquire(&self, tokens: u64, on_wait: impl Fn(Duration, usize)) {
+        self.waiting.fetch_add(1, Ordering::Relaxed);
+        while let Some(wait) = self.try_acquire(tokens, Instant::now()) {
+            on_wait(wait, self.waiting.load(Ordering::Relaxed));
+            tokio::time::sleep(wait).await;
+        }
+        self.waiting.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    /// Count a request sent `now` if the rates allow it, otherwise return
+    /// how long until the oldest counted request leaves the window
+    fn try_acquire(&self, tokens: u64, now: Instant) -> Option<Duration> {
+        let mut sent = self.sent.lock().unwrap_or_else(|e| e.into_inner());
+        while sent
+            .front()
+            .is_some_and(|(at, _)| now.duration_since(*at) >= WINDOW)
+        {
+            sent.pop_front();
+        }
+
+        let requests_full = self
+            .config
+            .requests_per_minute
+            .is_some_and(|limit| sent.len() >= limit);
+        // A request larger than the whole budget goes out alone
+        let tokens_full = self.config.tokens_per_minute.is_some_and(|limit| {
+            !sent.is_empty() && sent.iter().map(|(_, t)| t).sum::<u64>() + tokens > limit
+        });
+        if requests_full || tokens_full {
+            sent.front()
+                .map(|(at, _)| (*at + WINDOW).saturating_duration_since(now))
+        } else {
+            sent.push_back((now, tokens));
+            None
+        }
+    }
+}
+
+/// Log a wait for the rate limits, for requests without a spinner to show
+/// it in
+pub fn log_wait(wait: Duration, waiting: usize) {
+    tracing::debug!(
+        "Rate limited, {waiting} requests waiting, next slot in {}s",
+        wait.as_secs()
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_requests_per_minute() {
+        let limiter = RateLimiter::new(RateLimitConfig {
+            requests_per_minute: Some(2),
+            tokens_per_minute: None,
+        });
+        let now = Instant::now();
+        assert_eq!(limiter.try_acquire(10, now), None);
+        assert_eq!(limiter.try_acquire(10, now + Duration::from_secs(20)), None);
+        assert_eq!(
+            limiter.try_acquire(10, now + Duration::from_secs(30)),
+            Some(Duration::from_secs(30))
+        );
+        // The first request has left the window
+        assert_eq!(limiter.try_acquire(10, now + WINDOW), None);
+    }
+
+    #[test]
+    fn test_tokens_per_minute() {
+        let limiter = RateLimiter::new(RateLimitConfig {
+            requests_per_minute: None,
+            tokens_per_minute: Some(1000),
+        });
+        let now = Instant::now();
+        assert_eq!(limiter.try_acquire(5000, now), None);
+        assert_eq!(
+            limiter.try_acquire(100, now + Duration::from_secs(10)),
+            Some(Duration::from_secs(50))
+        );
+        assert_eq!(limiter.try_acquire(900, now + WINDOW), None);
+        assert_eq!(limiter.try_acquire(100, now + WINDOW), None);
+        assert!(limiter.try_acquire(1, now + WINDOW).is_some());
+    }
+
+    #[test]
+    fn test_init() {
+        let config = BTreeMap::from([("openai".to_string(), RateLimitConfig::default())]);
+        assert!(init(&config).is_err());
+    }
+}
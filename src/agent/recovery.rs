@@ -0,0 +1,285 @@
+use std::collections::BTreeSet;
+use std::error::Error;
+use std::path::Path;
+
+use serde_json::Value;
+
+use crate::agent::outcome::ErrorKind;
+use crate::agent::scope;
+use crate::agent::tools::{
+    ALLOWED_COMMANDS, BashCommandError, GitHistoryError, ReadFileError, ReadLogError,
+};
+
+/// Similar paths suggested for one that doesn't exist, at most
+const MAX_SUGGESTIONS: usize = 3;
+
+/// Allowed commands that do the job of common disallowed ones
+const SUBSTITUTES: &[(&str, &str)] = &[
+    ("ack", "rg"),
+    ("ag", "rg"),
+    ("egrep", "grep -E"),
+    ("fgrep", "grep -F"),
+    ("fd", "find"),
+    ("less", "cat"),
+    ("more", "cat"),
+    (
+        "sed",
+        "head or tail, or the read_file tool with a line range",
+    ),
+    ("awk", "rg or grep"),
+];
+
+/// Suggested to a call that timed out
+const NARROW_SCOPE: &str = "Narrow the call: a subdirectory instead of the whole tree, a more \
+    specific pattern or glob, or a line range of a large file.";
+
+/// How the model can recover from a failed call in one step: the closest
+/// valid alternative to what it asked for.
+///
+/// `args` are the call's arguments and `parameters` the tool's JSON schema.
+pub async fn hint(
+    base_dir: &Path,
+    error: &(dyn Error + Send + Sync + 'static),
+    kind: ErrorKind,
+    args: &Value,
+    parameters: &Value,
+) -> Option<String> {
+    match (error.downcast_ref::<BashCommandError>(), kind) {
+        (Some(BashCommandError::CommandNotAllowed(command, _)), _) => Some(command_hint(command)),
+        (Some(BashCommandError::ForbiddenPattern(pattern)), _) => Some(format!(
+            "`{pattern}` isn't allowed. Run a single command, or allowed commands joined with \
+             `|`, and use their own flags (e.g. rg's -g, -t or -l) instead of chaining or \
+             redirecting."
+        )),
+        (_, ErrorKind::Timeout) => Some(NARROW_SCOPE.to_string()),
+        (_, ErrorKind::InvalidArgs) => Some(format!(
+            "Expected arguments: {}.",
+            describe_parameters(parameters)
+        )),
+        (_, ErrorKind::NotFound) => match args.get("path").and_then(Value::as_str) {
+            Some(path) => path_hint(base_dir, path).await,
+            None => None,
+        },
+        (_, ErrorKind::NotAllowed) => Some(not_allowed_hint(error)),
+        _ => None,
+    }
+}
+
+/// How to do without a path a tool refused: one out of the session's scope,
+/// or out of the working directory
+fn not_allowed_hint(error: &(dyn Error + Send + Sync + 'static)) -> String {
+    let out_of_scope = if let Some(BashCommandError::OutsideScope(path)) = error.downcast_ref() {
+        Some(path)
+    } else if let Some(ReadFileError::OutsideScope(path)) = error.downcast_ref() {
+        Some(path)
+    } else if let Some(GitHistoryError::OutsideScope(path)) = error.downcast_ref() {
+        Some(path)
+    } else if let Some(ReadLogError::OutsideScope(path)) = error.downcast_ref() {
+        Some(path)
+    } else {
+        None
+    };
+    match out_of_scope {
+        Some(path) => format!(
+            "`{path}` is excluded by the --include/--exclude scope the user set for this \
+             session. Don't retry it or other paths outside the scope; answer from the files \
+             inside it, or tell the user which files you'd need."
+        ),
+        None => "Use a path relative to the working directory, without `..`; the files \
+                 outside it can't be read."
+            .to_string(),
+    }
+}
+
+/// The allowed command closest to `command`, or the list of them
+fn command_hint(command: &str) -> String {
+    let substitute = SUBSTITUTES
+        .iter()
+        .find(|(name, _)| *name == command)
+        .map(|(_, substitute)| substitute.to_string())
+        .or_else(|| {
+            ALLOWED_COMMANDS
+                .iter()
+                .map(|allowed| (levenshtein(allowed, command), allowed))
+                .min()
+                .filter(|(distance, _)| *distance <= 2)
+                .map(|(_, allowed)| format!("`{allowed}`"))
+        });
+    match substitute {
+        Some(substitute) => format!(
+            "`{command}` isn't allowed; use {substitute} instead. Allowed commands: {}.",
+            ALLOWED_COMMANDS.join(", ")
+        ),
+        None => format!(
+            "`{command}` isn't allowed. Allowed commands: {}.",
+            ALLOWED_COMMANDS.join(", ")
+        ),
+    }
+}
+
+/// `name (type, required), ...` of a JSON schema's properties
+fn describe_parameters(parameters: &Value) -> String {
+    let required: Vec<&str> = parameters["required"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(Value::as_str)
+        .collect();
+    parameters["properties"]
+        .as_object()
+        .into_iter()
+        .flatten()
+        .map(|(name, schema)| {
+            let kind = schema["type"].as_str().unwrap_or("any");
+            if required.contains(&name.as_str()) {
+                format!("{name} ({kind}, required)")
+            } else {
+                format!("{name} ({kind})")
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// The workspace paths closest to `path`, which doesn't exist
+async fn path_hint(base_dir: &Path, path: &str) -> Option<String> {
+    let output = scope::search_command(base_dir, "rg", &["--files"])
+        .output()
+        .await
+        .ok()?;
+    let listing = String::from_utf8_lossy(&output.stdout);
+    // Directories are only listed through their files
+    let paths: BTreeSet<&str> = listing
+        .lines()
+        .flat_map(|file| {
+            std::iter::successors(Some(file), |path| {
+                path.rsplit_once('/').map(|(parent, _)| parent)
+            })
+        })
+        .collect();
+    let similar = similar_paths(path, &paths);
+    (!similar.is_empty()).then(|| {
+        format!(
+            "`{path}` doesn't exist. Did you mean {}?",
+            similar
+                .iter()
+                .map(|path| format!("`{path}`"))
+                .collect::<Vec<_>>()
+                .join(", ")
+        )
+    })
+}
+
+/// Paths whose last component is close to the one of `wanted`, closest
+/// path first
+fn similar_paths<'a>(wanted: &str, paths: &BTreeSet<&'a str>) -> Vec<&'a str> {
+    let wanted = wanted.trim_start_matches("./").trim_end_matches('/');
+    let name = file_name(wanted);
+    let max_distance = name.chars().count() / 3 + 1;
+    let mut scored: Vec<(usize, usize, &str)> = paths
+        .iter()
+        .filter_map(|path| {
+            let distance = levenshtein(file_name(path), name);
+            (distance <= max_distance).then(|| (levenshtein(path, wanted), distance, *path))
+        })
+        .collect();
+    scored.sort_unstable();
+    scored
+        .into_iter()
+        .take(MAX_SUGGESTIONS)
+        .map(|(_, _, path)| path)
+        .collect()
+}
+
+fn file_name(path: &str) -> &str {
+    path.rsplit('/').next().unwrap_or(path)
+}
+
+/// Edit distance between two strings, in characters
+pub(crate) fn levenshtein(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substituted = diagonal + usize::from(ca != *cb);
+            diagonal = row[j + 1];
+            row[j + 1] = substituted.min(row[j] + 1).min(diagonal + 1);
+        }
+    }
+    row[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_levenshtein() {
+        assert_eq!(levenshtein("grep", "grpe"), 2);
+        assert_eq!(levenshtein("", "rg"), 2);
+        assert_eq!(levenshtein("kitten", "sitting"), 3);
+    }
+
+    #[test]
+    fn test_command_hint() {
+        assert!(command_hint("ag").starts_with("`ag` isn't allowed; use rg instead."));
+        assert!(command_hint("gerp").starts_with("`gerp` isn't allowed; use `grep` instead."));
+        assert!(command_hint("python").starts_with("`python` isn't allowed. Allowed commands:"));
+    }
+
+    #[test]
+    fn test_not_allowed_hint() {
+        assert!(
+            not_allowed_hint(&BashCommandError::OutsideScope("vendor".to_string()))
+                .starts_with("`vendor` is excluded by the --include/--exclude scope")
+        );
+        assert!(
+            not_allowed_hint(&ReadLogError::OutsideScope("logs/app.log".to_string()))
+                .starts_with("`logs/app.log` is excluded")
+        );
+        assert!(
+            not_allowed_hint(&ReadFileError::PathTraversal("../etc".to_string()))
+                .contains("without `..`")
+        );
+    }
+
+    #[test]
+    fn test_similar_paths() {
+        let paths = BTreeSet::from([
+            "src",
+            "src/agent",
+            "src/agent/hooks.rs",
+            "src/console/hooks.rs",
+            "src/main.rs",
+            "tests/hook.rs",
+        ]);
+        assert_eq!(
+            similar_paths("src/agent/hook.rs", &paths),
+            [
+                "src/agent/hooks.rs",
+                "tests/hook.rs",
+                "src/console/hooks.rs"
+            ]
+        );
+        assert_eq!(similar_paths("./src/agnet/", &paths), ["src/agent"]);
+        assert!(similar_paths("README.md", &paths).is_empty());
+    }
+
+    #[test]
+    fn test_describe_parameters() {
+        let parameters = serde_json::json!({
+            "type": "object",
+            "properties": {
+                "path": { "type": "string" },
+                "start_line": { "type": "integer" }
+            },
+            "required": ["path"]
+        });
+        assert_eq!(
+            describe_parameters(&parameters),
+            "path (string, required), start_line (integer)"
+        );
+    }
+}
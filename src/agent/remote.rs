@@ -0,0 +1,199 @@
+use std::fmt;
+use std::path::Path;
+use std::sync::OnceLock;
+
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+use tokio::process::Command;
+
+/// The remote workspace of this process, if exploring one over SSH
+static REMOTE: OnceLock<Remote> = OnceLock::new();
+
+/// A directory on a host reached over SSH
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Remote {
+    /// `host` or `user@host`, as passed to `ssh`
+    destination: String,
+    dir: String,
+}
+
+impl Remote {
+    /// Parse a `[user@]host:/path` workspace, as `scp` does. Returns `None`
+    /// for local paths, including ones that merely contain a colon.
+    pub fn parse(spec: &str) -> Option<Self> {
+        match spec.split_once(':') {
+            Some((destination, dir))
+                if !destination.is_empty()
+                    && !dir.is_empty()
+                    && !destination.contains('/')
+                    && !Path::new(spec).exists() =>
+            {
+                Some(Self {
+                    destination: destination.to_string(),
+                    dir: dir.to_string(),
+                })
+            }
+            _ => None,
+        }
+    }
+
+    /// The command line running `program` in the remote directory
+    fn command_line(&self, program: &str, args: &[&str]) -> String {
+        std::iter::once(program)
+            .chain(args.iter().copied())
+            .map(shell_quote)
+            .fold(format!("cd {} &&", shell_quote(&self.dir)), |line, word| {
+                line + " " + &word
+            })
+    }
+}
+
+impl fmt::Display for Remote {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.destination, self.dir)
+    }
+}
+
+/// Quote a value as a single shell word
+pub fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', r"'\''"))
+}
+
+/// Run the tools on a remote workspace from now on
+pub fn init(remote: Remote) {
+    let _ = REMOTE.set(remote);
+}
+
+/// The remote workspace, if any
+pub fn get() -> Option<&'static Remote> {
+    REMOTE.get()
+}
+
+/// A command running `program` with `args` in the workspace: locally in
+/// `base_dir`, or through `ssh` in the remote directory.
+///
+/// `ssh` runs in batch mode, so a host needing a password fails instead of
+/// prompting; keys and host settings come from the user's SSH config.
+pub fn command(base_dir: &Path, program: &str, args: &[&str]) -> Command {
+    match REMOTE.get() {
+        Some(remote) => {
+            let mut command = Command::new("ssh");
+            command
+                .args(["-o", "BatchMode=yes", "--", &remote.destination])
+                .arg(remote.command_line(program, args))
+                .kill_on_drop(true);
+            command
+        }
+        None => {
+            let mut command = Command::new(program);
+            command.args(args).current_dir(base_dir);
+            command
+        }
+    }
+}
+
+/// Read a file of the workspace, given relative to it
+pub async fn read_to_string(base_dir: &Path, path: &str) -> std::io::Result<String> {
+    match REMOTE.get() {
+        Some(_) => {
+            let output = command(base_dir, "cat", &["--", path]).output().await?;
+            if output.status.success() {
+                String::from_utf8(output.stdout).map_err(std::io::Error::other)
+            } else {
+                Err(std::io::Error::other(
+                    String::from_utf8_lossy(&output.stderr).trim().to_string(),
+                ))
+            }
+        }
+        None => tokio::fs::read_to_string(base_dir.join(path)).await,
+    }
+}
+
+/// Size in bytes of a file of the workspace, given relative to it
+pub async fn file_size(base_dir: &Path, path: &str) -> std::io::Result<u64> {
+    match REMOTE.get() {
+        Some(_) => {
+            let output = command(base_dir, "stat", &["-c", "%s", "--", path])
+                .output()
+                .await?;
+            if output.status.success() {
+                String::from_utf8_lossy(&output.stdout)
+                    .trim()
+                    .parse()
+                    .map_err(std::io::Error::other)
+            } else {
+                Err(std::io::Error::other(
+                    String::from_utf8_lossy(&output.stderr).trim().to_string(),
+                ))
+            }
+        }
+        None => Ok(tokio::fs::metadata(base_dir.join(path)).await?.len()),
+    }
+}
+
+/// Read up to `len` bytes of a file of the workspace starting at `offset`,
+/// so large files can be read a part at a time
+pub async fn read_range(
+    base_dir: &Path,
+    path: &str,
+    offset: u64,
+    len: usize,
+) -> std::io::Result<Vec<u8>> {
+    match REMOTE.get() {
+        Some(_) => {
+            let input = format!("if={path}");
+            let skip = format!("skip={offset}");
+            let count = format!("count={len}");
+            let args = [
+                input.as_str(),
+                "iflag=skip_bytes,count_bytes",
+                "bs=65536",
+                "status=none",
+                &skip,
+                &count,
+            ];
+            let output = command(base_dir, "dd", &args).output().await?;
+            if output.status.success() {
+                Ok(output.stdout)
+            } else {
+                Err(std::io::Error::other(
+                    String::from_utf8_lossy(&output.stderr).trim().to_string(),
+                ))
+            }
+        }
+        None => {
+            let mut file = tokio::fs::File::open(base_dir.join(path)).await?;
+            file.seek(std::io::SeekFrom::Start(offset)).await?;
+            let mut buffer = Vec::with_capacity(len);
+            file.take(len as u64).read_to_end(&mut buffer).await?;
+            Ok(buffer)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let remote = Remote::parse("deploy@app-1:/srv/app").unwrap();
+        assert_eq!(remote.destination, "deploy@app-1");
+        assert_eq!(remote.dir, "/srv/app");
+        assert_eq!(remote.to_string(), "deploy@app-1:/srv/app");
+        assert_eq!(Remote::parse("app-1:src").unwrap().dir, "src");
+
+        assert_eq!(Remote::parse("."), None);
+        assert_eq!(Remote::parse("/srv/app"), None);
+        assert_eq!(Remote::parse("./a:b"), None);
+        assert_eq!(Remote::parse("app-1:"), None);
+    }
+
+    #[test]
+    fn test_command_line() {
+        let remote = Remote::parse("app-1:/srv/my app").unwrap();
+        assert_eq!(
+            remote.command_line("sh", &["-c", "grep -rn 'fn main' src | head"]),
+            r"cd '/srv/my app' && 'sh' '-c' 'grep -rn '\''fn main'\'' src | head'"
+        );
+    }
+}
@@ -0,0 +1,128 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::process::Stdio;
+use std::time::Duration;
+
+use serde_json::{Value, json};
+use thiserror::Error;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader, Lines};
+use tokio::process::{Child, ChildStdin, ChildStdout, Command};
+use tokio::sync::Mutex;
+use tokio::time::timeout;
+
+/// How long to wait for a single response from the process
+const REQUEST_TIMEOUT_SECS: u64 = 60;
+
+#[derive(Debug, Error)]
+pub enum RpcError {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Process did not respond within {0} seconds")]
+    Timeout(u64),
+    #[error("Process closed the connection")]
+    Closed,
+    #[error("Process returned an error: {0}")]
+    Remote(String),
+}
+
+struct Connection {
+    stdin: ChildStdin,
+    stdout: Lines<BufReader<ChildStdout>>,
+    next_id: u64,
+    /// Kept so the process is killed when the client goes away
+    _child: Child,
+}
+
+/// A JSON-RPC 2.0 connection to a child process over stdio, one message per
+/// line. Used for MCP servers and plugins.
+pub struct RpcClient {
+    connection: Mutex<Connection>,
+}
+
+impl RpcClient {
+    /// Spawn the process in `base_dir`; its stderr is discarded
+    pub fn spawn(
+        command: &str,
+        args: &[String],
+        env: &BTreeMap<String, String>,
+        base_dir: &Path,
+    ) -> Result<Self, RpcError> {
+        let mut child = Command::new(command)
+            .args(args)
+            .envs(env)
+            .current_dir(base_dir)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .kill_on_drop(true)
+            .spawn()?;
+
+        let stdin = child.stdin.take().ok_or(RpcError::Closed)?;
+        let stdout = child.stdout.take().ok_or(RpcError::Closed)?;
+        Ok(Self {
+            connection: Mutex::new(Connection {
+                stdin,
+                stdout: BufReader::new(stdout).lines(),
+                next_id: 0,
+                _child: child,
+            }),
+        })
+    }
+
+    async fn send(connection: &mut Connection, message: &Value) -> Result<(), RpcError> {
+        let mut line = message.to_string();
+        line.push('\n');
+        connection.stdin.write_all(line.as_bytes()).await?;
+        connection.stdin.flush().await?;
+        Ok(())
+    }
+
+    pub async fn notify(&self, method: &str) -> Result<(), RpcError> {
+        let mut connection = self.connection.lock().await;
+        Self::send(
+            &mut connection,
+            &json!({ "jsonrpc": "2.0", "method": method }),
+        )
+        .await
+    }
+
+    /// Send a request and wait for its response, skipping unrelated messages
+    pub async fn request(&self, method: &str, params: Value) -> Result<Value, RpcError> {
+        let mut connection = self.connection.lock().await;
+        connection.next_id += 1;
+        let id = connection.next_id;
+
+        Self::send(
+            &mut connection,
+            &json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params }),
+        )
+        .await?;
+
+        let response = loop {
+            let line = timeout(
+                Duration::from_secs(REQUEST_TIMEOUT_SECS),
+                connection.stdout.next_line(),
+            )
+            .await
+            .map_err(|_| RpcError::Timeout(REQUEST_TIMEOUT_SECS))??
+            .ok_or(RpcError::Closed)?;
+
+            // Notifications, requests from the process and log noise are not ours
+            if let Ok(message) = serde_json::from_str::<Value>(&line)
+                && message["id"] == id
+            {
+                break message;
+            }
+        };
+
+        match response.get("error") {
+            Some(error) => Err(RpcError::Remote(
+                error["message"]
+                    .as_str()
+                    .unwrap_or("unknown error")
+                    .to_string(),
+            )),
+            None => Ok(response["result"].clone()),
+        }
+    }
+}
@@ -0,0 +1,211 @@
+use rig::agent::Agent;
+use rig::completion::{Message, Prompt, PromptError};
+use rig::message::{ToolResultContent, UserContent};
+use rig::providers::anthropic;
+
+use crate::agent::{TOOL_CONCURRENCY, hooks::ProgressHook, timebox};
+
+/// Turns the model gets for the partial answer, one to spare should it
+/// reach for a tool anyway
+const SALVAGE_MAX_TURNS: usize = 1;
+
+const SALVAGE_REQUEST: &str = "You have run out of turns and can't call any more tools. \
+    Summarize what you found so far for the question: what you established, with `path:line` \
+    citations, and what is still open. Say plainly that the answer is incomplete.";
+
+const WRAP_UP_REQUEST: &str = "The time for this investigation is up and you can't call any \
+    more tools. Wrap up: summarize your findings so far, with `path:line` citations, then list \
+    the open questions and what to look at next to answer them.";
+
+const TRIMMED_REQUEST: &str = "At the user's request, the tool outputs of this turn were \
+    trimmed before being sent. Continue the investigation, calling tools with narrower ranges \
+    or patterns where you need more, then give your final answer.";
+
+/// Sent by `/continue` to pick the investigation back up
+pub const CONTINUE_REQUEST: &str =
+    "Continue the investigation where you left off, then give your final answer.";
+
+/// Ask the model for what it found so far, after a query ran out of turns.
+///
+/// `history` is the conversation up to the turn limit, as returned with
+/// `PromptError::MaxTurnsError`; the request and the partial answer are
+/// appended to it, so the investigation can be continued.
+pub async fn partial_answer(
+    agent: &Agent<anthropic::completion::CompletionModel>,
+    history: &mut Vec<Message>,
+    hook: &ProgressHook,
+) -> Result<String, PromptError> {
+    agent
+        .prompt(SALVAGE_REQUEST)
+        .with_history(history)
+        .max_turns(SALVAGE_MAX_TURNS)
+        .with_tool_concurrency(TOOL_CONCURRENCY)
+        .with_hook(hook.clone())
+        .await
+}
+
+/// Ask the model to wrap up with its findings and open questions, after
+/// the time box of `--max-duration` ran out.
+///
+/// `history` is the conversation up to the cancelled request, as returned
+/// with `PromptError::PromptCancelled`.
+pub async fn wrap_up(
+    agent: &Agent<anthropic::completion::CompletionModel>,
+    history: &mut Vec<Message>,
+    hook: &ProgressHook,
+) -> Result<String, PromptError> {
+    let _wrap_up = timebox::WrapUp::start();
+    agent
+        .prompt(WRAP_UP_REQUEST)
+        .with_history(history)
+        .max_turns(SALVAGE_MAX_TURNS)
+        .with_tool_concurrency(TOOL_CONCURRENCY)
+        .with_hook(hook.clone())
+        .await
+}
+
+/// Carry on with a turn whose tool outputs were cut down with
+/// `trim_tool_outputs`, after the user chose to trim them.
+///
+/// `history` is the conversation up to the cancelled request, as returned
+/// with `PromptError::PromptCancelled`.
+pub async fn resume_trimmed(
+    agent: &Agent<anthropic::completion::CompletionModel>,
+    history: &mut Vec<Message>,
+    hook: &ProgressHook,
+) -> Result<String, PromptError> {
+    agent
+        .prompt(TRIMMED_REQUEST)
+        .with_history(history)
+        .with_tool_concurrency(TOOL_CONCURRENCY)
+        .with_hook(hook.clone())
+        .await
+}
+
+/// Cut the tool outputs of `messages` down to `limit_bytes` in all: outputs
+/// under an even share of what's left are kept whole, the others are cut to
+/// that share, noting how much was dropped
+pub fn trim_tool_outputs(messages: &mut [Message], limit_bytes: usize) {
+    let mut outputs: Vec<&mut String> = messages
+        .iter_mut()
+        .filter_map(|message| match message {
+            Message::User { content } => Some(content.iter_mut()),
+            Message::Assistant { .. } => None,
+        })
+        .flatten()
+        .filter_map(|item| match item {
+            UserContent::ToolResult(result) => Some(result.content.iter_mut()),
+            _ => None,
+        })
+        .flatten()
+        .filter_map(|part| match part {
+            ToolResultContent::Text(text) => Some(&mut text.text),
+            ToolResultContent::Image(_) => None,
+        })
+        .collect();
+
+    let mut lengths: Vec<usize> = outputs.iter().map(|output| output.len()).collect();
+    lengths.sort_unstable();
+    let count = lengths.len();
+    let cap = lengths
+        .iter()
+        .enumerate()
+        .scan(limit_bytes, |remaining, (i, &len)| {
+            let share = *remaining / (count - i);
+            *remaining = remaining.saturating_sub(len);
+            Some((len, share))
+        })
+        .find(|(len, share)| len > share)
+        .map(|(_, share)| share);
+
+    if let Some(cap) = cap {
+        for output in outputs.iter_mut().filter(|output| output.len() > cap) {
+            let mut end = cap;
+            while !output.is_char_boundary(end) {
+                end -= 1;
+            }
+            let dropped = output.len() - end;
+            output.truncate(end);
+            output.push_str(&format!(
+                "\n[... {dropped} more bytes trimmed by the user before sending. Call the tool \
+                 again with a narrower range if you need them.]"
+            ));
+        }
+    }
+}
+
+/// The partial answer, marked as such for the reader
+pub fn mark(answer: &str, max_turns: usize) -> String {
+    format!(
+        "> **Partial answer:** the limit of {max_turns} turns was reached before the \
+         investigation finished.\n\n{answer}"
+    )
+}
+
+/// The wrap-up, marked as such for the reader
+pub fn mark_wrap_up(answer: &str, minutes: u64) -> String {
+    format!(
+        "> **Time box over:** the {minutes} minutes of this session ran out, these are the \
+         findings so far.\n\n{answer}"
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use rig::OneOrMany;
+    use rig::message::{Text, ToolResult};
+
+    use super::*;
+
+    fn tool_result(output: &str) -> Message {
+        Message::User {
+            content: OneOrMany::one(UserContent::ToolResult(ToolResult {
+                id: "call_1".to_string(),
+                call_id: None,
+                content: OneOrMany::one(ToolResultContent::Text(Text {
+                    text: output.to_string(),
+                })),
+            })),
+        }
+    }
+
+    fn output(message: &Message) -> String {
+        match message {
+            Message::User { content } => match content.first() {
+                UserContent::ToolResult(result) => match result.content.first() {
+                    ToolResultContent::Text(text) => text.text,
+                    ToolResultContent::Image(_) => String::new(),
+                },
+                _ => String::new(),
+            },
+            Message::Assistant { .. } => String::new(),
+        }
+    }
+
+    #[test]
+    fn test_trim_tool_outputs() {
+        let mut messages = vec![
+            Message::user("Where is the config read?"),
+            tool_result(&"a".repeat(100)),
+            tool_result(&"b".repeat(2000)),
+            tool_result(&"c".repeat(3000)),
+        ];
+        trim_tool_outputs(&mut messages, 1100);
+
+        assert_eq!(output(&messages[1]), "a".repeat(100));
+        assert!(output(&messages[2]).starts_with(&format!("{}\n[... 1500 more", "b".repeat(500))));
+        assert!(output(&messages[3]).starts_with(&format!("{}\n[... 2500 more", "c".repeat(500))));
+
+        // Outputs within the limit are left alone
+        let mut messages = vec![tool_result("short")];
+        trim_tool_outputs(&mut messages, 1100);
+        assert_eq!(output(&messages[0]), "short");
+    }
+
+    #[test]
+    fn test_mark() {
+        let marked = mark("`Config::load` reads `horse.toml`.", 20);
+        assert!(marked.starts_with("> **Partial answer:** the limit of 20 turns"));
+        assert!(marked.ends_with("\n\n`Config::load` reads `horse.toml`."));
+    }
+}
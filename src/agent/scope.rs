@@ -0,0 +1,339 @@
+use std::path::Path;
+use std::sync::OnceLock;
+
+use anyhow::{Result, anyhow};
+use glob::{MatchOptions, Pattern};
+use tokio::process::Command;
+
+use crate::agent::remote;
+
+/// `*` and `?` don't cross directory separators, `**` does, as in `rg --glob`
+const MATCH_OPTIONS: MatchOptions = MatchOptions {
+    case_sensitive: true,
+    require_literal_separator: true,
+    require_literal_leading_dot: false,
+};
+
+/// The scope of this process, set by `--include` and `--exclude`
+static SCOPE: OnceLock<Scope> = OnceLock::new();
+
+/// The paths of the workspace the tools see: those matching an include glob,
+/// if any, and no exclude glob. Globs are relative to the workspace.
+#[derive(Debug, Default)]
+pub struct Scope {
+    include: Vec<Pattern>,
+    exclude: Vec<Pattern>,
+}
+
+impl Scope {
+    pub fn new(include: &[String], exclude: &[String]) -> Result<Self> {
+        let parse = |globs: &[String]| {
+            globs
+                .iter()
+                .map(|glob| {
+                    Pattern::new(glob).map_err(|e| anyhow!("Invalid scope glob `{glob}`: {e}"))
+                })
+                .collect::<Result<Vec<_>>>()
+        };
+        Ok(Self {
+            include: parse(include)?,
+            exclude: parse(exclude)?,
+        })
+    }
+
+    fn is_empty(&self) -> bool {
+        self.include.is_empty() && self.exclude.is_empty()
+    }
+
+    /// Whether the tools may see `path`, given relative to the workspace
+    pub fn allows(&self, path: &Path) -> bool {
+        let path = path.strip_prefix(".").unwrap_or(path);
+        (self.include.is_empty()
+            || self
+                .include
+                .iter()
+                .any(|glob| glob.matches_path_with(path, MATCH_OPTIONS)))
+            && !self
+                .exclude
+                .iter()
+                .any(|glob| glob.matches_path_with(path, MATCH_OPTIONS))
+    }
+
+    /// `--glob` arguments confining `rg` and `rga` to the scope
+    fn rg_args(&self) -> Vec<String> {
+        self.include
+            .iter()
+            .map(|glob| glob.as_str().to_string())
+            .chain(
+                self.exclude
+                    .iter()
+                    .map(|glob| format!("!{}", glob.as_str())),
+            )
+            .flat_map(|glob| ["--glob".to_string(), glob])
+            .collect()
+    }
+
+    /// Pathspecs confining `git` to the scope, or to the part of it under
+    /// `path` when one is given
+    fn git_pathspecs(&self, path: Option<&str>) -> Vec<String> {
+        let included = match path {
+            Some(path) if !self.include.is_empty() => self
+                .include
+                .iter()
+                .flat_map(|glob| narrow(glob, path))
+                .map(|glob| format!(":(glob){glob}"))
+                .collect(),
+            Some(path) => vec![path.to_string()],
+            None => self
+                .include
+                .iter()
+                .map(|glob| format!(":(glob){}", glob.as_str()))
+                .collect(),
+        };
+        included
+            .into_iter()
+            .chain(
+                self.exclude
+                    .iter()
+                    .map(|glob| format!(":(glob,exclude){}", glob.as_str())),
+            )
+            .collect()
+    }
+
+    /// Whether no path under `path`, a file or directory relative to the
+    /// workspace, can match an include glob
+    fn excludes_path(&self, path: &str) -> bool {
+        !self.include.is_empty()
+            && self
+                .include
+                .iter()
+                .all(|glob| narrow(glob, path).is_empty())
+    }
+
+    /// Whether `path`, relative to `base_dir`, is a file outside the scope
+    fn excludes_file(&self, base_dir: &Path, path: &str) -> bool {
+        let path = Path::new(path.trim_start_matches("./"));
+        !path.is_absolute() && base_dir.join(path).is_file() && !self.allows(path)
+    }
+
+    /// The lines of a command's output that don't start with a file outside
+    /// the scope, as `grep -r` and `find` print them
+    fn filter_output(&self, base_dir: &Path, output: &str) -> String {
+        output
+            .lines()
+            .filter(|line| !self.excludes_file(base_dir, line.split(':').next().unwrap_or(line)))
+            .map(|line| format!("{line}\n"))
+            .collect()
+    }
+}
+
+/// Globs matching the paths under `path` that `glob` matches: `src/**/*.rs`
+/// under `src/agent` is `src/agent/**/*.rs`, and `src/agent/**` under `src`
+/// stays as it is. Empty if `glob` matches nothing under `path`.
+fn narrow(glob: &Pattern, path: &str) -> Vec<String> {
+    let path = path.trim_start_matches("./").trim_end_matches('/');
+    let components: Vec<&str> = path
+        .split('/')
+        .filter(|component| !component.is_empty() && *component != ".")
+        .collect();
+    let segments: Vec<&str> = glob.as_str().split('/').collect();
+
+    // Components of `path` matched by the leading segments of the glob; at
+    // a `**` the rest of `path` is matched too
+    let matched = components
+        .iter()
+        .zip(&segments)
+        .take_while(|(component, segment)| {
+            **segment != "**" && Pattern::new(segment).is_ok_and(|s| s.matches(component))
+        })
+        .count();
+    let reaches = matched == components.len() || segments.get(matched) == Some(&"**");
+    let mut narrowed: Vec<String> = reaches
+        .then(|| {
+            components
+                .iter()
+                .map(|component| Pattern::escape(component))
+                .chain(
+                    segments[matched..]
+                        .iter()
+                        .map(|segment| segment.to_string()),
+                )
+                .collect::<Vec<_>>()
+                .join("/")
+        })
+        .into_iter()
+        .collect();
+    // `path` itself matches, e.g. `src/main.rs` for `src/**/*.rs`; with all
+    // under it matching too, as for `src/**`, `path` alone says as much
+    let literal = Pattern::escape(path);
+    if glob.matches_path_with(Path::new(path), MATCH_OPTIONS) {
+        narrowed.retain(|narrowed| *narrowed != format!("{literal}/**") && *narrowed != literal);
+        narrowed.push(literal);
+    }
+    narrowed
+}
+
+/// Confine the tools of this process to the paths matching `include`, if
+/// any, and not `exclude`
+pub fn init(include: &[String], exclude: &[String]) -> Result<()> {
+    let scope = Scope::new(include, exclude)?;
+    let _ = SCOPE.set(scope);
+    Ok(())
+}
+
+fn get() -> Option<&'static Scope> {
+    SCOPE.get().filter(|scope| !scope.is_empty())
+}
+
+/// The scope, if it confines a local workspace's files; a remote one's
+/// can't be looked at locally
+fn get_local() -> Option<&'static Scope> {
+    get().filter(|_| remote::get().is_none())
+}
+
+/// Whether the tools may see `path`, given relative to the workspace
+pub fn allows(path: &Path) -> bool {
+    get().is_none_or(|scope| scope.allows(path))
+}
+
+/// Left out of every search, as files that usually hold secrets. Coming
+/// after the scope's globs, it wins over them.
+const SECRET_FILES_GLOB: &str = "!.env*";
+
+/// A command running the search program `rg` or `rga` with `args` in the
+/// workspace, confined to the scope and never listing or reading `.env*`
+/// files
+pub fn search_command(base_dir: &Path, program: &str, args: &[&str]) -> Command {
+    let globs = get().map(Scope::rg_args).unwrap_or_default();
+    let args: Vec<&str> = globs
+        .iter()
+        .map(String::as_str)
+        .chain(["--glob", SECRET_FILES_GLOB])
+        .chain(args.iter().copied())
+        .collect();
+    remote::command(base_dir, program, &args)
+}
+
+/// Pathspecs confining `git` to the scope, or to `path` within it when one
+/// is given
+pub fn git_pathspecs(path: Option<&str>) -> Vec<String> {
+    match get() {
+        Some(scope) => scope.git_pathspecs(path),
+        None => path.map(str::to_string).into_iter().collect(),
+    }
+}
+
+/// Whether `path`, a file or directory relative to the workspace, lies
+/// wholly outside the include globs
+pub fn excludes_path(path: &str) -> bool {
+    get().is_some_and(|scope| scope.excludes_path(path))
+}
+
+/// Whether `path`, relative to `base_dir`, is a file outside the scope
+pub fn excludes_file(base_dir: &Path, path: &str) -> bool {
+    get_local().is_some_and(|scope| scope.excludes_file(base_dir, path))
+}
+
+/// `output` of a command without the lines naming files outside the scope
+pub fn filter_output(base_dir: &Path, output: String) -> String {
+    match get_local() {
+        Some(scope) => scope.filter_output(base_dir, &output),
+        None => output,
+    }
+}
+
+/// The scope as told to the model, if the tools are confined
+pub fn describe() -> Option<String> {
+    get().map(|scope| {
+        let globs = |patterns: &[Pattern]| {
+            patterns
+                .iter()
+                .map(|glob| format!("`{}`", glob.as_str()))
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        let included = if scope.include.is_empty() {
+            "All paths".to_string()
+        } else {
+            format!("Only paths matching {}", globs(&scope.include))
+        };
+        let excluded = if scope.exclude.is_empty() {
+            String::new()
+        } else {
+            format!(", except those matching {},", globs(&scope.exclude))
+        };
+        format!(
+            "{included}{excluded} are in scope of this session; the tools don't see other \
+             files. Don't look for or cite code outside the scope."
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_allows() {
+        let scope = Scope::new(&["src/**".to_string()], &["src/vendor/**".to_string()]).unwrap();
+        assert!(scope.allows(Path::new("src/main.rs")));
+        assert!(scope.allows(Path::new("./src/agent/tools.rs")));
+        assert!(!scope.allows(Path::new("src/vendor/lib.rs")));
+        assert!(!scope.allows(Path::new("docs/index.md")));
+        assert!(Scope::default().allows(Path::new("docs/index.md")));
+
+        assert_eq!(
+            scope.rg_args(),
+            ["--glob", "src/**", "--glob", "!src/vendor/**"]
+        );
+        assert_eq!(
+            scope.git_pathspecs(None),
+            [":(glob)src/**", ":(glob,exclude)src/vendor/**"]
+        );
+        assert_eq!(
+            scope.git_pathspecs(Some("src/agent")),
+            [":(glob)src/agent", ":(glob,exclude)src/vendor/**"]
+        );
+        assert!(!scope.excludes_path("src/agent"));
+        assert!(!scope.excludes_path("./src/main.rs"));
+        assert!(!scope.excludes_path("."));
+        assert!(scope.excludes_path("docs"));
+        assert!(!Scope::default().excludes_path("docs"));
+        assert_eq!(Scope::default().git_pathspecs(Some("docs")), ["docs"]);
+    }
+
+    #[test]
+    fn test_narrow() {
+        let narrowed = |glob: &str, path: &str| narrow(&Pattern::new(glob).unwrap(), path);
+        assert_eq!(narrowed("src/**", "src/agent"), ["src/agent"]);
+        assert_eq!(narrowed("src/**/*.rs", "src/agent/"), ["src/agent/**/*.rs"]);
+        assert_eq!(narrowed("src/agent/**", "src"), ["src/agent/**"]);
+        assert_eq!(narrowed("src/agent/**", "."), ["src/agent/**"]);
+        assert_eq!(narrowed("src/*/mod.rs", "src/agent"), ["src/agent/mod.rs"]);
+        assert_eq!(
+            narrowed("src/**/*.rs", "src/main.rs"),
+            ["src/main.rs/**/*.rs", "src/main.rs"]
+        );
+        assert!(narrowed("src/**", "docs").is_empty());
+        assert!(narrowed("src/agent/**", "src/console").is_empty());
+        assert!(narrowed("src", "src/agent").is_empty());
+    }
+
+    #[test]
+    fn test_search_command() {
+        let command = search_command(Path::new("."), "rg", &["--files", "--hidden"]);
+        let args: Vec<_> = command.as_std().get_args().collect();
+        assert_eq!(args, ["--glob", "!.env*", "--files", "--hidden"]);
+    }
+
+    #[test]
+    fn test_filter_output() {
+        let base_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+        let scope = Scope::new(&["src/**".to_string()], &[]).unwrap();
+        let output = "src/main.rs:1:use std::io;\nCargo.toml:3:edition = \"2024\"\n./Cargo.toml\nno file here\n";
+        assert_eq!(
+            scope.filter_output(base_dir, output),
+            "src/main.rs:1:use std::io;\nno file here\n"
+        );
+    }
+}
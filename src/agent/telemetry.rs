@@ -0,0 +1,112 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use rig::completion::Usage;
+use tracing::Span;
+use tracing::field::Empty;
+
+use crate::agent::outcome::ToolFailure;
+
+/// Span covering one question and all completion requests and tool calls
+/// made to answer it. Frontends instrument the prompt future with it, so the
+/// spans opened by the hooks become its children.
+pub fn turn_span() -> Span {
+    tracing::info_span!("turn")
+}
+
+/// Opens and closes spans for completion requests and tool calls from the
+/// hook callbacks, which only see the start and end of each as separate calls
+#[derive(Clone, Debug, Default)]
+pub struct SpanTracker {
+    completion: Arc<Mutex<Option<Span>>>,
+    /// Spans of in-flight tool calls, keyed by internal call id
+    tools: Arc<Mutex<HashMap<String, Span>>>,
+}
+
+impl SpanTracker {
+    pub fn completion_started(&self) {
+        let span = tracing::info_span!(
+            "completion",
+            gen_ai.usage.input_tokens = Empty,
+            gen_ai.usage.output_tokens = Empty,
+            gen_ai.usage.cached_input_tokens = Empty,
+        );
+        if let Ok(mut completion) = self.completion.lock() {
+            *completion = Some(span);
+        }
+    }
+
+    /// Record the token counts and close the completion span
+    pub fn completion_finished(&self, usage: Usage) {
+        if let Some(span) = self.completion.lock().ok().and_then(|mut c| c.take()) {
+            span.record("gen_ai.usage.input_tokens", usage.input_tokens);
+            span.record("gen_ai.usage.output_tokens", usage.output_tokens);
+            span.record(
+                "gen_ai.usage.cached_input_tokens",
+                usage.cached_input_tokens,
+            );
+        }
+    }
+
+    pub fn tool_started(&self, internal_call_id: &str, tool_name: &str) {
+        let span = tracing::info_span!(
+            "tool_call",
+            tool.name = tool_name,
+            tool.failed = Empty,
+            tool.error_kind = Empty,
+            tool.output_bytes = Empty,
+            tool.exit_code = Empty,
+        );
+        if let Ok(mut tools) = self.tools.lock() {
+            tools.insert(internal_call_id.to_string(), span);
+        }
+    }
+
+    /// Record the outcome and close the tool call span
+    pub fn tool_finished(
+        &self,
+        internal_call_id: &str,
+        failure: Option<&ToolFailure>,
+        output: &str,
+    ) {
+        if let Some(span) = self
+            .tools
+            .lock()
+            .ok()
+            .and_then(|mut tools| tools.remove(internal_call_id))
+        {
+            span.record("tool.failed", failure.is_some());
+            span.record("tool.output_bytes", output.len());
+            if let Some(failure) = failure {
+                span.record("tool.error_kind", failure.kind.as_str());
+                if let Some(code) = exit_code(&failure.message) {
+                    span.record("tool.exit_code", code);
+                }
+            }
+        }
+    }
+}
+
+/// Exit code reported in a failed bash command's error message
+fn exit_code(output: &str) -> Option<i32> {
+    let (_, rest) = output.split_once("exit code ")?;
+    let end = rest
+        .find(|c: char| !(c.is_ascii_digit() || c == '-'))
+        .unwrap_or(rest.len());
+    rest[..end].parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_exit_code() {
+        assert_eq!(
+            exit_code("Command failed with exit code 2: grep: x: No such file"),
+            Some(2)
+        );
+        assert_eq!(exit_code("Command exited with code 1 (no output)"), None);
+        assert_eq!(exit_code("src/main.rs:1:fn main"), None);
+    }
+}
@@ -0,0 +1,109 @@
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+/// Start of the reason a request is cancelled with once the time is up
+const EXPIRED: &str = "The time box of";
+
+/// The time box of this process, set by `--max-duration`
+static TIMEBOX: OnceLock<Timebox> = OnceLock::new();
+
+#[derive(Debug)]
+struct Timebox {
+    limit: Duration,
+    deadline: Instant,
+    /// Set while the wrap-up runs, the only requests let through after the
+    /// deadline
+    wrapping_up: AtomicBool,
+}
+
+impl Timebox {
+    fn new(limit: Duration) -> Self {
+        Self {
+            limit,
+            deadline: Instant::now() + limit,
+            wrapping_up: AtomicBool::new(false),
+        }
+    }
+
+    fn minutes(&self) -> u64 {
+        self.limit.as_secs().div_ceil(60)
+    }
+
+    fn expired(&self) -> bool {
+        Instant::now() >= self.deadline
+    }
+
+    fn gate(&self) -> Result<(), String> {
+        if self.expired() && !self.wrapping_up.load(Ordering::Relaxed) {
+            Err(format!("{EXPIRED} {} minutes is over", self.minutes()))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Give the session `limit` from now; after it, requests are cancelled
+/// except for the wrap-up
+pub fn init(limit: Duration) {
+    let _ = TIMEBOX.set(Timebox::new(limit));
+}
+
+/// Whether the time box, if any, has run out
+pub fn expired() -> bool {
+    TIMEBOX.get().is_some_and(Timebox::expired)
+}
+
+/// The length of the time box in minutes, if any
+pub fn minutes() -> Option<u64> {
+    TIMEBOX.get().map(Timebox::minutes)
+}
+
+/// Check a request before it goes out.
+///
+/// Returns why it must not be sent once the time box has run out, unless it
+/// is part of the wrap-up.
+pub fn gate() -> Result<(), String> {
+    TIMEBOX.get().map_or(Ok(()), Timebox::gate)
+}
+
+/// Whether a request was cancelled with `reason` because the time was up
+pub fn cancelled_by(reason: &str) -> bool {
+    reason.starts_with(EXPIRED)
+}
+
+/// Lets requests through past the deadline while it lives, for the wrap-up
+pub struct WrapUp(());
+
+impl WrapUp {
+    pub fn start() -> Self {
+        if let Some(timebox) = TIMEBOX.get() {
+            timebox.wrapping_up.store(true, Ordering::Relaxed);
+        }
+        Self(())
+    }
+}
+
+impl Drop for WrapUp {
+    fn drop(&mut self) {
+        if let Some(timebox) = TIMEBOX.get() {
+            timebox.wrapping_up.store(false, Ordering::Relaxed);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_gate() {
+        assert!(Timebox::new(Duration::from_secs(600)).gate().is_ok());
+
+        let timebox = Timebox::new(Duration::ZERO);
+        let reason = timebox.gate().unwrap_err();
+        assert!(cancelled_by(&reason));
+        timebox.wrapping_up.store(true, Ordering::Relaxed);
+        assert!(timebox.gate().is_ok());
+    }
+}
@@ -1,19 +1,71 @@
 #![allow(dead_code, unused_imports)]
 
 mod bash;
+mod cache;
+mod code_metrics;
+mod config_inventory;
+mod custom;
+mod deep_search;
+mod find_duplicates;
+mod find_todos;
+mod forge;
+mod git_history;
+mod injection;
+mod license_scan;
+mod list_endpoints;
+mod module_graph;
+mod object_storage;
+mod ocr;
 mod read_file;
+mod read_log;
 mod search_docs;
+mod spawn_agent;
+mod ticket;
 
-pub use bash::{BashCommand, BashCommandArgs};
-pub use read_file::{ReadFile, ReadFileArgs};
+pub(crate) use bash::ALLOWED_COMMANDS;
+pub use bash::{BashCommand, BashCommandArgs, BashCommandError};
+pub use cache::CACHED_MARKER;
+pub use code_metrics::{CodeMetrics, CodeMetricsArgs};
+pub use config_inventory::{ConfigInventory, ConfigInventoryArgs};
+pub use custom::{CustomTool, CustomToolConfig, load_tools as load_custom_tools};
+pub(crate) use deep_search::DEFINITION_KEYWORDS;
+pub use deep_search::{DeepSearch, DeepSearchArgs};
+pub use find_duplicates::{FindDuplicates, FindDuplicatesArgs};
+pub use find_todos::{FindTodos, FindTodosArgs};
+pub use forge::{DetectedForge, Forge, ForgeArgs, ForgeTool, ForgeType};
+pub use git_history::{GitHistory, GitHistoryArgs, GitHistoryError};
+pub use license_scan::{LicenseScan, LicenseScanArgs};
+pub use list_endpoints::{ListEndpoints, ListEndpointsArgs};
+pub use module_graph::{ModuleGraph, ModuleGraphArgs, last_dot as module_graph_dot};
+pub use object_storage::{ListObjects, ObjectArgs, ObjectStorage, ObjectStorageConfig, ReadObject};
+pub use ocr::{Indexed, OCR_DIR, index as ocr_index};
+pub use read_file::{ReadFile, ReadFileArgs, ReadFileError};
+pub use read_log::{ReadLog, ReadLogArgs, ReadLogError};
 pub use search_docs::{SearchDocs, SearchDocsArgs};
+pub use spawn_agent::{DEFAULT_SUB_AGENT_MODEL, SpawnAgent, SpawnAgentArgs, SpawnAgentConfig};
+pub use ticket::{TicketArgs, TicketTool, TrackerConfig, TrackerKind};
 
 /// Available tool types
 #[derive(Debug, Clone, Copy)]
 pub enum Tools {
     Bash,
+    CodeMetrics,
+    ConfigInventory,
+    DeepSearch,
+    FindDuplicates,
+    FindTodos,
+    Forge,
+    GitHistory,
+    LicenseScan,
+    ListEndpoints,
+    ListObjects,
+    ModuleGraph,
     ReadFile,
+    ReadLog,
+    ReadObject,
     SearchDocs,
+    SpawnAgent,
+    Ticket,
 }
 
 impl TryFrom<&str> for Tools {
@@ -22,8 +74,23 @@ impl TryFrom<&str> for Tools {
     fn try_from(value: &str) -> Result<Self, Self::Error> {
         match value {
             "bash" => Ok(Tools::Bash),
+            "code_metrics" => Ok(Tools::CodeMetrics),
+            "config_inventory" => Ok(Tools::ConfigInventory),
+            "deep_search" => Ok(Tools::DeepSearch),
+            "find_duplicates" => Ok(Tools::FindDuplicates),
+            "find_todos" => Ok(Tools::FindTodos),
+            "forge" => Ok(Tools::Forge),
+            "git_history" => Ok(Tools::GitHistory),
+            "license_scan" => Ok(Tools::LicenseScan),
+            "list_endpoints" => Ok(Tools::ListEndpoints),
+            "list_objects" => Ok(Tools::ListObjects),
+            "module_graph" => Ok(Tools::ModuleGraph),
             "read_file" => Ok(Tools::ReadFile),
+            "read_log" => Ok(Tools::ReadLog),
+            "read_object" => Ok(Tools::ReadObject),
             "search_docs" => Ok(Tools::SearchDocs),
+            "spawn_agent" => Ok(Tools::SpawnAgent),
+            "ticket" => Ok(Tools::Ticket),
             _ => Err(()),
         }
     }
@@ -36,15 +103,73 @@ impl Tools {
             Tools::Bash => serde_json::from_str::<BashCommandArgs>(args)
                 .map(|parsed| parsed.command)
                 .unwrap_or_else(|_| args.to_string()),
+            Tools::CodeMetrics => serde_json::from_str::<CodeMetricsArgs>(args)
+                .map(|parsed| parsed.path.unwrap_or_else(|| ".".to_string()))
+                .unwrap_or_else(|_| args.to_string()),
+            Tools::ConfigInventory => serde_json::from_str::<ConfigInventoryArgs>(args)
+                .map(|parsed| parsed.path.unwrap_or_else(|| ".".to_string()))
+                .unwrap_or_else(|_| args.to_string()),
+            Tools::DeepSearch => serde_json::from_str::<DeepSearchArgs>(args)
+                .map(|parsed| {
+                    let path = parsed.path.as_deref().unwrap_or(".");
+                    format!("{} in {}", parsed.query, path)
+                })
+                .unwrap_or_else(|_| args.to_string()),
+            Tools::FindDuplicates => serde_json::from_str::<FindDuplicatesArgs>(args)
+                .map(|parsed| parsed.path.unwrap_or_else(|| ".".to_string()))
+                .unwrap_or_else(|_| args.to_string()),
+            Tools::FindTodos => serde_json::from_str::<FindTodosArgs>(args)
+                .map(|parsed| parsed.path.unwrap_or_else(|| ".".to_string()))
+                .unwrap_or_else(|_| args.to_string()),
+            Tools::Forge => serde_json::from_str::<ForgeArgs>(args)
+                .map(|parsed| {
+                    let diff = if parsed.diff { " with diff" } else { "" };
+                    format!("#{}{diff}", parsed.number)
+                })
+                .unwrap_or_else(|_| args.to_string()),
+            Tools::GitHistory => serde_json::from_str::<GitHistoryArgs>(args)
+                .map(|parsed| match (parsed.rev, parsed.since) {
+                    (Some(rev), _) => rev,
+                    (None, Some(since)) => format!("since {since}"),
+                    (None, None) => parsed.path.unwrap_or_else(|| ".".to_string()),
+                })
+                .unwrap_or_else(|_| args.to_string()),
+            Tools::LicenseScan => serde_json::from_str::<LicenseScanArgs>(args)
+                .map(|parsed| parsed.path.unwrap_or_else(|| ".".to_string()))
+                .unwrap_or_else(|_| args.to_string()),
+            Tools::ListEndpoints => serde_json::from_str::<ListEndpointsArgs>(args)
+                .map(|parsed| parsed.path.unwrap_or_else(|| ".".to_string()))
+                .unwrap_or_else(|_| args.to_string()),
+            Tools::ListObjects | Tools::ReadObject => serde_json::from_str::<ObjectArgs>(args)
+                .map(|parsed| parsed.uri)
+                .unwrap_or_else(|_| args.to_string()),
+            Tools::ModuleGraph => serde_json::from_str::<ModuleGraphArgs>(args)
+                .map(|parsed| {
+                    let path = parsed.path.as_deref().unwrap_or(".");
+                    match parsed.module {
+                        Some(module) => format!("{module} in {path}"),
+                        None => path.to_string(),
+                    }
+                })
+                .unwrap_or_else(|_| args.to_string()),
             Tools::ReadFile => serde_json::from_str::<ReadFileArgs>(args)
                 .map(|parsed| parsed.path)
                 .unwrap_or_else(|_| args.to_string()),
+            Tools::ReadLog => serde_json::from_str::<ReadLogArgs>(args)
+                .map(|parsed| parsed.path)
+                .unwrap_or_else(|_| args.to_string()),
             Tools::SearchDocs => serde_json::from_str::<SearchDocsArgs>(args)
                 .map(|parsed| {
                     let path = parsed.path.as_deref().unwrap_or(".");
                     format!("{} in {}", parsed.query, path)
                 })
                 .unwrap_or_else(|_| args.to_string()),
+            Tools::SpawnAgent => serde_json::from_str::<SpawnAgentArgs>(args)
+                .map(|parsed| parsed.task)
+                .unwrap_or_else(|_| args.to_string()),
+            Tools::Ticket => serde_json::from_str::<TicketArgs>(args)
+                .map(|parsed| parsed.key)
+                .unwrap_or_else(|_| args.to_string()),
         }
     }
 }
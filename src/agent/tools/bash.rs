@@ -1,4 +1,4 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::process::Stdio;
 use std::time::Duration;
 
@@ -8,12 +8,16 @@ use serde::{Deserialize, Serialize};
 use serde_json::json;
 use thiserror::Error;
 use tokio::io::AsyncReadExt;
-use tokio::process::Command;
+use tokio::process::Child;
 use tokio::time::timeout;
 
+use crate::agent::tools::cache::{self, ResultCache};
+use crate::agent::tools::injection;
+use crate::agent::{remote, scope};
+
 const TIMEOUT_SECS: u64 = 30;
 
-const ALLOWED_COMMANDS: &[&str] = &[
+pub(crate) const ALLOWED_COMMANDS: &[&str] = &[
     "grep", "xargs", "find", "cat", "head", "tail", "ls", "tree", "wc", "file", "rg", "rga",
 ];
 
@@ -40,17 +44,24 @@ pub enum BashCommandError {
     CommandFailed(i32, String),
     #[error("Empty command")]
     EmptyCommand,
+    #[error("{0} is outside the --include/--exclude scope of this session")]
+    OutsideScope(String),
 }
 
 #[derive(Deserialize, Serialize)]
 pub struct BashCommand {
     #[serde(skip)]
     base_dir: PathBuf,
+    #[serde(skip)]
+    cache: ResultCache,
 }
 
 impl BashCommand {
     pub fn new(base_dir: PathBuf) -> Self {
-        Self { base_dir }
+        Self {
+            base_dir,
+            cache: ResultCache::default(),
+        }
     }
 
     fn validate_command(&self, command: &str) -> Result<(), BashCommandError> {
@@ -94,6 +105,19 @@ impl BashCommand {
         }
     }
 
+    /// Refuse commands naming a file outside the `--include`/`--exclude`
+    /// scope, e.g. `cat vendor/lib.rs`
+    fn check_scope(&self, command: &str) -> Result<(), BashCommandError> {
+        match self
+            .parse_args(command)
+            .into_iter()
+            .find(|arg| scope::excludes_file(&self.base_dir, arg))
+        {
+            Some(path) => Err(BashCommandError::OutsideScope(path)),
+            None => Ok(()),
+        }
+    }
+
     /// Split a string by a delimiter while respecting quoted sections
     fn split_respecting_quotes<'a>(&self, s: &'a str, delimiter: char) -> Vec<&'a str> {
         let mut result = Vec::new();
@@ -175,6 +199,32 @@ impl BashCommand {
 
         args
     }
+
+    /// Run a validated command in the workspace
+    async fn run(&self, command: &str) -> Result<String, BashCommandError> {
+        // If command contains pipe or glob patterns, use shell; otherwise execute directly
+        let needs_shell = command.contains('|')
+            || command.contains('*')
+            || command.contains('?')
+            || command.contains('[')
+            || command.contains('{');
+
+        let child = if needs_shell {
+            spawn_shell(&self.base_dir, command)?
+        } else {
+            // Parse command into parts respecting quotes
+            let parts = self.parse_args(command);
+            let (cmd, cmd_args) = parts.split_first().ok_or(BashCommandError::EmptyCommand)?;
+
+            let cmd_args: Vec<&str> = cmd_args.iter().map(String::as_str).collect();
+            remote::command(&self.base_dir, cmd, &cmd_args)
+                .stdout(Stdio::piped())
+                .stderr(Stdio::piped())
+                .spawn()?
+        };
+
+        wait_for_output(child).await
+    }
 }
 
 impl Tool for BashCommand {
@@ -207,82 +257,83 @@ impl Tool for BashCommand {
 
     async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
         self.validate_command(&args.command)?;
+        self.check_scope(&args.command)?;
+
+        let key = cache::normalize(&args.command);
+        match self.cache.get(&key).await {
+            Some(cached) => Ok(cached),
+            None => {
+                let output = scope::filter_output(&self.base_dir, self.run(&args.command).await?);
+                // `xargs` runs commands whose inputs can't be told, so their
+                // results are left uncached
+                let sources = if key.split([' ', '|']).any(|word| word == "xargs") {
+                    Vec::new()
+                } else {
+                    cache::sources(&self.base_dir, &[&key], &output)
+                };
+                self.cache.insert(key, &output, sources).await;
+                Ok(output)
+            }
+        }
+    }
+}
 
-        // If command contains pipe or glob patterns, use shell; otherwise execute directly
-        let needs_shell = args.command.contains('|')
-            || args.command.contains('*')
-            || args.command.contains('?')
-            || args.command.contains('[')
-            || args.command.contains('{');
-
-        let mut child = if needs_shell {
-            Command::new("sh")
-                .arg("-c")
-                .arg(&args.command)
-                .current_dir(&self.base_dir)
-                .stdout(Stdio::piped())
-                .stderr(Stdio::piped())
-                .spawn()?
-        } else {
-            // Parse command into parts respecting quotes
-            let parts = self.parse_args(&args.command);
-            let (cmd, cmd_args) = parts.split_first().ok_or(BashCommandError::EmptyCommand)?;
-
-            Command::new(cmd)
-                .args(cmd_args)
-                .current_dir(&self.base_dir)
-                .stdout(Stdio::piped())
-                .stderr(Stdio::piped())
-                .spawn()?
-        };
+/// Run a command line through `sh -c` in the workspace
+pub(super) fn spawn_shell(base_dir: &Path, command: &str) -> std::io::Result<Child> {
+    remote::command(base_dir, "sh", &["-c", command])
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+}
 
-        let result = timeout(Duration::from_secs(TIMEOUT_SECS), async {
-            let mut stdout = String::new();
-            let mut stderr = String::new();
+/// Collect the output of a command, killing it after the timeout
+pub(super) async fn wait_for_output(mut child: Child) -> Result<String, BashCommandError> {
+    let result = timeout(Duration::from_secs(TIMEOUT_SECS), async {
+        let mut stdout = String::new();
+        let mut stderr = String::new();
 
-            if let Some(ref mut stdout_pipe) = child.stdout {
-                stdout_pipe.read_to_string(&mut stdout).await?;
-            }
-            if let Some(ref mut stderr_pipe) = child.stderr {
-                stderr_pipe.read_to_string(&mut stderr).await?;
-            }
+        if let Some(ref mut stdout_pipe) = child.stdout {
+            stdout_pipe.read_to_string(&mut stdout).await?;
+        }
+        if let Some(ref mut stderr_pipe) = child.stderr {
+            stderr_pipe.read_to_string(&mut stderr).await?;
+        }
 
-            let status = child.wait().await?;
+        let status = child.wait().await?;
 
-            Ok::<_, std::io::Error>((status, stdout, stderr))
-        })
-        .await;
+        Ok::<_, std::io::Error>((status, stdout, stderr))
+    })
+    .await;
 
-        match result {
-            Ok(Ok((status, stdout, stderr))) => {
-                if status.success() {
-                    let mut output = stdout;
-                    if !stderr.is_empty() {
-                        if !output.is_empty() {
-                            output.push_str("\n--- stderr ---\n");
-                        }
-                        output.push_str(&stderr);
+    match result {
+        Ok(Ok((status, stdout, stderr))) => {
+            if status.success() {
+                let mut output = stdout;
+                if !stderr.is_empty() {
+                    if !output.is_empty() {
+                        output.push_str("\n--- stderr ---\n");
                     }
-                    Ok(output)
-                } else {
-                    let exit_code = status.code().unwrap_or(-1);
-                    // Provide both stdout and stderr for better context
-                    let error_output = if !stderr.is_empty() {
-                        stderr
-                    } else if !stdout.is_empty() {
-                        stdout
-                    } else {
-                        format!("Command exited with code {} (no output)", exit_code)
-                    };
-                    Err(BashCommandError::CommandFailed(exit_code, error_output))
+                    output.push_str(&stderr);
                 }
+                Ok(injection::guard(output))
+            } else {
+                let exit_code = status.code().unwrap_or(-1);
+                // Provide both stdout and stderr for better context
+                let error_output = if !stderr.is_empty() {
+                    stderr
+                } else if !stdout.is_empty() {
+                    stdout
+                } else {
+                    format!("Command exited with code {} (no output)", exit_code)
+                };
+                Err(BashCommandError::CommandFailed(exit_code, error_output))
             }
-            Ok(Err(e)) => Err(BashCommandError::Io(e)),
-            Err(_) => {
-                // Timeout - kill the process
-                let _ = child.kill().await;
-                Err(BashCommandError::Timeout(TIMEOUT_SECS))
-            }
+        }
+        Ok(Err(e)) => Err(BashCommandError::Io(e)),
+        Err(_) => {
+            // Timeout - kill the process
+            let _ = child.kill().await;
+            Err(BashCommandError::Timeout(TIMEOUT_SECS))
         }
     }
 }
@@ -0,0 +1,249 @@
+use std::collections::{BTreeSet, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::UNIX_EPOCH;
+
+use sha2::{Digest, Sha256};
+
+use crate::agent::remote;
+
+/// Prefix of a result served from the cache, shown to the model and the user
+pub const CACHED_MARKER: &str = "(cached)";
+
+/// Files hashed to validate a single result, at most
+const MAX_SOURCES: usize = 200;
+
+/// Files listed to validate a result computed from a directory, at most;
+/// results from larger trees aren't cached
+const MAX_LISTED: usize = 20_000;
+
+/// Directories left out of listings: git's and horse's own state, which
+/// changes while the files searched don't
+const UNLISTED_DIRS: &[&str] = &[".git", ".horse"];
+
+/// A result and the files and directories it was computed from
+#[derive(Debug, Clone)]
+struct Entry {
+    output: String,
+    sources: Vec<PathBuf>,
+    fingerprint: String,
+}
+
+/// Results of earlier calls of a tool within the session, keyed by the
+/// normalized arguments.
+///
+/// A result is reused while what it was computed from is unchanged: the
+/// content of the files it was computed from, and the names, sizes and
+/// modification times of the files under the directories it searched, so
+/// that a file added or changed anywhere in them invalidates it. Results
+/// without sources, from trees too large to list, or from a remote
+/// workspace, whose files can't be looked at locally, aren't cached.
+#[derive(Debug, Default)]
+pub(super) struct ResultCache {
+    entries: Mutex<HashMap<String, Entry>>,
+}
+
+impl ResultCache {
+    /// The cached result for `key`, marked as such, if its files are unchanged
+    pub(super) async fn get(&self, key: &str) -> Option<String> {
+        let entry = self
+            .entries
+            .lock()
+            .ok()
+            .and_then(|entries| entries.get(key).cloned())?;
+
+        (fingerprint(&entry.sources).await.as_ref() == Some(&entry.fingerprint))
+            .then(|| format!("{CACHED_MARKER}\n{}", entry.output))
+    }
+
+    pub(super) async fn insert(&self, key: String, output: &str, sources: Vec<PathBuf>) {
+        if !sources.is_empty()
+            && remote::get().is_none()
+            && let Some(fingerprint) = fingerprint(&sources).await
+        {
+            let entry = Entry {
+                output: output.to_string(),
+                fingerprint,
+                sources,
+            };
+            if let Ok(mut entries) = self.entries.lock() {
+                entries.insert(key, entry);
+            }
+        }
+    }
+}
+
+/// Hash over the paths and contents of the files of `sources`, and the
+/// listings of its directories; missing files hash differently from empty
+/// ones. `None` if a directory is too large to list.
+async fn fingerprint(sources: &[PathBuf]) -> Option<String> {
+    let mut hasher = Sha256::new();
+    for path in sources {
+        hasher.update(path.as_os_str().as_encoded_bytes());
+        if path.is_dir() {
+            let dir = path.clone();
+            let listing = tokio::task::spawn_blocking(move || listing(&dir))
+                .await
+                .ok()
+                .flatten()?;
+            hasher.update(b"\x02");
+            for (file, len, modified) in listing {
+                hasher.update(file.as_os_str().as_encoded_bytes());
+                hasher.update(len.to_le_bytes());
+                hasher.update(modified.to_le_bytes());
+            }
+        } else {
+            match tokio::fs::read(path).await {
+                Ok(content) => {
+                    hasher.update(b"\0");
+                    hasher.update(&content);
+                }
+                Err(_) => hasher.update(b"\x01"),
+            }
+        }
+    }
+    Some(format!("{:x}", hasher.finalize()))
+}
+
+/// The files under `dir` with their sizes and modification times (in
+/// nanoseconds since the epoch), sorted; `None` if there are more than
+/// `MAX_LISTED`. Symbolic links aren't followed.
+fn listing(dir: &Path) -> Option<Vec<(PathBuf, u64, u128)>> {
+    let mut files = Vec::new();
+    let mut pending = vec![dir.to_path_buf()];
+    while let Some(dir) = pending.pop()
+        && files.len() <= MAX_LISTED
+    {
+        for entry in fs::read_dir(&dir).into_iter().flatten().flatten() {
+            match entry.metadata() {
+                Ok(metadata) if metadata.is_dir() => {
+                    if !UNLISTED_DIRS.contains(&entry.file_name().to_string_lossy().as_ref()) {
+                        pending.push(entry.path());
+                    }
+                }
+                Ok(metadata) => {
+                    let modified = metadata
+                        .modified()
+                        .ok()
+                        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+                        .map_or(0, |since| since.as_nanos());
+                    files.push((entry.path(), metadata.len(), modified));
+                }
+                Err(_) => {}
+            }
+        }
+    }
+    files.sort_unstable();
+    (files.len() <= MAX_LISTED).then_some(files)
+}
+
+/// Normalize a command line for use as a key: runs of whitespace are one space
+pub(super) fn normalize(command: &str) -> String {
+    command.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Paths under `base_dir` named in `texts`, existing or not
+fn named_paths(base_dir: &Path, texts: &[&str]) -> BTreeSet<PathBuf> {
+    texts
+        .iter()
+        .flat_map(|text| text.split(|c: char| c.is_whitespace() || c == ':' || c == '\''))
+        .map(|word| word.trim_matches('"'))
+        .filter(|word| !word.is_empty() && !word.starts_with('-') && !word.contains(".."))
+        .map(|word| base_dir.join(word))
+        .collect()
+}
+
+/// Files under `base_dir` named in `texts`, such as the paths in a command
+/// line or at the start of `grep -n` output lines
+pub(super) fn referenced_files(base_dir: &Path, texts: &[&str]) -> Vec<PathBuf> {
+    named_paths(base_dir, texts)
+        .into_iter()
+        .filter(|path| path.is_file())
+        .take(MAX_SOURCES)
+        .collect()
+}
+
+/// What a search with the arguments `args` reads: the files and
+/// directories under `base_dir` named in them, or the whole workspace when
+/// none is named
+pub(super) fn searched_paths(base_dir: &Path, args: &[&str]) -> Vec<PathBuf> {
+    let named: Vec<PathBuf> = named_paths(base_dir, args)
+        .into_iter()
+        .filter(|path| path.exists())
+        .take(MAX_SOURCES)
+        .collect();
+    if named.is_empty() {
+        vec![base_dir.to_path_buf()]
+    } else {
+        named
+    }
+}
+
+/// The sources of a search's result: what its arguments `args` have it
+/// read, and the files named in its `output`
+pub(super) fn sources(base_dir: &Path, args: &[&str], output: &str) -> Vec<PathBuf> {
+    searched_paths(base_dir, args)
+        .into_iter()
+        .chain(referenced_files(base_dir, &[output]))
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_result_cache() {
+        let base_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+        let cache = ResultCache::default();
+
+        let command = normalize("grep  -n 'fn main'   src/main.rs");
+        assert_eq!(command, "grep -n 'fn main' src/main.rs");
+        let output = "src/main.rs:237:async fn main() -> Result<ExitCode> {";
+        let sources = referenced_files(base_dir, &[&command, output]);
+        assert_eq!(sources, [base_dir.join("src/main.rs")]);
+
+        cache.insert(command.clone(), output, sources).await;
+        assert_eq!(
+            cache.get(&command).await,
+            Some(format!("{CACHED_MARKER}\n{output}"))
+        );
+        assert_eq!(cache.get("grep -n main").await, None);
+
+        // Without files to validate against, nothing is cached
+        cache.insert("wc -l".to_string(), "42", Vec::new()).await;
+        assert_eq!(cache.get("wc -l").await, None);
+    }
+
+    #[tokio::test]
+    async fn test_searched_directory() {
+        let base_dir =
+            std::env::temp_dir().join(format!("horse-cache-test-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(base_dir.join("src")).unwrap();
+        fs::write(base_dir.join("src/a.rs"), "// TODO: a").unwrap();
+        let cache = ResultCache::default();
+
+        // A search naming a directory, and one naming nothing, read all of it
+        let output = "src/a.rs:1:// TODO: a";
+        assert_eq!(
+            searched_paths(&base_dir, &["grep -rn TODO src/"]),
+            [base_dir.join("src/")]
+        );
+        assert_eq!(
+            searched_paths(&base_dir, &["rg TODO"]),
+            vec![base_dir.clone()]
+        );
+        let sources = sources(&base_dir, &["grep -rn TODO src/"], output);
+        cache.insert("todos".to_string(), output, sources).await;
+        assert!(cache.get("todos").await.is_some());
+
+        // A match lands in a file the result didn't name
+        fs::write(base_dir.join("src/b.rs"), "// TODO: b").unwrap();
+        assert_eq!(cache.get("todos").await, None);
+
+        fs::remove_dir_all(&base_dir).unwrap();
+    }
+}
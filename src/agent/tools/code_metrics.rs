@@ -0,0 +1,540 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use rig::completion::ToolDefinition;
+use rig::tool::Tool;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use thiserror::Error;
+use tokio::time::timeout;
+use tree_sitter::{Language, Node, Parser};
+
+use crate::agent::tools::cache::{self, ResultCache};
+use crate::agent::{remote, scope};
+
+const TIMEOUT_SECS: u64 = 30;
+
+/// Source files measured, at most
+const MAX_FILES: usize = 1000;
+
+/// Larger files, usually generated, are skipped
+const MAX_FILE_BYTES: usize = 200 * 1024; // 200KB
+
+/// Functions listed as the most complex
+const MAX_FUNCTIONS: usize = 15;
+
+/// Files listed as hotspots
+const MAX_HOTSPOTS: usize = 10;
+
+/// History counted as churn
+const CHURN_SINCE: &str = "1 year ago";
+
+/// What counts as a function and as a decision point in a language
+struct Grammar {
+    /// Nodes measured as functions of their own
+    functions: &'static [&'static str],
+    /// Nodes that add a path through a function
+    decisions: &'static [&'static str],
+    /// Binary operators that short-circuit, each adding a path
+    operators: &'static [&'static str],
+}
+
+const RUST: Grammar = Grammar {
+    functions: &["function_item"],
+    decisions: &["if_expression", "while_expression", "for_expression"],
+    operators: &["&&", "||"],
+};
+
+const PYTHON: Grammar = Grammar {
+    functions: &["function_definition"],
+    decisions: &[
+        "if_statement",
+        "elif_clause",
+        "for_statement",
+        "while_statement",
+        "except_clause",
+        "conditional_expression",
+        "case_clause",
+        "if_clause",
+    ],
+    operators: &["and", "or"],
+};
+
+const JAVASCRIPT: Grammar = Grammar {
+    functions: &[
+        "function_declaration",
+        "function_expression",
+        "generator_function_declaration",
+        "arrow_function",
+        "method_definition",
+    ],
+    decisions: &[
+        "if_statement",
+        "for_statement",
+        "for_in_statement",
+        "while_statement",
+        "do_statement",
+        "switch_case",
+        "catch_clause",
+        "ternary_expression",
+    ],
+    operators: &["&&", "||", "??"],
+};
+
+const GO: Grammar = Grammar {
+    functions: &["function_declaration", "method_declaration", "func_literal"],
+    decisions: &[
+        "if_statement",
+        "for_statement",
+        "expression_case",
+        "type_case",
+        "communication_case",
+    ],
+    operators: &["&&", "||"],
+};
+
+/// Parser language and grammar of a file, by extension
+fn grammar(file: &str) -> Option<(Language, &'static Grammar)> {
+    let extension = Path::new(file).extension()?.to_str()?;
+    match extension {
+        "rs" => Some((tree_sitter_rust::LANGUAGE.into(), &RUST)),
+        "py" => Some((tree_sitter_python::LANGUAGE.into(), &PYTHON)),
+        "js" | "jsx" | "mjs" | "cjs" => {
+            Some((tree_sitter_javascript::LANGUAGE.into(), &JAVASCRIPT))
+        }
+        "ts" => Some((
+            tree_sitter_typescript::LANGUAGE_TYPESCRIPT.into(),
+            &JAVASCRIPT,
+        )),
+        "tsx" => Some((tree_sitter_typescript::LANGUAGE_TSX.into(), &JAVASCRIPT)),
+        "go" => Some((tree_sitter_go::LANGUAGE.into(), &GO)),
+        _ => None,
+    }
+}
+
+#[derive(Deserialize)]
+pub struct CodeMetricsArgs {
+    /// Optional directory or file to measure (defaults to the working directory)
+    pub path: Option<String>,
+}
+
+#[derive(Debug, Error)]
+pub enum CodeMetricsError {
+    #[error("Path traversal not allowed: {0}")]
+    PathTraversal(String),
+    #[error("Listing files timed out after {0} seconds")]
+    Timeout(u64),
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Listing files failed: {0}")]
+    ListFailed(String),
+}
+
+/// Cyclomatic complexity of one function
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Function {
+    path: String,
+    name: String,
+    line: usize,
+    lines: usize,
+    complexity: usize,
+}
+
+/// Measures the complexity of functions with tree-sitter and combines it
+/// with how often files change into hotspots
+#[derive(Deserialize, Serialize)]
+pub struct CodeMetrics {
+    #[serde(skip)]
+    base_dir: PathBuf,
+    #[serde(skip)]
+    cache: ResultCache,
+}
+
+impl CodeMetrics {
+    pub fn new(base_dir: PathBuf) -> Self {
+        Self {
+            base_dir,
+            cache: ResultCache::default(),
+        }
+    }
+
+    /// Files under `path` in a supported language, as listed by ripgrep
+    async fn source_files(&self, path: &str) -> Result<Vec<String>, CodeMetricsError> {
+        let output = timeout(
+            Duration::from_secs(TIMEOUT_SECS),
+            scope::search_command(&self.base_dir, "rg", &["--files", "--", path]).output(),
+        )
+        .await
+        .map_err(|_| CodeMetricsError::Timeout(TIMEOUT_SECS))??;
+
+        match output.status.code() {
+            Some(0 | 1) => {
+                let mut files: Vec<String> = String::from_utf8_lossy(&output.stdout)
+                    .lines()
+                    .filter(|file| grammar(file).is_some())
+                    .map(|file| file.trim_start_matches("./").to_string())
+                    .collect();
+                files.sort();
+                files.truncate(MAX_FILES);
+                Ok(files)
+            }
+            _ => Err(CodeMetricsError::ListFailed(
+                String::from_utf8_lossy(&output.stderr).trim().to_string(),
+            )),
+        }
+    }
+
+    /// Commits per file in the last year, or None outside a git repository
+    async fn churn(&self, path: &str) -> Option<HashMap<String, usize>> {
+        let since = format!("--since={CHURN_SINCE}");
+        let output = timeout(
+            Duration::from_secs(TIMEOUT_SECS),
+            remote::command(
+                &self.base_dir,
+                "git",
+                // `--relative` gives paths relative to the workspace, like ripgrep's
+                &[
+                    "log",
+                    &since,
+                    "--relative",
+                    "--name-only",
+                    "--format=",
+                    "--",
+                    path,
+                ],
+            )
+            .output(),
+        )
+        .await;
+
+        match output {
+            Ok(Ok(output)) if output.status.success() => {
+                Some(parse_churn(&String::from_utf8_lossy(&output.stdout)))
+            }
+            _ => None,
+        }
+    }
+
+    async fn measure(&self, path: &str) -> Result<String, CodeMetricsError> {
+        let files = self.source_files(path).await?;
+        let mut functions = Vec::new();
+        for file in &files {
+            if let Ok(text) = remote::read_to_string(&self.base_dir, file).await
+                && text.len() <= MAX_FILE_BYTES
+            {
+                functions.extend(measure_file(file, &text));
+            }
+        }
+        let churn = self.churn(path).await;
+        Ok(render(&functions, churn.as_ref()))
+    }
+}
+
+impl Tool for CodeMetrics {
+    const NAME: &'static str = "code_metrics";
+
+    type Error = CodeMetricsError;
+    type Args = CodeMetricsArgs;
+    type Output = String;
+
+    async fn definition(&self, _prompt: String) -> ToolDefinition {
+        ToolDefinition {
+            name: Self::NAME.to_string(),
+            description: "Measure the cyclomatic complexity of every function (Rust, Python, \
+                JavaScript, TypeScript and Go) and the churn of every file (commits in the \
+                last year), and list the most complex functions and the hotspots: complex \
+                files that change often. Use it to ground questions about risky, fragile or \
+                hard-to-maintain parts of the codebase in numbers."
+                .to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "path": {
+                        "type": "string",
+                        "description": "Optional directory or file to measure (defaults to the working directory)"
+                    }
+                }
+            }),
+        }
+    }
+
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+        let path = args.path.as_deref().unwrap_or(".");
+
+        if path.contains("..") || path.starts_with('/') {
+            Err(CodeMetricsError::PathTraversal(path.to_string()))
+        } else {
+            match self.cache.get(path).await {
+                Some(cached) => Ok(cached),
+                None => {
+                    let output = self.measure(path).await?;
+                    let sources = cache::sources(&self.base_dir, &[path], &output);
+                    self.cache.insert(path.to_string(), &output, sources).await;
+                    Ok(output)
+                }
+            }
+        }
+    }
+}
+
+/// The functions of a file with their complexity; none if the file can't
+/// be parsed
+fn measure_file(path: &str, text: &str) -> Vec<Function> {
+    let mut functions = Vec::new();
+    let mut parser = Parser::new();
+    if let Some((language, grammar)) = grammar(path)
+        && parser.set_language(&language).is_ok()
+        && let Some(tree) = parser.parse(text, None)
+    {
+        let mut measure = Measure {
+            path,
+            source: text.as_bytes(),
+            grammar,
+            functions: &mut functions,
+        };
+        measure.decisions(tree.root_node());
+    }
+    functions
+}
+
+struct Measure<'a> {
+    path: &'a str,
+    source: &'a [u8],
+    grammar: &'static Grammar,
+    functions: &'a mut Vec<Function>,
+}
+
+impl Measure<'_> {
+    /// Decision points under `node`, not counting nested functions, which
+    /// are measured on their own
+    fn decisions(&mut self, node: Node) -> usize {
+        let mut cursor = node.walk();
+        let children: Vec<Node> = node.children(&mut cursor).collect();
+        children
+            .into_iter()
+            .map(|child| {
+                if self.grammar.functions.contains(&child.kind()) {
+                    self.function(child);
+                    0
+                } else {
+                    self.weight(child) + self.decisions(child)
+                }
+            })
+            .sum()
+    }
+
+    /// Decision points `node` itself adds
+    fn weight(&self, node: Node) -> usize {
+        match node.kind() {
+            kind if self.grammar.decisions.contains(&kind) => 1,
+            // A match is a decision per arm after the first
+            "match_block" => {
+                let mut cursor = node.walk();
+                node.named_children(&mut cursor)
+                    .filter(|arm| arm.kind() == "match_arm")
+                    .count()
+                    .saturating_sub(1)
+            }
+            "binary_expression" | "boolean_operator" => {
+                node.child_by_field_name("operator").map_or(0, |operator| {
+                    usize::from(self.grammar.operators.contains(&operator.kind()))
+                })
+            }
+            _ => 0,
+        }
+    }
+
+    fn function(&mut self, node: Node) {
+        // Anonymous functions are named after the variable they are assigned to
+        let name = node
+            .child_by_field_name("name")
+            .or_else(|| {
+                node.parent()
+                    .filter(|parent| parent.kind() == "variable_declarator")
+                    .and_then(|parent| parent.child_by_field_name("name"))
+            })
+            .and_then(|name| name.utf8_text(self.source).ok())
+            .unwrap_or("<anonymous>")
+            .to_string();
+        let complexity = 1 + self.decisions(node);
+        self.functions.push(Function {
+            path: self.path.to_string(),
+            name,
+            line: node.start_position().row + 1,
+            lines: node.end_position().row - node.start_position().row + 1,
+            complexity,
+        });
+    }
+}
+
+/// Commits per file from `git log --name-only --format=` output
+fn parse_churn(output: &str) -> HashMap<String, usize> {
+    let mut churn = HashMap::new();
+    for file in output.lines().filter(|line| !line.is_empty()) {
+        *churn.entry(file.to_string()).or_default() += 1;
+    }
+    churn
+}
+
+/// The most complex functions, and the files ranked by complexity times
+/// churn
+fn render(functions: &[Function], churn: Option<&HashMap<String, usize>>) -> String {
+    if functions.is_empty() {
+        "No functions found in Rust, Python, JavaScript, TypeScript or Go files".to_string()
+    } else {
+        let total: usize = functions.iter().map(|function| function.complexity).sum();
+        let mut files: HashMap<&str, (usize, usize)> = HashMap::new();
+        for function in functions {
+            let (complexity, count) = files.entry(&function.path).or_default();
+            *complexity += function.complexity;
+            *count += 1;
+        }
+        let mut out = format!(
+            "Cyclomatic complexity of {} functions in {} files: mean {:.1}, total {total}\n",
+            functions.len(),
+            files.len(),
+            total as f64 / functions.len() as f64
+        );
+
+        let mut complex: Vec<&Function> = functions.iter().collect();
+        complex.sort_by(|a, b| {
+            b.complexity
+                .cmp(&a.complexity)
+                .then_with(|| a.path.cmp(&b.path))
+                .then_with(|| a.line.cmp(&b.line))
+        });
+        out.push_str("\nMost complex functions:\n");
+        for function in complex.into_iter().take(MAX_FUNCTIONS) {
+            out.push_str(&format!(
+                "  {}:{} {} - complexity {}, {} lines\n",
+                function.path, function.line, function.name, function.complexity, function.lines
+            ));
+        }
+
+        let commits = |path: &str| {
+            churn
+                .and_then(|churn| churn.get(path))
+                .copied()
+                .unwrap_or(0)
+        };
+        let mut hotspots: Vec<(&str, usize, usize)> = files
+            .into_iter()
+            .map(|(path, (complexity, count))| (path, complexity, count))
+            .collect();
+        // Without history, the most complex files are the hotspots
+        hotspots.sort_by(|a, b| {
+            (b.1 * commits(b.0).max(1))
+                .cmp(&(a.1 * commits(a.0).max(1)))
+                .then_with(|| a.0.cmp(b.0))
+        });
+        match churn {
+            Some(_) => out.push_str(&format!(
+                "\nHotspots (complexity x commits since {CHURN_SINCE}):\n"
+            )),
+            None => out.push_str("\nMost complex files (no git history for churn):\n"),
+        }
+        for (path, complexity, count) in hotspots.into_iter().take(MAX_HOTSPOTS) {
+            let commits =
+                churn.map_or_else(String::new, |_| format!(", {} commits", commits(path)));
+            out.push_str(&format!(
+                "  {path} - complexity {complexity} in {count} functions{commits}\n"
+            ));
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn complexities(path: &str, text: &str) -> Vec<(String, usize)> {
+        measure_file(path, text)
+            .into_iter()
+            .map(|function| (function.name, function.complexity))
+            .collect()
+    }
+
+    #[test]
+    fn test_rust_complexity() {
+        let text = r#"
+fn simple() -> u32 {
+    1
+}
+
+fn branchy(x: Option<u32>, flag: bool) -> u32 {
+    let double = |n: u32| if n > 10 { n } else { n * 2 };
+    if flag && x.is_some() {
+        return 0;
+    }
+    for i in 0..3 {
+        fn nested() {}
+    }
+    match x {
+        Some(0) => 1,
+        Some(n) => double(n),
+        None => 2,
+    }
+}
+"#;
+        assert_eq!(
+            complexities("src/lib.rs", text),
+            [
+                ("simple".to_string(), 1),
+                ("nested".to_string(), 1),
+                ("branchy".to_string(), 7)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_other_languages() {
+        let python = "def check(a, b):\n    if a or b:\n        return 1\n    elif a:\n        return 2\n    return [x for x in b if x]\n";
+        assert_eq!(complexities("app.py", python), [("check".to_string(), 5)]);
+
+        let typescript = "const pick = (a?: number) => a ?? (a > 1 ? 2 : 3);\n";
+        assert_eq!(
+            complexities("app.ts", typescript),
+            [("pick".to_string(), 3)]
+        );
+
+        let go = "package main\nfunc run(x int) int {\n\tif x > 0 && x < 9 {\n\t\treturn 1\n\t}\n\treturn 0\n}\n";
+        assert_eq!(complexities("main.go", go), [("run".to_string(), 3)]);
+
+        assert!(complexities("notes.txt", "if x").is_empty());
+    }
+
+    #[test]
+    fn test_render() {
+        let function = |path: &str, name: &str, complexity| Function {
+            path: path.to_string(),
+            name: name.to_string(),
+            line: 1,
+            lines: 10,
+            complexity,
+        };
+        let functions = [
+            function("src/a.rs", "parse", 12),
+            function("src/a.rs", "new", 1),
+            function("src/b.rs", "run", 8),
+        ];
+        let churn = parse_churn("src/b.rs\n\nsrc/b.rs\nsrc/a.rs\n\nsrc/b.rs\n");
+        let out = render(&functions, Some(&churn));
+
+        assert!(
+            out.starts_with(
+                "Cyclomatic complexity of 3 functions in 2 files: mean 7.0, total 21\n"
+            )
+        );
+        assert!(
+            out.contains(
+                "\nMost complex functions:\n  src/a.rs:1 parse - complexity 12, 10 lines\n"
+            )
+        );
+        assert!(out.contains(
+            ":\n  src/b.rs - complexity 8 in 1 functions, 3 commits\n  src/a.rs - complexity 13 in 2 functions, 1 commits\n"
+        ));
+        assert!(render(&functions, None).contains("no git history"));
+    }
+}
@@ -0,0 +1,594 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
+use std::time::Duration;
+
+use regex::Regex;
+use rig::completion::ToolDefinition;
+use rig::tool::Tool;
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use thiserror::Error;
+use tokio::time::timeout;
+
+use crate::agent::tools::cache::{self, ResultCache};
+use crate::agent::tools::injection;
+use crate::agent::{remote, scope};
+
+const TIMEOUT_SECS: u64 = 30;
+
+/// Config files read, at most
+const MAX_FILES: usize = 300;
+
+/// Larger files are listed without their keys
+const MAX_FILE_BYTES: usize = 100 * 1024; // 100KB
+
+/// Keys listed per file, at most
+const MAX_KEYS: usize = 40;
+
+/// Levels of nesting listed as dotted keys
+const MAX_DEPTH: usize = 3;
+
+/// Files that look like config by extension but are generated
+const SKIPPED_NAMES: &[&str] = &[
+    "package-lock.json",
+    "composer.lock",
+    "Cargo.lock",
+    "yarn.lock",
+];
+
+/// `${VAR}`, `${VAR:-default}` and `$VAR` as in shells, Compose and many
+/// config loaders
+static SHELL_VAR: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"\$\{([A-Za-z_][A-Za-z0-9_]*)[^}]*\}|\$([A-Z_][A-Z0-9_]*)")
+        .expect("Invalid variable regex")
+});
+
+/// `env("VAR")`, `env(VAR)`, `{{ env "VAR" }}` and `os.environ["VAR"]`
+static ENV_CALL: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r#"\benv(?:\s*\(\s*["']?|\s+["'])([A-Za-z_][A-Za-z0-9_]*)|environ\[\s*["']([A-Za-z_][A-Za-z0-9_]*)"#,
+    )
+    .expect("Invalid env regex")
+});
+
+/// `KEY=value` or `export KEY=value` of an env file
+static ENV_ASSIGNMENT: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?m)^\s*(?:export\s+)?([A-Za-z_][A-Za-z0-9_.]*)\s*=").expect("Invalid env regex")
+});
+
+/// `key = value` or `key: value` of an INI or properties file
+static INI_ASSIGNMENT: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^\s*([A-Za-z0-9_.\-]+)\s*[=:]").expect("Invalid assignment regex")
+});
+
+static INI_SECTION: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^\s*\[([^\]]+)\]").expect("Invalid section regex"));
+
+/// A Terraform block with its labels, e.g. `resource "aws_s3_bucket" "logs"`
+static TERRAFORM_BLOCK: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r#"(?m)^\s*(variable|output|resource|data|module|provider)\s+"([^"]+)"(?:\s+"([^"]+)")?"#,
+    )
+    .expect("Invalid block regex")
+});
+
+static TERRAFORM_ASSIGNMENT: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?m)^\s*([A-Za-z_][A-Za-z0-9_]*)\s*=").expect("Invalid assignment regex")
+});
+
+/// What a config file is for, in the order they are listed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Kind {
+    Env,
+    Compose,
+    Kubernetes,
+    Helm,
+    Terraform,
+    Yaml,
+    Toml,
+    Json,
+    Ini,
+}
+
+impl Kind {
+    fn label(self) -> &'static str {
+        match self {
+            Kind::Env => "Environment files",
+            Kind::Compose => "Docker Compose",
+            Kind::Kubernetes => "Kubernetes manifests",
+            Kind::Helm => "Helm charts",
+            Kind::Terraform => "Terraform",
+            Kind::Yaml => "YAML",
+            Kind::Toml => "TOML",
+            Kind::Json => "JSON",
+            Kind::Ini => "INI and properties",
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct ConfigInventoryArgs {
+    /// Optional directory to look for config files in (defaults to the working directory)
+    pub path: Option<String>,
+}
+
+#[derive(Debug, Error)]
+pub enum ConfigInventoryError {
+    #[error("Path traversal not allowed: {0}")]
+    PathTraversal(String),
+    #[error("Listing files timed out after {0} seconds")]
+    Timeout(u64),
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Listing files failed: {0}")]
+    ListFailed(String),
+}
+
+/// A config file with what it configures
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct ConfigFile {
+    path: String,
+    kind: Kind,
+    /// Dotted key paths, or None if the file couldn't be parsed
+    keys: Option<Vec<String>>,
+    /// Environment variables the file references
+    env_vars: BTreeSet<String>,
+}
+
+/// Finds the config files of the workspace and lists their keys and the
+/// environment variables they reference
+#[derive(Deserialize, Serialize)]
+pub struct ConfigInventory {
+    #[serde(skip)]
+    base_dir: PathBuf,
+    #[serde(skip)]
+    cache: ResultCache,
+}
+
+impl ConfigInventory {
+    pub fn new(base_dir: PathBuf) -> Self {
+        Self {
+            base_dir,
+            cache: ResultCache::default(),
+        }
+    }
+
+    /// Files under `path`, hidden ones included so e.g. `.github` workflows
+    /// are found; `.env*` files, which usually hold secrets, are left out
+    async fn files(&self, path: &str) -> Result<Vec<String>, ConfigInventoryError> {
+        let output = timeout(
+            Duration::from_secs(TIMEOUT_SECS),
+            scope::search_command(
+                &self.base_dir,
+                "rg",
+                &["--files", "--hidden", "-g", "!.git", "--", path],
+            )
+            .output(),
+        )
+        .await
+        .map_err(|_| ConfigInventoryError::Timeout(TIMEOUT_SECS))??;
+
+        match output.status.code() {
+            Some(0 | 1) => {
+                let mut files: Vec<String> = String::from_utf8_lossy(&output.stdout)
+                    .lines()
+                    .map(|file| file.trim_start_matches("./").to_string())
+                    .collect();
+                files.sort();
+                Ok(files)
+            }
+            _ => Err(ConfigInventoryError::ListFailed(
+                String::from_utf8_lossy(&output.stderr).trim().to_string(),
+            )),
+        }
+    }
+
+    async fn inventory(&self, path: &str) -> Result<String, ConfigInventoryError> {
+        let files = self.files(path).await?;
+        let charts: Vec<&str> = files
+            .iter()
+            .filter_map(|file| file.strip_suffix("Chart.yaml"))
+            .filter(|dir| dir.is_empty() || dir.ends_with('/'))
+            .collect();
+
+        let mut configs = Vec::new();
+        for file in &files {
+            if configs.len() < MAX_FILES
+                && let Some(kind) = classify(file, &charts)
+            {
+                let text = remote::read_to_string(&self.base_dir, file)
+                    .await
+                    .ok()
+                    .filter(|text| text.len() <= MAX_FILE_BYTES);
+                configs.push(describe(file, kind, text.as_deref()));
+            }
+        }
+        Ok(render(&configs))
+    }
+}
+
+impl Tool for ConfigInventory {
+    const NAME: &'static str = "config_inventory";
+
+    type Error = ConfigInventoryError;
+    type Args = ConfigInventoryArgs;
+    type Output = String;
+
+    async fn definition(&self, _prompt: String) -> ToolDefinition {
+        ToolDefinition {
+            name: Self::NAME.to_string(),
+            description: "Find the config files of the workspace and classify them (env \
+                files, Docker Compose, Kubernetes manifests, Helm charts, Terraform, YAML, \
+                TOML, JSON, INI/properties), list the keys each defines (never values) and the \
+                environment variables each references. Use it for deployment and \
+                configuration questions instead of reading the files one by one."
+                .to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "path": {
+                        "type": "string",
+                        "description": "Optional directory to look for config files in (defaults to the working directory)"
+                    }
+                }
+            }),
+        }
+    }
+
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+        let path = args.path.as_deref().unwrap_or(".");
+
+        if path.contains("..") || path.starts_with('/') {
+            Err(ConfigInventoryError::PathTraversal(path.to_string()))
+        } else {
+            match self.cache.get(path).await {
+                Some(cached) => Ok(cached),
+                None => {
+                    let output = injection::guard(self.inventory(path).await?);
+                    let sources = cache::sources(&self.base_dir, &[path], &output);
+                    self.cache.insert(path.to_string(), &output, sources).await;
+                    Ok(output)
+                }
+            }
+        }
+    }
+}
+
+/// The kind of config `file` is, if it is one; `charts` are the
+/// directories of Helm charts
+fn classify(file: &str, charts: &[&str]) -> Option<Kind> {
+    let name = Path::new(file)
+        .file_name()
+        .map(|name| name.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    let extension = name.rsplit_once('.').map_or("", |(_, extension)| extension);
+
+    match (name.as_str(), extension) {
+        (name, _)
+            if SKIPPED_NAMES
+                .iter()
+                .any(|skipped| skipped.eq_ignore_ascii_case(name)) =>
+        {
+            None
+        }
+        (name, _) if name == ".env" || name.starts_with(".env.") => Some(Kind::Env),
+        (_, "env") => Some(Kind::Env),
+        (_, "yaml" | "yml") if charts.iter().any(|chart| file.starts_with(chart)) => {
+            Some(Kind::Helm)
+        }
+        (name, "yaml" | "yml")
+            if name.starts_with("docker-compose") || name.starts_with("compose.") =>
+        {
+            Some(Kind::Compose)
+        }
+        (_, "yaml" | "yml") => Some(Kind::Yaml),
+        (_, "tf" | "tfvars") => Some(Kind::Terraform),
+        (_, "toml") => Some(Kind::Toml),
+        (_, "json") => Some(Kind::Json),
+        (_, "ini" | "cfg" | "conf" | "properties") => Some(Kind::Ini),
+        _ => None,
+    }
+}
+
+/// Keys and environment variables of a config file; `text` is None if it
+/// couldn't be read or is too large
+fn describe(path: &str, kind: Kind, text: Option<&str>) -> ConfigFile {
+    let is_template = path.contains("/templates/");
+    let (kind, keys) = match text {
+        None => (kind, None),
+        // Helm templates are Go templates, not YAML
+        Some(_) if kind == Kind::Helm && is_template => (kind, None),
+        Some(text) => match kind {
+            Kind::Env => (kind, Some(capture_keys(&ENV_ASSIGNMENT, text))),
+            Kind::Terraform => (kind, Some(terraform_keys(text))),
+            Kind::Ini => (kind, Some(ini_keys(text))),
+            Kind::Toml => (
+                kind,
+                toml::from_str::<Value>(text)
+                    .ok()
+                    .map(|value| value_keys(&value)),
+            ),
+            Kind::Json => (
+                kind,
+                serde_json::from_str::<Value>(text)
+                    .ok()
+                    .map(|value| value_keys(&value)),
+            ),
+            Kind::Compose | Kind::Kubernetes | Kind::Helm | Kind::Yaml => {
+                let documents: Vec<Value> = serde_norway::Deserializer::from_str(text)
+                    .filter_map(|document| Value::deserialize(document).ok())
+                    .filter(|document| !document.is_null())
+                    .collect();
+                // Manifests are YAML with an apiVersion and kind
+                let kind = match kind {
+                    Kind::Yaml
+                        if !documents.is_empty()
+                            && documents.iter().all(|document| {
+                                document.get("apiVersion").is_some()
+                                    && document.get("kind").is_some()
+                            }) =>
+                    {
+                        Kind::Kubernetes
+                    }
+                    kind => kind,
+                };
+                let keys = match kind {
+                    Kind::Kubernetes => documents
+                        .iter()
+                        .map(|document| {
+                            let field = |name: &str| {
+                                document
+                                    .pointer(name)
+                                    .and_then(Value::as_str)
+                                    .unwrap_or("?")
+                                    .to_string()
+                            };
+                            format!("{}/{}", field("/kind"), field("/metadata/name"))
+                        })
+                        .collect(),
+                    _ => documents.iter().flat_map(value_keys).collect(),
+                };
+                (kind, Some(dedup(keys)))
+            }
+        },
+    };
+    ConfigFile {
+        path: path.to_string(),
+        kind,
+        keys,
+        env_vars: text.map(env_vars).unwrap_or_default(),
+    }
+}
+
+/// Keys in order of appearance, without repeats
+fn dedup(keys: Vec<String>) -> Vec<String> {
+    let mut seen = BTreeSet::new();
+    keys.into_iter()
+        .filter(|key| seen.insert(key.clone()))
+        .collect()
+}
+
+fn capture_keys(regex: &Regex, text: &str) -> Vec<String> {
+    dedup(
+        regex
+            .captures_iter(text)
+            .map(|caps| caps[1].to_string())
+            .collect(),
+    )
+}
+
+/// Dotted paths of the leaves of a parsed document, down to `MAX_DEPTH`;
+/// the elements of arrays share one `[]` path
+fn value_keys(value: &Value) -> Vec<String> {
+    fn walk(value: &Value, prefix: &str, depth: usize, keys: &mut Vec<String>) {
+        match value {
+            Value::Object(map) if depth < MAX_DEPTH && !map.is_empty() => {
+                for (key, value) in map {
+                    let path = if prefix.is_empty() {
+                        key.clone()
+                    } else {
+                        format!("{prefix}.{key}")
+                    };
+                    walk(value, &path, depth + 1, keys);
+                }
+            }
+            Value::Array(items) if depth < MAX_DEPTH && items.iter().any(Value::is_object) => {
+                for item in items {
+                    walk(item, &format!("{prefix}[]"), depth + 1, keys);
+                }
+            }
+            _ if !prefix.is_empty() => keys.push(prefix.to_string()),
+            _ => {}
+        }
+    }
+    let mut keys = Vec::new();
+    walk(value, "", 0, &mut keys);
+    dedup(keys)
+}
+
+fn ini_keys(text: &str) -> Vec<String> {
+    let mut section = String::new();
+    let mut keys = Vec::new();
+    for line in text.lines() {
+        if let Some(caps) = INI_SECTION.captures(line) {
+            section = format!("{}.", caps[1].trim());
+        } else if !line.trim_start().starts_with(['#', ';'])
+            && let Some(caps) = INI_ASSIGNMENT.captures(line)
+        {
+            keys.push(format!("{section}{}", &caps[1]));
+        }
+    }
+    dedup(keys)
+}
+
+/// Declared blocks, e.g. `variable.region`; for `.tfvars`, the assigned
+/// variables
+fn terraform_keys(text: &str) -> Vec<String> {
+    let blocks: Vec<String> = TERRAFORM_BLOCK
+        .captures_iter(text)
+        .map(|caps| match caps.get(3) {
+            Some(name) => format!("{}.{}.{}", &caps[1], &caps[2], name.as_str()),
+            None => format!("{}.{}", &caps[1], &caps[2]),
+        })
+        .collect();
+    if blocks.is_empty() {
+        capture_keys(&TERRAFORM_ASSIGNMENT, text)
+    } else {
+        blocks
+    }
+}
+
+fn env_vars(text: &str) -> BTreeSet<String> {
+    SHELL_VAR
+        .captures_iter(text)
+        .chain(ENV_CALL.captures_iter(text))
+        .filter_map(|caps| caps.get(1).or_else(|| caps.get(2)))
+        .map(|name| name.as_str().to_string())
+        .collect()
+}
+
+fn render(configs: &[ConfigFile]) -> String {
+    if configs.is_empty() {
+        "No config files found".to_string()
+    } else {
+        let mut by_kind: BTreeMap<Kind, Vec<&ConfigFile>> = BTreeMap::new();
+        let mut variables: BTreeMap<&str, usize> = BTreeMap::new();
+        for config in configs {
+            by_kind.entry(config.kind).or_default().push(config);
+            for name in &config.env_vars {
+                *variables.entry(name).or_default() += 1;
+            }
+        }
+
+        let mut out = format!("{} config files\n", configs.len());
+        for (kind, configs) in by_kind {
+            out.push_str(&format!("\n{} ({}):\n", kind.label(), configs.len()));
+            for config in configs {
+                out.push_str(&format!("  {}\n", config.path));
+                match &config.keys {
+                    Some(keys) if keys.is_empty() => {}
+                    Some(keys) => {
+                        let more = keys.len().saturating_sub(MAX_KEYS);
+                        let more = if more > 0 {
+                            format!(" (+{more} more)")
+                        } else {
+                            String::new()
+                        };
+                        let label = if config.kind == Kind::Kubernetes {
+                            "resources"
+                        } else {
+                            "keys"
+                        };
+                        out.push_str(&format!(
+                            "    {label}: {}{more}\n",
+                            keys.iter()
+                                .take(MAX_KEYS)
+                                .cloned()
+                                .collect::<Vec<_>>()
+                                .join(", ")
+                        ));
+                    }
+                    None => out.push_str("    keys: not parsed\n"),
+                }
+                if !config.env_vars.is_empty() {
+                    let names: Vec<&str> = config.env_vars.iter().map(String::as_str).collect();
+                    out.push_str(&format!("    env vars: {}\n", names.join(", ")));
+                }
+            }
+        }
+
+        if !variables.is_empty() {
+            let variables: Vec<String> = variables
+                .iter()
+                .map(|(name, count)| format!("{name} ({count})"))
+                .collect();
+            out.push_str(&format!(
+                "\nEnvironment variables referenced (files): {}\n",
+                variables.join(", ")
+            ));
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classify() {
+        let charts = ["deploy/chart/"];
+        assert_eq!(classify(".env.example", &charts), Some(Kind::Env));
+        assert_eq!(classify("config/prod.env", &charts), Some(Kind::Env));
+        assert_eq!(
+            classify("deploy/chart/values.yaml", &charts),
+            Some(Kind::Helm)
+        );
+        assert_eq!(classify("docker-compose.yml", &charts), Some(Kind::Compose));
+        assert_eq!(classify("infra/main.tf", &charts), Some(Kind::Terraform));
+        assert_eq!(classify("Cargo.toml", &charts), Some(Kind::Toml));
+        assert_eq!(classify("package-lock.json", &charts), None);
+        assert_eq!(classify("src/main.rs", &charts), None);
+    }
+
+    #[test]
+    fn test_describe() {
+        let compose = "services:\n  api:\n    image: api:${TAG:-latest}\n    environment:\n      - DATABASE_URL=$DATABASE_URL\n";
+        let file = describe("docker-compose.yml", Kind::Compose, Some(compose));
+        assert_eq!(
+            file.keys.unwrap(),
+            ["services.api.environment", "services.api.image"]
+        );
+        assert_eq!(
+            file.env_vars.into_iter().collect::<Vec<_>>(),
+            ["DATABASE_URL", "TAG"]
+        );
+
+        let manifests = "apiVersion: v1\nkind: Service\nmetadata:\n  name: api\n---\napiVersion: apps/v1\nkind: Deployment\nmetadata:\n  name: api\n";
+        let file = describe("k8s/api.yaml", Kind::Yaml, Some(manifests));
+        assert_eq!(file.kind, Kind::Kubernetes);
+        assert_eq!(file.keys.unwrap(), ["Service/api", "Deployment/api"]);
+
+        let env = "# comment\nexport PORT=8080\nSECRET_KEY=hunter2\n";
+        assert_eq!(
+            describe(".env", Kind::Env, Some(env)).keys.unwrap(),
+            ["PORT", "SECRET_KEY"]
+        );
+
+        let terraform = "variable \"region\" {}\nresource \"aws_s3_bucket\" \"logs\" {\n  bucket = var.name\n}\n";
+        assert_eq!(
+            describe("main.tf", Kind::Terraform, Some(terraform))
+                .keys
+                .unwrap(),
+            ["variable.region", "resource.aws_s3_bucket.logs"]
+        );
+
+        let toml = "[server]\nport = 80\nhosts = [\"a\"]\n\n[[workers]]\nname = \"w\"\n";
+        assert_eq!(
+            describe("app.toml", Kind::Toml, Some(toml)).keys.unwrap(),
+            ["server.hosts", "server.port", "workers[].name"]
+        );
+
+        let ini = "[database]\nhost = localhost\n; port = 1\nurl = ${DB_URL}\n";
+        let file = describe("app.ini", Kind::Ini, Some(ini));
+        assert_eq!(file.keys.unwrap(), ["database.host", "database.url"]);
+        assert!(file.env_vars.contains("DB_URL"));
+
+        assert_eq!(describe("big.json", Kind::Json, None).keys, None);
+    }
+
+    #[test]
+    fn test_render() {
+        let configs = [
+            describe(".env.example", Kind::Env, Some("PORT=1\n")),
+            describe("app.yaml", Kind::Yaml, Some("url: ${API_URL}\n")),
+        ];
+        assert_eq!(
+            render(&configs),
+            "2 config files\n\nEnvironment files (1):\n  .env.example\n    keys: PORT\n\n\
+             YAML (1):\n  app.yaml\n    keys: url\n    env vars: API_URL\n\n\
+             Environment variables referenced (files): API_URL (1)\n"
+        );
+    }
+}
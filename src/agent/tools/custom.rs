@@ -0,0 +1,228 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
+
+use regex::{Captures, Regex};
+use rig::completion::ToolDefinition;
+use rig::tool::Tool;
+use serde::Deserialize;
+use serde_json::{Map, Value, json};
+use thiserror::Error;
+
+use crate::agent::remote::shell_quote;
+use crate::agent::tools::bash::{self, BashCommandError};
+
+/// Names of the native tools, which custom tools can't shadow
+const RESERVED_NAMES: &[&str] = &[
+    "bash",
+    "code_metrics",
+    "config_inventory",
+    "deep_search",
+    "find_duplicates",
+    "find_todos",
+    "forge",
+    "git_history",
+    "license_scan",
+    "list_endpoints",
+    "list_objects",
+    "module_graph",
+    "read_file",
+    "read_log",
+    "read_object",
+    "search_docs",
+    "spawn_agent",
+    "ticket",
+];
+
+/// Matches `{param}` placeholders in a command
+static PLACEHOLDER: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\{(\w+)\}").expect("Invalid placeholder regex"));
+
+/// A tool declared in config under `[tool.<name>]`
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CustomToolConfig {
+    /// Shell command run in the workspace; `{param}` placeholders are
+    /// replaced by the quoted argument values
+    pub command: String,
+    /// What the tool does, shown to the model
+    pub description: String,
+    /// Parameters the model fills in, mapped to their descriptions
+    pub params: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Error)]
+pub enum CustomToolError {
+    #[error("Missing argument: {0}")]
+    MissingArgument(String),
+    #[error("Argument {0} must be a string or number")]
+    InvalidArgument(String),
+    #[error(transparent)]
+    Command(#[from] BashCommandError),
+}
+
+/// A shell command from config, run with the same timeout and output limit
+/// as the bash tool
+#[derive(Clone)]
+pub struct CustomTool {
+    name: String,
+    config: CustomToolConfig,
+    base_dir: PathBuf,
+}
+
+impl CustomTool {
+    /// Fill the `{param}` placeholders with the shell-quoted argument values.
+    ///
+    /// Placeholders are replaced in a single pass, so values can't inject
+    /// further placeholders.
+    fn render(&self, args: &Map<String, Value>) -> Result<String, CustomToolError> {
+        let values = self
+            .config
+            .params
+            .keys()
+            .map(|param| match args.get(param) {
+                Some(Value::String(value)) => Ok((param.as_str(), shell_quote(value))),
+                Some(Value::Number(value)) => Ok((param.as_str(), shell_quote(&value.to_string()))),
+                Some(_) => Err(CustomToolError::InvalidArgument(param.clone())),
+                None => Err(CustomToolError::MissingArgument(param.clone())),
+            })
+            .collect::<Result<BTreeMap<_, _>, _>>()?;
+
+        Ok(PLACEHOLDER
+            .replace_all(&self.config.command, |caps: &Captures| {
+                values
+                    .get(&caps[1])
+                    .cloned()
+                    .unwrap_or_else(|| caps[0].to_string())
+            })
+            .into_owned())
+    }
+}
+
+/// Tool names must be valid for the API and must not shadow a native tool
+fn valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= 64
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+        && !RESERVED_NAMES.contains(&name)
+}
+
+/// Create the tools declared in config, skipping invalid ones with a warning
+pub fn load_tools(
+    configs: &BTreeMap<String, CustomToolConfig>,
+    base_dir: &Path,
+) -> Vec<CustomTool> {
+    configs
+        .iter()
+        .filter_map(|(name, config)| {
+            if !valid_name(name) {
+                tracing::warn!("[!] Warning: Skipping tool `{name}`: invalid or reserved name");
+                None
+            } else if config.command.trim().is_empty() {
+                tracing::warn!("[!] Warning: Skipping tool `{name}`: no command");
+                None
+            } else {
+                Some(CustomTool {
+                    name: name.clone(),
+                    config: config.clone(),
+                    base_dir: base_dir.to_path_buf(),
+                })
+            }
+        })
+        .collect()
+}
+
+impl Tool for CustomTool {
+    /// Placeholder; the registered name comes from `name()`
+    const NAME: &'static str = "custom";
+
+    type Error = CustomToolError;
+    type Args = Map<String, Value>;
+    type Output = String;
+
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    async fn definition(&self, _prompt: String) -> ToolDefinition {
+        let properties: Map<String, Value> = self
+            .config
+            .params
+            .iter()
+            .map(|(param, description)| {
+                (
+                    param.clone(),
+                    json!({ "type": "string", "description": description }),
+                )
+            })
+            .collect();
+        let description = if self.config.description.is_empty() {
+            format!("Run `{}` in the working directory", self.config.command)
+        } else {
+            self.config.description.clone()
+        };
+
+        ToolDefinition {
+            name: self.name.clone(),
+            description,
+            parameters: json!({
+                "type": "object",
+                "properties": properties,
+                "required": self.config.params.keys().collect::<Vec<_>>()
+            }),
+        }
+    }
+
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+        let command = self.render(&args)?;
+        let child = bash::spawn_shell(&self.base_dir, &command).map_err(BashCommandError::Io)?;
+        Ok(bash::wait_for_output(child).await?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let tool = CustomTool {
+            name: "grep_make".to_string(),
+            config: CustomToolConfig {
+                command: "make -qp | grep -F {target} | head -n {limit}".to_string(),
+                description: String::new(),
+                params: BTreeMap::from([
+                    ("target".to_string(), "Target name".to_string()),
+                    ("limit".to_string(), "Lines".to_string()),
+                ]),
+            },
+            base_dir: PathBuf::from("."),
+        };
+
+        let args = json!({ "target": "it's; rm -rf /", "limit": 5 });
+        assert_eq!(
+            tool.render(args.as_object().unwrap()).unwrap(),
+            r"make -qp | grep -F 'it'\''s; rm -rf /' | head -n '5'"
+        );
+        // A value can't smuggle in a placeholder that closes its quotes
+        let args = json!({ "target": "{limit}", "limit": "; rm -rf /" });
+        assert_eq!(
+            tool.render(args.as_object().unwrap()).unwrap(),
+            "make -qp | grep -F '{limit}' | head -n '; rm -rf /'"
+        );
+        assert!(matches!(
+            tool.render(json!({ "target": "all" }).as_object().unwrap()),
+            Err(CustomToolError::MissingArgument(_))
+        ));
+    }
+
+    #[test]
+    fn test_valid_name() {
+        assert!(valid_name("make_targets"));
+        assert!(!valid_name("bash"));
+        assert!(!valid_name("make targets"));
+        assert!(!valid_name(""));
+    }
+}
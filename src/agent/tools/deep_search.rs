@@ -0,0 +1,377 @@
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::LazyLock;
+use std::time::Duration;
+
+use regex::Regex;
+use rig::completion::ToolDefinition;
+use rig::tool::Tool;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use thiserror::Error;
+use tokio::time::timeout;
+
+use crate::agent::scope;
+use crate::agent::tools::cache::{self, ResultCache};
+use crate::agent::tools::injection;
+
+const TIMEOUT_SECS: u64 = 30;
+
+/// Files listed in the consolidated result
+const MAX_FILES: usize = 25;
+
+/// Files whose name matches the query taken into the ranking
+const MAX_NAME_MATCHES: usize = 10;
+
+/// Lines shown per file
+const MAX_LINES_PER_FILE: usize = 3;
+
+/// Content matches collected per file
+const MAX_COUNT: usize = 5;
+
+/// Keywords that introduce a definition in common languages
+pub(crate) const DEFINITION_KEYWORDS: &str =
+    "fn|struct|enum|trait|impl|type|mod|class|def|func|function|interface|const|let|var|val";
+
+/// A `path:line:text` line of ripgrep output
+static RG_LINE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^(.+?):(\d+):(.*)$").expect("Invalid ripgrep line regex"));
+
+#[derive(Deserialize)]
+pub struct DeepSearchArgs {
+    /// A name, identifier or phrase to look for
+    pub query: String,
+    /// Optional directory to search in (defaults to the working directory)
+    pub path: Option<String>,
+}
+
+#[derive(Debug, Error)]
+pub enum DeepSearchError {
+    #[error("Search query is empty")]
+    EmptyQuery,
+    #[error("Path traversal not allowed: {0}")]
+    PathTraversal(String),
+    #[error("Every search strategy failed: {0}")]
+    Failed(String),
+}
+
+/// What the strategies found in one file
+#[derive(Debug, Default)]
+struct FileHits {
+    /// Fuzzy score of the path against the query, if it is among the best
+    name_score: Option<u32>,
+    /// Lines that define the query
+    definitions: Vec<(u32, String)>,
+    /// Other lines that mention the query
+    matches: Vec<(u32, String)>,
+}
+
+impl FileHits {
+    /// Definitions weigh most, then a matching name, then the mentions
+    fn rank(&self) -> usize {
+        10 * self.definitions.len().min(1)
+            + self.name_score.map_or(0, |_| 5)
+            + self.matches.len().min(MAX_COUNT)
+    }
+}
+
+/// Searches file names, contents and definitions at once and returns a
+/// single ranked list of files
+#[derive(Deserialize, Serialize)]
+pub struct DeepSearch {
+    #[serde(skip)]
+    base_dir: PathBuf,
+    #[serde(skip)]
+    cache: ResultCache,
+}
+
+impl DeepSearch {
+    pub fn new(base_dir: PathBuf) -> Self {
+        Self {
+            base_dir,
+            cache: ResultCache::default(),
+        }
+    }
+
+    /// Run ripgrep, returning its stdout; no matches is an empty output
+    async fn rg(&self, args: &[&str]) -> Result<String, String> {
+        let output = timeout(
+            Duration::from_secs(TIMEOUT_SECS),
+            scope::search_command(&self.base_dir, "rg", args).output(),
+        )
+        .await
+        .map_err(|_| format!("rg timed out after {TIMEOUT_SECS} seconds"))?
+        .map_err(|e| format!("rg: {e}"))?;
+
+        match output.status.code() {
+            Some(0 | 1) => Ok(String::from_utf8_lossy(&output.stdout).into_owned()),
+            _ => Err(String::from_utf8_lossy(&output.stderr).trim().to_string()),
+        }
+    }
+
+    /// Files whose path fuzzily matches the query, best first
+    async fn search_names(&self, query: &str, path: &str) -> Result<Vec<(String, u32)>, String> {
+        let files = self.rg(&["--files", path]).await?;
+        let mut scored: Vec<(String, u32)> = files
+            .lines()
+            .filter_map(|file| fuzzy_score(query, file).map(|score| (file.to_string(), score)))
+            .collect();
+        scored.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        scored.truncate(MAX_NAME_MATCHES);
+        Ok(scored)
+    }
+
+    /// Lines containing the query literally, case-insensitively
+    async fn search_contents(&self, query: &str, path: &str) -> Result<String, String> {
+        let max_count = MAX_COUNT.to_string();
+        self.rg(&[
+            "-n",
+            "-i",
+            "--fixed-strings",
+            "--max-count",
+            &max_count,
+            "--max-columns",
+            "200",
+            "--",
+            query,
+            path,
+        ])
+        .await
+    }
+
+    /// Lines that define the query as a function, type, variable, ...
+    async fn search_definitions(&self, query: &str, path: &str) -> Result<String, String> {
+        // Only identifiers can be defined
+        if query.contains(char::is_whitespace) {
+            Ok(String::new())
+        } else {
+            let pattern = format!(r"\b({DEFINITION_KEYWORDS})\s+{}\b", regex::escape(query));
+            self.rg(&["-n", "--max-columns", "200", "-e", &pattern, "--", path])
+                .await
+        }
+    }
+
+    async fn search(&self, query: &str, path: &str) -> Result<String, DeepSearchError> {
+        let (names, contents, definitions) = tokio::join!(
+            self.search_names(query, path),
+            self.search_contents(query, path),
+            self.search_definitions(query, path),
+        );
+
+        let errors: Vec<&String> = [
+            names.as_ref().err(),
+            contents.as_ref().err(),
+            definitions.as_ref().err(),
+        ]
+        .into_iter()
+        .flatten()
+        .collect();
+        if errors.len() == 3 {
+            Err(DeepSearchError::Failed(
+                errors
+                    .iter()
+                    .map(|e| e.as_str())
+                    .collect::<Vec<_>>()
+                    .join("; "),
+            ))
+        } else {
+            let mut hits: BTreeMap<String, FileHits> = BTreeMap::new();
+            for (file, score) in names.unwrap_or_default() {
+                hits.entry(file).or_default().name_score = Some(score);
+            }
+            for (file, line, text) in parse_rg_lines(&definitions.unwrap_or_default()) {
+                hits.entry(file).or_default().definitions.push((line, text));
+            }
+            for (file, line, text) in parse_rg_lines(&contents.unwrap_or_default()) {
+                let file_hits = hits.entry(file).or_default();
+                if !file_hits.definitions.iter().any(|(l, _)| *l == line) {
+                    file_hits.matches.push((line, text));
+                }
+            }
+            Ok(format_hits(hits))
+        }
+    }
+}
+
+/// Score of `path` for a fuzzy `query`: the query's characters must appear
+/// in order. Consecutive characters and matches in the file name score
+/// higher.
+fn fuzzy_score(query: &str, path: &str) -> Option<u32> {
+    let path = path.to_lowercase();
+    let name_start = path.rfind('/').map_or(0, |i| i + 1);
+    let mut chars = path.char_indices();
+    let mut score = 0;
+    let mut last_index: Option<usize> = None;
+
+    for q in query.to_lowercase().chars().filter(|c| !c.is_whitespace()) {
+        let (index, _) = chars.by_ref().find(|(_, c)| *c == q)?;
+        score += 1;
+        if last_index.is_some_and(|last| last + 1 == index) {
+            score += 2;
+        }
+        if index >= name_start {
+            score += 1;
+        }
+        last_index = Some(index);
+    }
+
+    let name = &path[name_start..];
+    let whole = query.to_lowercase().replace(char::is_whitespace, "");
+    Some(if name.contains(&whole) {
+        score + 10
+    } else {
+        score
+    })
+}
+
+/// `(path, line, text)` of each `path:line:text` line
+fn parse_rg_lines(output: &str) -> Vec<(String, u32, String)> {
+    output
+        .lines()
+        .filter_map(|line| RG_LINE.captures(line))
+        .filter_map(|caps| {
+            let line = caps[2].parse().ok()?;
+            Some((caps[1].to_string(), line, caps[3].trim().to_string()))
+        })
+        .collect()
+}
+
+/// The files ranked best first, each with its best lines
+fn format_hits(hits: BTreeMap<String, FileHits>) -> String {
+    let mut ranked: Vec<(String, FileHits)> = hits.into_iter().collect();
+    ranked.sort_by(|a, b| b.1.rank().cmp(&a.1.rank()).then_with(|| a.0.cmp(&b.0)));
+
+    let entries: Vec<String> = ranked
+        .iter()
+        .take(MAX_FILES)
+        .enumerate()
+        .map(|(i, (file, hits))| {
+            let mut found = Vec::new();
+            if hits.name_score.is_some() {
+                found.push("name".to_string());
+            }
+            if !hits.definitions.is_empty() {
+                found.push("definition".to_string());
+            }
+            match hits.matches.len() {
+                0 => {}
+                1 => found.push("1 match".to_string()),
+                n => found.push(format!("{n} matches")),
+            }
+
+            let mut entry = format!("{}. {file} ({})", i + 1, found.join(", "));
+            for (line, text) in hits
+                .definitions
+                .iter()
+                .chain(&hits.matches)
+                .take(MAX_LINES_PER_FILE)
+            {
+                entry.push_str(&format!("\n   {file}:{line}: {text}"));
+            }
+            entry
+        })
+        .collect();
+
+    if entries.is_empty() {
+        "No matches found".to_string()
+    } else {
+        entries.join("\n")
+    }
+}
+
+impl Tool for DeepSearch {
+    const NAME: &'static str = "deep_search";
+
+    type Error = DeepSearchError;
+    type Args = DeepSearchArgs;
+    type Output = String;
+
+    async fn definition(&self, _prompt: String) -> ToolDefinition {
+        ToolDefinition {
+            name: Self::NAME.to_string(),
+            description: "Find where something is in the codebase in one call: searches file \
+                names (fuzzy), file contents and definitions (functions, types, variables, ...) \
+                at once and returns files ranked by relevance, with their best matching lines. \
+                Use it as the first step when looking for a name or concept."
+                .to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "query": {
+                        "type": "string",
+                        "description": "A name, identifier or phrase to look for"
+                    },
+                    "path": {
+                        "type": "string",
+                        "description": "Optional directory to search in (defaults to the working directory)"
+                    }
+                },
+                "required": ["query"]
+            }),
+        }
+    }
+
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+        let query = args.query.trim();
+        let path = args.path.as_deref().unwrap_or(".");
+
+        if query.is_empty() {
+            Err(DeepSearchError::EmptyQuery)
+        } else if path.contains("..") || path.starts_with('/') {
+            Err(DeepSearchError::PathTraversal(path.to_string()))
+        } else {
+            let key = format!("{query}\0{path}");
+            match self.cache.get(&key).await {
+                Some(cached) => Ok(cached),
+                None => {
+                    let output = injection::guard(self.search(query, path).await?);
+                    let sources = cache::sources(&self.base_dir, &[path], &output);
+                    self.cache.insert(key, &output, sources).await;
+                    Ok(output)
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fuzzy_score() {
+        assert_eq!(fuzzy_score("xyz", "src/main.rs"), None);
+        let exact = fuzzy_score("policy", "src/agent/policy.rs").unwrap();
+        let scattered = fuzzy_score("policy", "src/plugin/console/yaml.rs").unwrap_or(0);
+        assert!(exact > scattered);
+    }
+
+    #[test]
+    fn test_format_hits() {
+        let definitions = "src/agent/policy.rs:92:pub fn decide(tool: &str) -> Action {";
+        let contents = "src/agent/policy.rs:92:pub fn decide(tool: &str) -> Action {\n\
+            src/agent/hooks.rs:212:        match policy::decide(tool_name, args) {\n\
+            src/agent/policy.rs:140:    decide(\"bash\", \"{}\")";
+
+        let mut hits: BTreeMap<String, FileHits> = BTreeMap::new();
+        for (file, line, text) in parse_rg_lines(definitions) {
+            hits.entry(file).or_default().definitions.push((line, text));
+        }
+        for (file, line, text) in parse_rg_lines(contents) {
+            let file_hits = hits.entry(file).or_default();
+            if !file_hits.definitions.iter().any(|(l, _)| *l == line) {
+                file_hits.matches.push((line, text));
+            }
+        }
+
+        assert_eq!(
+            format_hits(hits),
+            "1. src/agent/policy.rs (definition, 1 match)\n   \
+             src/agent/policy.rs:92: pub fn decide(tool: &str) -> Action {\n   \
+             src/agent/policy.rs:140: decide(\"bash\", \"{}\")\n\
+             2. src/agent/hooks.rs (1 match)\n   \
+             src/agent/hooks.rs:212: match policy::decide(tool_name, args) {"
+        );
+        assert_eq!(format_hits(BTreeMap::new()), "No matches found");
+    }
+}
@@ -0,0 +1,464 @@
+use std::collections::HashMap;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use rig::completion::ToolDefinition;
+use rig::tool::Tool;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use thiserror::Error;
+use tokio::time::timeout;
+
+use crate::agent::tools::cache::{self, ResultCache};
+use crate::agent::tools::injection;
+use crate::agent::{remote, scope};
+
+const TIMEOUT_SECS: u64 = 30;
+
+/// Source files compared, at most
+const MAX_FILES: usize = 1000;
+
+/// Larger files, usually generated, are skipped
+const MAX_FILE_BYTES: usize = 100 * 1024; // 100KB
+
+/// Tokens per shingle; blocks shorter than this aren't found
+const WINDOW_TOKENS: usize = 40;
+
+/// Shingles occurring more often than this are boilerplate, not duplication
+const MAX_OCCURRENCES: usize = 8;
+
+/// Blocks reported, at most
+const MAX_BLOCKS: usize = 20;
+
+/// Default for the smallest block reported, in lines
+const DEFAULT_MIN_LINES: usize = 6;
+
+/// Base of the rolling hash
+const HASH_BASE: u64 = 1_000_003;
+
+/// Extensions of the source files compared
+const SOURCE_EXTENSIONS: &[&str] = &[
+    "rs", "c", "h", "cc", "cpp", "hpp", "cs", "go", "java", "kt", "scala", "swift", "js", "jsx",
+    "ts", "tsx", "mjs", "py", "rb", "php", "lua", "sh", "sql", "dart", "ex", "exs", "hs", "ml",
+    "zig", "vue",
+];
+
+#[derive(Deserialize)]
+pub struct FindDuplicatesArgs {
+    /// Optional directory to compare the files of (defaults to the working directory)
+    pub path: Option<String>,
+    /// Smallest block reported, in lines
+    pub min_lines: Option<usize>,
+}
+
+#[derive(Debug, Error)]
+pub enum FindDuplicatesError {
+    #[error("Path traversal not allowed: {0}")]
+    PathTraversal(String),
+    #[error("Listing files timed out after {0} seconds")]
+    Timeout(u64),
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Listing files failed: {0}")]
+    ListFailed(String),
+}
+
+/// A source file as normalized tokens
+struct Tokenized {
+    path: String,
+    /// Hash of each token
+    tokens: Vec<u64>,
+    /// Line of each token
+    lines: Vec<u32>,
+}
+
+/// Lines of one copy of a duplicated block
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Location {
+    path: String,
+    start: u32,
+    end: u32,
+}
+
+/// Two similar stretches of code
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Block {
+    first: Location,
+    second: Location,
+    /// Tokens covered by matching shingles
+    matched: usize,
+    /// Tokens from the start of the first match to the end of the last
+    span: usize,
+}
+
+impl Block {
+    fn similarity(&self) -> usize {
+        100 * self.matched / self.span.max(1)
+    }
+}
+
+/// Finds similar blocks of code across the source files of the workspace
+#[derive(Deserialize, Serialize)]
+pub struct FindDuplicates {
+    #[serde(skip)]
+    base_dir: PathBuf,
+    #[serde(skip)]
+    cache: ResultCache,
+}
+
+impl FindDuplicates {
+    pub fn new(base_dir: PathBuf) -> Self {
+        Self {
+            base_dir,
+            cache: ResultCache::default(),
+        }
+    }
+
+    /// Source files under `path`, as listed by ripgrep (so ignored files
+    /// are left out)
+    async fn source_files(&self, path: &str) -> Result<Vec<String>, FindDuplicatesError> {
+        let output = timeout(
+            Duration::from_secs(TIMEOUT_SECS),
+            scope::search_command(&self.base_dir, "rg", &["--files", "--", path]).output(),
+        )
+        .await
+        .map_err(|_| FindDuplicatesError::Timeout(TIMEOUT_SECS))??;
+
+        match output.status.code() {
+            Some(0 | 1) => {
+                let mut files: Vec<String> = String::from_utf8_lossy(&output.stdout)
+                    .lines()
+                    .filter(|file| is_source(file))
+                    .map(|file| file.trim_start_matches("./").to_string())
+                    .collect();
+                files.sort();
+                files.truncate(MAX_FILES);
+                Ok(files)
+            }
+            _ => Err(FindDuplicatesError::ListFailed(
+                String::from_utf8_lossy(&output.stderr).trim().to_string(),
+            )),
+        }
+    }
+
+    async fn find(&self, path: &str, min_lines: usize) -> Result<String, FindDuplicatesError> {
+        let files = self.source_files(path).await?;
+        let mut tokenized = Vec::new();
+        for file in &files {
+            if let Ok(text) = remote::read_to_string(&self.base_dir, file).await
+                && text.len() <= MAX_FILE_BYTES
+            {
+                tokenized.push(tokenize(file, &text));
+            }
+        }
+        Ok(render(
+            &find_blocks(&tokenized, min_lines),
+            tokenized.len(),
+            min_lines,
+        ))
+    }
+}
+
+impl Tool for FindDuplicates {
+    const NAME: &'static str = "find_duplicates";
+
+    type Error = FindDuplicatesError;
+    type Args = FindDuplicatesArgs;
+    type Output = String;
+
+    async fn definition(&self, _prompt: String) -> ToolDefinition {
+        ToolDefinition {
+            name: Self::NAME.to_string(),
+            description: "Find duplicated and near-duplicated blocks of code across the \
+                source files: compares normalized token sequences (ignoring whitespace, \
+                comment lines and literal values) and returns pairs of similar blocks with \
+                their locations and similarity. Use it to answer whether logic is duplicated \
+                anywhere, then read_file the locations to compare them."
+                .to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "path": {
+                        "type": "string",
+                        "description": "Optional directory to compare the files of (defaults to the working directory)"
+                    },
+                    "min_lines": {
+                        "type": "integer",
+                        "description": format!("Smallest block reported, in lines (defaults to {DEFAULT_MIN_LINES})")
+                    }
+                }
+            }),
+        }
+    }
+
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+        let path = args.path.as_deref().unwrap_or(".");
+        let min_lines = args.min_lines.unwrap_or(DEFAULT_MIN_LINES).max(1);
+
+        if path.contains("..") || path.starts_with('/') {
+            Err(FindDuplicatesError::PathTraversal(path.to_string()))
+        } else {
+            let key = format!("{path}\0{min_lines}");
+            match self.cache.get(&key).await {
+                Some(cached) => Ok(cached),
+                None => {
+                    let output = injection::guard(self.find(path, min_lines).await?);
+                    let sources = cache::sources(&self.base_dir, &[path], &output);
+                    self.cache.insert(key, &output, sources).await;
+                    Ok(output)
+                }
+            }
+        }
+    }
+}
+
+fn is_source(file: &str) -> bool {
+    Path::new(file)
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| SOURCE_EXTENSIONS.contains(&extension))
+}
+
+fn hash(token: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    token.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Split a file into tokens: words, literals and single punctuation
+/// characters. Literal values are normalized so copies differing only in
+/// a string or number still match; comment lines are skipped.
+fn tokenize(path: &str, text: &str) -> Tokenized {
+    let mut tokenized = Tokenized {
+        path: path.to_string(),
+        tokens: Vec::new(),
+        lines: Vec::new(),
+    };
+    for (number, line) in (1..).zip(text.lines()) {
+        let trimmed = line.trim_start();
+        let comment = ["//", "#", "--", "/*", "*"]
+            .iter()
+            .any(|prefix| trimmed.starts_with(prefix));
+        let mut chars = trimmed.chars().peekable();
+        while let (false, Some(c)) = (comment, chars.next()) {
+            let token = match c {
+                c if c.is_whitespace() => None,
+                c if c.is_alphanumeric() || c == '_' => {
+                    let mut word = c.to_string();
+                    while let Some(c) = chars.next_if(|c| c.is_alphanumeric() || *c == '_') {
+                        word.push(c);
+                    }
+                    Some(if c.is_ascii_digit() {
+                        "0".to_string()
+                    } else {
+                        word
+                    })
+                }
+                '"' | '\'' | '`' => {
+                    let mut escaped = false;
+                    for next in chars.by_ref() {
+                        match next {
+                            _ if escaped => escaped = false,
+                            '\\' => escaped = true,
+                            next if next == c => break,
+                            _ => {}
+                        }
+                    }
+                    Some("\"\"".to_string())
+                }
+                c => Some(c.to_string()),
+            };
+            if let Some(token) = token {
+                tokenized.tokens.push(hash(&token));
+                tokenized.lines.push(number);
+            }
+        }
+    }
+    tokenized
+}
+
+/// Hashes of every `WINDOW_TOKENS`-long window of `tokens`, by start
+fn shingles(tokens: &[u64]) -> Vec<u64> {
+    let top = (1..WINDOW_TOKENS).fold(1u64, |power, _| power.wrapping_mul(HASH_BASE));
+    let mut hashes = Vec::new();
+    let mut rolling = 0u64;
+    for (index, token) in tokens.iter().enumerate() {
+        if index >= WINDOW_TOKENS {
+            rolling = rolling.wrapping_sub(tokens[index - WINDOW_TOKENS].wrapping_mul(top));
+        }
+        rolling = rolling.wrapping_mul(HASH_BASE).wrapping_add(*token);
+        if index + 1 >= WINDOW_TOKENS {
+            hashes.push(rolling);
+        }
+    }
+    hashes
+}
+
+/// Pairs of similar blocks of at least `min_lines` lines, most tokens first
+fn find_blocks(files: &[Tokenized], min_lines: usize) -> Vec<Block> {
+    // Where each shingle occurs, as file and window start
+    let mut occurrences: HashMap<u64, Vec<(usize, usize)>> = HashMap::new();
+    for (file, tokenized) in files.iter().enumerate() {
+        for (start, shingle) in shingles(&tokenized.tokens).into_iter().enumerate() {
+            occurrences.entry(shingle).or_default().push((file, start));
+        }
+    }
+
+    // Shared shingles as (first file, second file, offset of the second
+    // copy, start in the first file)
+    let mut matches: Vec<(usize, usize, isize, usize)> = Vec::new();
+    for places in occurrences.values() {
+        if (2..=MAX_OCCURRENCES).contains(&places.len()) {
+            for (i, &(file_a, start_a)) in places.iter().enumerate() {
+                for &(file_b, start_b) in &places[i + 1..] {
+                    // Overlapping windows of one file aren't copies of each other
+                    if file_a != file_b || start_b >= start_a + WINDOW_TOKENS {
+                        matches.push((
+                            file_a,
+                            file_b,
+                            start_b as isize - start_a as isize,
+                            start_a,
+                        ));
+                    }
+                }
+            }
+        }
+    }
+    matches.sort_unstable();
+
+    // Runs of shingles along the same offset are one block; gaps shorter
+    // than a window are edits within it, which makes it a near-duplicate
+    let mut blocks = Vec::new();
+    let mut index = 0;
+    while index < matches.len() {
+        let (file_a, file_b, offset, first) = matches[index];
+        let (mut last, mut matched) = (first, WINDOW_TOKENS);
+        index += 1;
+        while let Some(&(a, b, o, start)) = matches.get(index)
+            && (a, b, o) == (file_a, file_b, offset)
+            && start - last <= WINDOW_TOKENS
+        {
+            matched += (start - last).min(WINDOW_TOKENS);
+            last = start;
+            index += 1;
+        }
+
+        let end = last + WINDOW_TOKENS - 1;
+        let location = |file: usize, start: usize, end: usize| Location {
+            path: files[file].path.clone(),
+            start: files[file].lines[start],
+            end: files[file].lines[end],
+        };
+        let first_copy = location(file_a, first, end);
+        if (first_copy.end - first_copy.start + 1) as usize >= min_lines {
+            blocks.push(Block {
+                second: location(
+                    file_b,
+                    first.saturating_add_signed(offset),
+                    end.saturating_add_signed(offset),
+                ),
+                first: first_copy,
+                matched,
+                span: end - first + 1,
+            });
+        }
+    }
+    blocks.sort_by(|a, b| {
+        b.matched
+            .cmp(&a.matched)
+            .then_with(|| a.first.path.cmp(&b.first.path))
+            .then_with(|| a.first.start.cmp(&b.first.start))
+    });
+    blocks
+}
+
+fn render(blocks: &[Block], files: usize, min_lines: usize) -> String {
+    if blocks.is_empty() {
+        format!("No duplicated blocks of {min_lines} or more lines found in {files} source files")
+    } else {
+        let mut out = format!(
+            "{} duplicated blocks of {min_lines} or more lines found in {files} source files{}:\n",
+            blocks.len(),
+            if blocks.len() > MAX_BLOCKS {
+                format!(", the {MAX_BLOCKS} largest shown")
+            } else {
+                String::new()
+            }
+        );
+        for (number, block) in (1..).zip(blocks.iter().take(MAX_BLOCKS)) {
+            out.push_str(&format!(
+                "\n{number}. {} lines, {}% similar\n   {}:{}-{}\n   {}:{}-{}\n",
+                block.first.end - block.first.start + 1,
+                block.similarity(),
+                block.first.path,
+                block.first.start,
+                block.first.end,
+                block.second.path,
+                block.second.start,
+                block.second.end
+            ));
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A function of `lines` statements using `name` and `value`
+    fn function(name: &str, value: &str, lines: usize) -> String {
+        let body: String = (0..lines)
+            .map(|i| format!("    let {name}_{i} = compute({name}, \"{value}\", {i});\n"))
+            .collect();
+        format!("fn {name}() {{\n{body}}}\n")
+    }
+
+    #[test]
+    fn test_tokenize() {
+        let a = tokenize("a.rs", "let x = \"one\" + 1;\n// comment\n");
+        let b = tokenize("b.rs", "let  x = 'two' + 22;\n");
+        assert_eq!(a.tokens, b.tokens);
+        assert_eq!(a.lines, [1; 7]);
+    }
+
+    #[test]
+    fn test_find_blocks() {
+        let original = format!("use std::io;\n\n{}", function("load", "a", 10));
+        let copy = format!(
+            "// copied\n{}\nfn other() {{}}\n",
+            function("load", "b", 10)
+        );
+        let unrelated = "fn main() {\n    println!(\"hello\");\n}\n";
+        let files = [
+            tokenize("src/a.rs", &original),
+            tokenize("src/b.rs", &copy),
+            tokenize("src/c.rs", unrelated),
+        ];
+
+        let blocks = find_blocks(&files, DEFAULT_MIN_LINES);
+        assert_eq!(blocks.len(), 1);
+        assert_eq!(
+            blocks[0].first,
+            Location {
+                path: "src/a.rs".to_string(),
+                start: 3,
+                end: 14
+            }
+        );
+        assert_eq!((blocks[0].second.start, blocks[0].second.end), (2, 13));
+        assert_eq!(blocks[0].similarity(), 100);
+
+        assert!(find_blocks(&files, 20).is_empty());
+        assert!(render(&blocks, 3, 6).contains("\n1. 12 lines, 100% similar\n   src/a.rs:3-14\n"));
+    }
+
+    #[test]
+    fn test_find_blocks_in_one_file() {
+        let text = format!("{}\n{}", function("load", "a", 8), function("load", "b", 8));
+        let blocks = find_blocks(&[tokenize("src/a.rs", &text)], DEFAULT_MIN_LINES);
+        assert_eq!(blocks.len(), 1);
+        assert_eq!((blocks[0].first.start, blocks[0].second.start), (1, 12));
+    }
+}
@@ -0,0 +1,459 @@
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
+use std::time::Duration;
+
+use regex::Regex;
+use rig::completion::ToolDefinition;
+use rig::tool::Tool;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use thiserror::Error;
+use tokio::time::timeout;
+
+use crate::agent::tools::cache::{self, ResultCache};
+use crate::agent::tools::injection;
+use crate::agent::{remote, scope};
+
+const TIMEOUT_SECS: u64 = 30;
+
+/// Markers listed, at most
+const MAX_MARKERS: usize = 300;
+
+/// Files blamed for the authors of their markers, at most
+const MAX_BLAMED_FILES: usize = 30;
+
+/// Markers looked for, in the order they are counted
+const MARKERS: [&str; 4] = ["TODO", "FIXME", "HACK", "XXX"];
+
+/// Author shown when blame isn't available for a line
+const UNKNOWN_AUTHOR: &str = "unknown";
+
+/// A `path:line:text` line of ripgrep output
+static RG_LINE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^(.+?):(\d+):(.*)$").expect("Invalid ripgrep line regex"));
+
+/// A marker as a whole word
+static MARKER: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\b(TODO|FIXME|HACK|XXX)\b").expect("Invalid marker regex"));
+
+/// The header of a `git blame --line-porcelain` entry; captures the line
+/// number in the final file
+static BLAME_HEADER: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^[0-9a-f]{40} \d+ (\d+)").expect("Invalid blame header regex"));
+
+#[derive(Deserialize)]
+pub struct FindTodosArgs {
+    /// Optional directory to scan (defaults to the working directory)
+    pub path: Option<String>,
+}
+
+#[derive(Debug, Error)]
+pub enum FindTodosError {
+    #[error("Path traversal not allowed: {0}")]
+    PathTraversal(String),
+    #[error("Scan timed out after {0} seconds")]
+    Timeout(u64),
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Scan failed: {0}")]
+    ScanFailed(String),
+}
+
+/// A marker found in a comment
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Todo {
+    line: u32,
+    marker: &'static str,
+    /// The comment from the marker on
+    text: String,
+    author: Option<Author>,
+}
+
+/// Who last changed a line, from blame
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Author {
+    name: String,
+    /// Date of the change, as `YYYY-MM-DD`
+    date: String,
+}
+
+/// Lists TODO/FIXME/HACK/XXX comments of the workspace, grouped by file and
+/// author
+#[derive(Deserialize, Serialize)]
+pub struct FindTodos {
+    #[serde(skip)]
+    base_dir: PathBuf,
+    #[serde(skip)]
+    cache: ResultCache,
+}
+
+impl FindTodos {
+    pub fn new(base_dir: PathBuf) -> Self {
+        Self {
+            base_dir,
+            cache: ResultCache::default(),
+        }
+    }
+
+    /// Markers in comments under `path`, by file
+    async fn scan(&self, path: &str) -> Result<BTreeMap<String, Vec<Todo>>, FindTodosError> {
+        let output = timeout(
+            Duration::from_secs(TIMEOUT_SECS),
+            scope::search_command(
+                &self.base_dir,
+                "rg",
+                &[
+                    "-n",
+                    "--no-heading",
+                    "--color",
+                    "never",
+                    "--max-columns",
+                    "300",
+                    "-e",
+                    MARKER.as_str(),
+                    "--",
+                    path,
+                ],
+            )
+            .output(),
+        )
+        .await
+        .map_err(|_| FindTodosError::Timeout(TIMEOUT_SECS))??;
+
+        match output.status.code() {
+            // 1 is no matches
+            Some(0 | 1) => Ok(parse_matches(&String::from_utf8_lossy(&output.stdout))),
+            _ => Err(FindTodosError::ScanFailed(
+                String::from_utf8_lossy(&output.stderr).trim().to_string(),
+            )),
+        }
+    }
+
+    /// Authors of `lines` of `file`; empty when blame isn't available, e.g.
+    /// outside a git repository or for untracked files
+    async fn blame(&self, file: &str, lines: &[u32]) -> HashMap<u32, Author> {
+        let ranges: Vec<String> = lines
+            .iter()
+            .map(|line| format!("-L{line},{line}"))
+            .collect();
+        let args: Vec<&str> = ["blame", "--line-porcelain"]
+            .into_iter()
+            .chain(ranges.iter().map(String::as_str))
+            .chain(["--", file])
+            .collect();
+        let output = timeout(
+            Duration::from_secs(TIMEOUT_SECS),
+            remote::command(&self.base_dir, "git", &args).output(),
+        )
+        .await;
+
+        match output {
+            Ok(Ok(output)) if output.status.success() => {
+                parse_blame(&String::from_utf8_lossy(&output.stdout))
+            }
+            _ => HashMap::new(),
+        }
+    }
+
+    async fn find(&self, path: &str) -> Result<String, FindTodosError> {
+        let mut files = self.scan(path).await?;
+        for (file, todos) in files.iter_mut().take(MAX_BLAMED_FILES) {
+            let lines: Vec<u32> = todos.iter().map(|todo| todo.line).collect();
+            let mut authors = self.blame(file, &lines).await;
+            for todo in todos.iter_mut() {
+                todo.author = authors.remove(&todo.line);
+            }
+        }
+        Ok(render(&files))
+    }
+}
+
+impl Tool for FindTodos {
+    const NAME: &'static str = "find_todos";
+
+    type Error = FindTodosError;
+    type Args = FindTodosArgs;
+    type Output = String;
+
+    async fn definition(&self, _prompt: String) -> ToolDefinition {
+        ToolDefinition {
+            name: Self::NAME.to_string(),
+            description: "List the TODO, FIXME, HACK and XXX comments of the codebase, grouped \
+                by file, with who last changed each line and when (from git blame), plus a \
+                count per marker and per author. Use it to find known gaps, workarounds and \
+                unfinished work."
+                .to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "path": {
+                        "type": "string",
+                        "description": "Optional directory to scan (defaults to the working directory)"
+                    }
+                }
+            }),
+        }
+    }
+
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+        let path = args.path.as_deref().unwrap_or(".");
+
+        if path.contains("..") || path.starts_with('/') {
+            Err(FindTodosError::PathTraversal(path.to_string()))
+        } else {
+            match self.cache.get(path).await {
+                Some(cached) => Ok(cached),
+                None => {
+                    let output = injection::guard(self.find(path).await?);
+                    let sources = cache::sources(&self.base_dir, &[path], &output);
+                    self.cache.insert(path.to_string(), &output, sources).await;
+                    Ok(output)
+                }
+            }
+        }
+    }
+}
+
+/// Markers of ripgrep output that are in comments, by file
+fn parse_matches(output: &str) -> BTreeMap<String, Vec<Todo>> {
+    let mut files: BTreeMap<String, Vec<Todo>> = BTreeMap::new();
+    for caps in output.lines().filter_map(|line| RG_LINE.captures(line)) {
+        let file = caps[1].trim_start_matches("./");
+        let text = &caps[3];
+        if let Ok(line) = caps[2].parse()
+            && let Some(found) = MARKER.find(text)
+            && in_comment(file, &text[..found.start()])
+        {
+            let marker = MARKERS
+                .into_iter()
+                .find(|marker| *marker == found.as_str())
+                .unwrap_or(MARKERS[0]);
+            let text = text[found.start()..]
+                .trim_end()
+                .trim_end_matches("*/")
+                .trim_end_matches("-->")
+                .trim_end()
+                .to_string();
+            files.entry(file.to_string()).or_default().push(Todo {
+                line,
+                marker,
+                text,
+                author: None,
+            });
+        }
+    }
+    files
+}
+
+/// Comment openers of the language of `file`; none if it isn't known
+fn comment_tokens(file: &str) -> &'static [&'static str] {
+    let name = Path::new(file)
+        .file_name()
+        .map(|name| name.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    let extension = name.rsplit_once('.').map_or("", |(_, extension)| extension);
+
+    match (name.as_str(), extension) {
+        ("makefile" | "dockerfile" | "gemfile" | "rakefile", _) => &["#"],
+        (
+            _,
+            "rs" | "c" | "h" | "cc" | "cpp" | "hpp" | "cs" | "go" | "java" | "kt" | "kts" | "scala"
+            | "swift" | "js" | "jsx" | "ts" | "tsx" | "mjs" | "dart" | "proto" | "zig" | "css"
+            | "scss" | "less",
+        ) => &["//", "/*", "*"],
+        (_, "php") => &["//", "/*", "*", "#"],
+        (
+            _,
+            "py" | "sh" | "bash" | "zsh" | "fish" | "rb" | "pl" | "pm" | "r" | "yaml" | "yml"
+            | "toml" | "cfg" | "conf" | "nix" | "tf" | "cmake" | "ps1" | "ex" | "exs" | "jl" | "mk",
+        ) => &["#"],
+        (_, "sql" | "lua" | "hs" | "elm" | "ada") => &["--"],
+        (_, "html" | "htm" | "xml" | "svg" | "vue" | "md" | "markdown") => &["<!--"],
+        (_, "lisp" | "el" | "clj" | "cljs" | "scm" | "asm" | "s" | "ini") => &[";"],
+        (_, "tex" | "erl" | "hrl" | "m") => &["%"],
+        _ => &[],
+    }
+}
+
+/// Whether a marker preceded by `before` on its line is in a comment.
+/// Files of unknown languages count every marker.
+fn in_comment(file: &str, before: &str) -> bool {
+    let tokens = comment_tokens(file);
+    tokens.is_empty()
+        || tokens.iter().any(|token| match *token {
+            // Continuation line of a block comment
+            "*" => before.trim_start().starts_with('*'),
+            token => before.contains(token),
+        })
+}
+
+/// Authors by final line number from `git blame --line-porcelain` output
+fn parse_blame(output: &str) -> HashMap<u32, Author> {
+    let mut authors = HashMap::new();
+    let mut current: Option<(u32, Option<String>)> = None;
+    for line in output.lines() {
+        if let Some(caps) = BLAME_HEADER.captures(line) {
+            current = caps[1].parse().ok().map(|number| (number, None));
+        } else if let Some(name) = line.strip_prefix("author ")
+            && let Some((_, author)) = current.as_mut()
+        {
+            *author = Some(name.to_string());
+        } else if let Some(time) = line.strip_prefix("author-time ")
+            && let Some((number, Some(name))) = current.take()
+        {
+            let date = time
+                .parse()
+                .ok()
+                .and_then(|time| chrono::DateTime::from_timestamp(time, 0))
+                .map_or_else(String::new, |time| time.format("%Y-%m-%d").to_string());
+            authors.insert(number, Author { name, date });
+        }
+    }
+    authors
+}
+
+/// The inventory: counts per marker, the markers by file, and counts per
+/// author
+fn render(files: &BTreeMap<String, Vec<Todo>>) -> String {
+    let todos: Vec<&Todo> = files.values().flatten().collect();
+    if todos.is_empty() {
+        "No TODO, FIXME, HACK or XXX comments found".to_string()
+    } else {
+        let counts: Vec<String> = MARKERS
+            .iter()
+            .map(|marker| {
+                let count = todos.iter().filter(|todo| todo.marker == *marker).count();
+                (marker, count)
+            })
+            .filter(|(_, count)| *count > 0)
+            .map(|(marker, count)| format!("{count} {marker}"))
+            .collect();
+        let mut out = format!(
+            "{} markers in {} files: {}\n",
+            todos.len(),
+            files.len(),
+            counts.join(", ")
+        );
+
+        let mut listed = 0;
+        for (file, todos) in files {
+            if listed < MAX_MARKERS {
+                out.push_str(&format!("\n{file}\n"));
+                for todo in todos.iter().take(MAX_MARKERS - listed) {
+                    let author = todo.author.as_ref().map_or_else(String::new, |author| {
+                        format!(" ({}, {})", author.name, author.date)
+                    });
+                    out.push_str(&format!("  {}: {}{author}\n", todo.line, todo.text));
+                }
+                listed += todos.len();
+            }
+        }
+        let unlisted = todos.len().saturating_sub(MAX_MARKERS);
+        if unlisted > 0 {
+            out.push_str(&format!(
+                "\n[{unlisted} more markers not listed; scan a subdirectory to see them]\n"
+            ));
+        }
+
+        let mut authors: BTreeMap<&str, usize> = BTreeMap::new();
+        for todo in &todos {
+            let name = todo
+                .author
+                .as_ref()
+                .map_or(UNKNOWN_AUTHOR, |author| author.name.as_str());
+            *authors.entry(name).or_default() += 1;
+        }
+        let mut authors: Vec<(&str, usize)> = authors.into_iter().collect();
+        authors.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+        out.push_str("\nBy author:\n");
+        for (name, count) in authors {
+            out.push_str(&format!("  {name}: {count}\n"));
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_matches() {
+        let output = "./src/main.rs:3:    // TODO: handle errors\n\
+                      ./src/main.rs:7:    let todo = \"TODO\";\n\
+                      ./src/lib.rs:12: * FIXME(ann): racy */\n\
+                      ./run.py:4:x = 1  # HACK around the cache\n\
+                      ./run.py:5:TODO = []\n\
+                      ./NOTES:1:XXX check this\n";
+        let files = parse_matches(output);
+
+        assert_eq!(
+            files["src/main.rs"]
+                .iter()
+                .map(|todo| (todo.line, todo.text.as_str()))
+                .collect::<Vec<_>>(),
+            [(3, "TODO: handle errors")]
+        );
+        assert_eq!(files["src/lib.rs"][0].text, "FIXME(ann): racy");
+        assert_eq!(files["src/lib.rs"][0].marker, "FIXME");
+        assert_eq!(files["run.py"].len(), 1);
+        assert_eq!(files["NOTES"][0].marker, "XXX");
+    }
+
+    #[test]
+    fn test_parse_blame() {
+        let output = "\
+0123456789abcdef0123456789abcdef01234567 2 3 1
+author Ann Lee
+author-mail <ann@example.com>
+author-time 1700000000
+author-tz +0000
+summary Add parser
+filename src/main.rs
+\t// TODO: handle errors
+";
+        let authors = parse_blame(output);
+        assert_eq!(
+            authors[&3],
+            Author {
+                name: "Ann Lee".to_string(),
+                date: "2023-11-14".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn test_render() {
+        let mut files = parse_matches("src/a.rs:1:// TODO one\nsrc/b.rs:2:// FIXME two\n");
+        files.get_mut("src/a.rs").unwrap()[0].author = Some(Author {
+            name: "Ann".to_string(),
+            date: "2024-01-02".to_string(),
+        });
+        let out = render(&files);
+
+        assert!(out.starts_with("2 markers in 2 files: 1 TODO, 1 FIXME\n"));
+        assert!(out.contains("src/a.rs\n  1: TODO one (Ann, 2024-01-02)\n"));
+        assert!(out.contains("By author:\n  Ann: 1\n  unknown: 1\n"));
+        assert_eq!(
+            render(&BTreeMap::new()),
+            "No TODO, FIXME, HACK or XXX comments found"
+        );
+    }
+
+    #[test]
+    fn test_render_unlisted() {
+        let matches: String = [("src/a.rs", 250), ("src/b.rs", 100), ("src/c.rs", 50)]
+            .into_iter()
+            .flat_map(|(file, count)| {
+                (1..=count).map(move |line| format!("{file}:{line}:// TODO item {line}\n"))
+            })
+            .collect();
+        let out = render(&parse_matches(&matches));
+
+        assert!(out.starts_with("400 markers in 3 files: 400 TODO\n"));
+        assert!(out.contains("\nsrc/b.rs\n  1: TODO item 1\n"));
+        assert!(!out.contains("\nsrc/c.rs\n"));
+        assert!(out.contains("[100 more markers not listed;"));
+    }
+}
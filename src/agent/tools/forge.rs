@@ -0,0 +1,455 @@
+use std::path::Path;
+
+use rig::completion::ToolDefinition;
+use rig::tool::Tool;
+use serde::Deserialize;
+use serde_json::json;
+use thiserror::Error;
+use tokio::process::Command;
+
+use crate::agent::keyring;
+
+mod gitea;
+mod github;
+mod gitlab;
+
+pub use gitea::Gitea;
+pub use github::GitHub;
+pub use gitlab::GitLab;
+
+const MAX_DIFF_BYTES: usize = 50 * 1024; // 50KB
+const MAX_COMMENTS: usize = 30;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ItemKind {
+    Issue,
+    PullRequest,
+}
+
+#[derive(Deserialize)]
+pub struct ForgeArgs {
+    /// Issue or pull/merge request number
+    pub number: u64,
+    /// Whether the number refers to an issue or a pull request; forges like
+    /// GitLab number them separately
+    pub kind: Option<ItemKind>,
+    /// Include the diff of a pull request
+    #[serde(default)]
+    pub diff: bool,
+}
+
+impl ForgeArgs {
+    /// A requested diff implies a pull request
+    fn item_kind(&self) -> ItemKind {
+        match (self.kind, self.diff) {
+            (Some(kind), false) => kind,
+            (_, true) => ItemKind::PullRequest,
+            (None, false) => ItemKind::Issue,
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum ForgeError {
+    #[error("HTTP error: {0}")]
+    Http(#[from] reqwest::Error),
+    #[error("{0} API returned {1}: {2}")]
+    Api(&'static str, u16, String),
+    #[error("#{0} is not a pull request, it has no diff")]
+    NotPullRequest(u64),
+}
+
+/// Forge software, for self-hosted instances whose host name doesn't tell
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ForgeType {
+    GitHub,
+    GitLab,
+    Gitea,
+}
+
+impl ForgeType {
+    pub const ALL: [Self; 3] = [Self::GitHub, Self::GitLab, Self::Gitea];
+
+    /// Name the token of the forge is kept under in the system keyring
+    pub fn account(self) -> &'static str {
+        match self {
+            Self::GitHub => "github",
+            Self::GitLab => "gitlab",
+            Self::Gitea => "gitea",
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::GitHub => "GitHub",
+            Self::GitLab => "GitLab",
+            Self::Gitea => "Gitea/Forgejo",
+        }
+    }
+}
+
+/// Host and repository path of a git remote
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Remote {
+    pub host: String,
+    /// `owner/name`, with GitLab subgroups in between
+    pub path: String,
+}
+
+impl Remote {
+    /// Parse `https://host/path(.git)`, `ssh://git@host[:port]/path(.git)`
+    /// and `git@host:path(.git)` remote URLs
+    pub fn from_url(url: &str) -> Option<Self> {
+        let (host, path) = match url.split_once("://") {
+            Some((_, rest)) => {
+                let (authority, path) = rest.split_once('/')?;
+                let host = authority.rsplit('@').next()?;
+                (host.split(':').next()?, path)
+            }
+            None => {
+                let (user_host, path) = url.split_once(':')?;
+                (user_host.rsplit('@').next()?, path)
+            }
+        };
+        let path = path.trim_matches('/');
+        let path = path.strip_suffix(".git").unwrap_or(path);
+
+        (!host.is_empty() && path.contains('/')).then(|| Self {
+            host: host.to_string(),
+            path: path.to_string(),
+        })
+    }
+
+    /// Guess the forge software from well-known hosts and host names
+    fn forge_type(&self) -> Option<ForgeType> {
+        let host = self.host.to_lowercase();
+        match host.as_str() {
+            "github.com" => Some(ForgeType::GitHub),
+            "gitlab.com" => Some(ForgeType::GitLab),
+            "codeberg.org" | "gitea.com" => Some(ForgeType::Gitea),
+            _ if host.contains("gitlab") => Some(ForgeType::GitLab),
+            _ if host.contains("gitea") || host.contains("forgejo") => Some(ForgeType::Gitea),
+            _ if host.contains("github") => Some(ForgeType::GitHub),
+            _ => None,
+        }
+    }
+}
+
+/// A comment on an issue or pull request
+#[derive(Debug, Clone, Default)]
+pub struct Comment {
+    pub author: String,
+    pub created_at: String,
+    pub body: String,
+}
+
+/// An issue or pull request, normalized across forges
+#[derive(Debug, Clone, Default)]
+pub struct Issue {
+    pub number: u64,
+    pub title: String,
+    pub state: String,
+    pub author: String,
+    pub created_at: String,
+    pub body: String,
+    pub is_pull_request: bool,
+    pub comments: Vec<Comment>,
+}
+
+/// Read-only access to issues and pull requests of a hosted repository
+// Implementations are only used through `DetectedForge`, whose futures are Send
+#[allow(async_fn_in_trait)]
+pub trait Forge {
+    /// Human readable name of the forge and repository, e.g. `GitHub grouzen/horse`
+    fn describe(&self) -> String;
+
+    /// Fetch an issue or pull request with its comments
+    async fn issue(&self, number: u64, kind: ItemKind) -> Result<Issue, ForgeError>;
+
+    /// Fetch the unified diff of a pull request
+    async fn diff(&self, number: u64) -> Result<String, ForgeError>;
+}
+
+/// The forge hosting the workspace's `origin` remote
+#[derive(Clone)]
+pub enum DetectedForge {
+    GitHub(GitHub),
+    GitLab(GitLab),
+    Gitea(Gitea),
+}
+
+impl DetectedForge {
+    /// Detect the forge of the `origin` remote of `base_dir`.
+    ///
+    /// `forge_type` overrides detection from the host name, for self-hosted instances.
+    pub async fn detect(base_dir: &Path, forge_type: Option<ForgeType>) -> Option<Self> {
+        let url = command_output(base_dir, "git", &["remote", "get-url", "origin"]).await?;
+        let remote = Remote::from_url(&url)?;
+
+        let forge_type = forge_type.or_else(|| remote.forge_type())?;
+        // The environment wins over the keyring, as it does for API keys
+        let stored = || keyring::load(forge_type.account());
+        match forge_type {
+            ForgeType::GitHub => {
+                let token = match env_token(&["GITHUB_TOKEN", "GH_TOKEN"]).or_else(stored) {
+                    Some(token) => Some(token),
+                    None => command_output(base_dir, "gh", &["auth", "token"]).await,
+                };
+                Some(Self::GitHub(GitHub::new(remote, token)))
+            }
+            ForgeType::GitLab => {
+                let token = env_token(&["GITLAB_TOKEN"]).or_else(stored);
+                Some(Self::GitLab(GitLab::new(remote, token)))
+            }
+            ForgeType::Gitea => {
+                let token = env_token(&["GITEA_TOKEN", "FORGEJO_TOKEN"]).or_else(stored);
+                Some(Self::Gitea(Gitea::new(remote, token)))
+            }
+        }
+    }
+}
+
+impl Forge for DetectedForge {
+    fn describe(&self) -> String {
+        match self {
+            Self::GitHub(forge) => forge.describe(),
+            Self::GitLab(forge) => forge.describe(),
+            Self::Gitea(forge) => forge.describe(),
+        }
+    }
+
+    async fn issue(&self, number: u64, kind: ItemKind) -> Result<Issue, ForgeError> {
+        match self {
+            Self::GitHub(forge) => forge.issue(number, kind).await,
+            Self::GitLab(forge) => forge.issue(number, kind).await,
+            Self::Gitea(forge) => forge.issue(number, kind).await,
+        }
+    }
+
+    async fn diff(&self, number: u64) -> Result<String, ForgeError> {
+        match self {
+            Self::GitHub(forge) => forge.diff(number).await,
+            Self::GitLab(forge) => forge.diff(number).await,
+            Self::Gitea(forge) => forge.diff(number).await,
+        }
+    }
+}
+
+/// Run a command in `dir` and return its trimmed stdout if it succeeds
+async fn command_output(dir: &Path, program: &str, args: &[&str]) -> Option<String> {
+    let output = Command::new(program)
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .await
+        .ok()?;
+    let stdout = String::from_utf8(output.stdout).ok()?;
+    (output.status.success() && !stdout.trim().is_empty()).then(|| stdout.trim().to_string())
+}
+
+/// First non-empty value of the given environment variables
+fn env_token(names: &[&str]) -> Option<String> {
+    names
+        .iter()
+        .filter_map(|name| std::env::var(name).ok())
+        .find(|value| !value.is_empty())
+}
+
+/// String at a JSON pointer, or an empty string
+fn text_at(value: &serde_json::Value, pointer: &str) -> String {
+    value
+        .pointer(pointer)
+        .and_then(serde_json::Value::as_str)
+        .unwrap_or_default()
+        .to_string()
+}
+
+/// Send a request, turning unsuccessful responses into errors
+async fn send(
+    forge: &'static str,
+    request: reqwest::RequestBuilder,
+) -> Result<reqwest::Response, ForgeError> {
+    let response = request.header("User-Agent", "horse").send().await?;
+    if response.status().is_success() {
+        Ok(response)
+    } else {
+        let status = response.status().as_u16();
+        let body = response.text().await.unwrap_or_default();
+        Err(ForgeError::Api(forge, status, body))
+    }
+}
+
+/// Render an issue or pull request with its comments as markdown
+fn format_issue(issue: &Issue) -> String {
+    let kind = if issue.is_pull_request {
+        "Pull request"
+    } else {
+        "Issue"
+    };
+    let body = if issue.body.is_empty() {
+        "(no description)"
+    } else {
+        &issue.body
+    };
+    let mut text = format!(
+        "# {kind} #{}: {}\n\nState: {} | Author: {} | Created: {}\n\n{body}\n",
+        issue.number, issue.title, issue.state, issue.author, issue.created_at,
+    );
+
+    for comment in issue.comments.iter().take(MAX_COMMENTS) {
+        text.push_str(&format!(
+            "\n## Comment by {} ({})\n\n{}\n",
+            comment.author, comment.created_at, comment.body,
+        ));
+    }
+    if issue.comments.len() > MAX_COMMENTS {
+        text.push_str(&format!(
+            "\n[{} more comments omitted]\n",
+            issue.comments.len() - MAX_COMMENTS
+        ));
+    }
+    text
+}
+
+/// Render a diff as a markdown section, truncated to `MAX_DIFF_BYTES`
+fn format_diff(diff: &str) -> String {
+    let end = (0..=MAX_DIFF_BYTES.min(diff.len()))
+        .rev()
+        .find(|&i| diff.is_char_boundary(i))
+        .unwrap_or(0);
+    let notice = if end < diff.len() {
+        format!(
+            "\n[truncated - diff exceeds {}KB limit]\n",
+            MAX_DIFF_BYTES / 1024
+        )
+    } else {
+        String::new()
+    };
+    format!("\n## Diff\n\n```diff\n{}\n```\n{notice}", &diff[..end])
+}
+
+/// Tool exposing the issues and pull requests of the workspace's forge
+#[derive(Clone)]
+pub struct ForgeTool {
+    forge: DetectedForge,
+}
+
+impl ForgeTool {
+    pub fn new(forge: DetectedForge) -> Self {
+        Self { forge }
+    }
+}
+
+impl Tool for ForgeTool {
+    const NAME: &'static str = "forge";
+
+    type Error = ForgeError;
+    type Args = ForgeArgs;
+    type Output = String;
+
+    async fn definition(&self, _prompt: String) -> ToolDefinition {
+        ToolDefinition {
+            name: Self::NAME.to_string(),
+            description: format!(
+                "Fetch an issue or pull request of the repository ({}): \
+                title, description and comments, optionally with the pull request diff.",
+                self.forge.describe()
+            ),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "number": {
+                        "type": "integer",
+                        "description": "Issue or pull/merge request number"
+                    },
+                    "kind": {
+                        "type": "string",
+                        "enum": ["issue", "pull_request"],
+                        "description": "Whether the number is an issue or a pull/merge request (default: issue)"
+                    },
+                    "diff": {
+                        "type": "boolean",
+                        "description": "Include the diff of a pull request (default: false)"
+                    }
+                },
+                "required": ["number"]
+            }),
+        }
+    }
+
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+        let issue = self.forge.issue(args.number, args.item_kind()).await?;
+        let text = format_issue(&issue);
+
+        match (args.diff, issue.is_pull_request) {
+            (true, true) => Ok(text + &format_diff(&self.forge.diff(args.number).await?)),
+            (true, false) => Err(ForgeError::NotPullRequest(args.number)),
+            (false, _) => Ok(text),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_remote_from_url() {
+        let github = Some(Remote {
+            host: "github.com".to_string(),
+            path: "grouzen/horse".to_string(),
+        });
+        assert_eq!(
+            Remote::from_url("https://github.com/grouzen/horse.git"),
+            github
+        );
+        assert_eq!(Remote::from_url("git@github.com:grouzen/horse.git"), github);
+        assert_eq!(
+            Remote::from_url("ssh://git@gitlab.example.com:2222/team/tools/horse.git"),
+            Some(Remote {
+                host: "gitlab.example.com".to_string(),
+                path: "team/tools/horse".to_string(),
+            })
+        );
+        assert_eq!(Remote::from_url("/srv/git/horse.git"), None);
+    }
+
+    #[test]
+    fn test_forge_type() {
+        let forge_type = |url| Remote::from_url(url).and_then(|r| r.forge_type());
+        assert_eq!(forge_type("git@github.com:a/b"), Some(ForgeType::GitHub));
+        assert_eq!(
+            forge_type("https://gitlab.example.com/a/b/c"),
+            Some(ForgeType::GitLab)
+        );
+        assert_eq!(
+            forge_type("https://codeberg.org/a/b"),
+            Some(ForgeType::Gitea)
+        );
+        assert_eq!(forge_type("https://git.example.com/a/b"), None);
+    }
+
+    #[test]
+    fn test_format_issue() {
+        let issue = Issue {
+            number: 142,
+            title: "Add pager".to_string(),
+            state: "open".to_string(),
+            author: "octocat".to_string(),
+            created_at: "2025-01-01T00:00:00Z".to_string(),
+            body: "Long answers scroll away.".to_string(),
+            is_pull_request: true,
+            comments: vec![Comment {
+                author: "reviewer".to_string(),
+                created_at: "2025-01-02T00:00:00Z".to_string(),
+                body: "LGTM".to_string(),
+            }],
+        };
+
+        let text = format_issue(&issue);
+        assert!(text.starts_with("# Pull request #142: Add pager\n"));
+        assert!(text.contains("## Comment by reviewer (2025-01-02T00:00:00Z)\n\nLGTM"));
+    }
+}
@@ -0,0 +1,80 @@
+use serde_json::Value;
+
+use crate::agent::tools::forge::{self, Comment, Forge, ForgeError, Issue, ItemKind, Remote};
+
+const NAME: &str = "Gitea";
+
+/// Gitea, Forgejo and Codeberg
+#[derive(Clone)]
+pub struct Gitea {
+    remote: Remote,
+    token: Option<String>,
+    client: reqwest::Client,
+}
+
+impl Gitea {
+    pub fn new(remote: Remote, token: Option<String>) -> Self {
+        Self {
+            remote,
+            token,
+            client: reqwest::Client::new(),
+        }
+    }
+
+    async fn get(&self, path: &str) -> Result<reqwest::Response, ForgeError> {
+        let url = format!(
+            "https://{}/api/v1/repos/{}/{path}",
+            self.remote.host, self.remote.path
+        );
+        let request = self.client.get(url);
+        let request = match &self.token {
+            Some(token) => request.header("Authorization", format!("token {token}")),
+            None => request,
+        };
+        forge::send(NAME, request).await
+    }
+
+    async fn get_json(&self, path: &str) -> Result<Value, ForgeError> {
+        Ok(self.get(path).await?.json().await?)
+    }
+}
+
+impl Forge for Gitea {
+    fn describe(&self) -> String {
+        format!("{NAME} {}/{}", self.remote.host, self.remote.path)
+    }
+
+    /// Issues and pull requests share numbers, so `kind` is not needed
+    async fn issue(&self, number: u64, _kind: ItemKind) -> Result<Issue, ForgeError> {
+        let issue = self.get_json(&format!("issues/{number}")).await?;
+        let comments = self.get_json(&format!("issues/{number}/comments")).await?;
+
+        Ok(Issue {
+            number,
+            title: forge::text_at(&issue, "/title"),
+            state: forge::text_at(&issue, "/state"),
+            author: forge::text_at(&issue, "/user/login"),
+            created_at: forge::text_at(&issue, "/created_at"),
+            body: forge::text_at(&issue, "/body"),
+            is_pull_request: issue.get("pull_request").is_some_and(|pr| !pr.is_null()),
+            comments: comments
+                .as_array()
+                .into_iter()
+                .flatten()
+                .map(|comment| Comment {
+                    author: forge::text_at(comment, "/user/login"),
+                    created_at: forge::text_at(comment, "/created_at"),
+                    body: forge::text_at(comment, "/body"),
+                })
+                .collect(),
+        })
+    }
+
+    async fn diff(&self, number: u64) -> Result<String, ForgeError> {
+        Ok(self
+            .get(&format!("pulls/{number}.diff"))
+            .await?
+            .text()
+            .await?)
+    }
+}
@@ -0,0 +1,95 @@
+use serde_json::Value;
+
+use crate::agent::tools::forge::{self, Comment, Forge, ForgeError, Issue, ItemKind, Remote};
+
+const NAME: &str = "GitHub";
+
+/// GitHub and GitHub Enterprise
+#[derive(Clone)]
+pub struct GitHub {
+    remote: Remote,
+    token: Option<String>,
+    client: reqwest::Client,
+}
+
+impl GitHub {
+    pub fn new(remote: Remote, token: Option<String>) -> Self {
+        Self {
+            remote,
+            token,
+            client: reqwest::Client::new(),
+        }
+    }
+
+    fn api_url(&self) -> String {
+        if self.remote.host == "github.com" {
+            "https://api.github.com".to_string()
+        } else {
+            format!("https://{}/api/v3", self.remote.host)
+        }
+    }
+
+    async fn get(&self, path: &str, accept: &str) -> Result<reqwest::Response, ForgeError> {
+        let url = format!("{}/repos/{}/{path}", self.api_url(), self.remote.path);
+        let request = self
+            .client
+            .get(url)
+            .header("Accept", accept)
+            .header("X-GitHub-Api-Version", "2022-11-28");
+        let request = match &self.token {
+            Some(token) => request.bearer_auth(token),
+            None => request,
+        };
+        forge::send(NAME, request).await
+    }
+
+    async fn get_json(&self, path: &str) -> Result<Value, ForgeError> {
+        Ok(self
+            .get(path, "application/vnd.github+json")
+            .await?
+            .json()
+            .await?)
+    }
+}
+
+impl Forge for GitHub {
+    fn describe(&self) -> String {
+        format!("{NAME} {}", self.remote.path)
+    }
+
+    /// Issues and pull requests share numbers, so `kind` is not needed
+    async fn issue(&self, number: u64, _kind: ItemKind) -> Result<Issue, ForgeError> {
+        let issue = self.get_json(&format!("issues/{number}")).await?;
+        let comments = self
+            .get_json(&format!("issues/{number}/comments?per_page=100"))
+            .await?;
+
+        Ok(Issue {
+            number,
+            title: forge::text_at(&issue, "/title"),
+            state: forge::text_at(&issue, "/state"),
+            author: forge::text_at(&issue, "/user/login"),
+            created_at: forge::text_at(&issue, "/created_at"),
+            body: forge::text_at(&issue, "/body"),
+            is_pull_request: issue.get("pull_request").is_some(),
+            comments: comments
+                .as_array()
+                .into_iter()
+                .flatten()
+                .map(|comment| Comment {
+                    author: forge::text_at(comment, "/user/login"),
+                    created_at: forge::text_at(comment, "/created_at"),
+                    body: forge::text_at(comment, "/body"),
+                })
+                .collect(),
+        })
+    }
+
+    async fn diff(&self, number: u64) -> Result<String, ForgeError> {
+        Ok(self
+            .get(&format!("pulls/{number}"), "application/vnd.github.diff")
+            .await?
+            .text()
+            .await?)
+    }
+}
@@ -0,0 +1,108 @@
+use serde_json::Value;
+
+use crate::agent::tools::forge::{self, Comment, Forge, ForgeError, Issue, ItemKind, Remote};
+
+const NAME: &str = "GitLab";
+
+/// GitLab.com and self-managed GitLab
+#[derive(Clone)]
+pub struct GitLab {
+    remote: Remote,
+    token: Option<String>,
+    client: reqwest::Client,
+}
+
+impl GitLab {
+    pub fn new(remote: Remote, token: Option<String>) -> Self {
+        Self {
+            remote,
+            token,
+            client: reqwest::Client::new(),
+        }
+    }
+
+    async fn get_json(&self, path: &str) -> Result<Value, ForgeError> {
+        // The project is addressed by its URL-encoded path, subgroups included
+        let url = format!(
+            "https://{}/api/v4/projects/{}/{path}",
+            self.remote.host,
+            self.remote.path.replace('/', "%2F")
+        );
+        let request = self.client.get(url);
+        let request = match &self.token {
+            Some(token) => request.header("PRIVATE-TOKEN", token),
+            None => request,
+        };
+        Ok(forge::send(NAME, request).await?.json().await?)
+    }
+
+    /// API collection of an item kind; issues and merge requests are numbered separately
+    fn collection(kind: ItemKind) -> &'static str {
+        match kind {
+            ItemKind::Issue => "issues",
+            ItemKind::PullRequest => "merge_requests",
+        }
+    }
+}
+
+impl Forge for GitLab {
+    fn describe(&self) -> String {
+        format!(
+            "{NAME} {}/{}, merge requests are numbered separately from issues",
+            self.remote.host, self.remote.path
+        )
+    }
+
+    async fn issue(&self, number: u64, kind: ItemKind) -> Result<Issue, ForgeError> {
+        let collection = Self::collection(kind);
+        let issue = self.get_json(&format!("{collection}/{number}")).await?;
+        let notes = self
+            .get_json(&format!(
+                "{collection}/{number}/notes?sort=asc&per_page=100"
+            ))
+            .await?;
+
+        Ok(Issue {
+            number,
+            title: forge::text_at(&issue, "/title"),
+            state: forge::text_at(&issue, "/state"),
+            author: forge::text_at(&issue, "/author/username"),
+            created_at: forge::text_at(&issue, "/created_at"),
+            body: forge::text_at(&issue, "/description"),
+            is_pull_request: kind == ItemKind::PullRequest,
+            comments: notes
+                .as_array()
+                .into_iter()
+                .flatten()
+                // System notes record events like label changes, not discussion
+                .filter(|note| !note["system"].as_bool().unwrap_or(false))
+                .map(|note| Comment {
+                    author: forge::text_at(note, "/author/username"),
+                    created_at: forge::text_at(note, "/created_at"),
+                    body: forge::text_at(note, "/body"),
+                })
+                .collect(),
+        })
+    }
+
+    async fn diff(&self, number: u64) -> Result<String, ForgeError> {
+        let diffs = self
+            .get_json(&format!("merge_requests/{number}/diffs?per_page=100"))
+            .await?;
+
+        Ok(diffs
+            .as_array()
+            .into_iter()
+            .flatten()
+            .map(|file| {
+                format!(
+                    "--- a/{}\n+++ b/{}\n{}",
+                    forge::text_at(file, "/old_path"),
+                    forge::text_at(file, "/new_path"),
+                    forge::text_at(file, "/diff")
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(""))
+    }
+}
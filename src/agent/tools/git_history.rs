@@ -0,0 +1,239 @@
+use std::path::PathBuf;
+use std::time::Duration;
+
+use rig::completion::ToolDefinition;
+use rig::tool::Tool;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use thiserror::Error;
+use tokio::time::timeout;
+
+use crate::agent::tools::injection;
+use crate::agent::{remote, scope};
+
+const TIMEOUT_SECS: u64 = 30;
+
+/// Commits listed by one call, at most
+const MAX_COMMITS: usize = 100;
+
+/// Output returned by one call, at most
+const MAX_OUTPUT_BYTES: usize = 64 * 1024;
+
+#[derive(Deserialize)]
+pub struct GitHistoryArgs {
+    /// Commit to show, e.g. a SHA or `HEAD~2`; lists commits when unset
+    pub rev: Option<String>,
+    /// Include the patch of the shown commit
+    #[serde(default)]
+    pub patch: bool,
+    /// Only commits at or after this time, in any format `git log --since`
+    /// takes
+    pub since: Option<String>,
+    /// Only commits at or before this time
+    pub until: Option<String>,
+    /// Only commits whose message matches this pattern, case-insensitively
+    pub grep: Option<String>,
+    /// Only commits touching this file or directory
+    pub path: Option<String>,
+}
+
+#[derive(Debug, Error)]
+pub enum GitHistoryError {
+    #[error("Path traversal not allowed: {0}")]
+    PathTraversal(String),
+    #[error("{0} is outside the --include/--exclude scope of this session")]
+    OutsideScope(String),
+    #[error("git timed out after {0} seconds")]
+    Timeout(u64),
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("git failed: {0}")]
+    GitFailed(String),
+}
+
+/// Read-only access to the commit history of the workspace: commits in a
+/// time range, and single commits with their changes
+#[derive(Deserialize, Serialize)]
+pub struct GitHistory {
+    #[serde(skip)]
+    base_dir: PathBuf,
+}
+
+impl GitHistory {
+    pub fn new(base_dir: PathBuf) -> Self {
+        Self { base_dir }
+    }
+
+    async fn git(&self, args: &[String]) -> Result<String, GitHistoryError> {
+        let args: Vec<&str> = args.iter().map(String::as_str).collect();
+        let output = timeout(
+            Duration::from_secs(TIMEOUT_SECS),
+            remote::command(&self.base_dir, "git", &args).output(),
+        )
+        .await
+        .map_err(|_| GitHistoryError::Timeout(TIMEOUT_SECS))??;
+
+        if output.status.success() {
+            Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+        } else {
+            Err(GitHistoryError::GitFailed(
+                String::from_utf8_lossy(&output.stderr).trim().to_string(),
+            ))
+        }
+    }
+}
+
+impl Tool for GitHistory {
+    const NAME: &'static str = "git_history";
+
+    type Error = GitHistoryError;
+    type Args = GitHistoryArgs;
+    type Output = String;
+
+    async fn definition(&self, _prompt: String) -> ToolDefinition {
+        ToolDefinition {
+            name: Self::NAME.to_string(),
+            description: format!(
+                "Read the git history of the workspace. Without rev, list commits (newest \
+                first, at most {MAX_COMMITS}) with their date, author, subject and changed \
+                files, optionally narrowed by time range, message pattern and path. With rev, \
+                show that commit's full message and changed files, and its patch if asked. \
+                Use it to find out when and why code changed and who changed it."
+            ),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "rev": {
+                        "type": "string",
+                        "description": "Commit to show, e.g. a SHA, `HEAD~2` or a tag; lists commits when omitted"
+                    },
+                    "patch": {
+                        "type": "boolean",
+                        "description": "Include the patch of the shown commit (default false)"
+                    },
+                    "since": {
+                        "type": "string",
+                        "description": "Only commits at or after this time, e.g. `2024-05-01 14:30` or `2 days ago`"
+                    },
+                    "until": {
+                        "type": "string",
+                        "description": "Only commits at or before this time, in the same formats as since"
+                    },
+                    "grep": {
+                        "type": "string",
+                        "description": "Only commits whose message matches this regex, case-insensitively"
+                    },
+                    "path": {
+                        "type": "string",
+                        "description": "Only commits touching this file or directory"
+                    }
+                }
+            }),
+        }
+    }
+
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+        match args.path.as_deref() {
+            Some(path) if path.contains("..") || path.starts_with('/') => {
+                Err(GitHistoryError::PathTraversal(path.to_string()))
+            }
+            Some(path)
+                if scope::excludes_file(&self.base_dir, path) || scope::excludes_path(path) =>
+            {
+                Err(GitHistoryError::OutsideScope(path.to_string()))
+            }
+            _ => {
+                let output = self.git(&git_args(&args)).await?;
+                let output = if output.trim().is_empty() {
+                    "No commits found".to_string()
+                } else {
+                    truncate(output)
+                };
+                Ok(injection::guard(output))
+            }
+        }
+    }
+}
+
+/// Arguments of the `git log` or `git show` call answering `args`.
+///
+/// External diff and textconv drivers are turned off, so reading the
+/// history runs no program configured by the repository.
+fn git_args(args: &GitHistoryArgs) -> Vec<String> {
+    let options: Vec<String> = match &args.rev {
+        // The stat alone replaces the patch `git show` prints by default
+        Some(rev) => [
+            "show",
+            "--no-ext-diff",
+            "--no-textconv",
+            "--format=fuller",
+            "--date=iso",
+            "--stat",
+        ]
+        .into_iter()
+        .chain(args.patch.then_some("--patch"))
+        .chain(["--end-of-options", rev.as_str()])
+        .map(str::to_string)
+        .collect(),
+        None => [
+            "log".to_string(),
+            "--no-ext-diff".to_string(),
+            "--date=iso".to_string(),
+            "--format=%h %ad %an%n    %s".to_string(),
+            "--name-status".to_string(),
+            format!("--max-count={MAX_COMMITS}"),
+        ]
+        .into_iter()
+        .chain(args.since.iter().map(|since| format!("--since={since}")))
+        .chain(args.until.iter().map(|until| format!("--until={until}")))
+        .chain(
+            args.grep
+                .iter()
+                .flat_map(|grep| [format!("--grep={grep}"), "--regexp-ignore-case".to_string()]),
+        )
+        .collect(),
+    };
+    options
+        .into_iter()
+        .chain(["--".to_string()])
+        .chain(scope::git_pathspecs(args.path.as_deref()))
+        .collect()
+}
+
+fn truncate(mut output: String) -> String {
+    if output.len() > MAX_OUTPUT_BYTES {
+        let mut end = MAX_OUTPUT_BYTES;
+        while !output.is_char_boundary(end) {
+            end -= 1;
+        }
+        output.truncate(end);
+        output.push_str("\n[truncated - narrow the time range or path, or leave out the patch]");
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_git_args() {
+        let args: GitHistoryArgs = serde_json::from_value(json!({
+            "since": "2024-05-01 14:30",
+            "grep": "timeout",
+            "path": "src/server"
+        }))
+        .unwrap();
+        let git = git_args(&args);
+        assert_eq!(git[0], "log");
+        assert!(git.contains(&"--since=2024-05-01 14:30".to_string()));
+        assert!(git.contains(&"--grep=timeout".to_string()));
+        assert_eq!(git[git.len() - 2..], ["--", "src/server"]);
+
+        let args: GitHistoryArgs = serde_json::from_value(json!({ "rev": "-x" })).unwrap();
+        let git = git_args(&args);
+        assert_eq!(git[0], "show");
+        assert!(!git.contains(&"--patch".to_string()));
+        assert_eq!(git[git.len() - 3..], ["--end-of-options", "-x", "--"]);
+    }
+}
@@ -0,0 +1,733 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use rig::completion::ToolDefinition;
+use rig::tool::Tool;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use thiserror::Error;
+use tokio::time::timeout;
+
+use crate::agent::tools::cache::{self, ResultCache};
+use crate::agent::{remote, scope};
+
+const TIMEOUT_SECS: u64 = 30;
+
+/// License files read, at most
+const MAX_LICENSE_FILES: usize = 10;
+
+/// Dependencies named per category, at most
+const MAX_NAMED: usize = 20;
+
+/// How freely code under a license can be combined with other code, from
+/// least to most restrictive
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Category {
+    Permissive,
+    WeakCopyleft,
+    StrongCopyleft,
+    Unknown,
+}
+
+impl Category {
+    fn label(self) -> &'static str {
+        match self {
+            Category::Permissive => "Permissive",
+            Category::WeakCopyleft => "Weak copyleft",
+            Category::StrongCopyleft => "Strong copyleft",
+            Category::Unknown => "Unknown",
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct LicenseScanArgs {
+    /// Optional directory of the project to scan (defaults to the working directory)
+    pub path: Option<String>,
+}
+
+#[derive(Debug, Error)]
+pub enum LicenseScanError {
+    #[error("Path traversal not allowed: {0}")]
+    PathTraversal(String),
+    #[error("No license file, Cargo.toml or package.json found in {0}")]
+    NothingFound(String),
+}
+
+/// A third-party package the project depends on
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Dependency {
+    ecosystem: &'static str,
+    name: String,
+    version: String,
+    /// SPDX expression, if the package declares one
+    license: Option<String>,
+}
+
+/// `cargo metadata` output, reduced to the packages
+#[derive(Deserialize)]
+struct CargoMetadata {
+    packages: Vec<CargoPackage>,
+}
+
+#[derive(Deserialize)]
+struct CargoPackage {
+    name: String,
+    version: String,
+    license: Option<String>,
+    /// None for the workspace's own and path packages
+    source: Option<String>,
+}
+
+/// `Cargo.lock`, read when `cargo metadata` isn't available; it has no
+/// licenses
+#[derive(Deserialize)]
+struct CargoLock {
+    #[serde(default)]
+    package: Vec<CargoPackage>,
+}
+
+/// `package-lock.json` (lockfile version 2 and later)
+#[derive(Deserialize)]
+struct PackageLock {
+    #[serde(default)]
+    packages: BTreeMap<String, LockedPackage>,
+}
+
+#[derive(Deserialize)]
+struct LockedPackage {
+    version: Option<String>,
+    license: Option<serde_json::Value>,
+}
+
+/// Detects the project's license and its dependencies' licenses, and
+/// points out combinations that need attention
+#[derive(Deserialize, Serialize)]
+pub struct LicenseScan {
+    #[serde(skip)]
+    base_dir: PathBuf,
+    #[serde(skip)]
+    cache: ResultCache,
+}
+
+impl LicenseScan {
+    pub fn new(base_dir: PathBuf) -> Self {
+        Self {
+            base_dir,
+            cache: ResultCache::default(),
+        }
+    }
+
+    /// Stdout of a successful command, or None
+    async fn output(&self, program: &str, args: &[&str]) -> Option<String> {
+        let output = timeout(
+            Duration::from_secs(TIMEOUT_SECS),
+            remote::command(&self.base_dir, program, args).output(),
+        )
+        .await;
+        match output {
+            Ok(Ok(output)) if output.status.success() => {
+                Some(String::from_utf8_lossy(&output.stdout).into_owned())
+            }
+            _ => None,
+        }
+    }
+
+    async fn read(&self, path: &str) -> Option<String> {
+        remote::read_to_string(&self.base_dir, path).await.ok()
+    }
+
+    /// License files under `path` with the license each is recognized as
+    async fn license_files(&self, path: &str) -> Vec<(String, Option<&'static str>)> {
+        let files = self
+            .output(
+                "rg",
+                &[
+                    "--files",
+                    "--max-depth",
+                    "3",
+                    "--iglob",
+                    "{LICENSE,LICENCE,COPYING,UNLICENSE}*",
+                    "--",
+                    path,
+                ],
+            )
+            .await
+            .unwrap_or_default();
+        let mut found = Vec::new();
+        let files = files
+            .lines()
+            .filter(|file| scope::allows(Path::new(file)))
+            .take(MAX_LICENSE_FILES);
+        for file in files {
+            let license = self.read(file).await.and_then(|text| identify(&text));
+            found.push((file.trim_start_matches("./").to_string(), license));
+        }
+        found.sort();
+        found
+    }
+
+    /// License declared in the manifests under `path`, with the manifest
+    async fn declared_license(&self, path: &str) -> Option<(String, &'static str)> {
+        let cargo = self
+            .read(&join(path, "Cargo.toml"))
+            .await
+            .and_then(|text| text.parse::<toml::Table>().ok())
+            .and_then(|manifest| {
+                let package = manifest.get("package");
+                let workspace = manifest
+                    .get("workspace")
+                    .and_then(|workspace| workspace.get("package"));
+                package
+                    .or(workspace)?
+                    .get("license")?
+                    .as_str()
+                    .map(str::to_string)
+            })
+            .map(|license| (license, "Cargo.toml"));
+        match cargo {
+            Some(cargo) => Some(cargo),
+            None => self
+                .read(&join(path, "package.json"))
+                .await
+                .and_then(|text| serde_json::from_str::<serde_json::Value>(&text).ok())
+                .and_then(|manifest| manifest.get("license")?.as_str().map(str::to_string))
+                .map(|license| (license, "package.json")),
+        }
+    }
+
+    /// Rust dependencies with their licenses from `cargo metadata`, or
+    /// without them from Cargo.lock
+    async fn cargo_dependencies(&self, path: &str) -> Vec<Dependency> {
+        let manifest = join(path, "Cargo.toml");
+        let metadata = self
+            .output(
+                "cargo",
+                &[
+                    "metadata",
+                    "--format-version",
+                    "1",
+                    "--offline",
+                    "--manifest-path",
+                    &manifest,
+                ],
+            )
+            .await
+            .and_then(|output| serde_json::from_str::<CargoMetadata>(&output).ok())
+            .map(|metadata| metadata.packages);
+        let packages = match metadata {
+            Some(packages) => packages,
+            None => self
+                .read(&join(path, "Cargo.lock"))
+                .await
+                .and_then(|text| toml::from_str::<CargoLock>(&text).ok())
+                .map(|lock| lock.package)
+                .unwrap_or_default(),
+        };
+        packages
+            .into_iter()
+            .filter(|package| package.source.is_some())
+            .map(|package| Dependency {
+                ecosystem: "cargo",
+                name: package.name,
+                version: package.version,
+                license: package.license,
+            })
+            .collect()
+    }
+
+    /// npm dependencies with their licenses from package-lock.json
+    async fn npm_dependencies(&self, path: &str) -> Vec<Dependency> {
+        self.read(&join(path, "package-lock.json"))
+            .await
+            .map_or_else(Vec::new, |text| parse_package_lock(&text))
+    }
+
+    async fn scan(&self, path: &str) -> Result<String, LicenseScanError> {
+        let files = self.license_files(path).await;
+        let declared = self.declared_license(path).await;
+        let mut dependencies = self.cargo_dependencies(path).await;
+        dependencies.extend(self.npm_dependencies(path).await);
+
+        if files.is_empty() && declared.is_none() && dependencies.is_empty() {
+            Err(LicenseScanError::NothingFound(path.to_string()))
+        } else {
+            Ok(render(&files, declared.as_ref(), &dependencies))
+        }
+    }
+}
+
+impl Tool for LicenseScan {
+    const NAME: &'static str = "license_scan";
+
+    type Error = LicenseScanError;
+    type Args = LicenseScanArgs;
+    type Output = String;
+
+    async fn definition(&self, _prompt: String) -> ToolDefinition {
+        ToolDefinition {
+            name: Self::NAME.to_string(),
+            description: "Report the project's license (declared in Cargo.toml or \
+                package.json, and recognized from LICENSE/COPYING files) and the licenses of \
+                its Rust and npm dependencies, grouped into permissive, weak copyleft, strong \
+                copyleft and unknown, with notes on combinations that need attention. Use it \
+                for licensing and compliance questions."
+                .to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "path": {
+                        "type": "string",
+                        "description": "Optional directory of the project to scan (defaults to the working directory)"
+                    }
+                }
+            }),
+        }
+    }
+
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+        let path = args.path.as_deref().unwrap_or(".");
+
+        if path.contains("..") || path.starts_with('/') {
+            Err(LicenseScanError::PathTraversal(path.to_string()))
+        } else {
+            match self.cache.get(path).await {
+                Some(cached) => Ok(cached),
+                None => {
+                    let output = self.scan(path).await?;
+                    let sources = cache::sources(&self.base_dir, &[path], &output);
+                    self.cache.insert(path.to_string(), &output, sources).await;
+                    Ok(output)
+                }
+            }
+        }
+    }
+}
+
+/// `name` in the directory `path`
+fn join(path: &str, name: &str) -> String {
+    match path.trim_end_matches('/') {
+        "." | "" => name.to_string(),
+        dir => format!("{dir}/{name}"),
+    }
+}
+
+/// Recognize a license from the text of a license file
+fn identify(text: &str) -> Option<&'static str> {
+    let text = text.to_lowercase();
+    let has = |phrase: &str| text.contains(phrase);
+    let gpl_version = |name: &'static str, v2: &'static str, v3: &'static str| {
+        if has("version 3") {
+            v3
+        } else if has("version 2") {
+            v2
+        } else {
+            name
+        }
+    };
+
+    if has("gnu affero general public license") {
+        Some("AGPL-3.0")
+    } else if has("gnu lesser general public license") || has("gnu library general public") {
+        Some(gpl_version("LGPL", "LGPL-2.1", "LGPL-3.0"))
+    } else if has("gnu general public license") {
+        Some(gpl_version("GPL", "GPL-2.0", "GPL-3.0"))
+    } else if has("mozilla public license") {
+        Some("MPL-2.0")
+    } else if has("eclipse public license") {
+        Some("EPL-2.0")
+    } else if has("apache license") {
+        Some("Apache-2.0")
+    } else if has("permission is hereby granted, free of charge") {
+        Some("MIT")
+    } else if has("permission to use, copy, modify, and/or distribute") {
+        Some("ISC")
+    } else if has("neither the name") && has("redistribution and use") {
+        Some("BSD-3-Clause")
+    } else if has("redistribution and use") {
+        Some("BSD-2-Clause")
+    } else if has("this is free and unencumbered software") {
+        Some("Unlicense")
+    } else if has("creative commons") && has("cc0") {
+        Some("CC0-1.0")
+    } else if has("boost software license") {
+        Some("BSL-1.0")
+    } else {
+        None
+    }
+}
+
+/// Category of a single SPDX license id
+fn classify_id(id: &str) -> Category {
+    let id = id.to_uppercase();
+    let starts = |prefixes: &[&str]| prefixes.iter().any(|prefix| id.starts_with(prefix));
+
+    if starts(&["AGPL", "GPL", "SSPL", "OSL", "CC-BY-SA"]) {
+        Category::StrongCopyleft
+    } else if starts(&["LGPL", "MPL", "EPL", "CDDL", "EUPL", "CPL"]) {
+        Category::WeakCopyleft
+    } else if starts(&[
+        "MIT",
+        "APACHE",
+        "BSD",
+        "0BSD",
+        "ISC",
+        "ZLIB",
+        "UNLICENSE",
+        "CC0",
+        "BSL",
+        "UNICODE",
+        "WTFPL",
+        "PSF",
+        "PYTHON",
+        "X11",
+        "BLUEOAK",
+        "NCSA",
+        "CC-BY",
+    ]) {
+        Category::Permissive
+    } else {
+        Category::Unknown
+    }
+}
+
+/// Category of an SPDX expression: the most permissive of `OR`
+/// alternatives, and the most restrictive of `AND`ed licenses. `WITH`
+/// exceptions only relax the license they follow, so they are ignored.
+fn classify(expression: &str) -> Category {
+    // Older Cargo manifests separate alternatives with `/`
+    let expression = expression
+        .replace('/', " OR ")
+        .replace('(', " ( ")
+        .replace(')', " ) ");
+    let mut tokens = expression.split_whitespace().peekable();
+    classify_or(&mut tokens)
+}
+
+type Tokens<'a> = std::iter::Peekable<std::str::SplitWhitespace<'a>>;
+
+fn classify_or(tokens: &mut Tokens) -> Category {
+    let mut category = classify_and(tokens);
+    while tokens
+        .next_if(|token| token.eq_ignore_ascii_case("or"))
+        .is_some()
+    {
+        category = category.min(classify_and(tokens));
+    }
+    category
+}
+
+fn classify_and(tokens: &mut Tokens) -> Category {
+    let mut category = classify_license(tokens);
+    while tokens
+        .next_if(|token| token.eq_ignore_ascii_case("and"))
+        .is_some()
+    {
+        category = category.max(classify_license(tokens));
+    }
+    category
+}
+
+/// A license id with its optional exception, or a parenthesized expression
+fn classify_license(tokens: &mut Tokens) -> Category {
+    match tokens.next() {
+        Some("(") => {
+            let category = classify_or(tokens);
+            tokens.next_if_eq(&")");
+            category
+        }
+        Some(id) => {
+            if tokens
+                .next_if(|token| token.eq_ignore_ascii_case("with"))
+                .is_some()
+            {
+                tokens.next();
+            }
+            classify_id(id)
+        }
+        None => Category::Unknown,
+    }
+}
+
+/// Packages with their licenses from package-lock.json
+fn parse_package_lock(text: &str) -> Vec<Dependency> {
+    serde_json::from_str::<PackageLock>(text).map_or_else(
+        |_| Vec::new(),
+        |lock| {
+            lock.packages
+                .into_iter()
+                .filter_map(|(key, package)| {
+                    // The "" key is the project itself
+                    let (_, name) = key.rsplit_once("node_modules/")?;
+                    let license = match package.license {
+                        Some(serde_json::Value::String(license)) => Some(license),
+                        Some(license) => license
+                            .get("type")
+                            .and_then(|license| license.as_str())
+                            .map(str::to_string),
+                        None => None,
+                    };
+                    Some(Dependency {
+                        ecosystem: "npm",
+                        name: name.to_string(),
+                        version: package.version.unwrap_or_default(),
+                        license,
+                    })
+                })
+                .collect()
+        },
+    )
+}
+
+/// Notes on how the dependencies' licenses combine with the project's
+fn compatibility(project: Option<&str>, dependencies: &[Dependency]) -> Vec<String> {
+    let count = |category: Category| {
+        dependencies
+            .iter()
+            .filter(|dependency| {
+                dependency
+                    .license
+                    .as_deref()
+                    .map_or(Category::Unknown, classify)
+                    == category
+            })
+            .count()
+    };
+    let project_category = project.map(classify);
+    let mut notes = Vec::new();
+
+    match project_category {
+        None => notes.push(
+            "The project declares no license; without one, others have no right to use, \
+             modify or share it."
+                .to_string(),
+        ),
+        Some(Category::Unknown) => notes.push(format!(
+            "The project's license ({}) isn't a recognized SPDX license; check it manually.",
+            project.unwrap_or_default()
+        )),
+        Some(_) => {}
+    }
+    let strong = count(Category::StrongCopyleft);
+    if strong > 0 && project_category != Some(Category::StrongCopyleft) {
+        notes.push(format!(
+            "{strong} dependencies are under strong copyleft licenses (GPL, AGPL, ...): \
+             distributing the project with them generally requires releasing it under a \
+             compatible copyleft license, which conflicts with a permissive project license."
+        ));
+    }
+    let weak = count(Category::WeakCopyleft);
+    if weak > 0 {
+        notes.push(format!(
+            "{weak} dependencies are under weak copyleft licenses (LGPL, MPL, ...): changes \
+             to those dependencies must be shared under the same license, and LGPL libraries \
+             must stay replaceable when linked statically."
+        ));
+    }
+    let unknown = count(Category::Unknown);
+    if unknown > 0 {
+        notes.push(format!(
+            "{unknown} dependencies have no declared or recognized license; check them manually."
+        ));
+    }
+    if notes.is_empty() {
+        notes.push(
+            "No conflicts found: the dependencies' licenses are compatible with the \
+             project's license."
+                .to_string(),
+        );
+    }
+    notes
+}
+
+/// The report: the project's license, its license files, the dependencies
+/// by category and license, and compatibility notes
+fn render(
+    files: &[(String, Option<&'static str>)],
+    declared: Option<&(String, &'static str)>,
+    dependencies: &[Dependency],
+) -> String {
+    // A declared license takes precedence over the one of the top license file
+    let project = declared
+        .map(|(license, _)| license.as_str())
+        .or_else(|| files.iter().find_map(|(_, license)| *license));
+    let mut out = match declared {
+        Some((license, manifest)) => {
+            format!("Project license: {license} (declared in {manifest})\n")
+        }
+        None => format!(
+            "Project license: {} (no license declared in a manifest)\n",
+            project.unwrap_or("none found")
+        ),
+    };
+
+    if !files.is_empty() {
+        let files: Vec<String> = files
+            .iter()
+            .map(|(file, license)| format!("{file} ({})", license.unwrap_or("unrecognized")))
+            .collect();
+        out.push_str(&format!("License files: {}\n", files.join(", ")));
+    }
+
+    if dependencies.is_empty() {
+        out.push_str(
+            "\nNo dependencies found (looked at Cargo.toml, Cargo.lock and package-lock.json)\n",
+        );
+    } else {
+        let mut ecosystems: BTreeMap<&str, usize> = BTreeMap::new();
+        let mut by_category: BTreeMap<Category, Vec<&Dependency>> = BTreeMap::new();
+        let mut by_license: BTreeMap<&str, usize> = BTreeMap::new();
+        for dependency in dependencies {
+            *ecosystems.entry(dependency.ecosystem).or_default() += 1;
+            let license = dependency.license.as_deref();
+            by_category
+                .entry(license.map_or(Category::Unknown, classify))
+                .or_default()
+                .push(dependency);
+            *by_license.entry(license.unwrap_or("(none)")).or_default() += 1;
+        }
+
+        let ecosystems: Vec<String> = ecosystems
+            .iter()
+            .map(|(ecosystem, count)| format!("{ecosystem} {count}"))
+            .collect();
+        out.push_str(&format!(
+            "\nDependencies: {} ({})\n",
+            dependencies.len(),
+            ecosystems.join(", ")
+        ));
+        for (category, members) in &by_category {
+            out.push_str(&format!("  {}: {}", category.label(), members.len()));
+            // Permissive dependencies need no attention; name the others
+            if *category != Category::Permissive {
+                let named: Vec<String> = members
+                    .iter()
+                    .take(MAX_NAMED)
+                    .map(|dependency| {
+                        format!(
+                            "{} {} ({})",
+                            dependency.name,
+                            dependency.version,
+                            dependency.license.as_deref().unwrap_or("none")
+                        )
+                    })
+                    .collect();
+                let more = members.len().saturating_sub(MAX_NAMED);
+                let more = if more > 0 {
+                    format!(", {more} more")
+                } else {
+                    String::new()
+                };
+                out.push_str(&format!(" - {}{more}", named.join(", ")));
+            }
+            out.push('\n');
+        }
+
+        let mut by_license: Vec<(&str, usize)> = by_license.into_iter().collect();
+        by_license.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+        out.push_str("\nBy license:\n");
+        for (license, count) in by_license {
+            out.push_str(&format!("  {license}: {count}\n"));
+        }
+    }
+
+    out.push_str("\nCompatibility (heuristic, not legal advice):\n");
+    for note in compatibility(project, dependencies) {
+        out.push_str(&format!("  - {note}\n"));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dependency(name: &str, license: Option<&str>) -> Dependency {
+        Dependency {
+            ecosystem: "cargo",
+            name: name.to_string(),
+            version: "1.0.0".to_string(),
+            license: license.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn test_classify() {
+        assert_eq!(classify("MIT OR Apache-2.0"), Category::Permissive);
+        assert_eq!(classify("MIT/Apache-2.0"), Category::Permissive);
+        assert_eq!(classify("GPL-3.0-only OR MIT"), Category::Permissive);
+        assert_eq!(
+            classify("(MIT OR Apache-2.0) AND LGPL-2.1"),
+            Category::WeakCopyleft
+        );
+        assert_eq!(
+            classify("GPL-2.0 WITH Classpath-exception-2.0"),
+            Category::StrongCopyleft
+        );
+        assert_eq!(classify("LicenseRef-proprietary"), Category::Unknown);
+    }
+
+    #[test]
+    fn test_identify() {
+        assert_eq!(
+            identify("Permission is hereby granted, free of charge, to any person"),
+            Some("MIT")
+        );
+        assert_eq!(
+            identify("GNU LESSER GENERAL PUBLIC LICENSE\nVersion 3, 29 June 2007"),
+            Some("LGPL-3.0")
+        );
+        assert_eq!(
+            identify("Apache License\nVersion 2.0, January 2004"),
+            Some("Apache-2.0")
+        );
+        assert_eq!(identify("All rights reserved."), None);
+    }
+
+    #[test]
+    fn test_parse_package_lock() {
+        let lock = r#"{
+            "lockfileVersion": 3,
+            "packages": {
+                "": {"name": "app", "license": "MIT"},
+                "node_modules/left-pad": {"version": "1.3.0", "license": "WTFPL"},
+                "node_modules/a/node_modules/@scope/b": {"version": "2.0.0", "license": {"type": "GPL-3.0"}},
+                "node_modules/c": {"version": "0.1.0"}
+            }
+        }"#;
+        let dependencies = parse_package_lock(lock);
+
+        assert_eq!(dependencies.len(), 3);
+        assert_eq!(dependencies[0].name, "@scope/b");
+        assert_eq!(dependencies[0].license.as_deref(), Some("GPL-3.0"));
+        assert_eq!(dependencies[1].license, None);
+        assert_eq!(dependencies[2].name, "left-pad");
+    }
+
+    #[test]
+    fn test_render() {
+        let dependencies = [
+            dependency("serde", Some("MIT OR Apache-2.0")),
+            dependency("readline", Some("GPL-3.0")),
+            dependency("mystery", None),
+        ];
+        let declared = ("MIT".to_string(), "Cargo.toml");
+        let out = render(
+            &[("LICENSE".to_string(), Some("MIT"))],
+            Some(&declared),
+            &dependencies,
+        );
+
+        assert!(out.starts_with("Project license: MIT (declared in Cargo.toml)\n"));
+        assert!(out.contains("License files: LICENSE (MIT)\n"));
+        assert!(out.contains("Dependencies: 3 (cargo 3)\n  Permissive: 1\n"));
+        assert!(out.contains("  Strong copyleft: 1 - readline 1.0.0 (GPL-3.0)\n"));
+        assert!(out.contains("  Unknown: 1 - mystery 1.0.0 (none)\n"));
+        assert!(out.contains("1 dependencies are under strong copyleft licenses"));
+
+        let out = render(&[], None, &[dependency("serde", Some("MIT"))]);
+        assert!(out.contains("Project license: none found"));
+        assert!(out.contains("The project declares no license"));
+    }
+}
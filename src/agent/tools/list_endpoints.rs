@@ -0,0 +1,565 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
+use std::time::Duration;
+
+use regex::Regex;
+use rig::completion::ToolDefinition;
+use rig::tool::Tool;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use thiserror::Error;
+use tokio::time::timeout;
+
+use crate::agent::tools::cache::{self, ResultCache};
+use crate::agent::tools::injection;
+use crate::agent::{remote, scope};
+
+const TIMEOUT_SECS: u64 = 30;
+
+/// Files with route definitions read, at most
+const MAX_FILES: usize = 500;
+
+/// Endpoints listed, at most
+const MAX_ENDPOINTS: usize = 300;
+
+/// Lines that may define a route in any of the supported frameworks, to
+/// find the files worth reading
+const CANDIDATE_PATTERN: &str = r#"\.route\(|\.nest\(|#\[(get|post|put|delete|patch|head|options)\(|\.(get|post|put|delete|patch|all|use)\(\s*['"`]/|@\w+\.(route|get|post|put|delete|patch|options|head)\(|Mapping\b"#;
+
+/// Files of the supported languages
+const CANDIDATE_GLOB: &str = "*.{rs,js,jsx,mjs,cjs,ts,tsx,py,java,kt}";
+
+/// HTTP methods as route builder functions
+const METHODS: &str = "get|post|put|delete|patch|head|options|trace|any|all";
+
+/// `.route("/path", ...` of axum and actix-web
+static ROUTE_CALL: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"\.route\(\s*"([^"]*)"\s*,"#).expect("Invalid route regex"));
+
+/// `.nest("/prefix", router)` of axum
+static NEST_CALL: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"\.nest\(\s*"([^"]*)"\s*,\s*([\w:]+)"#).expect("Invalid nest regex")
+});
+
+/// A method router and its handler: `get(handler)` (axum) or
+/// `web::get().to(handler)` (actix-web)
+static METHOD_HANDLER: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(&format!(
+        r"\b({METHODS})\s*\(\s*(?:\)\s*\.to\(\s*)?([\w:]+)"
+    ))
+    .expect("Invalid method regex")
+});
+
+/// `#[get("/path")]` of actix-web and Rocket
+static ROUTE_ATTRIBUTE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"#\[(get|post|put|delete|patch|head|options)\(\s*"([^"]*)""#)
+        .expect("Invalid attribute regex")
+});
+
+static RUST_FN: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\bfn\s+(\w+)").expect("Invalid fn regex"));
+
+/// `app.get('/path', handler)` of Express
+static EXPRESS_ROUTE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"\b\w+\.(get|post|put|delete|patch|all|use)\(\s*['"`](/[^'"`]*)['"`]\s*,([^\n]*)"#)
+        .expect("Invalid express regex")
+});
+
+/// `@app.route("/path", methods=[...])` of Flask, `@app.get("/path")` of
+/// FastAPI and Flask 2
+static PYTHON_ROUTE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r#"@\w+\.(route|get|post|put|delete|patch|options|head)\(\s*['"]([^'"]*)['"]([^\n]*)"#,
+    )
+    .expect("Invalid decorator regex")
+});
+
+static PYTHON_METHODS: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"methods\s*=\s*[\[(]([^\])]*)").expect("Invalid methods regex"));
+
+static PYTHON_DEF: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\bdef\s+(\w+)").expect("Invalid def regex"));
+
+/// A Spring mapping annotation with its arguments
+static SPRING_MAPPING: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"@(Get|Post|Put|Delete|Patch|Request)Mapping\b(?:\s*\(([^)]*)\))?")
+        .expect("Invalid mapping regex")
+});
+
+static SPRING_METHOD: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"RequestMethod\.(\w+)").expect("Invalid method regex"));
+
+/// The declaration an annotation applies to: a class or a method
+static JVM_DECLARATION: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?m)^[^@\n]*?\b(?:(class)\s+(\w+)|(\w+)\s*\()").expect("Invalid declaration regex")
+});
+
+static QUOTED: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#""([^"]*)""#).expect("Invalid quoted regex"));
+
+/// A string in single or double quotes
+static QUOTED_ANY: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"['"]([^'"]*)['"]"#).expect("Invalid quoted regex"));
+
+#[derive(Deserialize)]
+pub struct ListEndpointsArgs {
+    /// Optional directory to look for routes in (defaults to the working directory)
+    pub path: Option<String>,
+}
+
+#[derive(Debug, Error)]
+pub enum ListEndpointsError {
+    #[error("Path traversal not allowed: {0}")]
+    PathTraversal(String),
+    #[error("Search timed out after {0} seconds")]
+    Timeout(u64),
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Search failed: {0}")]
+    SearchFailed(String),
+}
+
+/// A route the service exposes
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Endpoint {
+    method: String,
+    path: String,
+    handler: String,
+    file: String,
+    line: usize,
+    framework: &'static str,
+}
+
+/// Lists the HTTP endpoints defined with axum, actix-web, Rocket, Express,
+/// Flask, FastAPI and Spring
+#[derive(Deserialize, Serialize)]
+pub struct ListEndpoints {
+    #[serde(skip)]
+    base_dir: PathBuf,
+    #[serde(skip)]
+    cache: ResultCache,
+}
+
+impl ListEndpoints {
+    pub fn new(base_dir: PathBuf) -> Self {
+        Self {
+            base_dir,
+            cache: ResultCache::default(),
+        }
+    }
+
+    /// Files under `path` with lines that look like route definitions
+    async fn candidate_files(&self, path: &str) -> Result<Vec<String>, ListEndpointsError> {
+        let output = timeout(
+            Duration::from_secs(TIMEOUT_SECS),
+            scope::search_command(
+                &self.base_dir,
+                "rg",
+                &[
+                    "-l",
+                    "-g",
+                    CANDIDATE_GLOB,
+                    "-e",
+                    CANDIDATE_PATTERN,
+                    "--",
+                    path,
+                ],
+            )
+            .output(),
+        )
+        .await
+        .map_err(|_| ListEndpointsError::Timeout(TIMEOUT_SECS))??;
+
+        match output.status.code() {
+            // 1 is no matches
+            Some(0 | 1) => {
+                let mut files: Vec<String> = String::from_utf8_lossy(&output.stdout)
+                    .lines()
+                    .map(|file| file.trim_start_matches("./").to_string())
+                    // The candidate glob would let files outside the scope in
+                    .filter(|file| scope::allows(Path::new(file)))
+                    .collect();
+                files.sort();
+                files.truncate(MAX_FILES);
+                Ok(files)
+            }
+            _ => Err(ListEndpointsError::SearchFailed(
+                String::from_utf8_lossy(&output.stderr).trim().to_string(),
+            )),
+        }
+    }
+
+    async fn list(&self, path: &str) -> Result<String, ListEndpointsError> {
+        let mut endpoints = Vec::new();
+        for file in self.candidate_files(path).await? {
+            if let Ok(text) = remote::read_to_string(&self.base_dir, &file).await {
+                endpoints.extend(extract(&file, &text));
+            }
+        }
+        Ok(render(&endpoints))
+    }
+}
+
+impl Tool for ListEndpoints {
+    const NAME: &'static str = "list_endpoints";
+
+    type Error = ListEndpointsError;
+    type Args = ListEndpointsArgs;
+    type Output = String;
+
+    async fn definition(&self, _prompt: String) -> ToolDefinition {
+        ToolDefinition {
+            name: Self::NAME.to_string(),
+            description: "List the HTTP endpoints the code defines, with method, path and the \
+                handler with its location. Understands axum and actix-web routers, \
+                actix-web/Rocket route attributes, Express, Flask and FastAPI decorators, and \
+                Spring mapping annotations. Use it to answer what APIs a service exposes. \
+                Prefixes added elsewhere (axum nest, Express routers mounted with use) are \
+                listed as their own entries, not applied."
+                .to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "path": {
+                        "type": "string",
+                        "description": "Optional directory to look for routes in (defaults to the working directory)"
+                    }
+                }
+            }),
+        }
+    }
+
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+        let path = args.path.as_deref().unwrap_or(".");
+
+        if path.contains("..") || path.starts_with('/') {
+            Err(ListEndpointsError::PathTraversal(path.to_string()))
+        } else {
+            match self.cache.get(path).await {
+                Some(cached) => Ok(cached),
+                None => {
+                    let output = injection::guard(self.list(path).await?);
+                    let sources = cache::sources(&self.base_dir, &[path], &output);
+                    self.cache.insert(path.to_string(), &output, sources).await;
+                    Ok(output)
+                }
+            }
+        }
+    }
+}
+
+/// Line number of a byte offset
+fn line_of(text: &str, offset: usize) -> usize {
+    text[..offset].matches('\n').count() + 1
+}
+
+/// The text of a call from its opening parenthesis at or after `start` to
+/// the matching closing one
+fn call_text(text: &str, start: usize) -> &str {
+    let mut depth = 0;
+    let end = text[start..]
+        .char_indices()
+        .find_map(|(index, c)| {
+            match c {
+                '(' => depth += 1,
+                ')' => depth -= 1,
+                _ => {}
+            }
+            (depth == 0 && c == ')').then_some(start + index + 1)
+        })
+        .unwrap_or(text.len());
+    &text[start..end]
+}
+
+/// The endpoints a file defines, with the extractors of its language
+fn extract(file: &str, text: &str) -> Vec<Endpoint> {
+    let extension = Path::new(file)
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .unwrap_or_default();
+    let endpoint = |method: &str, path: &str, handler: &str, offset: usize, framework| Endpoint {
+        method: method.to_uppercase(),
+        path: path.to_string(),
+        handler: handler.to_string(),
+        file: file.to_string(),
+        line: line_of(text, offset),
+        framework,
+    };
+    let mut endpoints = Vec::new();
+
+    match extension {
+        "rs" => {
+            for caps in ROUTE_CALL.captures_iter(text) {
+                let whole = caps.get(0).map_or(0..0, |whole| whole.range());
+                let call = call_text(text, whole.start + ".route".len());
+                let framework = if call.contains(".to(") {
+                    "actix-web"
+                } else {
+                    "axum"
+                };
+                for method in METHOD_HANDLER.captures_iter(&call[whole.len() - ".route".len()..]) {
+                    endpoints.push(endpoint(
+                        &method[1],
+                        &caps[1],
+                        &method[2],
+                        whole.start,
+                        framework,
+                    ));
+                }
+            }
+            for caps in NEST_CALL.captures_iter(text) {
+                let start = caps.get(0).map_or(0, |whole| whole.start());
+                endpoints.push(endpoint("nest", &caps[1], &caps[2], start, "axum"));
+            }
+            for caps in ROUTE_ATTRIBUTE.captures_iter(text) {
+                let end = caps.get(0).map_or(0, |whole| whole.end());
+                let handler = RUST_FN
+                    .captures(&text[end..])
+                    .map_or("?".to_string(), |handler| handler[1].to_string());
+                endpoints.push(endpoint(&caps[1], &caps[2], &handler, end, "actix-web"));
+            }
+        }
+        "js" | "jsx" | "mjs" | "cjs" | "ts" | "tsx" => {
+            for caps in EXPRESS_ROUTE.captures_iter(text) {
+                let start = caps.get(0).map_or(0, |whole| whole.start());
+                // The handler is the last argument; middleware comes before it
+                let handler = match caps[3].trim() {
+                    rest if rest.contains("=>") || rest.contains("function") => "<inline>",
+                    rest => rest
+                        .trim_end_matches(|c: char| c == ';' || c == ')' || c.is_whitespace())
+                        .rsplit(',')
+                        .next()
+                        .map_or("?", str::trim),
+                };
+                endpoints.push(endpoint(&caps[1], &caps[2], handler, start, "Express"));
+            }
+        }
+        "py" => {
+            for caps in PYTHON_ROUTE.captures_iter(text) {
+                let whole = caps.get(0).map_or(0..0, |whole| whole.range());
+                let handler = PYTHON_DEF
+                    .captures(&text[whole.end..])
+                    .map_or("?".to_string(), |handler| handler[1].to_string());
+                match &caps[1] {
+                    "route" => {
+                        // Flask routes answer GET unless given methods
+                        let methods = PYTHON_METHODS.captures(&caps[3]).map_or_else(
+                            || vec!["GET".to_string()],
+                            |methods| {
+                                QUOTED_ANY
+                                    .captures_iter(&methods[1])
+                                    .map(|method| method[1].to_string())
+                                    .collect()
+                            },
+                        );
+                        for method in methods {
+                            endpoints.push(endpoint(
+                                &method,
+                                &caps[2],
+                                &handler,
+                                whole.start,
+                                "Flask",
+                            ));
+                        }
+                    }
+                    method => {
+                        endpoints.push(endpoint(
+                            method,
+                            &caps[2],
+                            &handler,
+                            whole.start,
+                            "FastAPI",
+                        ));
+                    }
+                }
+            }
+        }
+        "java" | "kt" => endpoints.extend(spring_endpoints(file, text)),
+        _ => {}
+    }
+    endpoints
+}
+
+/// Endpoints of Spring controllers; a class-level `@RequestMapping` is the
+/// prefix of the methods after it
+fn spring_endpoints(file: &str, text: &str) -> Vec<Endpoint> {
+    let mut endpoints = Vec::new();
+    let mut prefix = String::new();
+    for caps in SPRING_MAPPING.captures_iter(text) {
+        let whole = caps.get(0).map_or(0..0, |whole| whole.range());
+        let args = caps.get(2).map_or("", |args| args.as_str());
+        let path = QUOTED
+            .captures(args)
+            .map_or(String::new(), |path| path[1].to_string());
+        let declaration = JVM_DECLARATION.captures(&text[whole.end..]);
+        match declaration
+            .as_ref()
+            .and_then(|declaration| declaration.get(2))
+        {
+            Some(_) => prefix = path.trim_end_matches('/').to_string(),
+            None => {
+                let method = match &caps[1] {
+                    "Request" => SPRING_METHOD
+                        .captures(args)
+                        .map_or("ANY".to_string(), |method| method[1].to_string()),
+                    method => method.to_uppercase(),
+                };
+                let handler = declaration
+                    .as_ref()
+                    .and_then(|declaration| declaration.get(3))
+                    .map_or("?", |handler| handler.as_str());
+                let path = match path.as_str() {
+                    "" => prefix.clone(),
+                    path => format!("{prefix}/{}", path.trim_start_matches('/')),
+                };
+                endpoints.push(Endpoint {
+                    method,
+                    path: if path.is_empty() {
+                        "/".to_string()
+                    } else {
+                        path
+                    },
+                    handler: handler.to_string(),
+                    file: file.to_string(),
+                    line: line_of(text, whole.start),
+                    framework: "Spring",
+                });
+            }
+        }
+    }
+    endpoints
+}
+
+fn render(endpoints: &[Endpoint]) -> String {
+    if endpoints.is_empty() {
+        "No endpoints found (looked for axum, actix-web, Rocket, Express, Flask, FastAPI and \
+         Spring routes)"
+            .to_string()
+    } else {
+        let mut frameworks: BTreeMap<&str, usize> = BTreeMap::new();
+        for endpoint in endpoints {
+            *frameworks.entry(endpoint.framework).or_default() += 1;
+        }
+        let frameworks: Vec<String> = frameworks
+            .iter()
+            .map(|(framework, count)| format!("{framework} {count}"))
+            .collect();
+        let mut out = format!(
+            "{} endpoints ({})\n\n",
+            endpoints.len(),
+            frameworks.join(", ")
+        );
+
+        let mut sorted: Vec<&Endpoint> = endpoints.iter().collect();
+        sorted.sort_by(|a, b| {
+            (&a.path, &a.method, &a.file, a.line).cmp(&(&b.path, &b.method, &b.file, b.line))
+        });
+        for endpoint in sorted.iter().take(MAX_ENDPOINTS) {
+            out.push_str(&format!(
+                "{} {} -> {} ({}:{})\n",
+                endpoint.method, endpoint.path, endpoint.handler, endpoint.file, endpoint.line
+            ));
+        }
+        if sorted.len() > MAX_ENDPOINTS {
+            out.push_str(&format!(
+                "\n[{} more endpoints not listed; look in a subdirectory to see them]\n",
+                sorted.len() - MAX_ENDPOINTS
+            ));
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn summary(file: &str, text: &str) -> Vec<String> {
+        extract(file, text)
+            .into_iter()
+            .map(|e| {
+                format!(
+                    "{} {} {} :{} {}",
+                    e.method, e.path, e.handler, e.line, e.framework
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_rust_routes() {
+        let axum = r#"
+let app = Router::new()
+    .route("/", get(root))
+    .route(
+        "/users/{id}",
+        get(handlers::get_user).delete(delete_user),
+    )
+    .nest("/api", api::router());
+"#;
+        assert_eq!(
+            summary("src/main.rs", axum),
+            [
+                "GET / root :3 axum",
+                "GET /users/{id} handlers::get_user :4 axum",
+                "DELETE /users/{id} delete_user :4 axum",
+                "NEST /api api::router :8 axum",
+            ]
+        );
+
+        let actix = "#[post(\"/echo\")]\nasync fn echo(body: String) {}\n\
+                     App::new().route(\"/hey\", web::get().to(manual_hello));\n";
+        assert_eq!(
+            summary("src/main.rs", actix),
+            [
+                "GET /hey manual_hello :3 actix-web",
+                "POST /echo echo :1 actix-web",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_other_frameworks() {
+        let express = "app.get('/users', auth, listUsers);\nrouter.post(\"/users\", (req, res) => {});\nmap.get(key);\n";
+        assert_eq!(
+            summary("server.js", express),
+            [
+                "GET /users listUsers :1 Express",
+                "POST /users <inline> :2 Express"
+            ]
+        );
+
+        let python = "@app.route('/login', methods=['GET', 'POST'])\ndef login():\n    pass\n\n@router.get(\"/items/{id}\")\nasync def read_item(id: int):\n    pass\n";
+        assert_eq!(
+            summary("app.py", python),
+            [
+                "GET /login login :1 Flask",
+                "POST /login login :1 Flask",
+                "GET /items/{id} read_item :5 FastAPI",
+            ]
+        );
+
+        let spring = "@RestController\n@RequestMapping(\"/api\")\npublic class UserController {\n    @GetMapping(\"/users/{id}\")\n    public User get(@PathVariable long id) {}\n\n    @RequestMapping(value = \"/users\", method = RequestMethod.POST)\n    public User create(@RequestBody User user) {}\n}\n";
+        assert_eq!(
+            summary("UserController.java", spring),
+            [
+                "GET /api/users/{id} get :4 Spring",
+                "POST /api/users create :7 Spring",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_render() {
+        let endpoints = extract(
+            "app.py",
+            "@app.get('/b')\ndef b(): pass\n@app.post('/a')\ndef a(): pass\n",
+        );
+        assert_eq!(
+            render(&endpoints),
+            "2 endpoints (FastAPI 2)\n\nPOST /a -> a (app.py:3)\nGET /b -> b (app.py:1)\n"
+        );
+        assert!(render(&[]).starts_with("No endpoints found"));
+    }
+}
@@ -0,0 +1,824 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap, VecDeque};
+use std::path::{Path, PathBuf};
+use std::sync::{LazyLock, Mutex};
+use std::time::Duration;
+
+use regex::Regex;
+use rig::completion::ToolDefinition;
+use rig::tool::Tool;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use thiserror::Error;
+use tokio::time::timeout;
+
+use crate::agent::{remote, scope};
+
+const TIMEOUT_SECS: u64 = 30;
+
+/// Source files read, at most
+const MAX_FILES: usize = 2000;
+
+/// Larger files, usually generated, are skipped
+const MAX_FILE_BYTES: usize = 200 * 1024; // 200KB
+
+/// Modules listed per ranking or dependency list, at most
+const MAX_LISTED: usize = 30;
+
+/// Extensions of the files the graph is built from
+const EXTENSIONS: &[&str] = &["rs", "py", "js", "jsx", "mjs", "cjs", "ts", "tsx", "go"];
+
+/// Extensions and index files tried for a relative JavaScript import
+const JS_CANDIDATES: &[&str] = &[
+    "",
+    ".ts",
+    ".tsx",
+    ".js",
+    ".jsx",
+    ".mjs",
+    ".cjs",
+    "/index.ts",
+    "/index.tsx",
+    "/index.js",
+    "/index.jsx",
+];
+
+/// The last graph built, as DOT, for `/export <file>.dot`
+static LAST_DOT: Mutex<Option<String>> = Mutex::new(None);
+
+/// A Rust `use` declaration of a crate-local path, up to its `;`
+static RUST_USE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"\buse\s+((?:crate|super|self)\b[^;]*);").expect("Invalid use regex")
+});
+
+/// A crate-local path used in code, e.g. `crate::agent::remote::command`
+static RUST_PATH: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\b(?:crate|super|self)(?:::\w+)+").expect("Invalid path regex"));
+
+/// A `mod name;` declaration of a module in its own file
+static RUST_MOD: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?m)^\s*(?:pub(?:\([^)]*\))?\s+)?mod\s+(\w+)\s*;").expect("Invalid mod regex")
+});
+
+/// `import a.b, c`
+static PYTHON_IMPORT: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?m)^\s*import\s+([\w.]+(?:\s*,\s*[\w.]+)*)").expect("Invalid import regex")
+});
+
+/// `from .a.b import c, d`
+static PYTHON_FROM: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?m)^\s*from\s+(\.*)([\w.]*)\s+import\s+(?:\(([\w\s,*]+)\)|([\w \t,*]+))")
+        .expect("Invalid from regex")
+});
+
+/// A relative module specifier of an import, export or require
+static JS_IMPORT: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"(?:\bfrom\s*|\bimport\s*\(?\s*|\brequire\s*\(\s*)['"](\.{1,2}/[^'"]+)['"]"#)
+        .expect("Invalid import regex")
+});
+
+/// The block or single form of a Go import
+static GO_IMPORT: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"\bimport\s*(?:\(([^)]*)\)|(?:[\w.]+\s+)?"([^"]+)")"#)
+        .expect("Invalid import regex")
+});
+
+static QUOTED: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#""([^"]+)""#).expect("Invalid quoted regex"));
+
+static GO_MODULE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?m)^module\s+(\S+)").expect("Invalid module regex"));
+
+/// The graph last built by `module_graph`, as DOT
+pub fn last_dot() -> Option<String> {
+    LAST_DOT.lock().ok().and_then(|dot| dot.clone())
+}
+
+#[derive(Deserialize)]
+pub struct ModuleGraphArgs {
+    /// Optional directory to build the graph for (defaults to the working directory)
+    pub path: Option<String>,
+    /// Optional module whose dependents and dependencies to report
+    pub module: Option<String>,
+}
+
+#[derive(Debug, Error)]
+pub enum ModuleGraphError {
+    #[error("Path traversal not allowed: {0}")]
+    PathTraversal(String),
+    #[error("Listing files timed out after {0} seconds")]
+    Timeout(u64),
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Listing files failed: {0}")]
+    ListFailed(String),
+    #[error("No module matches {0}")]
+    UnknownModule(String),
+    #[error("{0} matches several modules: {1}")]
+    AmbiguousModule(String, String),
+}
+
+/// Which modules import which. Nodes are files, except for Go, where they
+/// are package directories.
+#[derive(Debug, Default, PartialEq, Eq)]
+struct Graph {
+    edges: BTreeMap<String, BTreeSet<String>>,
+}
+
+impl Graph {
+    fn add(&mut self, from: &str, to: &str) {
+        if from != to {
+            self.edges
+                .entry(from.to_string())
+                .or_default()
+                .insert(to.to_string());
+        }
+    }
+
+    /// Modules importing each module
+    fn reversed(&self) -> BTreeMap<&str, BTreeSet<&str>> {
+        let mut reversed: BTreeMap<&str, BTreeSet<&str>> = BTreeMap::new();
+        for (from, targets) in &self.edges {
+            for to in targets {
+                reversed.entry(to).or_default().insert(from);
+            }
+        }
+        reversed
+    }
+
+    /// The node a user-given module name refers to: a path, a path suffix,
+    /// or a Rust (`agent::remote`) or Python (`pkg.mod`) module path
+    fn find(&self, query: &str) -> Result<&str, ModuleGraphError> {
+        let query = query.trim().trim_start_matches("./").trim_end_matches('/');
+        let has_extension = Path::new(query)
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .is_some_and(|extension| EXTENSIONS.contains(&extension));
+        let as_path = if query.contains('/') || has_extension {
+            query.to_string()
+        } else {
+            query
+                .trim_start_matches("crate::")
+                .replace("::", "/")
+                .replace('.', "/")
+        };
+        let stem = |node: &str| {
+            let node = node.rsplit_once('.').map_or(node, |(stem, _)| stem);
+            ["/mod", "/__init__", "/index"]
+                .iter()
+                .fold(node, |node, suffix| {
+                    node.strip_suffix(suffix).unwrap_or(node)
+                })
+                .to_string()
+        };
+        let matches = |node: &str| {
+            node == query
+                || node.ends_with(&format!("/{query}"))
+                || stem(node) == as_path
+                || stem(node).ends_with(&format!("/{as_path}"))
+        };
+
+        let nodes: Vec<&str> = self.edges.keys().map(String::as_str).collect();
+        match nodes.iter().find(|node| **node == query) {
+            Some(node) => Ok(node),
+            None => {
+                let found: Vec<&str> = nodes.into_iter().filter(|node| matches(node)).collect();
+                match found.as_slice() {
+                    [node] => Ok(node),
+                    [] => Err(ModuleGraphError::UnknownModule(query.to_string())),
+                    several => Err(ModuleGraphError::AmbiguousModule(
+                        query.to_string(),
+                        several.join(", "),
+                    )),
+                }
+            }
+        }
+    }
+
+    fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph modules {\n    rankdir=LR;\n    node [shape=box];\n");
+        for (from, targets) in &self.edges {
+            if targets.is_empty() {
+                dot.push_str(&format!("    \"{from}\";\n"));
+            }
+            for to in targets {
+                dot.push_str(&format!("    \"{from}\" -> \"{to}\";\n"));
+            }
+        }
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+/// Builds the import graph of the workspace and answers what depends on a
+/// module
+#[derive(Deserialize, Serialize)]
+pub struct ModuleGraph {
+    #[serde(skip)]
+    base_dir: PathBuf,
+}
+
+impl ModuleGraph {
+    pub fn new(base_dir: PathBuf) -> Self {
+        Self { base_dir }
+    }
+
+    /// Source files under `path` in a supported language
+    async fn source_files(&self, path: &str) -> Result<Vec<String>, ModuleGraphError> {
+        let output = timeout(
+            Duration::from_secs(TIMEOUT_SECS),
+            scope::search_command(&self.base_dir, "rg", &["--files", "--", path]).output(),
+        )
+        .await
+        .map_err(|_| ModuleGraphError::Timeout(TIMEOUT_SECS))??;
+
+        match output.status.code() {
+            Some(0 | 1) => {
+                let mut files: Vec<String> = String::from_utf8_lossy(&output.stdout)
+                    .lines()
+                    .filter(|file| {
+                        Path::new(file)
+                            .extension()
+                            .and_then(|extension| extension.to_str())
+                            .is_some_and(|extension| EXTENSIONS.contains(&extension))
+                    })
+                    .map(|file| file.trim_start_matches("./").to_string())
+                    .collect();
+                files.sort();
+                files.truncate(MAX_FILES);
+                Ok(files)
+            }
+            _ => Err(ModuleGraphError::ListFailed(
+                String::from_utf8_lossy(&output.stderr).trim().to_string(),
+            )),
+        }
+    }
+
+    /// How many modules import each module under `path`, telling the
+    /// central ones
+    pub async fn fan_in(&self, path: &str) -> Result<BTreeMap<String, usize>, ModuleGraphError> {
+        let graph = self.graph(path).await?;
+        Ok(graph
+            .reversed()
+            .into_iter()
+            .map(|(module, importers)| (module.to_string(), importers.len()))
+            .collect())
+    }
+
+    async fn graph(&self, path: &str) -> Result<Graph, ModuleGraphError> {
+        let mut sources = Vec::new();
+        for file in self.source_files(path).await? {
+            if let Ok(text) = remote::read_to_string(&self.base_dir, &file).await
+                && text.len() <= MAX_FILE_BYTES
+            {
+                sources.push((file, text));
+            }
+        }
+        let go_mod = join(path, "go.mod");
+        let go_module = remote::read_to_string(&self.base_dir, &go_mod)
+            .await
+            .ok()
+            .and_then(|text| GO_MODULE.captures(&text).map(|caps| caps[1].to_string()))
+            .map(|module| (module, parent(&go_mod).to_string()));
+        Ok(build(&sources, go_module.as_ref()))
+    }
+}
+
+impl Tool for ModuleGraph {
+    const NAME: &'static str = "module_graph";
+
+    type Error = ModuleGraphError;
+    type Args = ModuleGraphArgs;
+    type Output = String;
+
+    async fn definition(&self, _prompt: String) -> ToolDefinition {
+        ToolDefinition {
+            name: Self::NAME.to_string(),
+            description: "Build the import/use dependency graph of the workspace's Rust, \
+                Python, JavaScript/TypeScript and Go modules. Without `module`, lists the most \
+                imported and most importing modules. With `module` (a path like \
+                src/agent/remote.rs or a module path like agent::remote or pkg.utils), lists \
+                what imports it directly and transitively, i.e. what would break if it were \
+                removed, and what it imports. The user can save the graph as DOT with \
+                `/export graph.dot`."
+                .to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "path": {
+                        "type": "string",
+                        "description": "Optional directory to build the graph for (defaults to the working directory)"
+                    },
+                    "module": {
+                        "type": "string",
+                        "description": "Optional module whose dependents and dependencies to report"
+                    }
+                }
+            }),
+        }
+    }
+
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+        let path = args.path.as_deref().unwrap_or(".");
+
+        if path.contains("..") || path.starts_with('/') {
+            Err(ModuleGraphError::PathTraversal(path.to_string()))
+        } else {
+            let graph = self.graph(path).await?;
+            if let Ok(mut dot) = LAST_DOT.lock() {
+                *dot = Some(graph.to_dot());
+            }
+            match args.module.as_deref() {
+                Some(module) => Ok(render_module(&graph, graph.find(module)?)),
+                None => Ok(render_summary(&graph)),
+            }
+        }
+    }
+}
+
+/// `name` in the directory `path`
+fn join(path: &str, name: &str) -> String {
+    match path.trim_end_matches('/') {
+        "." | "" => name.to_string(),
+        dir => format!("{dir}/{name}"),
+    }
+}
+
+/// Directory of a relative path, "." at the top
+fn parent(path: &str) -> &str {
+    path.rsplit_once('/').map_or(".", |(dir, _)| dir)
+}
+
+/// Resolve `.` and `..` segments; None if the path leaves the workspace
+fn normalize(path: &str) -> Option<String> {
+    let mut segments: Vec<&str> = Vec::new();
+    for segment in path.split('/') {
+        match segment {
+            "" | "." => {}
+            ".." => {
+                segments.pop()?;
+            }
+            segment => segments.push(segment),
+        }
+    }
+    Some(segments.join("/"))
+}
+
+/// Crate directory and module path of a Rust file, e.g. `src/agent/tools.rs`
+/// is `("", ["agent", "tools"])`; None outside a crate's `src`
+fn rust_module(file: &str) -> Option<(String, Vec<String>)> {
+    let (root, relative) = file
+        .split_once("src/")
+        .filter(|(root, _)| root.is_empty() || root.ends_with('/'))?;
+    let relative = relative.strip_suffix(".rs")?;
+    let relative = relative.strip_suffix("/mod").unwrap_or(relative);
+    let segments = match relative {
+        "lib" | "main" => Vec::new(),
+        relative => relative.split('/').map(str::to_string).collect(),
+    };
+    Some((root.to_string(), segments))
+}
+
+/// Dotted module names of a Python file; packages under `src/` are also
+/// known without it
+fn python_modules(file: &str) -> Vec<String> {
+    let stem = file.strip_suffix(".py").unwrap_or(file);
+    let stem = stem.strip_suffix("/__init__").unwrap_or(stem);
+    let name = stem.replace('/', ".");
+    match name.strip_prefix("src.") {
+        Some(short) => vec![short.to_string(), name.clone()],
+        None => vec![name],
+    }
+}
+
+/// The paths of a `use` tree, e.g. `crate::a::{b, c::{self, d}}` is
+/// `crate::a::b`, `crate::a::c` and `crate::a::c::d`
+fn expand_use(tree: &str) -> Vec<String> {
+    let tree: String = tree.split_whitespace().collect::<Vec<_>>().join(" ");
+    let tree = Regex::new(r"\s+as\s+\w+")
+        .map(|alias| alias.replace_all(&tree, "").into_owned())
+        .unwrap_or(tree);
+    let tree: String = tree.chars().filter(|c| !c.is_whitespace()).collect();
+
+    match (tree.find('{'), tree.ends_with('}')) {
+        (Some(open), true) => {
+            let prefix = &tree[..open];
+            let mut items = Vec::new();
+            let (mut depth, mut start) = (0, open + 1);
+            for (index, c) in tree.char_indices().skip(open + 1) {
+                match c {
+                    '{' => depth += 1,
+                    '}' if depth > 0 => depth -= 1,
+                    ',' | '}' if depth == 0 => {
+                        items.push(&tree[start..index]);
+                        start = index + 1;
+                    }
+                    _ => {}
+                }
+            }
+            items
+                .into_iter()
+                .filter(|item| !item.is_empty())
+                .flat_map(|item| expand_use(&format!("{prefix}{item}")))
+                .collect()
+        }
+        _ => {
+            let path = tree.trim_end_matches("::*").trim_end_matches("::self");
+            vec![path.to_string()]
+        }
+    }
+}
+
+/// The graph of `sources`; `go_module` is the module path of `go.mod` and
+/// its directory
+fn build(sources: &[(String, String)], go_module: Option<&(String, String)>) -> Graph {
+    let mut graph = Graph::default();
+    let files: BTreeSet<&str> = sources.iter().map(|(file, _)| file.as_str()).collect();
+    let mut rust: HashMap<(String, Vec<String>), &str> = HashMap::new();
+    let mut python: HashMap<String, &str> = HashMap::new();
+    for (file, _) in sources {
+        match Path::new(file)
+            .extension()
+            .and_then(|extension| extension.to_str())
+        {
+            Some("rs") => {
+                // Files are sorted, so lib.rs rather than main.rs is the root
+                if let Some(module) = rust_module(file) {
+                    rust.entry(module).or_insert(file);
+                }
+            }
+            Some("py") => {
+                for name in python_modules(file) {
+                    python.entry(name).or_insert(file);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    for (file, text) in sources {
+        let node = if file.ends_with(".go") {
+            parent(file).to_string()
+        } else {
+            file.clone()
+        };
+        graph.edges.entry(node.clone()).or_default();
+        let targets = match Path::new(file)
+            .extension()
+            .and_then(|extension| extension.to_str())
+        {
+            Some("rs") => rust_imports(file, text, &rust),
+            Some("py") => python_imports(file, text, &python),
+            Some("go") => go_imports(text, go_module, &files),
+            _ => js_imports(file, text, &files),
+        };
+        for target in targets {
+            graph.add(&node, &target);
+        }
+    }
+    graph
+}
+
+fn rust_imports(
+    file: &str,
+    text: &str,
+    modules: &HashMap<(String, Vec<String>), &str>,
+) -> Vec<String> {
+    rust_module(file).map_or_else(Vec::new, |(root, current)| {
+        // The module a path refers into: its longest prefix that is a module
+        let resolve = |path: &str| {
+            let mut segments = current.clone();
+            for segment in path.split("::") {
+                match segment {
+                    "crate" => segments.clear(),
+                    "self" => {}
+                    "super" => {
+                        segments.pop();
+                    }
+                    segment => segments.push(segment.to_string()),
+                }
+            }
+            (0..=segments.len())
+                .rev()
+                .find_map(|len| modules.get(&(root.clone(), segments[..len].to_vec())))
+                .map(|target| target.to_string())
+        };
+
+        let uses = RUST_USE
+            .captures_iter(text)
+            .flat_map(|caps| expand_use(&caps[1]));
+        let code = RUST_USE.replace_all(text, "");
+        let paths = RUST_PATH
+            .find_iter(&code)
+            .map(|found| found.as_str().to_string());
+        let children = RUST_MOD
+            .captures_iter(text)
+            .map(|caps| format!("self::{}", &caps[1]));
+        uses.chain(paths)
+            .chain(children)
+            .filter_map(|path| resolve(&path))
+            .collect()
+    })
+}
+
+fn python_imports(file: &str, text: &str, modules: &HashMap<String, &str>) -> Vec<String> {
+    // The longest prefix of a dotted name that is a module of the workspace
+    let resolve = |name: &str| {
+        let parts: Vec<&str> = name.split('.').collect();
+        (1..=parts.len())
+            .rev()
+            .find_map(|len| modules.get(&parts[..len].join(".")))
+            .map(|target| target.to_string())
+    };
+    let current = python_modules(file).remove(0);
+    let mut package: Vec<&str> = current.split('.').collect();
+    if !file.ends_with("__init__.py") {
+        package.pop();
+    }
+
+    let imports = PYTHON_IMPORT.captures_iter(text).flat_map(|caps| {
+        caps[1]
+            .split(',')
+            .filter_map(|name| resolve(name.trim()))
+            .collect::<Vec<_>>()
+    });
+    let from = PYTHON_FROM.captures_iter(text).flat_map(|caps| {
+        let dots = caps[1].len();
+        let base = match dots {
+            0 => caps[2].to_string(),
+            dots => {
+                let up = package.len().saturating_sub(dots - 1);
+                package[..up]
+                    .iter()
+                    .copied()
+                    .chain(caps[2].split('.').filter(|part| !part.is_empty()))
+                    .collect::<Vec<_>>()
+                    .join(".")
+            }
+        };
+        // Imported names may be submodules of the package
+        caps.get(3)
+            .or_else(|| caps.get(4))
+            .map_or("", |names| names.as_str())
+            .split(',')
+            .filter_map(|name| name.split_whitespace().next())
+            .filter_map(|name| {
+                let submodule = if base.is_empty() {
+                    name.to_string()
+                } else {
+                    format!("{base}.{name}")
+                };
+                modules
+                    .get(&submodule)
+                    .map(|target| target.to_string())
+                    .or_else(|| resolve(&base))
+            })
+            .collect::<Vec<_>>()
+    });
+    imports.chain(from).collect()
+}
+
+fn js_imports(file: &str, text: &str, files: &BTreeSet<&str>) -> Vec<String> {
+    JS_IMPORT
+        .captures_iter(text)
+        .filter_map(|caps| {
+            let target = normalize(&format!("{}/{}", parent(file), &caps[1]))?;
+            JS_CANDIDATES
+                .iter()
+                .map(|suffix| format!("{target}{suffix}"))
+                .find(|candidate| files.contains(candidate.as_str()))
+        })
+        .collect()
+}
+
+fn go_imports(
+    text: &str,
+    go_module: Option<&(String, String)>,
+    files: &BTreeSet<&str>,
+) -> Vec<String> {
+    go_module.map_or_else(Vec::new, |(module, root)| {
+        GO_IMPORT
+            .captures_iter(text)
+            .flat_map(|caps| match (caps.get(1), caps.get(2)) {
+                (Some(block), _) => QUOTED
+                    .captures_iter(block.as_str())
+                    .map(|quoted| quoted[1].to_string())
+                    .collect(),
+                (None, Some(single)) => vec![single.as_str().to_string()],
+                (None, None) => Vec::new(),
+            })
+            .filter_map(|import| {
+                let package = import
+                    .strip_prefix(module.as_str())
+                    .filter(|package| package.is_empty() || package.starts_with('/'))?;
+                let dir = join(root, package.trim_start_matches('/'));
+                let dir = if dir.is_empty() { ".".to_string() } else { dir };
+                // Only packages with Go files in the workspace are nodes
+                files
+                    .iter()
+                    .any(|file| file.ends_with(".go") && parent(file) == dir)
+                    .then_some(dir)
+            })
+            .collect()
+    })
+}
+
+/// Most imported and most importing modules
+fn render_summary(graph: &Graph) -> String {
+    let reversed = graph.reversed();
+    let count: usize = graph.edges.values().map(BTreeSet::len).sum();
+    let mut out = format!(
+        "Import graph of {} modules with {count} dependencies\n",
+        graph.edges.len()
+    );
+
+    let mut imported: Vec<(&str, usize)> = reversed
+        .iter()
+        .map(|(node, importers)| (*node, importers.len()))
+        .collect();
+    imported.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+    out.push_str("\nMost imported:\n");
+    for (node, count) in imported.into_iter().take(MAX_LISTED) {
+        out.push_str(&format!("  {node} - imported by {count}\n"));
+    }
+
+    let mut importing: Vec<(&str, usize)> = graph
+        .edges
+        .iter()
+        .map(|(node, targets)| (node.as_str(), targets.len()))
+        .filter(|(_, count)| *count > 0)
+        .collect();
+    importing.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+    out.push_str("\nMost importing:\n");
+    for (node, count) in importing.into_iter().take(MAX_LISTED) {
+        out.push_str(&format!("  {node} - imports {count}\n"));
+    }
+    out
+}
+
+/// What imports `module`, directly and transitively, and what it imports
+fn render_module(graph: &Graph, module: &str) -> String {
+    let reversed = graph.reversed();
+    let direct = reversed.get(module).cloned().unwrap_or_default();
+
+    // Everything that reaches the module through its importers
+    let mut affected: BTreeSet<&str> = BTreeSet::new();
+    let mut queue: VecDeque<&str> = direct.iter().copied().collect();
+    while let Some(node) = queue.pop_front() {
+        if node != module && affected.insert(node) {
+            queue.extend(reversed.get(node).into_iter().flatten().copied());
+        }
+    }
+    let direct: Vec<&str> = direct.into_iter().collect();
+    let indirect: Vec<&str> = affected
+        .iter()
+        .filter(|node| !direct.contains(node))
+        .copied()
+        .collect();
+    let imports: Vec<&str> = graph
+        .edges
+        .get(module)
+        .into_iter()
+        .flatten()
+        .map(String::as_str)
+        .collect();
+
+    format!(
+        "{module}\n\nImported directly by {}:\n{}\nImported indirectly, through those, by {}:\n{}\n\
+         Removing or changing its interface could break {} modules.\n\nIt imports {}:\n{}",
+        direct.len(),
+        list(&direct),
+        indirect.len(),
+        list(&indirect),
+        affected.len(),
+        imports.len(),
+        list(&imports)
+    )
+}
+
+/// One module per line, cut at `MAX_LISTED`
+fn list(nodes: &[&str]) -> String {
+    let mut out: String = nodes
+        .iter()
+        .take(MAX_LISTED)
+        .map(|node| format!("  {node}\n"))
+        .collect();
+    if nodes.len() > MAX_LISTED {
+        out.push_str(&format!("  ... and {} more\n", nodes.len() - MAX_LISTED));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sources(files: &[(&str, &str)]) -> Vec<(String, String)> {
+        files
+            .iter()
+            .map(|(file, text)| (file.to_string(), text.to_string()))
+            .collect()
+    }
+
+    fn targets<'a>(graph: &'a Graph, node: &str) -> Vec<&'a str> {
+        graph.edges[node].iter().map(String::as_str).collect()
+    }
+
+    #[test]
+    fn test_expand_use() {
+        assert_eq!(
+            expand_use("crate::agent::{remote, tools::{self, cache as c}, *}"),
+            [
+                "crate::agent::remote",
+                "crate::agent::tools",
+                "crate::agent::tools::cache",
+                "crate::agent"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_rust_graph() {
+        let graph = build(
+            &sources(&[
+                ("src/main.rs", "mod agent;\nfn main() { agent::run(); }\n"),
+                (
+                    "src/agent.rs",
+                    "mod remote;\nuse crate::agent::{\n    remote::command,\n};\n",
+                ),
+                (
+                    "src/agent/remote.rs",
+                    "use super::Config;\nfn f() { crate::helpers::x(); }\n",
+                ),
+            ]),
+            None,
+        );
+        assert_eq!(targets(&graph, "src/main.rs"), ["src/agent.rs"]);
+        assert_eq!(targets(&graph, "src/agent.rs"), ["src/agent/remote.rs"]);
+        assert_eq!(
+            targets(&graph, "src/agent/remote.rs"),
+            ["src/agent.rs", "src/main.rs"]
+        );
+    }
+
+    #[test]
+    fn test_other_languages() {
+        let graph = build(
+            &sources(&[
+                ("pkg/__init__.py", ""),
+                ("pkg/util.py", "import os\n"),
+                (
+                    "pkg/app.py",
+                    "from . import util\nfrom pkg.models import User\n",
+                ),
+                ("pkg/models.py", "import pkg.util as u\n"),
+                (
+                    "web/app.ts",
+                    "import { a } from './lib';\nconst b = require(\"../web/lib/index\");\n",
+                ),
+                ("web/lib/index.ts", "export * from 'react';\n"),
+                (
+                    "cmd/main.go",
+                    "package main\nimport (\n\t\"fmt\"\n\t\"example.com/app/store\"\n)\n",
+                ),
+                ("store/db.go", "package store\n"),
+            ]),
+            Some(&("example.com/app".to_string(), ".".to_string())),
+        );
+        assert_eq!(
+            targets(&graph, "pkg/app.py"),
+            ["pkg/models.py", "pkg/util.py"]
+        );
+        assert_eq!(targets(&graph, "pkg/models.py"), ["pkg/util.py"]);
+        assert_eq!(targets(&graph, "web/app.ts"), ["web/lib/index.ts"]);
+        assert_eq!(targets(&graph, "cmd"), ["store"]);
+    }
+
+    #[test]
+    fn test_render_module() {
+        let mut graph = Graph::default();
+        graph.add("src/main.rs", "src/agent.rs");
+        graph.add("src/agent.rs", "src/agent/remote.rs");
+        graph.add("src/tools.rs", "src/agent/remote.rs");
+        graph
+            .edges
+            .entry("src/agent/remote.rs".to_string())
+            .or_default();
+
+        let module = graph.find("agent::remote").unwrap();
+        assert_eq!(module, "src/agent/remote.rs");
+        let out = render_module(&graph, module);
+        assert!(out.contains("Imported directly by 2:\n  src/agent.rs\n  src/tools.rs\n"));
+        assert!(out.contains("indirectly, through those, by 1:\n  src/main.rs\n"));
+        assert!(out.contains("could break 3 modules"));
+
+        assert!(matches!(
+            graph.find("nothing"),
+            Err(ModuleGraphError::UnknownModule(_))
+        ));
+        assert!(
+            graph
+                .to_dot()
+                .contains("    \"src/main.rs\" -> \"src/agent.rs\";\n")
+        );
+    }
+}
@@ -0,0 +1,320 @@
+use std::process::Stdio;
+use std::time::Duration;
+
+use rig::completion::ToolDefinition;
+use rig::tool::Tool;
+use serde::Deserialize;
+use serde_json::json;
+use thiserror::Error;
+use tokio::io::AsyncReadExt;
+use tokio::process::Command;
+use tokio::time::timeout;
+
+use crate::agent::tools::injection;
+
+/// Object content or listing returned at most
+const MAX_BYTES: usize = 50 * 1024; // 50KB
+const TIMEOUT_SECS: u64 = 30;
+
+/// `[tools.object-storage]` config section
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ObjectStorageConfig {
+    /// Prefixes the agent may list and read, e.g. `s3://data-lake/raw/` or
+    /// `gs://team-configs/`
+    pub prefixes: Vec<String>,
+}
+
+#[derive(Deserialize)]
+pub struct ObjectArgs {
+    /// `s3://bucket/key` or `gs://bucket/key`
+    pub uri: String,
+}
+
+#[derive(Debug, Error)]
+pub enum ObjectStorageError {
+    #[error("Not an s3:// or gs:// URI: {0}")]
+    InvalidUri(String),
+    #[error("{0} is outside the configured prefixes: {1}")]
+    OutsidePrefixes(String, String),
+    #[error("Failed to run {0}: {1}")]
+    Spawn(&'static str, std::io::Error),
+    #[error("{0} timed out after {TIMEOUT_SECS} seconds")]
+    Timeout(&'static str),
+    #[error("{0} failed: {1}")]
+    Failed(&'static str, String),
+    #[error("{0} is not a text object")]
+    Binary(String),
+}
+
+/// Storage service of a URI, accessed through its CLI and the credentials
+/// the CLI is logged in with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Service {
+    S3,
+    Gcs,
+}
+
+impl Service {
+    fn of(uri: &str) -> Option<Self> {
+        match uri.split_once("://") {
+            Some(("s3", rest)) if !rest.is_empty() => Some(Self::S3),
+            Some(("gs", rest)) if !rest.is_empty() => Some(Self::Gcs),
+            _ => None,
+        }
+    }
+
+    /// Read-only commands only: listing, and copying an object to stdout
+    fn command(self, list: bool, uri: &str) -> (&'static str, Vec<&str>) {
+        match (self, list) {
+            (Self::S3, true) => ("aws", vec!["s3", "ls", uri]),
+            (Self::S3, false) => ("aws", vec!["s3", "cp", "--quiet", uri, "-"]),
+            (Self::Gcs, true) => ("gcloud", vec!["storage", "ls", "--long", uri]),
+            (Self::Gcs, false) => ("gcloud", vec!["storage", "cat", uri]),
+        }
+    }
+}
+
+/// Read access to the configured object storage prefixes, shared by the
+/// listing and the reading tool
+#[derive(Debug, Clone)]
+pub struct ObjectStorage {
+    prefixes: Vec<String>,
+}
+
+impl ObjectStorage {
+    pub fn new(config: &ObjectStorageConfig) -> Self {
+        Self {
+            prefixes: config.prefixes.clone(),
+        }
+    }
+
+    /// The service of a URI under one of the configured prefixes
+    fn check(&self, uri: &str) -> Result<Service, ObjectStorageError> {
+        let service = Service::of(uri).ok_or_else(|| ObjectStorageError::InvalidUri(uri.into()))?;
+        let allowed = !uri.split('/').any(|segment| segment == "..")
+            && self.prefixes.iter().any(|prefix| {
+                // Prefixes are directory-like: `s3://lake/raw` covers
+                // `s3://lake/raw/x.csv` but not `s3://lake/raw-backup/`
+                let prefix = prefix.trim_end_matches('/');
+                uri.trim_end_matches('/') == prefix
+                    || uri
+                        .strip_prefix(prefix)
+                        .is_some_and(|rest| rest.starts_with('/'))
+            });
+        if allowed {
+            Ok(service)
+        } else {
+            Err(ObjectStorageError::OutsidePrefixes(
+                uri.to_string(),
+                self.prefixes.join(", "),
+            ))
+        }
+    }
+
+    /// Run the CLI for a listing or a read, returning at most `max_bytes`
+    /// of its output and whether there was more
+    async fn run(
+        &self,
+        list: bool,
+        uri: &str,
+        max_bytes: usize,
+    ) -> Result<(Vec<u8>, bool), ObjectStorageError> {
+        let (program, args) = self.check(uri)?.command(list, uri);
+        let mut child = Command::new(program)
+            .args(args)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|e| ObjectStorageError::Spawn(program, e))?;
+
+        let mut stdout = child.stdout.take().expect("stdout is piped");
+        let mut stderr = child.stderr.take().expect("stderr is piped");
+        let collect = async {
+            // Read one byte past the cap to know whether it was hit; the
+            // rest of a large object is never downloaded
+            let mut output = Vec::new();
+            (&mut stdout)
+                .take(max_bytes as u64 + 1)
+                .read_to_end(&mut output)
+                .await?;
+            let truncated = output.len() > max_bytes;
+            output.truncate(max_bytes);
+
+            let status = if truncated {
+                child.kill().await?;
+                None
+            } else {
+                Some(child.wait().await?)
+            };
+            let mut errors = String::new();
+            stderr.read_to_string(&mut errors).await?;
+            Ok::<_, std::io::Error>((output, truncated, status, errors))
+        };
+
+        let (output, truncated, status, errors) =
+            timeout(Duration::from_secs(TIMEOUT_SECS), collect)
+                .await
+                .map_err(|_| ObjectStorageError::Timeout(program))?
+                .map_err(|e| ObjectStorageError::Spawn(program, e))?;
+        match status {
+            Some(status) if !status.success() => Err(ObjectStorageError::Failed(
+                program,
+                errors.trim().to_string(),
+            )),
+            _ => Ok((output, truncated)),
+        }
+    }
+}
+
+/// Lists the objects and sub-prefixes under a storage prefix
+pub struct ListObjects {
+    storage: ObjectStorage,
+}
+
+impl ListObjects {
+    pub fn new(storage: ObjectStorage) -> Self {
+        Self { storage }
+    }
+}
+
+impl Tool for ListObjects {
+    const NAME: &'static str = "list_objects";
+
+    type Error = ObjectStorageError;
+    type Args = ObjectArgs;
+    type Output = String;
+
+    async fn definition(&self, _prompt: String) -> ToolDefinition {
+        ToolDefinition {
+            name: Self::NAME.to_string(),
+            description: format!(
+                "List the objects (with size and modification time) and sub-prefixes directly \
+                under an S3 or GCS prefix, like `ls` for a directory. End directory-like \
+                prefixes with /. Only these prefixes can be accessed: {}",
+                self.storage.prefixes.join(", ")
+            ),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "uri": {
+                        "type": "string",
+                        "description": "Prefix to list, e.g. s3://bucket/path/"
+                    }
+                },
+                "required": ["uri"]
+            }),
+        }
+    }
+
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+        let (output, truncated) = self.storage.run(true, &args.uri, MAX_BYTES).await?;
+        let listing = String::from_utf8_lossy(&output);
+        Ok(match (listing.trim_end(), truncated) {
+            ("", _) => format!("Nothing under {}", args.uri),
+            // Drop the entry the cap cut off
+            (listing, true) => format!(
+                "{}\n\n[truncated - listing exceeds 50KB; list a narrower prefix]",
+                listing
+                    .rsplit_once('\n')
+                    .map_or(listing, |(complete, _)| complete)
+            ),
+            (listing, false) => listing.to_string(),
+        })
+    }
+}
+
+/// Reads a text object, up to `MAX_BYTES`
+pub struct ReadObject {
+    storage: ObjectStorage,
+}
+
+impl ReadObject {
+    pub fn new(storage: ObjectStorage) -> Self {
+        Self { storage }
+    }
+}
+
+impl Tool for ReadObject {
+    const NAME: &'static str = "read_object";
+
+    type Error = ObjectStorageError;
+    type Args = ObjectArgs;
+    type Output = String;
+
+    async fn definition(&self, _prompt: String) -> ToolDefinition {
+        ToolDefinition {
+            name: Self::NAME.to_string(),
+            description: format!(
+                "Read a text object (config, schema, CSV, JSON lines, ...) from S3 or GCS. \
+                Only the first 50KB are returned. Only these prefixes can be accessed: {}",
+                self.storage.prefixes.join(", ")
+            ),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "uri": {
+                        "type": "string",
+                        "description": "Object to read, e.g. s3://bucket/path/config.yaml"
+                    }
+                },
+                "required": ["uri"]
+            }),
+        }
+    }
+
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+        let (mut output, truncated) = self.storage.run(false, &args.uri, MAX_BYTES).await?;
+        // The cap may have split a character
+        if truncated
+            && let Err(e) = std::str::from_utf8(&output)
+            && e.error_len().is_none()
+        {
+            output.truncate(e.valid_up_to());
+        }
+        let mut content =
+            String::from_utf8(output).map_err(|_| ObjectStorageError::Binary(args.uri))?;
+        if truncated {
+            content.push_str("\n\n[truncated - object exceeds 50KB limit]");
+        }
+        Ok(injection::guard(content))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check() {
+        let storage = ObjectStorage::new(&ObjectStorageConfig {
+            prefixes: vec!["s3://lake/raw/".to_string(), "gs://configs".to_string()],
+        });
+
+        assert_eq!(storage.check("s3://lake/raw/").unwrap(), Service::S3);
+        assert_eq!(storage.check("s3://lake/raw").unwrap(), Service::S3);
+        assert_eq!(
+            storage.check("gs://configs/prod/app.yaml").unwrap(),
+            Service::Gcs
+        );
+
+        assert!(matches!(
+            storage.check("s3://lake/curated/x.csv"),
+            Err(ObjectStorageError::OutsidePrefixes(..))
+        ));
+        assert!(matches!(
+            storage.check("gs://configs-secret/key.json"),
+            Err(ObjectStorageError::OutsidePrefixes(..))
+        ));
+        assert!(matches!(
+            storage.check("s3://lake/raw/../curated/x.csv"),
+            Err(ObjectStorageError::OutsidePrefixes(..))
+        ));
+        assert!(matches!(
+            storage.check("/lake/raw/x.csv"),
+            Err(ObjectStorageError::InvalidUri(_))
+        ));
+    }
+}
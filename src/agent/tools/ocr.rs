@@ -0,0 +1,285 @@
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, UNIX_EPOCH};
+
+use regex::{Regex, RegexBuilder};
+use sha2::{Digest, Sha256};
+use tokio::process::Command;
+use tokio::time::timeout;
+
+use crate::agent::scope;
+
+/// Directory of the OCR results, relative to the workspace
+pub const OCR_DIR: &str = ".horse/ocr";
+
+/// Files that may hold scanned text
+const OCR_GLOBS: &[&str] = &[
+    "*.{png,jpg,jpeg,tif,tiff,bmp,gif,webp}",
+    "*.{PNG,JPG,JPEG,TIF,TIFF,BMP,GIF,WEBP}",
+    "*.{pdf,PDF}",
+];
+
+/// Files OCRed per search, at most; the rest are left for later searches,
+/// which find the earlier results cached
+const MAX_OCR_FILES: usize = 50;
+
+/// Per page or image
+const OCR_TIMEOUT_SECS: u64 = 60;
+
+/// PDFs with more text than this on their first pages have a text layer,
+/// which rga searches already
+const TEXT_LAYER_CHARS: usize = 100;
+
+/// Resolution PDF pages are rendered at for OCR
+const PDF_DPI: &str = "200";
+
+/// Text recognized in the scanned PDFs and images under `path`, searched
+/// for `query` like rga does, with `context` lines around each match.
+///
+/// Returns `None` if nothing matched. Fails with `NotFound` if tesseract
+/// isn't installed.
+pub async fn search(
+    base_dir: &Path,
+    query: &str,
+    path: &str,
+    context: usize,
+) -> std::io::Result<Option<String>> {
+    let pattern = RegexBuilder::new(query)
+        .case_insensitive(true)
+        .build()
+        .or_else(|_| {
+            RegexBuilder::new(&regex::escape(query))
+                .case_insensitive(true)
+                .build()
+        })
+        .map_err(std::io::Error::other)?;
+
+    let mut matches = Vec::new();
+    let mut pending = 0;
+    let mut recognized = 0;
+    for file in candidates(base_dir, path).await? {
+        let text = match cached(base_dir, &file).await {
+            Some(text) => Some(text),
+            None if recognized < MAX_OCR_FILES => {
+                recognized += 1;
+                // A file that fails is skipped, a missing program fails the search
+                match recognize(base_dir, &file).await {
+                    Ok(text) => Some(text),
+                    Err(e) if e.kind() == std::io::ErrorKind::NotFound => Err(e)?,
+                    Err(_) => None,
+                }
+            }
+            None => {
+                pending += 1;
+                None
+            }
+        };
+        if let Some(found) = text.and_then(|text| grep(&file, &text, &pattern, context)) {
+            matches.push(found);
+        }
+    }
+
+    let mut out = matches.join("\n--\n");
+    if pending > 0 {
+        out.push_str(&format!(
+            "\n[{pending} more scanned files not OCRed yet; search again to include them]"
+        ));
+    }
+    Ok((!matches.is_empty() || pending > 0).then_some(out))
+}
+
+/// Scanned files of the workspace, by what `index` did with them
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Indexed {
+    /// OCRed by earlier searches or indexing, and unchanged since
+    pub cached: usize,
+    pub recognized: usize,
+    pub failed: usize,
+}
+
+/// OCR every scanned PDF and image of the workspace that isn't cached yet,
+/// so searches find all of them rather than `MAX_OCR_FILES` more at a time.
+///
+/// Fails with `NotFound` if tesseract isn't installed.
+pub async fn index(base_dir: &Path) -> std::io::Result<Indexed> {
+    let mut indexed = Indexed::default();
+    for file in candidates(base_dir, ".").await? {
+        if cached(base_dir, &file).await.is_some() {
+            indexed.cached += 1;
+        } else {
+            match recognize(base_dir, &file).await {
+                Ok(_) => indexed.recognized += 1,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => Err(e)?,
+                Err(_) => indexed.failed += 1,
+            }
+        }
+    }
+    Ok(indexed)
+}
+
+/// Images and PDFs under `path`, relative to the workspace
+async fn candidates(base_dir: &Path, path: &str) -> std::io::Result<Vec<String>> {
+    let mut args = vec!["--files"];
+    for glob in OCR_GLOBS {
+        args.extend(["-g", glob]);
+    }
+    args.extend(["--", path]);
+    let output = Command::new("rg")
+        .args(&args)
+        .current_dir(base_dir)
+        .output()
+        .await?;
+    let mut files: Vec<String> = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(|file| file.trim_start_matches("./").to_string())
+        .filter(|file| scope::allows(Path::new(file)))
+        .collect();
+    files.sort();
+    Ok(files)
+}
+
+/// Where the OCR result of `file` is kept: named by its path, size and
+/// modification time, so a changed file is OCRed again
+async fn cache_path(base_dir: &Path, file: &str) -> Option<PathBuf> {
+    let metadata = tokio::fs::metadata(base_dir.join(file)).await.ok()?;
+    let modified = metadata
+        .modified()
+        .ok()?
+        .duration_since(UNIX_EPOCH)
+        .ok()?
+        .as_nanos();
+    let key = format!("{file}\0{}\0{modified}", metadata.len());
+    Some(
+        base_dir
+            .join(OCR_DIR)
+            .join(format!("{:x}.txt", Sha256::digest(key.as_bytes()))),
+    )
+}
+
+async fn cached(base_dir: &Path, file: &str) -> Option<String> {
+    let path = cache_path(base_dir, file).await?;
+    tokio::fs::read_to_string(path).await.ok()
+}
+
+/// OCR `file` and cache the text; PDFs with a text layer are cached as
+/// empty
+async fn recognize(base_dir: &Path, file: &str) -> std::io::Result<String> {
+    let text = if !file.to_lowercase().ends_with(".pdf") {
+        tesseract(&base_dir.join(file)).await?
+    } else if has_text_layer(base_dir, file).await {
+        String::new()
+    } else {
+        recognize_pdf(base_dir, file).await?
+    };
+    if let Some(path) = cache_path(base_dir, file).await {
+        if let Some(dir) = path.parent() {
+            crate::state::create_dir(dir)?;
+        }
+        tokio::fs::write(path, &text).await?;
+    }
+    Ok(text)
+}
+
+async fn has_text_layer(base_dir: &Path, file: &str) -> bool {
+    let output = Command::new("pdftotext")
+        .args(["-l", "3", "--", file, "-"])
+        .current_dir(base_dir)
+        .output()
+        .await;
+    output.is_ok_and(|output| {
+        String::from_utf8_lossy(&output.stdout)
+            .chars()
+            .filter(|c| !c.is_whitespace())
+            .count()
+            > TEXT_LAYER_CHARS
+    })
+}
+
+/// Render the pages of a PDF with pdftoppm and OCR each of them
+async fn recognize_pdf(base_dir: &Path, file: &str) -> std::io::Result<String> {
+    let dir = std::env::temp_dir().join(format!("horse-ocr-{}", uuid::Uuid::new_v4()));
+    tokio::fs::create_dir_all(&dir).await?;
+    let rendered = timeout(
+        Duration::from_secs(OCR_TIMEOUT_SECS),
+        Command::new("pdftoppm")
+            .args(["-r", PDF_DPI, "-png", "--"])
+            .arg(base_dir.join(file))
+            .arg(dir.join("page"))
+            .output(),
+    )
+    .await
+    .map_err(|_| std::io::Error::from(std::io::ErrorKind::TimedOut))
+    .and_then(|result| result);
+
+    let mut pages: Vec<PathBuf> = Vec::new();
+    if rendered.is_ok() {
+        let mut entries = tokio::fs::read_dir(&dir).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            pages.push(entry.path());
+        }
+        pages.sort();
+    }
+    let mut text = String::new();
+    for page in &pages {
+        text.push_str(&tesseract(page).await?);
+        text.push('\n');
+    }
+    tokio::fs::remove_dir_all(&dir).await?;
+    rendered.map(|_| text)
+}
+
+/// Text of an image, empty if tesseract can't read it
+async fn tesseract(image: &Path) -> std::io::Result<String> {
+    let output = timeout(
+        Duration::from_secs(OCR_TIMEOUT_SECS),
+        Command::new("tesseract").arg(image).arg("stdout").output(),
+    )
+    .await
+    .map_err(|_| std::io::Error::from(std::io::ErrorKind::TimedOut))??;
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Matching lines of `text` with `context` lines around them, as
+/// `file:line` for matches and `file-line` for context, like rga
+fn grep(file: &str, text: &str, pattern: &Regex, context: usize) -> Option<String> {
+    let lines: Vec<&str> = text.lines().collect();
+    let hits: Vec<usize> = (0..lines.len())
+        .filter(|&i| pattern.is_match(lines[i]))
+        .collect();
+
+    let shown: BTreeSet<usize> = hits
+        .iter()
+        .flat_map(|&i| i.saturating_sub(context)..=(i + context).min(lines.len() - 1))
+        .collect();
+    (!hits.is_empty()).then(|| {
+        shown
+            .iter()
+            .map(|&i| {
+                let separator = if hits.contains(&i) { ':' } else { '-' };
+                format!("{file}{separator}[OCR] {}", lines[i])
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_grep() {
+        let text = "CONTRACT\n\nThe term of this\nagreement is 12 months.\nSigned";
+        let pattern = Regex::new("(?i)agreement").unwrap();
+        assert_eq!(
+            grep("scans/contract.png", text, &pattern, 1).unwrap(),
+            "scans/contract.png-[OCR] The term of this\n\
+             scans/contract.png:[OCR] agreement is 12 months.\n\
+             scans/contract.png-[OCR] Signed"
+        );
+        assert_eq!(
+            grep("a.png", text, &Regex::new("missing").unwrap(), 1),
+            None
+        );
+    }
+}
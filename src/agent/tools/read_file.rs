@@ -1,4 +1,4 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use rig::completion::ToolDefinition;
 use rig::tool::Tool;
@@ -6,6 +6,10 @@ use serde::{Deserialize, Serialize};
 use serde_json::json;
 use thiserror::Error;
 
+use crate::agent::tools::cache::ResultCache;
+use crate::agent::tools::injection;
+use crate::agent::{remote, scope};
+
 const MAX_BYTES: usize = 50 * 1024; // 50KB
 const MAX_LINES: usize = 1000;
 
@@ -27,17 +31,24 @@ pub enum ReadFileError {
     Io(#[from] std::io::Error),
     #[error("Path is outside base directory")]
     OutsideBaseDir,
+    #[error("{0} is outside the --include/--exclude scope of this session")]
+    OutsideScope(String),
 }
 
 #[derive(Deserialize, Serialize)]
 pub struct ReadFile {
     #[serde(skip)]
     base_dir: PathBuf,
+    #[serde(skip)]
+    cache: ResultCache,
 }
 
 impl ReadFile {
     pub fn new(base_dir: PathBuf) -> Self {
-        Self { base_dir }
+        Self {
+            base_dir,
+            cache: ResultCache::default(),
+        }
     }
 
     fn resolve_path(&self, path: &str) -> Result<PathBuf, ReadFileError> {
@@ -51,12 +62,68 @@ impl ReadFile {
             let canonical = resolved.canonicalize()?;
             let base_canonical = self.base_dir.canonicalize()?;
 
-            if canonical.starts_with(&base_canonical) {
-                Ok(canonical)
-            } else {
-                Err(ReadFileError::OutsideBaseDir)
+            match canonical.strip_prefix(&base_canonical) {
+                Ok(relative) if !scope::allows(relative) => {
+                    Err(ReadFileError::OutsideScope(path.to_string()))
+                }
+                Ok(_) => Ok(canonical),
+                Err(_) => Err(ReadFileError::OutsideBaseDir),
+            }
+        }
+    }
+
+    /// Read a file of a remote workspace. Its paths can't be canonicalized
+    /// locally, so absolute paths are rejected along with `..`.
+    async fn read_remote(&self, path: &str) -> Result<String, ReadFileError> {
+        if path.contains("..") {
+            Err(ReadFileError::PathTraversal(path.to_string()))
+        } else if Path::new(path).is_absolute() {
+            Err(ReadFileError::OutsideBaseDir)
+        } else if !scope::allows(Path::new(path)) {
+            Err(ReadFileError::OutsideScope(path.to_string()))
+        } else {
+            Ok(remote::read_to_string(&self.base_dir, path).await?)
+        }
+    }
+
+    /// The lines `start_line..=end_line` of a file, up to the size limits
+    fn select_lines(content: &str, start_line: Option<usize>, end_line: Option<usize>) -> String {
+        let lines: Vec<&str> = content.lines().collect();
+        let total_lines = lines.len();
+
+        // Apply line range filter if specified
+        let start = start_line.map(|s| s.saturating_sub(1)).unwrap_or(0);
+        let end = end_line.unwrap_or(total_lines).min(total_lines);
+
+        let selected_lines: Vec<&str> = lines
+            .into_iter()
+            .skip(start)
+            .take(end.saturating_sub(start))
+            .collect();
+
+        // Check truncation limits
+        let mut result = String::new();
+        let mut byte_count = 0;
+        let mut truncated = false;
+
+        for (line_count, line) in selected_lines.into_iter().enumerate() {
+            if line_count >= MAX_LINES || byte_count + line.len() + 1 > MAX_BYTES {
+                truncated = true;
+                break;
             }
+            if !result.is_empty() {
+                result.push('\n');
+                byte_count += 1;
+            }
+            result.push_str(line);
+            byte_count += line.len();
+        }
+
+        if truncated {
+            result.push_str("\n\n[truncated - file exceeds 50KB or 1000 lines limit]");
         }
+
+        injection::guard(result)
     }
 }
 
@@ -96,44 +163,29 @@ impl Tool for ReadFile {
     }
 
     async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
-        let path = self.resolve_path(&args.path)?;
-        let content = tokio::fs::read_to_string(&path).await?;
-
-        let lines: Vec<&str> = content.lines().collect();
-        let total_lines = lines.len();
-
-        // Apply line range filter if specified
-        let start = args.start_line.map(|s| s.saturating_sub(1)).unwrap_or(0);
-        let end = args.end_line.unwrap_or(total_lines).min(total_lines);
-
-        let selected_lines: Vec<&str> = lines
-            .into_iter()
-            .skip(start)
-            .take(end.saturating_sub(start))
-            .collect();
-
-        // Check truncation limits
-        let mut result = String::new();
-        let mut byte_count = 0;
-        let mut truncated = false;
-
-        for (line_count, line) in selected_lines.into_iter().enumerate() {
-            if line_count >= MAX_LINES || byte_count + line.len() + 1 > MAX_BYTES {
-                truncated = true;
-                break;
+        match remote::get() {
+            Some(_) => {
+                let content = self.read_remote(&args.path).await?;
+                Ok(Self::select_lines(&content, args.start_line, args.end_line))
             }
-            if !result.is_empty() {
-                result.push('\n');
-                byte_count += 1;
+            None => {
+                let path = self.resolve_path(&args.path)?;
+                let key = format!(
+                    "{}:{:?}:{:?}",
+                    path.display(),
+                    args.start_line,
+                    args.end_line
+                );
+                match self.cache.get(&key).await {
+                    Some(cached) => Ok(cached),
+                    None => {
+                        let content = tokio::fs::read_to_string(&path).await?;
+                        let output = Self::select_lines(&content, args.start_line, args.end_line);
+                        self.cache.insert(key, &output, vec![path]).await;
+                        Ok(output)
+                    }
+                }
             }
-            result.push_str(line);
-            byte_count += line.len();
         }
-
-        if truncated {
-            result.push_str("\n\n[truncated - file exceeds 50KB or 1000 lines limit]");
-        }
-
-        Ok(result)
     }
 }
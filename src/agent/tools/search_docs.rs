@@ -1,23 +1,56 @@
-use rig::completion::ToolDefinition;
+use rig::agent::{Agent, AgentBuilder};
+use rig::completion::{Prompt, PromptError, ToolDefinition};
+use rig::providers::anthropic;
 use rig::tool::Tool;
 use serde::{Deserialize, Serialize};
 use serde_json::json;
 use std::path::PathBuf;
 use std::time::Duration;
 use thiserror::Error;
-use tokio::process::Command;
 use tokio::time::timeout;
 
+use crate::agent::{
+    self,
+    hooks::HelperHook,
+    profile, remote, scope,
+    tools::{
+        DEFAULT_SUB_AGENT_MODEL,
+        cache::{self, ResultCache},
+        injection, ocr,
+    },
+};
+
 const TIMEOUT_SECS: u64 = 30;
 const MAX_COUNT: usize = 100;
 const CONTEXT_LINES: usize = 2;
 
+/// Output of a search without matches
+const NO_MATCHES: &str = "No matches found";
+
+/// Lines of the matches of a translated query that are translated back
+const MAX_TRANSLATED_LINES: usize = 60;
+
+const TRANSLATE_QUERY_PREAMBLE: &str = "You translate search queries for a full-text \
+    search of documentation. For each requested language, reply with one line \
+    `<language>: <translation>` holding only the translated search terms, as they would \
+    appear in a document written in that language. No explanations.";
+
+const TRANSLATE_MATCHES_PREAMBLE: &str = "You translate search results. Translate the \
+    text of each line into the language of the given query, keeping file paths, line \
+    numbers and separators as they are. Reply with the translated lines only.";
+
 #[derive(Deserialize)]
 pub struct SearchDocsArgs {
     /// The search query/pattern to find in documents
     pub query: String,
     /// Optional path or glob pattern to search in (defaults to current directory)
     pub path: Option<String>,
+    /// Languages of documents to search as well, with the query translated
+    #[serde(default)]
+    pub languages: Vec<String>,
+    /// Search the text of scanned PDFs and images as well
+    #[serde(default)]
+    pub ocr: bool,
 }
 
 #[derive(Debug, Error)]
@@ -34,73 +67,72 @@ pub enum SearchDocsError {
     Io(#[from] std::io::Error),
     #[error("Search failed with exit code {0}: {1}")]
     SearchFailed(i32, String),
+    #[error(
+        "OCR needs tesseract, and pdftoppm for PDFs: https://tesseract-ocr.github.io/tessdoc/Installation.html"
+    )]
+    OcrNotInstalled,
+    #[error("OCR is not available in remote workspaces")]
+    OcrRemote,
+    #[error("Translation failed: {0}")]
+    Translation(#[from] PromptError),
 }
 
 #[derive(Deserialize, Serialize)]
 pub struct SearchDocs {
     #[serde(skip)]
     base_dir: PathBuf,
+    #[serde(skip)]
+    cache: ResultCache,
 }
 
 impl SearchDocs {
     pub fn new(base_dir: PathBuf) -> Self {
-        Self { base_dir }
-    }
-}
-
-impl Tool for SearchDocs {
-    const NAME: &'static str = "search_docs";
-
-    type Error = SearchDocsError;
-    type Args = SearchDocsArgs;
-    type Output = String;
-
-    async fn definition(&self, _prompt: String) -> ToolDefinition {
-        ToolDefinition {
-            name: Self::NAME.to_string(),
-            description:
-                "Search through documents (PDFs, Word docs, Excel, etc.) using ripgrep-all. \
-                Automatically handles binary formats and extracts text. \
-                Use this when you need to find content in non-text files. \
-                Do not use it until other tools have been tried."
-                    .to_string(),
-            parameters: json!({
-                "type": "object",
-                "properties": {
-                    "query": {
-                        "type": "string",
-                        "description": "The search query/pattern to find in documents"
-                    },
-                    "path": {
-                        "type": "string",
-                        "description": "Optional path or glob pattern to search in (defaults to current directory)"
-                    }
-                },
-                "required": ["query"]
-            }),
+        Self {
+            base_dir,
+            cache: ResultCache::default(),
         }
     }
 
-    async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
-        // Validate query is not empty
-        if args.query.trim().is_empty() {
-            return Err(SearchDocsError::EmptyQuery);
+    /// rga's matches for `query` under `path`, followed by the matches in
+    /// the text of scanned PDFs and images if `ocr`
+    async fn search(&self, query: &str, path: &str, ocr: bool) -> Result<String, SearchDocsError> {
+        let text = self.search_text(query, path).await?;
+        if !ocr {
+            Ok(text)
+        } else if remote::get().is_some() {
+            Err(SearchDocsError::OcrRemote)
+        } else {
+            match ocr::search(&self.base_dir, query, path, CONTEXT_LINES).await {
+                Ok(Some(found)) if text == NO_MATCHES => Ok(injection::guard(found)),
+                Ok(Some(found)) => Ok(format!("{text}\n{}", injection::guard(found))),
+                Ok(None) => Ok(text),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                    Err(SearchDocsError::OcrNotInstalled)
+                }
+                Err(e) => Err(SearchDocsError::Io(e)),
+            }
         }
+    }
 
+    /// Run rga for `query` under `path`
+    async fn search_text(&self, query: &str, path: &str) -> Result<String, SearchDocsError> {
         // Build rga command with flags
-        let path = args.path.as_deref().unwrap_or(".");
-
-        let mut cmd = Command::new("rga");
-        cmd.arg("-i") // case-insensitive
-            .arg("--max-count")
-            .arg(MAX_COUNT.to_string())
-            .arg("--context")
-            .arg(CONTEXT_LINES.to_string())
-            .arg("--color")
-            .arg("never")
-            .arg(&args.query)
-            .arg(path)
-            .current_dir(&self.base_dir);
+        let (max_count, context_lines) = (MAX_COUNT.to_string(), CONTEXT_LINES.to_string());
+        let mut cmd = scope::search_command(
+            &self.base_dir,
+            "rga",
+            &[
+                "-i", // case-insensitive
+                "--max-count",
+                &max_count,
+                "--context",
+                &context_lines,
+                "--color",
+                "never",
+                query,
+                path,
+            ],
+        );
 
         // Execute with timeout
         let result = timeout(Duration::from_secs(TIMEOUT_SECS), cmd.output()).await;
@@ -110,11 +142,13 @@ impl Tool for SearchDocs {
                 match output.status.code() {
                     Some(0) => {
                         // Success - return stdout
-                        Ok(String::from_utf8_lossy(&output.stdout).to_string())
+                        Ok(injection::guard(
+                            String::from_utf8_lossy(&output.stdout).to_string(),
+                        ))
                     }
                     Some(1) => {
                         // No matches found (rga returns 1 when no matches)
-                        Ok("No matches found".to_string())
+                        Ok(NO_MATCHES.to_string())
                     }
                     Some(127) => {
                         // Command not found
@@ -146,4 +180,172 @@ impl Tool for SearchDocs {
             }
         }
     }
+
+    /// Run rga for `query` and for its translations into `languages`; the
+    /// matches of each translation are followed by their translation back
+    /// into the language of the query
+    async fn search_translated(
+        &self,
+        query: &str,
+        path: &str,
+        languages: &[String],
+        ocr: bool,
+    ) -> Result<String, SearchDocsError> {
+        let reply = translator(TRANSLATE_QUERY_PREAMBLE)
+            .prompt(format!(
+                "Query: {query}\nLanguages: {}",
+                languages.join(", ")
+            ))
+            .with_hook(HelperHook::new(DEFAULT_SUB_AGENT_MODEL))
+            .await?;
+
+        let mut out = format!(
+            "Matches for \"{query}\":\n{}",
+            self.search(query, path, ocr).await?
+        );
+        for (language, translation) in parse_translations(&reply, query) {
+            let matches = self.search(&regex::escape(&translation), path, ocr).await?;
+            out.push_str(&format!(
+                "\n\nMatches for \"{translation}\" ({language}):\n{matches}"
+            ));
+            if matches != NO_MATCHES {
+                let excerpt: Vec<&str> = matches.lines().take(MAX_TRANSLATED_LINES).collect();
+                let translated = translator(TRANSLATE_MATCHES_PREAMBLE)
+                    .prompt(format!("Query: {query}\n\n{}", excerpt.join("\n")))
+                    .with_hook(HelperHook::new(DEFAULT_SUB_AGENT_MODEL))
+                    .await?;
+                out.push_str(&format!(
+                    "\n\nTranslated from {language}:\n{}",
+                    injection::guard(translated.trim().to_string())
+                ));
+            }
+        }
+        Ok(out)
+    }
+}
+
+/// A cheap model without tools that translates
+fn translator(preamble: &str) -> Agent<anthropic::completion::CompletionModel> {
+    let model =
+        anthropic::completion::CompletionModel::new(agent::client(), DEFAULT_SUB_AGENT_MODEL);
+    profile::tag(AgentBuilder::new(model))
+        .preamble(preamble)
+        .build()
+}
+
+/// `(language, translation)` pairs of the model's reply, without those
+/// identical to the query
+fn parse_translations(reply: &str, query: &str) -> Vec<(String, String)> {
+    reply
+        .lines()
+        .filter_map(|line| line.split_once(':'))
+        .map(|(language, translation)| {
+            (
+                language
+                    .trim()
+                    .trim_start_matches(['-', '*', ' '])
+                    .to_string(),
+                translation.trim().trim_matches(['"', '`']).to_string(),
+            )
+        })
+        .filter(|(language, translation)| {
+            !language.is_empty()
+                && !translation.is_empty()
+                && !translation.eq_ignore_ascii_case(query)
+        })
+        .collect()
+}
+
+impl Tool for SearchDocs {
+    const NAME: &'static str = "search_docs";
+
+    type Error = SearchDocsError;
+    type Args = SearchDocsArgs;
+    type Output = String;
+
+    async fn definition(&self, _prompt: String) -> ToolDefinition {
+        ToolDefinition {
+            name: Self::NAME.to_string(),
+            description:
+                "Search through documents (PDFs, Word docs, Excel, etc.) using ripgrep-all. \
+                Automatically handles binary formats and extracts text. \
+                Use this when you need to find content in non-text files. \
+                Do not use it until other tools have been tried. If the documentation \
+                may be written in other languages, list them in languages: the query is \
+                translated into each, and the matches are returned with a translation. \
+                Set ocr to also search the text of scanned PDFs and images; the first \
+                search OCRs them, which is slow, later ones use the cached text."
+                    .to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "query": {
+                        "type": "string",
+                        "description": "The search query/pattern to find in documents"
+                    },
+                    "path": {
+                        "type": "string",
+                        "description": "Optional path or glob pattern to search in (defaults to current directory)"
+                    },
+                    "languages": {
+                        "type": "array",
+                        "items": { "type": "string" },
+                        "description": "Optional languages of documents to search as well, e.g. [\"German\", \"Japanese\"]"
+                    },
+                    "ocr": {
+                        "type": "boolean",
+                        "description": "Also search the text of scanned PDFs and images (screenshots, scanned contracts) with OCR"
+                    }
+                },
+                "required": ["query"]
+            }),
+        }
+    }
+
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+        // Validate query is not empty
+        if args.query.trim().is_empty() {
+            return Err(SearchDocsError::EmptyQuery);
+        }
+
+        let path = args.path.as_deref().unwrap_or(".");
+        let key = format!(
+            "{}\0{path}\0{}\0{}",
+            args.query,
+            args.languages.join(","),
+            args.ocr
+        );
+        match self.cache.get(&key).await {
+            Some(cached) => Ok(cached),
+            None => {
+                let output = if args.languages.is_empty() {
+                    self.search(&args.query, path, args.ocr).await?
+                } else {
+                    self.search_translated(&args.query, path, &args.languages, args.ocr)
+                        .await?
+                };
+                let sources = cache::sources(&self.base_dir, &[path], &output);
+                self.cache.insert(key, &output, sources).await;
+                Ok(output)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_translations() {
+        let reply =
+            "German: Zugriffskontrolle\n- Japanese: `アクセス制御`\nFrench: access control\n\nNote";
+        assert_eq!(
+            parse_translations(reply, "access control"),
+            [
+                ("German".to_string(), "Zugriffskontrolle".to_string()),
+                ("Japanese".to_string(), "アクセス制御".to_string()),
+            ]
+        );
+    }
 }
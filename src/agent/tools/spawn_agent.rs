@@ -0,0 +1,184 @@
+use std::path::PathBuf;
+
+use rig::agent::{Agent, AgentBuilder};
+use rig::completion::{Prompt, PromptError, ToolDefinition};
+use rig::providers::anthropic;
+use rig::tool::Tool;
+use serde::Deserialize;
+use serde_json::json;
+use thiserror::Error;
+
+use crate::agent::{
+    self,
+    hooks::HelperHook,
+    profile,
+    tools::{BashCommand, ReadFile, SearchDocs},
+};
+
+/// Model of sub-agents unless configured otherwise
+pub const DEFAULT_SUB_AGENT_MODEL: &str = "claude-haiku-4-5";
+
+/// Turn cap of sub-agents unless configured otherwise
+pub const DEFAULT_SUB_AGENT_MAX_TURNS: usize = 10;
+
+/// Native tools a sub-agent may be given
+const SUB_AGENT_TOOLS: &[&str] = &["read_file", "bash", "search_docs"];
+
+const SUB_AGENT_PREAMBLE: &str = "You are a sub-agent investigating one scoped question \
+    about a codebase for another agent. Use the tools to find the answer, then reply with \
+    a concise summary of your findings, citing file paths and line numbers. Don't ask \
+    questions back; if something can't be determined, say so.";
+
+/// `[tools.spawn-agent]` config section
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SpawnAgentConfig {
+    /// Model of the sub-agents, usually a cheaper one than the main agent's
+    pub model: String,
+    /// Maximum number of turns of each sub-agent
+    #[serde(alias = "max-turns")]
+    pub max_turns: usize,
+}
+
+impl Default for SpawnAgentConfig {
+    fn default() -> Self {
+        Self {
+            model: DEFAULT_SUB_AGENT_MODEL.to_string(),
+            max_turns: DEFAULT_SUB_AGENT_MAX_TURNS,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct SpawnAgentArgs {
+    /// The scoped question the sub-agent investigates
+    pub task: String,
+    /// Tools the sub-agent may use; all read-only native tools if absent
+    tools: Option<Vec<String>>,
+}
+
+#[derive(Debug, Error)]
+pub enum SpawnAgentError {
+    #[error("Unknown tool for sub-agent: {0}")]
+    UnknownTool(String),
+    #[error("Sub-agent failed: {0}")]
+    Prompt(#[from] PromptError),
+}
+
+/// Launches a child agent with its own model, tools and turn cap for a
+/// scoped sub-question, and returns its summary
+#[derive(Clone)]
+pub struct SpawnAgent {
+    base_dir: PathBuf,
+    config: SpawnAgentConfig,
+}
+
+impl SpawnAgent {
+    pub fn new(base_dir: PathBuf, config: SpawnAgentConfig) -> Self {
+        Self { base_dir, config }
+    }
+
+    /// Validate the requested tool names, defaulting to all of them
+    fn select_tools(requested: Option<Vec<String>>) -> Result<Vec<String>, SpawnAgentError> {
+        match requested.filter(|tools| !tools.is_empty()) {
+            Some(tools) => tools
+                .into_iter()
+                .map(|tool| {
+                    if SUB_AGENT_TOOLS.contains(&tool.as_str()) {
+                        Ok(tool)
+                    } else {
+                        Err(SpawnAgentError::UnknownTool(tool))
+                    }
+                })
+                .collect(),
+            None => Ok(SUB_AGENT_TOOLS.iter().map(ToString::to_string).collect()),
+        }
+    }
+
+    fn build(&self, tools: &[String]) -> Agent<anthropic::completion::CompletionModel> {
+        let client = agent::client();
+        let model = anthropic::completion::CompletionModel::new(client, &self.config.model)
+            .with_prompt_caching();
+
+        tools
+            .iter()
+            .fold(
+                profile::tag(AgentBuilder::new(model))
+                    .preamble(SUB_AGENT_PREAMBLE)
+                    .default_max_turns(self.config.max_turns),
+                |builder, tool| match tool.as_str() {
+                    "read_file" => builder.tool(ReadFile::new(self.base_dir.clone())),
+                    "bash" => builder.tool(BashCommand::new(self.base_dir.clone())),
+                    _ => builder.tool(SearchDocs::new(self.base_dir.clone())),
+                },
+            )
+            .build()
+    }
+}
+
+impl Tool for SpawnAgent {
+    const NAME: &'static str = "spawn_agent";
+
+    type Error = SpawnAgentError;
+    type Args = SpawnAgentArgs;
+    type Output = String;
+
+    async fn definition(&self, _prompt: String) -> ToolDefinition {
+        ToolDefinition {
+            name: Self::NAME.to_string(),
+            description: format!(
+                "Delegate a scoped sub-question to a sub-agent ({}, at most {} turns) with \
+                its own read-only tools, and get back a summary of its findings. Call it \
+                several times in one response to run independent investigations in \
+                parallel, e.g. the frontend and the backend side of the auth flow.",
+                self.config.model, self.config.max_turns
+            ),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "task": {
+                        "type": "string",
+                        "description": "Self-contained question for the sub-agent, including the context it needs"
+                    },
+                    "tools": {
+                        "type": "array",
+                        "items": { "type": "string", "enum": SUB_AGENT_TOOLS },
+                        "description": "Tools the sub-agent may use (default: all)"
+                    }
+                },
+                "required": ["task"]
+            }),
+        }
+    }
+
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+        let tools = Self::select_tools(args.tools)?;
+        let agent = self.build(&tools);
+        Ok(agent
+            .prompt(args.task)
+            .with_hook(HelperHook::new(&self.config.model))
+            .await?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_select_tools() {
+        assert_eq!(SpawnAgent::select_tools(None).unwrap(), SUB_AGENT_TOOLS);
+        assert_eq!(
+            SpawnAgent::select_tools(Some(Vec::new())).unwrap(),
+            SUB_AGENT_TOOLS
+        );
+        assert_eq!(
+            SpawnAgent::select_tools(Some(vec!["read_file".to_string()])).unwrap(),
+            ["read_file"]
+        );
+        assert!(matches!(
+            SpawnAgent::select_tools(Some(vec!["spawn_agent".to_string()])),
+            Err(SpawnAgentError::UnknownTool(_))
+        ));
+    }
+}
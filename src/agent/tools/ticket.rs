@@ -0,0 +1,309 @@
+use rig::completion::ToolDefinition;
+use rig::tool::Tool;
+use serde::Deserialize;
+use serde_json::{Value, json};
+use thiserror::Error;
+
+const LINEAR_API_URL: &str = "https://api.linear.app/graphql";
+
+const LINEAR_QUERY: &str = "query Issue($id: String!) { issue(id: $id) { \
+    identifier title description url state { name } assignee { name } } }";
+
+/// Issue tracker software
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TrackerKind {
+    Jira,
+    Linear,
+}
+
+/// `[tools.tracker]` config section
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TrackerConfig {
+    pub kind: TrackerKind,
+    /// Base URL of the Jira site, e.g. `https://company.atlassian.net`
+    pub url: Option<String>,
+    /// Jira custom field holding acceptance criteria, e.g. `customfield_10045`
+    #[serde(alias = "acceptance-field")]
+    pub acceptance_field: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct TicketArgs {
+    /// Ticket key, e.g. `PROJ-123`
+    pub key: String,
+}
+
+#[derive(Debug, Error)]
+pub enum TicketError {
+    #[error("HTTP error: {0}")]
+    Http(#[from] reqwest::Error),
+    #[error("Tracker API returned {0}: {1}")]
+    Api(u16, String),
+    #[error("Ticket {0} not found")]
+    NotFound(String),
+    #[error("Invalid ticket key: {0}")]
+    InvalidKey(String),
+}
+
+/// A ticket, normalized across trackers
+#[derive(Debug, Clone, Default)]
+struct Ticket {
+    key: String,
+    summary: String,
+    status: String,
+    assignee: String,
+    url: String,
+    description: String,
+    acceptance_criteria: Option<String>,
+}
+
+impl Ticket {
+    fn to_markdown(&self) -> String {
+        let description = if self.description.is_empty() {
+            "(no description)"
+        } else {
+            &self.description
+        };
+        let mut text = format!(
+            "# {}: {}\n\nStatus: {} | Assignee: {} | {}\n\n## Description\n\n{description}\n",
+            self.key, self.summary, self.status, self.assignee, self.url
+        );
+        if let Some(criteria) = &self.acceptance_criteria {
+            text.push_str(&format!("\n## Acceptance Criteria\n\n{criteria}\n"));
+        }
+        text
+    }
+}
+
+#[derive(Clone)]
+enum Backend {
+    Jira {
+        url: String,
+        /// `(email, api token)` for Jira Cloud, or a personal access token
+        auth: JiraAuth,
+        acceptance_field: Option<String>,
+    },
+    Linear {
+        api_key: String,
+    },
+}
+
+#[derive(Clone)]
+enum JiraAuth {
+    Basic { email: String, token: String },
+    Bearer(String),
+}
+
+/// Looks up tickets in Jira or Linear
+#[derive(Clone)]
+pub struct TicketTool {
+    backend: Backend,
+    client: reqwest::Client,
+}
+
+impl TicketTool {
+    /// Set up the tool from config, with credentials from the environment:
+    /// `JIRA_API_TOKEN` (plus `JIRA_EMAIL` for Jira Cloud) or `LINEAR_API_KEY`
+    pub fn from_config(config: &TrackerConfig) -> Result<Self, String> {
+        let env = |name: &str| std::env::var(name).ok().filter(|v| !v.is_empty());
+
+        let backend = match config.kind {
+            TrackerKind::Jira => {
+                let url = config
+                    .url
+                    .clone()
+                    .ok_or("Jira needs `url` in [tools.tracker]")?;
+                let token = env("JIRA_API_TOKEN").ok_or("JIRA_API_TOKEN is not set")?;
+                let auth = match env("JIRA_EMAIL") {
+                    Some(email) => JiraAuth::Basic { email, token },
+                    None => JiraAuth::Bearer(token),
+                };
+                Backend::Jira {
+                    url: url.trim_end_matches('/').to_string(),
+                    auth,
+                    acceptance_field: config.acceptance_field.clone(),
+                }
+            }
+            TrackerKind::Linear => Backend::Linear {
+                api_key: env("LINEAR_API_KEY").ok_or("LINEAR_API_KEY is not set")?,
+            },
+        };
+
+        Ok(Self {
+            backend,
+            client: reqwest::Client::new(),
+        })
+    }
+
+    async fn send(&self, request: reqwest::RequestBuilder) -> Result<Value, TicketError> {
+        let response = request.header("User-Agent", "horse").send().await?;
+        if response.status().is_success() {
+            Ok(response.json().await?)
+        } else {
+            let status = response.status().as_u16();
+            let body = response.text().await.unwrap_or_default();
+            Err(TicketError::Api(status, body))
+        }
+    }
+
+    async fn fetch(&self, key: &str) -> Result<Ticket, TicketError> {
+        match &self.backend {
+            Backend::Jira {
+                url,
+                auth,
+                acceptance_field,
+            } => {
+                let fields = ["summary", "description", "status", "assignee"]
+                    .into_iter()
+                    .chain(acceptance_field.as_deref())
+                    .collect::<Vec<_>>()
+                    .join(",");
+                let request = self
+                    .client
+                    .get(format!("{url}/rest/api/2/issue/{key}"))
+                    .query(&[("fields", fields)]);
+                let request = match auth {
+                    JiraAuth::Basic { email, token } => request.basic_auth(email, Some(token)),
+                    JiraAuth::Bearer(token) => request.bearer_auth(token),
+                };
+                let issue = self.send(request).await?;
+                Ok(parse_jira(&issue, url, acceptance_field.as_deref()))
+            }
+            Backend::Linear { api_key } => {
+                let request = self
+                    .client
+                    .post(LINEAR_API_URL)
+                    .header("Authorization", api_key)
+                    .json(&json!({ "query": LINEAR_QUERY, "variables": { "id": key } }));
+                let response = self.send(request).await?;
+                parse_linear(&response["data"]["issue"])
+                    .ok_or_else(|| TicketError::NotFound(key.to_string()))
+            }
+        }
+    }
+}
+
+/// String at a JSON pointer, or an empty string
+fn text_at(value: &Value, pointer: &str) -> String {
+    value
+        .pointer(pointer)
+        .and_then(Value::as_str)
+        .unwrap_or_default()
+        .to_string()
+}
+
+fn parse_jira(issue: &Value, url: &str, acceptance_field: Option<&str>) -> Ticket {
+    let key = text_at(issue, "/key");
+    Ticket {
+        url: format!("{url}/browse/{key}"),
+        key,
+        summary: text_at(issue, "/fields/summary"),
+        status: text_at(issue, "/fields/status/name"),
+        assignee: text_at(issue, "/fields/assignee/displayName"),
+        description: text_at(issue, "/fields/description"),
+        acceptance_criteria: acceptance_field
+            .map(|field| text_at(issue, &format!("/fields/{field}")))
+            .filter(|criteria| !criteria.is_empty()),
+    }
+}
+
+fn parse_linear(issue: &Value) -> Option<Ticket> {
+    (!issue.is_null()).then(|| Ticket {
+        key: text_at(issue, "/identifier"),
+        summary: text_at(issue, "/title"),
+        status: text_at(issue, "/state/name"),
+        assignee: text_at(issue, "/assignee/name"),
+        url: text_at(issue, "/url"),
+        description: text_at(issue, "/description"),
+        // Linear has no dedicated field, criteria are part of the description
+        acceptance_criteria: None,
+    })
+}
+
+impl Tool for TicketTool {
+    const NAME: &'static str = "ticket";
+
+    type Error = TicketError;
+    type Args = TicketArgs;
+    type Output = String;
+
+    async fn definition(&self, _prompt: String) -> ToolDefinition {
+        let tracker = match self.backend {
+            Backend::Jira { .. } => "Jira",
+            Backend::Linear { .. } => "Linear",
+        };
+        ToolDefinition {
+            name: Self::NAME.to_string(),
+            description: format!(
+                "Fetch a {tracker} ticket by key: summary, status, description and \
+                acceptance criteria. Use it to check code against the requirement a ticket describes."
+            ),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "key": {
+                        "type": "string",
+                        "description": "Ticket key, e.g. PROJ-123"
+                    }
+                },
+                "required": ["key"]
+            }),
+        }
+    }
+
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+        let key = args.key.trim().to_uppercase();
+        // Keys end up in URLs, only accept the usual `PROJ-123` shape
+        let valid = key.split_once('-').is_some_and(|(project, number)| {
+            !project.is_empty()
+                && project
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '_')
+                && !number.is_empty()
+                && number.chars().all(|c| c.is_ascii_digit())
+        });
+
+        if valid {
+            Ok(self.fetch(&key).await?.to_markdown())
+        } else {
+            Err(TicketError::InvalidKey(args.key))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_jira() {
+        let issue = json!({
+            "key": "PROJ-7",
+            "fields": {
+                "summary": "Retry uploads",
+                "status": { "name": "In Progress" },
+                "assignee": null,
+                "description": "Uploads fail on flaky networks.",
+                "customfield_10045": "Three retries with backoff"
+            }
+        });
+
+        let ticket = parse_jira(
+            &issue,
+            "https://acme.atlassian.net",
+            Some("customfield_10045"),
+        );
+        assert_eq!(
+            ticket.to_markdown(),
+            "# PROJ-7: Retry uploads\n\nStatus: In Progress | Assignee:  | https://acme.atlassian.net/browse/PROJ-7\n\n\
+             ## Description\n\nUploads fail on flaky networks.\n\n## Acceptance Criteria\n\nThree retries with backoff\n"
+        );
+    }
+
+    #[test]
+    fn test_parse_linear_missing_issue() {
+        assert!(parse_linear(&Value::Null).is_none());
+    }
+}
@@ -0,0 +1,85 @@
+use std::path::Path;
+
+use anyhow::Result;
+use rig::agent::{Agent, AgentBuilder};
+use rig::completion::Prompt;
+use rig::providers::anthropic;
+
+use crate::agent::{self, hooks::HelperHook, profile, tools::ReadFile};
+use crate::console::hyperlinks;
+
+/// Enough turns to read every cited location of a long answer
+const VERIFY_MAX_TURNS: usize = 15;
+
+/// Reply of the verifier when every citation holds
+const ALL_VERIFIED: &str = "ALL CITATIONS VERIFIED";
+
+const VERIFY_PREAMBLE: &str = "You check answers about a codebase written by another \
+    agent. For every `path:line` citation in the answer, read that location with read_file \
+    and check that the file exists and the cited lines contain what the answer claims. \
+    If all citations hold, reply with exactly `ALL CITATIONS VERIFIED`. Otherwise reply \
+    with a short markdown list of corrections, one per wrong citation: the citation, what \
+    is actually there, and the right location if you found it.";
+
+/// Second pass over an answer that checks its `path:line` citations
+pub struct Verifier {
+    agent: Agent<anthropic::completion::CompletionModel>,
+    model: String,
+}
+
+impl Verifier {
+    /// A verifier asking `model`, the session's, tagged with its profile
+    pub fn new(base_dir: &Path, model: &str) -> Self {
+        let client = agent::client();
+        let agent_model =
+            anthropic::completion::CompletionModel::new(client, model).with_prompt_caching();
+        let agent = profile::tag(AgentBuilder::new(agent_model))
+            .preamble(VERIFY_PREAMBLE)
+            .default_max_turns(VERIFY_MAX_TURNS)
+            .tool(ReadFile::new(base_dir.to_path_buf()))
+            .build();
+
+        Self {
+            agent,
+            model: model.to_string(),
+        }
+    }
+
+    /// Check the citations of `answer`.
+    ///
+    /// Returns corrections in markdown, or `None` if the answer cites nothing
+    /// or every citation holds.
+    pub async fn check(&self, answer: &str) -> Result<Option<String>> {
+        if hyperlinks::REFERENCE.is_match(answer) {
+            let verdict = self
+                .agent
+                .prompt(format!("Answer to check:\n\n{answer}"))
+                .with_hook(HelperHook::new(&self.model))
+                .await?;
+            Ok(corrections(&verdict))
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+/// Corrections from the verifier's reply, unless it verified everything
+fn corrections(verdict: &str) -> Option<String> {
+    let verdict = verdict.trim();
+    (!verdict.is_empty() && !verdict.contains(ALL_VERIFIED)).then(|| verdict.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_corrections() {
+        assert_eq!(corrections("ALL CITATIONS VERIFIED\n"), None);
+        assert_eq!(corrections(""), None);
+        assert_eq!(
+            corrections("- `src/main.rs:10` is a blank line; `main` is at `src/main.rs:42`"),
+            Some("- `src/main.rs:10` is a blank line; `main` is at `src/main.rs:42`".to_string())
+        );
+    }
+}
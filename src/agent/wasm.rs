@@ -0,0 +1,156 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+#[cfg(feature = "wasm")]
+use std::sync::Arc;
+
+use rig::completion::ToolDefinition;
+use rig::tool::Tool;
+use serde::Deserialize;
+use serde_json::Value;
+use thiserror::Error;
+
+#[cfg(feature = "wasm")]
+use crate::agent::mcp;
+use crate::agent::plugin::PluginToolInfo;
+
+#[cfg(feature = "wasm")]
+mod runtime;
+
+/// A sandboxed plugin declared in config under `[wasm.<name>]`
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WasmPluginConfig {
+    /// WASI module (`wasm32-wasip1`), relative to the workspace
+    pub module: PathBuf,
+}
+
+#[derive(Debug, Error)]
+pub enum WasmError {
+    #[error("horse was built without the `wasm` feature")]
+    Disabled,
+    #[error("WASM runtime: {0}")]
+    Runtime(String),
+    #[error("Module exited with status {0}: {1}")]
+    Failed(i32, String),
+    #[error("Module returned an invalid response: {0}")]
+    InvalidResponse(String),
+}
+
+/// A tool provided by a WASM module, registered on the agent like a native
+/// tool.
+///
+/// Modules run in a fresh instance per call, with the workspace mounted
+/// read-only as their only filesystem access, no network, no environment
+/// and bounded memory and fuel.
+#[derive(Clone)]
+pub struct WasmTool {
+    #[cfg(feature = "wasm")]
+    module: Arc<runtime::WasmModule>,
+    name: String,
+    info: PluginToolInfo,
+}
+
+impl Tool for WasmTool {
+    /// Placeholder; the registered name comes from `name()`
+    const NAME: &'static str = "wasm";
+
+    type Error = WasmError;
+    type Args = Value;
+    type Output = String;
+
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    async fn definition(&self, _prompt: String) -> ToolDefinition {
+        ToolDefinition {
+            name: self.name.clone(),
+            description: self.info.description.clone(),
+            parameters: self.info.parameters.clone(),
+        }
+    }
+
+    #[cfg_attr(not(feature = "wasm"), allow(unused_variables))]
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+        #[cfg(feature = "wasm")]
+        let output = self.module.call(&self.info.name, &args).await;
+        #[cfg(not(feature = "wasm"))]
+        let output = Err(WasmError::Disabled);
+        output
+    }
+}
+
+/// Load the configured modules and collect their tools.
+///
+/// Modules that fail to load or describe themselves are reported and skipped.
+#[cfg(feature = "wasm")]
+pub async fn load_tools(
+    plugins: &BTreeMap<String, WasmPluginConfig>,
+    base_dir: &Path,
+) -> Vec<WasmTool> {
+    let mut tools = Vec::new();
+
+    for (plugin, config) in plugins {
+        let loaded = match runtime::WasmModule::load(&base_dir.join(&config.module), base_dir) {
+            Ok(module) => {
+                let module = Arc::new(module);
+                module.describe().await.map(|infos| (module, infos))
+            }
+            Err(e) => Err(e),
+        };
+
+        match loaded {
+            Ok((module, infos)) => {
+                tracing::info!(">> WASM plugin {plugin}: {} tools", infos.len());
+                tools.extend(infos.into_iter().map(|info| WasmTool {
+                    module: module.clone(),
+                    name: mcp::qualified_name(plugin, &info.name),
+                    info,
+                }));
+            }
+            Err(e) => tracing::warn!("[!] Warning: WASM plugin {plugin} unavailable: {e}"),
+        }
+    }
+
+    tools
+}
+
+/// Without the `wasm` feature, configured modules are reported and skipped
+#[cfg(not(feature = "wasm"))]
+pub async fn load_tools(
+    plugins: &BTreeMap<String, WasmPluginConfig>,
+    _base_dir: &Path,
+) -> Vec<WasmTool> {
+    for plugin in plugins.keys() {
+        tracing::warn!(
+            "[!] Warning: WASM plugin {plugin} unavailable: {}",
+            WasmError::Disabled
+        );
+    }
+    Vec::new()
+}
+
+/// Tools listed in the `{"tools": [...]}` JSON a module prints for
+/// `describe`, in the same format as process plugins
+#[cfg(feature = "wasm")]
+fn parse_describe(stdout: &[u8]) -> Result<Vec<PluginToolInfo>, WasmError> {
+    serde_json::from_slice::<Value>(stdout)
+        .and_then(|response| serde_json::from_value(response["tools"].clone()))
+        .map_err(|e| WasmError::InvalidResponse(e.to_string()))
+}
+
+#[cfg(all(test, feature = "wasm"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_describe() {
+        let infos =
+            parse_describe(br#"{"tools": [{"name": "count", "description": "Count"}]}"#).unwrap();
+        assert_eq!(infos[0].name, "count");
+        assert!(matches!(
+            parse_describe(b"usage: count <file>"),
+            Err(WasmError::InvalidResponse(_))
+        ));
+    }
+}
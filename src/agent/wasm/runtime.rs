@@ -0,0 +1,149 @@
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use serde_json::Value;
+use wasmtime::{Config, Engine, Linker, Module, Store, StoreLimits, StoreLimitsBuilder};
+use wasmtime_wasi::pipe::{MemoryInputPipe, MemoryOutputPipe};
+use wasmtime_wasi::preview1::{self, WasiP1Ctx};
+use wasmtime_wasi::{DirPerms, FilePerms, I32Exit, WasiCtxBuilder};
+
+use crate::agent::plugin::PluginToolInfo;
+use crate::agent::wasm::{WasmError, parse_describe};
+
+/// Output kept of a run, each of stdout and stderr
+const MAX_OUTPUT_BYTES: usize = 50 * 1024; // 50KB
+
+/// Linear memory a module may grow to
+const MAX_MEMORY_BYTES: usize = 256 * 1024 * 1024;
+
+/// Instructions (roughly) a single run may execute before it is aborted
+const FUEL: u64 = 10_000_000_000;
+
+/// What the workspace is mounted as inside the sandbox
+const GUEST_WORKSPACE: &str = ".";
+
+struct Sandbox {
+    wasi: WasiP1Ctx,
+    limits: StoreLimits,
+}
+
+/// A compiled WASI module, instantiated afresh for every run
+pub(super) struct WasmModule {
+    engine: Engine,
+    module: Module,
+    base_dir: PathBuf,
+}
+
+impl WasmModule {
+    pub(super) fn load(path: &Path, base_dir: &Path) -> Result<Self, WasmError> {
+        let mut config = Config::new();
+        config.consume_fuel(true);
+        let engine = Engine::new(&config).map_err(runtime_error)?;
+        let module = Module::from_file(&engine, path).map_err(runtime_error)?;
+
+        Ok(Self {
+            engine,
+            module,
+            base_dir: base_dir.to_path_buf(),
+        })
+    }
+
+    /// Ask the module for its tools
+    pub(super) async fn describe(self: &Arc<Self>) -> Result<Vec<PluginToolInfo>, WasmError> {
+        let stdout = self.run(vec!["describe".to_string()], Vec::new()).await?;
+        parse_describe(&stdout)
+    }
+
+    /// Run `tool` with the JSON `args` on stdin; its stdout is the output
+    pub(super) async fn call(
+        self: &Arc<Self>,
+        tool: &str,
+        args: &Value,
+    ) -> Result<String, WasmError> {
+        let stdout = self
+            .run(
+                vec!["call".to_string(), tool.to_string()],
+                args.to_string().into_bytes(),
+            )
+            .await?;
+        Ok(String::from_utf8_lossy(&stdout).into_owned())
+    }
+
+    /// Run the module's `_start` on a blocking thread
+    async fn run(
+        self: &Arc<Self>,
+        args: Vec<String>,
+        stdin: Vec<u8>,
+    ) -> Result<Vec<u8>, WasmError> {
+        let module = self.clone();
+        tokio::task::spawn_blocking(move || module.run_blocking(&args, stdin))
+            .await
+            .map_err(runtime_error)?
+    }
+
+    fn run_blocking(&self, args: &[String], stdin: Vec<u8>) -> Result<Vec<u8>, WasmError> {
+        let stdout = MemoryOutputPipe::new(MAX_OUTPUT_BYTES);
+        let stderr = MemoryOutputPipe::new(MAX_OUTPUT_BYTES);
+        // argv[0] is the program name by convention
+        let argv: Vec<&str> = std::iter::once("plugin")
+            .chain(args.iter().map(String::as_str))
+            .collect();
+        let wasi = WasiCtxBuilder::new()
+            .args(&argv)
+            .stdin(MemoryInputPipe::new(stdin))
+            .stdout(stdout.clone())
+            .stderr(stderr.clone())
+            .preopened_dir(
+                &self.base_dir,
+                GUEST_WORKSPACE,
+                DirPerms::READ,
+                FilePerms::READ,
+            )
+            .map_err(runtime_error)?
+            .build_p1();
+
+        let mut store = Store::new(
+            &self.engine,
+            Sandbox {
+                wasi,
+                limits: StoreLimitsBuilder::new()
+                    .memory_size(MAX_MEMORY_BYTES)
+                    .build(),
+            },
+        );
+        store.limiter(|sandbox| &mut sandbox.limits);
+        store.set_fuel(FUEL).map_err(runtime_error)?;
+
+        let mut linker: Linker<Sandbox> = Linker::new(&self.engine);
+        preview1::add_to_linker_sync(&mut linker, |sandbox| &mut sandbox.wasi)
+            .map_err(runtime_error)?;
+        let start = linker
+            .instantiate(&mut store, &self.module)
+            .and_then(|instance| instance.get_typed_func::<(), ()>(&mut store, "_start"))
+            .map_err(runtime_error)?;
+
+        // `proc_exit` surfaces as an error carrying the exit status
+        let status = match start.call(&mut store, ()) {
+            Ok(()) => 0,
+            Err(e) => e
+                .downcast_ref::<I32Exit>()
+                .map(|exit| exit.0)
+                .ok_or_else(|| runtime_error(e))?,
+        };
+
+        if status == 0 {
+            Ok(stdout.contents().to_vec())
+        } else {
+            Err(WasmError::Failed(
+                status,
+                String::from_utf8_lossy(&stderr.contents())
+                    .trim()
+                    .to_string(),
+            ))
+        }
+    }
+}
+
+fn runtime_error(e: impl std::fmt::Display) -> WasmError {
+    WasmError::Runtime(format!("{e:#}"))
+}
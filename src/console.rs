@@ -1,4 +1,40 @@
+pub mod agents_md;
+pub mod answers;
+pub mod attach;
+pub mod batch;
+pub mod citations;
 pub mod colors;
+pub mod commands;
+pub mod doctor;
+pub mod editor;
+pub mod eval;
+pub mod explain;
+pub mod hyperlinks;
+pub mod i18n;
+pub mod incident;
+pub mod logging;
 pub mod markdown;
+pub mod notify;
+pub mod oneshot;
+pub mod output;
+pub mod overview;
+pub mod pack;
+pub mod pager;
+pub mod plan;
+pub mod prompt;
+pub mod quickfix;
 pub mod repl;
+pub mod review;
+pub mod sessions;
+pub mod setup;
 pub mod spinner;
+pub mod stats;
+pub mod templates;
+pub mod terminal;
+pub mod theme;
+pub mod transcript;
+pub mod trust;
+pub mod tui;
+pub mod usage;
+pub mod voice;
+pub mod watch;
@@ -0,0 +1,141 @@
+use std::path::Path;
+use std::process::ExitCode;
+use std::time::Instant;
+
+use anyhow::{Context, Result, ensure};
+use rig::agent::Agent;
+use rig::completion::{Message, Prompt, PromptError};
+use rig::providers::anthropic;
+use tracing::Instrument;
+
+use crate::agent::hooks::ProgressHook;
+use crate::agent::{TOOL_CONCURRENCY, lifecycle, telemetry};
+use crate::console::colors;
+use crate::console::usage::UsageLog;
+
+/// Exit code used when no draft could be written
+const EXIT_DRAFT_FAILED: u8 = 1;
+
+/// Turns of the exploration; a draft needs the big picture, not every module
+const EXPLORE_MAX_TURNS: usize = 20;
+
+/// Turns for writing the draft once the exploration ran out, one to spare
+/// should the model reach for a tool anyway
+const WRITE_MAX_TURNS: usize = 1;
+
+const REQUEST: &str = "Write a draft AGENTS.md for this repository: the instructions an AI \
+    coding agent, or a new contributor, reads before working on it. Explore the workspace with \
+    your tools, but stay brief: the manifests, the README, the CI configuration and a few \
+    central modules, not every file. Use these sections:\n\n\
+    # <project name>\n\
+    What the project is and who uses it, in two to four sentences.\n\n\
+    ## Layout\n\
+    The main directories and modules, one line each.\n\n\
+    ## Conventions\n\
+    Naming, error handling, test layout and style, as the code actually does them.\n\n\
+    ## Commands\n\
+    Build, test, lint and run commands in a `sh` code block.\n\n\
+    Only state what you found in the code. Reply with the document's markdown alone, without \
+    `path:line` citations or any text around it.";
+
+/// Sent when the exploration runs out of turns
+const WRITE_REQUEST: &str = "You have run out of turns and can't call any more tools. Write the \
+    AGENTS.md now from what you found, in the sections asked for, and mark what you couldn't \
+    check with `<!-- TODO -->`. Reply with the document's markdown alone.";
+
+/// First line of the draft, so it isn't mistaken for reviewed instructions
+const DRAFT_NOTE: &str =
+    "<!-- Draft written by `horse generate-agents-md`: check it against the code and edit it -->";
+
+/// Explore the workspace for a bounded number of turns and write a draft
+/// AGENTS.md to `out`, the workspace's AGENTS.md by default.
+///
+/// An existing file is only replaced with `force`.
+pub async fn run(
+    agent: &Agent<anthropic::completion::CompletionModel>,
+    base_dir: &Path,
+    out: Option<&Path>,
+    force: bool,
+    usage_log: &UsageLog,
+) -> Result<ExitCode> {
+    let path = out.map_or_else(|| base_dir.join("AGENTS.md"), Path::to_path_buf);
+    ensure!(
+        force || !path.exists(),
+        "{} exists; pass --force to replace it",
+        path.display()
+    );
+
+    let hook = ProgressHook::new();
+    let started = Instant::now();
+    let mut history: Vec<Message> = Vec::new();
+    let result = agent
+        .prompt(REQUEST)
+        .with_history(&mut history)
+        .max_turns(EXPLORE_MAX_TURNS)
+        .with_tool_concurrency(TOOL_CONCURRENCY)
+        .with_hook(hook.clone())
+        .into_future()
+        .instrument(telemetry::turn_span())
+        .await;
+    let result = match result {
+        Err(PromptError::MaxTurnsError { chat_history, .. }) => {
+            let mut history = *chat_history;
+            agent
+                .prompt(WRITE_REQUEST)
+                .with_history(&mut history)
+                .max_turns(WRITE_MAX_TURNS)
+                .with_hook(hook.clone())
+                .await
+        }
+        result => result,
+    };
+    lifecycle::turn_end(REQUEST, &result).await;
+    usage_log.record(
+        hook.get_total_usage().into(),
+        hook.take_tool_calls().len(),
+        started.elapsed(),
+    );
+
+    match result {
+        Ok(answer) => {
+            std::fs::write(&path, format!("{DRAFT_NOTE}\n\n{}\n", strip_fence(&answer)))
+                .with_context(|| format!("Failed to write {}", path.display()))?;
+            eprintln!(
+                "{}",
+                colors::color_success(format!(
+                    ">> Wrote {}; review it before relying on it",
+                    path.display()
+                ))
+            );
+            Ok(ExitCode::SUCCESS)
+        }
+        Err(e) => {
+            eprintln!("{}", colors::color_error(format!(">> Error: {e:#}")));
+            Ok(ExitCode::from(EXIT_DRAFT_FAILED))
+        }
+    }
+}
+
+/// The answer without the ```markdown fence models like to wrap documents in
+fn strip_fence(answer: &str) -> &str {
+    let answer = answer.trim();
+    answer
+        .strip_prefix("```markdown")
+        .or_else(|| answer.strip_prefix("```md"))
+        .and_then(|rest| rest.strip_suffix("```"))
+        .map_or(answer, str::trim)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strip_fence() {
+        assert_eq!(strip_fence("```markdown\n# horse\n```\n"), "# horse");
+        assert_eq!(
+            strip_fence("\n# horse\n\n```sh\ncargo test\n```"),
+            "# horse\n\n```sh\ncargo test\n```"
+        );
+    }
+}
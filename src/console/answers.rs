@@ -0,0 +1,217 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result, ensure};
+use chrono::{DateTime, Local, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::agent::remote;
+use crate::state;
+
+/// Directory of the answer cache, relative to the workspace
+pub const ANSWERS_DIR: &str = ".horse/answers";
+
+/// An answer given before to the same question about the same workspace
+/// content
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CachedAnswer {
+    pub question: String,
+    pub answer: String,
+    pub created: DateTime<Utc>,
+    /// Hash of the workspace content the answer was given for
+    workspace: String,
+}
+
+impl CachedAnswer {
+    /// Shown above a cached answer, so it isn't taken for a fresh one
+    pub fn marker(&self) -> String {
+        format!(
+            "_(Cached answer from {}; the workspace hasn't changed since)_",
+            self.created.with_timezone(&Local).format("%Y-%m-%d %H:%M")
+        )
+    }
+}
+
+/// What an answer depends on besides the question and the workspace's
+/// content; answers given with other settings aren't reused
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct AnswerSettings {
+    pub model: String,
+    /// Natural language of the answer, `--lang`
+    pub lang: Option<String>,
+    /// Profile of the requests, `--profile`
+    pub profile: Option<String>,
+    /// Globs of `--include`
+    pub include: Vec<String>,
+    /// Globs of `--exclude`
+    pub exclude: Vec<String>,
+    /// Whether wrong citations are corrected, `--verify`
+    pub verify: bool,
+}
+
+/// Answers of earlier `ask` and `batch` runs, one per question and
+/// settings.
+///
+/// An answer is reused while the workspace's content is the one it was
+/// given for; any change to a file not ignored by git invalidates it.
+#[derive(Debug)]
+pub struct AnswerCache {
+    dir: PathBuf,
+    settings: AnswerSettings,
+    workspace: String,
+}
+
+impl AnswerCache {
+    /// The answer cache of `base_dir` for answers given with `settings`,
+    /// hashing the workspace's current content. Remote workspaces can't be
+    /// hashed locally.
+    pub async fn open(base_dir: &Path, settings: AnswerSettings) -> Result<Self> {
+        ensure!(
+            remote::get().is_none(),
+            "The answer cache needs a local workspace"
+        );
+        Ok(Self {
+            dir: base_dir.join(ANSWERS_DIR),
+            settings,
+            workspace: workspace_hash(base_dir).await?,
+        })
+    }
+
+    /// The cached answer to `question`, if the workspace is unchanged
+    pub fn get(&self, question: &str) -> Option<CachedAnswer> {
+        fs::read_to_string(self.path(question))
+            .ok()
+            .and_then(|json| serde_json::from_str::<CachedAnswer>(&json).ok())
+            .filter(|cached| cached.workspace == self.workspace)
+    }
+
+    /// Cache `answer` to `question`, replacing the answer for an earlier
+    /// workspace content
+    pub fn insert(&self, question: &str, answer: &str) -> Result<()> {
+        let cached = CachedAnswer {
+            question: question.to_string(),
+            answer: answer.to_string(),
+            created: Utc::now(),
+            workspace: self.workspace.clone(),
+        };
+        state::create_dir(&self.dir)
+            .with_context(|| format!("Failed to create {}", self.dir.display()))?;
+        let path = self.path(question);
+        fs::write(&path, serde_json::to_string(&cached)?)
+            .with_context(|| format!("Failed to write {}", path.display()))
+    }
+
+    fn path(&self, question: &str) -> PathBuf {
+        let mut hasher = Sha256::new();
+        hasher.update(serde_json::to_string(&self.settings).unwrap_or_default());
+        hasher.update(b"\0");
+        hasher.update(normalize(question).as_bytes());
+        self.dir.join(format!("{:x}.json", hasher.finalize()))
+    }
+}
+
+/// The question as asked again in other words of no consequence: case,
+/// whitespace and trailing punctuation
+fn normalize(question: &str) -> String {
+    question
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .trim_end_matches(['?', '.', '!'])
+        .to_lowercase()
+}
+
+/// Hash over the paths and contents of the workspace's files, as listed by
+/// `rg --files`
+async fn workspace_hash(base_dir: &Path) -> Result<String> {
+    let output = remote::command(base_dir, "rg", &["--files"])
+        .output()
+        .await
+        .context("Failed to list the workspace's files with rg")?;
+    let listing = String::from_utf8_lossy(&output.stdout);
+    let mut files: Vec<&str> = listing.lines().collect();
+    files.sort_unstable();
+
+    let mut hasher = Sha256::new();
+    for file in files {
+        hasher.update(file.as_bytes());
+        hasher.update(b"\0");
+        // A file removed while listing hashes like an empty one
+        hasher.update(
+            tokio::fs::read(base_dir.join(file))
+                .await
+                .unwrap_or_default(),
+        );
+        hasher.update(b"\0");
+    }
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize() {
+        assert_eq!(
+            normalize("  Where is the  config parsed?\n"),
+            "where is the config parsed"
+        );
+    }
+
+    #[test]
+    fn test_answer_cache() {
+        let base_dir =
+            std::env::temp_dir().join(format!("horse-answers-test-{}", uuid::Uuid::new_v4()));
+        let settings = AnswerSettings {
+            model: "claude-sonnet-4-5".to_string(),
+            ..AnswerSettings::default()
+        };
+        let cache = AnswerCache {
+            dir: base_dir.join(ANSWERS_DIR),
+            settings: settings.clone(),
+            workspace: "a".to_string(),
+        };
+        cache.insert("Where is main?", "src/main.rs:1").unwrap();
+        assert_eq!(
+            cache.get("where is main").map(|cached| cached.answer),
+            Some("src/main.rs:1".to_string())
+        );
+
+        let other_settings = [
+            AnswerSettings {
+                lang: Some("de".to_string()),
+                ..settings.clone()
+            },
+            AnswerSettings {
+                include: vec!["src/**".to_string()],
+                ..settings.clone()
+            },
+            AnswerSettings {
+                profile: Some("precise".to_string()),
+                ..settings.clone()
+            },
+            AnswerSettings {
+                verify: true,
+                ..settings.clone()
+            },
+        ];
+        for settings in other_settings {
+            let other = AnswerCache {
+                dir: cache.dir.clone(),
+                settings,
+                workspace: cache.workspace.clone(),
+            };
+            assert_eq!(other.get("Where is main?"), None);
+        }
+
+        let changed = AnswerCache {
+            workspace: "b".to_string(),
+            ..cache
+        };
+        assert_eq!(changed.get("Where is main?"), None);
+
+        fs::remove_dir_all(&base_dir).unwrap();
+    }
+}
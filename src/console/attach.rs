@@ -0,0 +1,267 @@
+use std::path::Path;
+use std::sync::LazyLock;
+
+use anyhow::{Context, Result, bail};
+use base64::Engine;
+use regex::Regex;
+use rig::OneOrMany;
+use rig::completion::Message;
+use rig::message::{ImageMediaType, UserContent};
+
+use crate::agent::preflight::BYTES_PER_TOKEN;
+use crate::console::hyperlinks;
+use crate::console::prompt::format_token_count;
+use crate::console::stats::format_size;
+
+/// Content of one attachment sent, at most
+const MAX_ATTACHMENT_BYTES: usize = 100 * 1024; // 100KB
+
+/// Size of one image sent, at most, as the API rejects larger ones
+const MAX_IMAGE_BYTES: usize = 5 * 1024 * 1024; // 5MB
+
+/// An image path as terminals paste a dropped file: quoted, with escaped
+/// spaces, or as a `file://` URL
+static DROPPED_IMAGE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r#"(?i)'([^']+\.(?:png|jpe?g|gif|webp))'|"([^"]+\.(?:png|jpe?g|gif|webp))"|((?:[^\s'"\\]|\\.)+\.(?:png|jpe?g|gif|webp))\b"#,
+    )
+    .expect("Invalid image path regex")
+});
+
+/// Where `/attach` takes its content from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Source {
+    /// A file, relative to the workspace
+    Path(String),
+    /// Output of the last `!` command
+    LastShellOutput,
+    Clipboard,
+}
+
+impl Source {
+    pub fn parse(arg: &str) -> Self {
+        match arg {
+            "last-shell-output" => Self::LastShellOutput,
+            "clipboard" => Self::Clipboard,
+            path => Self::Path(path.to_string()),
+        }
+    }
+}
+
+/// Content the user attached to their next question
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Attachment {
+    /// Where the content came from, shown to the model
+    label: String,
+    content: String,
+    truncated: bool,
+}
+
+impl Attachment {
+    /// An attachment of `content`, cut down to `MAX_ATTACHMENT_BYTES`
+    pub fn new(label: impl Into<String>, mut content: String) -> Self {
+        let truncated = content.len() > MAX_ATTACHMENT_BYTES;
+        if truncated {
+            let mut end = MAX_ATTACHMENT_BYTES;
+            while !content.is_char_boundary(end) {
+                end -= 1;
+            }
+            content.truncate(end);
+        }
+        Self {
+            label: label.into(),
+            content,
+            truncated,
+        }
+    }
+
+    /// The source with the size and estimated tokens of the content, e.g.
+    /// `clipboard (12 lines, 1.2KB, ~300 tokens)`
+    pub fn preview(&self) -> String {
+        let truncated = if self.truncated {
+            format!(", truncated to {}", format_size(MAX_ATTACHMENT_BYTES))
+        } else {
+            String::new()
+        };
+        format!(
+            "{} ({} lines, {}, ~{} tokens{truncated})",
+            self.label,
+            self.content.lines().count(),
+            format_size(self.content.len()),
+            format_token_count((self.content.len() / BYTES_PER_TOKEN) as u64)
+        )
+    }
+}
+
+/// An image the user attached to their next question, sent as a content
+/// block of its own
+#[derive(Debug, Clone)]
+pub struct ImageAttachment {
+    label: String,
+    media_type: ImageMediaType,
+    /// Base64 of the file
+    data: String,
+    bytes: usize,
+}
+
+impl ImageAttachment {
+    /// Read the image at `path`, shown to the user as `label`
+    pub fn load(path: &Path, label: impl Into<String>) -> Result<Self> {
+        let label = label.into();
+        let media_type = path
+            .extension()
+            .and_then(|extension| media_type(&extension.to_string_lossy()))
+            .with_context(|| format!("{label} is not a PNG, JPEG, GIF or WebP image"))?;
+        let content = std::fs::read(path).with_context(|| format!("Failed to read {label}"))?;
+        if content.len() > MAX_IMAGE_BYTES {
+            bail!(
+                "{label} is {}KB, images are limited to {}KB",
+                content.len() / 1024,
+                MAX_IMAGE_BYTES / 1024
+            );
+        }
+        Ok(Self {
+            label,
+            media_type,
+            data: base64::engine::general_purpose::STANDARD.encode(&content),
+            bytes: content.len(),
+        })
+    }
+
+    /// The source with the type and size of the image, e.g.
+    /// `docs/arch.png (PNG, 120.3KB)`
+    pub fn preview(&self) -> String {
+        format!(
+            "{} ({:?}, {})",
+            self.label,
+            self.media_type,
+            format_size(self.bytes)
+        )
+    }
+}
+
+fn media_type(extension: &str) -> Option<ImageMediaType> {
+    match extension.to_lowercase().as_str() {
+        "png" => Some(ImageMediaType::PNG),
+        "jpg" | "jpeg" => Some(ImageMediaType::JPEG),
+        "gif" => Some(ImageMediaType::GIF),
+        "webp" => Some(ImageMediaType::WEBP),
+        _ => None,
+    }
+}
+
+/// Paths of images dropped into the terminal as part of `line`
+pub fn dropped_images(line: &str) -> Vec<String> {
+    DROPPED_IMAGE
+        .captures_iter(line)
+        .filter_map(|caps| {
+            caps.get(1)
+                .map(|path| path.as_str().to_string())
+                .or_else(|| caps.get(2).map(|path| path.as_str().to_string()))
+                .or_else(|| caps.get(3).map(|path| path.as_str().replace("\\", "")))
+        })
+        .map(|path| match path.strip_prefix("file://") {
+            Some(url) => hyperlinks::decode_path(url),
+            None => path,
+        })
+        .collect()
+}
+
+/// The prompt as a user message, with the images as content blocks after
+/// the text
+pub fn message(prompt: &str, images: &[ImageAttachment]) -> Message {
+    let content = std::iter::once(UserContent::text(prompt)).chain(images.iter().map(|image| {
+        UserContent::image_base64(image.data.clone(), Some(image.media_type.clone()), None)
+    }));
+    OneOrMany::many(content)
+        .map(|content| Message::User { content })
+        .unwrap_or_else(|_| Message::user(prompt))
+}
+
+/// The query with the attachments as context
+pub fn build_prompt(query: &str, attachments: &[Attachment]) -> String {
+    attachments.iter().fold(query.to_string(), |prompt, attachment| {
+        let notice = if attachment.truncated {
+            format!(
+                "\n[truncated - attachment exceeds {}KB limit]",
+                MAX_ATTACHMENT_BYTES / 1024
+            )
+        } else {
+            String::new()
+        };
+        format!(
+            "{prompt}\n\nThe user attached the following content:\n\n<attachment source=\"{}\">\n{}{notice}\n</attachment>",
+            attachment.label, attachment.content
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_source() {
+        assert_eq!(Source::parse("clipboard"), Source::Clipboard);
+        assert_eq!(Source::parse("last-shell-output"), Source::LastShellOutput);
+        assert_eq!(
+            Source::parse("logs/app.log"),
+            Source::Path("logs/app.log".to_string())
+        );
+    }
+
+    #[test]
+    fn test_build_prompt() {
+        let attachment = Attachment::new("last-shell-output", "$ git status\nclean".to_string());
+        assert_eq!(
+            attachment.preview(),
+            "last-shell-output (2 lines, 18B, ~4 tokens)"
+        );
+        assert_eq!(
+            build_prompt("why is it clean?", &[attachment]),
+            "why is it clean?\n\nThe user attached the following content:\n\n\
+             <attachment source=\"last-shell-output\">\n$ git status\nclean\n</attachment>"
+        );
+        assert_eq!(build_prompt("plain", &[]), "plain");
+
+        let large = Attachment::new("clipboard", "é".repeat(MAX_ATTACHMENT_BYTES));
+        assert!(large.truncated);
+        assert!(build_prompt("q", &[large]).contains("[truncated"));
+    }
+
+    #[test]
+    fn test_dropped_images() {
+        assert_eq!(
+            dropped_images("what's wrong in '/home/me/Screen Shot 1.png'?"),
+            ["/home/me/Screen Shot 1.png"]
+        );
+        assert_eq!(
+            dropped_images(r"explain /Users/me/Desktop/arch\ diagram.PNG please"),
+            ["/Users/me/Desktop/arch diagram.PNG"]
+        );
+        assert_eq!(
+            dropped_images("file:///tmp/ui%20bug.jpg and docs/flow.webp"),
+            ["/tmp/ui bug.jpg", "docs/flow.webp"]
+        );
+        assert!(dropped_images("how does image.rs load a png?").is_empty());
+    }
+
+    #[test]
+    fn test_image_message() {
+        let image = ImageAttachment {
+            label: "ui.png".to_string(),
+            media_type: ImageMediaType::PNG,
+            data: "iVBORw0KGgo=".to_string(),
+            bytes: 8,
+        };
+        assert_eq!(image.preview(), "ui.png (PNG, 8B)");
+        match message("what is this?", &[image]) {
+            Message::User { content } => {
+                let content: Vec<UserContent> = content.into_iter().collect();
+                assert_eq!(content.len(), 2);
+                assert!(matches!(&content[1], UserContent::Image(_)));
+            }
+            other => panic!("unexpected message: {other:?}"),
+        }
+    }
+}
@@ -0,0 +1,203 @@
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::Path;
+use std::process::ExitCode;
+use std::time::Instant;
+
+use anyhow::{Context, Result};
+use rig::agent::Agent;
+use rig::completion::Prompt;
+use rig::providers::anthropic;
+use tokio::sync::mpsc;
+use tracing::Instrument;
+
+use crate::agent::{TOOL_CONCURRENCY, hooks::EventHook, lifecycle, telemetry};
+use crate::console::answers::AnswerCache;
+use crate::console::i18n;
+use crate::console::output::{self, Collected, OutputEvent, OutputFormat};
+use crate::console::usage::UsageLog;
+
+/// Line separating multi-line query stanzas in a batch file
+const STANZA_SEPARATOR: &str = "---";
+
+/// Exit code used when at least one query failed
+const EXIT_QUERY_FAILED: u8 = 1;
+
+/// Split a batch file into queries.
+///
+/// If the file contains `---` separator lines, each stanza between them is one
+/// (possibly multi-line) query; otherwise every non-empty line is a query.
+/// Lines starting with `#` are comments.
+pub fn parse_queries(content: &str) -> Vec<String> {
+    let lines: Vec<&str> = content
+        .lines()
+        .filter(|line| !line.trim_start().starts_with('#'))
+        .collect();
+
+    if lines.iter().any(|line| line.trim() == STANZA_SEPARATOR) {
+        lines
+            .split(|line| line.trim() == STANZA_SEPARATOR)
+            .map(|stanza| stanza.join("\n").trim().to_string())
+            .filter(|query| !query.is_empty())
+            .collect()
+    } else {
+        lines
+            .iter()
+            .map(|line| line.trim())
+            .filter(|line| !line.is_empty())
+            .map(str::to_string)
+            .collect()
+    }
+}
+
+/// Run every query of a batch file against the same agent and write a report.
+///
+/// Queries are independent unless `chain` is set, in which case each query
+/// sees the conversation of the previous ones. Independent queries are
+/// answered from `answers` when the workspace is unchanged.
+pub async fn run(
+    agent: &Agent<anthropic::completion::CompletionModel>,
+    queries_file: &Path,
+    chain: bool,
+    format: OutputFormat,
+    report: Option<&Path>,
+    answers: Option<&AnswerCache>,
+    usage_log: &UsageLog,
+) -> Result<ExitCode> {
+    let content = tokio::fs::read_to_string(queries_file)
+        .await
+        .with_context(|| format!("Failed to read {}", queries_file.display()))?;
+    let queries = parse_queries(&content);
+
+    let mut writer: Box<dyn Write> = match report {
+        Some(path) => Box::new(
+            File::create(path)
+                .with_context(|| format!("Failed to create report {}", path.display()))?,
+        ),
+        None => Box::new(io::stdout()),
+    };
+
+    let mut history = Vec::new();
+    let mut failed = 0;
+
+    for (i, query) in queries.iter().enumerate() {
+        tracing::info!(
+            ">> [{}/{}] {}",
+            i + 1,
+            queries.len(),
+            query.lines().next().unwrap_or_default()
+        );
+
+        if !chain {
+            history.clear();
+        }
+
+        // A chained query depends on the conversation, not just the question
+        let answers = answers.filter(|_| !chain);
+        let cached = answers.and_then(|answers| answers.get(query));
+        let (result, collected) = match &cached {
+            Some(cached) => (Ok(cached.answer.clone()), Collected::default()),
+            None => {
+                let (tx, rx) = mpsc::unbounded_channel();
+                let collector = output::collect_events(rx, false);
+                let started = Instant::now();
+                let result = agent
+                    .prompt(query.as_str())
+                    .with_history(&mut history)
+                    .with_tool_concurrency(TOOL_CONCURRENCY)
+                    .with_hook(EventHook::new(tx))
+                    .into_future()
+                    .instrument(telemetry::turn_span())
+                    .await;
+                lifecycle::turn_end(query, &result).await;
+                let collected = collector.await.unwrap_or_default();
+                usage_log.record(
+                    collected.usage.into(),
+                    collected.tool_calls.len(),
+                    started.elapsed(),
+                );
+                if let (Some(answers), Ok(answer)) = (answers, &result)
+                    && let Err(e) = answers.insert(query, answer)
+                {
+                    tracing::warn!("[!] Warning: Could not cache the answer: {e:#}");
+                }
+                (result, collected)
+            }
+        };
+
+        if let Err(e) = &result {
+            failed += 1;
+            tracing::error!(
+                "{}",
+                i18n::tr_with(">> Error: {error}", &[("error", &format!("{e:#}"))])
+            );
+        }
+
+        match format {
+            OutputFormat::Text | OutputFormat::Quickfix => {
+                let body = match (&result, &cached) {
+                    (Ok(answer), Some(cached)) => format!("{}\n\n{answer}", cached.marker()),
+                    (Ok(answer), None) => answer.clone(),
+                    (Err(e), _) => format!("**Error:** {e:#}"),
+                };
+                let quoted = query.replace('\n', "\n> ");
+                writeln!(writer, "## Query {}\n\n> {quoted}\n\n{body}\n", i + 1)?;
+            }
+            OutputFormat::Json | OutputFormat::StreamJson => {
+                OutputEvent::Result {
+                    prompt: query,
+                    answer: result.as_ref().ok().map(String::as_str),
+                    error: result.as_ref().err().map(|e| format!("{e:#}")),
+                    tool_calls: &collected.tool_calls,
+                    usage: collected.usage.into(),
+                    cached_at: cached.as_ref().map(|cached| cached.created),
+                }
+                .write_to(&mut writer)?;
+            }
+        }
+        writer.flush()?;
+    }
+
+    tracing::info!(
+        "{}",
+        i18n::tr_with(
+            ">> Done: {succeeded} succeeded, {failed} failed",
+            &[
+                ("succeeded", &(queries.len() - failed)),
+                ("failed", &failed)
+            ]
+        )
+    );
+
+    Ok(if failed == 0 {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::from(EXIT_QUERY_FAILED)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_queries_lines() {
+        let content = "# question bank\nwhere is main?\n\n  list the tools  \n";
+        assert_eq!(
+            parse_queries(content),
+            vec!["where is main?".to_string(), "list the tools".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_parse_queries_stanzas() {
+        let content = "Explain the build.\nInclude CI.\n---\n# skipped\nwhat license?\n---\n";
+        assert_eq!(
+            parse_queries(content),
+            vec![
+                "Explain the build.\nInclude CI.".to_string(),
+                "what license?".to_string()
+            ]
+        );
+    }
+}
@@ -0,0 +1,89 @@
+use std::fs;
+use std::path::Path;
+
+use crate::console::hyperlinks;
+
+/// A `path:line` reference in an answer that doesn't point into the workspace
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeadReference {
+    /// The reference as written in the answer
+    pub reference: String,
+    pub reason: String,
+}
+
+/// References to missing files, or to lines past the end of a file.
+///
+/// Only references that look like paths (with a `/` or an extension) are
+/// checked, so times like `12:30` and URLs are left alone.
+pub fn dead_references(answer: &str, base_dir: &Path) -> Vec<DeadReference> {
+    let mut dead: Vec<DeadReference> = Vec::new();
+
+    for caps in hyperlinks::REFERENCE.captures_iter(answer) {
+        let path = &caps["path"];
+        let looks_like_path = (path.contains('/') || path.contains('.')) && !path.starts_with("//");
+        let line: usize = caps["line"].parse().unwrap_or(1);
+
+        let reason = if !looks_like_path {
+            None
+        } else if let Ok(content) = fs::read_to_string(base_dir.join(path)) {
+            let lines = content.lines().count();
+            (line > lines).then(|| format!("the file has {lines} lines"))
+        } else {
+            Some("no such file".to_string())
+        };
+
+        if let Some(reason) = reason
+            && !dead.iter().any(|d| d.reference == caps[0])
+        {
+            dead.push(DeadReference {
+                reference: caps[0].to_string(),
+                reason,
+            });
+        }
+    }
+
+    dead
+}
+
+/// Post-process an answer before rendering: append a note listing the
+/// references that don't resolve, if any
+pub fn annotate(answer: &str, base_dir: &Path) -> String {
+    let dead = dead_references(answer, base_dir);
+    if dead.is_empty() {
+        answer.to_string()
+    } else {
+        let list = dead
+            .iter()
+            .map(|d| format!("`{}` ({})", d.reference, d.reason))
+            .collect::<Vec<_>>()
+            .join(", ");
+        format!("{answer}\n\n> **Unresolved references:** {list}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dead_references() {
+        let base_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+        let answer = "`main` is at src/main.rs:1, see also src/main.rs:100000 and \
+            src/gone.rs:3. Meeting at 12:30, docs at http://localhost:8080.";
+
+        let dead = dead_references(answer, base_dir);
+        assert_eq!(dead.len(), 2);
+        assert_eq!(dead[0].reference, "src/main.rs:100000");
+        assert!(dead[0].reason.starts_with("the file has"));
+        assert_eq!(
+            dead[1],
+            DeadReference {
+                reference: "src/gone.rs:3".to_string(),
+                reason: "no such file".to_string()
+            }
+        );
+
+        assert!(annotate(answer, base_dir).ends_with("`src/gone.rs:3` (no such file)"));
+        assert_eq!(annotate("src/main.rs:2", base_dir), "src/main.rs:2");
+    }
+}
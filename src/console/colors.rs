@@ -1,36 +1,99 @@
-use owo_colors::OwoColorize;
+use std::ffi::OsStr;
+use std::sync::OnceLock;
+
+use owo_colors::{OwoColorize, Style};
+use termimad::MadSkin;
+
+use crate::console::terminal;
+use crate::console::theme::{Theme, ThemeName};
+
+/// The active theme, `None` when colors are disabled
+static THEME: OnceLock<Option<Theme>> = OnceLock::new();
+
+/// Returns true if colored output should be produced: stdout is a terminal
+/// and `NO_COLOR` (https://no-color.org) is not set.
+fn colors_supported() -> bool {
+    colors_enabled(
+        std::env::var_os("NO_COLOR").as_deref(),
+        terminal::stdout_is_terminal(),
+    )
+}
+
+/// Whether to color output with `NO_COLOR` set to `no_color`, to a terminal
+/// or not. An empty `NO_COLOR` counts as unset.
+fn colors_enabled(no_color: Option<&OsStr>, terminal: bool) -> bool {
+    terminal && no_color.is_none_or(OsStr::is_empty)
+}
+
+/// Select the theme used for all subsequent output.
+///
+/// Must be called before anything is printed; later calls are ignored.
+pub fn init(name: ThemeName) {
+    let _ = THEME.set(colors_supported().then(|| Theme::new(name)));
+}
+
+fn theme() -> Option<&'static Theme> {
+    THEME
+        .get_or_init(|| colors_supported().then(|| Theme::new(ThemeName::default())))
+        .as_ref()
+}
+
+fn paint(text: impl std::fmt::Display, role: impl Fn(&Theme) -> Style) -> String {
+    match theme() {
+        Some(theme) => format!("{}", text.style(role(theme))),
+        None => text.to_string(),
+    }
+}
+
+/// Termimad skin for the active theme, or a style-free skin when colors are disabled
+pub fn skin() -> MadSkin {
+    theme().map_or_else(MadSkin::no_style, Theme::skin)
+}
 
 /// Format prompt numbers and token counts in cyan/blue
 pub fn color_prompt_number(text: impl std::fmt::Display) -> String {
-    format!("{}", text.cyan())
+    paint(text, |t| t.prompt_number)
 }
 
 /// Format debug messages (e.g., tool calls) in dark gray
 pub fn color_debug(text: impl std::fmt::Display) -> String {
-    format!("{}", text.bright_black())
+    paint(text, |t| t.debug)
 }
 
 /// Format error messages in bright red
 pub fn color_error(text: impl std::fmt::Display) -> String {
-    format!("{}", text.bright_red())
+    paint(text, |t| t.error)
 }
 
 /// Format warning messages in dim magenta
 pub fn color_warning(text: impl std::fmt::Display) -> String {
-    format!("{}", text.magenta().dimmed())
+    paint(text, |t| t.warning)
 }
 
 /// Format success messages in bright green
 pub fn color_success(text: impl std::fmt::Display) -> String {
-    format!("{}", text.bright_green())
+    paint(text, |t| t.success)
 }
 
 /// Format status messages (loading/ready) in dim green
 pub fn color_status(text: impl std::fmt::Display) -> String {
-    format!("{}", text.green().dimmed())
+    paint(text, |t| t.status)
 }
 
 /// Format dim text (e.g., normal text in prompt) in gray
 pub fn color_dim(text: impl std::fmt::Display) -> String {
-    format!("{}", text.bright_black())
+    paint(text, |t| t.dim)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_colors_enabled() {
+        assert!(colors_enabled(None, true));
+        assert!(colors_enabled(Some(OsStr::new("")), true));
+        assert!(!colors_enabled(Some(OsStr::new("1")), true));
+        assert!(!colors_enabled(None, false));
+    }
 }
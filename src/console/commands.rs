@@ -0,0 +1,289 @@
+use crate::console::{attach::Source, i18n};
+
+/// What `/copy` places on the clipboard
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CopyTarget {
+    /// The whole last answer
+    Answer,
+    /// The n-th (1-indexed) code block of the last answer
+    CodeBlock(usize),
+}
+
+/// Slash commands available in the REPL
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReplCommand {
+    /// Re-view the last response in the pager
+    Page,
+    /// Show session statistics
+    Stats,
+    /// Copy the last answer or one of its code blocks to the clipboard
+    Copy(CopyTarget),
+    /// Show or hide extended thinking; toggles when no state is given
+    Thinking(Option<bool>),
+    /// Expand a prompt template from config, or list templates when no name is given
+    Template { name: Option<String>, args: String },
+    /// Write the full conversation, including tool outputs, to a file
+    Export(String),
+    /// List the workspace's sessions with their titles and summaries
+    Sessions,
+    /// Remove the last turn from the conversation
+    Undo,
+    /// Give a query that ran out of turns more, as many as it had when none
+    /// are given
+    Continue(Option<usize>),
+    /// Remove the last turn and send its query again, after editing it if
+    /// asked to
+    Retry { edit: bool },
+    /// Run a shell command for the user; its output is not sent to the agent
+    Shell(String),
+    /// Send a file, the last shell output or the clipboard with the next query
+    Attach(Source),
+    /// Send an image with the next query
+    Image(String),
+    /// List the tools, or turn one on or off; toggles when no state is given
+    Tools(Option<(String, Option<bool>)>),
+}
+
+impl ReplCommand {
+    /// Parse a REPL input line into a command.
+    ///
+    /// Returns `None` if the line is neither a slash command nor a `!` shell
+    /// escape and should be sent to the agent.
+    pub fn parse(input: &str) -> Option<Result<Self, String>> {
+        match input.strip_prefix('!').map(str::trim) {
+            Some("") => Some(Err("Usage: !<command>".to_string())),
+            Some(command) => Some(Ok(ReplCommand::Shell(command.to_string()))),
+            None => Self::parse_slash(input),
+        }
+    }
+
+    fn parse_slash(input: &str) -> Option<Result<Self, String>> {
+        input.strip_prefix('/').map(|rest| {
+            let mut parts = rest.split_whitespace();
+            match parts.next().unwrap_or_default() {
+                "page" => Ok(ReplCommand::Page),
+                "stats" => Ok(ReplCommand::Stats),
+                "sessions" => Ok(ReplCommand::Sessions),
+                "attach" => match parts.collect::<Vec<_>>().join(" ").as_str() {
+                    "" => Err("Usage: /attach <path|last-shell-output|clipboard>".to_string()),
+                    source => Ok(ReplCommand::Attach(Source::parse(source))),
+                },
+                "image" => match parts.collect::<Vec<_>>().join(" ").as_str() {
+                    "" => Err("Usage: /image <path>".to_string()),
+                    path => Ok(ReplCommand::Image(path.to_string())),
+                },
+                "undo" => Ok(ReplCommand::Undo),
+                "continue" => match (parts.next(), parts.next()) {
+                    (None, _) => Ok(ReplCommand::Continue(None)),
+                    (Some(turns), None) => turns
+                        .parse::<usize>()
+                        .ok()
+                        .filter(|turns| *turns > 0)
+                        .map(|turns| ReplCommand::Continue(Some(turns)))
+                        .ok_or_else(|| format!("Invalid number of turns: {turns}")),
+                    _ => Err("Usage: /continue [turns]".to_string()),
+                },
+                "retry" => match parts.next() {
+                    None => Ok(ReplCommand::Retry { edit: false }),
+                    Some("edit") => Ok(ReplCommand::Retry { edit: true }),
+                    Some(_) => Err("Usage: /retry [edit]".to_string()),
+                },
+                "copy" => match (parts.next(), parts.next()) {
+                    (None, _) => Ok(ReplCommand::Copy(CopyTarget::Answer)),
+                    (Some("code"), None) => Ok(ReplCommand::Copy(CopyTarget::CodeBlock(1))),
+                    (Some("code"), Some(n)) => n
+                        .parse::<usize>()
+                        .ok()
+                        .filter(|n| *n > 0)
+                        .map(|n| ReplCommand::Copy(CopyTarget::CodeBlock(n)))
+                        .ok_or_else(|| format!("Invalid code block number: {n}")),
+                    _ => Err("Usage: /copy [code [n]]".to_string()),
+                },
+                "thinking" => match parts.next() {
+                    None => Ok(ReplCommand::Thinking(None)),
+                    Some("on") => Ok(ReplCommand::Thinking(Some(true))),
+                    Some("off") => Ok(ReplCommand::Thinking(Some(false))),
+                    Some(_) => Err("Usage: /thinking [on|off]".to_string()),
+                },
+                "t" => Ok(ReplCommand::Template {
+                    name: parts.next().map(str::to_string),
+                    args: parts.collect::<Vec<_>>().join(" "),
+                }),
+                "export" => match (parts.next(), parts.next()) {
+                    (Some(path), None) => Ok(ReplCommand::Export(path.to_string())),
+                    _ => Err("Usage: /export <file>".to_string()),
+                },
+                "tools" => match (parts.next(), parts.next(), parts.next()) {
+                    (None, _, _) => Ok(ReplCommand::Tools(None)),
+                    (Some(name), None, _) => Ok(ReplCommand::Tools(Some((name.to_string(), None)))),
+                    (Some(name), Some(state @ ("on" | "off")), None) => Ok(ReplCommand::Tools(
+                        Some((name.to_string(), Some(state == "on"))),
+                    )),
+                    _ => Err("Usage: /tools [<tool> [on|off]]".to_string()),
+                },
+                other => Err(i18n::tr_with(
+                    "Unknown command: /{command}",
+                    &[("command", &other)],
+                )),
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_not_a_command() {
+        assert_eq!(ReplCommand::parse("where is main?"), None);
+    }
+
+    #[test]
+    fn test_parse_copy() {
+        assert_eq!(
+            ReplCommand::parse("/copy"),
+            Some(Ok(ReplCommand::Copy(CopyTarget::Answer)))
+        );
+        assert_eq!(
+            ReplCommand::parse("/copy code"),
+            Some(Ok(ReplCommand::Copy(CopyTarget::CodeBlock(1))))
+        );
+        assert_eq!(
+            ReplCommand::parse("/copy code 3"),
+            Some(Ok(ReplCommand::Copy(CopyTarget::CodeBlock(3))))
+        );
+        assert!(matches!(ReplCommand::parse("/copy code 0"), Some(Err(_))));
+        assert!(matches!(ReplCommand::parse("/copy all"), Some(Err(_))));
+    }
+
+    #[test]
+    fn test_parse_page() {
+        assert_eq!(ReplCommand::parse("/page"), Some(Ok(ReplCommand::Page)));
+    }
+
+    #[test]
+    fn test_parse_stats() {
+        assert_eq!(ReplCommand::parse("/stats"), Some(Ok(ReplCommand::Stats)));
+    }
+
+    #[test]
+    fn test_parse_template() {
+        assert_eq!(
+            ReplCommand::parse("/t explain src/lib.rs error handling"),
+            Some(Ok(ReplCommand::Template {
+                name: Some("explain".to_string()),
+                args: "src/lib.rs error handling".to_string()
+            }))
+        );
+        assert_eq!(
+            ReplCommand::parse("/t"),
+            Some(Ok(ReplCommand::Template {
+                name: None,
+                args: String::new()
+            }))
+        );
+    }
+
+    #[test]
+    fn test_parse_thinking() {
+        assert_eq!(
+            ReplCommand::parse("/thinking"),
+            Some(Ok(ReplCommand::Thinking(None)))
+        );
+        assert_eq!(
+            ReplCommand::parse("/thinking off"),
+            Some(Ok(ReplCommand::Thinking(Some(false))))
+        );
+        assert!(matches!(
+            ReplCommand::parse("/thinking maybe"),
+            Some(Err(_))
+        ));
+    }
+
+    #[test]
+    fn test_parse_export() {
+        assert_eq!(
+            ReplCommand::parse("/export session.json"),
+            Some(Ok(ReplCommand::Export("session.json".to_string())))
+        );
+        assert!(matches!(ReplCommand::parse("/export"), Some(Err(_))));
+    }
+
+    #[test]
+    fn test_parse_retry() {
+        assert_eq!(
+            ReplCommand::parse("/retry"),
+            Some(Ok(ReplCommand::Retry { edit: false }))
+        );
+        assert_eq!(
+            ReplCommand::parse("/retry edit"),
+            Some(Ok(ReplCommand::Retry { edit: true }))
+        );
+        assert!(matches!(ReplCommand::parse("/retry now"), Some(Err(_))));
+    }
+
+    #[test]
+    fn test_parse_tools() {
+        assert_eq!(
+            ReplCommand::parse("/tools"),
+            Some(Ok(ReplCommand::Tools(None)))
+        );
+        assert_eq!(
+            ReplCommand::parse("/tools bash"),
+            Some(Ok(ReplCommand::Tools(Some(("bash".to_string(), None)))))
+        );
+        assert_eq!(
+            ReplCommand::parse("/tools forge on"),
+            Some(Ok(ReplCommand::Tools(Some((
+                "forge".to_string(),
+                Some(true)
+            )))))
+        );
+        assert!(matches!(
+            ReplCommand::parse("/tools bash disable"),
+            Some(Err(_))
+        ));
+    }
+
+    #[test]
+    fn test_parse_shell() {
+        assert_eq!(
+            ReplCommand::parse("!git status --short"),
+            Some(Ok(ReplCommand::Shell("git status --short".to_string())))
+        );
+        assert!(matches!(ReplCommand::parse("! "), Some(Err(_))));
+    }
+
+    #[test]
+    fn test_parse_continue() {
+        assert_eq!(
+            ReplCommand::parse("/continue"),
+            Some(Ok(ReplCommand::Continue(None)))
+        );
+        assert_eq!(
+            ReplCommand::parse("/continue 10"),
+            Some(Ok(ReplCommand::Continue(Some(10))))
+        );
+        assert!(matches!(ReplCommand::parse("/continue 0"), Some(Err(_))));
+    }
+
+    #[test]
+    fn test_parse_attach() {
+        assert_eq!(
+            ReplCommand::parse("/attach clipboard"),
+            Some(Ok(ReplCommand::Attach(Source::Clipboard)))
+        );
+        assert!(matches!(ReplCommand::parse("/attach"), Some(Err(_))));
+        assert_eq!(
+            ReplCommand::parse("/image docs/arch diagram.png"),
+            Some(Ok(ReplCommand::Image("docs/arch diagram.png".to_string())))
+        );
+        assert!(matches!(ReplCommand::parse("/image"), Some(Err(_))));
+    }
+
+    #[test]
+    fn test_parse_unknown() {
+        assert!(matches!(ReplCommand::parse("/nope"), Some(Err(_))));
+    }
+}
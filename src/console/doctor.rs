@@ -0,0 +1,274 @@
+use std::path::Path;
+use std::process::ExitCode;
+use std::time::{Duration, Instant};
+
+use reqwest::StatusCode;
+use tokio::process::Command;
+
+use crate::agent::keyring::{self, KeySource};
+use crate::config::{self, Config};
+use crate::console::colors;
+
+/// Endpoint used to check connectivity and the API key without spending tokens
+const MODELS_URL: &str = "https://api.anthropic.com/v1/models";
+
+/// `anthropic-version` header sent with the connectivity check
+const ANTHROPIC_VERSION: &str = "2023-06-01";
+
+/// Give up on the provider after this long
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Exit code used when at least one check failed
+const EXIT_CHECK_FAILED: u8 = 1;
+
+/// External programs horse relies on, and how to get them
+const PROGRAMS: &[(&str, &str)] = &[
+    (
+        "rg",
+        "Install ripgrep for fast content search: https://github.com/BurntSushi/ripgrep#installation",
+    ),
+    (
+        "rga",
+        "Install ripgrep-all to search PDFs and documents: https://github.com/phiresky/ripgrep-all",
+    ),
+    (
+        "git",
+        "Install git to show the branch in the prompt and use the forge tool",
+    ),
+    (
+        "tesseract",
+        "Install tesseract to search scanned PDFs and images: https://tesseract-ocr.github.io/tessdoc/Installation.html",
+    ),
+    (
+        "fd",
+        "Install fd for faster file lookups: https://github.com/sharkdp/fd#installation",
+    ),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Status {
+    Ok,
+    Warn,
+    Fail,
+}
+
+/// Result of a single diagnostic
+#[derive(Debug)]
+struct Check {
+    name: String,
+    status: Status,
+    detail: String,
+    fix: Option<String>,
+}
+
+impl Check {
+    fn ok(name: impl Into<String>, detail: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            status: Status::Ok,
+            detail: detail.into(),
+            fix: None,
+        }
+    }
+
+    fn problem(
+        status: Status,
+        name: impl Into<String>,
+        detail: impl Into<String>,
+        fix: impl Into<String>,
+    ) -> Self {
+        Self {
+            name: name.into(),
+            status,
+            detail: detail.into(),
+            fix: Some(fix.into()),
+        }
+    }
+
+    fn print(&self) {
+        let (mark, detail) = match self.status {
+            Status::Ok => ("ok", colors::color_success(&self.detail)),
+            Status::Warn => ("!!", colors::color_warning(&self.detail)),
+            Status::Fail => ("xx", colors::color_error(&self.detail)),
+        };
+        println!("[{mark}] {:<10} {detail}", self.name);
+        if let Some(fix) = &self.fix {
+            println!(
+                "     {:<10} {}",
+                "",
+                colors::color_debug(format!("-> {fix}"))
+            );
+        }
+    }
+}
+
+/// Check the environment horse runs in and print actionable fixes.
+///
+/// Returns a failure exit code if anything that prevents horse from working
+/// is broken; missing optional programs are only warnings.
+pub async fn run(base_dir: &Path) -> ExitCode {
+    let mut checks = Vec::new();
+
+    for (program, fix) in PROGRAMS {
+        checks.push(check_program(program, fix).await);
+    }
+    checks.push(check_config(base_dir));
+    checks.extend(check_provider().await);
+
+    checks.iter().for_each(Check::print);
+
+    let failed = checks.iter().filter(|c| c.status == Status::Fail).count();
+    let warned = checks.iter().filter(|c| c.status == Status::Warn).count();
+    println!();
+    if failed > 0 {
+        println!(
+            "{}",
+            colors::color_error(format!("{failed} failed, {warned} warnings"))
+        );
+        ExitCode::from(EXIT_CHECK_FAILED)
+    } else {
+        println!(
+            "{}",
+            colors::color_success(format!("All required checks passed, {warned} warnings"))
+        );
+        ExitCode::SUCCESS
+    }
+}
+
+/// Check and print the given programs of `PROGRAMS`, for `horse init`
+pub async fn check_programs(names: &[&str]) {
+    for (program, fix) in PROGRAMS
+        .iter()
+        .filter(|(program, _)| names.contains(program))
+    {
+        check_program(program, fix).await.print();
+    }
+}
+
+async fn check_program(program: &str, fix: &str) -> Check {
+    match Command::new(program).arg("--version").output().await {
+        Ok(output) => {
+            let stdout = String::from_utf8_lossy(&output.stdout);
+            let version = stdout.lines().next().unwrap_or("unknown version").trim();
+            Check::ok(program, version)
+        }
+        Err(_) => Check::problem(Status::Warn, program, "not found in PATH", fix),
+    }
+}
+
+fn check_config(base_dir: &Path) -> Check {
+    let files: Vec<String> = config::global_config_path()
+        .into_iter()
+        .chain([base_dir.join(config::WORKSPACE_CONFIG_FILE)])
+        .filter(|path| path.exists())
+        .map(|path| path.display().to_string())
+        .collect();
+
+    match Config::load(base_dir) {
+        Ok(_) if files.is_empty() => Check::ok("config", "no config files, using defaults"),
+        Ok(_) => Check::ok("config", files.join(", ")),
+        Err(e) => Check::problem(
+            Status::Fail,
+            "config",
+            format!("{e:#}"),
+            "Fix the reported key or syntax error; see the Configuration section of the README",
+        ),
+    }
+}
+
+/// Check the API key and how fast the provider answers
+async fn check_provider() -> Vec<Check> {
+    let key = keyring::api_key();
+    let key_check = match &key {
+        Some((_, KeySource::Env)) => Check::ok("api key", "ANTHROPIC_API_KEY is set"),
+        Some((_, KeySource::Keyring)) => Check::ok("api key", "stored in the system keyring"),
+        None => Check::problem(
+            Status::Fail,
+            "api key",
+            "ANTHROPIC_API_KEY is not set and no key is stored in the system keyring",
+            "Create a key at https://console.anthropic.com and run `horse init`, or \
+             `export ANTHROPIC_API_KEY=...`",
+        ),
+    };
+    let key = key.map(|(key, _)| key);
+
+    let client = reqwest::Client::builder()
+        .timeout(CONNECT_TIMEOUT)
+        .build()
+        .unwrap_or_default();
+    let started = Instant::now();
+    let response = client
+        .get(MODELS_URL)
+        .header("x-api-key", key.as_deref().unwrap_or_default())
+        .header("anthropic-version", ANTHROPIC_VERSION)
+        .send()
+        .await;
+    let latency = started.elapsed();
+
+    let provider_check = match response {
+        Ok(response) => provider_status(response.status(), key.is_some(), latency),
+        Err(e) => Check::problem(
+            Status::Fail,
+            "provider",
+            format!("api.anthropic.com unreachable: {e}"),
+            "Check the network connection, or set HTTPS_PROXY if you are behind a proxy",
+        ),
+    };
+
+    vec![key_check, provider_check]
+}
+
+/// Interpret the status code of the models endpoint
+fn provider_status(status: StatusCode, has_key: bool, latency: Duration) -> Check {
+    let reachable = format!("api.anthropic.com reachable in {}ms", latency.as_millis());
+
+    match status {
+        status if status.is_success() => {
+            Check::ok("provider", format!("{reachable}, key accepted"))
+        }
+        StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN if has_key => Check::problem(
+            Status::Fail,
+            "provider",
+            format!("{reachable}, but the API key was rejected"),
+            "Check that ANTHROPIC_API_KEY is current and belongs to an active workspace",
+        ),
+        // Without a key, a rejection still proves connectivity
+        StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => Check::ok("provider", reachable),
+        status => Check::problem(
+            Status::Warn,
+            "provider",
+            format!("{reachable}, but it answered {status}"),
+            "Retry later; see https://status.anthropic.com",
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_provider_status() {
+        let latency = Duration::from_millis(120);
+
+        let check = provider_status(StatusCode::OK, true, latency);
+        assert_eq!(check.status, Status::Ok);
+        assert_eq!(
+            check.detail,
+            "api.anthropic.com reachable in 120ms, key accepted"
+        );
+
+        assert_eq!(
+            provider_status(StatusCode::UNAUTHORIZED, true, latency).status,
+            Status::Fail
+        );
+        assert_eq!(
+            provider_status(StatusCode::UNAUTHORIZED, false, latency).status,
+            Status::Ok
+        );
+        assert_eq!(
+            provider_status(StatusCode::SERVICE_UNAVAILABLE, true, latency).status,
+            Status::Warn
+        );
+    }
+}
@@ -0,0 +1,81 @@
+use anyhow::{Context, Result};
+use rustyline::error::ReadlineError;
+use rustyline::{Config, DefaultEditor, EditMode};
+use serde::Deserialize;
+
+/// Keybinding mode of the line editor
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum EditingMode {
+    #[default]
+    Emacs,
+    /// Modal editing: starts in insert mode, Esc switches to normal mode
+    Vi,
+}
+
+impl From<EditingMode> for EditMode {
+    fn from(mode: EditingMode) -> Self {
+        match mode {
+            EditingMode::Emacs => Self::Emacs,
+            EditingMode::Vi => Self::Vi,
+        }
+    }
+}
+
+/// Line editor with history and configurable keybindings
+pub struct LineEditor {
+    editor: DefaultEditor,
+}
+
+impl LineEditor {
+    pub fn new(mode: EditingMode) -> Result<Self> {
+        let config = Config::builder()
+            .edit_mode(mode.into())
+            .auto_add_history(true)
+            .build();
+        let editor = DefaultEditor::with_config(config).context("Failed to set up line editor")?;
+
+        Ok(Self { editor })
+    }
+
+    /// Read a line of input.
+    ///
+    /// Returns `None` when the user asks to exit with Ctrl+C or Ctrl+D.
+    pub fn read_line(&mut self, prompt: &str) -> Result<Option<String>> {
+        match self.editor.readline(prompt) {
+            Ok(line) => Ok(Some(line)),
+            Err(ReadlineError::Eof | ReadlineError::Interrupted) => Ok(None),
+            Err(e) => Err(e).context("Failed to read line"),
+        }
+    }
+
+    /// Read a line of input, pre-filled with `initial` for editing.
+    ///
+    /// Returns `None` when the user asks to exit with Ctrl+C or Ctrl+D.
+    pub fn edit_line(&mut self, prompt: &str, initial: &str) -> Result<Option<String>> {
+        match self.editor.readline_with_initial(prompt, (initial, "")) {
+            Ok(line) => Ok(Some(line)),
+            Err(ReadlineError::Eof | ReadlineError::Interrupted) => Ok(None),
+            Err(e) => Err(e).context("Failed to read line"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Deserialize)]
+    struct Settings {
+        #[serde(default)]
+        editing_mode: EditingMode,
+    }
+
+    #[test]
+    fn test_editing_mode() {
+        let mode = |toml: &str| toml::from_str::<Settings>(toml).unwrap().editing_mode;
+        assert_eq!(EditMode::from(mode("editing_mode = \"vi\"")), EditMode::Vi);
+        assert_eq!(EditMode::from(mode("")), EditMode::Emacs);
+        assert!(toml::from_str::<Settings>("editing_mode = \"vim\"").is_err());
+    }
+}
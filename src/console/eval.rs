@@ -0,0 +1,313 @@
+use std::fs::File;
+use std::io::Write;
+use std::ops::RangeInclusive;
+use std::path::Path;
+use std::process::ExitCode;
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result};
+use rig::agent::Agent;
+use rig::completion::Prompt;
+use rig::providers::anthropic;
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
+use tracing::Instrument;
+
+use crate::agent::{TOOL_CONCURRENCY, hooks::EventHook, lifecycle, models, telemetry};
+use crate::console::output::{self, UsageRecord};
+use crate::console::usage::UsageLog;
+use crate::console::{colors, hyperlinks};
+
+/// Exit code used when at least one case failed
+const EXIT_CASE_FAILED: u8 = 1;
+
+/// A set of questions about a repository and the evidence their answers
+/// must cite
+#[derive(Debug, Deserialize)]
+pub struct Suite {
+    pub cases: Vec<Case>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Case {
+    /// Shown in the report instead of the question
+    pub name: Option<String>,
+    pub question: String,
+    /// Locations the answer must cite: `path` for any line of a file,
+    /// `path:line` or `path:start-end` for specific lines
+    #[serde(default)]
+    pub expect: Vec<String>,
+}
+
+/// A location an answer is expected to cite
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Evidence {
+    path: String,
+    lines: Option<RangeInclusive<usize>>,
+}
+
+impl Evidence {
+    fn parse(expected: &str) -> Result<Self> {
+        let expected = expected.trim().trim_start_matches("./");
+        match expected.rsplit_once(':') {
+            Some((path, lines)) => {
+                let (start, end) = lines.split_once('-').unwrap_or((lines, lines));
+                let range = start
+                    .trim()
+                    .parse::<usize>()
+                    .and_then(|start| end.trim().parse::<usize>().map(|end| start..=end));
+                let range = range.with_context(|| format!("Invalid line range in {expected}"))?;
+                Ok(Self {
+                    path: path.to_string(),
+                    lines: Some(range),
+                })
+            }
+            None => Ok(Self {
+                path: expected.to_string(),
+                lines: None,
+            }),
+        }
+    }
+
+    /// Whether a `path:line` reference cites this evidence
+    fn is_cited_by(&self, path: &str, line: usize) -> bool {
+        path.trim_start_matches("./") == self.path
+            && self
+                .lines
+                .as_ref()
+                .is_none_or(|lines| lines.contains(&line))
+    }
+}
+
+/// The outcome of one case, as written to the report
+#[derive(Debug, Serialize)]
+pub struct CaseResult {
+    pub name: String,
+    pub passed: bool,
+    /// Expected locations the answer didn't cite
+    pub missing: Vec<String>,
+    pub expected: usize,
+    pub error: Option<String>,
+    /// Completion requests the agent made
+    pub turns: usize,
+    pub tool_calls: usize,
+    pub usage: UsageRecord,
+    pub cost_usd: Option<f64>,
+    pub duration_ms: u64,
+}
+
+/// The `path:line` references cited in an answer
+fn citations(answer: &str) -> Vec<(String, usize)> {
+    hyperlinks::REFERENCE
+        .captures_iter(answer)
+        .filter_map(|caps| Some((caps["path"].to_string(), caps["line"].parse().ok()?)))
+        .collect()
+}
+
+/// The expected locations that none of the answer's references cite
+fn missing_evidence(answer: &str, expect: &[Evidence]) -> Vec<Evidence> {
+    let cited = citations(answer);
+    expect
+        .iter()
+        .filter(|evidence| {
+            !cited
+                .iter()
+                .any(|(path, line)| evidence.is_cited_by(path, *line))
+        })
+        .cloned()
+        .collect()
+}
+
+/// Run every case of a suite against the agent, each in a fresh
+/// conversation, and print a report of the evidence found, turns, tokens
+/// and estimated cost per case. With `report`, the results are also written
+/// there as JSON lines, for comparing runs.
+pub async fn run(
+    agent: &Agent<anthropic::completion::CompletionModel>,
+    model: &str,
+    suite_file: &Path,
+    report: Option<&Path>,
+    usage_log: &UsageLog,
+) -> Result<ExitCode> {
+    let content = tokio::fs::read_to_string(suite_file)
+        .await
+        .with_context(|| format!("Failed to read {}", suite_file.display()))?;
+    let suite: Suite = serde_norway::from_str(&content)
+        .with_context(|| format!("Failed to parse {}", suite_file.display()))?;
+    let expectations = suite
+        .cases
+        .iter()
+        .map(|case| case.expect.iter().map(|e| Evidence::parse(e)).collect())
+        .collect::<Result<Vec<Vec<Evidence>>>>()?;
+
+    let mut writer = report
+        .map(|path| {
+            File::create(path).with_context(|| format!("Failed to create {}", path.display()))
+        })
+        .transpose()?;
+
+    let mut results = Vec::new();
+    for (i, (case, expect)) in suite.cases.iter().zip(&expectations).enumerate() {
+        let name = case
+            .name
+            .clone()
+            .unwrap_or_else(|| case.question.lines().next().unwrap_or_default().to_string());
+        tracing::info!(">> [{}/{}] {name}", i + 1, suite.cases.len());
+
+        let result = run_case(agent, model, name, &case.question, expect).await;
+        usage_log.record(
+            result.usage,
+            result.tool_calls,
+            Duration::from_millis(result.duration_ms),
+        );
+        if let Some(writer) = &mut writer {
+            serde_json::to_writer(&mut *writer, &result)?;
+            writeln!(writer)?;
+        }
+        results.push(result);
+    }
+
+    println!("{}", render(&results));
+    Ok(if results.iter().all(|r| r.passed) {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::from(EXIT_CASE_FAILED)
+    })
+}
+
+async fn run_case(
+    agent: &Agent<anthropic::completion::CompletionModel>,
+    model: &str,
+    name: String,
+    question: &str,
+    expect: &[Evidence],
+) -> CaseResult {
+    let (tx, rx) = mpsc::unbounded_channel();
+    let collector = output::collect_events(rx, false);
+    let started = Instant::now();
+    let result = agent
+        .prompt(question)
+        .with_tool_concurrency(TOOL_CONCURRENCY)
+        .with_hook(EventHook::new(tx))
+        .into_future()
+        .instrument(telemetry::turn_span())
+        .await;
+    lifecycle::turn_end(question, &result).await;
+    let collected = collector.await.unwrap_or_default();
+    let usage = UsageRecord::from(collected.usage);
+
+    // A failed case cites nothing
+    let answer = result.as_deref().unwrap_or_default();
+    let missing: Vec<String> = missing_evidence(answer, expect)
+        .into_iter()
+        .map(|evidence| match evidence.lines {
+            Some(lines) => format!("{}:{}-{}", evidence.path, lines.start(), lines.end()),
+            None => evidence.path,
+        })
+        .collect();
+
+    CaseResult {
+        name,
+        passed: result.is_ok() && missing.is_empty(),
+        missing,
+        expected: expect.len(),
+        error: result.as_ref().err().map(|e| format!("{e:#}")),
+        turns: collected.completions,
+        tool_calls: collected.tool_calls.len(),
+        cost_usd: models::estimate_cost(
+            model,
+            usage.input_tokens,
+            usage.output_tokens,
+            usage.cached_input_tokens,
+        ),
+        usage,
+        duration_ms: started.elapsed().as_millis() as u64,
+    }
+}
+
+/// One line per case, then the accuracy and totals
+fn render(results: &[CaseResult]) -> String {
+    let cost = |cost: Option<f64>| cost.map_or_else(|| "-".to_string(), |c| format!("${c:.4}"));
+    let mut lines: Vec<String> = results
+        .iter()
+        .map(|r| {
+            let status = if r.passed {
+                colors::color_success("pass")
+            } else {
+                colors::color_error("FAIL")
+            };
+            let found = r.expected - r.missing.len();
+            let detail = match (&r.error, r.missing.is_empty()) {
+                (Some(e), _) => format!(" error: {e}"),
+                (None, false) => format!(" missing: {}", r.missing.join(", ")),
+                (None, true) => String::new(),
+            };
+            format!(
+                "{status} {}  evidence {found}/{}, {} turns, {} in / {} out tokens, {}{}",
+                r.name,
+                r.expected,
+                r.turns,
+                r.usage.input_tokens + r.usage.cached_input_tokens,
+                r.usage.output_tokens,
+                cost(r.cost_usd),
+                colors::color_dim(detail)
+            )
+        })
+        .collect();
+
+    let passed = results.iter().filter(|r| r.passed).count();
+    let accuracy = if results.is_empty() {
+        0.0
+    } else {
+        100.0 * passed as f64 / results.len() as f64
+    };
+    let total_cost = results.iter().map(|r| r.cost_usd).sum::<Option<f64>>();
+    lines.push(format!(
+        "\nAccuracy: {passed}/{} ({accuracy:.0}%), {} turns, {} tokens, {}",
+        results.len(),
+        results.iter().map(|r| r.turns).sum::<usize>(),
+        results
+            .iter()
+            .map(|r| r.usage.input_tokens + r.usage.cached_input_tokens + r.usage.output_tokens)
+            .sum::<u64>(),
+        cost(total_cost)
+    ));
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_suite() {
+        let suite: Suite = serde_norway::from_str(
+            "cases:\n  - name: cli\n    question: Where is the CLI defined?\n    expect:\n      - src/main.rs\n  - question: What license?\n",
+        )
+        .unwrap();
+        assert_eq!(suite.cases.len(), 2);
+        assert_eq!(suite.cases[0].expect, ["src/main.rs"]);
+        assert!(suite.cases[1].expect.is_empty());
+    }
+
+    #[test]
+    fn test_missing_evidence() {
+        let expect = [
+            Evidence::parse("src/main.rs").unwrap(),
+            Evidence::parse("./src/config.rs:10-40").unwrap(),
+            Evidence::parse("src/agent.rs:86").unwrap(),
+        ];
+        assert_eq!(
+            expect[1],
+            Evidence {
+                path: "src/config.rs".to_string(),
+                lines: Some(10..=40)
+            }
+        );
+        assert!(Evidence::parse("src/main.rs:ten").is_err());
+
+        let answer = "Args are parsed in `src/main.rs:250` with defaults from \
+                      `./src/config.rs:12`; the agent is built at src/agent.rs:90.";
+        assert_eq!(missing_evidence(answer, &expect), [expect[2].clone()]);
+    }
+}
@@ -0,0 +1,108 @@
+use std::path::Path;
+use std::process::ExitCode;
+use std::time::Instant;
+
+use anyhow::Result;
+use rig::agent::Agent;
+use rig::completion::Prompt;
+use rig::providers::anthropic;
+use tracing::Instrument;
+
+use crate::agent::hooks::ProgressHook;
+use crate::agent::{TOOL_CONCURRENCY, lifecycle, telemetry};
+use crate::console::usage::UsageLog;
+use crate::console::{citations, colors, markdown, review};
+
+/// Exit code used when the commit couldn't be explained
+const EXIT_EXPLAIN_FAILED: u8 = 1;
+
+const EXPLAIN_REQUEST: &str = "Explain the following commit of this repository to a developer \
+    who doesn't know the codebase. Read the surrounding code with read_file and deep_search, \
+    and the history with bash (`git log`, `git show`, `git blame`) where it helps, to work out \
+    what the changed code does and why it was changed. Write a narrative: the problem or goal \
+    behind the commit, how the change addresses it, the parts of the system it touches, and \
+    anything surprising or risky about it. Cite code as path:line.";
+
+/// Arguments of the `git show` call printing the message, changed files and
+/// patch of `rev`; `--end-of-options` keeps a revision starting with `-`
+/// from being taken for an option
+fn show_args(rev: &str) -> [&str; 6] {
+    [
+        "show",
+        "--stat",
+        "--patch",
+        "--format=fuller",
+        "--end-of-options",
+        rev,
+    ]
+}
+
+/// The explanation asked of the agent for the commit `rev` shown as `commit`
+fn request(rev: &str, commit: &str) -> String {
+    format!("{EXPLAIN_REQUEST}\n\n<commit rev=\"{rev}\">\n{commit}\n</commit>")
+}
+
+/// Explain the commit `rev` with the help of the code around it, and print
+/// the explanation.
+///
+/// Returns a success exit code if an explanation was produced.
+pub async fn run(
+    agent: &Agent<anthropic::completion::CompletionModel>,
+    base_dir: &Path,
+    rev: &str,
+    usage_log: &UsageLog,
+) -> Result<ExitCode> {
+    let commit = review::git_output(base_dir, &show_args(rev)).await?;
+    let prompt = request(rev, &commit);
+
+    let hook = ProgressHook::new();
+    let started = Instant::now();
+    let result = agent
+        .prompt(prompt.as_str())
+        .with_tool_concurrency(TOOL_CONCURRENCY)
+        .with_hook(hook.clone())
+        .into_future()
+        .instrument(telemetry::turn_span())
+        .await;
+    lifecycle::turn_end(&prompt, &result).await;
+    usage_log.record(
+        hook.get_total_usage().into(),
+        hook.take_tool_calls().len(),
+        started.elapsed(),
+    );
+
+    match result {
+        Ok(answer) => {
+            println!(
+                "{}",
+                markdown::format_markdown(&citations::annotate(&answer, base_dir))
+            );
+            Ok(ExitCode::SUCCESS)
+        }
+        Err(e) => {
+            eprintln!("{}", colors::color_error(format!(">> Error: {e:#}")));
+            Ok(ExitCode::from(EXIT_EXPLAIN_FAILED))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_show_args() {
+        assert_eq!(show_args("HEAD~2")[5], "HEAD~2");
+        assert_eq!(show_args("-p")[4..], ["--end-of-options", "-p"]);
+    }
+
+    #[test]
+    fn test_request() {
+        let request = request("abc123", "commit abc123\n\n    Fix retries");
+        assert!(request.starts_with(EXPLAIN_REQUEST));
+        assert!(
+            request
+                .ends_with("<commit rev=\"abc123\">\ncommit abc123\n\n    Fix retries\n</commit>")
+        );
+    }
+}
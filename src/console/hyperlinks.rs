@@ -0,0 +1,187 @@
+use std::path::{Path, PathBuf};
+use std::sync::{LazyLock, OnceLock};
+
+use regex::{Captures, Regex};
+use serde::Deserialize;
+
+/// Matches `path:line` and `path:line:col` references
+pub static REFERENCE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?P<path>[\w./-]*[\w-]):(?P<line>\d+)(?::(?P<col>\d+))?")
+        .expect("Invalid reference regex")
+});
+
+/// Matches ANSI SGR sequences, which must not be touched when linkifying
+static ANSI_SGR: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\x1b\[[0-9;]*m").expect("Invalid ANSI regex"));
+
+/// `[hyperlinks]` config section
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HyperlinkConfig {
+    pub enabled: bool,
+    /// URL template with `{path}` (absolute, percent-encoded), `{line}` and
+    /// `{col}` placeholders, e.g. `vscode://file/{path}:{line}:{col}`
+    pub url: String,
+}
+
+impl Default for HyperlinkConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            url: "file://{path}".to_string(),
+        }
+    }
+}
+
+struct Linker {
+    base_dir: PathBuf,
+    url: String,
+}
+
+static LINKER: OnceLock<Option<Linker>> = OnceLock::new();
+
+/// Enable hyperlinking of references to files under `base_dir`.
+///
+/// `terminal` tells whether output goes to a terminal; links are never emitted otherwise.
+pub fn init(config: &HyperlinkConfig, base_dir: &Path, terminal: bool) {
+    let _ = LINKER.set((config.enabled && terminal).then(|| Linker {
+        base_dir: base_dir.to_path_buf(),
+        url: config.url.clone(),
+    }));
+}
+
+/// Wrap `path:line` references to existing files in OSC-8 hyperlinks.
+///
+/// Text is returned unchanged if hyperlinks are disabled.
+pub fn linkify(text: &str) -> String {
+    match LINKER.get().and_then(Option::as_ref) {
+        Some(linker) => linkify_with(text, &linker.base_dir, &linker.url),
+        None => text.to_string(),
+    }
+}
+
+/// `path` as the path of a URL: bytes other than unreserved characters and
+/// `/` are percent-encoded, so spaces and `#` don't break the link
+pub fn encode_path(path: &Path) -> String {
+    path.to_string_lossy()
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => {
+                char::from(byte).to_string()
+            }
+            _ => format!("%{byte:02X}"),
+        })
+        .collect()
+}
+
+/// The path of a `file://` URL, percent-decoded
+pub fn decode_path(path: &str) -> String {
+    let bytes = path.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = (bytes[i] == b'%')
+            .then(|| path.get(i + 1..i + 3))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+fn linkify_with(text: &str, base_dir: &Path, url: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut last = 0;
+
+    // Only plain text between escape sequences is linkified
+    for sgr in ANSI_SGR.find_iter(text) {
+        result.push_str(&linkify_segment(&text[last..sgr.start()], base_dir, url));
+        result.push_str(sgr.as_str());
+        last = sgr.end();
+    }
+    result.push_str(&linkify_segment(&text[last..], base_dir, url));
+
+    result
+}
+
+fn linkify_segment(text: &str, base_dir: &Path, url: &str) -> String {
+    REFERENCE
+        .replace_all(text, |caps: &Captures| {
+            let reference = &caps[0];
+            let path = base_dir.join(&caps["path"]);
+
+            if path.is_file() {
+                let target = url
+                    .replace("{path}", &encode_path(&path))
+                    .replace("{line}", &caps["line"])
+                    .replace("{col}", caps.name("col").map_or("1", |c| c.as_str()));
+                format!("\x1b]8;;{target}\x1b\\{reference}\x1b]8;;\x1b\\")
+            } else {
+                reference.to_string()
+            }
+        })
+        .into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_linkify_existing_files_only() {
+        let base_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+        let main = base_dir.join("src/main.rs");
+
+        let result = linkify_with(
+            "see src/main.rs:12 and missing.rs:3",
+            base_dir,
+            "file://{path}",
+        );
+        assert_eq!(
+            result,
+            format!(
+                "see \x1b]8;;file://{}\x1b\\src/main.rs:12\x1b]8;;\x1b\\ and missing.rs:3",
+                main.display()
+            )
+        );
+    }
+
+    #[test]
+    fn test_encode_path() {
+        let path = Path::new("/home/me/My Notes/#1 ünïcode.md");
+        assert_eq!(
+            encode_path(path),
+            "/home/me/My%20Notes/%231%20%C3%BCn%C3%AFcode.md"
+        );
+        assert_eq!(decode_path(&encode_path(path)), path.to_string_lossy());
+        assert_eq!(decode_path("/tmp/100%"), "/tmp/100%");
+    }
+
+    #[test]
+    fn test_linkify_skips_ansi_sequences() {
+        let base_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+        let main = base_dir.join("src/main.rs");
+
+        let result = linkify_with(
+            "\x1b[38;5;222msrc/main.rs:4:2\x1b[0m",
+            base_dir,
+            "vscode://file/{path}:{line}:{col}",
+        );
+        assert_eq!(
+            result,
+            format!(
+                "\x1b[38;5;222m\x1b]8;;vscode://file/{}:4:2\x1b\\src/main.rs:4:2\x1b]8;;\x1b\\\x1b[0m",
+                main.display()
+            )
+        );
+    }
+}
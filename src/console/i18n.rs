@@ -0,0 +1,650 @@
+use std::fmt::Display;
+use std::sync::OnceLock;
+
+/// Language of the answers and of horse's own messages, if not English
+static LANGUAGE: OnceLock<Language> = OnceLock::new();
+
+/// Languages known by code, as ISO 639-1 code and English name
+const KNOWN_LANGUAGES: &[(&str, &str)] = &[
+    ("en", "English"),
+    ("de", "German"),
+    ("fr", "French"),
+    ("es", "Spanish"),
+    ("it", "Italian"),
+    ("pt", "Portuguese"),
+    ("nl", "Dutch"),
+    ("pl", "Polish"),
+    ("ru", "Russian"),
+    ("uk", "Ukrainian"),
+    ("ja", "Japanese"),
+    ("ko", "Korean"),
+    ("zh", "Chinese"),
+];
+
+/// Translations of horse's messages, keyed by the English message.
+/// `{name}` placeholders are filled in by `tr_with`.
+const GERMAN: &[(&str, &str)] = &[
+    ("Working directory", "Arbeitsverzeichnis"),
+    ("Model", "Modell"),
+    ("Max turns", "Maximale Runden"),
+    (
+        ">> Ready! Type your queries (Ctrl+C or Ctrl+D to exit)",
+        ">> Bereit! Stelle deine Fragen (Strg+C oder Strg+D zum Beenden)",
+    ),
+    (">> Goodbye!", ">> Auf Wiedersehen!"),
+    ("Processing", "Verarbeite"),
+    ("Summarizing", "Fasse zusammen"),
+    ("Verifying citations", "Prüfe Belegstellen"),
+    ("Transcribing", "Transkribiere"),
+    (
+        "Rate limited: {waiting} queued, next slot in {secs}s",
+        "Ratenlimit: {waiting} in der Warteschlange, nächster Platz in {secs}s",
+    ),
+    (">> Nothing to retry", ">> Nichts zu wiederholen"),
+    (">> Nothing to undo", ">> Nichts rückgängig zu machen"),
+    (
+        ">> This session has no transcript",
+        ">> Diese Sitzung hat kein Protokoll",
+    ),
+    (
+        ">> Attached to your next question",
+        ">> An deine nächste Frage angehängt",
+    ),
+    (
+        ">> Nothing to continue: the last query didn't run out of turns",
+        ">> Nichts fortzusetzen: der letzten Frage sind die Runden nicht ausgegangen",
+    ),
+    (
+        ">> Reached the limit of {max_turns} turns, summarizing the findings so far",
+        ">> Limit von {max_turns} Runden erreicht, fasse die bisherigen Ergebnisse zusammen",
+    ),
+    (
+        ">> Voice input: press Enter on an empty line to speak",
+        ">> Spracheingabe: Enter in einer leeren Zeile drücken, um zu sprechen",
+    ),
+    (
+        ">> Listening, press Enter to stop",
+        ">> Höre zu, Enter zum Beenden",
+    ),
+    (">> Heard nothing", ">> Nichts gehört"),
+    (
+        "Unknown command: /{command}",
+        "Unbekannter Befehl: /{command}",
+    ),
+    (">> Unknown tool: {name}", ">> Unbekanntes Werkzeug: {name}"),
+    (">> Attached {preview}", ">> {preview} angehängt"),
+    (
+        ">> Attach {preview}? [Y/n] ",
+        ">> {preview} anhängen? [Y/n] ",
+    ),
+    (
+        ">> Attached {preview} to your next question",
+        ">> {preview} an deine nächste Frage angehängt",
+    ),
+    (
+        ">> Removed the last turn: {input}",
+        ">> Letzte Runde entfernt: {input}",
+    ),
+    (
+        ">> No response to page yet",
+        ">> Noch keine Antwort zum Anzeigen",
+    ),
+    ("No response to copy yet", "Noch keine Antwort zum Kopieren"),
+    (
+        "The last response has no code block {n}",
+        "Die letzte Antwort hat keinen Codeblock {n}",
+    ),
+    (">> Copied to clipboard", ">> In die Zwischenablage kopiert"),
+    (
+        ">> Thinking blocks are shown",
+        ">> Denkblöcke werden angezeigt",
+    ),
+    (
+        ">> Thinking blocks are hidden",
+        ">> Denkblöcke werden ausgeblendet",
+    ),
+    (
+        ">> No templates defined in config",
+        ">> Keine Vorlagen in der Konfiguration definiert",
+    ),
+    (
+        ">> Unknown template: {name}",
+        ">> Unbekannte Vorlage: {name}",
+    ),
+    (
+        ">> {name} is enabled for this session",
+        ">> {name} ist für diese Sitzung aktiviert",
+    ),
+    (
+        ">> {name} is disabled for this session",
+        ">> {name} ist für diese Sitzung deaktiviert",
+    ),
+    (
+        ">> /continue grants {turns} more turns to finish the investigation",
+        ">> /continue gewährt {turns} weitere Runden, um die Untersuchung abzuschließen",
+    ),
+    (">> Error: {error}", ">> Fehler: {error}"),
+    (
+        ">> Not sent to the model; /attach last-shell-output sends it with your next question",
+        ">> Nicht an das Modell gesendet; /attach last-shell-output sendet es mit deiner nächsten Frage",
+    ),
+    (
+        ">> Failed to run {command}: {error}",
+        ">> {command} konnte nicht ausgeführt werden: {error}",
+    ),
+    (
+        ">> No wrong citations found",
+        ">> Keine falschen Belegstellen gefunden",
+    ),
+    (
+        ">> Verification failed: {error}",
+        ">> Prüfung fehlgeschlagen: {error}",
+    ),
+    (">> Plan discarded", ">> Plan verworfen"),
+    (">> Plan stopped", ">> Plan abgebrochen"),
+    (
+        ">> Run this plan? [Y]es, [e]dit, [n]o: ",
+        ">> Diesen Plan ausführen? [Y] ja, [e] bearbeiten, [n] nein: ",
+    ),
+    (
+        ">> Edit each step, clear it to drop it; add steps at the end, empty line to finish",
+        ">> Bearbeite jeden Schritt, leere ihn, um ihn zu streichen; füge am Ende Schritte hinzu, leere Zeile zum Beenden",
+    ),
+    (
+        ">> Allow {tool}({args})? [y]es, [N]o, [a]lways for {tool}:",
+        ">> {tool}({args}) erlauben? [y] ja, [N] nein, [a] immer für {tool}:",
+    ),
+    (
+        ">> This turn is about to send {size} of tool output to the API (~{tokens} tokens{cost}). Send it? [y]es, [t]rim it to {limit}, [N]o:",
+        ">> Diese Runde sendet gleich {size} Werkzeugausgabe an die API (~{tokens} Tokens{cost}). Senden? [y] ja, [t] auf {limit} kürzen, [N] nein:",
+    ),
+    (
+        ">> Error: Reached the limit of {max_turns} turns, and summarizing the findings failed: {error}",
+        ">> Fehler: Limit von {max_turns} Runden erreicht, und das Zusammenfassen der Ergebnisse ist fehlgeschlagen: {error}",
+    ),
+    (
+        ">> Done: {succeeded} succeeded, {failed} failed",
+        ">> Fertig: {succeeded} erfolgreich, {failed} fehlgeschlagen",
+    ),
+    (
+        ">> The time box is over, ending the session",
+        ">> Die Zeit ist um, die Sitzung endet",
+    ),
+    (
+        ">> The time box of {minutes} minutes is over, wrapping up the findings",
+        ">> Die Zeit von {minutes} Minuten ist um, fasse die Ergebnisse zusammen",
+    ),
+    (
+        ">> Error: The time box of {minutes} minutes is over, and wrapping up the findings failed: {error}",
+        ">> Fehler: Die Zeit von {minutes} Minuten ist um, und das Zusammenfassen der Ergebnisse ist fehlgeschlagen: {error}",
+    ),
+];
+
+const FRENCH: &[(&str, &str)] = &[
+    ("Working directory", "Répertoire de travail"),
+    ("Model", "Modèle"),
+    ("Max turns", "Tours maximum"),
+    (
+        ">> Ready! Type your queries (Ctrl+C or Ctrl+D to exit)",
+        ">> Prêt ! Posez vos questions (Ctrl+C ou Ctrl+D pour quitter)",
+    ),
+    (">> Goodbye!", ">> Au revoir !"),
+    ("Processing", "Traitement"),
+    ("Summarizing", "Synthèse"),
+    ("Verifying citations", "Vérification des citations"),
+    ("Transcribing", "Transcription"),
+    (
+        "Rate limited: {waiting} queued, next slot in {secs}s",
+        "Limite de débit : {waiting} en attente, prochain créneau dans {secs}s",
+    ),
+    (">> Nothing to retry", ">> Rien à relancer"),
+    (">> Nothing to undo", ">> Rien à annuler"),
+    (
+        ">> This session has no transcript",
+        ">> Cette session n'a pas de transcription",
+    ),
+    (
+        ">> Attached to your next question",
+        ">> Joint à votre prochaine question",
+    ),
+    (
+        ">> Nothing to continue: the last query didn't run out of turns",
+        ">> Rien à poursuivre : la dernière question n'a pas épuisé ses tours",
+    ),
+    (
+        ">> Reached the limit of {max_turns} turns, summarizing the findings so far",
+        ">> Limite de {max_turns} tours atteinte, synthèse des résultats obtenus",
+    ),
+    (
+        ">> Voice input: press Enter on an empty line to speak",
+        ">> Saisie vocale : appuyez sur Entrée sur une ligne vide pour parler",
+    ),
+    (
+        ">> Listening, press Enter to stop",
+        ">> À l'écoute, appuyez sur Entrée pour arrêter",
+    ),
+    (">> Heard nothing", ">> Rien entendu"),
+    (
+        "Unknown command: /{command}",
+        "Commande inconnue : /{command}",
+    ),
+    (">> Unknown tool: {name}", ">> Outil inconnu : {name}"),
+    (">> Attached {preview}", ">> {preview} joint"),
+    (
+        ">> Attach {preview}? [Y/n] ",
+        ">> Joindre {preview} ? [Y/n] ",
+    ),
+    (
+        ">> Attached {preview} to your next question",
+        ">> {preview} joint à votre prochaine question",
+    ),
+    (
+        ">> Removed the last turn: {input}",
+        ">> Dernier tour supprimé : {input}",
+    ),
+    (
+        ">> No response to page yet",
+        ">> Aucune réponse à afficher pour l'instant",
+    ),
+    (
+        "No response to copy yet",
+        "Aucune réponse à copier pour l'instant",
+    ),
+    (
+        "The last response has no code block {n}",
+        "La dernière réponse n'a pas de bloc de code {n}",
+    ),
+    (">> Copied to clipboard", ">> Copié dans le presse-papiers"),
+    (
+        ">> Thinking blocks are shown",
+        ">> Les blocs de réflexion sont affichés",
+    ),
+    (
+        ">> Thinking blocks are hidden",
+        ">> Les blocs de réflexion sont masqués",
+    ),
+    (
+        ">> No templates defined in config",
+        ">> Aucun modèle défini dans la configuration",
+    ),
+    (">> Unknown template: {name}", ">> Modèle inconnu : {name}"),
+    (
+        ">> {name} is enabled for this session",
+        ">> {name} est activé pour cette session",
+    ),
+    (
+        ">> {name} is disabled for this session",
+        ">> {name} est désactivé pour cette session",
+    ),
+    (
+        ">> /continue grants {turns} more turns to finish the investigation",
+        ">> /continue accorde {turns} tours de plus pour terminer l'enquête",
+    ),
+    (">> Error: {error}", ">> Erreur : {error}"),
+    (
+        ">> Not sent to the model; /attach last-shell-output sends it with your next question",
+        ">> Non envoyé au modèle ; /attach last-shell-output l'envoie avec votre prochaine question",
+    ),
+    (
+        ">> Failed to run {command}: {error}",
+        ">> Impossible d'exécuter {command} : {error}",
+    ),
+    (
+        ">> No wrong citations found",
+        ">> Aucune citation erronée trouvée",
+    ),
+    (
+        ">> Verification failed: {error}",
+        ">> La vérification a échoué : {error}",
+    ),
+    (">> Plan discarded", ">> Plan abandonné"),
+    (">> Plan stopped", ">> Plan interrompu"),
+    (
+        ">> Run this plan? [Y]es, [e]dit, [n]o: ",
+        ">> Exécuter ce plan ? [Y] oui, [e] modifier, [n] non : ",
+    ),
+    (
+        ">> Edit each step, clear it to drop it; add steps at the end, empty line to finish",
+        ">> Modifiez chaque étape, videz-la pour la retirer ; ajoutez des étapes à la fin, ligne vide pour terminer",
+    ),
+    (
+        ">> Allow {tool}({args})? [y]es, [N]o, [a]lways for {tool}:",
+        ">> Autoriser {tool}({args}) ? [y] oui, [N] non, [a] toujours pour {tool} :",
+    ),
+    (
+        ">> This turn is about to send {size} of tool output to the API (~{tokens} tokens{cost}). Send it? [y]es, [t]rim it to {limit}, [N]o:",
+        ">> Ce tour va envoyer {size} de sortie d'outils à l'API (~{tokens} jetons{cost}). L'envoyer ? [y] oui, [t] réduire à {limit}, [N] non :",
+    ),
+    (
+        ">> Error: Reached the limit of {max_turns} turns, and summarizing the findings failed: {error}",
+        ">> Erreur : limite de {max_turns} tours atteinte, et la synthèse des résultats a échoué : {error}",
+    ),
+    (
+        ">> Done: {succeeded} succeeded, {failed} failed",
+        ">> Terminé : {succeeded} réussies, {failed} échouées",
+    ),
+    (
+        ">> The time box is over, ending the session",
+        ">> Le temps imparti est écoulé, fin de la session",
+    ),
+    (
+        ">> The time box of {minutes} minutes is over, wrapping up the findings",
+        ">> Les {minutes} minutes imparties sont écoulées, synthèse des résultats",
+    ),
+    (
+        ">> Error: The time box of {minutes} minutes is over, and wrapping up the findings failed: {error}",
+        ">> Erreur : les {minutes} minutes imparties sont écoulées, et la synthèse des résultats a échoué : {error}",
+    ),
+];
+
+const SPANISH: &[(&str, &str)] = &[
+    ("Working directory", "Directorio de trabajo"),
+    ("Model", "Modelo"),
+    ("Max turns", "Turnos máximos"),
+    (
+        ">> Ready! Type your queries (Ctrl+C or Ctrl+D to exit)",
+        ">> ¡Listo! Escribe tus preguntas (Ctrl+C o Ctrl+D para salir)",
+    ),
+    (">> Goodbye!", ">> ¡Adiós!"),
+    ("Processing", "Procesando"),
+    ("Summarizing", "Resumiendo"),
+    ("Verifying citations", "Verificando citas"),
+    ("Transcribing", "Transcribiendo"),
+    (
+        "Rate limited: {waiting} queued, next slot in {secs}s",
+        "Límite de tasa: {waiting} en cola, siguiente turno en {secs}s",
+    ),
+    (">> Nothing to retry", ">> Nada que reintentar"),
+    (">> Nothing to undo", ">> Nada que deshacer"),
+    (
+        ">> This session has no transcript",
+        ">> Esta sesión no tiene transcripción",
+    ),
+    (
+        ">> Attached to your next question",
+        ">> Adjuntado a tu próxima pregunta",
+    ),
+    (
+        ">> Nothing to continue: the last query didn't run out of turns",
+        ">> Nada que continuar: la última pregunta no agotó sus turnos",
+    ),
+    (
+        ">> Reached the limit of {max_turns} turns, summarizing the findings so far",
+        ">> Se alcanzó el límite de {max_turns} turnos, resumiendo lo encontrado hasta ahora",
+    ),
+    (
+        ">> Voice input: press Enter on an empty line to speak",
+        ">> Entrada de voz: pulsa Enter en una línea vacía para hablar",
+    ),
+    (
+        ">> Listening, press Enter to stop",
+        ">> Escuchando, pulsa Enter para terminar",
+    ),
+    (">> Heard nothing", ">> No se oyó nada"),
+    (
+        "Unknown command: /{command}",
+        "Comando desconocido: /{command}",
+    ),
+    (
+        ">> Unknown tool: {name}",
+        ">> Herramienta desconocida: {name}",
+    ),
+    (">> Attached {preview}", ">> {preview} adjuntado"),
+    (
+        ">> Attach {preview}? [Y/n] ",
+        ">> ¿Adjuntar {preview}? [Y/n] ",
+    ),
+    (
+        ">> Attached {preview} to your next question",
+        ">> {preview} adjuntado a tu próxima pregunta",
+    ),
+    (
+        ">> Removed the last turn: {input}",
+        ">> Se eliminó el último turno: {input}",
+    ),
+    (
+        ">> No response to page yet",
+        ">> Todavía no hay respuesta que mostrar",
+    ),
+    (
+        "No response to copy yet",
+        "Todavía no hay respuesta que copiar",
+    ),
+    (
+        "The last response has no code block {n}",
+        "La última respuesta no tiene bloque de código {n}",
+    ),
+    (">> Copied to clipboard", ">> Copiado al portapapeles"),
+    (
+        ">> Thinking blocks are shown",
+        ">> Los bloques de razonamiento se muestran",
+    ),
+    (
+        ">> Thinking blocks are hidden",
+        ">> Los bloques de razonamiento se ocultan",
+    ),
+    (
+        ">> No templates defined in config",
+        ">> No hay plantillas definidas en la configuración",
+    ),
+    (
+        ">> Unknown template: {name}",
+        ">> Plantilla desconocida: {name}",
+    ),
+    (
+        ">> {name} is enabled for this session",
+        ">> {name} queda activado en esta sesión",
+    ),
+    (
+        ">> {name} is disabled for this session",
+        ">> {name} queda desactivado en esta sesión",
+    ),
+    (
+        ">> /continue grants {turns} more turns to finish the investigation",
+        ">> /continue concede {turns} turnos más para terminar la investigación",
+    ),
+    (">> Error: {error}", ">> Error: {error}"),
+    (
+        ">> Not sent to the model; /attach last-shell-output sends it with your next question",
+        ">> No se envió al modelo; /attach last-shell-output lo envía con tu próxima pregunta",
+    ),
+    (
+        ">> Failed to run {command}: {error}",
+        ">> No se pudo ejecutar {command}: {error}",
+    ),
+    (
+        ">> No wrong citations found",
+        ">> No se encontraron citas erróneas",
+    ),
+    (
+        ">> Verification failed: {error}",
+        ">> La verificación falló: {error}",
+    ),
+    (">> Plan discarded", ">> Plan descartado"),
+    (">> Plan stopped", ">> Plan detenido"),
+    (
+        ">> Run this plan? [Y]es, [e]dit, [n]o: ",
+        ">> ¿Ejecutar este plan? [Y] sí, [e] editar, [n] no: ",
+    ),
+    (
+        ">> Edit each step, clear it to drop it; add steps at the end, empty line to finish",
+        ">> Edita cada paso, vacíalo para quitarlo; añade pasos al final, línea vacía para terminar",
+    ),
+    (
+        ">> Allow {tool}({args})? [y]es, [N]o, [a]lways for {tool}:",
+        ">> ¿Permitir {tool}({args})? [y] sí, [N] no, [a] siempre para {tool}:",
+    ),
+    (
+        ">> This turn is about to send {size} of tool output to the API (~{tokens} tokens{cost}). Send it? [y]es, [t]rim it to {limit}, [N]o:",
+        ">> Este turno va a enviar {size} de salida de herramientas a la API (~{tokens} tokens{cost}). ¿Enviarla? [y] sí, [t] recortarla a {limit}, [N] no:",
+    ),
+    (
+        ">> Error: Reached the limit of {max_turns} turns, and summarizing the findings failed: {error}",
+        ">> Error: se alcanzó el límite de {max_turns} turnos, y resumir lo encontrado falló: {error}",
+    ),
+    (
+        ">> Done: {succeeded} succeeded, {failed} failed",
+        ">> Listo: {succeeded} correctas, {failed} fallidas",
+    ),
+    (
+        ">> The time box is over, ending the session",
+        ">> Se acabó el tiempo, terminando la sesión",
+    ),
+    (
+        ">> The time box of {minutes} minutes is over, wrapping up the findings",
+        ">> Se acabaron los {minutes} minutos, resumiendo lo encontrado",
+    ),
+    (
+        ">> Error: The time box of {minutes} minutes is over, and wrapping up the findings failed: {error}",
+        ">> Error: se acabaron los {minutes} minutos, y resumir lo encontrado falló: {error}",
+    ),
+];
+
+/// A natural language, by code and English name
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Language {
+    pub code: String,
+    pub name: String,
+}
+
+impl Language {
+    /// A language from its code (`de`, `pt-BR`) or English name (`German`);
+    /// other values are taken as the name of the language
+    pub fn parse(value: &str) -> Self {
+        let value = value.trim();
+        let code = value
+            .split(['-', '_'])
+            .next()
+            .unwrap_or_default()
+            .to_lowercase();
+        match KNOWN_LANGUAGES
+            .iter()
+            .find(|(known, name)| *known == code || name.eq_ignore_ascii_case(value))
+        {
+            Some((code, name)) => Self {
+                code: code.to_string(),
+                name: name.to_string(),
+            },
+            None => Self {
+                code,
+                name: value.to_string(),
+            },
+        }
+    }
+
+    fn catalog(&self) -> &'static [(&'static str, &'static str)] {
+        match self.code.as_str() {
+            "de" => GERMAN,
+            "fr" => FRENCH,
+            "es" => SPANISH,
+            _ => &[],
+        }
+    }
+}
+
+/// Answer in `language`, and show horse's messages in it where translated
+pub fn init(language: Option<&str>) {
+    if let Some(language) = language {
+        let _ = LANGUAGE.set(Language::parse(language));
+    }
+}
+
+/// The configured language, unless English
+pub fn language() -> Option<&'static Language> {
+    LANGUAGE.get().filter(|language| language.code != "en")
+}
+
+/// Appended to the preamble so answers are in the configured language
+pub fn answer_instructions() -> Option<String> {
+    language().map(|language| {
+        format!(
+            "\n\n## Language\n\nAnswer in {}, whatever the language of the repository, its \
+             comments and documentation, or the question. Keep code, identifiers, paths and \
+             quoted text as they are.",
+            language.name
+        )
+    })
+}
+
+/// `message` in the configured language, or as is if it has no translation
+pub fn tr(message: &'static str) -> &'static str {
+    language().map_or(message, |language| translate(language, message))
+}
+
+/// `message` in the configured language with its `{name}` placeholders
+/// filled in
+pub fn tr_with(message: &'static str, args: &[(&str, &dyn Display)]) -> String {
+    args.iter()
+        .fold(tr(message).to_string(), |text, (name, value)| {
+            text.replace(&format!("{{{name}}}"), &value.to_string())
+        })
+}
+
+fn translate(language: &Language, message: &'static str) -> &'static str {
+    language
+        .catalog()
+        .iter()
+        .find(|(english, _)| *english == message)
+        .map_or(message, |(_, translated)| translated)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(Language::parse("de").name, "German");
+        assert_eq!(Language::parse("pt-BR").name, "Portuguese");
+        assert_eq!(Language::parse("french").code, "fr");
+        assert_eq!(Language::parse("Esperanto").name, "Esperanto");
+    }
+
+    #[test]
+    fn test_translate() {
+        let german = Language::parse("de");
+        assert_eq!(translate(&german, ">> Goodbye!"), ">> Auf Wiedersehen!");
+        assert_eq!(translate(&german, "untranslated"), "untranslated");
+        assert_eq!(
+            translate(&Language::parse("ja"), ">> Goodbye!"),
+            ">> Goodbye!"
+        );
+    }
+
+    #[test]
+    fn test_catalogs() {
+        // Every catalog translates the same messages, keeping placeholders
+        for catalog in [FRENCH, SPANISH] {
+            assert_eq!(catalog.len(), GERMAN.len());
+            for ((english, _), (other, translated)) in GERMAN.iter().zip(catalog) {
+                assert_eq!(english, other);
+                for placeholder in [
+                    "{max_turns}",
+                    "{command}",
+                    "{name}",
+                    "{waiting}",
+                    "{secs}",
+                    "{preview}",
+                    "{input}",
+                    "{n}",
+                    "{turns}",
+                    "{error}",
+                    "{tool}",
+                    "{args}",
+                    "{size}",
+                    "{tokens}",
+                    "{cost}",
+                    "{limit}",
+                    "{succeeded}",
+                    "{failed}",
+                    "{minutes}",
+                ] {
+                    assert_eq!(
+                        english.contains(placeholder),
+                        translated.contains(placeholder)
+                    );
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,192 @@
+use std::path::Path;
+use std::process::ExitCode;
+use std::time::Instant;
+
+use anyhow::{Context, Result, anyhow};
+use chrono::{Local, NaiveDate, NaiveDateTime, NaiveTime};
+use rig::agent::Agent;
+use rig::completion::Prompt;
+use rig::providers::anthropic;
+use tracing::Instrument;
+
+use crate::agent::hooks::ProgressHook;
+use crate::agent::{TOOL_CONCURRENCY, lifecycle, remote, telemetry};
+use crate::console::usage::UsageLog;
+use crate::console::{citations, colors, markdown};
+
+/// Exit code used when no timeline could be built
+const EXIT_INCIDENT_FAILED: u8 = 1;
+
+/// How `--since` is passed on to `read_log` and `git_history`, which both
+/// take it
+const SINCE_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+/// Parse `--since`: a date and time, a date, or a time of today
+pub fn parse_since(value: &str) -> Result<NaiveDateTime, String> {
+    let value = value.trim();
+    [
+        "%Y-%m-%d %H:%M:%S",
+        "%Y-%m-%dT%H:%M:%S",
+        "%Y-%m-%d %H:%M",
+        "%Y-%m-%dT%H:%M",
+    ]
+    .iter()
+    .find_map(|format| NaiveDateTime::parse_from_str(value, format).ok())
+    .or_else(|| {
+        ["%H:%M:%S", "%H:%M"]
+            .iter()
+            .find_map(|format| NaiveTime::parse_from_str(value, format).ok())
+            .map(|time| Local::now().date_naive().and_time(time))
+    })
+    .or_else(|| {
+        NaiveDate::parse_from_str(value, "%Y-%m-%d")
+            .ok()
+            .and_then(|date| date.and_hms_opt(0, 0, 0))
+    })
+    .ok_or_else(|| {
+        format!("invalid time `{value}`, expected YYYY-MM-DD HH:MM[:SS], YYYY-MM-DD or HH:MM[:SS]")
+    })
+}
+
+/// The investigation asked of the agent, for the log at `log` (relative to
+/// the workspace) from `since` on
+fn request(log: &str, since: NaiveDateTime) -> String {
+    let since = since.format(SINCE_FORMAT);
+    format!(
+        "Investigate an incident and build a timeline of what happened from {since} on.\n\
+         \n\
+         1. Read the log `{log}` with read_log from since `{since}`: first the errors and \
+         warnings (level), then the lines around the earliest anomalies, to find when \
+         things started to go wrong and how it unfolded.\n\
+         2. List the commits since `{since}` with git_history, and show the ones that could \
+         be related; read the code they change with read_file where it helps. A commit is \
+         only deployed some time after it was made, so also look at the commits shortly \
+         before.\n\
+         3. Correlate the log with the history: errors that start right after a change, \
+         code paths named in stack traces or messages, and configuration that changed.\n\
+         \n\
+         Answer with exactly these sections:\n\
+         \n\
+         ## Timeline\n\
+         A Markdown table with the columns Time | Event | Source | Evidence, one row per \
+         event in chronological order. Source is `log` or `commit`. Evidence is a short \
+         quote of the log line, or the commit's short SHA with the code it changed as \
+         path:line. Only list events you have evidence for.\n\
+         \n\
+         ## Likely trigger\n\
+         The event that most likely started the incident and why, with how confident you \
+         are.\n\
+         \n\
+         ## Open questions\n\
+         What the log and history don't tell, and where to look next."
+    )
+}
+
+/// Path of the log file `log` relative to the workspace, which `read_log`
+/// is confined to. Paths of a remote workspace are taken as relative to it.
+fn workspace_path(base_dir: &Path, log: &Path) -> Result<String> {
+    match remote::get() {
+        Some(_) => Ok(log.display().to_string()),
+        None => {
+            let canonical = log
+                .canonicalize()
+                .with_context(|| format!("Failed to find the log {}", log.display()))?;
+            let base_canonical = base_dir
+                .canonicalize()
+                .with_context(|| format!("Failed to resolve {}", base_dir.display()))?;
+            canonical
+                .strip_prefix(&base_canonical)
+                .map(|relative| relative.display().to_string())
+                .map_err(|_| {
+                    anyhow!(
+                        "The log {} is outside the workspace {}; copy it in, or investigate \
+                         from a directory that contains it",
+                        log.display(),
+                        base_dir.display()
+                    )
+                })
+        }
+    }
+}
+
+/// Investigate an incident from the log `log` and the git history since
+/// `since`, and print the timeline of events with its evidence.
+///
+/// Returns a success exit code if a timeline was built.
+pub async fn run(
+    agent: &Agent<anthropic::completion::CompletionModel>,
+    base_dir: &Path,
+    log: &Path,
+    since: NaiveDateTime,
+    usage_log: &UsageLog,
+) -> Result<ExitCode> {
+    let prompt = request(&workspace_path(base_dir, log)?, since);
+
+    let hook = ProgressHook::new();
+    let started = Instant::now();
+    let result = agent
+        .prompt(prompt.as_str())
+        .with_tool_concurrency(TOOL_CONCURRENCY)
+        .with_hook(hook.clone())
+        .into_future()
+        .instrument(telemetry::turn_span())
+        .await;
+    lifecycle::turn_end(&prompt, &result).await;
+    usage_log.record(
+        hook.get_total_usage().into(),
+        hook.take_tool_calls().len(),
+        started.elapsed(),
+    );
+
+    match result {
+        Ok(answer) => {
+            println!(
+                "{}",
+                markdown::format_markdown(&citations::annotate(&answer, base_dir))
+            );
+            Ok(ExitCode::SUCCESS)
+        }
+        Err(e) => {
+            eprintln!("{}", colors::color_error(format!(">> Error: {e:#}")));
+            Ok(ExitCode::from(EXIT_INCIDENT_FAILED))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_since() {
+        let parsed = |value: &str| parse_since(value).unwrap().format(SINCE_FORMAT).to_string();
+        assert_eq!(parsed("2024-05-01 14:32"), "2024-05-01 14:32:00");
+        assert_eq!(parsed("2024-05-01T14:32:10"), "2024-05-01 14:32:10");
+        assert_eq!(parsed("2024-05-01"), "2024-05-01 00:00:00");
+        assert_eq!(
+            parse_since("14:32").unwrap().time(),
+            NaiveTime::from_hms_opt(14, 32, 0).unwrap()
+        );
+        assert!(parse_since("yesterday").is_err());
+    }
+
+    #[test]
+    fn test_request() {
+        let since = parse_since("2024-05-01 14:32").unwrap();
+        let request = request("logs/app.log", since);
+        assert!(request.contains(
+            "Read the log `logs/app.log` with read_log from since `2024-05-01 14:32:00`"
+        ));
+        assert!(request.contains("Time | Event | Source | Evidence"));
+    }
+
+    #[test]
+    fn test_workspace_path() {
+        let base_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+        assert_eq!(
+            workspace_path(base_dir, &base_dir.join("src/main.rs")).unwrap(),
+            "src/main.rs"
+        );
+        assert!(workspace_path(&base_dir.join("src"), &base_dir.join("Cargo.toml")).is_err());
+    }
+}
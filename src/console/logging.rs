@@ -0,0 +1,175 @@
+use std::fmt;
+use std::fs::OpenOptions;
+use std::future::Future;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::Instant;
+
+use anyhow::{Context, Result};
+use tracing::{Event, Level, Subscriber};
+use tracing_subscriber::filter::{LevelFilter, Targets};
+use tracing_subscriber::fmt::format::Writer;
+use tracing_subscriber::fmt::{FmtContext, FormatEvent, FormatFields};
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{Layer, Registry};
+
+use crate::console::colors;
+
+#[cfg(feature = "otel")]
+mod otel;
+
+/// Target of the tool call messages, which `-q` hides
+pub const TOOLS_TARGET: &str = "horse::tools";
+
+/// Await a startup phase, logging how long it took at `-v`
+pub async fn timed<T, F>(phase: &str, future: F) -> T
+where
+    F: Future<Output = T>,
+{
+    let started = Instant::now();
+    let output = future.await;
+    tracing::debug!(">> {phase}: {} ms", started.elapsed().as_millis());
+    output
+}
+
+/// Console log level for the `-q` and `-v`/`-vv` flags
+pub fn console_level(quiet: bool, verbose: u8) -> LevelFilter {
+    match (quiet, verbose) {
+        (true, _) => LevelFilter::WARN,
+        (false, 0) => LevelFilter::INFO,
+        (false, 1) => LevelFilter::DEBUG,
+        (false, _) => LevelFilter::TRACE,
+    }
+}
+
+/// Prints bare messages to stderr, styled like the rest of horse's output
+struct ConsoleFormat;
+
+impl<S, N> FormatEvent<S, N> for ConsoleFormat
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    N: for<'a> FormatFields<'a> + 'static,
+{
+    fn format_event(
+        &self,
+        ctx: &FmtContext<'_, S, N>,
+        mut writer: Writer<'_>,
+        event: &Event<'_>,
+    ) -> fmt::Result {
+        let mut message = String::new();
+        ctx.format_fields(Writer::new(&mut message), event)?;
+
+        let metadata = event.metadata();
+        let tool_call = metadata.target() == TOOLS_TARGET && *metadata.level() == Level::INFO;
+        let styled = match *metadata.level() {
+            Level::ERROR => colors::color_error(message),
+            Level::WARN => colors::color_warning(message),
+            Level::INFO if tool_call => colors::color_debug(message),
+            Level::INFO => colors::color_status(message),
+            Level::DEBUG | Level::TRACE => colors::color_dim(message),
+        };
+
+        // Separate each tool call from the output before it
+        if tool_call {
+            writeln!(writer)?;
+        }
+        writeln!(writer, "{styled}")
+    }
+}
+
+/// Keeps exporters alive until the end of `main`, flushing them when dropped
+#[must_use]
+pub struct LogGuard {
+    #[cfg(feature = "otel")]
+    provider: Option<opentelemetry_sdk::trace::SdkTracerProvider>,
+}
+
+impl Drop for LogGuard {
+    fn drop(&mut self) {
+        #[cfg(feature = "otel")]
+        if let Some(provider) = self.provider.take()
+            && let Err(e) = provider.shutdown()
+        {
+            eprintln!("Failed to flush OpenTelemetry spans: {e}");
+        }
+    }
+}
+
+/// Install the global subscriber.
+///
+/// Console output goes to stderr at `console` level (`LevelFilter::OFF`
+/// disables it, e.g. while the TUI owns the terminal); the optional log file
+/// receives everything regardless of the console level. Built with the `otel`
+/// feature, spans are also exported over OTLP when
+/// `OTEL_EXPORTER_OTLP_ENDPOINT` is set.
+pub fn init(console: LevelFilter, log_file: Option<&Path>) -> Result<LogGuard> {
+    // Library internals are only interesting at the most verbose level
+    let dependencies = match console {
+        LevelFilter::TRACE => LevelFilter::DEBUG,
+        level => level.min(LevelFilter::WARN),
+    };
+    let console_layer = tracing_subscriber::fmt::layer()
+        .event_format(ConsoleFormat)
+        .with_writer(std::io::stderr)
+        .with_filter(
+            Targets::new()
+                .with_target("horse", console)
+                .with_default(dependencies),
+        );
+
+    let file_layer = match log_file {
+        Some(path) => {
+            let file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .with_context(|| format!("Failed to open log file {}", path.display()))?;
+            let layer = tracing_subscriber::fmt::layer()
+                .with_ansi(false)
+                .with_writer(Mutex::new(file))
+                .with_filter(
+                    Targets::new()
+                        .with_target("horse", LevelFilter::TRACE)
+                        .with_target("rig", LevelFilter::TRACE)
+                        .with_default(LevelFilter::INFO),
+                );
+            Some(layer)
+        }
+        None => None,
+    };
+
+    let registry = Registry::default().with(console_layer).with(file_layer);
+
+    #[cfg(feature = "otel")]
+    let (registry, guard) = {
+        let (layer, provider) = otel::layer()?.unzip();
+        (registry.with(layer), LogGuard { provider })
+    };
+    #[cfg(not(feature = "otel"))]
+    let guard = LogGuard {};
+
+    registry
+        .try_init()
+        .context("Failed to initialize logging")?;
+    Ok(guard)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_console_level() {
+        assert_eq!(console_level(true, 2), LevelFilter::WARN);
+        assert_eq!(console_level(false, 0), LevelFilter::INFO);
+        assert_eq!(console_level(false, 1), LevelFilter::DEBUG);
+        assert_eq!(console_level(false, 3), LevelFilter::TRACE);
+    }
+
+    #[tokio::test]
+    async fn test_timed() {
+        assert_eq!(timed("Preamble", async { 42 }).await, 42);
+    }
+}
@@ -0,0 +1,41 @@
+use anyhow::{Context, Result};
+use opentelemetry::trace::TracerProvider;
+use opentelemetry_otlp::SpanExporter;
+use opentelemetry_sdk::Resource;
+use opentelemetry_sdk::trace::SdkTracerProvider;
+use tracing::Subscriber;
+use tracing_subscriber::Layer;
+use tracing_subscriber::filter::{LevelFilter, Targets};
+use tracing_subscriber::registry::LookupSpan;
+
+/// Standard variable pointing at the OTLP collector; exporting is off without it
+const ENDPOINT_VAR: &str = "OTEL_EXPORTER_OTLP_ENDPOINT";
+
+/// Layer exporting horse's and rig's spans over OTLP/gRPC, together with the
+/// provider that must be shut down to flush the last batch
+pub fn layer<S>() -> Result<Option<(impl Layer<S>, SdkTracerProvider)>>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    std::env::var_os(ENDPOINT_VAR)
+        .map(|_| {
+            let exporter = SpanExporter::builder()
+                .with_tonic()
+                .build()
+                .context("Failed to create the OTLP exporter")?;
+            let provider = SdkTracerProvider::builder()
+                .with_batch_exporter(exporter)
+                .with_resource(Resource::builder().with_service_name("horse").build())
+                .build();
+            let layer = tracing_opentelemetry::layer()
+                .with_tracer(provider.tracer("horse"))
+                .with_filter(
+                    Targets::new()
+                        .with_target("horse", LevelFilter::INFO)
+                        .with_target("rig", LevelFilter::INFO)
+                        .with_default(LevelFilter::OFF),
+                );
+            Ok((layer, provider))
+        })
+        .transpose()
+}
@@ -1,8 +1,108 @@
-use termimad::MadSkin;
+use anyhow::Result;
+use termimad::FmtText;
 
-/// Renders markdown text to the terminal using termimad's default theme.
-pub fn render_markdown(text: &str) {
-    let skin = MadSkin::default();
-    let rendered = skin.term_text(text);
-    println!("\n{}\n", rendered);
+use crate::console::{colors, hyperlinks, pager, terminal};
+
+/// Narrowest width we render to, even if the terminal reports less
+const MIN_WIDTH: usize = 20;
+
+/// Width available for rendering, queried on every call so that terminal
+/// resizes (SIGWINCH) are picked up by the next render.
+///
+/// One column is left free so lines that exactly fill the terminal don't
+/// trigger the terminal's own auto-wrap.
+pub fn render_width() -> usize {
+    let (width, _) = termimad::terminal_size();
+    width_for(width)
+}
+
+/// Width rendered to on a terminal `columns` wide
+fn width_for(columns: u16) -> usize {
+    usize::from(columns).saturating_sub(1).max(MIN_WIDTH)
+}
+
+/// Formats markdown text for the terminal using the active theme.
+///
+/// Paragraphs are wrapped and tables are shrunk to fit the current terminal width,
+/// and `path:line` references become hyperlinks. When stdout is not a
+/// terminal, the markdown is left as is for whatever reads it.
+pub fn format_markdown(text: &str) -> String {
+    format_for(text, terminal::stdout_is_terminal())
+}
+
+/// `text` rendered for a terminal, or as is for anything else
+fn format_for(text: &str, terminal: bool) -> String {
+    if terminal {
+        let skin = colors::skin();
+        let rendered = FmtText::from(&skin, text, Some(render_width())).to_string();
+        hyperlinks::linkify(&rendered)
+    } else {
+        text.to_string()
+    }
+}
+
+/// Extract the contents of fenced code blocks (```` ``` ```` or `~~~`), in order.
+pub fn extract_code_blocks(text: &str) -> Vec<String> {
+    let mut blocks = Vec::new();
+    let mut current: Option<(&str, Vec<&str>)> = None;
+
+    for line in text.lines() {
+        let trimmed = line.trim_start();
+        current = match current {
+            Some((fence, lines)) if trimmed.starts_with(fence) => {
+                blocks.push(lines.join("\n"));
+                None
+            }
+            Some((fence, mut lines)) => {
+                lines.push(line);
+                Some((fence, lines))
+            }
+            None if trimmed.starts_with("```") => Some(("```", Vec::new())),
+            None if trimmed.starts_with("~~~") => Some(("~~~", Vec::new())),
+            None => None,
+        };
+    }
+
+    blocks
+}
+
+/// Renders markdown text to the terminal, piping it through the pager
+/// when it doesn't fit on the screen.
+pub fn render_markdown(text: &str) -> Result<()> {
+    let rendered = format_markdown(text);
+
+    if pager::exceeds_screen(&rendered) {
+        pager::page(&rendered)
+    } else {
+        println!("\n{rendered}\n");
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_width_for() {
+        assert_eq!(width_for(120), 119);
+        assert_eq!(width_for(21), MIN_WIDTH);
+        assert_eq!(width_for(0), MIN_WIDTH);
+    }
+
+    #[test]
+    fn test_format_for() {
+        let text = "## Retries\n\n**Backoff** lives in `src/retry.rs:42`.\n";
+        assert_eq!(format_for(text, false), text);
+        assert!(!format_for(text, true).contains("**"));
+    }
+
+    #[test]
+    fn test_extract_code_blocks() {
+        let text = "Run this:\n\n```bash\ncargo build\ncargo test\n```\n\nthen\n~~~\nls\n~~~\n```\nunterminated";
+        assert_eq!(
+            extract_code_blocks(text),
+            vec!["cargo build\ncargo test".to_string(), "ls".to_string()]
+        );
+    }
 }
@@ -0,0 +1,126 @@
+use std::io::{self, Write};
+use std::process::{Command, Stdio};
+use std::time::Duration;
+
+use serde::Deserialize;
+
+/// How to notify the user that a long turn has finished
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum NotifyMethod {
+    /// Ring the terminal bell
+    #[default]
+    Bell,
+    /// Show a desktop notification, falling back to the bell if that fails
+    Desktop,
+    Off,
+}
+
+/// `[notify]` config section
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NotifyConfig {
+    /// Only turns taking at least this many seconds trigger a notification
+    #[serde(alias = "after-secs")]
+    pub after_secs: u64,
+    pub method: NotifyMethod,
+}
+
+impl Default for NotifyConfig {
+    fn default() -> Self {
+        Self {
+            after_secs: 30,
+            method: NotifyMethod::default(),
+        }
+    }
+}
+
+impl NotifyConfig {
+    /// Notify the user that the answer is ready if the turn took long enough
+    /// and the terminal doesn't have focus.
+    ///
+    /// `focused` tells whether the terminal has focus; frontends that can't
+    /// track it pass `None`, and focus is then asked of the desktop. When
+    /// that can't tell either, nobody is notified.
+    pub fn turn_finished(&self, elapsed: Duration, focused: Option<bool>) {
+        let long = self.method != NotifyMethod::Off && elapsed.as_secs() >= self.after_secs;
+        if long && self.due(elapsed, focused.or_else(terminal_focused)) {
+            match self.method {
+                NotifyMethod::Bell => ring_bell(),
+                NotifyMethod::Desktop => {
+                    let shown = notify_rust::Notification::new()
+                        .summary("horse")
+                        .body("Your answer is ready")
+                        .show();
+                    if shown.is_err() {
+                        ring_bell();
+                    }
+                }
+                NotifyMethod::Off => {}
+            }
+        }
+    }
+
+    /// Whether a turn that took `elapsed` is notified, with the terminal's
+    /// focus if known
+    fn due(&self, elapsed: Duration, focused: Option<bool>) -> bool {
+        elapsed.as_secs() >= self.after_secs && focused == Some(false)
+    }
+}
+
+/// Whether the terminal horse runs in has focus, as far as the desktop can
+/// tell: the active X11 window against `$WINDOWID`, or on macOS the
+/// frontmost app against the one horse was started from
+fn terminal_focused() -> Option<bool> {
+    if cfg!(target_os = "macos") {
+        let app = std::env::var("__CFBundleIdentifier").ok()?;
+        let front = output(
+            "osascript",
+            &[
+                "-e",
+                "id of application (path to frontmost application as text)",
+            ],
+        )?;
+        Some(front == app)
+    } else {
+        let window = std::env::var("WINDOWID").ok()?;
+        let active = output("xdotool", &["getactivewindow"])?;
+        Some(active == window)
+    }
+}
+
+/// Trimmed output of a successful command
+fn output(program: &str, args: &[&str]) -> Option<String> {
+    Command::new(program)
+        .args(args)
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+fn ring_bell() {
+    let mut stdout = io::stdout();
+    let _ = stdout.write_all(b"\x07");
+    let _ = stdout.flush();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_due() {
+        let notify = NotifyConfig::default();
+        let long = Duration::from_secs(45);
+        assert!(notify.due(long, Some(false)));
+        assert!(!notify.due(long, Some(true)));
+        assert!(!notify.due(long, None));
+        assert!(!notify.due(Duration::from_secs(10), Some(false)));
+
+        let notify: NotifyConfig = toml::from_str("after-secs = 5").unwrap();
+        assert!(notify.due(Duration::from_secs(10), Some(false)));
+    }
+}
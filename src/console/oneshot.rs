@@ -0,0 +1,390 @@
+use std::io::{self, IsTerminal, Read};
+use std::path::Path;
+use std::process::ExitCode;
+use std::time::Instant;
+
+use anyhow::{Context, Result};
+use rig::agent::Agent;
+use rig::completion::{Prompt, PromptError, Usage};
+use rig::providers::anthropic;
+
+use tokio::sync::mpsc;
+use tracing::Instrument;
+
+use crate::agent::hooks::{EventHook, ProgressHook};
+use crate::agent::{TOOL_CONCURRENCY, lifecycle, salvage, telemetry, timebox, verify::Verifier};
+use crate::console::answers::{AnswerCache, CachedAnswer};
+use crate::console::output::{self, OutputEvent, OutputFormat};
+use crate::console::quickfix::Quickfix;
+use crate::console::usage::UsageLog;
+use crate::console::{citations, colors, i18n, markdown};
+
+/// Exit code used when the agent fails to produce an answer
+const EXIT_AGENT_ERROR: u8 = 1;
+
+/// Maximum amount of piped stdin attached to the prompt
+const MAX_STDIN_BYTES: usize = 100 * 1024; // 100KB
+
+/// Query value meaning "read the question itself from stdin"
+pub const STDIN_QUERY: &str = "-";
+
+/// Content piped into horse through stdin
+#[derive(Debug, PartialEq, Eq)]
+pub struct PipedInput {
+    pub content: String,
+    pub truncated: bool,
+}
+
+impl PipedInput {
+    /// Decode piped bytes, keeping at most `MAX_STDIN_BYTES` of valid UTF-8
+    fn from_bytes(bytes: &[u8]) -> Self {
+        let truncated = bytes.len() > MAX_STDIN_BYTES;
+        let content = String::from_utf8_lossy(&bytes[..bytes.len().min(MAX_STDIN_BYTES)]);
+        // Drop a multi-byte character cut in half by the limit
+        let content = if truncated {
+            content.trim_end_matches(char::REPLACEMENT_CHARACTER)
+        } else {
+            &content
+        };
+
+        Self {
+            content: content.to_string(),
+            truncated,
+        }
+    }
+}
+
+/// Read stdin if it is piped rather than a terminal
+pub fn read_piped_stdin() -> Result<Option<PipedInput>> {
+    let stdin = io::stdin();
+    if stdin.is_terminal() {
+        Ok(None)
+    } else {
+        let mut bytes = Vec::new();
+        stdin
+            .lock()
+            .take(MAX_STDIN_BYTES as u64 + 1)
+            .read_to_end(&mut bytes)
+            .context("Failed to read stdin")?;
+        Ok((!bytes.is_empty()).then(|| PipedInput::from_bytes(&bytes)))
+    }
+}
+
+/// Combine the query with piped content.
+///
+/// With a query of `-`, the piped content is the question itself;
+/// otherwise it is attached as context.
+pub fn build_prompt(query: &str, piped: Option<&PipedInput>) -> String {
+    match piped {
+        None => query.to_string(),
+        Some(piped) => {
+            let notice = if piped.truncated {
+                format!(
+                    "\n[truncated - stdin exceeds {}KB limit]",
+                    MAX_STDIN_BYTES / 1024
+                )
+            } else {
+                String::new()
+            };
+
+            if query == STDIN_QUERY {
+                format!("{}{notice}", piped.content)
+            } else {
+                format!(
+                    "{query}\n\nThe following content was piped to stdin:\n\n<stdin>\n{}{notice}\n</stdin>",
+                    piped.content
+                )
+            }
+        }
+    }
+}
+
+/// Run a single query non-interactively and print the answer to stdout.
+///
+/// With `answers`, an answer cached for the same question and workspace
+/// content is printed instead, and a new complete answer is cached.
+///
+/// Returns a success exit code if a complete answer was produced.
+#[allow(clippy::too_many_arguments)]
+pub async fn run(
+    agent: &Agent<anthropic::completion::CompletionModel>,
+    base_dir: &Path,
+    prompt: &str,
+    format: OutputFormat,
+    quickfix: &Quickfix,
+    verifier: Option<&Verifier>,
+    answers: Option<&AnswerCache>,
+    usage_log: &UsageLog,
+) -> ExitCode {
+    match answers.and_then(|answers| answers.get(prompt)) {
+        Some(cached) => print_cached(&cached, base_dir, prompt, format, quickfix),
+        None => {
+            let started = Instant::now();
+            let (code, usage, tool_calls, answer) = match format {
+                OutputFormat::Text => run_text(agent, base_dir, prompt, None, verifier).await,
+                OutputFormat::Quickfix => {
+                    run_text(agent, base_dir, prompt, Some(quickfix), verifier).await
+                }
+                OutputFormat::Json => run_json(agent, prompt, false).await,
+                OutputFormat::StreamJson => run_json(agent, prompt, true).await,
+            };
+            usage_log.record(usage.into(), tool_calls, started.elapsed());
+            if let (Some(answers), Some(answer)) = (answers, answer)
+                && let Err(e) = answers.insert(prompt, &answer)
+            {
+                tracing::warn!("[!] Warning: Could not cache the answer: {e:#}");
+            }
+            code
+        }
+    }
+}
+
+/// Print a cached answer as a fresh one would be, marked as cached
+fn print_cached(
+    cached: &CachedAnswer,
+    base_dir: &Path,
+    prompt: &str,
+    format: OutputFormat,
+    quickfix: &Quickfix,
+) -> ExitCode {
+    match format {
+        OutputFormat::Text | OutputFormat::Quickfix => {
+            eprintln!("{}", colors::color_debug(cached.marker()));
+            println!(
+                "{}",
+                markdown::format_markdown(&citations::annotate(&cached.answer, base_dir))
+            );
+        }
+        OutputFormat::Json | OutputFormat::StreamJson => OutputEvent::Result {
+            prompt,
+            answer: Some(&cached.answer),
+            error: None,
+            tool_calls: &[],
+            usage: Usage::default().into(),
+            cached_at: Some(cached.created),
+        }
+        .print(),
+    }
+    match (format, quickfix.emit(&cached.answer)) {
+        (OutputFormat::Quickfix, Err(e)) => {
+            eprintln!(
+                "{}",
+                colors::color_error(i18n::tr_with(
+                    ">> Error: {error}",
+                    &[("error", &format!("{e:#}"))]
+                ))
+            );
+            ExitCode::from(EXIT_AGENT_ERROR)
+        }
+        _ => ExitCode::SUCCESS,
+    }
+}
+
+async fn run_text(
+    agent: &Agent<anthropic::completion::CompletionModel>,
+    base_dir: &Path,
+    prompt: &str,
+    quickfix: Option<&Quickfix>,
+    verifier: Option<&Verifier>,
+) -> (ExitCode, Usage, usize, Option<String>) {
+    let hook = ProgressHook::new();
+
+    let result = agent
+        .prompt(prompt)
+        .with_tool_concurrency(TOOL_CONCURRENCY)
+        .with_hook(hook.clone())
+        .into_future()
+        .instrument(telemetry::turn_span())
+        .await;
+    lifecycle::turn_end(prompt, &result).await;
+    let complete = result.as_ref().ok().cloned();
+    let code = match result {
+        Ok(answer) => {
+            println!(
+                "{}",
+                markdown::format_markdown(&citations::annotate(&answer, base_dir))
+            );
+            if let Some(verifier) = verifier {
+                match verifier.check(&answer).await {
+                    Ok(Some(corrections)) => println!(
+                        "{}",
+                        markdown::format_markdown(&format!("## Corrections\n\n{corrections}"))
+                    ),
+                    Ok(None) => {}
+                    Err(e) => tracing::warn!("[!] Warning: Verification failed: {e:#}"),
+                }
+            }
+            match quickfix.map(|q| q.emit(&answer)) {
+                Some(Err(e)) => {
+                    eprintln!(
+                        "{}",
+                        colors::color_error(i18n::tr_with(
+                            ">> Error: {error}",
+                            &[("error", &format!("{e:#}"))]
+                        ))
+                    );
+                    ExitCode::from(EXIT_AGENT_ERROR)
+                }
+                _ => ExitCode::SUCCESS,
+            }
+        }
+        // Print what was found rather than nothing, but still fail: the
+        // answer is incomplete
+        Err(PromptError::MaxTurnsError {
+            max_turns,
+            chat_history,
+            ..
+        }) => {
+            let mut history = *chat_history;
+            match salvage::partial_answer(agent, &mut history, &hook).await {
+                Ok(answer) => println!(
+                    "{}",
+                    markdown::format_markdown(&citations::annotate(
+                        &salvage::mark(&answer, max_turns),
+                        base_dir
+                    ))
+                ),
+                Err(e) => eprintln!(
+                    "{}",
+                    colors::color_error(i18n::tr_with(
+                        ">> Error: Reached the limit of {max_turns} turns, and summarizing the findings failed: {error}",
+                        &[("max_turns", &max_turns), ("error", &format!("{e:#}"))]
+                    ))
+                ),
+            }
+            ExitCode::from(EXIT_AGENT_ERROR)
+        }
+        // Likewise when the time box ran out
+        Err(PromptError::PromptCancelled {
+            chat_history,
+            reason,
+        }) if timebox::cancelled_by(&reason) => {
+            let mut history = *chat_history;
+            match salvage::wrap_up(agent, &mut history, &hook).await {
+                Ok(answer) => println!(
+                    "{}",
+                    markdown::format_markdown(&citations::annotate(
+                        &salvage::mark_wrap_up(&answer, timebox::minutes().unwrap_or_default()),
+                        base_dir
+                    ))
+                ),
+                Err(e) => eprintln!(
+                    "{}",
+                    colors::color_error(i18n::tr_with(
+                        ">> Error: The time box of {minutes} minutes is over, and wrapping up the findings failed: {error}",
+                        &[
+                            ("minutes", &timebox::minutes().unwrap_or_default()),
+                            ("error", &format!("{e:#}"))
+                        ]
+                    ))
+                ),
+            }
+            ExitCode::from(EXIT_AGENT_ERROR)
+        }
+        Err(e) => {
+            eprintln!(
+                "{}",
+                colors::color_error(i18n::tr_with(
+                    ">> Error: {error}",
+                    &[("error", &format!("{e:#}"))]
+                ))
+            );
+            ExitCode::from(EXIT_AGENT_ERROR)
+        }
+    };
+    (
+        code,
+        hook.get_total_usage(),
+        hook.take_tool_calls().len(),
+        complete,
+    )
+}
+
+/// Run the query emitting JSON, either a single result object or a stream of
+/// events terminated by the result object.
+async fn run_json(
+    agent: &Agent<anthropic::completion::CompletionModel>,
+    prompt: &str,
+    stream: bool,
+) -> (ExitCode, Usage, usize, Option<String>) {
+    if stream {
+        OutputEvent::Prompt { text: prompt }.print();
+    }
+
+    let (tx, rx) = mpsc::unbounded_channel();
+    let collector = output::collect_events(rx, stream);
+    let result = agent
+        .prompt(prompt)
+        .with_tool_concurrency(TOOL_CONCURRENCY)
+        .with_hook(EventHook::new(tx))
+        .into_future()
+        .instrument(telemetry::turn_span())
+        .await;
+    lifecycle::turn_end(prompt, &result).await;
+    let collected = collector.await.unwrap_or_default();
+
+    let (answer, error) = match &result {
+        Ok(answer) => (Some(answer.as_str()), None),
+        Err(e) => (None, Some(format!("{e:#}"))),
+    };
+    OutputEvent::Result {
+        prompt,
+        answer,
+        error,
+        tool_calls: &collected.tool_calls,
+        usage: collected.usage.into(),
+        cached_at: None,
+    }
+    .print();
+
+    let code = if result.is_ok() {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::from(EXIT_AGENT_ERROR)
+    };
+    (
+        code,
+        collected.usage,
+        collected.tool_calls.len(),
+        result.ok(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_piped_input_truncation() {
+        let piped = PipedInput::from_bytes(b"small");
+        assert_eq!(
+            piped,
+            PipedInput {
+                content: "small".to_string(),
+                truncated: false
+            }
+        );
+
+        // A two-byte character straddling the limit is dropped entirely
+        let mut bytes = vec![b'a'; MAX_STDIN_BYTES - 1];
+        bytes.extend("é and more".as_bytes());
+        let piped = PipedInput::from_bytes(&bytes);
+        assert!(piped.truncated);
+        assert_eq!(piped.content.len(), MAX_STDIN_BYTES - 1);
+    }
+
+    #[test]
+    fn test_build_prompt() {
+        let piped = PipedInput {
+            content: "diff --git a/x b/x".to_string(),
+            truncated: false,
+        };
+
+        assert_eq!(build_prompt("question", None), "question");
+        assert_eq!(build_prompt("-", Some(&piped)), "diff --git a/x b/x");
+        assert_eq!(
+            build_prompt("summarize", Some(&piped)),
+            "summarize\n\nThe following content was piped to stdin:\n\n<stdin>\ndiff --git a/x b/x\n</stdin>"
+        );
+    }
+}
@@ -0,0 +1,309 @@
+use std::io::{self, Write};
+
+use chrono::{DateTime, Utc};
+use clap::ValueEnum;
+use rig::completion::Usage;
+use serde::Serialize;
+use tokio::sync::mpsc::UnboundedReceiver;
+use tokio::task::JoinHandle;
+
+use crate::agent::hooks::{AgentEvent, ProgressHook};
+use crate::agent::outcome::ErrorKind;
+
+/// Maximum length of tool results included in JSON output
+const MAX_RESULT_CHARS: usize = 2000;
+
+/// Output format of one-shot mode
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    /// Rendered markdown with progress output
+    #[default]
+    Text,
+    /// A single JSON object with the answer, tool calls and usage
+    Json,
+    /// One JSON object per line for every event as it happens, then the result
+    StreamJson,
+    /// Rendered markdown, plus the referenced locations as `file:line:col: message`
+    /// lines for the editor's quickfix list
+    Quickfix,
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct UsageRecord {
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    pub cached_input_tokens: u64,
+}
+
+impl UsageRecord {
+    /// Tokens added to a running total since the `earlier` snapshot of it
+    pub fn since(self, earlier: Self) -> Self {
+        Self {
+            input_tokens: self.input_tokens.saturating_sub(earlier.input_tokens),
+            output_tokens: self.output_tokens.saturating_sub(earlier.output_tokens),
+            cached_input_tokens: self
+                .cached_input_tokens
+                .saturating_sub(earlier.cached_input_tokens),
+        }
+    }
+}
+
+impl From<Usage> for UsageRecord {
+    fn from(usage: Usage) -> Self {
+        Self {
+            input_tokens: usage.input_tokens,
+            output_tokens: usage.output_tokens,
+            cached_input_tokens: usage.cached_input_tokens,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ToolCallRecord {
+    pub name: String,
+    pub args: serde_json::Value,
+    pub result: Option<String>,
+    pub is_error: bool,
+    /// How the call failed, if it did
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error_kind: Option<ErrorKind>,
+}
+
+/// A structured output event, serialized as one JSON object per line
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum OutputEvent<'a> {
+    Prompt {
+        text: &'a str,
+    },
+    ToolCall {
+        name: &'a str,
+        args: &'a serde_json::Value,
+    },
+    ToolResult {
+        name: &'a str,
+        result: &'a str,
+        is_error: bool,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        error_kind: Option<ErrorKind>,
+    },
+    Usage(UsageRecord),
+    /// Final outcome of the query
+    Result {
+        prompt: &'a str,
+        answer: Option<&'a str>,
+        error: Option<String>,
+        tool_calls: &'a [ToolCallRecord],
+        usage: UsageRecord,
+        /// When a cached answer was first given
+        #[serde(skip_serializing_if = "Option::is_none")]
+        cached_at: Option<DateTime<Utc>>,
+    },
+}
+
+impl OutputEvent<'_> {
+    /// Write the event as a single JSON line
+    pub fn write_to(&self, writer: &mut impl Write) -> io::Result<()> {
+        serde_json::to_writer(&mut *writer, self)?;
+        writeln!(writer)
+    }
+
+    pub fn print(&self) {
+        if let Err(e) = self.write_to(&mut io::stdout()) {
+            eprintln!("Failed to write output event: {e}");
+        }
+    }
+}
+
+/// Tool call arguments as JSON, or as a JSON string if they aren't valid JSON
+pub fn parse_tool_args(args: String) -> serde_json::Value {
+    serde_json::from_str(&args).unwrap_or(serde_json::Value::String(args))
+}
+
+/// Decoded tool result, truncated for structured output
+pub fn tool_result_text(result: &str) -> String {
+    ProgressHook::truncate_display(&ProgressHook::decode_tool_output(result), MAX_RESULT_CHARS)
+}
+
+/// Tool calls and total usage collected from agent events
+#[derive(Debug, Default)]
+pub struct Collected {
+    pub tool_calls: Vec<ToolCallRecord>,
+    pub usage: Usage,
+    /// Completion requests made, one per turn of the agent loop
+    pub completions: usize,
+}
+
+/// Consume agent events until the hook is dropped, printing them as they
+/// arrive when `stream` is set.
+pub fn collect_events(
+    mut events: UnboundedReceiver<AgentEvent>,
+    stream: bool,
+) -> JoinHandle<Collected> {
+    tokio::spawn(async move {
+        let mut collected = Collected::default();
+
+        while let Some(event) = events.recv().await {
+            match event {
+                AgentEvent::ToolCall { name, args } => {
+                    let args = parse_tool_args(args);
+                    if stream {
+                        OutputEvent::ToolCall {
+                            name: &name,
+                            args: &args,
+                        }
+                        .print();
+                    }
+                    collected.tool_calls.push(ToolCallRecord {
+                        name,
+                        args,
+                        result: None,
+                        is_error: false,
+                        error_kind: None,
+                    });
+                }
+                AgentEvent::ToolResult {
+                    name,
+                    result,
+                    error,
+                } => {
+                    let result = tool_result_text(&result);
+                    let error_kind = error.map(|e| e.kind);
+                    if stream {
+                        OutputEvent::ToolResult {
+                            name: &name,
+                            result: &result,
+                            is_error: error_kind.is_some(),
+                            error_kind,
+                        }
+                        .print();
+                    }
+                    if let Some(call) = collected
+                        .tool_calls
+                        .iter_mut()
+                        .find(|c| c.name == name && c.result.is_none())
+                    {
+                        call.result = Some(result);
+                        call.is_error = error_kind.is_some();
+                        call.error_kind = error_kind;
+                    }
+                }
+                AgentEvent::Usage(usage) => {
+                    if stream {
+                        OutputEvent::Usage(usage.into()).print();
+                    }
+                    collected.usage += usage;
+                    collected.completions += 1;
+                }
+            }
+        }
+
+        collected
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::sync::mpsc;
+
+    use crate::agent::outcome::ToolFailure;
+
+    fn line(event: &OutputEvent) -> String {
+        let mut out = Vec::new();
+        event.write_to(&mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_write_to() {
+        assert_eq!(
+            line(&OutputEvent::Prompt { text: "where?" }),
+            "{\"type\":\"prompt\",\"text\":\"where?\"}\n"
+        );
+        assert_eq!(
+            line(&OutputEvent::ToolResult {
+                name: "bash",
+                result: "ok",
+                is_error: false,
+                error_kind: None,
+            }),
+            "{\"type\":\"tool_result\",\"name\":\"bash\",\"result\":\"ok\",\"is_error\":false}\n"
+        );
+        let result = line(&OutputEvent::Result {
+            prompt: "where?",
+            answer: None,
+            error: Some("failed".to_string()),
+            tool_calls: &[],
+            usage: UsageRecord::default(),
+            cached_at: None,
+        });
+        assert!(result.starts_with("{\"type\":\"result\",\"prompt\":\"where?\",\"answer\":null"));
+        assert!(!result.contains("cached_at"));
+    }
+
+    #[test]
+    fn test_parse_tool_args() {
+        assert_eq!(
+            parse_tool_args("{\"path\":\"src\"}".to_string()),
+            serde_json::json!({ "path": "src" })
+        );
+        assert_eq!(
+            parse_tool_args("not json".to_string()),
+            serde_json::Value::String("not json".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_collect_events() {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let collector = collect_events(rx, false);
+        for event in [
+            AgentEvent::ToolCall {
+                name: "read_file".to_string(),
+                args: "{\"path\":\"a\"}".to_string(),
+            },
+            AgentEvent::ToolCall {
+                name: "read_file".to_string(),
+                args: "{\"path\":\"b\"}".to_string(),
+            },
+            AgentEvent::ToolResult {
+                name: "read_file".to_string(),
+                result: "missing".to_string(),
+                error: Some(ToolFailure {
+                    tool: "read_file".to_string(),
+                    kind: ErrorKind::NotFound,
+                    message: "missing".to_string(),
+                    hint: None,
+                }),
+            },
+            AgentEvent::Usage(Usage {
+                input_tokens: 10,
+                ..Usage::default()
+            }),
+            AgentEvent::Usage(Usage {
+                input_tokens: 5,
+                ..Usage::default()
+            }),
+        ] {
+            tx.send(event).unwrap();
+        }
+        drop(tx);
+
+        let collected = collector.await.unwrap();
+        assert_eq!(collected.completions, 2);
+        assert_eq!(collected.usage.input_tokens, 15);
+        // Results go to the oldest call of the tool still waiting for one
+        assert_eq!(collected.tool_calls.len(), 2);
+        assert_eq!(
+            collected.tool_calls[0].args,
+            serde_json::json!({ "path": "a" })
+        );
+        assert!(collected.tool_calls[0].is_error);
+        assert_eq!(
+            collected.tool_calls[0].error_kind,
+            Some(ErrorKind::NotFound)
+        );
+        assert!(collected.tool_calls[1].result.is_none());
+    }
+}
@@ -0,0 +1,150 @@
+use std::path::Path;
+use std::process::ExitCode;
+use std::time::Instant;
+
+use anyhow::{Context, Result};
+use rig::agent::Agent;
+use rig::completion::{Message, Prompt};
+use rig::providers::anthropic;
+use tracing::Instrument;
+
+use crate::agent::hooks::ProgressHook;
+use crate::agent::{TOOL_CONCURRENCY, lifecycle, telemetry};
+use crate::console::usage::UsageLog;
+use crate::console::{colors, plan};
+
+/// Exit code used when a section couldn't be written
+const EXIT_SECTION_FAILED: u8 = 1;
+
+/// Sections of the onboarding document, each a heading and what to find
+/// out for it. They are explored in order, in one conversation, so later
+/// sections build on what earlier ones found.
+const SECTIONS: &[(&str, &str)] = &[
+    (
+        "Purpose",
+        "What the project is for, who uses it and how: a library, a service, a CLI, ...",
+    ),
+    (
+        "Build system",
+        "How the project is built, run and configured: build tool, main dependencies, \
+         commands a newcomer needs, environment variables and config files",
+    ),
+    (
+        "Entry points",
+        "Where execution starts: binaries, main functions, servers and their routes, \
+         CLI commands, exported library APIs",
+    ),
+    (
+        "Architecture",
+        "The major components, how they depend on each other, and how a typical request \
+         or command flows through them",
+    ),
+    (
+        "Key modules",
+        "The modules a newcomer should read first, with what each is responsible for \
+         and its central types and functions",
+    ),
+    (
+        "Tests",
+        "How the tests are laid out and run: unit and integration tests, fixtures, \
+         helpers, CI configuration",
+    ),
+];
+
+/// Ask the agent to explore one section of the document
+fn section_request(heading: &str, focus: &str) -> String {
+    format!(
+        "You are writing an onboarding document for a developer new to this codebase. \
+         Explore the workspace with your tools and write its `{heading}` section: {focus}. \
+         Reply with the section's markdown body only, without its heading, and cite the \
+         code as path:line. Be concrete and brief; don't repeat earlier sections."
+    )
+}
+
+/// Explore the workspace section by section and write an onboarding
+/// document to `out`, or stdout.
+///
+/// Returns a success exit code if every section was written.
+pub async fn run(
+    agent: &Agent<anthropic::completion::CompletionModel>,
+    base_dir: &Path,
+    out: Option<&Path>,
+    usage_log: &UsageLog,
+) -> Result<ExitCode> {
+    let steps: Vec<String> = SECTIONS
+        .iter()
+        .map(|(heading, _)| heading.to_string())
+        .collect();
+    let mut history: Vec<Message> = Vec::new();
+    let mut sections = Vec::new();
+    let mut failed = 0;
+
+    for (index, (heading, focus)) in SECTIONS.iter().enumerate() {
+        eprintln!("{}\n", plan::render(&steps, Some(index)));
+        let request = section_request(heading, focus);
+        let hook = ProgressHook::new();
+        let started = Instant::now();
+        let result = agent
+            .prompt(request.as_str())
+            .with_history(&mut history)
+            .with_tool_concurrency(TOOL_CONCURRENCY)
+            .with_hook(hook.clone())
+            .into_future()
+            .instrument(telemetry::turn_span())
+            .await;
+        lifecycle::turn_end(&request, &result).await;
+        usage_log.record(
+            hook.get_total_usage().into(),
+            hook.take_tool_calls().len(),
+            started.elapsed(),
+        );
+
+        let body = match result {
+            Ok(body) => body.trim().to_string(),
+            Err(e) => {
+                eprintln!(
+                    "{}",
+                    colors::color_error(format!(">> Error: {heading}: {e:#}"))
+                );
+                failed += 1;
+                "_This section could not be written._".to_string()
+            }
+        };
+        sections.push(format!("## {heading}\n\n{body}"));
+    }
+
+    let project = base_dir.file_name().map_or_else(
+        || "the project".to_string(),
+        |name| name.to_string_lossy().into_owned(),
+    );
+    let document = format!("# Onboarding: {project}\n\n{}\n", sections.join("\n\n"));
+    match out {
+        Some(path) => {
+            std::fs::write(path, &document)
+                .with_context(|| format!("Failed to write {}", path.display()))?;
+            eprintln!(
+                "{}",
+                colors::color_success(format!(">> Wrote {}", path.display()))
+            );
+        }
+        None => print!("{document}"),
+    }
+
+    Ok(if failed == 0 {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::from(EXIT_SECTION_FAILED)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_section_request() {
+        let request = section_request(SECTIONS[2].0, SECTIONS[2].1);
+        assert!(request.contains("`Entry points` section: Where execution starts"));
+        assert!(request.contains("path:line"));
+    }
+}
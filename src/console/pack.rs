@@ -0,0 +1,333 @@
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::path::Path;
+use std::sync::LazyLock;
+
+use anyhow::{Context, Result};
+use regex::Regex;
+
+use crate::agent::preflight::BYTES_PER_TOKEN;
+use crate::agent::scope;
+use crate::agent::tools::{DEFINITION_KEYWORDS, ModuleGraph};
+use crate::console::colors;
+
+/// Files larger than this are never packed, not even as an outline
+const MAX_FILE_BYTES: usize = 512 * 1024;
+
+/// Share of the budget one file may take in full; larger ones are packed
+/// as an outline of their definitions
+const MAX_FILE_SHARE: usize = 4;
+
+/// Paths listed in the file tree, at most
+const MAX_TREE_LINES: usize = 300;
+
+/// Read before anything else: what the project is and how to work on it
+const DOCS: &[&str] = &["README.md", "README", "AGENTS.md", "CONTRIBUTING.md"];
+
+/// Manifests, telling the stack and dependencies
+const MANIFESTS: &[&str] = &[
+    "Cargo.toml",
+    "package.json",
+    "go.mod",
+    "pyproject.toml",
+    "setup.py",
+    "pom.xml",
+    "build.gradle",
+    "Makefile",
+];
+
+/// File names where execution starts
+const ENTRY_POINTS: &[&str] = &[
+    "main.rs",
+    "lib.rs",
+    "main.go",
+    "main.py",
+    "__main__.py",
+    "app.py",
+    "index.ts",
+    "index.js",
+    "main.ts",
+];
+
+/// Generated or binary files, which tell nothing a reader needs
+const SKIPPED: &[&str] = &[
+    "Cargo.lock",
+    "package-lock.json",
+    "yarn.lock",
+    "pnpm-lock.yaml",
+    "go.sum",
+    "poetry.lock",
+    ".min.js",
+    ".map",
+    ".png",
+    ".jpg",
+    ".jpeg",
+    ".gif",
+    ".ico",
+    ".svg",
+    ".pdf",
+    ".zip",
+    ".gz",
+    ".wasm",
+    ".woff",
+    ".woff2",
+    ".ttf",
+];
+
+/// A line that starts a definition, kept in outlines
+static DEFINITION: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(&format!(
+        r"^\s*(pub(\([\w:]+\))?\s+|export\s+(default\s+)?)?(async\s+)?({DEFINITION_KEYWORDS})\b"
+    ))
+    .expect("Invalid definition regex")
+});
+
+/// How a file went into the pack
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Packed {
+    Full,
+    Outline,
+}
+
+/// Parse a token budget like `20k`, `1.5k`, `1m` or `8000`
+pub fn parse_budget(value: &str) -> Result<usize, String> {
+    let lower = value.trim().to_lowercase();
+    let (amount, multiplier) = match (lower.strip_suffix('k'), lower.strip_suffix('m')) {
+        (Some(amount), _) => (amount, 1_000.0),
+        (_, Some(amount)) => (amount, 1_000_000.0),
+        _ => (lower.as_str(), 1.0),
+    };
+    amount
+        .parse::<f64>()
+        .ok()
+        .filter(|amount| *amount > 0.0)
+        .map(|amount| (amount * multiplier) as usize)
+        .ok_or_else(|| format!("expected a number of tokens like 20k or 8000, got `{value}`"))
+}
+
+/// Assemble the files that best show what the workspace is within `budget`
+/// tokens: docs and manifests first, then the modules most imported by
+/// others, whole or as an outline of their definitions. Written to `out`,
+/// or stdout.
+pub async fn run(base_dir: &Path, budget: usize, out: Option<&Path>) -> Result<()> {
+    let output = scope::search_command(base_dir, "rg", &["--files"])
+        .output()
+        .await
+        .context("Failed to list files with rg")?;
+    let mut files: Vec<String> = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(|file| file.trim_start_matches("./").to_string())
+        .collect();
+    files.sort_unstable();
+    let fan_in = ModuleGraph::new(base_dir.to_path_buf())
+        .fan_in(".")
+        .await
+        .unwrap_or_else(|e| {
+            tracing::warn!("[!] Warning: Could not build the module graph: {e}");
+            BTreeMap::new()
+        });
+
+    let project = base_dir.file_name().map_or_else(
+        || "the project".to_string(),
+        |name| name.to_string_lossy().into_owned(),
+    );
+    let (document, packed) = pack(&project, &files, &fan_in, budget, |file| {
+        std::fs::read_to_string(base_dir.join(file)).ok()
+    });
+    let outlines = packed
+        .iter()
+        .filter(|(_, how)| *how == Packed::Outline)
+        .count();
+    let summary = format!(
+        ">> Packed {} files ({outlines} as outlines), ~{}k of {}k tokens",
+        packed.len(),
+        document.len() / BYTES_PER_TOKEN / 1000,
+        budget / 1000
+    );
+    match out {
+        Some(path) => {
+            std::fs::write(path, &document)
+                .with_context(|| format!("Failed to write {}", path.display()))?;
+            eprintln!(
+                "{}",
+                colors::color_success(format!("{summary} into {}", path.display()))
+            );
+        }
+        None => {
+            print!("{document}");
+            eprintln!("{}", colors::color_success(summary));
+        }
+    }
+    Ok(())
+}
+
+/// The pack of `files` ranked by `score`, within `budget` tokens, and how
+/// each packed file went in
+fn pack(
+    project: &str,
+    files: &[String],
+    fan_in: &BTreeMap<String, usize>,
+    budget: usize,
+    read: impl Fn(&str) -> Option<String>,
+) -> (String, Vec<(String, Packed)>) {
+    let budget_bytes = budget * BYTES_PER_TOKEN;
+    let mut document = format!("# Context: {project}\n\n## Files\n\n```\n");
+    for file in files.iter().take(MAX_TREE_LINES) {
+        let _ = writeln!(document, "{file}");
+    }
+    if files.len() > MAX_TREE_LINES {
+        let _ = writeln!(document, "... {} more", files.len() - MAX_TREE_LINES);
+    }
+    document.push_str("```\n");
+
+    let mut ranked: Vec<(i64, &String)> = files
+        .iter()
+        .filter_map(|file| score(file, fan_in.get(file).copied().unwrap_or(0)).map(|s| (s, file)))
+        .collect();
+    ranked.sort_by(|(a, file_a), (b, file_b)| b.cmp(a).then(file_a.cmp(file_b)));
+
+    let mut packed = Vec::new();
+    let texts = ranked.into_iter().filter_map(|(_, file)| {
+        read(file)
+            .filter(|text| text.len() <= MAX_FILE_BYTES)
+            .map(|text| (file, text))
+    });
+    for (file, text) in texts {
+        let left = budget_bytes.saturating_sub(document.len());
+        let language = extension(file);
+        let section = |body: &str, note: &str| {
+            let fence = fence(body);
+            format!(
+                "\n## {file}{note}\n\n{fence}{language}\n{}\n{fence}\n",
+                body.trim_end()
+            )
+        };
+        let full = section(&text, "");
+        if full.len() <= left && full.len() <= budget_bytes / MAX_FILE_SHARE {
+            document.push_str(&full);
+            packed.push((file.clone(), Packed::Full));
+        } else {
+            let outline = outline(file, &text);
+            let section = section(&outline, " (outline)");
+            if !outline.is_empty() && section.len() <= left {
+                document.push_str(&section);
+                packed.push((file.clone(), Packed::Outline));
+            }
+        }
+    }
+    (document, packed)
+}
+
+/// How representative a file is, higher first; `None` for files not worth
+/// packing. `fan_in` is the number of modules importing it.
+fn score(file: &str, fan_in: usize) -> Option<i64> {
+    let name = file.rsplit('/').next().unwrap_or(file);
+    let depth = file.matches('/').count() as i64;
+    let is_test = file
+        .split('/')
+        .any(|part| part == "tests" || part == "test")
+        || name.contains("_test.")
+        || name.starts_with("test_")
+        || name.contains(".test.")
+        || name.contains(".spec.");
+    let fan_in = fan_in as i64 * 10;
+    if SKIPPED
+        .iter()
+        .any(|skipped| name == *skipped || name.ends_with(skipped))
+    {
+        None
+    } else if DOCS.contains(&name) && depth == 0 {
+        Some(1000)
+    } else if MANIFESTS.contains(&name) {
+        Some(900 - depth * 10)
+    } else if ENTRY_POINTS.contains(&name) && !is_test {
+        Some(500 + fan_in - depth * 5)
+    } else if is_test {
+        Some(fan_in - depth * 5)
+    } else {
+        Some(100 + fan_in - depth * 5)
+    }
+}
+
+/// The lines of `file` that start a definition, or its headings for
+/// markdown
+fn outline(file: &str, text: &str) -> String {
+    let markdown = extension(file) == "md";
+    text.lines()
+        .filter(|line| {
+            if markdown {
+                line.starts_with('#')
+            } else {
+                DEFINITION.is_match(line)
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn extension(file: &str) -> &str {
+    file.rsplit_once('.').map_or("", |(_, extension)| extension)
+}
+
+/// A code fence longer than any run of backticks in `body`, so fenced
+/// blocks in markdown files don't end it early
+fn fence(body: &str) -> String {
+    let longest = body.split(|c| c != '`').map(str::len).max().unwrap_or(0);
+    "`".repeat(longest.max(2) + 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_budget() {
+        assert_eq!(parse_budget("20k"), Ok(20_000));
+        assert_eq!(parse_budget("1.5K"), Ok(1_500));
+        assert_eq!(parse_budget("8000"), Ok(8_000));
+        assert!(parse_budget("lots").is_err());
+        assert!(parse_budget("0").is_err());
+    }
+
+    #[test]
+    fn test_score() {
+        assert!(score("README.md", 0) > score("Cargo.toml", 0));
+        assert!(score("Cargo.toml", 0) > score("src/main.rs", 0));
+        assert!(score("src/main.rs", 0) > score("src/agent.rs", 12));
+        assert!(score("src/agent.rs", 12) > score("src/util.rs", 0));
+        assert!(score("src/util.rs", 0) > score("tests/cli.rs", 0));
+        assert_eq!(score("Cargo.lock", 0), None);
+    }
+
+    #[test]
+    fn test_pack() {
+        let files: Vec<String> = ["README.md", "src/big.rs", "src/small.rs"]
+            .map(String::from)
+            .to_vec();
+        let big = format!("pub fn central() {{}}\n{}", "// filler\n".repeat(2000));
+        let read = |file: &str| match file {
+            "README.md" => Some("# horse".to_string()),
+            "src/big.rs" => Some(big.clone()),
+            _ => Some("fn helper() {}".to_string()),
+        };
+        let fan_in = BTreeMap::from([("src/big.rs".to_string(), 3)]);
+
+        let (document, packed) = pack("horse", &files, &fan_in, 1000, read);
+        assert_eq!(
+            packed,
+            [
+                ("README.md".to_string(), Packed::Full),
+                ("src/big.rs".to_string(), Packed::Outline),
+                ("src/small.rs".to_string(), Packed::Full),
+            ]
+        );
+        assert!(document.contains("## src/big.rs (outline)\n\n```rs\npub fn central() {}\n```"));
+        assert!(document.len() <= 1000 * BYTES_PER_TOKEN);
+    }
+
+    #[test]
+    fn test_fence() {
+        assert_eq!(fence("fn main() {}"), "```");
+        assert_eq!(fence("# Build\n\n```sh\ncargo build\n```"), "````");
+    }
+}
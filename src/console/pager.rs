@@ -0,0 +1,83 @@
+use std::io::{self, Write};
+use std::process::{Command, Stdio};
+
+use anyhow::{Context, Result};
+
+use crate::console::terminal;
+
+/// Pager used when `$PAGER` is not set. `-R` keeps ANSI colors intact.
+const DEFAULT_PAGER: &str = "less -R";
+
+/// Returns true if the text does not fit on the screen and stdout is a terminal.
+pub fn exceeds_screen(text: &str) -> bool {
+    let (_, height) = termimad::terminal_size();
+    terminal::stdout_is_terminal() && exceeds(text, height)
+}
+
+/// Whether the text is too long for a screen `height` lines high, leaving
+/// room for the prompt
+fn exceeds(text: &str, height: u16) -> bool {
+    text.lines().count() >= usize::from(height)
+}
+
+/// The pager command `$PAGER` names, or the default one when it is unset
+/// or blank
+fn pager_command(pager: Option<String>) -> String {
+    pager
+        .filter(|p| !p.trim().is_empty())
+        .unwrap_or_else(|| DEFAULT_PAGER.to_string())
+}
+
+/// Pipes already rendered text through `$PAGER` (or `less -R`).
+///
+/// Falls back to printing directly to stdout if the pager can't be spawned.
+pub fn page(text: &str) -> Result<()> {
+    let pager = pager_command(std::env::var("PAGER").ok());
+    let mut parts = pager.split_whitespace();
+
+    match parts.next().map(|program| {
+        Command::new(program)
+            .args(parts)
+            .stdin(Stdio::piped())
+            .spawn()
+    }) {
+        Some(Ok(mut child)) => {
+            // Stdin is dropped right after writing so the pager sees EOF
+            let written = child
+                .stdin
+                .take()
+                .map_or(Ok(()), |mut stdin| stdin.write_all(text.as_bytes()));
+            child.wait().context("Failed to wait for pager")?;
+
+            // The user may quit the pager before reading everything, which closes the pipe
+            match written {
+                Err(e) if e.kind() != io::ErrorKind::BrokenPipe => {
+                    Err(e).context("Failed to write to pager")
+                }
+                _ => Ok(()),
+            }
+        }
+        _ => {
+            println!("{text}");
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_exceeds() {
+        assert!(!exceeds("one\ntwo", 3));
+        assert!(exceeds("one\ntwo\nthree", 3));
+    }
+
+    #[test]
+    fn test_pager_command() {
+        assert_eq!(pager_command(None), "less -R");
+        assert_eq!(pager_command(Some("  ".to_string())), "less -R");
+        assert_eq!(pager_command(Some("bat -p".to_string())), "bat -p");
+    }
+}
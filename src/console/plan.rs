@@ -0,0 +1,93 @@
+use crate::console::colors;
+
+/// Ask the agent for an investigation plan instead of an answer
+pub fn plan_request(query: &str) -> String {
+    format!(
+        "Before investigating, write a short numbered plan (at most 8 steps, one line \
+        each, formatted as `1. ...`) of how you will investigate the question below. \
+        Don't call any tools and don't answer yet; the plan is reviewed first.\n\n\
+        Question: {query}"
+    )
+}
+
+/// Ask the agent to carry out one step of the approved plan
+pub fn step_request(steps: &[String], index: usize) -> String {
+    format!(
+        "Carry out step {} of the approved plan: {}\n\
+        Report briefly what you found; later steps build on it.",
+        index + 1,
+        steps[index]
+    )
+}
+
+/// Ask the agent for the final answer once every step is done
+pub fn answer_request(query: &str) -> String {
+    format!(
+        "All steps of the plan are done. Using your findings, answer the original \
+        question: {query}"
+    )
+}
+
+/// Extract the steps of a numbered list such as `1. Find the router` or
+/// `2) Read main.rs`, ignoring any text around it
+pub fn parse_steps(plan: &str) -> Vec<String> {
+    plan.lines()
+        .filter_map(|line| {
+            let line = line.trim();
+            let rest = line.trim_start_matches(|c: char| c.is_ascii_digit());
+            let numbered = rest.len() < line.len();
+            let step = rest
+                .strip_prefix(". ")
+                .or_else(|| rest.strip_prefix(") "))
+                .map(str::trim)
+                .filter(|step| numbered && !step.is_empty());
+            step.map(ToString::to_string)
+        })
+        .collect()
+}
+
+/// Render the plan with the progress of its execution: steps before
+/// `current` are done, `current` is in progress
+pub fn render(steps: &[String], current: Option<usize>) -> String {
+    steps
+        .iter()
+        .enumerate()
+        .map(|(i, step)| {
+            let line = format!("{}. {step}", i + 1);
+            match current {
+                Some(current) if i < current => {
+                    format!(
+                        "{} {}",
+                        colors::color_success("[x]"),
+                        colors::color_dim(line)
+                    )
+                }
+                Some(current) if i == current => {
+                    format!("{} {}", colors::color_status("[>]"), line)
+                }
+                _ => format!("[ ] {line}"),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_steps() {
+        let plan = "Here is my plan:\n\n1. Find the HTTP router\n2) Read `src/auth.rs`\n\
+            3.\n  4. Check the tests  \nStep 5 is optional\n";
+        assert_eq!(
+            parse_steps(plan),
+            [
+                "Find the HTTP router",
+                "Read `src/auth.rs`",
+                "Check the tests"
+            ]
+        );
+        assert!(parse_steps("No plan needed, the answer is 42.").is_empty());
+    }
+}
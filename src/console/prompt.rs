@@ -0,0 +1,290 @@
+use std::path::{Path, PathBuf};
+
+use rig::completion::Usage;
+
+use crate::agent::models;
+use crate::console::colors;
+
+/// Format a number with k suffix for values >= 1000
+pub fn format_token_count(count: u64) -> String {
+    if count < 1000 {
+        count.to_string()
+    } else {
+        let k_value = count as f64 / 1000.0;
+        format!("{k_value:.1}k")
+    }
+}
+
+/// Estimated cost of the session so far, e.g. `$0.43`, or `-` for models
+/// without known pricing
+fn format_cost(model: &str, usage: Usage) -> String {
+    models::estimate_cost(
+        model,
+        usage.input_tokens,
+        usage.output_tokens,
+        usage.cached_input_tokens,
+    )
+    .map_or_else(|| "-".to_string(), |cost| format!("${cost:.2}"))
+}
+
+/// Read the current branch (or short commit for a detached HEAD) of the git
+/// repository containing `dir`, if any.
+fn git_branch(dir: &Path) -> Option<String> {
+    let dot_git = dir
+        .ancestors()
+        .map(|d| d.join(".git"))
+        .find(|p| p.exists())?;
+
+    // In worktrees and submodules `.git` is a file pointing at the real git dir
+    let git_dir = if dot_git.is_file() {
+        let content = std::fs::read_to_string(&dot_git).ok()?;
+        let target = PathBuf::from(content.strip_prefix("gitdir:")?.trim());
+        dot_git.parent()?.join(target)
+    } else {
+        dot_git
+    };
+
+    let head = std::fs::read_to_string(git_dir.join("HEAD")).ok()?;
+    let head = head.trim();
+    match head.strip_prefix("ref: refs/heads/") {
+        Some(branch) => Some(branch.to_string()),
+        None => head.get(..7).map(str::to_string),
+    }
+}
+
+/// Everything the prompt line can show
+pub struct PromptValues {
+    pub usage: Usage,
+    /// Usage of the most recent completion request, used for the context meter
+    pub last_usage: Usage,
+    pub model: String,
+    pub branch: Option<String>,
+    pub time: String,
+}
+
+impl PromptValues {
+    /// Percentage of the context window used by the most recent request
+    fn context_percent(&self) -> u64 {
+        let used = self.last_usage.input_tokens + self.last_usage.cached_input_tokens;
+        used * 100 / models::context_window(&self.model).max(1)
+    }
+
+    /// Value of a template placeholder, or `None` if the placeholder is unknown
+    fn placeholder(&self, name: &str) -> Option<String> {
+        match name {
+            "in" => Some(format_token_count(self.usage.input_tokens)),
+            "out" => Some(format_token_count(self.usage.output_tokens)),
+            "cached" => Some(format_token_count(self.usage.cached_input_tokens)),
+            "total" => Some(format_token_count(
+                self.usage.input_tokens + self.usage.output_tokens,
+            )),
+            "model" => Some(self.model.clone()),
+            "branch" => Some(self.branch.clone().unwrap_or_else(|| "-".to_string())),
+            "context" => Some(format!("{}%", self.context_percent())),
+            "cost" => Some(format_cost(&self.model, self.usage)),
+            "time" => Some(self.time.clone()),
+            _ => None,
+        }
+    }
+}
+
+/// Render a prompt template such as `"{model} {branch} [{context}] {in}/{out}> "`.
+///
+/// Placeholder values are highlighted and literal text is dimmed.
+/// Unknown placeholders are kept verbatim.
+pub fn render_template(template: &str, values: &PromptValues) -> String {
+    let mut result = String::new();
+    let mut rest = template;
+
+    while let Some(start) = rest.find('{') {
+        let (literal, tail) = rest.split_at(start);
+        if !literal.is_empty() {
+            result.push_str(&colors::color_dim(literal));
+        }
+
+        match tail.find('}') {
+            Some(end) => {
+                let name = &tail[1..end];
+                match values.placeholder(name) {
+                    Some(value) => result.push_str(&colors::color_prompt_number(value)),
+                    None => result.push_str(&colors::color_dim(&tail[..=end])),
+                }
+                rest = &tail[end + 1..];
+            }
+            None => {
+                result.push_str(&colors::color_dim(tail));
+                rest = "";
+            }
+        }
+    }
+
+    if !rest.is_empty() {
+        result.push_str(&colors::color_dim(rest));
+    }
+    result
+}
+
+/// Generate the default prompt string with token usage information,
+/// followed by the estimated cost if `cost` is given
+fn format_default(usage: Usage, cost: Option<String>) -> String {
+    let input_str = format_token_count(usage.input_tokens);
+    let output_str = format_token_count(usage.output_tokens);
+    let cost = cost
+        .map(|cost| format!(", {}", colors::color_prompt_number(cost)))
+        .unwrap_or_default();
+
+    if usage.cached_input_tokens > 0 {
+        let cached_str = format_token_count(usage.cached_input_tokens);
+        format!(
+            "{} {} ({} {}), {} {}{cost}> ",
+            colors::color_dim("in"),
+            colors::color_prompt_number(&input_str),
+            colors::color_prompt_number(&cached_str),
+            colors::color_dim("cached"),
+            colors::color_dim("out"),
+            colors::color_prompt_number(&output_str)
+        )
+    } else {
+        format!(
+            "{} {}, {} {}{cost}> ",
+            colors::color_dim("in"),
+            colors::color_prompt_number(&input_str),
+            colors::color_dim("out"),
+            colors::color_prompt_number(&output_str)
+        )
+    }
+}
+
+/// The REPL prompt line, optionally customized by a template from config
+#[derive(Debug, Default, Clone)]
+pub struct PromptLine {
+    template: Option<String>,
+    model: String,
+    base_dir: PathBuf,
+    /// Show the estimated session cost in the default prompt
+    show_cost: bool,
+}
+
+impl PromptLine {
+    pub fn new(template: Option<String>, model: &str, base_dir: &Path) -> Self {
+        Self {
+            template,
+            model: model.to_string(),
+            base_dir: base_dir.to_path_buf(),
+            show_cost: false,
+        }
+    }
+
+    /// Append the estimated session cost to the default prompt; templates
+    /// use the `{cost}` placeholder instead
+    pub fn with_cost(mut self, show_cost: bool) -> Self {
+        self.show_cost = show_cost;
+        self
+    }
+
+    /// Generate the prompt string for the current session state
+    pub fn format(&self, usage: Usage, last_usage: Usage) -> String {
+        match &self.template {
+            Some(template) => {
+                let values = PromptValues {
+                    usage,
+                    last_usage,
+                    model: self.model.clone(),
+                    branch: git_branch(&self.base_dir),
+                    time: chrono::Local::now().format("%H:%M").to_string(),
+                };
+                render_template(template, &values)
+            }
+            None => format_default(
+                usage,
+                self.show_cost.then(|| format_cost(&self.model, usage)),
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Remove colors, which depend on whether the tests run in a terminal
+    fn strip_ansi(text: &str) -> String {
+        regex::Regex::new(r"\x1b\[[0-9;]*m")
+            .unwrap()
+            .replace_all(text, "")
+            .into_owned()
+    }
+
+    #[test]
+    fn test_render_template() {
+        let values = PromptValues {
+            usage: Usage {
+                input_tokens: 12_300,
+                output_tokens: 450,
+                total_tokens: 12_750,
+                cached_input_tokens: 0,
+            },
+            last_usage: Usage {
+                input_tokens: 40_000,
+                output_tokens: 100,
+                total_tokens: 40_100,
+                cached_input_tokens: 10_000,
+            },
+            model: "claude-sonnet-4-0".to_string(),
+            branch: Some("main".to_string()),
+            time: "09:41".to_string(),
+        };
+
+        assert_eq!(
+            strip_ansi(&render_template(
+                "{time} {model}@{branch} [{context}] {in}/{out} {cost} {nope}> ",
+                &values
+            )),
+            "09:41 claude-sonnet-4-0@main [25%] 12.3k/450 $0.04 {nope}> "
+        );
+        assert_eq!(
+            strip_ansi(&render_template("unclosed {in", &values)),
+            "unclosed {in"
+        );
+    }
+
+    #[test]
+    fn test_format_cost() {
+        let usage = Usage {
+            input_tokens: 12_300,
+            output_tokens: 450,
+            total_tokens: 12_750,
+            cached_input_tokens: 0,
+        };
+        assert_eq!(format_cost("claude-sonnet-4-0", usage), "$0.04");
+        assert_eq!(format_cost("claude-sonnet-4-0", Usage::default()), "$0.00");
+        assert_eq!(format_cost("unknown-model", usage), "-");
+    }
+
+    #[test]
+    fn test_prompt_line_cost() {
+        let usage = Usage {
+            input_tokens: 12_300,
+            output_tokens: 450,
+            total_tokens: 12_750,
+            cached_input_tokens: 0,
+        };
+        let line = PromptLine::new(None, "claude-sonnet-4-0", Path::new("."));
+        assert_eq!(
+            strip_ansi(&line.format(usage, usage)),
+            "in 12.3k, out 450> "
+        );
+        assert_eq!(
+            strip_ansi(&line.clone().with_cost(true).format(usage, usage)),
+            "in 12.3k, out 450, $0.04> "
+        );
+        assert_eq!(
+            strip_ansi(
+                &PromptLine::new(None, "unknown-model", Path::new("."))
+                    .with_cost(true)
+                    .format(usage, usage)
+            ),
+            "in 12.3k, out 450, -> "
+        );
+    }
+}
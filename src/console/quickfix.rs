@@ -0,0 +1,116 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+
+use crate::console::hyperlinks;
+
+/// Where the quickfix list of a one-shot answer goes
+#[derive(Debug, Clone)]
+pub struct Quickfix {
+    base_dir: PathBuf,
+    file: Option<PathBuf>,
+}
+
+impl Quickfix {
+    /// References are resolved against `base_dir`; the list is written to
+    /// `file`, or to stderr if none is given.
+    pub fn new(base_dir: &Path, file: Option<PathBuf>) -> Self {
+        Self {
+            base_dir: base_dir.to_path_buf(),
+            file,
+        }
+    }
+
+    /// Write the quickfix entries found in `answer`
+    pub fn emit(&self, answer: &str) -> Result<()> {
+        let entries = entries(answer, &self.base_dir);
+        match &self.file {
+            Some(path) => {
+                let content: String = entries.iter().map(|e| format!("{e}\n")).collect();
+                fs::write(path, content)
+                    .with_context(|| format!("Failed to write {}", path.display()))
+            }
+            None => {
+                entries.iter().for_each(|e| eprintln!("{e}"));
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Strip markdown decoration and list markers from an answer line
+fn clean_message(line: &str) -> String {
+    line.replace(['`', '*'], "")
+        .trim()
+        .trim_start_matches(['-', '+', '>', ' '])
+        .trim()
+        .to_string()
+}
+
+/// A reference to a file location in an answer
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Location {
+    /// Path relative to the workspace
+    pub path: String,
+    /// 1-based line
+    pub line: u32,
+    /// 1-based column
+    pub col: u32,
+    /// The answer line the reference appears on
+    pub message: String,
+}
+
+/// References to existing files in `answer`, in order of appearance and without duplicates
+pub fn locations(answer: &str, base_dir: &Path) -> Vec<Location> {
+    let mut locations: Vec<Location> = Vec::new();
+
+    for line in answer.lines() {
+        for caps in hyperlinks::REFERENCE.captures_iter(line) {
+            let path = &caps["path"];
+            if base_dir.join(path).is_file() {
+                let location = Location {
+                    path: path.to_string(),
+                    line: caps["line"].parse().unwrap_or(1),
+                    col: caps
+                        .name("col")
+                        .and_then(|c| c.as_str().parse().ok())
+                        .unwrap_or(1),
+                    message: clean_message(line),
+                };
+                if !locations.contains(&location) {
+                    locations.push(location);
+                }
+            }
+        }
+    }
+
+    locations
+}
+
+/// `file:line:col: message` entries for the references to existing files in `answer`
+pub fn entries(answer: &str, base_dir: &Path) -> Vec<String> {
+    locations(answer, base_dir)
+        .into_iter()
+        .map(|l| format!("{}:{}:{}: {}", l.path, l.line, l.col, l.message))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_entries() {
+        let base_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+        let answer = "The CLI is defined in:\n\n- `src/main.rs:12` - **Args** struct\n- `gone.rs:3`\n\nSee src/lib.rs:1:5.";
+
+        assert_eq!(
+            entries(answer, base_dir),
+            vec![
+                "src/main.rs:12:1: src/main.rs:12 - Args struct".to_string(),
+                "src/lib.rs:1:5: See src/lib.rs:1:5.".to_string(),
+            ]
+        );
+    }
+}
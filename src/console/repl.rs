@@ -1,128 +1,1069 @@
 use rig::{agent::Agent, providers::anthropic};
-use std::io::{self, BufRead, Write};
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+use std::time::Instant;
 
 use anyhow::{Context, Result};
-use rig::completion::{Prompt, Usage};
+use rig::completion::{Message, Prompt, PromptError, Usage};
+use tracing::Instrument;
 
 use crate::{
-    agent::hooks::ProgressHook,
-    console::{colors, markdown, spinner::create_spinner},
+    agent::{
+        TOOL_CONCURRENCY,
+        hooks::{self, ProgressHook},
+        lifecycle, salvage, telemetry, timebox, tools,
+        verify::Verifier,
+    },
+    console::{
+        attach::{self, Attachment, ImageAttachment, Source},
+        citations, colors,
+        commands::{CopyTarget, ReplCommand},
+        editor::{EditingMode, LineEditor},
+        i18n, markdown,
+        notify::NotifyConfig,
+        output::UsageRecord,
+        pager, plan,
+        prompt::PromptLine,
+        sessions::{self, Summarizer},
+        spinner::create_spinner,
+        stats::SessionStats,
+        templates,
+        transcript::Transcript,
+        usage::UsageLog,
+        voice::Voice,
+    },
 };
 
-/// Format a number with k suffix for values >= 1000
-fn format_token_count(count: u64) -> String {
-    if count < 1000 {
-        count.to_string()
-    } else {
-        let k_value = count as f64 / 1000.0;
-        format!("{:.1}k", k_value)
-    }
-}
+/// Tool output of a turn, in KB, above which the user is asked before it is
+/// sent
+pub const DEFAULT_CONFIRM_OUTPUT_KB: usize = 200;
 
-/// Generate the prompt string with token usage information
-fn format_prompt(usage: Usage) -> String {
-    let input_str = format_token_count(usage.input_tokens);
-    let output_str = format_token_count(usage.output_tokens);
-
-    if usage.cached_input_tokens > 0 {
-        let cached_str = format_token_count(usage.cached_input_tokens);
-        format!(
-            "{} {} ({} {}), {} {}> ",
-            colors::color_dim("in"),
-            colors::color_prompt_number(&input_str),
-            colors::color_prompt_number(&cached_str),
-            colors::color_dim("cached"),
-            colors::color_dim("out"),
-            colors::color_prompt_number(&output_str)
-        )
-    } else {
-        format!(
-            "{} {}, {} {}> ",
-            colors::color_dim("in"),
-            colors::color_prompt_number(&input_str),
-            colors::color_dim("out"),
-            colors::color_prompt_number(&output_str)
-        )
-    }
+/// Builds the agent, leaving out the given tools
+type AgentFactory = Box<dyn Fn(&BTreeSet<String>) -> Agent<anthropic::completion::CompletionModel>>;
+
+/// What `/undo` and `/retry` need to take a query's turn back
+#[derive(Debug)]
+struct TurnRecord {
+    /// The query, as entered or expanded from a template
+    input: String,
+    /// Attached to the query, and attached again when it is retried
+    attachments: Vec<Attachment>,
+    /// Images sent with the query, and sent again when it is retried
+    images: Vec<ImageAttachment>,
+    /// Length of the history before the turn
+    history_len: usize,
+    /// Session totals before the turn
+    usage_before: Usage,
+    /// Response shown before the turn, for `/copy` and `/page`
+    last_response: Option<String>,
 }
 
 pub struct Repl {
-    agent: Agent<anthropic::completion::CompletionModel>,
+    build_agent: AgentFactory,
+    /// Built when first prompted, so the prompt appears without waiting for
+    /// the API client, and again after tools are toggled
+    agent: Option<Agent<anthropic::completion::CompletionModel>>,
+    /// Registered tools, and whether each is enabled
+    tools: BTreeMap<String, bool>,
+    last_response: Option<String>,
+    stats: SessionStats,
+    notify: NotifyConfig,
+    /// Kept alive for the whole session, since on some platforms the copied
+    /// content is only available while the clipboard owner exists
+    clipboard: Option<arboard::Clipboard>,
+    prompt_line: PromptLine,
+    editing_mode: EditingMode,
+    templates: BTreeMap<String, String>,
+    usage_log: Option<UsageLog>,
+    /// Have the agent propose a plan for approval before investigating
+    plan_mode: bool,
+    /// Checks the citations of each answer
+    verifier: Option<Verifier>,
+    /// Workspace that cited `path:line` references are checked against
+    citation_base: Option<PathBuf>,
+    /// Directory `!` commands run in, and `/attach` paths are relative to
+    shell_dir: PathBuf,
+    /// Command line and output of the last `!` command
+    last_shell_output: Option<String>,
+    /// Sent along with the next query
+    attachments: Vec<Attachment>,
+    /// Sent along with the next query as image content blocks
+    images: Vec<ImageAttachment>,
+    /// Dictates queries on empty lines
+    voice: Option<Voice>,
+    /// Keeps the full conversation on disk
+    transcript: Option<Transcript>,
+    /// Names and summarizes the session as it goes
+    summarizer: Option<Summarizer>,
+    /// Queries of the session, oldest first
+    turns: Vec<TurnRecord>,
+    /// Turn limit the last query ran out of, for `/continue`
+    exhausted_turns: Option<usize>,
+    /// Turns `/continue` granted the next query
+    continue_turns: Option<usize>,
+    hook: ProgressHook,
 }
 
 impl Repl {
-    pub fn new(agent: Agent<anthropic::completion::CompletionModel>) -> Self {
-        Self { agent }
+    /// A REPL whose agent is built by `build_agent` before the first query
+    pub fn new(
+        build_agent: impl Fn(&BTreeSet<String>) -> Agent<anthropic::completion::CompletionModel>
+        + 'static,
+    ) -> Self {
+        Self {
+            build_agent: Box::new(build_agent),
+            agent: None,
+            tools: BTreeMap::new(),
+            last_response: None,
+            stats: SessionStats::default(),
+            notify: NotifyConfig::default(),
+            clipboard: None,
+            prompt_line: PromptLine::default(),
+            editing_mode: EditingMode::default(),
+            templates: BTreeMap::new(),
+            usage_log: None,
+            plan_mode: false,
+            verifier: None,
+            citation_base: None,
+            shell_dir: PathBuf::from("."),
+            last_shell_output: None,
+            attachments: Vec::new(),
+            images: Vec::new(),
+            voice: None,
+            transcript: None,
+            summarizer: None,
+            turns: Vec::new(),
+            exhausted_turns: None,
+            continue_turns: None,
+            hook: ProgressHook::new(),
+        }
+    }
+
+    /// Set the registered tools that `/tools` lists and toggles, and the
+    /// ones disabled at the start
+    pub fn with_tools(mut self, names: &[String], disabled: BTreeSet<String>) -> Self {
+        self.tools = names
+            .iter()
+            .map(|name| (name.clone(), !disabled.contains(name)))
+            .collect();
+        self
+    }
+
+    /// Set how the user is notified when a long turn completes
+    pub fn with_notify(mut self, notify: NotifyConfig) -> Self {
+        self.notify = notify;
+        self
+    }
+
+    /// Set the prompt line shown before each query
+    pub fn with_prompt_line(mut self, prompt_line: PromptLine) -> Self {
+        self.prompt_line = prompt_line;
+        self
+    }
+
+    /// Set the keybindings of the line editor
+    pub fn with_editing_mode(mut self, editing_mode: EditingMode) -> Self {
+        self.editing_mode = editing_mode;
+        self
+    }
+
+    /// Set the prompt templates available through `/t`
+    pub fn with_templates(mut self, templates: BTreeMap<String, String>) -> Self {
+        self.templates = templates;
+        self
+    }
+
+    /// Append a record of every turn to the usage log
+    pub fn with_usage_log(mut self, usage_log: UsageLog) -> Self {
+        self.usage_log = Some(usage_log);
+        self
+    }
+
+    /// Review an investigation plan before each query is answered
+    pub fn with_plan_mode(mut self, plan_mode: bool) -> Self {
+        self.plan_mode = plan_mode;
+        self
+    }
+
+    /// Check the citations of each answer with a second pass
+    pub fn with_verifier(mut self, verifier: Option<Verifier>) -> Self {
+        self.verifier = verifier;
+        self
+    }
+
+    /// Flag references in answers that don't resolve to a file and line
+    /// under `base_dir`
+    pub fn with_citation_check(mut self, base_dir: &Path) -> Self {
+        self.citation_base = Some(base_dir.to_path_buf());
+        self
+    }
+
+    /// Record and transcribe a query when the user enters an empty line
+    pub fn with_voice(mut self, voice: Option<Voice>) -> Self {
+        self.voice = voice;
+        self
+    }
+
+    /// Run `!` commands in `dir`, and resolve `/attach` paths against it
+    pub fn with_shell_dir(mut self, dir: &Path) -> Self {
+        self.shell_dir = dir.to_path_buf();
+        self
+    }
+
+    /// Write the conversation to a transcript after every turn, keeping only
+    /// the head of large tool outputs of earlier turns in memory
+    pub fn with_transcript(mut self, transcript: Transcript) -> Self {
+        self.transcript = Some(transcript);
+        self
+    }
+
+    /// Give the session a title and summary every few queries, shown by
+    /// `/sessions` and `horse sessions list`; needs a transcript
+    pub fn with_summaries(mut self, summarizer: Option<Summarizer>) -> Self {
+        self.summarizer = summarizer;
+        self
+    }
+
+    /// Ask before a turn sends more than `limit_kb` KB of tool output to
+    /// the API, with an estimate of its cost on `model`
+    pub fn with_output_gate(self, limit_kb: usize, model: &str) -> Self {
+        self.hook.set_output_gate(limit_kb, model);
+        self
+    }
+
+    /// Show extended thinking blocks, dimmed, until toggled with `/thinking`
+    pub fn with_thinking(self, show: bool) -> Self {
+        self.hook.set_show_thinking(show);
+        self
+    }
+
+    /// Expand a prompt template, asking for placeholder values that were not given.
+    ///
+    /// Returns `None` if the user aborts.
+    fn expand_template(
+        &self,
+        template: &str,
+        args: &str,
+        editor: &mut LineEditor,
+    ) -> Result<Option<String>> {
+        let names = templates::placeholders(template);
+        let mut values = templates::assign_args(&names, args);
+
+        let mut aborted = false;
+
+        for name in &names {
+            if !aborted && !values.contains_key(name) {
+                let label = format!("{} ", colors::color_dim(format!("{name}:")));
+                match editor.read_line(&label)? {
+                    Some(value) => {
+                        values.insert(name.clone(), value.trim().to_string());
+                    }
+                    None => aborted = true,
+                }
+            }
+        }
+
+        Ok((!aborted).then(|| templates::fill(template, &values)))
+    }
+
+    /// Read the content `/attach` asked for
+    fn load_attachment(&mut self, source: Source) -> Result<Attachment> {
+        match source {
+            Source::LastShellOutput => self
+                .last_shell_output
+                .clone()
+                .map(|output| Attachment::new("last-shell-output", output))
+                .context("No ! command has run yet"),
+            Source::Clipboard => {
+                let clipboard = match self.clipboard.take() {
+                    Some(clipboard) => clipboard,
+                    None => arboard::Clipboard::new().context("Failed to access the clipboard")?,
+                };
+                let text = self
+                    .clipboard
+                    .insert(clipboard)
+                    .get_text()
+                    .context("The clipboard holds no text")?;
+                Ok(Attachment::new("clipboard", text))
+            }
+            Source::Path(path) => {
+                let content = std::fs::read_to_string(self.shell_dir.join(&path))
+                    .with_context(|| format!("Failed to read {path}"))?;
+                Ok(Attachment::new(path, content))
+            }
+        }
+    }
+
+    /// Attach the images dropped into the terminal as part of `line`
+    fn attach_dropped_images(&mut self, line: &str) {
+        for path in attach::dropped_images(line) {
+            let resolved = self.shell_dir.join(&path);
+            if resolved.is_file() {
+                match ImageAttachment::load(&resolved, path) {
+                    Ok(image) => {
+                        eprintln!(
+                            "{}",
+                            colors::color_dim(i18n::tr_with(
+                                ">> Attached {preview}",
+                                &[("preview", &image.preview())]
+                            ))
+                        );
+                        self.images.push(image);
+                    }
+                    Err(e) => eprintln!("{}", colors::color_error(format!(">> {e:#}"))),
+                }
+            }
+        }
+    }
+
+    /// Copy text to the system clipboard
+    fn copy_to_clipboard(&mut self, text: String) -> Result<()> {
+        let clipboard = match self.clipboard.take() {
+            Some(clipboard) => clipboard,
+            None => arboard::Clipboard::new().context("Failed to access the clipboard")?,
+        };
+        let clipboard = self.clipboard.insert(clipboard);
+        clipboard
+            .set_text(text)
+            .context("Failed to copy to the clipboard")
+    }
+
+    /// Execute a slash command.
+    ///
+    /// Returns a prompt to send to the agent, for commands that produce one.
+    fn handle_command(
+        &mut self,
+        command: ReplCommand,
+        history: &mut Vec<Message>,
+        editor: &mut LineEditor,
+    ) -> Result<Option<String>> {
+        match command {
+            ReplCommand::Retry { edit } => match self.turns.last().map(|turn| turn.input.clone()) {
+                Some(input) => {
+                    let input = if edit {
+                        editor.edit_line(&colors::color_dim("retry> ").to_string(), &input)?
+                    } else {
+                        Some(input)
+                    };
+                    let input = input.filter(|input| !input.trim().is_empty());
+                    // Only drop the last turn once there is a query to replace it,
+                    // whose prompt is built again with the turn's attachments
+                    // and images
+                    if input.is_some()
+                        && let Some(turn) = self.undo(history)
+                    {
+                        self.attachments.splice(0..0, turn.attachments);
+                        self.images.splice(0..0, turn.images);
+                    }
+                    Ok(input)
+                }
+                None => {
+                    eprintln!("{}", colors::color_warning(i18n::tr(">> Nothing to retry")));
+                    Ok(None)
+                }
+            },
+            ReplCommand::Sessions => {
+                match self.transcript.as_ref().and_then(|t| t.path().parent()) {
+                    Some(dir) => println!("{}\n", sessions::render(&sessions::list(dir))),
+                    None => eprintln!(
+                        "{}",
+                        colors::color_warning(i18n::tr(">> This session has no transcript"))
+                    ),
+                }
+                Ok(None)
+            }
+            ReplCommand::Shell(command) => {
+                self.run_shell(&command);
+                Ok(None)
+            }
+            ReplCommand::Attach(source) => {
+                match self.load_attachment(source) {
+                    Ok(attachment) => {
+                        let question = colors::color_status(i18n::tr_with(
+                            ">> Attach {preview}? [Y/n] ",
+                            &[("preview", &attachment.preview())],
+                        ));
+                        let answer = editor.read_line(&question)?.unwrap_or_else(|| "n".into());
+                        if matches!(answer.trim().to_lowercase().as_str(), "" | "y" | "yes") {
+                            self.attachments.push(attachment);
+                            eprintln!(
+                                "{}",
+                                colors::color_dim(i18n::tr(">> Attached to your next question"))
+                            );
+                        }
+                    }
+                    Err(e) => eprintln!("{}", colors::color_error(format!(">> {e:#}"))),
+                }
+                Ok(None)
+            }
+            ReplCommand::Image(path) => {
+                match ImageAttachment::load(&self.shell_dir.join(&path), path) {
+                    Ok(image) => {
+                        eprintln!(
+                            "{}",
+                            colors::color_dim(i18n::tr_with(
+                                ">> Attached {preview} to your next question",
+                                &[("preview", &image.preview())]
+                            ))
+                        );
+                        self.images.push(image);
+                    }
+                    Err(e) => eprintln!("{}", colors::color_error(format!(">> {e:#}"))),
+                }
+                Ok(None)
+            }
+            ReplCommand::Continue(turns) => match self.exhausted_turns {
+                Some(exhausted) => {
+                    self.continue_turns = Some(turns.unwrap_or(exhausted));
+                    Ok(Some(salvage::CONTINUE_REQUEST.to_string()))
+                }
+                None => {
+                    eprintln!(
+                        "{}",
+                        colors::color_warning(i18n::tr(
+                            ">> Nothing to continue: the last query didn't run out of turns"
+                        ))
+                    );
+                    Ok(None)
+                }
+            },
+            ReplCommand::Undo => {
+                match self.undo(history) {
+                    Some(turn) => eprintln!(
+                        "{}",
+                        colors::color_status(i18n::tr_with(
+                            ">> Removed the last turn: {input}",
+                            &[("input", &ProgressHook::truncate_display(&turn.input, 60))]
+                        ))
+                    ),
+                    None => eprintln!("{}", colors::color_warning(i18n::tr(">> Nothing to undo"))),
+                }
+                Ok(None)
+            }
+            ReplCommand::Page => {
+                match &self.last_response {
+                    Some(response) => pager::page(&markdown::format_markdown(response))?,
+                    None => eprintln!(
+                        "{}",
+                        colors::color_warning(i18n::tr(">> No response to page yet"))
+                    ),
+                }
+                Ok(None)
+            }
+            ReplCommand::Copy(target) => {
+                let text = match (&self.last_response, &target) {
+                    (None, _) => Err(i18n::tr("No response to copy yet").to_string()),
+                    (Some(response), CopyTarget::Answer) => Ok(response.clone()),
+                    (Some(response), CopyTarget::CodeBlock(n)) => {
+                        markdown::extract_code_blocks(response)
+                            .into_iter()
+                            .nth(n - 1)
+                            .ok_or_else(|| {
+                                i18n::tr_with(
+                                    "The last response has no code block {n}",
+                                    &[("n", &n)],
+                                )
+                            })
+                    }
+                };
+
+                match text {
+                    Ok(text) => match self.copy_to_clipboard(text) {
+                        Ok(()) => eprintln!(
+                            "{}",
+                            colors::color_status(i18n::tr(">> Copied to clipboard"))
+                        ),
+                        Err(e) => eprintln!("{}", colors::color_error(format!(">> {e:#}"))),
+                    },
+                    Err(e) => eprintln!("{}", colors::color_warning(format!(">> {e}"))),
+                }
+                Ok(None)
+            }
+            ReplCommand::Stats => {
+                println!(
+                    "{}\n",
+                    self.stats.render(self.hook.get_total_usage().into())
+                );
+                Ok(None)
+            }
+            ReplCommand::Thinking(show) => {
+                let show = show.unwrap_or(!self.hook.show_thinking());
+                self.hook.set_show_thinking(show);
+                let message = if show {
+                    ">> Thinking blocks are shown"
+                } else {
+                    ">> Thinking blocks are hidden"
+                };
+                eprintln!("{}", colors::color_status(i18n::tr(message)));
+                Ok(None)
+            }
+            ReplCommand::Template { name: None, .. } => {
+                if self.templates.is_empty() {
+                    eprintln!(
+                        "{}",
+                        colors::color_warning(i18n::tr(">> No templates defined in config"))
+                    );
+                }
+                for (name, template) in &self.templates {
+                    println!(
+                        "{} {}",
+                        colors::color_prompt_number(name),
+                        colors::color_dim(template)
+                    );
+                }
+                Ok(None)
+            }
+            ReplCommand::Template {
+                name: Some(name),
+                args,
+            } => match self.templates.get(&name).cloned() {
+                Some(template) => self.expand_template(&template, &args, editor),
+                None => {
+                    eprintln!(
+                        "{}",
+                        colors::color_error(i18n::tr_with(
+                            ">> Unknown template: {name}",
+                            &[("name", &name)]
+                        ))
+                    );
+                    Ok(None)
+                }
+            },
+            ReplCommand::Tools(None) => {
+                for (name, enabled) in &self.tools {
+                    let state = if *enabled {
+                        colors::color_success("on")
+                    } else {
+                        colors::color_dim("off")
+                    };
+                    println!("{} {state}", colors::color_prompt_number(name));
+                }
+                Ok(None)
+            }
+            ReplCommand::Tools(Some((name, state))) => {
+                match self.tools.get_mut(&name) {
+                    Some(enabled) => {
+                        *enabled = state.unwrap_or(!*enabled);
+                        // Rebuilt with the new tool set on the next query;
+                        // the conversation is kept
+                        self.agent = None;
+                        let message = if *enabled {
+                            ">> {name} is enabled for this session"
+                        } else {
+                            ">> {name} is disabled for this session"
+                        };
+                        eprintln!(
+                            "{}",
+                            colors::color_status(i18n::tr_with(message, &[("name", &name)]))
+                        );
+                    }
+                    None => eprintln!(
+                        "{}",
+                        colors::color_error(i18n::tr_with(
+                            ">> Unknown tool: {name}",
+                            &[("name", &name)]
+                        ))
+                    ),
+                }
+                Ok(None)
+            }
+            ReplCommand::Export(path) => {
+                // A `.dot` file gets the last module graph instead of the conversation
+                let dot = Path::new(&path).extension().is_some_and(|ext| ext == "dot");
+                let exported = match (dot, &self.transcript) {
+                    (true, _) => match tools::module_graph_dot() {
+                        Some(graph) => std::fs::write(&path, graph)
+                            .map(|()| format!("Exported the module graph to {path}"))
+                            .with_context(|| format!("Failed to write {path}")),
+                        None => Err(anyhow::anyhow!(
+                            "No module graph yet; ask about module dependencies first"
+                        )),
+                    },
+                    (false, Some(transcript)) => transcript
+                        .export(Path::new(&path))
+                        .map(|count| format!("Exported {count} messages to {path}")),
+                    (false, None) => Err(anyhow::anyhow!("This session has no transcript")),
+                };
+                match exported {
+                    Ok(message) => eprintln!("{}", colors::color_status(format!(">> {message}"))),
+                    Err(e) => eprintln!("{}", colors::color_error(format!(">> {e:#}"))),
+                }
+                Ok(None)
+            }
+        }
     }
 
     pub async fn run(&mut self) -> Result<()> {
-        println!(
+        eprintln!(
             "{}",
-            colors::color_success(">> Ready! Type your queries (Ctrl+C or Ctrl+D to exit)")
+            colors::color_success(i18n::tr(
+                ">> Ready! Type your queries (Ctrl+C or Ctrl+D to exit)"
+            ))
         );
+        if self.voice.is_some() {
+            eprintln!(
+                "{}",
+                colors::color_dim(i18n::tr(
+                    ">> Voice input: press Enter on an empty line to speak"
+                ))
+            );
+        }
         println!();
 
-        let stdin = io::stdin();
-        let mut handle = stdin.lock();
-        let mut buffer = String::new();
+        let mut editor = LineEditor::new(self.editing_mode)?;
         let mut history = Vec::new();
-        let hook = ProgressHook::new();
+        let hook = self.hook.clone();
+
+        // Exit on Ctrl+C or Ctrl+D, or once the time box is over
+        while !timebox::expired()
+            && let Some(line) = editor.read_line(&self.prompt(&hook))?
+        {
+            // In voice mode an empty line dictates the query instead
+            let line = match &self.voice {
+                Some(voice) if line.trim().is_empty() => voice
+                    .dictate(&mut editor, &self.prompt(&hook))
+                    .await
+                    .unwrap_or_else(|e| {
+                        eprintln!("{}", colors::color_error(format!(">> {e:#}")));
+                        None
+                    })
+                    .unwrap_or_default(),
+                _ => line,
+            };
+            let line = line.trim();
 
-        loop {
-            // Prompt with token usage
-            print!("{}", format_prompt(hook.get_total_usage()));
-            io::stdout().flush()?;
+            // Skip empty lines
+            if line.is_empty() {
+                continue;
+            }
 
-            // Read line
-            buffer.clear();
-            let bytes_read = handle
-                .read_line(&mut buffer)
-                .context("Failed to read line from stdin")?;
+            let input = match ReplCommand::parse(line) {
+                Some(Ok(command)) => {
+                    match self.handle_command(command, &mut history, &mut editor)? {
+                        Some(prompt) => {
+                            eprintln!("{}", colors::color_dim(format!(">> {prompt}")));
+                            prompt
+                        }
+                        None => continue,
+                    }
+                }
+                Some(Err(e)) => {
+                    eprintln!("{}", colors::color_error(format!(">> {e}")));
+                    continue;
+                }
+                None => {
+                    self.attach_dropped_images(line);
+                    line.to_string()
+                }
+            };
 
-            // Check for EOF (Ctrl+D)
-            if bytes_read == 0 {
-                println!("\n{}", colors::color_status(">> Goodbye!"));
-                break;
+            let attachments = std::mem::take(&mut self.attachments);
+            let prompt = attach::build_prompt(&input, &attachments);
+            // A planned query is taken back as a whole, with all its steps
+            self.turns.push(TurnRecord {
+                input,
+                attachments,
+                images: self.images.clone(),
+                history_len: history.len(),
+                usage_before: hook.get_total_usage(),
+                last_response: self.last_response.clone(),
+            });
+            // Continuing picks up the previous query rather than planning anew
+            if self.plan_mode && self.continue_turns.is_none() {
+                self.run_plan(&prompt, &mut history, &hook, &mut editor)
+                    .await?;
+            } else if let Some(answer) = self.turn(&prompt, &mut history, &hook).await? {
+                self.verify(answer).await?;
             }
+            self.summarize();
+            if timebox::expired() {
+                eprintln!(
+                    "{}",
+                    colors::color_status(i18n::tr(">> The time box is over, ending the session"))
+                );
+            }
+        }
+        eprintln!("{}", colors::color_status(i18n::tr(">> Goodbye!")));
 
-            let input = buffer.trim();
+        Ok(())
+    }
 
-            // Skip empty lines
-            if input.is_empty() {
-                continue;
+    /// The prompt, with the token usage so far
+    fn prompt(&self, hook: &ProgressHook) -> String {
+        self.prompt_line
+            .format(hook.get_total_usage(), hook.get_last_usage())
+    }
+
+    /// Send one prompt to the agent and render the response.
+    ///
+    /// Returns the response, or `None` if the agent failed.
+    async fn turn(
+        &mut self,
+        input: &str,
+        history: &mut Vec<Message>,
+        hook: &ProgressHook,
+    ) -> Result<Option<String>> {
+        // Start spinner and give it to the hook for control
+        let spinner = create_spinner(i18n::tr("Processing"));
+        hook.set_external_spinner(spinner);
+
+        // Execute query with history and progress hook
+        let usage_before = hook.get_total_usage();
+        let started = Instant::now();
+        let turn_start = history.len();
+        let disabled = self
+            .tools
+            .iter()
+            .filter(|(_, enabled)| !**enabled)
+            .map(|(name, _)| name.clone())
+            .collect();
+        let agent = self
+            .agent
+            .get_or_insert_with(|| (self.build_agent)(&disabled));
+        let images = std::mem::take(&mut self.images);
+        let request = agent
+            .prompt(attach::message(input, &images))
+            .with_history(history)
+            .with_tool_concurrency(TOOL_CONCURRENCY)
+            .with_hook(hook.clone());
+        let request = match self.continue_turns.take() {
+            Some(turns) => request.max_turns(turns),
+            None => request,
+        };
+        let result = request
+            .into_future()
+            .instrument(telemetry::turn_span())
+            .await;
+        // Rather than lose the work at the turn limit, ask for what was found
+        let result = match result {
+            Err(PromptError::MaxTurnsError {
+                max_turns,
+                chat_history,
+                ..
+            }) => {
+                self.exhausted_turns = Some(max_turns);
+                *history = *chat_history;
+                if let Some(s) = hook.get_external_spinner() {
+                    s.finish_and_clear();
+                }
+                eprintln!(
+                    "{}",
+                    colors::color_warning(i18n::tr_with(
+                        ">> Reached the limit of {max_turns} turns, summarizing the findings so far",
+                        &[("max_turns", &max_turns)]
+                    ))
+                );
+                hook.set_external_spinner(create_spinner(i18n::tr("Summarizing")));
+                salvage::partial_answer(agent, history, hook)
+                    .await
+                    .map(|answer| salvage::mark(&answer, max_turns))
             }
+            // The time box ran out: rather than just stop, ask for a wrap-up
+            Err(PromptError::PromptCancelled {
+                chat_history,
+                reason,
+            }) if timebox::cancelled_by(&reason) => {
+                self.exhausted_turns = None;
+                *history = *chat_history;
+                if let Some(s) = hook.get_external_spinner() {
+                    s.finish_and_clear();
+                }
+                eprintln!(
+                    "{}",
+                    colors::color_warning(i18n::tr_with(
+                        ">> The time box of {minutes} minutes is over, wrapping up the findings",
+                        &[("minutes", &timebox::minutes().unwrap_or_default())]
+                    ))
+                );
+                hook.set_external_spinner(create_spinner(i18n::tr("Summarizing")));
+                salvage::wrap_up(agent, history, hook).await.map(|answer| {
+                    salvage::mark_wrap_up(&answer, timebox::minutes().unwrap_or_default())
+                })
+            }
+            // The tool output was too large to send as is: cut it down and
+            // carry on
+            Err(PromptError::PromptCancelled {
+                chat_history,
+                reason,
+            }) if hooks::trim_requested(&reason) => {
+                self.exhausted_turns = None;
+                *history = *chat_history;
+                let turn = turn_start.min(history.len());
+                salvage::trim_tool_outputs(
+                    &mut history[turn..],
+                    hook.output_limit().unwrap_or_default(),
+                );
+                hook.set_external_spinner(create_spinner(i18n::tr("Processing")));
+                salvage::resume_trimmed(agent, history, hook).await
+            }
+            result => {
+                self.exhausted_turns = None;
+                result
+            }
+        };
+        let elapsed = started.elapsed();
+        let calls = hook.take_tool_calls();
+        let footer = self.stats.record_turn(elapsed, &calls);
+        if let Some(usage_log) = &self.usage_log {
+            let tokens = UsageRecord::from(hook.get_total_usage()).since(usage_before.into());
+            usage_log.record(tokens, calls.len(), elapsed);
+        }
+        // The line-oriented REPL can't tell whether the terminal has focus
+        self.notify.turn_finished(elapsed, None);
 
-            // Start spinner and give it to the hook for control
-            let spinner = create_spinner("Processing");
-            hook.set_external_spinner(spinner);
+        // Clear any remaining spinner
+        if let Some(s) = hook.get_external_spinner() {
+            s.finish_and_clear();
+        }
+        lifecycle::turn_end(input, &result).await;
+        if let Some(transcript) = &mut self.transcript
+            && let Err(e) = transcript.spill(history)
+        {
+            tracing::warn!("[!] Warning: Failed to write the session transcript: {e:#}");
+        }
 
-            // Execute query with history and progress hook
-            match self
-                .agent
-                .prompt(input)
-                .with_history(&mut history)
-                .with_hook(hook.clone())
-                .await
-            {
-                Ok(response) => {
-                    // Clear any remaining spinner
-                    if let Some(s) = hook.get_external_spinner() {
-                        s.finish_and_clear();
-                    }
+        match result {
+            Ok(response) => {
+                let rendered = match &self.citation_base {
+                    Some(base_dir) => citations::annotate(&response, base_dir),
+                    None => response.clone(),
+                };
+                markdown::render_markdown(&rendered)?;
+                eprintln!("{footer}\n");
+                if let Some(turns) = self.exhausted_turns {
+                    eprintln!(
+                        "{}\n",
+                        colors::color_dim(i18n::tr_with(
+                            ">> /continue grants {turns} more turns to finish the investigation",
+                            &[("turns", &turns)]
+                        ))
+                    );
+                }
+                self.last_response = Some(response.clone());
+                Ok(Some(response))
+            }
+            Err(e) => {
+                eprintln!(
+                    "{}",
+                    colors::color_error(i18n::tr_with(
+                        ">> Error: {error}",
+                        &[("error", &format!("{e:#}"))]
+                    ))
+                );
+                eprintln!("{footer}\n");
+                Ok(None)
+            }
+        }
+    }
 
-                    markdown::render_markdown(&response);
+    /// Run a `!` command in the workspace and show its output to the user
+    /// only
+    fn run_shell(&mut self, command: &str) {
+        let output = std::process::Command::new("sh")
+            .arg("-c")
+            .arg(command)
+            .current_dir(&self.shell_dir)
+            .stdin(std::process::Stdio::inherit())
+            .output();
+        match output {
+            Ok(output) => {
+                print!("{}", String::from_utf8_lossy(&output.stdout));
+                eprint!(
+                    "{}",
+                    colors::color_warning(String::from_utf8_lossy(&output.stderr))
+                );
+                if !output.status.success() {
+                    eprintln!(
+                        "{}",
+                        colors::color_dim(format!(">> {command}: {}", output.status))
+                    );
                 }
-                Err(e) => {
-                    // Clear any remaining spinner
-                    if let Some(s) = hook.get_external_spinner() {
-                        s.finish_and_clear();
-                    }
+                eprintln!(
+                    "{}\n",
+                    colors::color_dim(i18n::tr(
+                        ">> Not sent to the model; /attach last-shell-output sends it with your next question"
+                    ))
+                );
+                self.last_shell_output = Some(format!(
+                    "$ {command}\n{}{}",
+                    String::from_utf8_lossy(&output.stdout),
+                    String::from_utf8_lossy(&output.stderr)
+                ));
+            }
+            Err(e) => eprintln!(
+                "{}",
+                colors::color_error(i18n::tr_with(
+                    ">> Failed to run {command}: {error}",
+                    &[("command", &command), ("error", &e)]
+                ))
+            ),
+        }
+    }
 
-                    eprintln!("{}", colors::color_error(format!(">> Error: {:#}\n", e)));
+    /// Every `SUMMARY_EVERY` queries, have the session's title and summary
+    /// brought up to date with the latest ones
+    fn summarize(&self) {
+        if let (Some(summarizer), Some(transcript)) = (&self.summarizer, &self.transcript)
+            && self.turns.len().is_multiple_of(sessions::SUMMARY_EVERY)
+        {
+            let start = self.turns.len() - sessions::SUMMARY_EVERY;
+            summarizer.update(
+                sessions::meta_path(transcript.path()),
+                self.turns[start..]
+                    .iter()
+                    .map(|t| t.input.clone())
+                    .collect(),
+                self.last_response.clone(),
+            );
+        }
+    }
+
+    /// Take the last turn back: drop its prompt, tool calls and answer from
+    /// the history, and its tokens from the session totals. The transcript
+    /// on disk keeps it.
+    ///
+    /// Returns the removed turn, or `None` if there is none.
+    fn undo(&mut self, history: &mut Vec<Message>) -> Option<TurnRecord> {
+        self.turns.pop().map(|mut turn| {
+            history.truncate(turn.history_len);
+            if let Some(transcript) = &mut self.transcript {
+                transcript.rewind(history.len());
+            }
+            self.hook.restore_total_usage(turn.usage_before);
+            self.last_response = turn.last_response.take();
+            turn
+        })
+    }
+
+    /// Check the citations of an answer if verification is enabled, and
+    /// append corrections to it
+    async fn verify(&mut self, answer: String) -> Result<()> {
+        if let Some(verifier) = &self.verifier {
+            let spinner = create_spinner(i18n::tr("Verifying citations"));
+            let result = verifier.check(&answer).await;
+            spinner.finish_and_clear();
+
+            match result {
+                Ok(Some(corrections)) => {
+                    let corrections = format!("## Corrections\n\n{corrections}");
+                    markdown::render_markdown(&corrections)?;
+                    println!();
+                    self.last_response = Some(format!("{answer}\n\n{corrections}"));
                 }
+                Ok(None) => eprintln!(
+                    "{}\n",
+                    colors::color_success(i18n::tr(">> No wrong citations found"))
+                ),
+                Err(e) => eprintln!(
+                    "{}",
+                    colors::color_error(i18n::tr_with(
+                        ">> Verification failed: {error}",
+                        &[("error", &format!("{e:#}"))]
+                    ))
+                ),
             }
         }
+        Ok(())
+    }
 
+    /// Have the agent propose a plan, let the user approve or edit it, then
+    /// execute it step by step and answer the query
+    async fn run_plan(
+        &mut self,
+        query: &str,
+        history: &mut Vec<Message>,
+        hook: &ProgressHook,
+        editor: &mut LineEditor,
+    ) -> Result<()> {
+        let steps = self
+            .turn(&plan::plan_request(query), history, hook)
+            .await?
+            .map(|proposal| plan::parse_steps(&proposal))
+            .unwrap_or_default();
+
+        // An agent that answered without a plan leaves nothing to review
+        if !steps.is_empty() {
+            match Self::review_plan(steps, editor)? {
+                Some(steps) => self.execute_plan(query, &steps, history, hook).await?,
+                None => eprintln!("{}", colors::color_status(i18n::tr(">> Plan discarded"))),
+            }
+        }
         Ok(())
     }
+
+    /// Run the steps one turn each, showing progress against the plan, and
+    /// finish with the answer. A failed step stops the plan.
+    async fn execute_plan(
+        &mut self,
+        query: &str,
+        steps: &[String],
+        history: &mut Vec<Message>,
+        hook: &ProgressHook,
+    ) -> Result<()> {
+        let mut stopped = false;
+        for index in 0..steps.len() {
+            if !stopped {
+                eprintln!("{}\n", plan::render(steps, Some(index)));
+                stopped = self
+                    .turn(&plan::step_request(steps, index), history, hook)
+                    .await?
+                    .is_none();
+            }
+        }
+
+        if stopped {
+            eprintln!("{}", colors::color_warning(i18n::tr(">> Plan stopped")));
+        } else {
+            eprintln!("{}\n", plan::render(steps, Some(steps.len())));
+            if let Some(answer) = self
+                .turn(&plan::answer_request(query), history, hook)
+                .await?
+            {
+                self.verify(answer).await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Ask the user to run, edit or discard the plan.
+    ///
+    /// Returns the steps to execute, or `None` if the plan was discarded.
+    fn review_plan(steps: Vec<String>, editor: &mut LineEditor) -> Result<Option<Vec<String>>> {
+        let label =
+            colors::color_status(i18n::tr(">> Run this plan? [Y]es, [e]dit, [n]o: ")).to_string();
+        let answer = editor.read_line(&label)?.unwrap_or_else(|| "n".to_string());
+
+        match answer.trim().to_lowercase().as_str() {
+            "" | "y" | "yes" => Ok(Some(steps)),
+            "e" | "edit" => Self::edit_plan(steps, editor).map(Some),
+            _ => Ok(None),
+        }
+    }
+
+    /// Let the user rewrite or clear each step, then append new ones
+    fn edit_plan(steps: Vec<String>, editor: &mut LineEditor) -> Result<Vec<String>> {
+        eprintln!(
+            "{}",
+            colors::color_dim(i18n::tr(
+                ">> Edit each step, clear it to drop it; add steps at the end, empty line to finish"
+            ))
+        );
+        let mut edited = Vec::new();
+        for step in &steps {
+            let label = format!("{}. ", edited.len() + 1);
+            if let Some(step) = editor.edit_line(&label, step)?
+                && !step.trim().is_empty()
+            {
+                edited.push(step.trim().to_string());
+            }
+        }
+        while let Some(step) = editor.read_line(&format!("{}. ", edited.len() + 1))?
+            && !step.trim().is_empty()
+        {
+            edited.push(step.trim().to_string());
+        }
+
+        println!("\n{}\n", plan::render(&edited, None));
+        Ok(edited)
+    }
 }
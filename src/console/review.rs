@@ -0,0 +1,445 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::Path;
+use std::process::ExitCode;
+use std::sync::LazyLock;
+use std::time::Instant;
+
+use anyhow::{Context, Result};
+use regex::Regex;
+use rig::agent::Agent;
+use rig::completion::Prompt;
+use rig::providers::anthropic;
+use serde::Deserialize;
+use tracing::Instrument;
+
+use crate::agent::hooks::ProgressHook;
+use crate::agent::{TOOL_CONCURRENCY, lifecycle, remote, telemetry};
+use crate::console::usage::UsageLog;
+use crate::console::{colors, markdown};
+
+/// Diff sent to the agent, at most
+const MAX_DIFF_BYTES: usize = 200 * 1024; // 200KB
+
+/// Exit code used when the review couldn't be done
+const EXIT_REVIEW_FAILED: u8 = 1;
+
+/// Exit code of the `pre-commit` hook when a finding blocks the commit
+const EXIT_BLOCKED: u8 = 1;
+
+/// What the review looks for, unless configured otherwise
+const REVIEW_FOCUS: &str = "Review the following diff of this repository. Look at the \
+    surrounding code with read_file and deep_search where the diff alone doesn't tell whether \
+    a change is right: callers of changed functions, types and invariants it relies on, tests. \
+    Look for bugs, regressions, missing error handling, security problems and unclear code; \
+    don't comment on formatting or restate what the diff does.";
+
+/// How findings are reported, so that they can be grouped
+const REVIEW_FORMAT: &str = "Report each finding on its own line, in exactly this form:\n\
+    [severity] path:line: finding\n\
+    where severity is critical (a bug or security problem), warning (likely wrong or fragile) \
+    or suggestion (an improvement), path is relative to the repository root and line is in \
+    the new version of the file. After the findings, add a short overall assessment. If the \
+    diff looks good, say so and report no findings.";
+
+/// Matches a `[severity] path:line: finding` line of the review
+static FINDING: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"(?i)^\s*(?:[-*]\s+)?\**\[(?P<severity>critical|warning|suggestion)\]\**\s+`?(?P<path>[^\s:`]+)(?::(?P<line>\d+))?`?:\s*(?P<message>.+)$",
+    )
+    .expect("Invalid finding regex")
+});
+
+/// Which changes are reviewed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DiffSource {
+    /// Uncommitted changes of tracked files
+    WorkingTree,
+    /// Changes staged for the next commit
+    Staged,
+    /// A revision range, e.g. `main..feature`
+    Range(String),
+}
+
+impl DiffSource {
+    /// Arguments of the `git diff` call, where a range starting with `-`
+    /// is still taken as a range rather than an option
+    fn git_args(&self) -> Vec<&str> {
+        match self {
+            Self::WorkingTree => vec!["diff", "HEAD"],
+            Self::Staged => vec!["diff", "--staged"],
+            Self::Range(range) => vec!["diff", "--end-of-options", range],
+        }
+    }
+}
+
+impl fmt::Display for DiffSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::WorkingTree => write!(f, "uncommitted changes"),
+            Self::Staged => write!(f, "staged changes"),
+            Self::Range(range) => write!(f, "{range}"),
+        }
+    }
+}
+
+/// `[review]` config section
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ReviewConfig {
+    /// What reviews look for, replacing the default instructions; the
+    /// format findings are reported in is always asked for
+    pub prompt: Option<String>,
+    /// Severities of findings that fail the `pre-commit` hook
+    #[serde(alias = "block-on")]
+    pub block_on: Vec<Severity>,
+}
+
+impl Default for ReviewConfig {
+    fn default() -> Self {
+        Self {
+            prompt: None,
+            block_on: vec![Severity::Critical],
+        }
+    }
+}
+
+/// How much a finding matters, most severe first
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Critical,
+    Warning,
+    Suggestion,
+}
+
+impl Severity {
+    fn parse(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "critical" => Some(Self::Critical),
+            "warning" => Some(Self::Warning),
+            "suggestion" => Some(Self::Suggestion),
+            _ => None,
+        }
+    }
+
+    fn tag(self) -> &'static str {
+        match self {
+            Self::Critical => "critical",
+            Self::Warning => "warning",
+            Self::Suggestion => "suggestion",
+        }
+    }
+}
+
+/// One problem the review found
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Finding {
+    pub severity: Severity,
+    pub path: String,
+    pub line: Option<usize>,
+    pub message: String,
+}
+
+/// The findings of a review, and the rest of its text
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Review {
+    pub findings: Vec<Finding>,
+    /// Lines that aren't findings, e.g. the overall assessment
+    pub remarks: String,
+}
+
+impl Review {
+    pub fn parse(answer: &str) -> Self {
+        let (findings, remarks): (Vec<_>, Vec<_>) = answer
+            .lines()
+            .map(|line| {
+                FINDING
+                    .captures(line)
+                    .and_then(|caps| {
+                        Some(Finding {
+                            severity: Severity::parse(&caps["severity"])?,
+                            path: caps["path"].to_string(),
+                            line: caps.name("line").and_then(|l| l.as_str().parse().ok()),
+                            message: caps["message"].trim().to_string(),
+                        })
+                    })
+                    .ok_or(line)
+            })
+            .partition(Result::is_ok);
+        Self {
+            findings: findings.into_iter().filter_map(Result::ok).collect(),
+            remarks: remarks
+                .into_iter()
+                .filter_map(Result::err)
+                .collect::<Vec<_>>()
+                .join("\n")
+                .trim()
+                .to_string(),
+        }
+    }
+
+    /// Number of findings with one of the `severities`
+    pub fn blocking(&self, severities: &[Severity]) -> usize {
+        self.findings
+            .iter()
+            .filter(|finding| severities.contains(&finding.severity))
+            .count()
+    }
+
+    /// Markdown with the findings grouped by file, most severe first, then
+    /// the remarks
+    pub fn render(&self) -> String {
+        let mut by_file: BTreeMap<&str, Vec<&Finding>> = BTreeMap::new();
+        for finding in &self.findings {
+            by_file.entry(&finding.path).or_default().push(finding);
+        }
+
+        let mut out = String::new();
+        for (path, mut findings) in by_file {
+            findings.sort_by_key(|finding| (finding.severity, finding.line));
+            out.push_str(&format!("## {path}\n\n"));
+            for finding in findings {
+                let location = finding
+                    .line
+                    .map(|line| format!("{path}:{line} "))
+                    .unwrap_or_default();
+                out.push_str(&format!(
+                    "- **[{}]** {location}{}\n",
+                    finding.severity.tag(),
+                    finding.message
+                ));
+            }
+            out.push('\n');
+        }
+        if self.findings.is_empty() {
+            out.push_str("No findings.\n\n");
+        }
+        out.push_str(&self.remarks);
+        out.trim_end().to_string()
+    }
+}
+
+/// Output of a git command in the workspace, cut down to `MAX_DIFF_BYTES`
+pub(crate) async fn git_output(base_dir: &Path, args: &[&str]) -> Result<String> {
+    let output = remote::command(base_dir, "git", args)
+        .output()
+        .await
+        .context("Failed to run git")?;
+    anyhow::ensure!(
+        output.status.success(),
+        "git {} failed: {}",
+        args.first().unwrap_or(&""),
+        String::from_utf8_lossy(&output.stderr).trim()
+    );
+
+    let mut text = String::from_utf8_lossy(&output.stdout).into_owned();
+    if text.len() > MAX_DIFF_BYTES {
+        let mut end = MAX_DIFF_BYTES;
+        while !text.is_char_boundary(end) {
+            end -= 1;
+        }
+        text.truncate(end);
+        text.push_str("\n[truncated - diff exceeds 200KB]");
+        tracing::warn!("[!] Warning: The diff was truncated to 200KB");
+    }
+    Ok(text)
+}
+
+/// Have the agent review `diff`, reporting failures
+async fn ask(
+    agent: &Agent<anthropic::completion::CompletionModel>,
+    diff: &str,
+    source: &DiffSource,
+    config: &ReviewConfig,
+    usage_log: &UsageLog,
+) -> Option<Review> {
+    let prompt = format!(
+        "{}\n\n{REVIEW_FORMAT}\n\n<diff source=\"{source}\">\n{diff}\n</diff>",
+        config.prompt.as_deref().unwrap_or(REVIEW_FOCUS)
+    );
+
+    let hook = ProgressHook::new();
+    let started = Instant::now();
+    let result = agent
+        .prompt(prompt.as_str())
+        .with_tool_concurrency(TOOL_CONCURRENCY)
+        .with_hook(hook.clone())
+        .into_future()
+        .instrument(telemetry::turn_span())
+        .await;
+    lifecycle::turn_end(&prompt, &result).await;
+    usage_log.record(
+        hook.get_total_usage().into(),
+        hook.take_tool_calls().len(),
+        started.elapsed(),
+    );
+
+    match result {
+        Ok(answer) => Some(Review::parse(&answer)),
+        Err(e) => {
+            eprintln!("{}", colors::color_error(format!(">> Error: {e:#}")));
+            None
+        }
+    }
+}
+
+/// Review the changes of `source` and print the findings grouped by file.
+///
+/// Returns a success exit code if the review was done.
+pub async fn run(
+    agent: &Agent<anthropic::completion::CompletionModel>,
+    base_dir: &Path,
+    source: &DiffSource,
+    config: &ReviewConfig,
+    usage_log: &UsageLog,
+) -> Result<ExitCode> {
+    let diff = git_output(base_dir, &source.git_args()).await?;
+    anyhow::ensure!(!diff.trim().is_empty(), "Nothing to review in the {source}");
+
+    match ask(agent, &diff, source, config, usage_log).await {
+        Some(review) => {
+            println!("{}", markdown::format_markdown(&review.render()));
+            Ok(ExitCode::SUCCESS)
+        }
+        None => Ok(ExitCode::from(EXIT_REVIEW_FAILED)),
+    }
+}
+
+/// Review the staged changes as a `pre-commit` hook, failing the commit
+/// when a finding has one of the `block_on` severities.
+///
+/// The hook is advisory: without staged changes, or when the review itself
+/// fails, the commit goes ahead.
+pub async fn pre_commit(
+    agent: &Agent<anthropic::completion::CompletionModel>,
+    base_dir: &Path,
+    config: &ReviewConfig,
+    usage_log: &UsageLog,
+) -> Result<ExitCode> {
+    let source = DiffSource::Staged;
+    let diff = git_output(base_dir, &source.git_args()).await?;
+    let review = if diff.trim().is_empty() {
+        None
+    } else {
+        ask(agent, &diff, &source, config, usage_log).await
+    };
+
+    match review {
+        Some(review) => {
+            println!("{}", markdown::format_markdown(&review.render()));
+            let blocking = review.blocking(&config.block_on);
+            if blocking > 0 {
+                eprintln!(
+                    "{}",
+                    colors::color_error(format!(
+                        ">> {blocking} blocking finding(s); fix them, or commit with --no-verify to skip the review"
+                    ))
+                );
+                Ok(ExitCode::from(EXIT_BLOCKED))
+            } else {
+                Ok(ExitCode::SUCCESS)
+            }
+        }
+        None => Ok(ExitCode::SUCCESS),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_review() {
+        let review = Review::parse(
+            "[suggestion] src/config.rs:40: Name the constant\n\
+             - **[critical]** `src/config.rs:12`: The error is dropped\n\
+             [warning] README.md: The example no longer matches\n\
+             \n\
+             Overall the change is sound.",
+        );
+        assert_eq!(review.findings.len(), 3);
+        assert_eq!(
+            review.findings[1],
+            Finding {
+                severity: Severity::Critical,
+                path: "src/config.rs".to_string(),
+                line: Some(12),
+                message: "The error is dropped".to_string(),
+            }
+        );
+        assert_eq!(review.findings[2].line, None);
+        assert_eq!(review.remarks, "Overall the change is sound.");
+        assert_eq!(review.blocking(&ReviewConfig::default().block_on), 1);
+        assert_eq!(review.blocking(&[Severity::Critical, Severity::Warning]), 2);
+
+        assert_eq!(
+            review.render(),
+            "## README.md\n\n- **[warning]** The example no longer matches\n\n\
+             ## src/config.rs\n\n- **[critical]** src/config.rs:12 The error is dropped\n\
+             - **[suggestion]** src/config.rs:40 Name the constant\n\n\
+             Overall the change is sound."
+        );
+    }
+
+    #[test]
+    fn test_git_args() {
+        assert_eq!(DiffSource::Staged.git_args(), ["diff", "--staged"]);
+        assert_eq!(
+            DiffSource::Range("--output=x".to_string()).git_args(),
+            ["diff", "--end-of-options", "--output=x"]
+        );
+    }
+
+    #[test]
+    fn test_review_config() {
+        assert_eq!(ReviewConfig::default().block_on, [Severity::Critical]);
+        let config: ReviewConfig =
+            toml::from_str("prompt = \"Look for races\"\nblock-on = [\"critical\", \"warning\"]")
+                .unwrap();
+        assert_eq!(config.prompt.as_deref(), Some("Look for races"));
+        assert_eq!(config.block_on, [Severity::Critical, Severity::Warning]);
+        assert!(toml::from_str::<ReviewConfig>("block-on = [\"minor\"]").is_err());
+        assert!(toml::from_str::<ReviewConfig>("blocking = []").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_pre_commit_nothing_staged() {
+        let dir = std::env::temp_dir().join(format!("horse-review-test-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let init = std::process::Command::new("git")
+            .args(["init", "--quiet"])
+            .current_dir(&dir)
+            .status()
+            .unwrap();
+        assert!(init.success());
+        std::fs::write(dir.join("unstaged.rs"), "fn main() {}\n").unwrap();
+
+        // Without staged changes the agent isn't asked and the commit goes ahead
+        let client = anthropic::Client::builder()
+            .api_key("test")
+            .build()
+            .unwrap();
+        let agent = rig::agent::AgentBuilder::new(anthropic::completion::CompletionModel::new(
+            client,
+            "claude-sonnet-4-0",
+        ))
+        .build();
+        let code = pre_commit(
+            &agent,
+            &dir,
+            &ReviewConfig::default(),
+            &UsageLog::new("claude-sonnet-4-0", &dir),
+        )
+        .await
+        .unwrap();
+        assert_eq!(code, ExitCode::SUCCESS);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_render_no_findings() {
+        let review = Review::parse("The diff looks good.");
+        assert_eq!(review.render(), "No findings.\n\nThe diff looks good.");
+    }
+}
@@ -0,0 +1,238 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, LazyLock};
+
+use anyhow::Context;
+use chrono::NaiveDateTime;
+use rig::agent::{Agent, AgentBuilder};
+use rig::completion::Prompt;
+use rig::providers::anthropic;
+use serde::{Deserialize, Serialize};
+
+use crate::agent::{self, hooks::HelperHook, profile, tools::DEFAULT_SUB_AGENT_MODEL};
+use crate::console::colors;
+
+/// Queries after which the title and summary are written, and refreshed
+pub const SUMMARY_EVERY: usize = 3;
+
+/// Answer text passed to the summarizer, at most
+const MAX_ANSWER_CHARS: usize = 2000;
+
+const SUMMARY_PREAMBLE: &str = "You name and summarize sessions in which a developer asks \
+    questions about a codebase. Given the previous summary, if any, and the latest questions \
+    and answer, reply with exactly two lines: `TITLE: ` followed by a title of at most six \
+    words, and `SUMMARY: ` followed by one or two sentences on what the session covered so \
+    far. No other text.";
+
+/// Title and rolling summary of a session, kept next to its transcript
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SessionMeta {
+    pub title: String,
+    pub summary: String,
+}
+
+impl SessionMeta {
+    fn load(path: &Path) -> Option<Self> {
+        fs::read_to_string(path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+    }
+
+    /// Parse the summarizer's `TITLE:`/`SUMMARY:` reply
+    fn parse(reply: &str) -> Option<Self> {
+        let field = |name: &str| {
+            reply
+                .lines()
+                .find_map(|line| line.trim().strip_prefix(name))
+                .map(|value| value.trim().trim_matches('"').to_string())
+                .filter(|value| !value.is_empty())
+        };
+        Some(Self {
+            title: field("TITLE:")?,
+            summary: field("SUMMARY:").unwrap_or_default(),
+        })
+    }
+}
+
+/// Names and summarizes sessions with a cheap model, in the background
+#[derive(Clone)]
+pub struct Summarizer {
+    /// Built on first use, so that short sessions don't pay for the client
+    agent: Arc<LazyLock<Agent<anthropic::completion::CompletionModel>>>,
+}
+
+impl Summarizer {
+    pub fn new() -> Self {
+        Self {
+            agent: Arc::new(LazyLock::new(|| {
+                let model = anthropic::completion::CompletionModel::new(
+                    agent::client(),
+                    DEFAULT_SUB_AGENT_MODEL,
+                );
+                profile::tag(AgentBuilder::new(model))
+                    .preamble(SUMMARY_PREAMBLE)
+                    .max_tokens(200)
+                    .build()
+            })),
+        }
+    }
+
+    /// Fold the latest queries and answer into the title and summary stored
+    /// at `meta_path`, without waiting for it. Failures only cost the
+    /// session its name.
+    pub fn update(&self, meta_path: PathBuf, queries: Vec<String>, answer: Option<String>) {
+        let agent = Arc::clone(&self.agent);
+        tokio::spawn(async move {
+            let previous = SessionMeta::load(&meta_path).unwrap_or_default();
+            let answer: String = answer
+                .unwrap_or_default()
+                .chars()
+                .take(MAX_ANSWER_CHARS)
+                .collect();
+            let request = format!(
+                "Previous summary: {}\n\nLatest questions:\n- {}\n\nLatest answer:\n{answer}",
+                if previous.summary.is_empty() {
+                    "(none)"
+                } else {
+                    &previous.summary
+                },
+                queries.join("\n- ")
+            );
+
+            let result = agent
+                .prompt(request)
+                .with_hook(HelperHook::new(DEFAULT_SUB_AGENT_MODEL))
+                .await
+                .map_err(anyhow::Error::from)
+                .and_then(|reply| {
+                    SessionMeta::parse(&reply).context("The summary reply has no title")
+                })
+                .and_then(|meta| {
+                    fs::write(&meta_path, serde_json::to_string_pretty(&meta)?)
+                        .with_context(|| format!("Failed to write {}", meta_path.display()))
+                });
+            if let Err(e) = result {
+                tracing::debug!("Failed to summarize the session: {e:#}");
+            }
+        });
+    }
+}
+
+impl Default for Summarizer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A past session of a workspace
+#[derive(Debug, Clone, PartialEq)]
+pub struct SessionInfo {
+    /// Name of the transcript, the time the session started
+    pub id: String,
+    pub messages: usize,
+    pub meta: Option<SessionMeta>,
+}
+
+/// The sessions whose transcripts are in `dir`, usually a workspace's
+/// `SESSIONS_DIR`, newest first
+pub fn list(dir: &Path) -> Vec<SessionInfo> {
+    // Without the directory, no session was recorded yet
+    let mut sessions: Vec<SessionInfo> = fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "jsonl"))
+        .filter_map(|path| {
+            let id = path.file_stem()?.to_str()?.to_string();
+            let messages = fs::read_to_string(&path).ok()?.lines().count();
+            Some(SessionInfo {
+                meta: SessionMeta::load(&meta_path(&path)),
+                id,
+                messages,
+            })
+        })
+        .collect();
+    sessions.sort_by(|a, b| b.id.cmp(&a.id));
+    sessions
+}
+
+/// Where the title and summary of the session with transcript `transcript`
+/// are kept
+pub fn meta_path(transcript: &Path) -> PathBuf {
+    transcript.with_extension("meta.json")
+}
+
+/// One entry per session: start time, title and size, then the summary
+pub fn render(sessions: &[SessionInfo]) -> String {
+    if sessions.is_empty() {
+        colors::color_warning("No sessions recorded in this workspace")
+    } else {
+        sessions
+            .iter()
+            .map(|session| {
+                let started = NaiveDateTime::parse_from_str(&session.id, "%Y%m%d-%H%M%S")
+                    .map_or_else(
+                        |_| session.id.clone(),
+                        |t| t.format("%Y-%m-%d %H:%M").to_string(),
+                    );
+                let title = session
+                    .meta
+                    .as_ref()
+                    .map_or("(untitled)", |meta| meta.title.as_str());
+                let mut entry = format!(
+                    "{} {title} {}",
+                    colors::color_prompt_number(started),
+                    colors::color_dim(format!("({} messages)", session.messages))
+                );
+                if let Some(meta) = session.meta.as_ref().filter(|m| !m.summary.is_empty()) {
+                    entry.push_str(&format!("\n    {}", colors::color_dim(&meta.summary)));
+                }
+                entry
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::console::transcript::SESSIONS_DIR;
+
+    use super::*;
+
+    #[test]
+    fn test_parse_meta() {
+        assert_eq!(
+            SessionMeta::parse(
+                "TITLE: \"Retry logic in uploads\"\nSUMMARY: Traced upload retries to the client."
+            ),
+            Some(SessionMeta {
+                title: "Retry logic in uploads".to_string(),
+                summary: "Traced upload retries to the client.".to_string(),
+            })
+        );
+        assert_eq!(SessionMeta::parse("I can't summarize this."), None);
+    }
+
+    #[test]
+    fn test_list() {
+        let base_dir = std::env::temp_dir().join(format!("horse-sessions-{}", std::process::id()));
+        let dir = base_dir.join(SESSIONS_DIR);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("20260101-090000.jsonl"), "{}\n{}\n").unwrap();
+        fs::write(dir.join("20260102-090000.jsonl"), "{}\n").unwrap();
+        fs::write(
+            dir.join("20260101-090000.meta.json"),
+            r#"{"title":"Config loading","summary":""}"#,
+        )
+        .unwrap();
+
+        let sessions = list(&dir);
+        assert_eq!(sessions.len(), 2);
+        assert_eq!(sessions[0].id, "20260102-090000");
+        assert_eq!(sessions[0].meta, None);
+        assert_eq!(sessions[1].messages, 2);
+        assert_eq!(sessions[1].meta.as_ref().unwrap().title, "Config loading");
+        fs::remove_dir_all(&base_dir).unwrap();
+    }
+}
@@ -0,0 +1,348 @@
+use std::fmt::Write as _;
+use std::fs;
+use std::io::{self, BufRead, IsTerminal, Write};
+use std::path::Path;
+
+use anyhow::{Context, Result, ensure};
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use ratatui::crossterm::terminal;
+
+use crate::agent::keyring::{self, KeySource};
+use crate::agent::tools::ForgeType;
+use crate::config;
+use crate::console::{colors, doctor, watch::IGNORED_DIRS};
+
+/// Providers to choose from: the name keys are stored under, and its label
+const PROVIDERS: &[(&str, &str)] = &[("anthropic", "Anthropic (Claude)")];
+
+/// Written to the global config file when there is none
+const STARTER_CONFIG: &str = r#"# Written by `horse init`; see the Configuration section of the README for
+# every key. A workspace's `.horse.toml` takes precedence over this file.
+
+# default, solarized, high-contrast or colorblind-safe
+theme = "default"
+# Show the estimated session cost in the prompt line
+show-cost = true
+# Keybindings of the line editor, emacs or vi
+editing-mode = "emacs"
+# Language of the answers and messages, e.g. "de"
+# lang = "de"
+
+# Let Claude think before answering, with this many tokens
+# [thinking]
+# budget = 4000
+"#;
+
+/// Files that tell a project's stack, with the stack's usual commands
+const STACKS: &[(&str, &str, &[&str])] = &[
+    (
+        "Cargo.toml",
+        "Rust",
+        &["cargo build", "cargo test", "cargo clippy --all-targets"],
+    ),
+    (
+        "package.json",
+        "JavaScript/TypeScript",
+        &["npm install", "npm test"],
+    ),
+    ("go.mod", "Go", &["go build ./...", "go test ./..."]),
+    ("pyproject.toml", "Python", &["pip install -e .", "pytest"]),
+    ("pom.xml", "Java", &["mvn package", "mvn test"]),
+    ("Makefile", "Make", &["make"]),
+];
+
+/// Walk through the first-run setup: the provider and its API key, the
+/// search programs, the global config file and the workspace's AGENTS.md
+pub async fn run(base_dir: &Path) -> Result<()> {
+    ensure!(
+        io::stdin().is_terminal(),
+        "horse init needs an interactive terminal"
+    );
+
+    let labels: Vec<&str> = PROVIDERS.iter().map(|(_, label)| *label).collect();
+    let (provider, label) = PROVIDERS[choose("Provider", &labels)?];
+    store_key(provider, label)?;
+    store_forge_token()?;
+
+    println!("\nSearch programs:");
+    doctor::check_programs(&["rg", "rga"]).await;
+    println!();
+
+    write_config()?;
+
+    let agents_md = base_dir.join("AGENTS.md");
+    if !agents_md.exists()
+        && confirm(
+            &format!(
+                "Write an AGENTS.md skeleton to {}, for you to fill in?",
+                base_dir.display()
+            ),
+            false,
+        )?
+    {
+        fs::write(&agents_md, skeleton(base_dir))
+            .with_context(|| format!("Failed to write {}", agents_md.display()))?;
+        println!(
+            "{}",
+            colors::color_success(format!("Wrote {}", agents_md.display()))
+        );
+    }
+
+    println!("\nRun `horse doctor` to check the setup, then `horse` to start.");
+    Ok(())
+}
+
+/// Store the provider's API key in the system keyring, from the
+/// environment or typed in
+fn store_key(provider: &str, label: &str) -> Result<()> {
+    let key = match keyring::api_key() {
+        Some((key, KeySource::Env)) => confirm(
+            "Store ANTHROPIC_API_KEY of the environment in the system keyring?",
+            true,
+        )?
+        .then_some(key),
+        Some((_, KeySource::Keyring))
+            if !confirm("Replace the API key stored in the system keyring?", false)? =>
+        {
+            None
+        }
+        _ => Some(read_secret(&format!(
+            "{label} API key (hidden, empty to skip): "
+        ))?)
+        .filter(|key| !key.is_empty()),
+    };
+    match key.map(|key| keyring::store(provider, &key)) {
+        Some(Ok(())) => println!(
+            "{}",
+            colors::color_success("Stored the API key in the system keyring")
+        ),
+        Some(Err(e)) => println!(
+            "{}",
+            colors::color_warning(format!(
+                "Couldn't store the API key in the system keyring ({e}); add \
+                 `export ANTHROPIC_API_KEY=...` to your shell profile instead"
+            ))
+        ),
+        None => {}
+    }
+    Ok(())
+}
+
+/// Store a token for the forge tool in the system keyring, if the user
+/// has one, so it never has to go into a config file
+fn store_forge_token() -> Result<()> {
+    if confirm(
+        "Store a token for the forge tool, to read private issues and pull requests?",
+        false,
+    )? {
+        let labels: Vec<&str> = ForgeType::ALL.iter().map(|forge| forge.label()).collect();
+        let forge = ForgeType::ALL[choose("Forge", &labels)?];
+        let token = read_secret(&format!(
+            "{} token (hidden, empty to skip): ",
+            forge.label()
+        ))?;
+        match (!token.is_empty()).then(|| keyring::store(forge.account(), &token)) {
+            Some(Ok(())) => println!(
+                "{}",
+                colors::color_success("Stored the token in the system keyring")
+            ),
+            Some(Err(e)) => println!(
+                "{}",
+                colors::color_warning(format!(
+                    "Couldn't store the token in the system keyring ({e}); set it in \
+                     the environment instead"
+                ))
+            ),
+            None => {}
+        }
+    }
+    Ok(())
+}
+
+/// Write the starter global config file, unless there is one
+fn write_config() -> Result<()> {
+    match config::global_config_path() {
+        Some(path) if path.exists() => println!("Keeping {}", path.display()),
+        Some(path) => {
+            if let Some(dir) = path.parent() {
+                fs::create_dir_all(dir)
+                    .with_context(|| format!("Failed to create {}", dir.display()))?;
+            }
+            fs::write(&path, STARTER_CONFIG)
+                .with_context(|| format!("Failed to write {}", path.display()))?;
+            println!(
+                "{}",
+                colors::color_success(format!("Wrote {}", path.display()))
+            );
+        }
+        None => println!(
+            "{}",
+            colors::color_warning("No home directory to write the config file to")
+        ),
+    }
+    Ok(())
+}
+
+/// An AGENTS.md to fill in, with the stack, layout and commands a quick
+/// scan of the workspace finds
+fn skeleton(base_dir: &Path) -> String {
+    let name = base_dir
+        .file_name()
+        .map_or("Project".into(), |name| name.to_string_lossy());
+    let stacks: Vec<_> = STACKS
+        .iter()
+        .filter(|(file, _, _)| base_dir.join(file).exists())
+        .collect();
+    let mut dirs: Vec<String> = fs::read_dir(base_dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter(|entry| entry.path().is_dir())
+        .map(|entry| entry.file_name().to_string_lossy().into_owned())
+        .filter(|dir| !dir.starts_with('.') && !IGNORED_DIRS.contains(&dir.as_str()))
+        .collect();
+    dirs.sort_unstable();
+
+    let mut skeleton =
+        format!("# {name}\n\n<!-- What the project does and who uses it, in a few sentences -->\n");
+    if !stacks.is_empty() {
+        let languages: Vec<&str> = stacks.iter().map(|(_, stack, _)| *stack).collect();
+        let _ = writeln!(skeleton, "\nBuilt with {}.", languages.join(", "));
+    }
+    skeleton.push_str("\n## Layout\n\n");
+    for dir in &dirs {
+        let _ = writeln!(skeleton, "- `{dir}/` <!-- what lives here -->");
+    }
+    skeleton.push_str("\n## Commands\n\n");
+    if stacks.is_empty() {
+        skeleton.push_str("<!-- How to build, test and lint -->\n");
+    } else {
+        skeleton.push_str("```sh\n");
+        for command in stacks.iter().flat_map(|(_, _, commands)| *commands) {
+            let _ = writeln!(skeleton, "{command}");
+        }
+        skeleton.push_str("```\n");
+    }
+    skeleton.push_str(
+        "\n## Conventions\n\n<!-- Naming, error handling, test layout: what a newcomer gets wrong -->\n",
+    );
+    skeleton
+}
+
+/// Print `prompt` and read a line of input
+fn ask(prompt: &str) -> io::Result<String> {
+    print!("{prompt}");
+    io::stdout().flush()?;
+    let mut line = String::new();
+    io::stdin().lock().read_line(&mut line)?;
+    Ok(line.trim().to_string())
+}
+
+pub(crate) fn confirm(question: &str, default: bool) -> io::Result<bool> {
+    let choices = if default { "[Y/n]" } else { "[y/N]" };
+    let answer = ask(&format!("{question} {choices} "))?.to_lowercase();
+    Ok(if answer.is_empty() {
+        default
+    } else {
+        answer.starts_with('y')
+    })
+}
+
+/// Let the user pick one of `options` by number, the first by default
+fn choose(question: &str, options: &[&str]) -> io::Result<usize> {
+    for (i, option) in options.iter().enumerate() {
+        println!("  {}) {option}", i + 1);
+    }
+    let mut choice = None;
+    while choice.is_none() {
+        choice = parse_choice(&ask(&format!("{question} [1]: "))?, options.len());
+        if choice.is_none() {
+            println!("Enter a number from 1 to {}", options.len());
+        }
+    }
+    Ok(choice.unwrap_or_default())
+}
+
+/// Index of the option numbered `answer`, counting from 1
+fn parse_choice(answer: &str, count: usize) -> Option<usize> {
+    if answer.is_empty() {
+        Some(0)
+    } else {
+        answer
+            .parse::<usize>()
+            .ok()
+            .filter(|number| (1..=count).contains(number))
+            .map(|number| number - 1)
+    }
+}
+
+/// Read a line without echoing it
+fn read_secret(prompt: &str) -> io::Result<String> {
+    print!("{prompt}");
+    io::stdout().flush()?;
+    terminal::enable_raw_mode()?;
+    let secret = read_keys();
+    terminal::disable_raw_mode()?;
+    println!();
+    secret
+}
+
+fn read_keys() -> io::Result<String> {
+    let mut secret = String::new();
+    loop {
+        if let Event::Key(KeyEvent {
+            code,
+            modifiers,
+            kind: KeyEventKind::Press,
+            ..
+        }) = event::read()?
+        {
+            match code {
+                KeyCode::Enter => break Ok(secret),
+                KeyCode::Char('c') if modifiers.contains(KeyModifiers::CONTROL) => {
+                    break Err(io::Error::from(io::ErrorKind::Interrupted));
+                }
+                KeyCode::Backspace => {
+                    secret.pop();
+                }
+                KeyCode::Char(c) => secret.push(c),
+                _ => {}
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_starter_config() {
+        assert!(toml::from_str::<config::Config>(STARTER_CONFIG).is_ok());
+    }
+
+    #[test]
+    fn test_parse_choice() {
+        assert_eq!(parse_choice("", 2), Some(0));
+        assert_eq!(parse_choice("2", 2), Some(1));
+        assert_eq!(parse_choice("3", 2), None);
+        assert_eq!(parse_choice("0", 2), None);
+    }
+
+    #[test]
+    fn test_skeleton() {
+        let base_dir =
+            std::env::temp_dir().join(format!("horse-setup-test-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(base_dir.join("src")).unwrap();
+        fs::create_dir_all(base_dir.join("target")).unwrap();
+        fs::write(base_dir.join("Cargo.toml"), "").unwrap();
+
+        let skeleton = skeleton(&base_dir);
+        assert!(skeleton.contains("\nBuilt with Rust.\n"));
+        assert!(skeleton.contains("- `src/`"));
+        assert!(!skeleton.contains("`target/`"));
+        assert!(skeleton.contains("```sh\ncargo build\ncargo test\n"));
+
+        fs::remove_dir_all(&base_dir).unwrap();
+    }
+}
@@ -1,5 +1,7 @@
 use indicatif::{ProgressBar, ProgressStyle};
 
+use crate::console::terminal;
+
 /// Creates a braille-pattern spinner with a custom message.
 ///
 /// The spinner uses Unicode braille characters (⠋⠙⠹⠸⠼⠴⠦⠧⠇⠏) to create a smooth
@@ -13,6 +15,7 @@ use indicatif::{ProgressBar, ProgressStyle};
 ///
 /// A `ProgressBar` handle that can be used to control the spinner lifecycle.
 /// Call `.finish_and_clear()` on the spinner to cleanly remove it from the terminal.
+/// The handle is hidden, drawing nothing, when stdout is not a terminal.
 ///
 /// # Example
 ///
@@ -24,17 +27,22 @@ use indicatif::{ProgressBar, ProgressStyle};
 /// spinner.finish_and_clear();
 /// ```
 pub fn create_spinner(message: &str) -> ProgressBar {
-    let spinner = ProgressBar::new_spinner();
+    // Piped output gets no spinner, even though it would draw on stderr
+    if terminal::stdout_is_terminal() {
+        let spinner = ProgressBar::new_spinner();
 
-    spinner.set_style(
-        ProgressStyle::default_spinner()
-            .tick_strings(&["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"])
-            .template("{spinner:.cyan} {msg}")
-            .expect("Failed to set spinner template"),
-    );
+        spinner.set_style(
+            ProgressStyle::default_spinner()
+                .tick_strings(&["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"])
+                .template("{spinner:.cyan} {msg}")
+                .expect("Failed to set spinner template"),
+        );
 
-    spinner.set_message(message.to_string());
-    spinner.enable_steady_tick(std::time::Duration::from_millis(80));
+        spinner.set_message(message.to_string());
+        spinner.enable_steady_tick(std::time::Duration::from_millis(80));
 
-    spinner
+        spinner
+    } else {
+        ProgressBar::hidden()
+    }
 }
@@ -0,0 +1,233 @@
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use crate::agent::hooks::ToolCallOutcome;
+use crate::console::colors;
+use crate::console::output::UsageRecord;
+
+/// Maximum number of individual tool calls listed in the turn footer
+const MAX_FOOTER_TOOLS: usize = 5;
+
+/// Format a duration as seconds with one decimal, or minutes and seconds past a minute
+pub fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs_f64();
+    if secs < 60.0 {
+        format!("{secs:.1}s")
+    } else {
+        let whole = duration.as_secs();
+        format!("{}m {:02}s", whole / 60, whole % 60)
+    }
+}
+
+/// Format a byte count as bytes or kilobytes
+pub fn format_size(bytes: usize) -> String {
+    if bytes < 1024 {
+        format!("{bytes}B")
+    } else {
+        format!("{:.1}KB", bytes as f64 / 1024.0)
+    }
+}
+
+/// Share of the input tokens read from the prompt cache
+fn format_cache_hits(tokens: UsageRecord) -> String {
+    let input = tokens.input_tokens + tokens.cached_input_tokens;
+    if input == 0 {
+        "-".to_string()
+    } else {
+        format!(
+            "{:.0}% ({} of {input} input tokens)",
+            tokens.cached_input_tokens as f64 * 100.0 / input as f64,
+            tokens.cached_input_tokens
+        )
+    }
+}
+
+/// Statistics of all calls of a single tool
+#[derive(Debug, Default, Clone, PartialEq)]
+struct ToolStats {
+    calls: usize,
+    failed: usize,
+    bytes: usize,
+    time: Duration,
+}
+
+/// Timing statistics accumulated over a REPL session
+#[derive(Debug, Default)]
+pub struct SessionStats {
+    turns: u32,
+    total_time: Duration,
+    tool_time: Duration,
+    tool_calls: usize,
+    tools: BTreeMap<String, ToolStats>,
+}
+
+impl SessionStats {
+    /// Record a finished turn and return its dim footer line
+    pub fn record_turn(&mut self, elapsed: Duration, calls: &[ToolCallOutcome]) -> String {
+        let tool_time: Duration = calls.iter().map(|t| t.duration).sum();
+
+        self.turns += 1;
+        self.total_time += elapsed;
+        self.tool_time += tool_time;
+        self.tool_calls += calls.len();
+        for call in calls {
+            let tool = self.tools.entry(call.name.clone()).or_default();
+            tool.calls += 1;
+            tool.failed += usize::from(call.error.is_some());
+            tool.bytes += call.bytes;
+            tool.time += call.duration;
+        }
+
+        let footer = if calls.is_empty() {
+            format!("-- {}", format_duration(elapsed))
+        } else {
+            let mut listed: Vec<String> = calls
+                .iter()
+                .take(MAX_FOOTER_TOOLS)
+                .map(|t| format!("{} {}", t.name, format_duration(t.duration)))
+                .collect();
+            if calls.len() > MAX_FOOTER_TOOLS {
+                listed.push(format!("+{} more", calls.len() - MAX_FOOTER_TOOLS));
+            }
+            format!(
+                "-- {} (model {}, tools {}: {})",
+                format_duration(elapsed),
+                format_duration(elapsed.saturating_sub(tool_time)),
+                format_duration(tool_time),
+                listed.join(", ")
+            )
+        };
+
+        colors::color_dim(footer)
+    }
+
+    /// Render the session statistics for the `/stats` command, with the
+    /// session's token usage
+    pub fn render(&self, tokens: UsageRecord) -> String {
+        let average = self.total_time.checked_div(self.turns).unwrap_or_default();
+
+        let mut lines = vec![
+            format!("Turns:       {}", self.turns),
+            format!("Total time:  {}", format_duration(self.total_time)),
+            format!(
+                "Model time:  {}",
+                format_duration(self.total_time.saturating_sub(self.tool_time))
+            ),
+            format!(
+                "Tool time:   {} ({} calls)",
+                format_duration(self.tool_time),
+                self.tool_calls
+            ),
+            format!("Avg/turn:    {}", format_duration(average)),
+            format!("Cache hits:  {}", format_cache_hits(tokens)),
+        ];
+
+        // Slowest tools first
+        let mut tools: Vec<_> = self.tools.iter().collect();
+        tools.sort_by_key(|(_, tool)| std::cmp::Reverse(tool.time));
+        if !tools.is_empty() {
+            lines.push(String::new());
+            lines.push(format!(
+                "{:<16} {:>6} {:>7} {:>9} {:>10}",
+                "Tool", "Calls", "Failed", "Time", "Output"
+            ));
+        }
+        lines.extend(tools.into_iter().map(|(name, tool)| {
+            format!(
+                "{name:<16} {:>6} {:>7} {:>9} {:>10}",
+                tool.calls,
+                tool.failed,
+                format_duration(tool.time),
+                format_size(tool.bytes)
+            )
+        }));
+
+        lines.join("\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agent::outcome::ErrorKind;
+
+    fn call(name: &str, secs: u64, bytes: usize, failed: bool) -> ToolCallOutcome {
+        ToolCallOutcome {
+            name: name.to_string(),
+            duration: Duration::from_secs(secs),
+            bytes,
+            error: failed.then_some(ErrorKind::Failed),
+        }
+    }
+
+    #[test]
+    fn test_format() {
+        assert_eq!(format_duration(Duration::from_millis(1500)), "1.5s");
+        assert_eq!(format_duration(Duration::from_secs(75)), "1m 15s");
+        assert_eq!(format_size(512), "512B");
+        assert_eq!(format_size(1536), "1.5KB");
+    }
+
+    #[test]
+    fn test_render_times() {
+        let mut stats = SessionStats::default();
+        let footer = stats.record_turn(Duration::from_secs(30), &[call("bash", 10, 0, false)]);
+        assert!(footer.contains("-- 30.0s (model 20.0s, tools 10.0s: bash 10.0s)"));
+        stats.record_turn(Duration::from_secs(10), &[]);
+
+        let rendered = stats.render(UsageRecord::default());
+        assert!(rendered.contains("Turns:       2"));
+        assert!(rendered.contains("Model time:  30.0s"));
+        assert!(rendered.contains("Tool time:   10.0s (1 calls)"));
+        assert!(rendered.contains("Avg/turn:    20.0s"));
+        assert!(rendered.contains("Cache hits:  -"));
+    }
+
+    #[test]
+    fn test_per_tool_stats() {
+        let mut stats = SessionStats::default();
+        stats.record_turn(
+            Duration::from_secs(30),
+            &[
+                call("bash", 10, 100, false),
+                call("read_file", 1, 4096, false),
+            ],
+        );
+        stats.record_turn(Duration::from_secs(20), &[call("bash", 8, 0, true)]);
+
+        assert_eq!(
+            stats.tools["bash"],
+            ToolStats {
+                calls: 2,
+                failed: 1,
+                bytes: 100,
+                time: Duration::from_secs(18),
+            }
+        );
+
+        let rendered = stats.render(UsageRecord {
+            input_tokens: 1000,
+            output_tokens: 500,
+            cached_input_tokens: 3000,
+        });
+        let bash = rendered.find("bash").unwrap();
+        let read_file = rendered.find("read_file").unwrap();
+        assert!(bash < read_file, "slowest tool is listed first");
+        assert!(rendered.contains("4.0KB"));
+        assert!(rendered.contains("Cache hits:  75% (3000 of 4000 input tokens)"));
+    }
+
+    #[test]
+    fn test_footer_overflow() {
+        let mut stats = SessionStats::default();
+        let calls: Vec<ToolCallOutcome> = (0..MAX_FOOTER_TOOLS + 2)
+            .map(|_| call("read_file", 1, 0, false))
+            .collect();
+        let footer = stats.record_turn(Duration::from_secs(30), &calls);
+        assert_eq!(footer.matches("read_file").count(), MAX_FOOTER_TOOLS);
+        assert!(footer.contains(", +2 more)"));
+
+        let footer = stats.record_turn(Duration::from_secs(30), &calls[..MAX_FOOTER_TOOLS]);
+        assert!(!footer.contains("more"));
+    }
+}
@@ -0,0 +1,120 @@
+use std::collections::HashMap;
+
+/// Names of the `{placeholders}` in a template, in order of first appearance
+pub fn placeholders(template: &str) -> Vec<String> {
+    let mut names: Vec<String> = Vec::new();
+    let mut rest = template;
+
+    while let Some((_, tail)) = rest.split_once('{') {
+        match tail.split_once('}') {
+            Some((name, after)) => {
+                let valid =
+                    !name.is_empty() && name.chars().all(|c| c.is_alphanumeric() || c == '_');
+                if valid && !names.iter().any(|n| n == name) {
+                    names.push(name.to_string());
+                }
+                rest = after;
+            }
+            None => rest = "",
+        }
+    }
+
+    names
+}
+
+/// Assign whitespace-separated arguments to placeholders in order.
+/// The last placeholder receives all remaining arguments, so free text works
+/// naturally: `/t explain src/lib.rs error handling`.
+pub fn assign_args(placeholders: &[String], args: &str) -> HashMap<String, String> {
+    let mut values = HashMap::new();
+    let mut rest = args.trim();
+
+    for (i, name) in placeholders.iter().enumerate() {
+        if rest.is_empty() {
+            break;
+        }
+        let value = if i + 1 == placeholders.len() {
+            std::mem::take(&mut rest)
+        } else {
+            let (value, tail) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+            rest = tail.trim_start();
+            value
+        };
+        values.insert(name.clone(), value.to_string());
+    }
+
+    values
+}
+
+/// Substitute placeholder values into a template in one pass, so values
+/// that look like placeholders are kept as they are; placeholders without
+/// a value are kept
+pub fn fill(template: &str, values: &HashMap<String, String>) -> String {
+    let mut text = String::with_capacity(template.len());
+    let mut rest = template;
+
+    while let Some((before, tail)) = rest.split_once('{') {
+        text.push_str(before);
+        match tail
+            .split_once('}')
+            .and_then(|(name, after)| values.get(name).map(|value| (value, after)))
+        {
+            Some((value, after)) => {
+                text.push_str(value);
+                rest = after;
+            }
+            None => {
+                text.push('{');
+                rest = tail;
+            }
+        }
+    }
+    text.push_str(rest);
+
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_placeholders() {
+        assert_eq!(
+            placeholders("Explain {file} focusing on {topic}, see {file} and {}"),
+            vec!["file".to_string(), "topic".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_assign_and_fill() {
+        let template = "Explain {file} focusing on {topic}";
+        let names = placeholders(template);
+
+        let values = assign_args(&names, "src/hooks.rs  error handling");
+        assert_eq!(
+            fill(template, &values),
+            "Explain src/hooks.rs focusing on error handling"
+        );
+
+        // Missing values are left for the user to fill in
+        let values = assign_args(&names, "src/hooks.rs");
+        assert_eq!(values.get("topic"), None);
+        assert_eq!(
+            fill(template, &values),
+            "Explain src/hooks.rs focusing on {topic}"
+        );
+    }
+
+    #[test]
+    fn test_fill_once() {
+        let values = HashMap::from([
+            ("file".to_string(), "{topic}.rs".to_string()),
+            ("topic".to_string(), "errors".to_string()),
+        ]);
+        assert_eq!(
+            fill("{{file}} on {topic} {}", &values),
+            "{{topic}.rs} on errors {}"
+        );
+    }
+}
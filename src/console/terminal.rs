@@ -0,0 +1,11 @@
+use std::io::{self, IsTerminal};
+use std::sync::LazyLock;
+
+/// Checked once, as stdout doesn't change for the life of the process
+static STDOUT_IS_TERMINAL: LazyLock<bool> = LazyLock::new(|| io::stdout().is_terminal());
+
+/// Returns true if stdout is a terminal. When it's piped or redirected,
+/// output is plain: no colors, spinners, hyperlinks or rendered markdown.
+pub fn stdout_is_terminal() -> bool {
+    *STDOUT_IS_TERMINAL
+}
@@ -0,0 +1,158 @@
+use owo_colors::Style;
+use serde::Deserialize;
+use termimad::MadSkin;
+use termimad::crossterm::style::Color;
+
+/// Named color themes selectable via the `theme` config key
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ThemeName {
+    #[default]
+    Default,
+    Solarized,
+    HighContrast,
+    ColorblindSafe,
+}
+
+/// Styles for every role used in horse's terminal output
+#[derive(Debug, Clone, Copy)]
+pub struct Theme {
+    pub prompt_number: Style,
+    pub debug: Style,
+    pub error: Style,
+    pub warning: Style,
+    pub success: Style,
+    pub status: Style,
+    pub dim: Style,
+    /// Foreground used by markdown headers
+    heading: Color,
+    /// Foreground used by markdown inline code and code blocks
+    code: Color,
+    /// Foreground used by markdown bold text
+    emphasis: Color,
+}
+
+impl Theme {
+    pub fn new(name: ThemeName) -> Self {
+        match name {
+            ThemeName::Default => Self {
+                prompt_number: Style::new().cyan(),
+                debug: Style::new().bright_black(),
+                error: Style::new().bright_red(),
+                warning: Style::new().magenta().dimmed(),
+                success: Style::new().bright_green(),
+                status: Style::new().green().dimmed(),
+                dim: Style::new().bright_black(),
+                heading: Color::Yellow,
+                code: Color::AnsiValue(222),
+                emphasis: Color::Yellow,
+            },
+            // https://ethanschoonover.com/solarized/
+            ThemeName::Solarized => Self {
+                prompt_number: Style::new().truecolor(0x2a, 0xa1, 0x98),
+                debug: Style::new().truecolor(0x58, 0x6e, 0x75),
+                error: Style::new().truecolor(0xdc, 0x32, 0x2f),
+                warning: Style::new().truecolor(0xcb, 0x4b, 0x16),
+                success: Style::new().truecolor(0x85, 0x99, 0x00),
+                status: Style::new().truecolor(0x93, 0xa1, 0xa1),
+                dim: Style::new().truecolor(0x58, 0x6e, 0x75),
+                heading: Color::Rgb {
+                    r: 0x26,
+                    g: 0x8b,
+                    b: 0xd2,
+                },
+                code: Color::Rgb {
+                    r: 0xb5,
+                    g: 0x89,
+                    b: 0x00,
+                },
+                emphasis: Color::Rgb {
+                    r: 0xd3,
+                    g: 0x36,
+                    b: 0x82,
+                },
+            },
+            ThemeName::HighContrast => Self {
+                prompt_number: Style::new().bright_cyan().bold(),
+                debug: Style::new().white(),
+                error: Style::new().bright_red().bold(),
+                warning: Style::new().bright_yellow().bold(),
+                success: Style::new().bright_green().bold(),
+                status: Style::new().bright_white(),
+                dim: Style::new().white(),
+                heading: Color::White,
+                code: Color::Cyan,
+                emphasis: Color::White,
+            },
+            // Okabe-Ito palette, distinguishable with the common forms of color blindness
+            ThemeName::ColorblindSafe => Self {
+                prompt_number: Style::new().truecolor(0x56, 0xb4, 0xe9),
+                debug: Style::new().bright_black(),
+                error: Style::new().truecolor(0xd5, 0x5e, 0x00).bold(),
+                warning: Style::new().truecolor(0xe6, 0x9f, 0x00),
+                success: Style::new().truecolor(0x00, 0x72, 0xb2),
+                status: Style::new().truecolor(0x00, 0x9e, 0x73),
+                dim: Style::new().bright_black(),
+                heading: Color::Rgb {
+                    r: 0x56,
+                    g: 0xb4,
+                    b: 0xe9,
+                },
+                code: Color::Rgb {
+                    r: 0xe6,
+                    g: 0x9f,
+                    b: 0x00,
+                },
+                emphasis: Color::Rgb {
+                    r: 0xcc,
+                    g: 0x79,
+                    b: 0xa7,
+                },
+            },
+        }
+    }
+
+    /// Build a termimad skin matching this theme
+    pub fn skin(&self) -> MadSkin {
+        let mut skin = MadSkin::default();
+        skin.set_headers_fg(self.heading);
+        skin.bold.set_fg(self.emphasis);
+        skin.inline_code.set_fg(self.code);
+        skin.code_block.set_fg(self.code);
+        skin
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Deserialize)]
+    struct Settings {
+        theme: ThemeName,
+    }
+
+    #[test]
+    fn test_theme_name() {
+        let theme = |toml: &str| toml::from_str::<Settings>(toml).map(|settings| settings.theme);
+        assert_eq!(
+            theme("theme = \"high-contrast\"").unwrap(),
+            ThemeName::HighContrast
+        );
+        assert_eq!(
+            theme("theme = \"colorblind-safe\"").unwrap(),
+            ThemeName::ColorblindSafe
+        );
+        assert!(theme("theme = \"HighContrast\"").is_err());
+    }
+
+    #[test]
+    fn test_skin() {
+        let skin = Theme::new(ThemeName::HighContrast).skin();
+        assert_eq!(
+            skin.inline_code.object_style.foreground_color,
+            Some(Color::Cyan)
+        );
+        assert_eq!(skin.bold.object_style.foreground_color, Some(Color::White));
+    }
+}
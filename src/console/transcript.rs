@@ -0,0 +1,205 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use chrono::Local;
+use rig::completion::Message;
+use rig::message::{ToolResultContent, UserContent};
+
+/// Directory of the session transcripts, relative to the workspace
+pub const SESSIONS_DIR: &str = ".horse/sessions";
+
+/// Tool output kept in the conversation once its turn is over; larger
+/// outputs are cut down to their first `KEPT_BYTES`
+const KEPT_BYTES: usize = 8 * 1024;
+
+/// The full conversation of a REPL session, written to disk after every turn.
+///
+/// Once written, large tool outputs of finished turns are cut down in the
+/// in-memory history, so that memory (and the tokens sent with every
+/// request) stays bounded in long sessions. The transcript keeps everything
+/// for `/export`.
+#[derive(Debug)]
+pub struct Transcript {
+    path: PathBuf,
+    /// Messages of the history already written
+    written: usize,
+}
+
+impl Transcript {
+    /// A transcript for a new session in `base_dir`; the file is created
+    /// with the first turn
+    pub fn new(base_dir: &Path) -> Self {
+        let name = format!("{}.jsonl", Local::now().format("%Y%m%d-%H%M%S"));
+        Self {
+            path: base_dir.join(SESSIONS_DIR).join(name),
+            written: 0,
+        }
+    }
+
+    /// Append the messages added to `history` since the last call, then cut
+    /// down their large tool outputs.
+    ///
+    /// If writing fails, the history is left as is, so nothing is lost.
+    pub fn spill(&mut self, history: &mut [Message]) -> Result<()> {
+        let new = history.get_mut(self.written..).unwrap_or_default();
+        if !new.is_empty() {
+            if let Some(dir) = self.path.parent() {
+                crate::state::create_dir(dir)
+                    .with_context(|| format!("Failed to create {}", dir.display()))?;
+            }
+            let lines = new
+                .iter()
+                .map(|message| serde_json::to_string(message).map(|json| json + "\n"))
+                .collect::<Result<String, _>>()?;
+            OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&self.path)
+                .and_then(|mut file| file.write_all(lines.as_bytes()))
+                .with_context(|| format!("Failed to append to {}", self.path.display()))?;
+
+            for message in new.iter_mut() {
+                compact(message, &self.path);
+            }
+        }
+        self.written = history.len();
+        Ok(())
+    }
+
+    /// Forget that messages past the first `len` of the history were
+    /// written, after the history was cut back to `len`, so the messages
+    /// that take their place are written too. The file keeps the old ones.
+    pub fn rewind(&mut self, len: usize) {
+        self.written = self.written.min(len);
+    }
+
+    /// Where the conversation is written
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The full conversation, as written
+    pub fn load(&self) -> Result<Vec<Message>> {
+        match File::open(&self.path) {
+            Ok(file) => BufReader::new(file)
+                .lines()
+                .map(|line| Ok(serde_json::from_str(&line?)?))
+                .collect(),
+            // Nothing was asked yet
+            Err(_) => Ok(Vec::new()),
+        }
+    }
+
+    /// Write the full conversation to `dest` as JSON.
+    ///
+    /// Returns the number of messages written.
+    pub fn export(&self, dest: &Path) -> Result<usize> {
+        let messages = self.load()?;
+        fs::write(dest, serde_json::to_string_pretty(&messages)?)
+            .with_context(|| format!("Failed to write {}", dest.display()))?;
+        Ok(messages.len())
+    }
+}
+
+/// Cut the tool outputs of a message down to `KEPT_BYTES`, noting where the
+/// rest can be found
+fn compact(message: &mut Message, transcript: &Path) {
+    if let Message::User { content } = message {
+        for item in content.iter_mut() {
+            if let UserContent::ToolResult(result) = item {
+                for part in result.content.iter_mut() {
+                    if let ToolResultContent::Text(text) = part
+                        && text.text.len() > KEPT_BYTES
+                    {
+                        let mut end = KEPT_BYTES;
+                        while !text.text.is_char_boundary(end) {
+                            end -= 1;
+                        }
+                        let dropped = text.text.len() - end;
+                        text.text.truncate(end);
+                        text.text.push_str(&format!(
+                            "\n[... {dropped} more bytes dropped from the conversation to save \
+                             memory; they are kept in {}. Call the tool again if you need them.]",
+                            transcript.display()
+                        ));
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rig::OneOrMany;
+    use rig::message::{Text, ToolResult};
+
+    use super::*;
+
+    fn tool_result(output: String) -> Message {
+        Message::User {
+            content: OneOrMany::one(UserContent::ToolResult(ToolResult {
+                id: "call_1".to_string(),
+                call_id: None,
+                content: OneOrMany::one(ToolResultContent::Text(Text { text: output })),
+            })),
+        }
+    }
+
+    #[test]
+    fn test_spill() {
+        let base_dir =
+            std::env::temp_dir().join(format!("horse-transcript-{}", std::process::id()));
+        let mut transcript = Transcript::new(&base_dir);
+
+        let output = "x".repeat(3 * KEPT_BYTES);
+        let mut history = vec![
+            Message::user("What is in big.txt?"),
+            tool_result(output.clone()),
+            Message::assistant("Lots of x"),
+        ];
+        transcript.spill(&mut history).unwrap();
+
+        // The history keeps the head of the output, the transcript all of it
+        let kept = serde_json::to_string(&history[1]).unwrap();
+        assert!(kept.len() < KEPT_BYTES + 500);
+        assert!(kept.contains("more bytes dropped"));
+        assert_eq!(transcript.load().unwrap()[1], tool_result(output));
+
+        // Only new messages are appended
+        history.push(Message::user("Thanks"));
+        transcript.spill(&mut history).unwrap();
+        assert_eq!(transcript.load().unwrap().len(), 4);
+
+        let dest = base_dir.join("export.json");
+        assert_eq!(transcript.export(&dest).unwrap(), 4);
+        fs::remove_dir_all(&base_dir).unwrap();
+    }
+
+    #[test]
+    fn test_rewind() {
+        let base_dir =
+            std::env::temp_dir().join(format!("horse-transcript-rewind-{}", std::process::id()));
+        let mut transcript = Transcript::new(&base_dir);
+
+        let mut history = vec![
+            Message::user("Where is main?"),
+            Message::assistant("src/main.rs:1"),
+        ];
+        transcript.spill(&mut history).unwrap();
+
+        // An undone turn, then a new one of the same length
+        history.truncate(0);
+        transcript.rewind(history.len());
+        history.push(Message::user("Where is the config parsed?"));
+        history.push(Message::assistant("src/config.rs:140"));
+        transcript.spill(&mut history).unwrap();
+
+        let written = transcript.load().unwrap();
+        assert_eq!(written.len(), 4);
+        assert_eq!(written[2], Message::user("Where is the config parsed?"));
+        fs::remove_dir_all(&base_dir).unwrap();
+    }
+}
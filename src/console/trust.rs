@@ -0,0 +1,140 @@
+use std::fmt::Write as _;
+use std::fs;
+use std::io::{self, IsTerminal};
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+
+use crate::config;
+use crate::console::setup;
+
+/// Tools left out of the agent in an untrusted workspace: they run programs
+/// on its files, `rga`'s adapters included
+pub const UNTRUSTED_TOOLS: &[&str] = &["bash", "search_docs"];
+
+const TRUST_FILE: &str = "trusted.txt";
+
+/// Location of the trust registry, `~/.local/share/horse/trusted.txt` on
+/// Linux: one trusted directory per line
+pub fn registry_path() -> Option<PathBuf> {
+    dirs::data_dir().map(|dir| dir.join("horse").join(TRUST_FILE))
+}
+
+/// Whether `workspace`, or a directory containing it, is trusted
+pub fn is_trusted(workspace: &Path) -> bool {
+    registry_path().is_some_and(|registry| listed(&read(&registry), workspace))
+}
+
+/// Whether to trust `workspace`: as registered, or else as the user decides
+/// when asked in a terminal. Trust given is remembered.
+///
+/// Asked before the workspace config is read, since the programs it
+/// declares are only run in a trusted workspace.
+pub fn check(workspace: &Path, ask: bool) -> Result<bool> {
+    match (is_trusted(workspace), ask && io::stdin().is_terminal()) {
+        (true, _) => Ok(true),
+        (false, true) => {
+            let trusted = setup::confirm(
+                &format!(
+                    "Do you trust the files in {}? Untrusted workspaces are explored \
+                     without the {} tools, and without the hooks, MCP servers, plugins \
+                     and custom tools of their config",
+                    workspace.display(),
+                    UNTRUSTED_TOOLS.join(" and ")
+                ),
+                false,
+            )?;
+            if trusted {
+                trust(workspace)?;
+            }
+            Ok(trusted)
+        }
+        (false, false) => Ok(false),
+    }
+}
+
+/// Warn that the workspace is explored without what it isn't trusted with
+pub fn warn_untrusted() {
+    tracing::warn!(
+        "[!] Warning: Untrusted workspace, the {} tools and the [{}] sections of its config \
+         are disabled; run `horse trust` to trust it",
+        UNTRUSTED_TOOLS.join(" and "),
+        config::UNTRUSTED_SECTIONS.join("], [")
+    );
+}
+
+/// Add `workspace` to the trust registry
+pub fn trust(workspace: &Path) -> Result<()> {
+    let registry = registry_path().context("No data directory for the trust registry")?;
+    add(&registry, workspace)
+}
+
+/// Remove `workspace` from the trust registry; returns whether it was in it
+pub fn revoke(workspace: &Path) -> Result<bool> {
+    let registry = registry_path().context("No data directory for the trust registry")?;
+    remove(&registry, workspace)
+}
+
+fn read(registry: &Path) -> String {
+    fs::read_to_string(registry).unwrap_or_default()
+}
+
+fn listed(registry: &str, workspace: &Path) -> bool {
+    registry
+        .lines()
+        .any(|line| !line.is_empty() && workspace.starts_with(line))
+}
+
+fn add(registry: &Path, workspace: &Path) -> Result<()> {
+    let mut content = read(registry);
+    if !content.lines().any(|line| Path::new(line) == workspace) {
+        let _ = writeln!(content, "{}", workspace.display());
+        if let Some(dir) = registry.parent() {
+            fs::create_dir_all(dir)
+                .with_context(|| format!("Failed to create {}", dir.display()))?;
+        }
+        fs::write(registry, content)
+            .with_context(|| format!("Failed to write {}", registry.display()))?;
+    }
+    Ok(())
+}
+
+fn remove(registry: &Path, workspace: &Path) -> Result<bool> {
+    let content = read(registry);
+    let removed = content.lines().any(|line| Path::new(line) == workspace);
+    if removed {
+        let kept: String = content
+            .lines()
+            .filter(|line| Path::new(line) != workspace)
+            .map(|line| format!("{line}\n"))
+            .collect();
+        fs::write(registry, kept)
+            .with_context(|| format!("Failed to write {}", registry.display()))?;
+    }
+    Ok(removed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_registry() {
+        let dir = std::env::temp_dir().join(format!("horse-trust-test-{}", uuid::Uuid::new_v4()));
+        let registry = dir.join(TRUST_FILE);
+        let workspace = Path::new("/home/me/src/horse");
+
+        assert!(!listed(&read(&registry), workspace));
+        add(&registry, Path::new("/home/me/src")).unwrap();
+        add(&registry, Path::new("/home/me/src")).unwrap();
+        assert_eq!(read(&registry), "/home/me/src\n");
+        assert!(listed(&read(&registry), workspace));
+        assert!(!listed(&read(&registry), Path::new("/home/me/srcs")));
+
+        assert!(remove(&registry, Path::new("/home/me/src")).unwrap());
+        assert!(!remove(&registry, Path::new("/home/me/src")).unwrap());
+        assert!(!listed(&read(&registry), workspace));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,501 @@
+use std::io;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result};
+use ratatui::crossterm::event::{
+    self, DisableFocusChange, DisableMouseCapture, EnableFocusChange, EnableMouseCapture, Event,
+    KeyCode, KeyEvent, KeyEventKind, KeyModifiers, MouseEventKind,
+};
+use ratatui::crossterm::execute;
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, List, ListItem, Paragraph};
+use ratatui::{DefaultTerminal, Frame};
+use rig::agent::Agent;
+use rig::completion::{Message, Prompt, Usage};
+use rig::providers::anthropic;
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+use tracing::Instrument;
+
+use crate::agent::hooks::{AgentEvent, EventHook};
+use crate::agent::tools::Tools;
+use crate::agent::{TOOL_CONCURRENCY, lifecycle, telemetry};
+use crate::console::notify::NotifyConfig;
+use crate::console::output::UsageRecord;
+use crate::console::usage::UsageLog;
+
+const SPINNER_FRAMES: &[&str] = &["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"];
+
+/// Maximum number of tool calls kept in the sidebar
+const MAX_TOOL_ACTIVITY: usize = 200;
+
+/// Lines scrolled per mouse wheel notch
+const MOUSE_SCROLL_LINES: u16 = 3;
+
+/// Everything the TUI event loop reacts to
+enum TuiEvent {
+    Input(Event),
+    Agent(AgentEvent),
+    Response {
+        history: Vec<Message>,
+        result: Result<String, String>,
+    },
+}
+
+enum Role {
+    User,
+    Assistant,
+    Error,
+}
+
+struct Entry {
+    role: Role,
+    text: String,
+}
+
+enum ToolStatus {
+    Running,
+    Done,
+    Failed,
+}
+
+struct ToolActivity {
+    name: String,
+    args: String,
+    status: ToolStatus,
+}
+
+/// Full-screen terminal interface with a scrollable conversation, a persistent
+/// input box and a sidebar showing tool activity, token usage and cost.
+pub struct Tui {
+    agent: Arc<Agent<anthropic::completion::CompletionModel>>,
+    history: Vec<Message>,
+    entries: Vec<Entry>,
+    input: String,
+    tools: Vec<ToolActivity>,
+    tool_calls: u64,
+    usage: Usage,
+    /// Number of lines the conversation is scrolled up from the bottom
+    scroll: u16,
+    /// Height of the conversation viewport during the last draw
+    viewport_height: u16,
+    busy: bool,
+    /// When the running turn was submitted
+    turn_started: Option<Instant>,
+    /// Usage and tool call count when the running turn was submitted
+    turn_baseline: (Usage, u64),
+    usage_log: Option<UsageLog>,
+    /// Whether the terminal has focus, as reported by focus change events
+    focused: bool,
+    notify: NotifyConfig,
+    tick: usize,
+    quit: bool,
+}
+
+impl Tui {
+    pub fn new(agent: Agent<anthropic::completion::CompletionModel>) -> Self {
+        Self {
+            agent: Arc::new(agent),
+            history: Vec::new(),
+            entries: Vec::new(),
+            input: String::new(),
+            tools: Vec::new(),
+            tool_calls: 0,
+            usage: Usage::default(),
+            scroll: 0,
+            viewport_height: 0,
+            busy: false,
+            turn_started: None,
+            turn_baseline: (Usage::default(), 0),
+            usage_log: None,
+            focused: true,
+            notify: NotifyConfig::default(),
+            tick: 0,
+            quit: false,
+        }
+    }
+
+    /// Set how the user is notified when a long turn completes while unfocused
+    pub fn with_notify(mut self, notify: NotifyConfig) -> Self {
+        self.notify = notify;
+        self
+    }
+
+    /// Append a record of every turn to the usage log
+    pub fn with_usage_log(mut self, usage_log: UsageLog) -> Self {
+        self.usage_log = Some(usage_log);
+        self
+    }
+
+    pub async fn run(&mut self) -> Result<()> {
+        let mut terminal = ratatui::init();
+        execute!(io::stdout(), EnableMouseCapture, EnableFocusChange)
+            .context("Failed to enable mouse capture")?;
+
+        let result = self.event_loop(&mut terminal).await;
+
+        execute!(io::stdout(), DisableMouseCapture, DisableFocusChange)
+            .context("Failed to disable mouse capture")?;
+        ratatui::restore();
+
+        result
+    }
+
+    async fn event_loop(&mut self, terminal: &mut DefaultTerminal) -> Result<()> {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        spawn_input_reader(tx.clone());
+
+        let mut ticker = tokio::time::interval(Duration::from_millis(80));
+
+        while !self.quit {
+            terminal
+                .draw(|frame| self.draw(frame))
+                .context("Failed to draw the terminal UI")?;
+
+            tokio::select! {
+                event = rx.recv() => match event {
+                    Some(event) => self.handle_event(event, &tx),
+                    None => self.quit = true,
+                },
+                _ = ticker.tick() => {
+                    self.tick = self.tick.wrapping_add(1);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn handle_event(&mut self, event: TuiEvent, tx: &UnboundedSender<TuiEvent>) {
+        match event {
+            TuiEvent::Input(Event::Key(key)) if key.kind == KeyEventKind::Press => {
+                self.handle_key(key, tx);
+            }
+            TuiEvent::Input(Event::Mouse(mouse)) => match mouse.kind {
+                MouseEventKind::ScrollUp => self.scroll_up(MOUSE_SCROLL_LINES),
+                MouseEventKind::ScrollDown => self.scroll_down(MOUSE_SCROLL_LINES),
+                _ => {}
+            },
+            TuiEvent::Input(Event::FocusGained) => self.focused = true,
+            TuiEvent::Input(Event::FocusLost) => self.focused = false,
+            TuiEvent::Input(_) => {}
+            TuiEvent::Agent(event) => self.handle_agent_event(event),
+            TuiEvent::Response { history, result } => {
+                self.history = history;
+                self.busy = false;
+                if let Some(started) = self.turn_started.take() {
+                    self.notify
+                        .turn_finished(started.elapsed(), Some(self.focused));
+                    if let Some(usage_log) = &self.usage_log {
+                        let (usage, tool_calls) = self.turn_baseline;
+                        usage_log.record(
+                            UsageRecord::from(self.usage).since(usage.into()),
+                            usize::try_from(self.tool_calls - tool_calls).unwrap_or_default(),
+                            started.elapsed(),
+                        );
+                    }
+                }
+                self.scroll = 0;
+                match result {
+                    Ok(text) => self.entries.push(Entry {
+                        role: Role::Assistant,
+                        text,
+                    }),
+                    Err(text) => self.entries.push(Entry {
+                        role: Role::Error,
+                        text,
+                    }),
+                }
+            }
+        }
+    }
+
+    fn handle_key(&mut self, key: KeyEvent, tx: &UnboundedSender<TuiEvent>) {
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        let page = self.viewport_height.max(2) / 2;
+
+        match key.code {
+            KeyCode::Char('c' | 'd') if ctrl => self.quit = true,
+            KeyCode::Esc => self.quit = true,
+            KeyCode::Char(c) if !ctrl => self.input.push(c),
+            KeyCode::Backspace => {
+                self.input.pop();
+            }
+            KeyCode::Enter if !self.busy && !self.input.trim().is_empty() => self.submit(tx),
+            KeyCode::Up => self.scroll_up(1),
+            KeyCode::Down => self.scroll_down(1),
+            KeyCode::PageUp => self.scroll_up(page),
+            KeyCode::PageDown => self.scroll_down(page),
+            KeyCode::End => self.scroll = 0,
+            _ => {}
+        }
+    }
+
+    fn handle_agent_event(&mut self, event: AgentEvent) {
+        match event {
+            AgentEvent::ToolCall { name, args } => {
+                let args = Tools::try_from(name.as_str())
+                    .map(|tool| tool.extract_display_args(&args))
+                    .unwrap_or(args);
+                self.tool_calls += 1;
+                if self.tools.len() >= MAX_TOOL_ACTIVITY {
+                    self.tools.remove(0);
+                }
+                self.tools.push(ToolActivity {
+                    name,
+                    args,
+                    status: ToolStatus::Running,
+                });
+            }
+            AgentEvent::ToolResult { name, error, .. } => {
+                if let Some(activity) = self
+                    .tools
+                    .iter_mut()
+                    .rev()
+                    .find(|t| t.name == name && matches!(t.status, ToolStatus::Running))
+                {
+                    activity.status = if error.is_some() {
+                        ToolStatus::Failed
+                    } else {
+                        ToolStatus::Done
+                    };
+                }
+            }
+            AgentEvent::Usage(usage) => self.usage += usage,
+        }
+    }
+
+    /// Send the current input to the agent in the background
+    fn submit(&mut self, tx: &UnboundedSender<TuiEvent>) {
+        let prompt = std::mem::take(&mut self.input).trim().to_string();
+        self.entries.push(Entry {
+            role: Role::User,
+            text: prompt.clone(),
+        });
+        self.busy = true;
+        self.turn_started = Some(Instant::now());
+        self.turn_baseline = (self.usage, self.tool_calls);
+        self.scroll = 0;
+
+        let agent = Arc::clone(&self.agent);
+        let mut history = std::mem::take(&mut self.history);
+        let tx = tx.clone();
+        let hook = EventHook::new(forward_agent_events(tx.clone()));
+
+        tokio::spawn(async move {
+            let result = agent
+                .prompt(prompt.as_str())
+                .with_history(&mut history)
+                .with_tool_concurrency(TOOL_CONCURRENCY)
+                .with_hook(hook)
+                .into_future()
+                .instrument(telemetry::turn_span())
+                .await
+                .map_err(|e| format!("{e:#}"));
+            lifecycle::turn_end(&prompt, &result).await;
+            let _ = tx.send(TuiEvent::Response { history, result });
+        });
+    }
+
+    fn scroll_up(&mut self, lines: u16) {
+        self.scroll = self.scroll.saturating_add(lines);
+    }
+
+    fn scroll_down(&mut self, lines: u16) {
+        self.scroll = self.scroll.saturating_sub(lines);
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [main, sidebar] =
+            Layout::horizontal([Constraint::Percentage(72), Constraint::Percentage(28)])
+                .areas(frame.area());
+        let [conversation, input] =
+            Layout::vertical([Constraint::Min(3), Constraint::Length(3)]).areas(main);
+        let [tools, usage] =
+            Layout::vertical([Constraint::Min(3), Constraint::Length(7)]).areas(sidebar);
+
+        self.draw_conversation(frame, conversation);
+        self.draw_input(frame, input);
+        self.draw_tools(frame, tools);
+        self.draw_usage(frame, usage);
+    }
+
+    fn draw_conversation(&mut self, frame: &mut Frame, area: Rect) {
+        let width = area.width.saturating_sub(2).max(1) as usize;
+        let mut lines: Vec<Line> = Vec::new();
+
+        for entry in &self.entries {
+            let (label, style) = match entry.role {
+                Role::User => ("You", Style::new().fg(Color::Cyan)),
+                Role::Assistant => ("Horse", Style::new().fg(Color::Green)),
+                Role::Error => ("Error", Style::new().fg(Color::Red)),
+            };
+            lines.push(Line::styled(label, style.add_modifier(Modifier::BOLD)));
+            lines.extend(
+                textwrap::wrap(&entry.text, width)
+                    .into_iter()
+                    .map(|line| Line::raw(line.into_owned())),
+            );
+            lines.push(Line::default());
+        }
+
+        if self.busy {
+            let frame_str = SPINNER_FRAMES[self.tick % SPINNER_FRAMES.len()];
+            lines.push(Line::styled(
+                format!("{frame_str} Processing"),
+                Style::new().fg(Color::Cyan),
+            ));
+        }
+
+        self.viewport_height = area.height.saturating_sub(2);
+        let total = u16::try_from(lines.len()).unwrap_or(u16::MAX);
+        let max_scroll = total.saturating_sub(self.viewport_height);
+        self.scroll = self.scroll.min(max_scroll);
+
+        let title = if self.scroll > 0 {
+            format!(
+                " Conversation (scrolled {} lines, End to follow) ",
+                self.scroll
+            )
+        } else {
+            " Conversation ".to_string()
+        };
+
+        let paragraph = Paragraph::new(lines)
+            .block(Block::bordered().title(title))
+            .scroll((max_scroll - self.scroll, 0));
+        frame.render_widget(paragraph, area);
+    }
+
+    fn draw_input(&self, frame: &mut Frame, area: Rect) {
+        let title = if self.busy {
+            " Waiting for the agent... (Esc to quit) "
+        } else {
+            " Ask (Enter to send, Esc to quit) "
+        };
+        let inner_width = area.width.saturating_sub(2) as usize;
+        // Keep the end of long inputs visible
+        let visible: String = {
+            let chars: Vec<char> = self.input.chars().collect();
+            chars[chars.len().saturating_sub(inner_width.saturating_sub(1))..]
+                .iter()
+                .collect()
+        };
+        let cursor_x = area.x + 1 + visible.chars().count() as u16;
+
+        frame.render_widget(
+            Paragraph::new(visible).block(Block::bordered().title(title)),
+            area,
+        );
+        frame.set_cursor_position((cursor_x, area.y + 1));
+    }
+
+    fn draw_tools(&self, frame: &mut Frame, area: Rect) {
+        let capacity = area.height.saturating_sub(2) as usize;
+        let items: Vec<ListItem> = self
+            .tools
+            .iter()
+            .skip(self.tools.len().saturating_sub(capacity))
+            .map(|activity| {
+                let (marker, style) = match activity.status {
+                    ToolStatus::Running => (
+                        SPINNER_FRAMES[self.tick % SPINNER_FRAMES.len()],
+                        Style::new().fg(Color::Cyan),
+                    ),
+                    ToolStatus::Done => ("✓", Style::new().fg(Color::Green)),
+                    ToolStatus::Failed => ("✗", Style::new().fg(Color::Red)),
+                };
+                ListItem::new(Line::from(vec![
+                    Span::styled(format!("{marker} "), style),
+                    Span::styled(
+                        format!("{}({})", activity.name, activity.args),
+                        Style::new().fg(Color::DarkGray),
+                    ),
+                ]))
+            })
+            .collect();
+
+        frame.render_widget(
+            List::new(items).block(Block::bordered().title(" Tools ")),
+            area,
+        );
+    }
+
+    fn draw_usage(&self, frame: &mut Frame, area: Rect) {
+        let label = Style::new().fg(Color::DarkGray);
+        let value = Style::new().fg(Color::Cyan);
+        let row = |name: &'static str, text: String| {
+            Line::from(vec![
+                Span::styled(format!("{name:<8}"), label),
+                Span::styled(text, value),
+            ])
+        };
+        let cost = self
+            .usage_log
+            .as_ref()
+            .and_then(|usage_log| usage_log.cost(self.usage.into()));
+
+        let lines = vec![
+            row("input", self.usage.input_tokens.to_string()),
+            row("cached", self.usage.cached_input_tokens.to_string()),
+            row("output", self.usage.output_tokens.to_string()),
+            row("calls", self.tool_calls.to_string()),
+            row("cost", cost_label(cost)),
+        ];
+
+        frame.render_widget(
+            Paragraph::new(lines).block(Block::bordered().title(" Usage ")),
+            area,
+        );
+    }
+}
+
+/// Estimated cost of the session so far, for models with known pricing
+fn cost_label(cost: Option<f64>) -> String {
+    cost.map_or_else(|| "n/a".to_string(), |cost| format!("${cost:.2}"))
+}
+
+/// Read terminal input on a dedicated thread since crossterm's reader blocks
+fn spawn_input_reader(tx: UnboundedSender<TuiEvent>) {
+    std::thread::spawn(move || {
+        while let Ok(event) = event::read() {
+            if tx.send(TuiEvent::Input(event)).is_err() {
+                break;
+            }
+        }
+    });
+}
+
+/// Create a sender for [`EventHook`] whose events are forwarded into the TUI event loop
+fn forward_agent_events(tx: UnboundedSender<TuiEvent>) -> UnboundedSender<AgentEvent> {
+    let (agent_tx, mut agent_rx): (_, UnboundedReceiver<AgentEvent>) = mpsc::unbounded_channel();
+
+    tokio::spawn(async move {
+        while let Some(event) = agent_rx.recv().await {
+            if tx.send(TuiEvent::Agent(event)).is_err() {
+                break;
+            }
+        }
+    });
+
+    agent_tx
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cost_label() {
+        let usage_log = UsageLog::new("claude-sonnet-4-0", std::path::Path::new("."));
+        let tokens = UsageRecord {
+            input_tokens: 1_000_000,
+            output_tokens: 100_000,
+            cached_input_tokens: 2_000_000,
+        };
+        assert_eq!(cost_label(usage_log.cost(tokens)), "$5.10");
+        assert_eq!(cost_label(None), "n/a");
+    }
+}
@@ -0,0 +1,340 @@
+use std::collections::BTreeMap;
+use std::fs::{self, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::agent::models;
+use crate::console::output::UsageRecord;
+use crate::console::{colors, prompt, stats};
+
+/// Name of the usage log in horse's data directory
+const USAGE_FILE: &str = "usage.jsonl";
+
+/// One line of the usage log, written after every turn
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TurnRecord {
+    pub timestamp: DateTime<Utc>,
+    pub workspace: PathBuf,
+    pub model: String,
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    pub cached_input_tokens: u64,
+    /// Estimated cost in USD, absent for models without known pricing
+    pub cost_usd: Option<f64>,
+    pub tool_calls: usize,
+    pub duration_ms: u64,
+}
+
+/// The usage log of this process, set at startup, for the requests of
+/// sub-agents and helper prompts
+static HELPERS: OnceLock<UsageLog> = OnceLock::new();
+
+/// Location of the usage log, `~/.local/share/horse/usage.jsonl` on Linux
+pub fn default_path() -> Option<PathBuf> {
+    dirs::data_dir().map(|dir| dir.join("horse").join(USAGE_FILE))
+}
+
+/// Log the requests of sub-agents and helper prompts of this process to
+/// `log`
+pub fn init(log: &UsageLog) {
+    let _ = HELPERS.set(log.clone());
+}
+
+/// Log a request of a sub-agent or helper prompt made with `model`
+pub fn record_helper(model: &str, tokens: UsageRecord, tool_calls: usize, duration: Duration) {
+    if let Some(log) = HELPERS.get() {
+        log.record_as(model, tokens, tool_calls, duration);
+    }
+}
+
+/// Appends a record per turn to the usage log
+#[derive(Debug, Clone)]
+pub struct UsageLog {
+    path: Option<PathBuf>,
+    model: String,
+    workspace: PathBuf,
+}
+
+impl UsageLog {
+    pub fn new(model: &str, workspace: &Path) -> Self {
+        Self {
+            path: default_path(),
+            model: model.to_string(),
+            workspace: workspace.to_path_buf(),
+        }
+    }
+
+    /// Estimated cost in USD of `tokens` spent with the session's model,
+    /// as logged for its turns
+    pub fn cost(&self, tokens: UsageRecord) -> Option<f64> {
+        cost(&self.model, tokens)
+    }
+
+    /// Log a finished turn; failures are reported but never interrupt the session
+    pub fn record(&self, tokens: UsageRecord, tool_calls: usize, duration: Duration) {
+        self.record_as(&self.model, tokens, tool_calls, duration);
+    }
+
+    /// Log a request made with `model` rather than the session's
+    fn record_as(&self, model: &str, tokens: UsageRecord, tool_calls: usize, duration: Duration) {
+        let record = TurnRecord {
+            timestamp: Utc::now(),
+            workspace: self.workspace.clone(),
+            model: model.to_string(),
+            input_tokens: tokens.input_tokens,
+            output_tokens: tokens.output_tokens,
+            cached_input_tokens: tokens.cached_input_tokens,
+            cost_usd: cost(model, tokens),
+            tool_calls,
+            duration_ms: u64::try_from(duration.as_millis()).unwrap_or(u64::MAX),
+        };
+
+        if let Some(path) = &self.path
+            && let Err(e) = append(path, &record)
+        {
+            tracing::warn!("[!] Warning: Failed to write usage log: {e:#}");
+        }
+    }
+}
+
+/// Estimated cost in USD of `tokens` spent with `model`
+fn cost(model: &str, tokens: UsageRecord) -> Option<f64> {
+    models::estimate_cost(
+        model,
+        tokens.input_tokens,
+        tokens.output_tokens,
+        tokens.cached_input_tokens,
+    )
+}
+
+fn append(path: &Path, record: &TurnRecord) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+    }
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .with_context(|| format!("Failed to open {}", path.display()))?;
+    let mut line = serde_json::to_string(record)?;
+    line.push('\n');
+    file.write_all(line.as_bytes())?;
+    Ok(())
+}
+
+/// Parse a look-back period such as `30m`, `12h`, `7d` or `4w`
+pub fn parse_since(value: &str) -> Result<Duration, String> {
+    let split = value.len() - value.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+    let (amount, unit) = value.split_at(split);
+    let amount: u64 = amount
+        .parse()
+        .map_err(|_| format!("expected a number followed by m, h, d or w, got `{value}`"))?;
+    let unit_secs = match unit {
+        "m" => Ok(60),
+        "h" => Ok(60 * 60),
+        "d" => Ok(24 * 60 * 60),
+        "w" => Ok(7 * 24 * 60 * 60),
+        _ => Err(format!("unknown unit `{unit}`, expected m, h, d or w")),
+    }?;
+    Ok(Duration::from_secs(amount * unit_secs))
+}
+
+/// Aggregated usage of a set of turns
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Totals {
+    pub turns: usize,
+    pub tool_calls: usize,
+    pub duration: Duration,
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    pub cached_input_tokens: u64,
+    pub cost_usd: f64,
+}
+
+impl Totals {
+    fn add(&mut self, record: &TurnRecord) {
+        self.turns += 1;
+        self.tool_calls += record.tool_calls;
+        self.duration += Duration::from_millis(record.duration_ms);
+        self.input_tokens += record.input_tokens;
+        self.output_tokens += record.output_tokens;
+        self.cached_input_tokens += record.cached_input_tokens;
+        self.cost_usd += record.cost_usd.unwrap_or_default();
+    }
+}
+
+/// Usage totals overall and grouped by workspace and model
+#[derive(Debug, Default)]
+pub struct Summary {
+    pub total: Totals,
+    pub by_workspace: BTreeMap<PathBuf, Totals>,
+    pub by_model: BTreeMap<String, Totals>,
+}
+
+/// Aggregate the records logged at or after `since`
+pub fn summarize<'a>(
+    records: impl IntoIterator<Item = &'a TurnRecord>,
+    since: DateTime<Utc>,
+) -> Summary {
+    let mut summary = Summary::default();
+
+    for record in records.into_iter().filter(|r| r.timestamp >= since) {
+        summary.total.add(record);
+        summary
+            .by_workspace
+            .entry(record.workspace.clone())
+            .or_default()
+            .add(record);
+        summary
+            .by_model
+            .entry(record.model.clone())
+            .or_default()
+            .add(record);
+    }
+
+    summary
+}
+
+/// Read the usage log, skipping lines that can't be parsed
+fn read_records(path: &Path) -> Result<Vec<TurnRecord>> {
+    match fs::File::open(path) {
+        Ok(file) => Ok(BufReader::new(file)
+            .lines()
+            .map_while(Result::ok)
+            .filter_map(|line| serde_json::from_str(&line).ok())
+            .collect()),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(e).with_context(|| format!("Failed to read {}", path.display())),
+    }
+}
+
+/// Print usage aggregated over the given look-back period
+pub fn report(period: Duration) -> Result<()> {
+    let path = default_path().context("Cannot determine the data directory")?;
+    let records = read_records(&path)?;
+    let since = Utc::now() - chrono::Duration::from_std(period)?;
+    let summary = summarize(&records, since);
+
+    println!(
+        "Usage since {}",
+        colors::color_status(since.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M"))
+    );
+    println!("{}", format_totals(&summary.total));
+
+    if summary.total.turns > 0 {
+        println!("\nBy workspace:");
+        for (workspace, totals) in &summary.by_workspace {
+            println!("  {}  {}", format_line(totals), workspace.display());
+        }
+        println!("\nBy model:");
+        for (model, totals) in &summary.by_model {
+            println!("  {}  {model}", format_line(totals));
+        }
+    }
+
+    Ok(())
+}
+
+fn format_totals(totals: &Totals) -> String {
+    [
+        format!(
+            "  {} turns, {} tool calls, {}",
+            totals.turns,
+            totals.tool_calls,
+            stats::format_duration(totals.duration)
+        ),
+        format!(
+            "  {} input, {} output, {} cached tokens",
+            prompt::format_token_count(totals.input_tokens),
+            prompt::format_token_count(totals.output_tokens),
+            prompt::format_token_count(totals.cached_input_tokens)
+        ),
+        format!(
+            "  Estimated cost: {}",
+            colors::color_prompt_number(format!("${:.2}", totals.cost_usd))
+        ),
+    ]
+    .join("\n")
+}
+
+fn format_line(totals: &Totals) -> String {
+    format!(
+        "{:>9}  {:>5} turns",
+        format!("${:.2}", totals.cost_usd),
+        totals.turns
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_since() {
+        assert_eq!(parse_since("30m"), Ok(Duration::from_secs(30 * 60)));
+        assert_eq!(parse_since("7d"), Ok(Duration::from_secs(7 * 24 * 3600)));
+        assert!(parse_since("7").is_err());
+        assert!(parse_since("d").is_err());
+        assert!(parse_since("3y").is_err());
+    }
+
+    #[test]
+    fn test_record_as() {
+        let path = std::env::temp_dir().join(format!("horse-usage-test-{}", uuid::Uuid::new_v4()));
+        let log = UsageLog {
+            path: Some(path.clone()),
+            model: "claude-sonnet-4-0".to_string(),
+            workspace: PathBuf::from("/a"),
+        };
+        let tokens = UsageRecord {
+            input_tokens: 100,
+            output_tokens: 10,
+            cached_input_tokens: 0,
+        };
+        log.record(tokens, 1, Duration::from_secs(2));
+        log.record_as("claude-haiku-4-5", tokens, 0, Duration::from_secs(1));
+
+        let models: Vec<String> = read_records(&path)
+            .unwrap()
+            .into_iter()
+            .map(|record| record.model)
+            .collect();
+        assert_eq!(models, ["claude-sonnet-4-0", "claude-haiku-4-5"]);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_summarize() {
+        let now = Utc::now();
+        let record = |days: i64, workspace: &str, cost: Option<f64>| TurnRecord {
+            timestamp: now - chrono::Duration::days(days),
+            workspace: PathBuf::from(workspace),
+            model: "claude-sonnet-4-0".to_string(),
+            input_tokens: 100,
+            output_tokens: 10,
+            cached_input_tokens: 1000,
+            cost_usd: cost,
+            tool_calls: 2,
+            duration_ms: 1500,
+        };
+        let records = [
+            record(1, "/a", Some(0.5)),
+            record(2, "/b", None),
+            record(10, "/a", Some(4.0)),
+        ];
+
+        let summary = summarize(&records, now - chrono::Duration::days(7));
+        assert_eq!(summary.total.turns, 2);
+        assert_eq!(summary.total.tool_calls, 4);
+        assert_eq!(summary.total.duration, Duration::from_secs(3));
+        assert_eq!(summary.total.cost_usd, 0.5);
+        assert_eq!(summary.by_workspace.len(), 2);
+        assert_eq!(summary.by_model["claude-sonnet-4-0"].turns, 2);
+    }
+}
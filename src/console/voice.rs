@@ -0,0 +1,229 @@
+use std::path::PathBuf;
+use std::process::Stdio;
+use std::sync::LazyLock;
+
+use anyhow::{Context, Result, bail};
+use regex::Regex;
+use serde::Deserialize;
+use tokio::process::Command;
+
+use crate::console::{colors, editor::LineEditor, i18n, spinner::create_spinner};
+
+/// Sample rate whisper.cpp expects
+const SAMPLE_RATE: u32 = 16_000;
+
+/// Recordings shorter than this are taken as nothing said
+const MIN_AUDIO_BYTES: usize = SAMPLE_RATE as usize / 2; // 0.25s of 16-bit mono
+
+/// Markers whisper.cpp puts in place of speech, e.g. `[BLANK_AUDIO]` or
+/// `(keyboard clicking)`
+static NON_SPEECH: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\[[^\]]*\]|\([^)]*\)").expect("Invalid marker regex"));
+
+/// `[voice]` config section
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct VoiceConfig {
+    /// whisper.cpp model file, e.g. `~/.local/share/whisper/ggml-base.en.bin`
+    pub model: Option<PathBuf>,
+    /// whisper.cpp command line program
+    pub whisper: String,
+    /// Spoken language, `auto` to detect it; whisper.cpp's default (English)
+    /// unless set
+    pub language: Option<String>,
+    /// Command writing 16kHz mono signed 16-bit raw audio from the microphone
+    /// to stdout until killed
+    pub recorder: Vec<String>,
+}
+
+impl Default for VoiceConfig {
+    fn default() -> Self {
+        let recorder: &[&str] = if cfg!(target_os = "linux") {
+            &[
+                "arecord", "-q", "-f", "S16_LE", "-r", "16000", "-c", "1", "-t", "raw",
+            ]
+        } else {
+            &[
+                "rec", "-q", "-t", "raw", "-r", "16000", "-c", "1", "-b", "16", "-e", "signed", "-",
+            ]
+        };
+        Self {
+            model: None,
+            whisper: "whisper-cli".to_string(),
+            language: None,
+            recorder: recorder.iter().map(ToString::to_string).collect(),
+        }
+    }
+}
+
+/// Voice input of the REPL: records the microphone and transcribes it
+/// locally with whisper.cpp into the prompt line
+#[derive(Debug)]
+pub struct Voice {
+    config: VoiceConfig,
+    model: PathBuf,
+}
+
+impl Voice {
+    pub fn new(config: VoiceConfig) -> Result<Self> {
+        let model = config.model.clone().context(
+            "Voice input needs a whisper.cpp model: set [voice] model, e.g. to ggml-base.en.bin",
+        )?;
+        if !model.is_file() {
+            bail!("The whisper.cpp model {} doesn't exist", model.display());
+        }
+        if config.recorder.is_empty() {
+            bail!("[voice] recorder is empty");
+        }
+        Ok(Self { config, model })
+    }
+
+    /// Record until the user presses Enter and put the transcript on a
+    /// prompt line for editing.
+    ///
+    /// Returns the edited line, or `None` if nothing was said or the user
+    /// cancelled.
+    pub async fn dictate(&self, editor: &mut LineEditor, prompt: &str) -> Result<Option<String>> {
+        let audio = self.record(editor).await?;
+        if audio.len() < MIN_AUDIO_BYTES {
+            Ok(None)
+        } else {
+            let spinner = create_spinner(i18n::tr("Transcribing"));
+            let text = self.transcribe(&audio).await;
+            spinner.finish_and_clear();
+            match text?.as_str() {
+                "" => {
+                    eprintln!("{}", colors::color_warning(i18n::tr(">> Heard nothing")));
+                    Ok(None)
+                }
+                text => editor.edit_line(prompt, text),
+            }
+        }
+    }
+
+    /// Raw audio recorded until the user presses Enter
+    async fn record(&self, editor: &mut LineEditor) -> Result<Vec<u8>> {
+        let path = std::env::temp_dir().join(format!("horse-voice-{}.raw", uuid::Uuid::new_v4()));
+        let file = std::fs::File::create(&path)
+            .with_context(|| format!("Failed to create {}", path.display()))?;
+        let program = &self.config.recorder[0];
+        let mut child = Command::new(program)
+            .args(&self.config.recorder[1..])
+            .stdout(Stdio::from(file))
+            .stderr(Stdio::null())
+            .kill_on_drop(true)
+            .spawn()
+            .with_context(|| {
+                format!("Failed to start {program}; install it or set [voice] recorder")
+            })?;
+
+        eprintln!(
+            "{}",
+            colors::color_status(i18n::tr(">> Listening, press Enter to stop"))
+        );
+        let stopped = editor.read_line("");
+        child.kill().await.ok();
+        let audio = std::fs::read(&path);
+        std::fs::remove_file(&path).ok();
+        stopped?;
+        Ok(audio?)
+    }
+
+    async fn transcribe(&self, audio: &[u8]) -> Result<String> {
+        let path = std::env::temp_dir().join(format!("horse-voice-{}.wav", uuid::Uuid::new_v4()));
+        std::fs::write(&path, wav(audio))
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+        let mut command = Command::new(&self.config.whisper);
+        command
+            .arg("-m")
+            .arg(&self.model)
+            .arg("-f")
+            .arg(&path)
+            .args(["--no-timestamps", "--no-prints"]);
+        if let Some(language) = &self.config.language {
+            command.args(["-l", language]);
+        }
+        let output = command.output().await;
+        std::fs::remove_file(&path).ok();
+
+        let output = output.with_context(|| {
+            format!(
+                "Failed to run {}; install whisper.cpp or set [voice] whisper",
+                self.config.whisper
+            )
+        })?;
+        if output.status.success() {
+            Ok(clean_transcript(&String::from_utf8_lossy(&output.stdout)))
+        } else {
+            bail!(
+                "{} failed: {}",
+                self.config.whisper,
+                String::from_utf8_lossy(&output.stderr).trim()
+            )
+        }
+    }
+}
+
+/// 16kHz mono 16-bit PCM wrapped in a WAV header
+fn wav(pcm: &[u8]) -> Vec<u8> {
+    let len = pcm.len() as u32;
+    let mut out = Vec::with_capacity(pcm.len() + 44);
+    out.extend_from_slice(b"RIFF");
+    out.extend_from_slice(&(36 + len).to_le_bytes());
+    out.extend_from_slice(b"WAVEfmt ");
+    out.extend_from_slice(&16u32.to_le_bytes());
+    out.extend_from_slice(&1u16.to_le_bytes()); // PCM
+    out.extend_from_slice(&1u16.to_le_bytes()); // mono
+    out.extend_from_slice(&SAMPLE_RATE.to_le_bytes());
+    out.extend_from_slice(&(SAMPLE_RATE * 2).to_le_bytes()); // bytes per second
+    out.extend_from_slice(&2u16.to_le_bytes()); // bytes per sample
+    out.extend_from_slice(&16u16.to_le_bytes()); // bits per sample
+    out.extend_from_slice(b"data");
+    out.extend_from_slice(&len.to_le_bytes());
+    out.extend_from_slice(pcm);
+    out
+}
+
+/// The spoken text of whisper.cpp's output on one line
+fn clean_transcript(output: &str) -> String {
+    NON_SPEECH
+        .replace_all(output, " ")
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wav() {
+        let out = wav(&[1, 0, 2, 0]);
+        assert_eq!(out.len(), 48);
+        assert_eq!(&out[..4], b"RIFF");
+        assert_eq!(u32::from_le_bytes(out[4..8].try_into().unwrap()), 40);
+        assert_eq!(u32::from_le_bytes(out[24..28].try_into().unwrap()), 16_000);
+        assert_eq!(u32::from_le_bytes(out[40..44].try_into().unwrap()), 4);
+        assert_eq!(&out[44..], [1, 0, 2, 0]);
+    }
+
+    #[test]
+    fn test_clean_transcript() {
+        assert_eq!(
+            clean_transcript(" Where is the retry logic\n of the HTTP client? [BLANK_AUDIO]\n"),
+            "Where is the retry logic of the HTTP client?"
+        );
+        assert_eq!(clean_transcript("[BLANK_AUDIO]\n (keyboard clicking)"), "");
+    }
+
+    #[test]
+    fn test_new() {
+        assert!(Voice::new(VoiceConfig::default()).is_err());
+        let config = VoiceConfig {
+            model: Some(PathBuf::from("/nonexistent/ggml-base.en.bin")),
+            ..VoiceConfig::default()
+        };
+        assert!(Voice::new(config).is_err());
+    }
+}
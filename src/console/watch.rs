@@ -0,0 +1,245 @@
+use std::path::{Component, Path, PathBuf};
+use std::process::ExitCode;
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result};
+use notify::{Event, EventKind, RecursiveMode, Watcher};
+use rig::agent::Agent;
+use rig::completion::Prompt;
+use rig::providers::anthropic;
+use similar::{ChangeTag, TextDiff};
+use tokio::sync::mpsc;
+use tokio::time::timeout;
+use tracing::Instrument;
+
+use crate::agent::hooks::ProgressHook;
+use crate::agent::{TOOL_CONCURRENCY, lifecycle, telemetry};
+use crate::console::usage::UsageLog;
+use crate::console::{citations, colors, markdown};
+
+/// Directories whose changes don't trigger a new answer: version control
+/// internals, build output, and horse's own logs and transcripts
+pub(crate) const IGNORED_DIRS: &[&str] = &[".git", ".horse", "target", "node_modules"];
+
+/// Answer `query`, then answer it again whenever files under `base_dir`
+/// change, once no change has happened for `debounce`. Prints the first
+/// answer in full and then how each answer differs from the previous one.
+///
+/// Runs until interrupted.
+pub async fn run(
+    agent: &Agent<anthropic::completion::CompletionModel>,
+    base_dir: &Path,
+    query: &str,
+    debounce: Duration,
+    usage_log: &UsageLog,
+) -> Result<ExitCode> {
+    let (tx, mut changes) = mpsc::unbounded_channel();
+    let root = base_dir.to_path_buf();
+    let mut watcher = notify::recommended_watcher(move |event: notify::Result<Event>| {
+        if let Ok(event) = event
+            && is_relevant(&event, &root)
+        {
+            let _ = tx.send(event.paths);
+        }
+    })
+    .context("Failed to start watching files")?;
+    watcher
+        .watch(base_dir, RecursiveMode::Recursive)
+        .with_context(|| format!("Failed to watch {}", base_dir.display()))?;
+
+    let mut previous = report(ask(agent, base_dir, query, usage_log).await, None)?;
+    print_watching(base_dir);
+    while let Some(mut changed) = changes.recv().await {
+        // Wait for the changes to settle, e.g. a checkout or a formatter run
+        while let Ok(Some(paths)) = timeout(debounce, changes.recv()).await {
+            changed.extend(paths);
+        }
+        changed.sort();
+        changed.dedup();
+        eprintln!(
+            "{}",
+            colors::color_status(format!(
+                ">> {} changed, answering again",
+                describe_changes(&changed, base_dir)
+            ))
+        );
+        previous = report(ask(agent, base_dir, query, usage_log).await, previous)?;
+        print_watching(base_dir);
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Print `answer` in full, or how it differs from the `previous` one.
+///
+/// Returns the answer to compare the next one with: the last one given,
+/// when this one failed.
+fn report(answer: Option<String>, previous: Option<String>) -> Result<Option<String>> {
+    match (answer, previous) {
+        (Some(answer), None) => {
+            markdown::render_markdown(&answer)?;
+            println!();
+            Ok(Some(answer))
+        }
+        (Some(answer), Some(old)) => {
+            let changed = changed_lines(&old, &answer);
+            if changed.is_empty() {
+                eprintln!("{}\n", colors::color_dim(">> The answer is unchanged"));
+            } else {
+                eprintln!("{}", colors::color_status(">> The answer changed:"));
+                for line in changed {
+                    let colored = match line.as_bytes().first() {
+                        Some(b'+') => colors::color_success(line),
+                        _ => colors::color_error(line),
+                    };
+                    println!("{colored}");
+                }
+                println!();
+            }
+            Ok(Some(answer))
+        }
+        (None, previous) => Ok(previous),
+    }
+}
+
+fn print_watching(base_dir: &Path) {
+    eprintln!(
+        "{}",
+        colors::color_dim(format!(
+            ">> Watching {} for changes (Ctrl+C to stop)",
+            base_dir.display()
+        ))
+    );
+}
+
+/// Answer the query from scratch, reporting failures.
+///
+/// Returns the answer with its citations checked.
+async fn ask(
+    agent: &Agent<anthropic::completion::CompletionModel>,
+    base_dir: &Path,
+    query: &str,
+    usage_log: &UsageLog,
+) -> Option<String> {
+    let hook = ProgressHook::new();
+    let started = Instant::now();
+    let result = agent
+        .prompt(query)
+        .with_tool_concurrency(TOOL_CONCURRENCY)
+        .with_hook(hook.clone())
+        .into_future()
+        .instrument(telemetry::turn_span())
+        .await;
+    lifecycle::turn_end(query, &result).await;
+    usage_log.record(
+        hook.get_total_usage().into(),
+        hook.take_tool_calls().len(),
+        started.elapsed(),
+    );
+
+    match result {
+        Ok(answer) => Some(citations::annotate(&answer, base_dir)),
+        Err(e) => {
+            eprintln!("{}", colors::color_error(format!(">> Error: {e:#}")));
+            None
+        }
+    }
+}
+
+/// Whether an event changed content outside the ignored directories
+fn is_relevant(event: &Event, base_dir: &Path) -> bool {
+    matches!(
+        event.kind,
+        EventKind::Create(_) | EventKind::Modify(_) | EventKind::Remove(_)
+    ) && event.paths.iter().any(|path| !is_ignored(path, base_dir))
+}
+
+/// Whether a path lies in one of the ignored directories
+fn is_ignored(path: &Path, base_dir: &Path) -> bool {
+    path.strip_prefix(base_dir)
+        .unwrap_or(path)
+        .components()
+        .any(|component| match component {
+            Component::Normal(name) => IGNORED_DIRS.iter().any(|dir| name == *dir),
+            _ => false,
+        })
+}
+
+/// The changed paths, relative to the workspace, or their count if many
+fn describe_changes(paths: &[PathBuf], base_dir: &Path) -> String {
+    match paths {
+        [path] => path
+            .strip_prefix(base_dir)
+            .unwrap_or(path)
+            .display()
+            .to_string(),
+        paths => format!("{} files", paths.len()),
+    }
+}
+
+/// Lines removed from the old answer (`- `) and added in the new one (`+ `)
+fn changed_lines(old: &str, new: &str) -> Vec<String> {
+    TextDiff::from_lines(old, new)
+        .iter_all_changes()
+        .filter_map(|change| {
+            let line = change.value().trim_end();
+            match change.tag() {
+                ChangeTag::Delete => Some(format!("- {line}")),
+                ChangeTag::Insert => Some(format!("+ {line}")),
+                ChangeTag::Equal => None,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use notify::event::{AccessKind, ModifyKind};
+
+    use super::*;
+
+    #[test]
+    fn test_is_relevant() {
+        let base_dir = Path::new("/repo");
+        let event = |kind, path: &str| Event::new(kind).add_path(PathBuf::from(path));
+        let modify = EventKind::Modify(ModifyKind::Any);
+
+        assert!(is_relevant(&event(modify, "/repo/src/main.rs"), base_dir));
+        assert!(!is_relevant(&event(modify, "/repo/.git/index"), base_dir));
+        assert!(!is_relevant(
+            &event(modify, "/repo/.horse/sessions/1.jsonl"),
+            base_dir
+        ));
+        // Reading files, as the agent does, is not a change
+        assert!(!is_relevant(
+            &event(EventKind::Access(AccessKind::Any), "/repo/src/main.rs"),
+            base_dir
+        ));
+    }
+
+    #[test]
+    fn test_describe_changes() {
+        let base_dir = Path::new("/repo");
+        assert_eq!(
+            describe_changes(&[PathBuf::from("/repo/src/main.rs")], base_dir),
+            "src/main.rs"
+        );
+        assert_eq!(
+            describe_changes(
+                &[PathBuf::from("/repo/a.rs"), PathBuf::from("/repo/b.rs")],
+                base_dir
+            ),
+            "2 files"
+        );
+    }
+
+    #[test]
+    fn test_changed_lines() {
+        let old = "Two TODOs reference the deadline:\n- src/a.rs:3\n- src/b.rs:9\n";
+        let new = "Two TODOs reference the deadline:\n- src/a.rs:3\n- src/c.rs:1\n";
+        assert_eq!(
+            changed_lines(old, new),
+            ["- - src/b.rs:9", "+ - src/c.rs:1"]
+        );
+        assert!(changed_lines(old, old).is_empty());
+    }
+}
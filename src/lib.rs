@@ -1,2 +1,10 @@
 pub mod agent;
+pub mod config;
 pub mod console;
+pub mod lsp;
+pub mod server;
+pub mod session;
+pub mod slack;
+pub mod state;
+
+pub use session::{Answer, Session};
@@ -0,0 +1,182 @@
+use std::path::Path;
+use std::sync::Arc;
+
+use anyhow::{Context, Result};
+use rig::agent::Agent;
+use rig::providers::anthropic;
+use serde::Deserialize;
+use serde_json::{Value, json};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader, Stdout};
+use tokio::sync::Mutex;
+use tokio::task::JoinSet;
+
+use crate::console::{hyperlinks, quickfix};
+use crate::session::Session;
+
+/// Custom request answering a question about the workspace
+pub const ASK_METHOD: &str = "horse/ask";
+
+/// JSON-RPC error code for unknown methods
+const METHOD_NOT_FOUND: i64 = -32601;
+
+/// JSON-RPC error code for requests that failed while being handled
+const INTERNAL_ERROR: i64 = -32603;
+
+#[derive(Debug, Deserialize)]
+struct AskParams {
+    question: String,
+}
+
+type Output = Arc<Mutex<Stdout>>;
+
+/// Read one `Content-Length` framed message, or `None` at end of input
+async fn read_message(reader: &mut (impl AsyncBufReadExt + Unpin)) -> Result<Option<Value>> {
+    let mut content_length = None;
+    let mut line = String::new();
+
+    loop {
+        line.clear();
+        if reader.read_line(&mut line).await? == 0 {
+            break Ok(None);
+        }
+
+        let header = line.trim_end();
+        if header.is_empty() {
+            let length = content_length.context("Message without Content-Length header")?;
+            let mut body = vec![0; length];
+            reader.read_exact(&mut body).await?;
+            break Ok(Some(serde_json::from_slice(&body)?));
+        }
+        if let Some((name, value)) = header.split_once(':')
+            && name.eq_ignore_ascii_case("content-length")
+        {
+            content_length = Some(value.trim().parse::<usize>()?);
+        }
+    }
+}
+
+/// Write one `Content-Length` framed message
+async fn write_message(output: &Output, message: &Value) -> Result<()> {
+    let body = message.to_string();
+    let mut output = output.lock().await;
+    output
+        .write_all(format!("Content-Length: {}\r\n\r\n{body}", body.len()).as_bytes())
+        .await?;
+    output.flush().await?;
+    Ok(())
+}
+
+fn response(id: &Value, result: Result<Value, (i64, String)>) -> Value {
+    match result {
+        Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+        Err((code, message)) => json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "error": { "code": code, "message": message }
+        }),
+    }
+}
+
+/// Answer a question, returning the markdown answer and the locations it references
+async fn ask(session: &Mutex<Session>, base_dir: &Path, params: Value) -> Result<Value> {
+    let params: AskParams = serde_json::from_value(params).context("Invalid horse/ask params")?;
+    let answer = session.lock().await.ask(&params.question).await?;
+
+    let locations: Vec<Value> = quickfix::locations(&answer.text, base_dir)
+        .into_iter()
+        .map(|location| {
+            let uri = format!(
+                "file://{}",
+                hyperlinks::encode_path(&base_dir.join(&location.path))
+            );
+            // LSP positions are 0-based
+            let position = json!({
+                "line": location.line.saturating_sub(1),
+                "character": location.col.saturating_sub(1)
+            });
+            json!({ "uri": uri, "range": { "start": position, "end": position } })
+        })
+        .collect();
+
+    Ok(json!({ "markdown": answer.text, "locations": locations }))
+}
+
+/// Run a minimal language server on stdin/stdout.
+///
+/// Besides the lifecycle requests it only handles `horse/ask`, which takes
+/// `{"question": "..."}` and returns `{"markdown": "...", "locations": [Location]}`.
+/// All questions share one conversation.
+pub async fn run(
+    agent: Agent<anthropic::completion::CompletionModel>,
+    base_dir: &Path,
+) -> Result<()> {
+    let mut reader = BufReader::new(tokio::io::stdin());
+    let output: Output = Arc::new(Mutex::new(tokio::io::stdout()));
+    let session = Arc::new(Mutex::new(Session::from_agent(agent)));
+    let base_dir = Arc::new(base_dir.to_path_buf());
+    let mut pending = JoinSet::new();
+
+    while let Some(message) = read_message(&mut reader).await? {
+        let method = message["method"].as_str().unwrap_or_default().to_string();
+        let id = message.get("id").cloned();
+        tracing::debug!("LSP {method}");
+
+        match (method.as_str(), id) {
+            ("exit", _) => break,
+            ("initialize", Some(id)) => {
+                let result = json!({
+                    "capabilities": {},
+                    "serverInfo": { "name": "horse", "version": env!("CARGO_PKG_VERSION") }
+                });
+                write_message(&output, &response(&id, Ok(result))).await?;
+            }
+            ("shutdown", Some(id)) => {
+                write_message(&output, &response(&id, Ok(Value::Null))).await?;
+            }
+            (ASK_METHOD, Some(id)) => {
+                // Answering takes a while, keep reading other messages meanwhile
+                let (output, session, base_dir) =
+                    (output.clone(), session.clone(), base_dir.clone());
+                let params = message["params"].clone();
+                pending.spawn(async move {
+                    let result = ask(&session, &base_dir, params)
+                        .await
+                        .map_err(|e| (INTERNAL_ERROR, format!("{e:#}")));
+                    if let Err(e) = write_message(&output, &response(&id, result)).await {
+                        tracing::error!("Failed to send LSP response: {e:#}");
+                    }
+                });
+            }
+            (_, Some(id)) => {
+                let error = Err((METHOD_NOT_FOUND, format!("Unsupported method: {method}")));
+                write_message(&output, &response(&id, error)).await?;
+            }
+            // Other notifications need no reply
+            (_, None) => {}
+        }
+    }
+
+    // Don't drop answers still being worked on when the input ends
+    pending.join_all().await;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_read_message() {
+        let body = r#"{"jsonrpc":"2.0","id":1,"method":"initialize"}"#;
+        let framed = format!(
+            "Content-Length: {}\r\nContent-Type: application/vscode-jsonrpc\r\n\r\n{body}",
+            body.len()
+        );
+        let mut reader = BufReader::new(framed.as_bytes());
+
+        let message = read_message(&mut reader).await.unwrap().unwrap();
+        assert_eq!(message["method"], "initialize");
+        assert!(read_message(&mut reader).await.unwrap().is_none());
+    }
+}
@@ -1,99 +1,652 @@
+use std::collections::BTreeSet;
+use std::ffi::OsStr;
+use std::io;
+use std::net::{IpAddr, SocketAddr};
 use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::time::{Duration, Instant};
 
 use anyhow::{Context, Result};
-use clap::Parser;
-use rig::agent::AgentBuilder;
-use rig::client::ProviderClient;
-use rig::providers::anthropic;
+use clap::builder::{PossibleValue, StringValueParser, TypedValueParser};
+use clap::{CommandFactory, Parser, Subcommand};
+use clap_complete::Shell;
+use tracing_subscriber::filter::LevelFilter;
 
-mod agent;
-mod console;
-
-use agent::tools::{BashCommand, ReadFile, SearchDocs};
-
-use crate::console::{colors, repl::Repl};
+use horse::agent::{
+    self, audit, cassette, lifecycle, loops, mcp, models, paranoid, plugin, policy, preamble,
+    preflight, profile, ratelimit,
+    remote::{self, Remote},
+    scope, timebox, tools,
+    verify::Verifier,
+    wasm,
+};
+use horse::config::{self, Config};
+use horse::console::{
+    agents_md,
+    answers::{AnswerCache, AnswerSettings},
+    batch, colors, doctor, eval, explain, hyperlinks, i18n, incident, logging,
+    notify::NotifyConfig,
+    oneshot,
+    output::OutputFormat,
+    overview, pack,
+    prompt::PromptLine,
+    quickfix::Quickfix,
+    repl::{self, Repl},
+    review::{self, DiffSource},
+    sessions::{self, Summarizer},
+    setup,
+    spinner::create_spinner,
+    stats::format_size,
+    terminal,
+    transcript::{SESSIONS_DIR, Transcript},
+    trust,
+    tui::Tui,
+    usage,
+    voice::Voice,
+    watch,
+};
+use horse::state::{self, Category};
+use horse::{lsp, server, session, slack};
 
 #[derive(Parser, Debug)]
 #[command(name = "horse")]
 #[command(about = "An agentic search assistant for intelligent directory exploration")]
 struct Args {
-    /// Target directory to search and execute commands in
-    #[arg(default_value = ".")]
-    dir: PathBuf,
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// Without a subcommand, `horse [DIR]` starts a chat
+    #[command(flatten)]
+    chat: ChatArgs,
+
+    #[command(flatten)]
+    global: GlobalArgs,
+}
 
+/// Options shared by all subcommands
+#[derive(clap::Args, Debug)]
+struct GlobalArgs {
     /// Claude model to use
-    #[arg(short, long, default_value = "claude-sonnet-4-0")]
+    #[arg(
+        short,
+        long,
+        default_value = "claude-sonnet-4-0",
+        value_parser = ModelParser,
+        hide_possible_values = true,
+        global = true
+    )]
     model: String,
 
     /// Maximum number of turns for the agent
-    #[arg(short = 't', long, default_value = "20")]
+    #[arg(short = 't', long, default_value_t = session::DEFAULT_MAX_TURNS, global = true)]
     max_turns: usize,
+
+    /// Increase output detail; `-v` adds full tool arguments and result previews,
+    /// `-vv` adds API request ids and library diagnostics
+    #[arg(short, long, action = clap::ArgAction::Count, global = true)]
+    verbose: u8,
+
+    /// Hide tool call chatter and status messages, keeping only warnings and errors
+    #[arg(short, long, conflicts_with = "verbose", global = true)]
+    quiet: bool,
+
+    /// Append a full log to this file, regardless of the console level
+    #[arg(long, value_name = "PATH", global = true)]
+    log_file: Option<PathBuf>,
+
+    /// Record the API responses and tool results of the session to a cassette
+    #[arg(long, value_name = "PATH", global = true)]
+    record: Option<PathBuf>,
+
+    /// Re-run a recorded session from a cassette, without network access or
+    /// running tools
+    #[arg(long, value_name = "PATH", conflicts_with = "record", global = true)]
+    replay: Option<PathBuf>,
+
+    /// Leave a tool out of the agent, e.g. `bash`; repeatable
+    #[arg(long, value_name = "TOOL", global = true)]
+    disable_tool: Vec<String>,
+
+    /// Turn on an opt-in tool, e.g. `forge`, or keep a disabled one; repeatable
+    #[arg(long, value_name = "TOOL", global = true)]
+    enable_tool: Vec<String>,
+
+    /// Answer in this natural language, e.g. `de`, whatever the language of
+    /// the repository; overrides the `lang` config setting
+    #[arg(long, value_name = "LANG", global = true)]
+    lang: Option<String>,
+
+    /// Provider parameters of a `[profiles.<name>]` config section to send
+    /// with the requests; overrides the `profile` config setting
+    #[arg(
+        long,
+        value_name = "NAME",
+        value_parser = ProfileParser,
+        hide_possible_values = true,
+        global = true
+    )]
+    profile: Option<String>,
+
+    /// Answer `ask` and `batch` queries asked before about the same workspace
+    /// content from the cache; turns on the `answer-cache` config setting
+    #[arg(long, global = true)]
+    answer_cache: bool,
+
+    /// Snapshot the workspace at the start and verify after every `bash`
+    /// call and at exit that no file changed, exiting with 3 if one did
+    #[arg(long, global = true)]
+    paranoid: bool,
+
+    /// Confine every tool to the paths matching this glob, relative to the
+    /// workspace, e.g. `src/**`; repeatable
+    #[arg(long, value_name = "GLOB", global = true)]
+    include: Vec<String>,
+
+    /// Hide the paths matching this glob from every tool, e.g. `vendor/**`;
+    /// repeatable
+    #[arg(long, value_name = "GLOB", global = true)]
+    exclude: Vec<String>,
+
+    /// Time box the session, e.g. `10m` or `1h`: once it is over, the agent
+    /// is asked to wrap up with its findings and open questions
+    #[arg(long, value_name = "DURATION", value_parser = usage::parse_since, global = true)]
+    max_duration: Option<Duration>,
 }
 
-/// Gather directory structure by running `find` command
-async fn gather_directory_context(base_dir: &Path) -> Result<String> {
-    use tokio::process::Command;
+#[derive(clap::Args, Debug)]
+struct ChatArgs {
+    /// Target directory to search and execute commands in, or
+    /// `[user@]host:/path` to explore one over SSH
+    #[arg(default_value = ".")]
+    dir: PathBuf,
+
+    /// Run the full-screen terminal UI instead of the line-oriented REPL
+    #[arg(long)]
+    tui: bool,
 
-    let output = Command::new("find")
-        .arg(".")
-        .arg("-maxdepth")
-        .arg("3")
-        .arg("-type")
-        .arg("f")
-        .current_dir(base_dir)
-        .output()
-        .await
-        .context("Failed to execute find command")?;
+    /// Review an investigation plan proposed by the agent before it starts
+    /// working on each query
+    #[arg(long, conflicts_with = "tui")]
+    plan: bool,
 
-    if output.status.success() {
-        String::from_utf8(output.stdout).context("Failed to parse find output as UTF-8")
-    } else {
-        Ok("(Directory listing unavailable)".to_string())
+    /// Check the `path:line` citations of each answer in a second pass and
+    /// show corrections
+    #[arg(long, conflicts_with = "tui")]
+    verify: bool,
+
+    /// Dictate queries: an empty line records the microphone until Enter and
+    /// transcribes it locally with whisper.cpp (see `[voice]` in the config)
+    #[arg(long, conflicts_with = "tui")]
+    voice: bool,
+
+    /// Notify when a turn taking at least this many seconds finishes while
+    /// the terminal is unfocused; overrides `after-secs` of `[notify]`
+    #[arg(long, value_name = "SECS")]
+    notify_after: Option<u64>,
+
+    /// Former spelling of `horse ask <QUERY>`
+    #[arg(
+        short,
+        long,
+        value_name = "QUERY",
+        hide = true,
+        conflicts_with_all = ["tui", "plan", "voice"]
+    )]
+    print: Option<String>,
+
+    /// Output format of `--print`
+    #[arg(long, value_enum, default_value_t = OutputFormat::Text, hide = true, requires = "print")]
+    output: OutputFormat,
+
+    /// Quickfix file of `--print`
+    #[arg(long, value_name = "PATH", hide = true, requires = "print")]
+    quickfix_file: Option<PathBuf>,
+}
+
+impl ChatArgs {
+    /// The command these arguments stand for: `ask` with `-p/--print`
+    fn into_command(self) -> Command {
+        match self.print {
+            Some(query) => Command::Ask(AskArgs {
+                query,
+                dir: self.dir,
+                output: self.output,
+                quickfix_file: self.quickfix_file,
+                verify: self.verify,
+            }),
+            None => Command::Chat(self),
+        }
     }
 }
 
-/// Load the AGENTS.md file from the target directory if it exists,
-/// otherwise return a default preamble.
-async fn load_preamble(base_dir: &Path) -> Result<String> {
-    let agents_file = base_dir.join("AGENTS.md");
-    let mut preamble = if agents_file.exists() {
-        println!("{}", colors::color_status(">> Loading AGENTS.md..."));
-        tokio::fs::read_to_string(&agents_file)
-            .await
-            .context("Failed to read AGENTS.md")?
-    } else {
-        "You are a helpful search assistant. You can read files and execute safe bash commands \
-            to help users explore and understand their codebase."
-            .to_string()
-    };
+#[derive(clap::Args, Debug)]
+struct AskArgs {
+    /// Query to answer. Piped stdin is attached as context; use `-` to read
+    /// the query itself from stdin
+    query: String,
 
-    // Add directory context
-    println!(
-        "{}",
-        colors::color_status(">> Gathering directory structure...")
-    );
-    match gather_directory_context(base_dir).await {
-        Ok(file_list) => {
-            preamble.push_str("\n\n## Available Files\n\n");
-            preamble.push_str("The following files are available in the working directory:\n\n");
-            preamble.push_str(&file_list);
-        }
-        Err(e) => {
-            eprintln!(
-                "{}",
-                colors::color_warning(format!(
-                    "[!] Warning: Could not gather directory context: {:#}",
-                    e
-                ))
-            );
+    /// Target directory to search and execute commands in, or
+    /// `[user@]host:/path` to explore one over SSH
+    #[arg(default_value = ".")]
+    dir: PathBuf,
+
+    /// Output format
+    #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+    output: OutputFormat,
+
+    /// Write the quickfix list of `--output quickfix` to a file instead of stderr
+    #[arg(long, value_name = "PATH")]
+    quickfix_file: Option<PathBuf>,
+
+    /// Check the `path:line` citations of the answer in a second pass and
+    /// append corrections (text and quickfix output)
+    #[arg(long)]
+    verify: bool,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Chat about the workspace interactively (the default)
+    Chat(ChatArgs),
+    /// Answer a single query non-interactively, print it to stdout and exit
+    Ask(AskArgs),
+    /// Run every query of a file against the workspace and write a report
+    Batch {
+        /// File with one query per line, or multi-line queries separated by `---` lines
+        queries: PathBuf,
+
+        /// Target directory to search and execute commands in
+        #[arg(default_value = ".")]
+        dir: PathBuf,
+
+        /// Let each query see the conversation of the previous ones
+        #[arg(long)]
+        chain: bool,
+
+        /// Report format
+        #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+        output: OutputFormat,
+
+        /// Write the report to a file instead of stdout
+        #[arg(long)]
+        report: Option<PathBuf>,
+    },
+    /// Run a suite of questions with expected citations and report accuracy,
+    /// turns, tokens and cost per case
+    Eval {
+        /// YAML file with `cases`, each a `question` and the locations it
+        /// must cite under `expect`
+        suite: PathBuf,
+
+        /// Target directory to search and execute commands in
+        #[arg(default_value = ".")]
+        dir: PathBuf,
+
+        /// Also write the results to a file as JSON lines
+        #[arg(long)]
+        report: Option<PathBuf>,
+    },
+    /// Review uncommitted changes, staged changes or a revision range with
+    /// the repository as context, and print the findings by file
+    Review {
+        /// Target directory, a git repository
+        #[arg(default_value = ".")]
+        dir: PathBuf,
+
+        /// Review the changes staged for the next commit
+        #[arg(long, conflicts_with = "rev")]
+        staged: bool,
+
+        /// Review a revision range, e.g. `main..feature`
+        #[arg(long, value_name = "A..B")]
+        rev: Option<String>,
+    },
+    /// Run as a git hook, e.g. `horse hook pre-commit` from
+    /// `.git/hooks/pre-commit`
+    Hook {
+        #[command(subcommand)]
+        command: HookCommand,
+    },
+    /// Explore the workspace step by step and write an onboarding document:
+    /// purpose, build system, entry points, architecture, key modules, tests
+    Overview {
+        /// Target directory to explore
+        #[arg(default_value = ".")]
+        dir: PathBuf,
+
+        /// Write the document to a file instead of stdout
+        #[arg(long)]
+        out: Option<PathBuf>,
+    },
+    /// Explore the workspace for a bounded number of turns and write a draft
+    /// AGENTS.md: purpose, layout, conventions and commands
+    GenerateAgentsMd {
+        /// Target directory to explore
+        #[arg(default_value = ".")]
+        dir: PathBuf,
+
+        /// Write the draft here instead of the workspace's AGENTS.md
+        #[arg(long)]
+        out: Option<PathBuf>,
+
+        /// Replace an existing file
+        #[arg(long)]
+        force: bool,
+    },
+    /// OCR the scanned PDFs and images of the workspace ahead of time, so
+    /// `search_docs` finds them all from the first search
+    Index {
+        /// Workspace to index
+        #[arg(default_value = ".")]
+        dir: PathBuf,
+    },
+    /// Assemble the most representative files of the workspace within a
+    /// token budget, for pasting into other LLM tools
+    Pack {
+        /// Workspace to pack
+        #[arg(default_value = ".")]
+        dir: PathBuf,
+
+        /// Write the pack to a file instead of stdout
+        #[arg(long)]
+        out: Option<PathBuf>,
+
+        /// Token budget, e.g. `20k` or `8000`
+        #[arg(long, default_value = "20k", value_parser = pack::parse_budget)]
+        budget: usize,
+    },
+    /// Explain what a commit does and why, reading the code around it
+    ExplainCommit {
+        /// Commit to explain, e.g. a SHA, `HEAD~2` or a tag
+        rev: String,
+
+        /// Target directory, a git repository
+        #[arg(default_value = ".")]
+        dir: PathBuf,
+    },
+    /// Investigate an incident: build a timeline of events with evidence
+    /// from a log file and the git history
+    Incident {
+        /// Log file to investigate, inside the workspace
+        #[arg(long)]
+        log: PathBuf,
+
+        /// Start of the incident window, e.g. `2024-05-01 14:30` or `14:30`
+        /// for today
+        #[arg(long, value_parser = incident::parse_since)]
+        since: chrono::NaiveDateTime,
+
+        /// Target directory, a git repository
+        #[arg(default_value = ".")]
+        dir: PathBuf,
+    },
+    /// Answer a standing question again whenever files change, printing how
+    /// the answer changed
+    Watch {
+        /// Question to keep answering
+        #[arg(short, long)]
+        prompt: String,
+
+        /// Target directory to search and execute commands in
+        #[arg(default_value = ".")]
+        dir: PathBuf,
+
+        /// Wait until files have been quiet for this many milliseconds
+        /// before answering again
+        #[arg(long, default_value_t = 2000)]
+        debounce_ms: u64,
+    },
+    /// Serve an HTTP API with streaming answers for editors and web UIs
+    Serve {
+        /// Target directory to search and execute commands in
+        #[arg(default_value = ".")]
+        dir: PathBuf,
+
+        /// Address to listen on
+        #[arg(long, default_value = "127.0.0.1")]
+        host: IpAddr,
+
+        /// Port to listen on
+        #[arg(long, default_value_t = 8080)]
+        port: u16,
+    },
+    /// Run a language server on stdio answering `horse/ask` requests from editors
+    Lsp {
+        /// Target directory to search and execute commands in
+        #[arg(default_value = ".")]
+        dir: PathBuf,
+    },
+    /// Answer Slack mentions and direct messages, one conversation per thread.
+    /// Needs `SLACK_APP_TOKEN` (Socket Mode) and `SLACK_BOT_TOKEN`
+    Slack {
+        /// Target directory to search and execute commands in
+        #[arg(default_value = ".")]
+        dir: PathBuf,
+    },
+    /// Set up horse: the provider's API key, the global config file and an
+    /// AGENTS.md skeleton for the workspace
+    Init {
+        /// Workspace the AGENTS.md skeleton is written to
+        #[arg(default_value = ".")]
+        dir: PathBuf,
+    },
+    /// Check external programs, config files, the API key and provider
+    /// connectivity, and suggest fixes
+    Doctor {
+        /// Directory whose workspace config is checked
+        #[arg(default_value = ".")]
+        dir: PathBuf,
+    },
+    /// Verify that the workspace's audit log is complete and unmodified
+    Audit {
+        /// Workspace whose `.horse/audit/` log is checked
+        #[arg(default_value = ".")]
+        dir: PathBuf,
+    },
+    /// Browse the REPL sessions recorded in a workspace
+    Sessions {
+        #[command(subcommand)]
+        command: SessionsCommand,
+    },
+    /// Validate or print the configuration
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommand,
+    },
+    /// Show the size of the workspace's `.horse/` state, or remove parts of it
+    Clean {
+        /// Workspace whose `.horse/` is shown or cleaned
+        #[arg(default_value = ".")]
+        dir: PathBuf,
+        /// Remove the caches, e.g. OCR text
+        #[arg(long)]
+        cache: bool,
+        /// Remove the recorded REPL sessions
+        #[arg(long)]
+        sessions: bool,
+        /// Remove all of `.horse/`, the audit log included
+        #[arg(long, conflicts_with_all = ["cache", "sessions"])]
+        all: bool,
+    },
+    /// Trust a workspace, and the directories in it, with the tools that run
+    /// programs on its files
+    Trust {
+        /// Directory to trust
+        #[arg(default_value = ".")]
+        dir: PathBuf,
+        /// Stop trusting the directory
+        #[arg(long)]
+        revoke: bool,
+    },
+    /// Summarize tokens and estimated cost logged by previous sessions
+    Usage {
+        /// Look-back period, e.g. `12h`, `7d` or `4w`
+        #[arg(long, default_value = "7d", value_parser = usage::parse_since)]
+        since: Duration,
+    },
+    /// Print a shell completion script, e.g. `horse completions zsh > ~/.zfunc/_horse`
+    Completions {
+        /// Shell to generate the script for
+        shell: Shell,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum HookCommand {
+    /// Review the staged changes, failing the commit on blocking findings
+    PreCommit {
+        /// Repository being committed to
+        #[arg(default_value = ".")]
+        dir: PathBuf,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum ConfigCommand {
+    /// Check the config files for syntax errors, unknown keys and values of
+    /// the wrong type
+    Check {
+        /// Workspace whose `.horse.toml` is checked with the global config
+        #[arg(default_value = ".")]
+        dir: PathBuf,
+    },
+    /// Print the config files in order of precedence
+    Show {
+        /// Workspace whose `.horse.toml` is shown with the global config
+        #[arg(default_value = ".")]
+        dir: PathBuf,
+        /// Print the configuration merged from all files instead
+        #[arg(long)]
+        effective: bool,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum SessionsCommand {
+    /// List the sessions, newest first, with their titles and summaries
+    List {
+        /// Workspace whose `.horse/sessions/` are listed
+        #[arg(default_value = ".")]
+        dir: PathBuf,
+    },
+}
+
+impl Args {
+    /// The command to run and the options shared by all; a bare
+    /// `horse [DIR]` is an alias for `horse chat [DIR]`
+    fn into_command(self) -> (Command, GlobalArgs) {
+        let command = match self.command.unwrap_or(Command::Chat(self.chat)) {
+            Command::Chat(chat) => chat.into_command(),
+            command => command,
+        };
+        (command, self.global)
+    }
+}
+
+impl Command {
+    /// Target directory of the command
+    fn dir(&self) -> &Path {
+        match self {
+            Command::Chat(ChatArgs { dir, .. })
+            | Command::Ask(AskArgs { dir, .. })
+            | Command::Batch { dir, .. }
+            | Command::Watch { dir, .. }
+            | Command::Review { dir, .. }
+            | Command::ExplainCommit { dir, .. }
+            | Command::Incident { dir, .. }
+            | Command::Overview { dir, .. }
+            | Command::GenerateAgentsMd { dir, .. }
+            | Command::Pack { dir, .. }
+            | Command::Index { dir }
+            | Command::Eval { dir, .. }
+            | Command::Serve { dir, .. }
+            | Command::Lsp { dir }
+            | Command::Slack { dir }
+            | Command::Init { dir }
+            | Command::Doctor { dir }
+            | Command::Audit { dir }
+            | Command::Clean { dir, .. }
+            | Command::Trust { dir, .. } => dir,
+            Command::Sessions {
+                command: SessionsCommand::List { dir },
+            }
+            | Command::Hook {
+                command: HookCommand::PreCommit { dir },
+            }
+            | Command::Config {
+                command: ConfigCommand::Check { dir } | ConfigCommand::Show { dir, .. },
+            } => dir,
+            Command::Usage { .. } | Command::Completions { .. } => Path::new("."),
         }
     }
+}
 
-    Ok(preamble)
+/// Accepts any model name, while offering the known ones to shell completion
+#[derive(Debug, Clone, Copy)]
+struct ModelParser;
+
+impl TypedValueParser for ModelParser {
+    type Value = String;
+
+    fn parse_ref(
+        &self,
+        cmd: &clap::Command,
+        arg: Option<&clap::Arg>,
+        value: &OsStr,
+    ) -> Result<String, clap::Error> {
+        StringValueParser::new().parse_ref(cmd, arg, value)
+    }
+
+    fn possible_values(&self) -> Option<Box<dyn Iterator<Item = PossibleValue> + '_>> {
+        Some(Box::new(
+            models::KNOWN_MODELS.iter().copied().map(PossibleValue::new),
+        ))
+    }
+}
+
+/// Accepts any profile name, while offering those of the config files of
+/// the current directory to shell completion
+#[derive(Debug, Clone, Copy)]
+struct ProfileParser;
+
+impl TypedValueParser for ProfileParser {
+    type Value = String;
+
+    fn parse_ref(
+        &self,
+        cmd: &clap::Command,
+        arg: Option<&clap::Arg>,
+        value: &OsStr,
+    ) -> Result<String, clap::Error> {
+        StringValueParser::new().parse_ref(cmd, arg, value)
+    }
+
+    fn possible_values(&self) -> Option<Box<dyn Iterator<Item = PossibleValue> + '_>> {
+        Some(Box::new(
+            profile_names(Path::new("."))
+                .into_iter()
+                .map(PossibleValue::new),
+        ))
+    }
+}
+
+/// Names of the `[profiles]` of the config files for `base_dir`, none if
+/// they don't load. Leaked, as clap takes static names and this runs once
+/// per completion script.
+fn profile_names(base_dir: &Path) -> Vec<&'static str> {
+    Config::load(base_dir)
+        .map(|config| {
+            config
+                .profiles
+                .into_keys()
+                .map(|name| &*name.leak())
+                .collect()
+        })
+        .unwrap_or_default()
 }
 
 #[tokio::main]
-async fn main() -> Result<()> {
+async fn main() -> Result<ExitCode> {
     // Install color-eyre without using `?` since it returns ErrReport
     if let Err(e) = color_eyre::install() {
         eprintln!(
@@ -104,46 +657,692 @@ async fn main() -> Result<()> {
 
     let args = Args::parse();
 
+    let (command, global) = args.into_command();
+    match command {
+        Command::Completions { shell } => {
+            clap_complete::generate(shell, &mut Args::command(), "horse", &mut io::stdout());
+            Ok(ExitCode::SUCCESS)
+        }
+        Command::Init { dir } => {
+            setup::run(&dir).await?;
+            Ok(ExitCode::SUCCESS)
+        }
+        // Diagnose before anything that could fail on a broken setup
+        Command::Doctor { dir } => Ok(doctor::run(&dir).await),
+        Command::Audit { dir } => {
+            let path = audit::log_path(&dir);
+            let content = std::fs::read_to_string(&path)
+                .with_context(|| format!("Failed to read {}", path.display()))?;
+            let entries = audit::verify(&content)?;
+            println!(
+                "{}",
+                colors::color_success(format!("{entries} entries, hash chain intact"))
+            );
+            Ok(ExitCode::SUCCESS)
+        }
+        Command::Sessions {
+            command: SessionsCommand::List { dir },
+        } => {
+            println!(
+                "{}",
+                sessions::render(&sessions::list(&dir.join(SESSIONS_DIR)))
+            );
+            Ok(ExitCode::SUCCESS)
+        }
+        Command::Config {
+            command: ConfigCommand::Check { dir },
+        } => {
+            Config::load(&dir)?;
+            let files = config::files(&dir);
+            if files.is_empty() {
+                println!("No config files");
+            } else {
+                for path in files {
+                    println!(
+                        "{}",
+                        colors::color_success(format!("{} OK", path.display()))
+                    );
+                }
+            }
+            Ok(ExitCode::SUCCESS)
+        }
+        Command::Config {
+            command: ConfigCommand::Show { dir, effective },
+        } => {
+            if effective {
+                Config::load(&dir)?;
+                print!("{}", toml::to_string_pretty(&config::effective(&dir)?)?);
+            } else {
+                for path in config::files(&dir) {
+                    let content = std::fs::read_to_string(&path)
+                        .with_context(|| format!("Failed to read {}", path.display()))?;
+                    println!("# {}\n{}", path.display(), content.trim_end());
+                }
+            }
+            Ok(ExitCode::SUCCESS)
+        }
+        Command::Clean {
+            dir,
+            cache,
+            sessions,
+            all,
+        } => {
+            let categories: Vec<Category> =
+                [(cache, Category::Cache), (sessions, Category::Sessions)]
+                    .into_iter()
+                    .filter_map(|(selected, category)| selected.then_some(category))
+                    .collect();
+            if all || !categories.is_empty() {
+                let freed = state::clean(&dir, &categories)?;
+                println!(
+                    "{}",
+                    colors::color_success(format!("Removed {}", format_size(freed as usize)))
+                );
+            } else {
+                println!("{}", state::render(&dir, &state::entries(&dir)?));
+            }
+            Ok(ExitCode::SUCCESS)
+        }
+        Command::Pack { dir, out, budget } => {
+            pack::run(&dir, budget, out.as_deref()).await?;
+            Ok(ExitCode::SUCCESS)
+        }
+        Command::Index { dir } => {
+            let spinner = create_spinner("OCRing scanned documents");
+            let indexed = tools::ocr_index(&dir).await;
+            spinner.finish_and_clear();
+            let indexed = indexed.context("Failed to OCR, is tesseract installed?")?;
+            println!(
+                "{}",
+                colors::color_success(format!(
+                    "OCRed {} scanned files ({} failed), {} were up to date",
+                    indexed.recognized, indexed.failed, indexed.cached
+                ))
+            );
+            Ok(ExitCode::SUCCESS)
+        }
+        Command::Trust { dir, revoke } => {
+            let dir = dir
+                .canonicalize()
+                .with_context(|| format!("Failed to canonicalize {}", dir.display()))?;
+            let message = match revoke {
+                true if trust::revoke(&dir)? => format!("No longer trusting {}", dir.display()),
+                true => format!("{} wasn't trusted", dir.display()),
+                false => {
+                    trust::trust(&dir)?;
+                    format!("Trusting {}", dir.display())
+                }
+            };
+            println!("{}", colors::color_success(message));
+            Ok(ExitCode::SUCCESS)
+        }
+        Command::Usage { since } => {
+            usage::report(since)?;
+            Ok(ExitCode::SUCCESS)
+        }
+        command => run(command, global).await,
+    }
+}
+
+async fn run(command: Command, global: GlobalArgs) -> Result<ExitCode> {
+    // A `[user@]host:/path` workspace is explored over SSH; config, logs
+    // and sessions are then those of the current directory
+    let remote = command.dir().to_str().and_then(Remote::parse);
+    if remote.is_some() && matches!(command, Command::Watch { .. }) {
+        anyhow::bail!("watch needs a local directory to watch");
+    }
+    let local_dir = if remote.is_some() {
+        Path::new(".")
+    } else {
+        command.dir()
+    };
     // Canonicalize directory to absolute path
-    let base_dir = args
-        .dir
+    let base_dir = local_dir
         .canonicalize()
         .context("Failed to canonicalize target directory")?;
+    if let Some(remote) = remote {
+        remote::init(remote);
+    }
+
+    // Trust is settled before the workspace config is read, as its hooks,
+    // MCP servers, plugins and custom tools run programs. A replayed session
+    // runs no tools, so it doesn't ask.
+    let workspace =
+        remote::get().map_or_else(|| base_dir.clone(), |r| PathBuf::from(r.to_string()));
+    let trusted = match global.replay {
+        Some(_) => trust::is_trusted(&workspace),
+        None => trust::check(&workspace, matches!(command, Command::Chat(_)))?,
+    };
+    let mut config = Config::load_trusted(&base_dir, trusted)?;
+    for name in &global.enable_tool {
+        config.tools.enable(name)?;
+    }
+    colors::init(config.theme);
+    i18n::init(global.lang.as_deref().or(config.lang.as_deref()));
+    let tui = matches!(command, Command::Chat(ChatArgs { tui: true, .. }));
+    hyperlinks::init(
+        &config.hyperlinks,
+        &base_dir,
+        terminal::stdout_is_terminal() && !tui,
+    );
 
-    println!(
-        "Horse - {}",
-        colors::color_success(
-            "An read-only agentic search assistant for intelligent directory exploration"
+    // The TUI owns the terminal, and one-shot answers are meant for scripts
+    let console_level = if tui {
+        LevelFilter::OFF
+    } else {
+        logging::console_level(
+            global.quiet || (matches!(command, Command::Ask(_)) && global.verbose == 0),
+            global.verbose,
         )
+    };
+    let _log_guard = logging::init(console_level, global.log_file.as_deref())?;
+    if !trusted && global.replay.is_none() {
+        trust::warn_untrusted();
+    }
+
+    // Only chat has a banner; like all status output, it goes to stderr
+    if matches!(command, Command::Chat(_)) {
+        eprintln!(
+            "Horse - {}",
+            colors::color_success(
+                "An read-only agentic search assistant for intelligent directory exploration"
+            )
+        );
+        eprintln!(
+            "{}: {}",
+            i18n::tr("Working directory"),
+            colors::color_status(
+                remote::get().map_or_else(|| base_dir.display().to_string(), Remote::to_string)
+            )
+        );
+        eprintln!(
+            "{}: {}",
+            i18n::tr("Model"),
+            colors::color_status(&global.model)
+        );
+        eprintln!(
+            "{}: {}",
+            i18n::tr("Max turns"),
+            colors::color_status(global.max_turns)
+        );
+        eprintln!();
+    }
+
+    let started = Instant::now();
+    match (global.record.clone(), global.replay.clone()) {
+        (Some(path), _) => cassette::init(cassette::Mode::Record(path)).await?,
+        (_, Some(path)) => cassette::init(cassette::Mode::Replay(path)).await?,
+        (None, None) => {}
+    }
+    if config.audit {
+        audit::init(&base_dir)?;
+    }
+    policy::init(&config.policy)?;
+    scope::init(&global.include, &global.exclude)?;
+    if let Some(limit) = global.max_duration {
+        timebox::init(limit);
+    }
+    loops::init(&config.tool_calls);
+    lifecycle::init(&config.hooks, &base_dir);
+    if global.paranoid {
+        paranoid::init(&base_dir).await?;
+    }
+    preflight::init(&global.model);
+    ratelimit::init(&config.rate_limit)?;
+    profile::init(
+        &config.profiles,
+        global.profile.as_deref().or(config.profile.as_deref()),
+    )?;
+
+    // The phases are independent, and each may wait on subprocesses or the
+    // network, so they run concurrently
+    let detect_forge = async {
+        if config.tools.forge {
+            let forge = tools::DetectedForge::detect(&base_dir, config.tools.forge_type).await;
+            if forge.is_none() {
+                tracing::warn!("[!] Warning: Forge tool disabled, origin is not on a known forge");
+            }
+            forge.map(tools::ForgeTool::new)
+        } else {
+            None
+        }
+    };
+    let (preamble, forge, mcp, plugins, wasm) = tokio::join!(
+        logging::timed("Preamble", preamble::load(&base_dir)),
+        logging::timed("Forge detection", detect_forge),
+        logging::timed("MCP servers", mcp::load_tools(&config.mcp, &base_dir)),
+        logging::timed("Plugins", plugin::load_tools(&config.plugin, &base_dir)),
+        logging::timed("WASM plugins", wasm::load_tools(&config.wasm, &base_dir)),
     );
-    println!(
-        "Working directory: {}",
-        colors::color_status(base_dir.display())
+    let preamble = preamble?;
+
+    let ticket = config.tools.tracker.as_ref().and_then(|tracker| {
+        match tools::TicketTool::from_config(tracker) {
+            Ok(ticket) => Some(ticket),
+            Err(e) => {
+                tracing::warn!("[!] Warning: Ticket tool disabled: {e}");
+                None
+            }
+        }
+    });
+    let spawn_agent = config
+        .tools
+        .spawn_agent
+        .clone()
+        .map(|sub_agent| tools::SpawnAgent::new(base_dir.clone(), sub_agent));
+    let extra = agent::ExtraTools {
+        forge,
+        ticket,
+        object_storage: config
+            .tools
+            .object_storage
+            .as_ref()
+            .map(tools::ObjectStorage::new),
+        spawn_agent,
+        mcp,
+        custom: tools::load_custom_tools(&config.tool, &base_dir),
+        plugins,
+        wasm,
+    };
+    tracing::debug!(">> Startup: {} ms", started.elapsed().as_millis());
+
+    let tool_names = agent::tool_names(&extra);
+    let unknown: Vec<&str> = global
+        .disable_tool
+        .iter()
+        .chain(&global.enable_tool)
+        .filter(|name| !tool_names.contains(name))
+        .map(String::as_str)
+        .collect();
+    anyhow::ensure!(
+        unknown.is_empty(),
+        "Unknown tool: {} (registered: {})",
+        unknown.join(", "),
+        tool_names.join(", ")
     );
-    println!("Model: {}", colors::color_status(&args.model));
-    println!("Max turns: {}", colors::color_status(args.max_turns));
-    println!();
+    // A replayed session runs no tools, so none are held back
+    let untrusted = trust::UNTRUSTED_TOOLS
+        .iter()
+        .filter(|_| !trusted && global.replay.is_none());
+    let disabled: BTreeSet<String> = global
+        .disable_tool
+        .iter()
+        .filter(|name| !global.enable_tool.contains(name))
+        .cloned()
+        .chain(untrusted.map(|name| name.to_string()))
+        .collect();
 
-    // Load preamble from AGENTS.md or use default
-    let preamble = load_preamble(&base_dir).await?;
+    // Create agent with tools and preamble. The REPL defers this to the
+    // first query, since constructing the API client isn't free, and builds
+    // it again when tools are toggled.
+    let build_agent = {
+        let (base_dir, model) = (base_dir.clone(), global.model.clone());
+        let (max_turns, thinking_budget) = (global.max_turns, config.thinking.budget);
+        move |disabled: &BTreeSet<String>| {
+            agent::build(
+                &base_dir,
+                &model,
+                max_turns,
+                thinking_budget,
+                &preamble,
+                extra.clone(),
+                disabled,
+            )
+        }
+    };
 
-    // Initialize Anthropic client (from_env reads ANTHROPIC_API_KEY automatically)
-    let client = anthropic::Client::from_env();
+    let usage_log = usage::UsageLog::new(&global.model, &base_dir);
+    usage::init(&usage_log);
+    let answer_cache = global.answer_cache || config.answer_cache;
+    let answer_settings = AnswerSettings {
+        model: global.model.clone(),
+        lang: global.lang.clone().or(config.lang.clone()),
+        profile: global.profile.clone().or(config.profile.clone()),
+        include: global.include.clone(),
+        exclude: global.exclude.clone(),
+        verify: false,
+    };
 
-    let model =
-        anthropic::completion::CompletionModel::new(client, &args.model).with_prompt_caching();
+    let result = match command {
+        Command::Chat(ChatArgs {
+            tui: true,
+            notify_after,
+            ..
+        }) => {
+            Tui::new(build_agent(&disabled))
+                .with_notify(NotifyConfig {
+                    after_secs: notify_after.unwrap_or(config.notify.after_secs),
+                    ..config.notify
+                })
+                .with_usage_log(usage_log)
+                .run()
+                .await?;
+            Ok(ExitCode::SUCCESS)
+        }
+        Command::Chat(ChatArgs {
+            plan,
+            verify,
+            voice,
+            notify_after,
+            ..
+        }) => {
+            let voice = voice.then(|| Voice::new(config.voice)).transpose()?;
+            // Run the REPL loop
+            Repl::new(build_agent)
+                .with_tools(&tool_names, disabled)
+                .with_prompt_line(
+                    PromptLine::new(config.prompt, &global.model, &base_dir)
+                        .with_cost(config.show_cost),
+                )
+                .with_editing_mode(config.editing_mode)
+                .with_templates(config.templates)
+                .with_notify(NotifyConfig {
+                    after_secs: notify_after.unwrap_or(config.notify.after_secs),
+                    ..config.notify
+                })
+                .with_usage_log(usage_log)
+                .with_plan_mode(plan)
+                .with_thinking(config.thinking.show)
+                .with_output_gate(
+                    config
+                        .confirm_output_kb
+                        .unwrap_or(repl::DEFAULT_CONFIRM_OUTPUT_KB),
+                    &global.model,
+                )
+                .with_verifier(verify.then(|| Verifier::new(&base_dir, &global.model)))
+                .with_citation_check(&base_dir)
+                .with_shell_dir(&base_dir)
+                .with_voice(voice)
+                .with_transcript(Transcript::new(&base_dir))
+                // Background requests would get in the way of a cassette's order
+                .with_summaries(cassette::base_url().is_none().then(Summarizer::new))
+                .run()
+                .await?;
+            Ok(ExitCode::SUCCESS)
+        }
+        Command::Ask(AskArgs {
+            query,
+            output,
+            quickfix_file,
+            verify,
+            ..
+        }) => {
+            let piped = oneshot::read_piped_stdin()?;
+            anyhow::ensure!(
+                query != oneshot::STDIN_QUERY || piped.is_some(),
+                "Expected the query on stdin"
+            );
+            if piped.as_ref().is_some_and(|p| p.truncated) {
+                tracing::warn!("[!] Warning: Piped input was truncated");
+            }
+            let prompt = oneshot::build_prompt(&query, piped.as_ref());
+            let quickfix = Quickfix::new(&base_dir, quickfix_file);
+            let verifier = verify.then(|| Verifier::new(&base_dir, &global.model));
+            let answers = open_answers(
+                answer_cache,
+                &base_dir,
+                AnswerSettings {
+                    verify,
+                    ..answer_settings
+                },
+            )
+            .await;
+            Ok(oneshot::run(
+                &build_agent(&disabled),
+                &base_dir,
+                &prompt,
+                output,
+                &quickfix,
+                verifier.as_ref(),
+                answers.as_ref(),
+                &usage_log,
+            )
+            .await)
+        }
+        Command::Batch {
+            queries,
+            chain,
+            output,
+            report,
+            ..
+        } => {
+            let answers = open_answers(answer_cache, &base_dir, answer_settings).await;
+            batch::run(
+                &build_agent(&disabled),
+                &queries,
+                chain,
+                output,
+                report.as_deref(),
+                answers.as_ref(),
+                &usage_log,
+            )
+            .await
+        }
+        Command::Eval { suite, report, .. } => {
+            eval::run(
+                &build_agent(&disabled),
+                &global.model,
+                &suite,
+                report.as_deref(),
+                &usage_log,
+            )
+            .await
+        }
+        Command::Review { staged, rev, .. } => {
+            let source = match (staged, rev) {
+                (true, _) => DiffSource::Staged,
+                (false, Some(range)) => DiffSource::Range(range),
+                (false, None) => DiffSource::WorkingTree,
+            };
+            review::run(
+                &build_agent(&disabled),
+                &base_dir,
+                &source,
+                &config.review,
+                &usage_log,
+            )
+            .await
+        }
+        Command::Hook {
+            command: HookCommand::PreCommit { .. },
+        } => {
+            review::pre_commit(
+                &build_agent(&disabled),
+                &base_dir,
+                &config.review,
+                &usage_log,
+            )
+            .await
+        }
+        Command::Overview { out, .. } => {
+            overview::run(
+                &build_agent(&disabled),
+                &base_dir,
+                out.as_deref(),
+                &usage_log,
+            )
+            .await
+        }
+        Command::GenerateAgentsMd { out, force, .. } => {
+            agents_md::run(
+                &build_agent(&disabled),
+                &base_dir,
+                out.as_deref(),
+                force,
+                &usage_log,
+            )
+            .await
+        }
+        Command::ExplainCommit { rev, .. } => {
+            explain::run(&build_agent(&disabled), &base_dir, &rev, &usage_log).await
+        }
+        Command::Incident { log, since, .. } => {
+            incident::run(&build_agent(&disabled), &base_dir, &log, since, &usage_log).await
+        }
+        Command::Watch {
+            prompt,
+            debounce_ms,
+            ..
+        } => {
+            watch::run(
+                &build_agent(&disabled),
+                &base_dir,
+                &prompt,
+                Duration::from_millis(debounce_ms),
+                &usage_log,
+            )
+            .await
+        }
+        Command::Serve { host, port, .. } => {
+            server::serve(build_agent(&disabled), SocketAddr::new(host, port)).await?;
+            Ok(ExitCode::SUCCESS)
+        }
+        Command::Lsp { .. } => {
+            lsp::run(build_agent(&disabled), &base_dir).await?;
+            Ok(ExitCode::SUCCESS)
+        }
+        Command::Slack { .. } => {
+            slack::run(build_agent(&disabled)).await?;
+            Ok(ExitCode::SUCCESS)
+        }
+        Command::Init { .. }
+        | Command::Doctor { .. }
+        | Command::Audit { .. }
+        | Command::Sessions { .. }
+        | Command::Clean { .. }
+        | Command::Config { .. }
+        | Command::Pack { .. }
+        | Command::Index { .. }
+        | Command::Trust { .. }
+        | Command::Usage { .. }
+        | Command::Completions { .. } => Ok(ExitCode::SUCCESS),
+    };
+    let unchanged = paranoid::verify().await;
+    lifecycle::session_end().await;
+    if unchanged {
+        result
+    } else {
+        result.map(|_| ExitCode::from(paranoid::EXIT_WORKSPACE_CHANGED))
+    }
+}
+
+/// The answer cache of `base_dir` if `enabled`, or `None` with a warning
+/// when the workspace can't be hashed
+async fn open_answers(
+    enabled: bool,
+    base_dir: &Path,
+    settings: AnswerSettings,
+) -> Option<AnswerCache> {
+    match enabled.then(|| AnswerCache::open(base_dir, settings)) {
+        Some(open) => open
+            .await
+            .inspect_err(|e| tracing::warn!("[!] Warning: Answer cache disabled: {e:#}"))
+            .ok(),
+        None => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_global(args: &[&str]) -> GlobalArgs {
+        Args::try_parse_from(std::iter::once("horse").chain(args.iter().copied()))
+            .unwrap()
+            .into_command()
+            .1
+    }
+
+    fn parse(args: &[&str]) -> Command {
+        Args::try_parse_from(std::iter::once("horse").chain(args.iter().copied()))
+            .unwrap()
+            .into_command()
+            .0
+    }
+
+    #[test]
+    fn test_print_alias() {
+        assert!(matches!(
+            parse(&["-p", "where is main?", "src", "--output", "json"]),
+            Command::Ask(AskArgs { query, dir, output: OutputFormat::Json, .. })
+                if query == "where is main?" && dir == Path::new("src")
+        ));
+        assert!(matches!(
+            parse(&["--print", "q"]),
+            Command::Ask(AskArgs {
+                output: OutputFormat::Text,
+                ..
+            })
+        ));
+        assert!(matches!(
+            parse(&["src"]),
+            Command::Chat(ChatArgs { print: None, .. })
+        ));
+        assert!(Args::try_parse_from(["horse", "-p", "q", "--tui"]).is_err());
+        assert!(Args::try_parse_from(["horse", "--output", "json"]).is_err());
+    }
+
+    #[test]
+    fn test_ask_args() {
+        assert!(matches!(
+            parse(&["ask", "where is retry logic implemented?"]),
+            Command::Ask(AskArgs { query, dir, output: OutputFormat::Text, quickfix_file: None, verify: false })
+                if query == "where is retry logic implemented?" && dir == Path::new(".")
+        ));
+        assert!(matches!(
+            parse(&["ask", "-", "src", "--output", "quickfix", "--quickfix-file", "qf.txt", "--verify"]),
+            Command::Ask(AskArgs { query, output: OutputFormat::Quickfix, quickfix_file: Some(file), verify: true, .. })
+                if query == oneshot::STDIN_QUERY && file == Path::new("qf.txt")
+        ));
+        assert!(Args::try_parse_from(["horse", "ask"]).is_err());
+        assert!(Args::try_parse_from(["horse", "ask", "q", "--output", "yaml"]).is_err());
+    }
+
+    #[test]
+    fn test_profile_names() {
+        let base_dir =
+            std::env::temp_dir().join(format!("horse-main-test-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&base_dir).unwrap();
+        std::fs::write(
+            base_dir.join(".horse.toml"),
+            "[profiles.fast]\ntemperature = 0.2\n[profiles.audit]\nuser-id = \"audit\"\n",
+        )
+        .unwrap();
+        let names = profile_names(&base_dir);
+        assert!(names.contains(&"fast") && names.contains(&"audit"));
+
+        std::fs::write(base_dir.join(".horse.toml"), "profiles = 1\n").unwrap();
+        assert!(profile_names(&base_dir).is_empty());
+        std::fs::remove_dir_all(&base_dir).unwrap();
 
-    // Create agent with tools and preamble
-    let agent = AgentBuilder::new(model)
-        .preamble(&preamble)
-        .default_max_turns(args.max_turns)
-        .tool(ReadFile::new(base_dir.clone()))
-        .tool(BashCommand::new(base_dir.clone()))
-        .tool(SearchDocs::new(base_dir.clone()))
-        .build();
+        // Names not in the config are left for `profile::init` to report
+        let global = parse_global(&["--profile", "unknown", "ask", "q"]);
+        assert_eq!(global.profile.as_deref(), Some("unknown"));
+    }
+
+    #[test]
+    fn test_explain_commit() {
+        assert!(matches!(
+            parse(&["explain-commit", "HEAD~2"]),
+            Command::ExplainCommit { rev, dir } if rev == "HEAD~2" && dir == Path::new(".")
+        ));
+        assert!(Args::try_parse_from(["horse", "explain-commit"]).is_err());
+    }
 
-    let mut repl = Repl::new(agent);
+    #[test]
+    fn test_hook_pre_commit() {
+        assert!(matches!(
+            parse(&["hook", "pre-commit"]),
+            Command::Hook { command: HookCommand::PreCommit { dir } } if dir == Path::new(".")
+        ));
+        assert!(Args::try_parse_from(["horse", "hook", "pre-push"]).is_err());
+    }
 
-    // Run the REPL loop
-    repl.run().await
+    #[test]
+    fn test_index() {
+        assert!(
+            matches!(parse(&["index", "docs"]), Command::Index { dir } if dir == Path::new("docs"))
+        );
+        assert!(matches!(parse(&["index"]), Command::Index { dir } if dir == Path::new(".")));
+    }
 }
@@ -0,0 +1,286 @@
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result};
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::response::sse::{Event, KeepAlive, Sse};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use rig::agent::Agent;
+use rig::providers::anthropic;
+use serde::{Deserialize, Serialize};
+use tokio::sync::{Mutex, mpsc};
+use tokio_stream::Stream;
+use tokio_stream::wrappers::UnboundedReceiverStream;
+use uuid::Uuid;
+
+use crate::agent::hooks::AgentEvent;
+use crate::console::output::{self, OutputEvent};
+use crate::session::Session;
+
+/// Time a conversation is kept without a message
+const SESSION_TTL: Duration = Duration::from_secs(60 * 60);
+
+/// Conversations kept at once, at most
+const MAX_SESSIONS: usize = 256;
+
+/// How often conversations past their time are dropped
+const EVICT_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Conversations of the server with when they were last used. Ones unused
+/// for `SESSION_TTL` are dropped, and past `MAX_SESSIONS` the least recently
+/// used one makes room for a new one.
+struct Sessions<S> {
+    entries: HashMap<Uuid, (Arc<Mutex<S>>, Instant)>,
+}
+
+impl<S> Sessions<S> {
+    fn new() -> Self {
+        Self {
+            entries: HashMap::new(),
+        }
+    }
+
+    fn insert(&mut self, id: Uuid, session: S, now: Instant) {
+        self.evict(now);
+        if self.entries.len() >= MAX_SESSIONS
+            && let Some(oldest) = self
+                .entries
+                .iter()
+                .min_by_key(|(_, (_, used))| *used)
+                .map(|(id, _)| *id)
+        {
+            self.entries.remove(&oldest);
+            tracing::debug!("Dropped session {oldest} to make room");
+        }
+        self.entries
+            .insert(id, (Arc::new(Mutex::new(session)), now));
+    }
+
+    /// The conversation `id`, which counts as used `now`
+    fn get(&mut self, id: Uuid, now: Instant) -> Option<Arc<Mutex<S>>> {
+        self.entries.get_mut(&id).map(|(session, used)| {
+            *used = now;
+            session.clone()
+        })
+    }
+
+    /// Drop the conversations unused for `SESSION_TTL`. One answering a
+    /// message is only freed once the answer is done.
+    fn evict(&mut self, now: Instant) {
+        self.entries.retain(|id, (_, used)| {
+            let kept = now.duration_since(*used) < SESSION_TTL;
+            if !kept {
+                tracing::debug!("Session {id} expired");
+            }
+            kept
+        });
+    }
+}
+
+/// Shared state of the HTTP server: one agent, many conversations
+#[derive(Clone)]
+struct AppState {
+    agent: Arc<Agent<anthropic::completion::CompletionModel>>,
+    sessions: Arc<Mutex<Sessions<Session>>>,
+}
+
+impl AppState {
+    async fn session(&self, id: Uuid) -> Result<Arc<Mutex<Session>>, StatusCode> {
+        self.sessions
+            .lock()
+            .await
+            .get(id, Instant::now())
+            .ok_or(StatusCode::NOT_FOUND)
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct CreatedSession {
+    id: Uuid,
+}
+
+#[derive(Debug, Deserialize)]
+struct NewMessage {
+    content: String,
+}
+
+/// Serve the HTTP API on `addr` until the process is stopped.
+///
+/// - `POST /sessions` creates a conversation and returns its id
+/// - `POST /sessions/{id}/messages` sends `{"content": "..."}` and streams
+///   `tool_call`, `tool_result` and `usage` server-sent events, followed by
+///   a final `answer` or `error` event
+/// - `GET /sessions/{id}/transcript` returns the conversation messages
+///
+/// Conversations are dropped after an hour without a message, and the
+/// least recently used one when too many are open.
+pub async fn serve(
+    agent: Agent<anthropic::completion::CompletionModel>,
+    addr: SocketAddr,
+) -> Result<()> {
+    let state = AppState {
+        agent: Arc::new(agent),
+        sessions: Arc::new(Mutex::new(Sessions::new())),
+    };
+
+    let sessions = state.sessions.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(EVICT_INTERVAL);
+        loop {
+            interval.tick().await;
+            sessions.lock().await.evict(Instant::now());
+        }
+    });
+
+    let app = Router::new()
+        .route("/sessions", post(create_session))
+        .route("/sessions/{id}/messages", post(post_message))
+        .route("/sessions/{id}/transcript", get(transcript))
+        .with_state(state);
+
+    let listener = tokio::net::TcpListener::bind(addr)
+        .await
+        .with_context(|| format!("Failed to bind {addr}"))?;
+    tracing::info!(">> Listening on http://{addr}");
+
+    axum::serve(listener, app).await.context("Server failed")
+}
+
+async fn create_session(State(state): State<AppState>) -> (StatusCode, Json<CreatedSession>) {
+    let id = Uuid::new_v4();
+    let session = Session::from_agent(state.agent.clone());
+    state
+        .sessions
+        .lock()
+        .await
+        .insert(id, session, Instant::now());
+    tracing::debug!("Created session {id}");
+
+    (StatusCode::CREATED, Json(CreatedSession { id }))
+}
+
+async fn post_message(
+    State(state): State<AppState>,
+    Path(id): Path<Uuid>,
+    Json(message): Json<NewMessage>,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, StatusCode> {
+    let session = state.session(id).await?;
+    let (tx, rx) = mpsc::unbounded_channel();
+
+    tokio::spawn(async move {
+        // Messages of one session are answered one at a time
+        let mut session = session.lock().await;
+
+        let (events_tx, mut events_rx) = mpsc::unbounded_channel();
+        let forward_tx = tx.clone();
+        let forward = tokio::spawn(async move {
+            while let Some(event) = events_rx.recv().await {
+                let _ = forward_tx.send(Ok(sse_event(event)));
+            }
+        });
+
+        let result = session.ask_streaming(&message.content, events_tx).await;
+        let _ = forward.await;
+
+        let last = match result {
+            Ok(text) => json_event("answer", &serde_json::json!({ "text": text })),
+            Err(e) => json_event("error", &serde_json::json!({ "message": format!("{e:#}") })),
+        };
+        let _ = tx.send(Ok(last));
+    });
+
+    Ok(Sse::new(UnboundedReceiverStream::new(rx)).keep_alive(KeepAlive::default()))
+}
+
+async fn transcript(
+    State(state): State<AppState>,
+    Path(id): Path<Uuid>,
+) -> Result<Json<serde_json::Value>, StatusCode> {
+    let session = state.session(id).await?;
+    let session = session.lock().await;
+    serde_json::to_value(session.history())
+        .map(Json)
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+fn json_event(name: &str, data: &impl Serialize) -> Event {
+    Event::default()
+        .event(name)
+        .json_data(data)
+        .unwrap_or_else(|e| Event::default().event("error").data(e.to_string()))
+}
+
+/// Convert an agent event to a server-sent event named after its type
+fn sse_event(event: AgentEvent) -> Event {
+    match event {
+        AgentEvent::ToolCall { name, args } => {
+            let args = output::parse_tool_args(args);
+            json_event(
+                "tool_call",
+                &OutputEvent::ToolCall {
+                    name: &name,
+                    args: &args,
+                },
+            )
+        }
+        AgentEvent::ToolResult {
+            name,
+            result,
+            error,
+        } => json_event(
+            "tool_result",
+            &OutputEvent::ToolResult {
+                name: &name,
+                result: &output::tool_result_text(&result),
+                is_error: error.is_some(),
+                error_kind: error.map(|e| e.kind),
+            },
+        ),
+        AgentEvent::Usage(usage) => json_event("usage", &OutputEvent::Usage(usage.into())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sessions_expire() {
+        let start = Instant::now();
+        let mut sessions = Sessions::new();
+        let (kept, expired) = (Uuid::new_v4(), Uuid::new_v4());
+        sessions.insert(kept, "kept", start);
+        sessions.insert(expired, "expired", start);
+
+        let later = start + SESSION_TTL - Duration::from_secs(1);
+        assert!(sessions.get(kept, later).is_some());
+        sessions.evict(start + SESSION_TTL);
+        assert!(sessions.get(kept, later).is_some());
+        assert!(sessions.get(expired, later).is_none());
+    }
+
+    #[test]
+    fn test_sessions_cap() {
+        let start = Instant::now();
+        let mut sessions = Sessions::new();
+        let ids: Vec<Uuid> = (0..MAX_SESSIONS).map(|_| Uuid::new_v4()).collect();
+        for (i, id) in ids.iter().enumerate() {
+            sessions.insert(*id, i, start + Duration::from_millis(i as u64));
+        }
+        // The first one was used last, so the second one makes room
+        let now = start + Duration::from_secs(1);
+        assert!(sessions.get(ids[0], now).is_some());
+        let new = Uuid::new_v4();
+        sessions.insert(new, MAX_SESSIONS, now);
+
+        assert_eq!(sessions.entries.len(), MAX_SESSIONS);
+        assert!(sessions.get(ids[0], now).is_some());
+        assert!(sessions.get(ids[1], now).is_none());
+        assert!(sessions.get(new, now).is_some());
+    }
+}
@@ -0,0 +1,165 @@
+use std::collections::BTreeSet;
+use std::path::Path;
+use std::sync::Arc;
+
+use anyhow::{Context, Result};
+use rig::agent::Agent;
+use rig::completion::{Message, Prompt, Usage};
+use rig::providers::anthropic;
+use tokio::sync::mpsc::{self, UnboundedSender};
+use tracing::Instrument;
+
+use crate::agent::{
+    self, TOOL_CONCURRENCY,
+    hooks::{AgentEvent, EventHook},
+    lifecycle, preamble, telemetry,
+};
+use crate::console::output::{self, ToolCallRecord};
+
+/// Maximum number of agent turns per query unless configured otherwise
+pub const DEFAULT_MAX_TURNS: usize = 20;
+
+/// The answer to a single query, with the tool calls made to produce it
+#[derive(Debug, Clone)]
+pub struct Answer {
+    pub text: String,
+    pub tool_calls: Vec<ToolCallRecord>,
+    pub usage: Usage,
+}
+
+/// A conversation with the search agent about one directory, for embedding
+/// horse in other programs.
+///
+/// ```no_run
+/// # async fn example() -> anyhow::Result<()> {
+/// let mut session = horse::Session::new(".", "claude-sonnet-4-0").await?;
+/// let answer = session.ask("Where is the CLI defined?").await?;
+/// println!("{} ({} tool calls)", answer.text, answer.tool_calls.len());
+/// # Ok(())
+/// # }
+/// ```
+pub struct Session {
+    agent: Arc<Agent<anthropic::completion::CompletionModel>>,
+    history: Vec<Message>,
+}
+
+impl Session {
+    /// Create a session exploring `dir` with the given Claude model.
+    ///
+    /// The Anthropic API key is read from `ANTHROPIC_API_KEY`.
+    pub async fn new(dir: impl AsRef<Path>, model: &str) -> Result<Self> {
+        let base_dir = dir
+            .as_ref()
+            .canonicalize()
+            .context("Failed to canonicalize target directory")?;
+        let preamble = preamble::load(&base_dir).await?;
+
+        Ok(Self::from_agent(agent::build(
+            &base_dir,
+            model,
+            DEFAULT_MAX_TURNS,
+            None,
+            &preamble,
+            agent::ExtraTools::default(),
+            &BTreeSet::new(),
+        )))
+    }
+
+    /// Create a session around an already configured agent, which may be
+    /// shared with other sessions
+    pub fn from_agent(
+        agent: impl Into<Arc<Agent<anthropic::completion::CompletionModel>>>,
+    ) -> Self {
+        Self {
+            agent: agent.into(),
+            history: Vec::new(),
+        }
+    }
+
+    /// Ask a question; later questions see the earlier conversation
+    pub async fn ask(&mut self, query: &str) -> Result<Answer> {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let collector = output::collect_events(rx, false);
+        let result = self.ask_streaming(query, tx).await;
+        let collected = collector.await.unwrap_or_default();
+
+        let text = result?;
+        Ok(Answer {
+            text,
+            tool_calls: collected.tool_calls,
+            usage: collected.usage,
+        })
+    }
+
+    /// Ask a question, sending tool calls, results and token usage to `events`
+    /// as they happen. Returns the answer text.
+    pub async fn ask_streaming(
+        &mut self,
+        query: &str,
+        events: UnboundedSender<AgentEvent>,
+    ) -> Result<String> {
+        let result = self
+            .agent
+            .prompt(query)
+            .with_history(&mut self.history)
+            .with_tool_concurrency(TOOL_CONCURRENCY)
+            .with_hook(EventHook::new(events))
+            .into_future()
+            .instrument(telemetry::turn_span())
+            .await;
+        lifecycle::turn_end(query, &result).await;
+        result.context("The agent failed to answer")
+    }
+
+    /// The conversation so far
+    pub fn history(&self) -> &[Message] {
+        &self.history
+    }
+
+    /// Forget the conversation so far
+    pub fn clear(&mut self) {
+        self.history.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rig::agent::AgentBuilder;
+
+    fn test_agent() -> Agent<anthropic::completion::CompletionModel> {
+        let client = anthropic::Client::builder()
+            .api_key("test")
+            .build()
+            .unwrap();
+        AgentBuilder::new(anthropic::completion::CompletionModel::new(
+            client,
+            "claude-sonnet-4-0",
+        ))
+        .build()
+    }
+
+    #[tokio::test]
+    async fn test_new_missing_dir() {
+        let dir = std::env::temp_dir().join(format!("horse-session-test-{}", uuid::Uuid::new_v4()));
+        let error = Session::new(&dir, "claude-sonnet-4-0").await.err().unwrap();
+        assert_eq!(error.to_string(), "Failed to canonicalize target directory");
+    }
+
+    #[test]
+    fn test_from_agent() {
+        let agent = Arc::new(test_agent());
+        let mut first = Session::from_agent(agent.clone());
+        let second = Session::from_agent(agent.clone());
+        assert_eq!(Arc::strong_count(&agent), 3);
+        assert!(first.history().is_empty() && second.history().is_empty());
+
+        first
+            .history
+            .push(Message::user("Where is the CLI defined?"));
+        assert_eq!(first.history().len(), 1);
+        assert!(second.history().is_empty());
+        first.clear();
+        assert!(first.history().is_empty());
+    }
+}
@@ -0,0 +1,276 @@
+use std::collections::HashMap;
+use std::sync::{Arc, LazyLock};
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use futures_util::{SinkExt, StreamExt};
+use regex::Regex;
+use rig::agent::Agent;
+use rig::providers::anthropic;
+use serde::Deserialize;
+use serde_json::{Value, json};
+use tokio::sync::Mutex;
+use tokio_tungstenite::tungstenite::Message;
+
+use crate::session::Session;
+
+const API_URL: &str = "https://slack.com/api";
+
+/// Environment variable with the app-level token (`xapp-...`) used for Socket Mode
+pub const APP_TOKEN_VAR: &str = "SLACK_APP_TOKEN";
+
+/// Environment variable with the bot token (`xoxb-...`) used to post replies
+pub const BOT_TOKEN_VAR: &str = "SLACK_BOT_TOKEN";
+
+/// Delay before reconnecting after the connection drops
+const RECONNECT_DELAY_SECS: u64 = 5;
+
+/// User mentions like `<@U123ABC>`
+static MENTION: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"<@[A-Z0-9]+>").expect("Invalid mention regex"));
+
+/// Markdown constructs that Slack's mrkdwn writes differently
+static BOLD: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\*\*(.+?)\*\*").expect("Invalid bold regex"));
+static LINK: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\[([^\]]+)\]\(([^)\s]+)\)").expect("Invalid link regex"));
+static HEADING: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?m)^#{1,6}\s+(.+)$").expect("Invalid heading regex"));
+
+/// A message the bot should answer
+#[derive(Debug, Deserialize)]
+struct SlackEvent {
+    #[serde(rename = "type")]
+    kind: String,
+    #[serde(default)]
+    text: String,
+    channel: String,
+    ts: String,
+    thread_ts: Option<String>,
+    channel_type: Option<String>,
+    /// Set on messages posted by bots, including our own replies
+    bot_id: Option<String>,
+    subtype: Option<String>,
+}
+
+impl SlackEvent {
+    /// Mentions in channels and direct messages are answered, everything else is ignored
+    fn is_question(&self) -> bool {
+        let direct_message = self.kind == "message" && self.channel_type.as_deref() == Some("im");
+        (self.kind == "app_mention" || direct_message)
+            && self.bot_id.is_none()
+            && self.subtype.is_none()
+    }
+
+    /// Conversations are keyed by the thread they happen in
+    fn thread(&self) -> (String, String) {
+        let thread_ts = self.thread_ts.clone().unwrap_or_else(|| self.ts.clone());
+        (self.channel.clone(), thread_ts)
+    }
+}
+
+/// Convert markdown answers to Slack mrkdwn, leaving code blocks untouched
+pub fn to_mrkdwn(markdown: &str) -> String {
+    markdown
+        .split("```")
+        .enumerate()
+        .map(|(i, part)| {
+            if i % 2 == 1 {
+                // Slack doesn't highlight code, drop the language tag
+                let code = part.split_once('\n').map_or(part, |(_, code)| code);
+                format!("```\n{code}```")
+            } else {
+                let text = HEADING.replace_all(part, "*$1*");
+                let text = BOLD.replace_all(&text, "*$1*");
+                LINK.replace_all(&text, "<$2|$1>").into_owned()
+            }
+        })
+        .collect()
+}
+
+/// Slack Web API client for the bot's replies
+struct WebClient {
+    client: reqwest::Client,
+    bot_token: String,
+}
+
+impl WebClient {
+    async fn post_reply(&self, channel: &str, thread_ts: &str, text: &str) -> Result<()> {
+        let response: Value = self
+            .client
+            .post(format!("{API_URL}/chat.postMessage"))
+            .bearer_auth(&self.bot_token)
+            .json(&json!({ "channel": channel, "thread_ts": thread_ts, "text": text }))
+            .send()
+            .await?
+            .json()
+            .await?;
+        anyhow::ensure!(
+            response["ok"].as_bool().unwrap_or(false),
+            "chat.postMessage failed: {}",
+            response["error"]
+        );
+        Ok(())
+    }
+}
+
+/// Open a Socket Mode connection, returning its WebSocket URL
+async fn open_connection(client: &reqwest::Client, app_token: &str) -> Result<String> {
+    let response: Value = client
+        .post(format!("{API_URL}/apps.connections.open"))
+        .bearer_auth(app_token)
+        .send()
+        .await?
+        .json()
+        .await?;
+    response["url"]
+        .as_str()
+        .map(str::to_string)
+        .with_context(|| format!("apps.connections.open failed: {}", response["error"]))
+}
+
+type Sessions = Arc<Mutex<HashMap<(String, String), Arc<Mutex<Session>>>>>;
+
+struct Bot {
+    agent: Arc<Agent<anthropic::completion::CompletionModel>>,
+    web: Arc<WebClient>,
+    sessions: Sessions,
+}
+
+impl Bot {
+    /// Answer a question in its thread, continuing the thread's conversation
+    fn answer(&self, event: SlackEvent) {
+        let (agent, web, sessions) = (self.agent.clone(), self.web.clone(), self.sessions.clone());
+
+        tokio::spawn(async move {
+            let thread = event.thread();
+            let session = sessions
+                .lock()
+                .await
+                .entry(thread.clone())
+                .or_insert_with(|| Arc::new(Mutex::new(Session::from_agent(agent))))
+                .clone();
+
+            let question = MENTION.replace_all(&event.text, "").trim().to_string();
+            tracing::info!(">> [{}] {question}", thread.0);
+
+            let reply = match session.lock().await.ask(&question).await {
+                Ok(answer) => to_mrkdwn(&answer.text),
+                Err(e) => format!(":warning: {e:#}"),
+            };
+            if let Err(e) = web.post_reply(&thread.0, &thread.1, &reply).await {
+                tracing::error!(">> Failed to reply in Slack: {e:#}");
+            }
+        });
+    }
+
+    /// Handle one Socket Mode connection until Slack closes it
+    async fn run_connection(&self, url: &str) -> Result<()> {
+        let (socket, _) = tokio_tungstenite::connect_async(url)
+            .await
+            .context("Failed to connect to Slack")?;
+        let (mut sink, mut stream) = socket.split();
+        tracing::info!(">> Connected to Slack");
+
+        while let Some(message) = stream.next().await {
+            let message = message?;
+            let envelope: Value = match &message {
+                Message::Text(text) => serde_json::from_str(text).unwrap_or_default(),
+                Message::Close(_) => break,
+                _ => continue,
+            };
+
+            // Every envelope must be acknowledged, or Slack retries it
+            if let Some(envelope_id) = envelope["envelope_id"].as_str() {
+                let ack = json!({ "envelope_id": envelope_id }).to_string();
+                sink.send(Message::Text(ack.into())).await?;
+            }
+
+            match envelope["type"].as_str() {
+                Some("events_api") => {
+                    match serde_json::from_value::<SlackEvent>(envelope["payload"]["event"].clone())
+                    {
+                        Ok(event) if event.is_question() => self.answer(event),
+                        Ok(_) => {}
+                        Err(e) => tracing::debug!("Ignoring Slack event: {e}"),
+                    }
+                }
+                Some("disconnect") => break,
+                _ => {}
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Answer Slack mentions and direct messages, one conversation per thread.
+///
+/// Connects through Socket Mode with the tokens from `SLACK_APP_TOKEN` and
+/// `SLACK_BOT_TOKEN`, reconnecting whenever the connection drops.
+pub async fn run(agent: Agent<anthropic::completion::CompletionModel>) -> Result<()> {
+    let app_token =
+        std::env::var(APP_TOKEN_VAR).with_context(|| format!("{APP_TOKEN_VAR} is not set"))?;
+    let bot_token =
+        std::env::var(BOT_TOKEN_VAR).with_context(|| format!("{BOT_TOKEN_VAR} is not set"))?;
+
+    let client = reqwest::Client::new();
+    let bot = Bot {
+        agent: Arc::new(agent),
+        web: Arc::new(WebClient {
+            client: client.clone(),
+            bot_token,
+        }),
+        sessions: Arc::new(Mutex::new(HashMap::new())),
+    };
+
+    loop {
+        let result = match open_connection(&client, &app_token).await {
+            Ok(url) => bot.run_connection(&url).await,
+            Err(e) => Err(e),
+        };
+        if let Err(e) = result {
+            tracing::warn!("[!] Warning: Slack connection failed: {e:#}");
+        }
+        tracing::info!(">> Reconnecting to Slack...");
+        tokio::time::sleep(Duration::from_secs(RECONNECT_DELAY_SECS)).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_mrkdwn() {
+        let markdown = "## Entry point\n\nThe **CLI** lives in [main](https://x.y/main.rs).\n\n```rust\nlet **x** = 1;\n```\n";
+        assert_eq!(
+            to_mrkdwn(markdown),
+            "*Entry point*\n\nThe *CLI* lives in <https://x.y/main.rs|main>.\n\n```\nlet **x** = 1;\n```\n"
+        );
+    }
+
+    #[test]
+    fn test_is_question() {
+        let event: SlackEvent = serde_json::from_value(json!({
+            "type": "app_mention",
+            "text": "<@U1> where is main?",
+            "channel": "C1",
+            "ts": "1.1"
+        }))
+        .unwrap();
+        assert!(event.is_question());
+        assert_eq!(event.thread(), ("C1".to_string(), "1.1".to_string()));
+
+        let reply: SlackEvent = serde_json::from_value(json!({
+            "type": "message",
+            "channel_type": "im",
+            "text": "answer",
+            "channel": "D1",
+            "ts": "1.2",
+            "bot_id": "B1"
+        }))
+        .unwrap();
+        assert!(!reply.is_question());
+    }
+}
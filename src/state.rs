@@ -0,0 +1,196 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::agent::{audit::AUDIT_DIR, tools::OCR_DIR};
+use crate::console::{answers::ANSWERS_DIR, stats::format_size, transcript::SESSIONS_DIR};
+
+/// Directory of horse's state in a workspace: sessions, caches and the
+/// audit log
+pub const STATE_DIR: &str = ".horse";
+
+/// Written to `STATE_DIR/.gitignore`, so the state isn't committed
+const GITIGNORE: &str = "# Workspace state of horse, not meant to be committed\n*\n";
+
+/// What the directories under `STATE_DIR` hold
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Category {
+    /// Results kept to avoid recomputing them, e.g. OCR text and answers
+    Cache,
+    /// Transcripts and summaries of REPL sessions
+    Sessions,
+    /// The hash-chained log of tool invocations
+    Audit,
+    Other,
+}
+
+/// Category of each directory under `STATE_DIR`, by its workspace path
+const CATEGORIES: &[(&str, Category)] = &[
+    (OCR_DIR, Category::Cache),
+    (ANSWERS_DIR, Category::Cache),
+    (SESSIONS_DIR, Category::Sessions),
+    (AUDIT_DIR, Category::Audit),
+];
+
+impl Category {
+    fn of(path: &str) -> Self {
+        CATEGORIES
+            .iter()
+            .find(|(dir, _)| *dir == path)
+            .map_or(Self::Other, |(_, category)| *category)
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            Self::Cache => "cache",
+            Self::Sessions => "sessions",
+            Self::Audit => "audit",
+            Self::Other => "other",
+        }
+    }
+}
+
+/// An entry of `STATE_DIR` and the bytes it takes up
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StateEntry {
+    pub path: PathBuf,
+    pub category: Category,
+    pub bytes: u64,
+}
+
+/// Create `dir`, a directory under a workspace's `STATE_DIR`, and the
+/// `.gitignore` that keeps the state out of git
+pub fn create_dir(dir: &Path) -> io::Result<()> {
+    fs::create_dir_all(dir)?;
+    match dir.ancestors().find(|dir| dir.ends_with(STATE_DIR)) {
+        Some(state_dir) if !state_dir.join(".gitignore").exists() => {
+            fs::write(state_dir.join(".gitignore"), GITIGNORE)
+        }
+        _ => Ok(()),
+    }
+}
+
+/// The entries of the workspace's `STATE_DIR` with their sizes; none if it
+/// doesn't exist
+pub fn entries(base_dir: &Path) -> io::Result<Vec<StateEntry>> {
+    match fs::read_dir(base_dir.join(STATE_DIR)) {
+        Ok(dir) => {
+            let mut entries = Vec::new();
+            for entry in dir {
+                let path = entry?.path();
+                if !path.ends_with(".gitignore") {
+                    let name = path.file_name().unwrap_or_default().to_string_lossy();
+                    entries.push(StateEntry {
+                        category: Category::of(&format!("{STATE_DIR}/{name}")),
+                        bytes: size(&path)?,
+                        path,
+                    });
+                }
+            }
+            entries.sort_by(|a, b| (a.category, &a.path).cmp(&(b.category, &b.path)));
+            Ok(entries)
+        }
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(e),
+    }
+}
+
+/// Remove the entries of the given categories, or all of `STATE_DIR`
+/// without categories, and return the bytes freed
+pub fn clean(base_dir: &Path, categories: &[Category]) -> io::Result<u64> {
+    let entries = entries(base_dir)?;
+    let freed = entries
+        .iter()
+        .filter(|entry| categories.is_empty() || categories.contains(&entry.category))
+        .map(|entry| entry.bytes)
+        .sum();
+    if categories.is_empty() {
+        if base_dir.join(STATE_DIR).exists() {
+            fs::remove_dir_all(base_dir.join(STATE_DIR))?;
+        }
+    } else {
+        for entry in entries.iter().filter(|e| categories.contains(&e.category)) {
+            if entry.path.is_dir() {
+                fs::remove_dir_all(&entry.path)?;
+            } else {
+                fs::remove_file(&entry.path)?;
+            }
+        }
+    }
+    Ok(freed)
+}
+
+/// Table of the entries of `STATE_DIR` with their categories and sizes
+pub fn render(base_dir: &Path, entries: &[StateEntry]) -> String {
+    if entries.is_empty() {
+        format!("No workspace state in {STATE_DIR}/")
+    } else {
+        let total: u64 = entries.iter().map(|entry| entry.bytes).sum();
+        entries
+            .iter()
+            .map(|entry| {
+                format!(
+                    "{:<24} {:<10} {:>10}",
+                    entry
+                        .path
+                        .strip_prefix(base_dir)
+                        .unwrap_or(&entry.path)
+                        .display(),
+                    entry.category.label(),
+                    format_size(entry.bytes as usize)
+                )
+            })
+            .chain([format!(
+                "{:<24} {:<10} {:>10}",
+                "Total",
+                "",
+                format_size(total as usize)
+            )])
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+/// Bytes taken up by the files under `path`
+fn size(path: &Path) -> io::Result<u64> {
+    let metadata = fs::symlink_metadata(path)?;
+    if metadata.is_dir() {
+        fs::read_dir(path)?.try_fold(0, |total, entry| Ok(total + size(&entry?.path())?))
+    } else {
+        Ok(metadata.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clean() {
+        let base_dir =
+            std::env::temp_dir().join(format!("horse-state-test-{}", uuid::Uuid::new_v4()));
+        let sessions = base_dir.join(SESSIONS_DIR);
+        create_dir(&sessions).unwrap();
+        create_dir(&base_dir.join(OCR_DIR)).unwrap();
+        fs::write(sessions.join("a.jsonl"), "x".repeat(100)).unwrap();
+        fs::write(base_dir.join(OCR_DIR).join("b.txt"), "y".repeat(10)).unwrap();
+
+        assert!(base_dir.join(STATE_DIR).join(".gitignore").exists());
+        let entries = entries(&base_dir).unwrap();
+        assert_eq!(
+            entries
+                .iter()
+                .map(|e| (e.category, e.bytes))
+                .collect::<Vec<_>>(),
+            [(Category::Cache, 10), (Category::Sessions, 100)]
+        );
+
+        assert_eq!(clean(&base_dir, &[Category::Cache]).unwrap(), 10);
+        assert!(!base_dir.join(OCR_DIR).exists());
+        assert!(sessions.exists());
+        assert_eq!(clean(&base_dir, &[]).unwrap(), 100);
+        assert!(!base_dir.join(STATE_DIR).exists());
+
+        fs::remove_dir_all(&base_dir).unwrap();
+    }
+}